@@ -98,11 +98,17 @@ impl Ast {
     pub fn const0(&self) -> &Const {
         self.files.last().unwrap().elements[0].to_const().unwrap()
     }
+
+    #[cfg(test)]
+    pub fn import0(&self) -> &Import {
+        self.files.last().unwrap().elements[0].to_import().unwrap()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct File {
     pub path: String,
+    pub package: Vec<Name>,
     pub elements: Vec<Elem>,
 }
 
@@ -127,6 +133,7 @@ pub enum Elem {
     ElemConst(Const),
     ElemEnum(Enum),
     ElemAlias(Alias),
+    ElemImport(Import),
 }
 
 impl Elem {
@@ -142,6 +149,7 @@ impl Elem {
             &ElemConst(ref c) => c.id,
             &ElemEnum(ref e) => e.id,
             &ElemAlias(ref e) => e.id,
+            &ElemImport(ref i) => i.id,
         }
     }
 
@@ -214,6 +222,13 @@ impl Elem {
             _ => None,
         }
     }
+
+    pub fn to_import(&self) -> Option<&Import> {
+        match self {
+            &ElemImport(ref import) => Some(import),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -223,6 +238,7 @@ pub struct Global {
     pub span: Span,
     pub name: Name,
     pub reassignable: bool,
+    pub is_pub: bool,
     pub data_type: Type,
     pub initializer: Option<Function>,
 }
@@ -233,6 +249,7 @@ pub struct Const {
     pub pos: Position,
     pub span: Span,
     pub name: Name,
+    pub is_pub: bool,
     pub data_type: Type,
     pub expr: Box<Expr>,
 }
@@ -243,6 +260,7 @@ pub struct Enum {
     pub pos: Position,
     pub span: Span,
     pub name: Name,
+    pub is_pub: bool,
     pub type_params: Option<Vec<TypeParam>>,
     pub variants: Vec<EnumVariant>,
 }
@@ -265,12 +283,28 @@ pub struct Alias {
     pub ty: Type,
 }
 
+#[derive(Clone, Debug)]
+pub struct Import {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+    pub package: Vec<Name>,
+    pub names: Vec<ImportName>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportName {
+    pub pos: Position,
+    pub name: Name,
+}
+
 #[derive(Clone, Debug)]
 pub struct Struct {
     pub id: NodeId,
     pub pos: Position,
     pub span: Span,
     pub name: Name,
+    pub is_pub: bool,
     pub fields: Vec<StructField>,
 }
 
@@ -465,6 +499,7 @@ pub struct Impl {
     pub type_params: Option<Vec<TypeParam>>,
     pub trait_type: Option<Type>,
     pub class_type: Type,
+    pub where_bounds: Vec<WhereBound>,
     pub methods: Vec<Function>,
 }
 
@@ -474,6 +509,7 @@ pub struct Trait {
     pub name: Name,
     pub pos: Position,
     pub span: Span,
+    pub is_pub: bool,
     pub methods: Vec<Function>,
 }
 
@@ -486,9 +522,13 @@ pub struct Class {
     pub parent_class: Option<ParentClass>,
     pub has_open: bool,
     pub is_abstract: bool,
+    pub is_pub: bool,
     pub internal: bool,
     pub has_constructor: bool,
 
+    // lints disabled with @allow(...)
+    pub allow: Vec<Name>,
+
     pub constructor: Option<Function>,
     pub fields: Vec<Field>,
     pub methods: Vec<Function>,
@@ -502,6 +542,7 @@ pub struct Module {
     pub name: Name,
     pub pos: Position,
     pub parent_class: Option<ParentClass>,
+    pub is_pub: bool,
     pub internal: bool,
     pub has_constructor: bool,
 
@@ -519,6 +560,15 @@ pub struct TypeParam {
     pub bounds: Vec<Type>,
 }
 
+// `T: Bound1 + Bound2` in a where clause
+#[derive(Clone, Debug)]
+pub struct WhereBound {
+    pub pos: Position,
+    pub span: Span,
+    pub ty: Type,
+    pub bounds: Vec<Type>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ConstructorParam {
     pub name: Name,
@@ -588,12 +638,17 @@ pub struct Function {
     pub use_cannon: bool,
     pub internal: bool,
     pub is_constructor: bool,
+    pub throws: bool,
+
+    // lints disabled with @allow(...)
+    pub allow: Vec<Name>,
 
     pub params: Vec<Param>,
 
     pub return_type: Option<Type>,
     pub block: Option<Box<ExprBlockType>>,
     pub type_params: Option<Vec<TypeParam>>,
+    pub where_bounds: Vec<WhereBound>,
 }
 
 impl Function {
@@ -614,14 +669,24 @@ impl Modifiers {
         self.0.iter().find(|el| el.value == modifier).is_some()
     }
 
-    pub fn add(&mut self, modifier: Modifier, pos: Position, span: Span) {
+    pub fn add(&mut self, modifier: Modifier, pos: Position, span: Span, args: Vec<Name>) {
         self.0.push(ModifierElement {
             value: modifier,
             pos,
             span,
+            args,
         });
     }
 
+    // names given to @allow
+    pub fn allowed(&self) -> Vec<Name> {
+        self.0
+            .iter()
+            .filter(|el| el.value == Modifier::Allow)
+            .flat_map(|el| el.args.iter().cloned())
+            .collect()
+    }
+
     pub fn iter(&self) -> Iter<ModifierElement> {
         self.0.iter()
     }
@@ -632,6 +697,7 @@ pub struct ModifierElement {
     pub value: Modifier,
     pub pos: Position,
     pub span: Span,
+    pub args: Vec<Name>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -647,6 +713,7 @@ pub enum Modifier {
     Test,
     Cannon,
     OptimizeImmediately,
+    Allow,
 }
 
 impl Modifier {
@@ -663,6 +730,7 @@ impl Modifier {
             Modifier::Test => "test",
             Modifier::Cannon => "cannon",
             Modifier::OptimizeImmediately => "optimize_immediately",
+            Modifier::Allow => "allow",
         }
     }
 }
@@ -687,6 +755,9 @@ pub enum Stmt {
     StmtContinue(StmtContinueType),
     StmtReturn(StmtReturnType),
     StmtFor(StmtForType),
+    StmtThrow(StmtThrowType),
+    StmtDo(StmtDoType),
+    StmtDefer(StmtDeferType),
 }
 
 impl Stmt {
@@ -775,6 +846,43 @@ impl Stmt {
         })
     }
 
+    pub fn create_throw(id: NodeId, pos: Position, span: Span, expr: Box<Expr>) -> Stmt {
+        Stmt::StmtThrow(StmtThrowType {
+            id,
+            pos,
+            span,
+
+            expr,
+        })
+    }
+
+    pub fn create_do(
+        id: NodeId,
+        pos: Position,
+        span: Span,
+        do_block: Box<Stmt>,
+        catch_blocks: Vec<CatchBlock>,
+    ) -> Stmt {
+        Stmt::StmtDo(StmtDoType {
+            id,
+            pos,
+            span,
+
+            do_block,
+            catch_blocks,
+        })
+    }
+
+    pub fn create_defer(id: NodeId, pos: Position, span: Span, block: Box<Stmt>) -> Stmt {
+        Stmt::StmtDefer(StmtDeferType {
+            id,
+            pos,
+            span,
+
+            block,
+        })
+    }
+
     pub fn id(&self) -> NodeId {
         match *self {
             Stmt::StmtVar(ref stmt) => stmt.id,
@@ -784,6 +892,9 @@ impl Stmt {
             Stmt::StmtBreak(ref stmt) => stmt.id,
             Stmt::StmtContinue(ref stmt) => stmt.id,
             Stmt::StmtReturn(ref stmt) => stmt.id,
+            Stmt::StmtThrow(ref stmt) => stmt.id,
+            Stmt::StmtDo(ref stmt) => stmt.id,
+            Stmt::StmtDefer(ref stmt) => stmt.id,
         }
     }
 
@@ -796,6 +907,9 @@ impl Stmt {
             Stmt::StmtBreak(ref stmt) => stmt.pos,
             Stmt::StmtContinue(ref stmt) => stmt.pos,
             Stmt::StmtReturn(ref stmt) => stmt.pos,
+            Stmt::StmtThrow(ref stmt) => stmt.pos,
+            Stmt::StmtDo(ref stmt) => stmt.pos,
+            Stmt::StmtDefer(ref stmt) => stmt.pos,
         }
     }
 
@@ -808,6 +922,9 @@ impl Stmt {
             Stmt::StmtBreak(ref stmt) => stmt.span,
             Stmt::StmtContinue(ref stmt) => stmt.span,
             Stmt::StmtReturn(ref stmt) => stmt.span,
+            Stmt::StmtThrow(ref stmt) => stmt.span,
+            Stmt::StmtDo(ref stmt) => stmt.span,
+            Stmt::StmtDefer(ref stmt) => stmt.span,
         }
     }
 
@@ -908,6 +1025,48 @@ impl Stmt {
             _ => false,
         }
     }
+
+    pub fn to_throw(&self) -> Option<&StmtThrowType> {
+        match *self {
+            Stmt::StmtThrow(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn is_throw(&self) -> bool {
+        match *self {
+            Stmt::StmtThrow(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn to_do(&self) -> Option<&StmtDoType> {
+        match *self {
+            Stmt::StmtDo(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn is_do(&self) -> bool {
+        match *self {
+            Stmt::StmtDo(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn to_defer(&self) -> Option<&StmtDeferType> {
+        match *self {
+            Stmt::StmtDefer(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn is_defer(&self) -> bool {
+        match *self {
+            Stmt::StmtDefer(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -976,6 +1135,45 @@ pub struct StmtContinueType {
     pub span: Span,
 }
 
+#[derive(Clone, Debug)]
+pub struct StmtThrowType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub expr: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StmtDoType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub do_block: Box<Stmt>,
+    pub catch_blocks: Vec<CatchBlock>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CatchBlock {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub name: Name,
+    pub data_type: Type,
+    pub block: Box<Stmt>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StmtDeferType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub block: Box<Stmt>,
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum UnOp {
     Plus,
@@ -1099,6 +1297,8 @@ pub enum Expr {
     ExprBlock(ExprBlockType),
     ExprIf(ExprIfType),
     ExprTuple(ExprTupleType),
+    ExprMatch(ExprMatchType),
+    ExprTry(ExprTryType),
 }
 
 impl Expr {
@@ -1138,6 +1338,33 @@ impl Expr {
         })
     }
 
+    pub fn create_match(
+        id: NodeId,
+        pos: Position,
+        span: Span,
+        expr: Box<Expr>,
+        cases: Vec<MatchCaseType>,
+    ) -> Expr {
+        Expr::ExprMatch(ExprMatchType {
+            id,
+            pos,
+            span,
+
+            expr,
+            cases,
+        })
+    }
+
+    pub fn create_try(id: NodeId, pos: Position, span: Span, expr: Box<Expr>) -> Expr {
+        Expr::ExprTry(ExprTryType {
+            id,
+            pos,
+            span,
+
+            expr,
+        })
+    }
+
     pub fn create_un(id: NodeId, pos: Position, span: Span, op: UnOp, opnd: Box<Expr>) -> Expr {
         Expr::ExprUn(ExprUnType {
             id,
@@ -1370,23 +1597,8 @@ impl Expr {
         })
     }
 
-    pub fn create_lambda(
-        id: NodeId,
-        pos: Position,
-        span: Span,
-        params: Vec<Param>,
-        ret: Option<Box<Type>>,
-        block: Box<Stmt>,
-    ) -> Expr {
-        Expr::ExprLambda(ExprLambdaType {
-            id,
-            pos,
-            span,
-
-            params,
-            ret,
-            block,
-        })
+    pub fn create_lambda(id: NodeId, pos: Position, span: Span, fct: Box<Function>) -> Expr {
+        Expr::ExprLambda(ExprLambdaType { id, pos, span, fct })
     }
 
     pub fn create_tuple(id: NodeId, pos: Position, span: Span, values: Vec<Box<Expr>>) -> Expr {
@@ -1699,10 +1911,39 @@ impl Expr {
         }
     }
 
+    pub fn to_match(&self) -> Option<&ExprMatchType> {
+        match *self {
+            Expr::ExprMatch(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn is_match(&self) -> bool {
+        match *self {
+            Expr::ExprMatch(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn to_try(&self) -> Option<&ExprTryType> {
+        match *self {
+            Expr::ExprTry(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn is_try(&self) -> bool {
+        match *self {
+            Expr::ExprTry(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn needs_semicolon(&self) -> bool {
         match self {
             &Expr::ExprBlock(_) => false,
             &Expr::ExprIf(_) => false,
+            &Expr::ExprMatch(_) => false,
             _ => true,
         }
     }
@@ -1731,6 +1972,8 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.pos,
             Expr::ExprIf(ref val) => val.pos,
             Expr::ExprTuple(ref val) => val.pos,
+            Expr::ExprMatch(ref val) => val.pos,
+            Expr::ExprTry(ref val) => val.pos,
         }
     }
 
@@ -1758,6 +2001,8 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.span,
             Expr::ExprIf(ref val) => val.span,
             Expr::ExprTuple(ref val) => val.span,
+            Expr::ExprMatch(ref val) => val.span,
+            Expr::ExprTry(ref val) => val.span,
         }
     }
 
@@ -1785,6 +2030,8 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.id,
             Expr::ExprIf(ref val) => val.id,
             Expr::ExprTuple(ref val) => val.id,
+            Expr::ExprMatch(ref val) => val.id,
+            Expr::ExprTry(ref val) => val.id,
         }
     }
 }
@@ -1800,6 +2047,125 @@ pub struct ExprIfType {
     pub else_block: Option<Box<Expr>>,
 }
 
+#[derive(Clone, Debug)]
+pub struct ExprTryType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub expr: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExprMatchType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub expr: Box<Expr>,
+    pub cases: Vec<MatchCaseType>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchCaseType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub pattern: Box<MatchPattern>,
+    pub value: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub enum MatchPattern {
+    // _
+    Underscore(MatchPatternUnderscore),
+    // literal: 1, -2.5, 'a', "abc", true
+    Lit(MatchPatternLit),
+    // binding of a new variable or a class without fields: x, None
+    Ident(MatchPatternIdent),
+    // tuple destructuring: (p1, p2, ...)
+    Tuple(MatchPatternTuple),
+    // enum variant or class with fields: Foo::A, Some(p1)
+    Variant(MatchPatternVariant),
+}
+
+impl MatchPattern {
+    pub fn id(&self) -> NodeId {
+        match *self {
+            MatchPattern::Underscore(ref p) => p.id,
+            MatchPattern::Lit(ref p) => p.id,
+            MatchPattern::Ident(ref p) => p.id,
+            MatchPattern::Tuple(ref p) => p.id,
+            MatchPattern::Variant(ref p) => p.id,
+        }
+    }
+
+    pub fn pos(&self) -> Position {
+        match *self {
+            MatchPattern::Underscore(ref p) => p.pos,
+            MatchPattern::Lit(ref p) => p.pos,
+            MatchPattern::Ident(ref p) => p.pos,
+            MatchPattern::Tuple(ref p) => p.pos,
+            MatchPattern::Variant(ref p) => p.pos,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match *self {
+            MatchPattern::Underscore(ref p) => p.span,
+            MatchPattern::Lit(ref p) => p.span,
+            MatchPattern::Ident(ref p) => p.span,
+            MatchPattern::Tuple(ref p) => p.span,
+            MatchPattern::Variant(ref p) => p.span,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchPatternUnderscore {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchPatternLit {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub expr: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchPatternIdent {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub name: Name,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchPatternTuple {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub params: Vec<Box<MatchPattern>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchPatternVariant {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+
+    pub path: Vec<Name>,
+    pub params: Option<Vec<Box<MatchPattern>>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ExprTupleType {
     pub id: NodeId,
@@ -1955,9 +2321,7 @@ pub struct ExprLambdaType {
     pub pos: Position,
     pub span: Span,
 
-    pub params: Vec<Param>,
-    pub ret: Option<Box<Type>>,
-    pub block: Box<Stmt>,
+    pub fct: Box<Function>,
 }
 
 #[derive(Clone, Debug)]
@@ -79,6 +79,7 @@ impl<'a> AstDumper<'a> {
                 ElemConst(ref xconst) => self.dump_const(xconst),
                 ElemEnum(ref xenum) => self.dump_enum(xenum),
                 ElemAlias(ref alias) => self.dump_alias(alias),
+                ElemImport(ref import) => self.dump_import(import),
             }
         }
     }
@@ -132,6 +133,22 @@ impl<'a> AstDumper<'a> {
         });
     }
 
+    fn dump_import(&mut self, import: &Import) {
+        let package = import
+            .package
+            .iter()
+            .map(|&name| self.str(name).to_string())
+            .collect::<Vec<_>>()
+            .join("::");
+        dump!(self, "import {} @ {} {}", package, import.pos, import.id);
+
+        self.indent(|d| {
+            for import_name in &import.names {
+                dump!(d, "{} @ {}", d.str(import_name.name), import_name.pos);
+            }
+        });
+    }
+
     fn dump_enum(&mut self, xenum: &Enum) {
         dump!(
             self,
@@ -374,6 +391,9 @@ impl<'a> AstDumper<'a> {
             StmtVar(ref stmt) => self.dump_stmt_var(stmt),
             StmtWhile(ref stmt) => self.dump_stmt_while(stmt),
             StmtFor(ref stmt) => self.dump_stmt_for(stmt),
+            StmtThrow(ref stmt) => self.dump_stmt_throw(stmt),
+            StmtDo(ref stmt) => self.dump_stmt_do(stmt),
+            StmtDefer(ref stmt) => self.dump_stmt_defer(stmt),
         }
     }
 
@@ -466,6 +486,41 @@ impl<'a> AstDumper<'a> {
         dump!(self, "continue @ {} {}", stmt.pos, stmt.id);
     }
 
+    fn dump_stmt_throw(&mut self, stmt: &StmtThrowType) {
+        dump!(self, "throw @ {} {}", stmt.pos, stmt.id);
+        self.indent(|d| {
+            d.dump_expr(&stmt.expr);
+        });
+    }
+
+    fn dump_stmt_do(&mut self, stmt: &StmtDoType) {
+        dump!(self, "do @ {} {}", stmt.pos, stmt.id);
+        self.indent(|d| {
+            d.dump_stmt(&stmt.do_block);
+        });
+
+        for catch in &stmt.catch_blocks {
+            dump!(
+                self,
+                "catch {} @ {} {}",
+                self.str(catch.name),
+                catch.pos,
+                catch.id
+            );
+            self.indent(|d| {
+                d.dump_type(&catch.data_type);
+                d.dump_stmt(&catch.block);
+            });
+        }
+    }
+
+    fn dump_stmt_defer(&mut self, stmt: &StmtDeferType) {
+        dump!(self, "defer @ {} {}", stmt.pos, stmt.id);
+        self.indent(|d| {
+            d.dump_stmt(&stmt.block);
+        });
+    }
+
     fn dump_expr(&mut self, expr: &Expr) {
         match *expr {
             ExprUn(ref un) => self.dump_expr_un(un),
@@ -490,6 +545,8 @@ impl<'a> AstDumper<'a> {
             ExprBlock(ref expr) => self.dump_expr_block(expr),
             ExprIf(ref expr) => self.dump_expr_if(expr),
             ExprTuple(ref expr) => self.dump_expr_tuple(expr),
+            ExprMatch(ref expr) => self.dump_expr_match(expr),
+            ExprTry(ref expr) => self.dump_expr_try(expr),
         }
     }
 
@@ -626,7 +683,7 @@ impl<'a> AstDumper<'a> {
 
     fn dump_expr_lambda(&mut self, expr: &ExprLambdaType) {
         dump!(self, "lambda @ {} {}", expr.pos, expr.id);
-        self.indent(|d| d.dump_stmt(&expr.block));
+        self.indent(|d| d.dump_expr_block(expr.fct.block()));
     }
 
     fn dump_expr_tuple(&mut self, expr: &ExprTupleType) {
@@ -638,6 +695,63 @@ impl<'a> AstDumper<'a> {
         });
     }
 
+    fn dump_expr_try(&mut self, expr: &ExprTryType) {
+        dump!(self, "try @ {} {}", expr.pos, expr.id);
+        self.indent(|d| {
+            d.dump_expr(&expr.expr);
+        });
+    }
+
+    fn dump_expr_match(&mut self, expr: &ExprMatchType) {
+        dump!(self, "match @ {} {}", expr.pos, expr.id);
+        self.indent(|d| {
+            d.dump_expr(&expr.expr);
+
+            for case in &expr.cases {
+                dump!(d, "case @ {} {}", case.pos, case.id);
+                d.indent(|d| {
+                    d.dump_match_pattern(&case.pattern);
+                    d.dump_expr(&case.value);
+                });
+            }
+        });
+    }
+
+    fn dump_match_pattern(&mut self, pattern: &MatchPattern) {
+        match *pattern {
+            MatchPattern::Underscore(ref p) => dump!(self, "pattern _ @ {} {}", p.pos, p.id),
+            MatchPattern::Lit(ref p) => {
+                dump!(self, "pattern literal @ {} {}", p.pos, p.id);
+                self.indent(|d| d.dump_expr(&p.expr));
+            }
+            MatchPattern::Ident(ref p) => {
+                dump!(self, "pattern {} @ {} {}", self.str(p.name), p.pos, p.id)
+            }
+            MatchPattern::Tuple(ref p) => {
+                dump!(self, "pattern tuple @ {} {}", p.pos, p.id);
+                self.indent(|d| {
+                    for param in &p.params {
+                        d.dump_match_pattern(param);
+                    }
+                });
+            }
+            MatchPattern::Variant(ref p) => {
+                let path = p
+                    .path
+                    .iter()
+                    .map(|&name| self.str(name).to_string())
+                    .collect::<Vec<_>>()
+                    .join("::");
+                dump!(self, "pattern {} @ {} {}", path, p.pos, p.id);
+                self.indent(|d| {
+                    for param in p.params.iter().flatten() {
+                        d.dump_match_pattern(param);
+                    }
+                });
+            }
+        }
+    }
+
     fn dump_expr_dot(&mut self, expr: &ExprDotType) {
         self.indent(|d| d.dump_expr(&expr.rhs));
         dump!(self, "dot @ {} {}", expr.pos, expr.id);
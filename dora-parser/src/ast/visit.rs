@@ -49,6 +49,8 @@ pub trait Visitor<'v>: Sized {
         walk_alias(self, e);
     }
 
+    fn visit_import(&mut self, _i: &'v Import) {}
+
     fn visit_struct_field(&mut self, f: &'v StructField) {
         walk_struct_field(self, f);
     }
@@ -105,6 +107,7 @@ pub fn walk_file<'v, V: Visitor<'v>>(v: &mut V, f: &'v File) {
             ElemConst(ref c) => v.visit_const(c),
             ElemEnum(ref e) => v.visit_enum(e),
             ElemAlias(ref e) => v.visit_alias(e),
+            ElemImport(ref i) => v.visit_import(i),
         }
     }
 }
@@ -260,6 +263,23 @@ pub fn walk_stmt<'v, V: Visitor<'v>>(v: &mut V, s: &'v Stmt) {
             }
         }
 
+        StmtThrow(ref value) => {
+            v.visit_expr(&value.expr);
+        }
+
+        StmtDo(ref value) => {
+            v.visit_stmt(&value.do_block);
+
+            for catch in &value.catch_blocks {
+                v.visit_type(&catch.data_type);
+                v.visit_stmt(&catch.block);
+            }
+        }
+
+        StmtDefer(ref value) => {
+            v.visit_stmt(&value.block);
+        }
+
         StmtBreak(_) => {}
         StmtContinue(_) => {}
     }
@@ -314,15 +334,23 @@ pub fn walk_expr<'v, V: Visitor<'v>>(v: &mut V, e: &'v Expr) {
         }
 
         ExprLambda(ref value) => {
-            for param in &value.params {
+            for param in &value.fct.params {
                 v.visit_type(&param.data_type);
             }
 
-            if let Some(ref ret) = value.ret {
+            if let Some(ref ret) = value.fct.return_type {
                 v.visit_type(ret);
             }
 
-            v.visit_stmt(&value.block);
+            let block = value.fct.block();
+
+            for stmt in &block.stmts {
+                v.visit_stmt(stmt);
+            }
+
+            if let Some(ref value) = block.expr {
+                v.visit_expr(value);
+            }
         }
 
         ExprBlock(ref value) => {
@@ -356,6 +384,18 @@ pub fn walk_expr<'v, V: Visitor<'v>>(v: &mut V, e: &'v Expr) {
             }
         }
 
+        ExprMatch(ref value) => {
+            v.visit_expr(&value.expr);
+
+            for case in &value.cases {
+                v.visit_expr(&case.value);
+            }
+        }
+
+        ExprTry(ref value) => {
+            v.visit_expr(&value.expr);
+        }
+
         ExprSuper(_) => {}
         ExprSelf(_) => {}
         ExprLitChar(_) => {}
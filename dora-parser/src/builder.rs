@@ -166,12 +166,15 @@ impl<'a> BuilderFct<'a> {
             is_abstract: false,
             internal: false,
             is_constructor: self.is_constructor,
+            throws: false,
             is_test: false,
             use_cannon: self.use_cannon,
+            allow: Vec::new(),
             params: self.params,
             return_type: self.return_type,
             block: self.block,
             type_params: None,
+            where_bounds: Vec::new(),
         }
     }
 }
@@ -21,6 +21,8 @@ pub enum ParseError {
     NumberOverflow,
     UnclosedStringTemplate,
     ExpectedIdentifier(String),
+    ExpectedPattern(String),
+    CatchBlockExpected,
 }
 
 impl ParseError {
@@ -56,6 +58,8 @@ impl ParseError {
             ParseError::ExpectedIdentifier(ref tok) => {
                 format!("identifier expected but got {}.", tok)
             }
+            ParseError::ExpectedPattern(ref tok) => format!("pattern expected but got {}.", tok),
+            ParseError::CatchBlockExpected => "catch block expected.".into(),
         }
     }
 }
@@ -305,6 +305,9 @@ impl Lexer {
                     } else {
                         TokenKind::EqEq
                     }
+                } else if nch == '>' {
+                    self.read_char();
+                    TokenKind::DoubleArrow
                 } else {
                     TokenKind::Eq
                 }
@@ -566,6 +569,7 @@ fn keywords_in_map() -> HashMap<&'static str, TokenKind> {
     keywords.insert("trait", TokenKind::Trait);
     keywords.insert("impl", TokenKind::Impl);
     keywords.insert("module", TokenKind::Module);
+    keywords.insert("import", TokenKind::Import);
 
     // "small" shapes
     keywords.insert("fun", TokenKind::Fun);
@@ -582,6 +586,15 @@ fn keywords_in_map() -> HashMap<&'static str, TokenKind> {
     keywords.insert("in", TokenKind::In);
     keywords.insert("break", TokenKind::Break);
     keywords.insert("continue", TokenKind::Continue);
+    keywords.insert("match", TokenKind::Match);
+
+    // exceptions
+    keywords.insert("do", TokenKind::Do);
+    keywords.insert("try", TokenKind::Try);
+    keywords.insert("throw", TokenKind::Throw);
+    keywords.insert("throws", TokenKind::Throws);
+    keywords.insert("catch", TokenKind::Catch);
+    keywords.insert("defer", TokenKind::Defer);
 
     // qualifiers
     keywords.insert("self", TokenKind::This);
@@ -1029,11 +1042,12 @@ mod tests {
         assert_tok(&mut reader, TokenKind::Class, 1, 6);
         assert_tok(&mut reader, TokenKind::Super, 1, 12);
 
-        let mut reader = Lexer::from_str("break continue return nil");
+        let mut reader = Lexer::from_str("break continue return nil match");
         assert_tok(&mut reader, TokenKind::Break, 1, 1);
         assert_tok(&mut reader, TokenKind::Continue, 1, 7);
         assert_tok(&mut reader, TokenKind::Return, 1, 16);
         assert_tok(&mut reader, TokenKind::Nil, 1, 23);
+        assert_tok(&mut reader, TokenKind::Match, 1, 27);
 
         let mut reader = Lexer::from_str("type struct enum alias trait const");
         assert_tok(&mut reader, TokenKind::Type, 1, 1);
@@ -1048,6 +1062,18 @@ mod tests {
         assert_tok(&mut reader, TokenKind::In, 1, 5);
         assert_tok(&mut reader, TokenKind::Impl, 1, 8);
         assert_tok(&mut reader, TokenKind::CapitalThis, 1, 13);
+
+        let mut reader = Lexer::from_str("do try throw throws catch defer");
+        assert_tok(&mut reader, TokenKind::Do, 1, 1);
+        assert_tok(&mut reader, TokenKind::Try, 1, 4);
+        assert_tok(&mut reader, TokenKind::Throw, 1, 8);
+        assert_tok(&mut reader, TokenKind::Throws, 1, 14);
+        assert_tok(&mut reader, TokenKind::Catch, 1, 21);
+        assert_tok(&mut reader, TokenKind::Defer, 1, 27);
+
+        let mut reader = Lexer::from_str("import module");
+        assert_tok(&mut reader, TokenKind::Import, 1, 1);
+        assert_tok(&mut reader, TokenKind::Module, 1, 8);
     }
 
     #[test]
@@ -1082,6 +1108,11 @@ mod tests {
         let mut reader = Lexer::from_str("->");
         assert_tok(&mut reader, TokenKind::Arrow, 1, 1);
 
+        let mut reader = Lexer::from_str("=>==>");
+        assert_tok(&mut reader, TokenKind::DoubleArrow, 1, 1);
+        assert_tok(&mut reader, TokenKind::EqEq, 1, 3);
+        assert_tok(&mut reader, TokenKind::Gt, 1, 5);
+
         let mut reader = Lexer::from_str(">><<>>>_::");
         assert_tok(&mut reader, TokenKind::GtGt, 1, 1);
         assert_tok(&mut reader, TokenKind::LtLt, 1, 3);
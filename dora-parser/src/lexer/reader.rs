@@ -44,6 +44,10 @@ impl Reader {
         &self.name
     }
 
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
     pub fn advance(&mut self) -> Option<char> {
         let curr = self.curr();
 
@@ -24,6 +24,7 @@ pub enum TokenKind {
     Trait,
     Impl,
     Module,
+    Import,
 
     // "small" shapes
     Fun,
@@ -40,6 +41,15 @@ pub enum TokenKind {
     In,
     Break,
     Continue,
+    Match,
+
+    // exceptions
+    Do,
+    Try,
+    Throw,
+    Throws,
+    Catch,
+    Defer,
 
     // qualifiers
     This,
@@ -89,6 +99,7 @@ pub enum TokenKind {
     ColonColon,
     At,
     Arrow,
+    DoubleArrow,
 
     // brackets
     LParen,
@@ -134,6 +145,7 @@ impl TokenKind {
             TokenKind::Trait => "trait",
             TokenKind::Impl => "impl",
             TokenKind::Module => "module",
+            TokenKind::Import => "import",
 
             // "small" shapes
             TokenKind::Fun => "fun",
@@ -150,6 +162,15 @@ impl TokenKind {
             TokenKind::In => "in",
             TokenKind::Break => "break",
             TokenKind::Continue => "continue",
+            TokenKind::Match => "match",
+
+            // exceptions
+            TokenKind::Do => "do",
+            TokenKind::Try => "try",
+            TokenKind::Throw => "throw",
+            TokenKind::Throws => "throws",
+            TokenKind::Catch => "catch",
+            TokenKind::Defer => "defer",
 
             // qualifiers
             TokenKind::This => "self",
@@ -199,6 +220,7 @@ impl TokenKind {
             TokenKind::ColonColon => "::",
             TokenKind::At => "@",
             TokenKind::Arrow => "->",
+            TokenKind::DoubleArrow => "=>",
 
             // brackets
             TokenKind::LParen => "(",
@@ -77,6 +77,7 @@ impl<'a> Parser<'a> {
 
         self.ast.files.push(ast::File {
             path: file.name.clone(),
+            package: Vec::new(),
             elements,
         });
 
@@ -105,6 +106,8 @@ impl<'a> Parser<'a> {
                         Modifier::OptimizeImmediately,
                         Modifier::Test,
                         Modifier::Cannon,
+                        Modifier::Pub,
+                        Modifier::Allow,
                     ],
                 )?;
                 let fct = self.parse_function(&modifiers)?;
@@ -119,6 +122,8 @@ impl<'a> Parser<'a> {
                         Modifier::Open,
                         Modifier::Internal,
                         Modifier::Cannon,
+                        Modifier::Pub,
+                        Modifier::Allow,
                     ],
                 )?;
                 let class = self.parse_class(&modifiers)?;
@@ -126,14 +131,14 @@ impl<'a> Parser<'a> {
             }
 
             TokenKind::Struct => {
-                self.ban_modifiers(&modifiers)?;
-                let struc = self.parse_struct()?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
+                let struc = self.parse_struct(&modifiers)?;
                 elements.push(ElemStruct(struc))
             }
 
             TokenKind::Trait => {
-                self.ban_modifiers(&modifiers)?;
-                let xtrait = self.parse_trait()?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
+                let xtrait = self.parse_trait(&modifiers)?;
                 elements.push(ElemTrait(xtrait));
             }
 
@@ -144,7 +149,7 @@ impl<'a> Parser<'a> {
             }
 
             TokenKind::Module => {
-                self.ban_modifiers(&modifiers)?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
                 let module = self.parse_module(&modifiers)?;
                 elements.push(ElemModule(module));
             }
@@ -156,22 +161,28 @@ impl<'a> Parser<'a> {
             }
 
             TokenKind::Let | TokenKind::Var => {
-                self.ban_modifiers(&modifiers)?;
-                self.parse_global(elements)?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
+                self.parse_global(&modifiers, elements)?;
             }
 
             TokenKind::Const => {
-                self.ban_modifiers(&modifiers)?;
-                let xconst = self.parse_const()?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
+                let xconst = self.parse_const(&modifiers)?;
                 elements.push(ElemConst(xconst));
             }
 
             TokenKind::Enum => {
-                self.ban_modifiers(&modifiers)?;
-                let xenum = self.parse_enum()?;
+                self.restrict_modifiers(&modifiers, &[Modifier::Pub])?;
+                let xenum = self.parse_enum(&modifiers)?;
                 elements.push(ElemEnum(xenum));
             }
 
+            TokenKind::Import => {
+                self.ban_modifiers(&modifiers)?;
+                let import = self.parse_import()?;
+                elements.push(ElemImport(import));
+            }
+
             _ => {
                 let msg = ParseError::ExpectedTopLevelElement(self.token.name());
                 return Err(ParseErrorAndPos::new(self.token.position, msg));
@@ -181,7 +192,53 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn parse_enum(&mut self) -> Result<Enum, ParseErrorAndPos> {
+    fn parse_import(&mut self) -> Result<Import, ParseErrorAndPos> {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Import)?.position;
+        let mut package = vec![self.expect_identifier()?];
+        let mut names = Vec::new();
+
+        loop {
+            self.expect_token(TokenKind::ColonColon)?;
+
+            if self.token.is(TokenKind::LBrace) {
+                self.advance_token()?;
+                names = self.parse_list(TokenKind::Comma, TokenKind::RBrace, |p| {
+                    p.parse_import_name()
+                })?;
+                break;
+            }
+
+            let import_name = self.parse_import_name()?;
+
+            if self.token.is(TokenKind::ColonColon) {
+                package.push(import_name.name);
+            } else {
+                names.push(import_name);
+                break;
+            }
+        }
+
+        self.expect_semicolon()?;
+        let span = self.span_from(start);
+
+        Ok(Import {
+            id: self.generate_id(),
+            pos,
+            span,
+            package,
+            names,
+        })
+    }
+
+    fn parse_import_name(&mut self) -> Result<ImportName, ParseErrorAndPos> {
+        let pos = self.token.position;
+        let name = self.expect_identifier()?;
+
+        Ok(ImportName { pos, name })
+    }
+
+    fn parse_enum(&mut self, modifiers: &Modifiers) -> Result<Enum, ParseErrorAndPos> {
         let start = self.token.span.start();
         let pos = self.expect_token(TokenKind::Enum)?.position;
         let name = self.expect_identifier()?;
@@ -198,6 +255,7 @@ impl<'a> Parser<'a> {
             pos,
             span,
             name,
+            is_pub: modifiers.contains(Modifier::Pub),
             type_params,
             variants,
         })
@@ -226,7 +284,7 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_const(&mut self) -> Result<Const, ParseErrorAndPos> {
+    fn parse_const(&mut self, modifiers: &Modifiers) -> Result<Const, ParseErrorAndPos> {
         let start = self.token.span.start();
         let pos = self.expect_token(TokenKind::Const)?.position;
         let name = self.expect_identifier()?;
@@ -242,6 +300,7 @@ impl<'a> Parser<'a> {
             pos,
             span,
             name,
+            is_pub: modifiers.contains(Modifier::Pub),
             data_type: ty,
             expr,
         })
@@ -263,13 +322,20 @@ impl<'a> Parser<'a> {
             (type_name, None)
         };
 
+        let where_bounds = self.parse_where()?;
+
         self.expect_token(TokenKind::LBrace)?;
 
         let mut methods = Vec::new();
 
         while !self.token.is(TokenKind::RBrace) {
             let modifiers = self.parse_annotations()?;
-            let mods = &[Modifier::Static, Modifier::Internal, Modifier::Cannon];
+            let mods = &[
+                Modifier::Static,
+                Modifier::Internal,
+                Modifier::Cannon,
+                Modifier::Allow,
+            ];
             self.restrict_modifiers(&modifiers, mods)?;
 
             methods.push(self.parse_function(&modifiers)?);
@@ -285,11 +351,16 @@ impl<'a> Parser<'a> {
             type_params,
             trait_type,
             class_type,
+            where_bounds,
             methods,
         })
     }
 
-    fn parse_global(&mut self, elements: &mut Vec<Elem>) -> Result<(), ParseErrorAndPos> {
+    fn parse_global(
+        &mut self,
+        modifiers: &Modifiers,
+        elements: &mut Vec<Elem>,
+    ) -> Result<(), ParseErrorAndPos> {
         let start = self.token.span.start();
         let pos = self.token.position;
         let reassignable = self.token.is(TokenKind::Var);
@@ -317,6 +388,7 @@ impl<'a> Parser<'a> {
             span,
             data_type,
             reassignable,
+            is_pub: modifiers.contains(Modifier::Pub),
             initializer: None,
         };
 
@@ -330,7 +402,7 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn parse_trait(&mut self) -> Result<Trait, ParseErrorAndPos> {
+    fn parse_trait(&mut self, modifiers: &Modifiers) -> Result<Trait, ParseErrorAndPos> {
         let start = self.token.span.start();
         let pos = self.expect_token(TokenKind::Trait)?.position;
         let ident = self.expect_identifier()?;
@@ -341,7 +413,7 @@ impl<'a> Parser<'a> {
 
         while !self.token.is(TokenKind::RBrace) {
             let modifiers = self.parse_annotations()?;
-            let mods = &[Modifier::Static];
+            let mods = &[Modifier::Static, Modifier::Allow];
             self.restrict_modifiers(&modifiers, mods)?;
 
             methods.push(self.parse_function(&modifiers)?);
@@ -355,11 +427,12 @@ impl<'a> Parser<'a> {
             name: ident,
             pos,
             span,
+            is_pub: modifiers.contains(Modifier::Pub),
             methods,
         })
     }
 
-    fn parse_struct(&mut self) -> Result<Struct, ParseErrorAndPos> {
+    fn parse_struct(&mut self, modifiers: &Modifiers) -> Result<Struct, ParseErrorAndPos> {
         let start = self.token.span.start();
         let pos = self.expect_token(TokenKind::Struct)?.position;
         let ident = self.expect_identifier()?;
@@ -375,6 +448,7 @@ impl<'a> Parser<'a> {
             name: ident,
             pos,
             span,
+            is_pub: modifiers.contains(Modifier::Pub),
             fields,
         })
     }
@@ -415,7 +489,9 @@ impl<'a> Parser<'a> {
             has_open,
             internal,
             is_abstract,
+            is_pub: modifiers.contains(Modifier::Pub),
             has_constructor: false,
+            allow: modifiers.allowed(),
             parent_class: None,
             constructor: None,
             fields: Vec::new(),
@@ -479,6 +555,7 @@ impl<'a> Parser<'a> {
             name: ident,
             pos: pos,
             parent_class: None,
+            is_pub: modifiers.contains(Modifier::Pub),
             internal: internal,
             has_constructor: false,
             constructor: None,
@@ -681,6 +758,7 @@ impl<'a> Parser<'a> {
                         Modifier::Pub,
                         Modifier::Static,
                         Modifier::Cannon,
+                        Modifier::Allow,
                     ];
                     self.restrict_modifiers(&modifiers, mods)?;
 
@@ -726,6 +804,7 @@ impl<'a> Parser<'a> {
                         Modifier::Final,
                         Modifier::Pub,
                         Modifier::Static,
+                        Modifier::Allow,
                     ];
                     self.restrict_modifiers(&modifiers, mods)?;
 
@@ -771,6 +850,7 @@ impl<'a> Parser<'a> {
                 "test" => Modifier::Test,
                 "cannon" => Modifier::Cannon,
                 "optimize_immediately" => Modifier::OptimizeImmediately,
+                "allow" => Modifier::Allow,
                 annotation => {
                     return Err(ParseErrorAndPos::new(
                         self.token.position,
@@ -786,7 +866,19 @@ impl<'a> Parser<'a> {
                 ));
             }
 
-            modifiers.add(modifier, self.token.position, self.token.span);
+            let pos = self.token.position;
+            let span = self.token.span;
+
+            let args = if modifier == Modifier::Allow {
+                self.expect_token(TokenKind::LParen)?;
+                self.parse_list(TokenKind::Comma, TokenKind::RParen, |p| {
+                    p.expect_identifier()
+                })?
+            } else {
+                Vec::new()
+            };
+
+            modifiers.add(modifier, pos, span, args);
         }
 
         Ok(modifiers)
@@ -858,7 +950,9 @@ impl<'a> Parser<'a> {
         let ident = self.expect_identifier()?;
         let type_params = self.parse_type_params()?;
         let params = self.parse_function_params()?;
+        let throws = self.parse_throws()?;
         let return_type = self.parse_function_type()?;
+        let where_bounds = self.parse_where()?;
         let block = self.parse_function_block()?;
         let span = self.span_from(start);
 
@@ -878,12 +972,66 @@ impl<'a> Parser<'a> {
             internal: modifiers.contains(Modifier::Internal),
             is_abstract: modifiers.contains(Modifier::Abstract),
             is_constructor: false,
+            throws,
             is_test: modifiers.contains(Modifier::Test),
             use_cannon: modifiers.contains(Modifier::Cannon),
+            allow: modifiers.allowed(),
             params,
             return_type,
             block,
             type_params,
+            where_bounds,
+        })
+    }
+
+    // `where` is no keyword and can still be used as identifier
+    fn parse_where(&mut self) -> Result<Vec<WhereBound>, ParseErrorAndPos> {
+        let mut where_bounds = Vec::new();
+
+        if self.token.kind != TokenKind::Identifier("where".into()) {
+            return Ok(where_bounds);
+        }
+
+        self.advance_token()?;
+
+        loop {
+            where_bounds.push(self.parse_where_bound()?);
+
+            if self.token.is(TokenKind::Comma) {
+                self.advance_token()?;
+            } else {
+                break;
+            }
+        }
+
+        Ok(where_bounds)
+    }
+
+    fn parse_where_bound(&mut self) -> Result<WhereBound, ParseErrorAndPos> {
+        let start = self.token.span.start();
+        let pos = self.token.position;
+        let ty = self.parse_type()?;
+        self.expect_token(TokenKind::Colon)?;
+
+        let mut bounds = Vec::new();
+
+        loop {
+            bounds.push(self.parse_type()?);
+
+            if self.token.is(TokenKind::Add) {
+                self.advance_token()?;
+            } else {
+                break;
+            }
+        }
+
+        let span = self.span_from(start);
+
+        Ok(WhereBound {
+            pos,
+            span,
+            ty,
+            bounds,
         })
     }
 
@@ -964,6 +1112,15 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn parse_throws(&mut self) -> Result<bool, ParseErrorAndPos> {
+        if self.token.is(TokenKind::Throws) {
+            self.advance_token()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     fn parse_function_type(&mut self) -> Result<Option<Type>, ParseErrorAndPos> {
         if self.token.is(TokenKind::Arrow) {
             self.advance_token()?;
@@ -1229,6 +1386,9 @@ impl<'a> Parser<'a> {
                 ParseError::MisplacedElse,
             )),
             TokenKind::For => Ok(StmtOrExpr::Stmt(self.parse_for()?)),
+            TokenKind::Throw => Ok(StmtOrExpr::Stmt(self.parse_throw()?)),
+            TokenKind::Do => Ok(StmtOrExpr::Stmt(self.parse_do()?)),
+            TokenKind::Defer => Ok(StmtOrExpr::Stmt(self.parse_defer()?)),
             _ => {
                 let expr = self.parse_expression()?;
 
@@ -1281,6 +1441,199 @@ impl<'a> Parser<'a> {
         )))
     }
 
+    fn parse_match(&mut self) -> ExprResult {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Match)?.position;
+
+        let expr = self.parse_expression_no_struct_lit()?;
+        self.expect_token(TokenKind::LBrace)?;
+
+        let mut cases = Vec::new();
+
+        while !self.token.is(TokenKind::RBrace) && !self.token.is_eof() {
+            let case = self.parse_match_case()?;
+            let needs_comma = case.value.needs_semicolon();
+            cases.push(case);
+
+            if self.token.is(TokenKind::Comma) {
+                self.advance_token()?;
+            } else if needs_comma && !self.token.is(TokenKind::RBrace) {
+                self.expect_token(TokenKind::Comma)?;
+            }
+        }
+
+        self.expect_token(TokenKind::RBrace)?;
+        let span = self.span_from(start);
+
+        Ok(Box::new(Expr::create_match(
+            self.generate_id(),
+            pos,
+            span,
+            expr,
+            cases,
+        )))
+    }
+
+    fn parse_match_case(&mut self) -> Result<MatchCaseType, ParseErrorAndPos> {
+        let start = self.token.span.start();
+        let pos = self.token.position;
+
+        let pattern = self.parse_match_pattern()?;
+        self.expect_token(TokenKind::DoubleArrow)?;
+        let value = self.parse_expression()?;
+        let span = self.span_from(start);
+
+        Ok(MatchCaseType {
+            id: self.generate_id(),
+            pos,
+            span,
+            pattern,
+            value,
+        })
+    }
+
+    fn parse_match_pattern(&mut self) -> Result<Box<MatchPattern>, ParseErrorAndPos> {
+        let start = self.token.span.start();
+        let pos = self.token.position;
+
+        let pattern = match self.token.kind {
+            TokenKind::Underscore => {
+                self.advance_token()?;
+                let span = self.span_from(start);
+
+                MatchPattern::Underscore(MatchPatternUnderscore {
+                    id: self.generate_id(),
+                    pos,
+                    span,
+                })
+            }
+
+            TokenKind::LParen => {
+                self.advance_token()?;
+                let mut params = Vec::new();
+
+                while !self.token.is(TokenKind::RParen) {
+                    let param = self.parse_match_pattern()?;
+
+                    if params.is_empty() && self.token.is(TokenKind::RParen) {
+                        self.advance_token()?;
+                        return Ok(param);
+                    }
+
+                    params.push(param);
+
+                    if !self.token.is(TokenKind::RParen) {
+                        self.expect_token(TokenKind::Comma)?;
+                    }
+                }
+
+                self.expect_token(TokenKind::RParen)?;
+                let span = self.span_from(start);
+
+                MatchPattern::Tuple(MatchPatternTuple {
+                    id: self.generate_id(),
+                    pos,
+                    span,
+                    params,
+                })
+            }
+
+            TokenKind::Sub => {
+                let tok = self.advance_token()?;
+
+                let expr = match self.token.kind {
+                    TokenKind::LitInt(_, _, _) => self.parse_lit_int()?,
+                    TokenKind::LitFloat(_, _) => self.parse_lit_float()?,
+                    _ => {
+                        return Err(ParseErrorAndPos::new(
+                            self.token.position,
+                            ParseError::ExpectedPattern(self.token.name()),
+                        ));
+                    }
+                };
+
+                let span = self.span_from(start);
+                let expr = Box::new(Expr::create_un(
+                    self.generate_id(),
+                    tok.position,
+                    span,
+                    UnOp::Neg,
+                    expr,
+                ));
+
+                MatchPattern::Lit(MatchPatternLit {
+                    id: self.generate_id(),
+                    pos,
+                    span,
+                    expr,
+                })
+            }
+
+            TokenKind::LitChar(_)
+            | TokenKind::LitInt(_, _, _)
+            | TokenKind::LitFloat(_, _)
+            | TokenKind::StringTail(_)
+            | TokenKind::True
+            | TokenKind::False => {
+                let expr = self.parse_factor()?;
+                let span = self.span_from(start);
+
+                MatchPattern::Lit(MatchPatternLit {
+                    id: self.generate_id(),
+                    pos,
+                    span,
+                    expr,
+                })
+            }
+
+            TokenKind::Identifier(_) => {
+                let mut path = vec![self.expect_identifier()?];
+
+                while self.token.is(TokenKind::ColonColon) {
+                    self.advance_token()?;
+                    path.push(self.expect_identifier()?);
+                }
+
+                let params = if self.token.is(TokenKind::LParen) {
+                    self.advance_token()?;
+                    Some(self.parse_list(TokenKind::Comma, TokenKind::RParen, |p| {
+                        p.parse_match_pattern()
+                    })?)
+                } else {
+                    None
+                };
+
+                let span = self.span_from(start);
+
+                if path.len() == 1 && params.is_none() {
+                    MatchPattern::Ident(MatchPatternIdent {
+                        id: self.generate_id(),
+                        pos,
+                        span,
+                        name: path[0],
+                    })
+                } else {
+                    MatchPattern::Variant(MatchPatternVariant {
+                        id: self.generate_id(),
+                        pos,
+                        span,
+                        path,
+                        params,
+                    })
+                }
+            }
+
+            _ => {
+                return Err(ParseErrorAndPos::new(
+                    pos,
+                    ParseError::ExpectedPattern(self.token.name()),
+                ));
+            }
+        };
+
+        Ok(Box::new(pattern))
+    }
+
     fn parse_for(&mut self) -> StmtResult {
         let start = self.token.span.start();
         let pos = self.expect_token(TokenKind::For)?.position;
@@ -1359,6 +1712,83 @@ impl<'a> Parser<'a> {
         )))
     }
 
+    fn parse_throw(&mut self) -> StmtResult {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Throw)?.position;
+        let expr = self.parse_expression()?;
+        self.expect_semicolon()?;
+        let span = self.span_from(start);
+
+        Ok(Box::new(Stmt::create_throw(
+            self.generate_id(),
+            pos,
+            span,
+            expr,
+        )))
+    }
+
+    fn parse_do(&mut self) -> StmtResult {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Do)?.position;
+        let do_block = self.parse_block_stmt()?;
+        let mut catch_blocks = Vec::new();
+
+        while self.token.is(TokenKind::Catch) {
+            catch_blocks.push(self.parse_catch()?);
+        }
+
+        if catch_blocks.is_empty() {
+            return Err(ParseErrorAndPos::new(
+                self.token.position,
+                ParseError::CatchBlockExpected,
+            ));
+        }
+
+        let span = self.span_from(start);
+
+        Ok(Box::new(Stmt::create_do(
+            self.generate_id(),
+            pos,
+            span,
+            do_block,
+            catch_blocks,
+        )))
+    }
+
+    fn parse_catch(&mut self) -> Result<CatchBlock, ParseErrorAndPos> {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Catch)?.position;
+        let name = self.expect_identifier()?;
+        self.expect_token(TokenKind::Colon)?;
+        let data_type = self.parse_type()?;
+        let block = self.parse_block_stmt()?;
+        let span = self.span_from(start);
+
+        Ok(CatchBlock {
+            id: self.generate_id(),
+            pos,
+            span,
+
+            name,
+            data_type,
+            block,
+        })
+    }
+
+    fn parse_defer(&mut self) -> StmtResult {
+        let start = self.token.span.start();
+        let pos = self.expect_token(TokenKind::Defer)?.position;
+        let block = self.parse_block_stmt()?;
+        let span = self.span_from(start);
+
+        Ok(Box::new(Stmt::create_defer(
+            self.generate_id(),
+            pos,
+            span,
+            block,
+        )))
+    }
+
     fn parse_expression(&mut self) -> ExprResult {
         self.parse_expression_struct_lit(true)
     }
@@ -1374,6 +1804,7 @@ impl<'a> Parser<'a> {
         let result = match self.token.kind {
             TokenKind::LBrace => self.parse_block(),
             TokenKind::If => self.parse_if(),
+            TokenKind::Match => self.parse_match(),
             _ => self.parse_binary(0),
         };
 
@@ -1457,6 +1888,20 @@ impl<'a> Parser<'a> {
                 )))
             }
 
+            TokenKind::Try => {
+                let start = self.token.span.start();
+                let pos = self.advance_token()?.position;
+                let expr = self.parse_primary()?;
+                let span = self.span_from(start);
+
+                Ok(Box::new(Expr::create_try(
+                    self.generate_id(),
+                    pos,
+                    span,
+                    expr,
+                )))
+            }
+
             _ => self.parse_primary(),
         }
     }
@@ -1583,6 +2028,7 @@ impl<'a> Parser<'a> {
             TokenKind::LParen => self.parse_parentheses(),
             TokenKind::LBrace => self.parse_block(),
             TokenKind::If => self.parse_if(),
+            TokenKind::Match => self.parse_match(),
             TokenKind::LitChar(_) => self.parse_lit_char(),
             TokenKind::LitInt(_, _, _) => self.parse_lit_int(),
             TokenKind::LitFloat(_, _) => self.parse_lit_float(),
@@ -1861,23 +2307,56 @@ impl<'a> Parser<'a> {
             })?
         };
 
-        let ret = if self.token.is(TokenKind::Arrow) {
+        let return_type = if self.token.is(TokenKind::Arrow) {
             self.advance_token()?;
-            Some(Box::new(self.parse_type()?))
+            Some(self.parse_type()?)
         } else {
             None
         };
 
-        let block = self.parse_block_stmt()?;
+        let block = self.parse_block()?;
+
+        let block = if let Expr::ExprBlock(block_type) = *block {
+            Box::new(block_type)
+        } else {
+            unreachable!()
+        };
+
         let span = self.span_from(start);
+        let name = self.interner.intern("<lambda>");
+
+        let function = Function {
+            id: self.generate_id(),
+            name,
+            pos: tok.position,
+            span,
+            method: false,
+            has_open: false,
+            has_override: false,
+            has_final: false,
+            has_optimize: false,
+            has_optimize_immediately: false,
+            is_pub: false,
+            is_static: false,
+            internal: false,
+            is_abstract: false,
+            is_constructor: false,
+            throws: false,
+            is_test: false,
+            use_cannon: false,
+            allow: Vec::new(),
+            params,
+            return_type,
+            block: Some(block),
+            type_params: None,
+            where_bounds: Vec::new(),
+        };
 
         Ok(Box::new(Expr::create_lambda(
             self.generate_id(),
             tok.position,
             span,
-            params,
-            ret,
-            block,
+            Box::new(function),
         )))
     }
 
@@ -3568,14 +4047,14 @@ mod tests {
         let (expr, _) = parse_expr("|| {}");
         let lambda = expr.to_lambda().unwrap();
 
-        assert!(lambda.ret.is_none());
+        assert!(lambda.fct.return_type.is_none());
     }
 
     #[test]
     fn parse_lambda_no_params_unit_as_return_value() {
         let (expr, _) = parse_expr("|| -> () {}");
         let lambda = expr.to_lambda().unwrap();
-        let ret = lambda.ret.as_ref().unwrap();
+        let ret = lambda.fct.return_type.as_ref().unwrap();
 
         assert!(ret.is_unit());
     }
@@ -3584,7 +4063,7 @@ mod tests {
     fn parse_lambda_no_params_with_return_value() {
         let (expr, interner) = parse_expr("|| -> A {}");
         let lambda = expr.to_lambda().unwrap();
-        let ret = lambda.ret.as_ref().unwrap();
+        let ret = lambda.fct.return_type.as_ref().unwrap();
         let basic = ret.to_basic().unwrap();
 
         assert_eq!("A", *interner.str(basic.name));
@@ -3595,14 +4074,14 @@ mod tests {
         let (expr, interner) = parse_expr("|a: A| -> B {}");
         let lambda = expr.to_lambda().unwrap();
 
-        assert_eq!(1, lambda.params.len());
+        assert_eq!(1, lambda.fct.params.len());
 
-        let param = &lambda.params[0];
+        let param = &lambda.fct.params[0];
         assert_eq!("a", *interner.str(param.name));
         let basic = param.data_type.to_basic().unwrap();
         assert_eq!("A", *interner.str(basic.name));
 
-        let ret = lambda.ret.as_ref().unwrap();
+        let ret = lambda.fct.return_type.as_ref().unwrap();
         let basic = ret.to_basic().unwrap();
 
         assert_eq!("B", *interner.str(basic.name));
@@ -3613,19 +4092,19 @@ mod tests {
         let (expr, interner) = parse_expr("|a: A, b: B| -> C {}");
         let lambda = expr.to_lambda().unwrap();
 
-        assert_eq!(2, lambda.params.len());
+        assert_eq!(2, lambda.fct.params.len());
 
-        let param = &lambda.params[0];
+        let param = &lambda.fct.params[0];
         assert_eq!("a", *interner.str(param.name));
         let basic = param.data_type.to_basic().unwrap();
         assert_eq!("A", *interner.str(basic.name));
 
-        let param = &lambda.params[1];
+        let param = &lambda.fct.params[1];
         assert_eq!("b", *interner.str(param.name));
         let basic = param.data_type.to_basic().unwrap();
         assert_eq!("B", *interner.str(basic.name));
 
-        let ret = lambda.ret.as_ref().unwrap();
+        let ret = lambda.fct.return_type.as_ref().unwrap();
         let basic = ret.to_basic().unwrap();
 
         assert_eq!("C", *interner.str(basic.name));
@@ -3696,6 +4175,88 @@ mod tests {
         assert_eq!(expr.to_tuple().unwrap().values.len(), 4);
     }
 
+    #[test]
+    fn parse_match() {
+        let (expr, interner) = parse_expr(
+            "match x { Foo::A => 1, Some((a, _)) => { a } -1 => 2, \"x\" => 3, (1,) => 4, y => y }",
+        );
+        let expr = expr.to_match().unwrap();
+        assert!(expr.expr.is_ident());
+        assert_eq!(expr.cases.len(), 6);
+
+        match *expr.cases[0].pattern {
+            MatchPattern::Variant(ref p) => {
+                assert_eq!(p.path.len(), 2);
+                assert_eq!("A", *interner.str(p.path[1]));
+                assert!(p.params.is_none());
+            }
+            _ => unreachable!(),
+        }
+
+        match *expr.cases[1].pattern {
+            MatchPattern::Variant(ref p) => {
+                let params = p.params.as_ref().unwrap();
+                assert_eq!(params.len(), 1);
+
+                match *params[0] {
+                    MatchPattern::Tuple(ref t) => assert_eq!(t.params.len(), 2),
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+        assert!(expr.cases[1].value.is_block());
+
+        match *expr.cases[2].pattern {
+            MatchPattern::Lit(ref p) => assert!(p.expr.is_un()),
+            _ => unreachable!(),
+        }
+
+        match *expr.cases[3].pattern {
+            MatchPattern::Lit(ref p) => assert!(p.expr.is_lit_str()),
+            _ => unreachable!(),
+        }
+
+        match *expr.cases[4].pattern {
+            MatchPattern::Tuple(ref p) => assert_eq!(p.params.len(), 1),
+            _ => unreachable!(),
+        }
+
+        match *expr.cases[5].pattern {
+            MatchPattern::Ident(ref p) => assert_eq!("y", *interner.str(p.name)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parse_match_underscore_and_trailing_comma() {
+        let (expr, _) = parse_expr("match (1, 2) { (1, _) => 1, _ => 2, }");
+        let expr = expr.to_match().unwrap();
+        assert!(expr.expr.is_tuple());
+        assert_eq!(expr.cases.len(), 2);
+
+        match *expr.cases[1].pattern {
+            MatchPattern::Underscore(_) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parse_match_errors() {
+        err_expr(
+            "match x { 1 => 2 3 => 4 }",
+            ParseError::ExpectedToken(",".into(), "3".into()),
+            1,
+            18,
+        );
+        err_expr(
+            "match x { + => 2 }",
+            ParseError::ExpectedPattern("+".into()),
+            1,
+            11,
+        );
+    }
+
     #[test]
     fn parse_enum() {
         let (prog, _) = parse("enum Foo { A, B, C }");
@@ -3717,4 +4278,155 @@ mod tests {
         let (prog, _) = parse("alias NewType = Int;");
         let _alias = prog.alias0();
     }
+
+    #[test]
+    fn parse_function_throws() {
+        let (prog, _) = parse("fun f() throws -> Int { 1 }");
+        let fct = prog.fct0();
+        assert!(fct.throws);
+        assert!(fct.return_type.is_some());
+
+        let (prog, _) = parse("fun f() { }");
+        assert!(!prog.fct0().throws);
+    }
+
+    #[test]
+    fn parse_throw() {
+        let stmt = parse_stmt("throw foo;");
+        let throw = stmt.to_throw().unwrap();
+
+        assert!(throw.expr.is_ident());
+    }
+
+    #[test]
+    fn parse_try() {
+        let (expr, _) = parse_expr("try foo(1)");
+        let expr = expr.to_try().unwrap();
+
+        assert!(expr.expr.is_call());
+    }
+
+    #[test]
+    fn parse_do_catch() {
+        let stmt = parse_stmt("do { 1; } catch e: Exception { 2; } catch x: Foo { 3; }");
+        let stmt = stmt.to_do().unwrap();
+
+        assert!(stmt.do_block.is_expr());
+        assert_eq!(stmt.catch_blocks.len(), 2);
+    }
+
+    #[test]
+    fn parse_do_without_catch() {
+        err_stmt("do { 1; } 2;", ParseError::CatchBlockExpected, 1, 11);
+    }
+
+    #[test]
+    fn parse_defer() {
+        let stmt = parse_stmt("defer { foo(); }");
+        let stmt = stmt.to_defer().unwrap();
+
+        assert!(stmt.block.is_expr());
+    }
+
+    #[test]
+    fn parse_import() {
+        let (prog, interner) = parse("import foo::bar::Baz;");
+        let import = prog.import0();
+
+        assert_eq!(2, import.package.len());
+        assert_eq!("foo", *interner.str(import.package[0]));
+        assert_eq!("bar", *interner.str(import.package[1]));
+        assert_eq!(1, import.names.len());
+        assert_eq!("Baz", *interner.str(import.names[0].name));
+    }
+
+    #[test]
+    fn parse_import_list() {
+        let (prog, interner) = parse("import foo::{Bar, baz};");
+        let import = prog.import0();
+
+        assert_eq!(1, import.package.len());
+        assert_eq!(2, import.names.len());
+        assert_eq!("Bar", *interner.str(import.names[0].name));
+        assert_eq!("baz", *interner.str(import.names[1].name));
+    }
+
+    #[test]
+    fn parse_import_without_name() {
+        parse_err(
+            "import foo;",
+            ParseError::ExpectedToken("::".into(), ";".into()),
+            1,
+            11,
+        );
+        parse_err(
+            "@pub import foo::Bar;",
+            ParseError::MisplacedAnnotation("pub".into()),
+            1,
+            6,
+        );
+    }
+
+    #[test]
+    fn parse_pub_top_level_elements() {
+        let (prog, _) = parse("@pub class Foo");
+        assert!(prog.cls0().is_pub);
+
+        let (prog, _) = parse("class Foo");
+        assert!(!prog.cls0().is_pub);
+
+        let (prog, _) = parse("@pub fun f() {}");
+        assert!(prog.fct0().is_pub);
+
+        let (prog, _) = parse("@pub struct Foo { a: Int }");
+        assert!(prog.struct0().is_pub);
+
+        let (prog, _) = parse("@pub enum Foo { A, B }");
+        assert!(prog.enum0().is_pub);
+
+        let (prog, _) = parse("@pub trait Foo {}");
+        assert!(prog.trait0().is_pub);
+
+        let (prog, _) = parse("@pub const x: Int = 0;");
+        assert!(prog.const0().is_pub);
+
+        let (prog, _) = parse("@pub let x: Int = 0;");
+        assert!(prog.global0().is_pub);
+    }
+
+    #[test]
+    fn parse_allow_annotation() {
+        let (prog, interner) = parse("@allow(unused, unreachable) fun f() {}");
+        let allow = &prog.fct0().allow;
+        assert_eq!(2, allow.len());
+        assert_eq!("unused", *interner.str(allow[0]));
+        assert_eq!("unreachable", *interner.str(allow[1]));
+
+        let (prog, interner) = parse("@allow(unused) class Foo { @allow(shadow) fun f() {} }");
+        assert_eq!("unused", *interner.str(prog.cls0().allow[0]));
+        assert_eq!("shadow", *interner.str(prog.cls0().methods[0].allow[0]));
+
+        let (prog, _) = parse("fun f() {}");
+        assert!(prog.fct0().allow.is_empty());
+    }
+
+    #[test]
+    fn parse_where_clause() {
+        let (prog, interner) =
+            parse("class A[T, U] { fun f(x: T) -> Bool where T: Equals + Hash, U: Zero = true; }");
+        let where_bounds = &prog.cls0().methods[0].where_bounds;
+        assert_eq!(2, where_bounds.len());
+        assert_eq!("T", where_bounds[0].ty.to_string(&interner));
+        assert_eq!(2, where_bounds[0].bounds.len());
+        assert_eq!("Hash", where_bounds[0].bounds[1].to_string(&interner));
+        assert_eq!("U", where_bounds[1].ty.to_string(&interner));
+
+        let (prog, interner) = parse("impl[T] Equals for A[T] where T: Equals {}");
+        let where_bounds = &prog.impl0().where_bounds;
+        assert_eq!(1, where_bounds.len());
+        assert_eq!("Equals", where_bounds[0].bounds[0].to_string(&interner));
+
+        let (prog, _) = parse("fun f() { let where = 1; }");
+        assert!(prog.fct0().where_bounds.is_empty());
+    }
 }
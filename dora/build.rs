@@ -5,11 +5,30 @@ use std::{
     fs::{self, copy, File},
     io::Write,
     path::Path,
+    process::Command,
 };
 
 const SOURCE_DIR: &str = "stdlib";
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // part of the key of `.dorabc` files, empty when not built from git
+    let git_hash = git(&["rev-parse", "HEAD"]).unwrap_or_default();
+    println!("cargo:rustc-env=DORA_GIT_HASH={}", git_hash);
+
+    // the hash changes with HEAD or the branch HEAD points to, emitting
+    // `rerun-if-changed` means the stdlib files need to be listed as well
+    let mut git_files = vec!["HEAD".to_string(), "packed-refs".to_string()];
+    git_files.extend(git(&["rev-parse", "--symbolic-full-name", "HEAD"]));
+
+    for git_file in git_files {
+        if let Some(path) = git(&["rev-parse", "--git-path", &git_file]) {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", SOURCE_DIR);
+
     let out_dir = env::var("OUT_DIR")?;
     let out_path = Path::new(&out_dir);
 
@@ -50,6 +69,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             None => continue,
         }
 
+        println!("cargo:rerun-if-changed={}", f.path().display());
         copy(root_path.join(f.path()), out_path.join(f.path()))?;
 
         let name = f.path().display().to_string();
@@ -67,3 +87,14 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+// trimmed output of the git command, none when it fails
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|output| !output.is_empty())
+}
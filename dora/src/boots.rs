@@ -1,7 +1,8 @@
 use std::mem;
 use std::ptr;
 
-use crate::bytecode::{self, BytecodeFunction, ConstPoolEntry, ConstPoolOpcode};
+use crate::bytecode::file::encode_const_pool;
+use crate::bytecode::{self, BytecodeFunction};
 use crate::compiler::codegen::should_emit_bytecode;
 use crate::compiler::fct::{Code, JitDescriptor};
 use crate::gc::Address;
@@ -102,44 +103,8 @@ fn allocate_registers_array(vm: &VM, fct: &BytecodeFunction) -> Ref<Int32Array>
 }
 
 fn allocate_constpool_array(vm: &VM, fct: &BytecodeFunction) -> Ref<UInt8Array> {
-    use byteorder::{LittleEndian, WriteBytesExt};
     let mut buffer = Vec::new();
-
-    for const_entry in fct.const_pool_entries() {
-        match const_entry {
-            ConstPoolEntry::String(ref value) => {
-                buffer.push(ConstPoolOpcode::Float32 as u8);
-                buffer
-                    .write_u32::<LittleEndian>(value.len() as u32)
-                    .unwrap();
-
-                for byte in value.bytes() {
-                    buffer.push(byte);
-                }
-            }
-            &ConstPoolEntry::Float32(value) => {
-                buffer.push(ConstPoolOpcode::Float32 as u8);
-                buffer.write_u32::<LittleEndian>(value.to_bits()).unwrap();
-            }
-            &ConstPoolEntry::Float64(value) => {
-                buffer.push(ConstPoolOpcode::Float64 as u8);
-                buffer.write_u64::<LittleEndian>(value.to_bits()).unwrap();
-            }
-            &ConstPoolEntry::Int32(value) => {
-                buffer.push(ConstPoolOpcode::Int32 as u8);
-                buffer.write_u32::<LittleEndian>(value as u32).unwrap();
-            }
-            &ConstPoolEntry::Int64(value) => {
-                buffer.push(ConstPoolOpcode::Int64 as u8);
-                buffer.write_u64::<LittleEndian>(value as u64).unwrap();
-            }
-            &ConstPoolEntry::Char(value) => {
-                buffer.push(ConstPoolOpcode::Char as u8);
-                buffer.write_u32::<LittleEndian>(value as u32).unwrap();
-            }
-        }
-    }
-
+    encode_const_pool(fct.const_pool_entries(), &mut buffer);
     byte_array_from_buffer(vm, &buffer)
 }
 
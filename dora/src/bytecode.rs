@@ -1,6 +1,8 @@
 pub mod builder;
+pub mod cache;
 pub mod data;
 pub mod dumper;
+pub mod file;
 pub mod generator;
 #[cfg(test)]
 mod generator_tests;
@@ -13,6 +15,6 @@ mod tests;
 pub use builder::*;
 pub use data::*;
 pub use dumper::dump;
-pub use generator::{generate, generate_fct};
+pub use generator::{generate, generate_fct, generate_relocatable_fct};
 pub use reader::*;
 pub use writer::*;
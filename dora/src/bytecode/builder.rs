@@ -4,7 +4,7 @@ use dora_parser::lexer::position::Position;
 
 use crate::bytecode::{BytecodeFunction, BytecodeType, BytecodeWriter, Label, Register};
 use crate::driver::cmd::Args;
-use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, TupleId};
+use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, StructDefId, StructFieldId, TupleId};
 
 pub struct BytecodeBuilder {
     writer: BytecodeWriter,
@@ -21,6 +21,10 @@ impl BytecodeBuilder {
         }
     }
 
+    pub fn set_relocatable(&mut self) {
+        self.writer.set_relocatable()
+    }
+
     pub fn create_label(&mut self) -> Label {
         self.writer.create_label()
     }
@@ -546,6 +550,23 @@ impl BytecodeBuilder {
             .emit_load_tuple_element(dest, src, tuple_id, element);
     }
 
+    pub fn emit_mov_struct(&mut self, dest: Register, src: Register, struct_def_id: StructDefId) {
+        assert!(self.def(dest) && self.used(src));
+        self.writer.emit_mov_struct(dest, src, struct_def_id);
+    }
+
+    pub fn emit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        assert!(self.def(dest) && self.used(src));
+        self.writer
+            .emit_load_struct_field(dest, src, struct_def_id, field_id);
+    }
+
     pub fn emit_ret(&mut self, src: Register) {
         assert!(self.used(src));
         self.writer.emit_ret(src);
@@ -555,6 +576,25 @@ impl BytecodeBuilder {
         self.writer.emit_ret_void();
     }
 
+    pub fn emit_throw(&mut self, src: Register, pos: Position) {
+        assert!(self.used(src));
+        self.writer.set_position(pos);
+        self.writer.emit_throw(src);
+    }
+
+    pub fn add_exception_handler(
+        &mut self,
+        try_start: Label,
+        try_end: Label,
+        catch: Label,
+        catch_type: Option<ClassDefId>,
+        register: Register,
+        depth: usize,
+    ) {
+        self.writer
+            .add_exception_handler(try_start, try_end, catch, catch_type, register, depth);
+    }
+
     pub fn emit_test_eq_bool(&mut self, dest: Register, lhs: Register, rhs: Register) {
         assert!(self.def(dest) && self.used(lhs) && self.used(rhs));
         self.writer.emit_test_eq_bool(dest, lhs, rhs);
@@ -808,6 +848,17 @@ impl BytecodeBuilder {
         self.writer.emit_invoke_virtual(dest, fid);
     }
 
+    pub fn emit_invoke_trait_void(&mut self, fid: FctDefId, pos: Position) {
+        self.writer.set_position(pos);
+        self.writer.emit_invoke_trait_void(fid);
+    }
+
+    pub fn emit_invoke_trait(&mut self, dest: Register, fid: FctDefId, pos: Position) {
+        assert!(self.def(dest));
+        self.writer.set_position(pos);
+        self.writer.emit_invoke_trait(dest, fid);
+    }
+
     pub fn emit_invoke_static_void(&mut self, fid: FctDefId, pos: Position) {
         self.writer.set_position(pos);
         self.writer.emit_invoke_static_void(fid);
@@ -819,6 +870,17 @@ impl BytecodeBuilder {
         self.writer.emit_invoke_static(dest, fid);
     }
 
+    pub fn emit_invoke_lambda_void(&mut self, pos: Position) {
+        self.writer.set_position(pos);
+        self.writer.emit_invoke_lambda_void();
+    }
+
+    pub fn emit_invoke_lambda(&mut self, dest: Register, pos: Position) {
+        assert!(self.def(dest));
+        self.writer.set_position(pos);
+        self.writer.emit_invoke_lambda(dest);
+    }
+
     pub fn emit_new_object(&mut self, dest: Register, cls_id: ClassDefId, pos: Position) {
         assert!(self.def(dest));
         self.writer.set_position(pos);
@@ -839,6 +901,15 @@ impl BytecodeBuilder {
         assert!(self.def(dest));
         self.writer.emit_new_tuple(dest, tuple_id);
     }
+    pub fn emit_new_struct(&mut self, dest: Register, struct_def_id: StructDefId) {
+        assert!(self.def(dest));
+        self.writer.emit_new_struct(dest, struct_def_id);
+    }
+    pub fn emit_new_lambda(&mut self, dest: Register, fid: FctDefId, pos: Position) {
+        assert!(self.def(dest));
+        self.writer.set_position(pos);
+        self.writer.emit_new_lambda(dest, fid);
+    }
 
     pub fn emit_nil_check(&mut self, obj: Register, pos: Position) {
         assert!(self.used(obj));
@@ -956,6 +1027,17 @@ impl BytecodeBuilder {
         self.writer.set_position(pos);
         self.writer.emit_store_array_tuple(src, array, index);
     }
+    pub fn emit_store_array_struct(
+        &mut self,
+        src: Register,
+        array: Register,
+        index: Register,
+        pos: Position,
+    ) {
+        assert!(self.used(src) && self.used(array) && self.used(index));
+        self.writer.set_position(pos);
+        self.writer.emit_store_array_struct(src, array, index);
+    }
 
     pub fn emit_load_array_uint8(
         &mut self,
@@ -1056,6 +1138,17 @@ impl BytecodeBuilder {
         self.writer.set_position(pos);
         self.writer.emit_load_array_tuple(dest, array, index);
     }
+    pub fn emit_load_array_struct(
+        &mut self,
+        dest: Register,
+        array: Register,
+        index: Register,
+        pos: Position,
+    ) {
+        assert!(self.def(dest) && self.used(array) && self.used(index));
+        self.writer.set_position(pos);
+        self.writer.emit_load_array_struct(dest, array, index);
+    }
 
     pub fn generate(self) -> BytecodeFunction {
         for reg in &self.registers.used {
@@ -0,0 +1,860 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::bytecode::file::{self, BytecodeFile, DeclKind, FileClassDef, FileFctDef, FileType};
+use crate::bytecode::{generate_relocatable_fct, BytecodeFunction, RelocationKind};
+use crate::driver::cmd::Args;
+use crate::semck::specialize::{
+    specialize_class_id_params, specialize_enum_id_params, specialize_struct_id_params,
+};
+use crate::ty::{BuiltinType, TypeList};
+use crate::vm::{
+    ensure_tuple, ClassDefId, EnumId, Fct, FctDef, FctDefId, FctId, FctParent, FileId, GlobalId,
+    StructDefId, TraitId, TupleId, VM,
+};
+
+// bytecode of functions loaded from a `.dorabc` file, either the file of
+// the program or the snapshot of the stdlib. the file is only used when it
+// was written for the same sources by the same compiler binary with the same
+// code generation arguments and all declarations it refers to still exist,
+// the bodies of cached functions are then not type checked again.
+pub struct BytecodeCache {
+    file: BytecodeFile,
+    // number of stdlib files when the file is the stdlib snapshot
+    stdlib_files: Option<usize>,
+    // set once the declarations of the file were verified
+    fcts: HashSet<FctId>,
+    // set once semck is finished
+    bytecode: HashMap<FctId, BytecodeFunction>,
+}
+
+impl BytecodeCache {
+    // the file was written for the first `files` source files of the VM
+    pub fn new(vm: &VM, file: BytecodeFile, files: usize) -> Option<BytecodeCache> {
+        if file.key != key(vm, files) {
+            return None;
+        }
+
+        Some(BytecodeCache {
+            file,
+            stdlib_files: None,
+            fcts: HashSet::new(),
+            bytecode: HashMap::new(),
+        })
+    }
+
+    // ids of declarations in the stdlib snapshot are indices into the stdlib
+    // declarations of the same kind, see `StdlibDecls`
+    pub fn stdlib_snapshot(vm: &VM, file: BytecodeFile, files: usize) -> Option<BytecodeCache> {
+        let mut cache = BytecodeCache::new(vm, file, files)?;
+        cache.stdlib_files = Some(files);
+        Some(cache)
+    }
+
+    pub fn contains(&self, id: FctId) -> bool {
+        self.fcts.contains(&id)
+    }
+
+    pub fn bytecode(&self, id: FctId) -> Option<BytecodeFunction> {
+        self.bytecode.get(&id).cloned()
+    }
+}
+
+// `--clear-regs` is the only argument read by `BytecodeBuilder`
+pub fn key(vm: &VM, files: usize) -> u64 {
+    let build = concat!(env!("CARGO_PKG_VERSION"), " ", env!("DORA_GIT_HASH"));
+    let binary = binary_identity();
+    let clear_regs = if vm.args.flag_clear_regs {
+        "clear-regs"
+    } else {
+        ""
+    };
+
+    let sources = vm.files[..files].iter().map(|f| &f.content[..]);
+    file::hash_strings([build, &binary, clear_regs].iter().cloned().chain(sources))
+}
+
+// size and modification time of the running binary, these change with
+// every build of the compiler, even when the changes aren't committed.
+// hashing the whole binary would be exact but too slow for every start.
+fn binary_identity() -> String {
+    let metadata = match env::current_exe().and_then(fs::metadata) {
+        Ok(metadata) => metadata,
+        Err(_) => return String::new(),
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+
+    format!("{} {}", metadata.len(), modified)
+}
+
+// `foo.dora` is cached in `foo.dorabc`, the package in directory `foo` in
+// `foo/foo.dorabc`
+pub fn path(arg_file: &str) -> Option<PathBuf> {
+    let path = Path::new(arg_file);
+
+    if path.is_file() {
+        Some(path.with_extension("dorabc"))
+    } else if path.is_dir() {
+        let dir = path.canonicalize().ok()?;
+        let mut name = dir.file_name()?.to_os_string();
+        name.push(".dorabc");
+        Some(path.join(name))
+    } else {
+        None
+    }
+}
+
+// the bytecode snapshot of the stdlib is written by `dora stdlib-snapshot`
+// next to the `dora` binary, unless another file is given
+pub fn stdlib_snapshot_path(args: &Args) -> Option<PathBuf> {
+    if let Some(ref path) = args.flag_stdlib_snapshot {
+        return Some(PathBuf::from(path));
+    }
+
+    let exe = env::current_exe().ok()?;
+    Some(exe.with_file_name("stdlib.dorabc"))
+}
+
+// a missing, invalid or outdated file isn't an error, the functions are
+// compiled from source instead
+pub fn open(vm: &VM, path: &Path, files: usize) -> Option<BytecodeCache> {
+    let data = fs::read(path).ok()?;
+    let file = file::read(&data).ok()?;
+    BytecodeCache::new(vm, file, files)
+}
+
+pub fn open_stdlib_snapshot(vm: &VM, path: &Path, files: usize) -> Option<BytecodeCache> {
+    let data = fs::read(path).ok()?;
+    let file = file::read(&data).ok()?;
+    BytecodeCache::stdlib_snapshot(vm, file, files)
+}
+
+// generic functions have no bytecode without concrete type arguments
+pub fn is_cacheable<'ast>(vm: &VM<'ast>, fct: &Fct<'ast>) -> bool {
+    if !fct.is_src() || fct.is_lambda() || fct.ast.block.is_none() || !fct.type_params.is_empty() {
+        return false;
+    }
+
+    match fct.parent {
+        FctParent::None | FctParent::Module(_) => true,
+        FctParent::Class(cls_id) => {
+            let cls = vm.classes.idx(cls_id);
+            let cls = cls.read();
+
+            // internal classes like `Unit` are never constructed
+            cls.type_params.is_empty() && !(cls.internal && fct.is_constructor)
+        }
+        FctParent::Impl(impl_id) => !vm.impls[impl_id].read().class_ty.contains_type_param(vm),
+        FctParent::Extension(extension_id) => {
+            vm.extensions[extension_id].read().type_params.is_empty()
+        }
+        FctParent::Trait(_) | FctParent::Function(_) => false,
+    }
+}
+
+// writes the functions of the given source files, the key of the file
+// covers all files up to the last one
+pub fn emit<'ast>(vm: &VM<'ast>, files: Range<usize>) -> BytecodeFile {
+    let fct_ids = vm
+        .fcts
+        .iter()
+        .filter(|fct| {
+            let fct = fct.read();
+            files.contains(&fct.file.to_usize()) && is_cacheable(vm, &fct)
+        })
+        .map(|fct| fct.read().id)
+        .collect::<Vec<_>>();
+
+    let mut symbols = Symbols::new(vm, key(vm, files.end));
+
+    for fct_id in fct_ids {
+        let bytecode_fct = generate_relocatable_fct(vm, fct_id);
+
+        // functions creating lambdas are left out, lambdas are type checked
+        // together with their enclosing function
+        let bytecode_fct = bytecode_fct.relocate(|kind, id| symbols.symbol(kind, id));
+
+        if let Some(bytecode_fct) = bytecode_fct {
+            let fct_id = fct_id.to_usize() as u32;
+            symbols.decl(DeclKind::Fct, fct_id);
+            symbols.file.functions.push((fct_id, bytecode_fct));
+        }
+    }
+
+    symbols.file
+}
+
+// the stdlib snapshot is written for the first `files` source files, which
+// are all files of the VM
+pub fn emit_stdlib_snapshot<'ast>(vm: &VM<'ast>, files: usize) -> BytecodeFile {
+    let mut file = emit(vm, 0..files);
+    let decls = StdlibDecls::new(vm, files);
+    map_decl_ids(&mut file, &|kind, id| decls.idx(kind, id))
+        .expect("declaration outside of the stdlib");
+    file
+}
+
+// semck creates the declarations of one kind for all files before the next
+// kind, e.g. the constructors of all classes are created after all
+// functions. ids of stdlib declarations therefore depend on the program
+// while their order among the stdlib declarations doesn't.
+struct StdlibDecls {
+    ids: HashMap<DeclKind, Vec<u32>>,
+}
+
+impl StdlibDecls {
+    fn new(vm: &VM, files: usize) -> StdlibDecls {
+        let in_stdlib = |file: FileId| file.to_usize() < files;
+        let ids_in_stdlib = |files: Vec<FileId>| {
+            files
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, file)| in_stdlib(file))
+                .map(|(id, _)| id as u32)
+                .collect::<Vec<_>>()
+        };
+
+        let mut ids = HashMap::new();
+        let fcts = vm.fcts.iter().map(|fct| fct.read().file).collect();
+        ids.insert(DeclKind::Fct, ids_in_stdlib(fcts));
+        let classes = vm.classes.iter().map(|cls| cls.read().file).collect();
+        ids.insert(DeclKind::Class, ids_in_stdlib(classes));
+        let structs = vm
+            .structs
+            .iter()
+            .map(|xstruct| xstruct.lock().file)
+            .collect();
+        ids.insert(DeclKind::Struct, ids_in_stdlib(structs));
+        let enums = vm.enums.iter().map(|xenum| xenum.read().file).collect();
+        ids.insert(DeclKind::Enum, ids_in_stdlib(enums));
+        let traits = vm.traits.iter().map(|xtrait| xtrait.read().file).collect();
+        ids.insert(DeclKind::Trait, ids_in_stdlib(traits));
+        let globals = vm.globals.iter().map(|glob| glob.read().file).collect();
+        ids.insert(DeclKind::Global, ids_in_stdlib(globals));
+
+        StdlibDecls { ids }
+    }
+
+    fn id(&self, kind: DeclKind, idx: u32) -> Option<u32> {
+        self.ids[&kind].get(idx as usize).cloned()
+    }
+
+    fn idx(&self, kind: DeclKind, id: u32) -> Option<u32> {
+        self.ids[&kind]
+            .binary_search(&id)
+            .ok()
+            .map(|idx| idx as u32)
+    }
+}
+
+// replaces the id of every declaration the file refers to
+fn map_decl_ids(file: &mut BytecodeFile, map: &dyn Fn(DeclKind, u32) -> Option<u32>) -> Option<()> {
+    for (kind, id, _) in &mut file.decls {
+        *id = map(*kind, *id)?;
+    }
+
+    for fct_def in &mut file.fct_defs {
+        fct_def.fct_id = map(DeclKind::Fct, fct_def.fct_id)?;
+        map_type_ids(&mut fct_def.cls_type_params, map)?;
+        map_type_ids(&mut fct_def.fct_type_params, map)?;
+    }
+
+    for class_def in &mut file.class_defs {
+        match class_def {
+            FileClassDef::Class(cls_id, type_params) => {
+                *cls_id = map(DeclKind::Class, *cls_id)?;
+                map_type_ids(type_params, map)?;
+            }
+
+            FileClassDef::EnumVariant(enum_id, type_params, _) => {
+                *enum_id = map(DeclKind::Enum, *enum_id)?;
+                map_type_ids(type_params, map)?;
+            }
+        }
+    }
+
+    for subtypes in &mut file.tuples {
+        map_type_ids(subtypes, map)?;
+    }
+
+    for (struct_id, type_params) in &mut file.struct_defs {
+        *struct_id = map(DeclKind::Struct, *struct_id)?;
+        map_type_ids(type_params, map)?;
+    }
+
+    for global_id in &mut file.globals {
+        *global_id = map(DeclKind::Global, *global_id)?;
+    }
+
+    for (fct_id, _) in &mut file.functions {
+        *fct_id = map(DeclKind::Fct, *fct_id)?;
+    }
+
+    Some(())
+}
+
+fn map_type_ids(types: &mut [FileType], map: &dyn Fn(DeclKind, u32) -> Option<u32>) -> Option<()> {
+    for ty in types {
+        match ty {
+            FileType::Class(cls_id, type_params) => {
+                *cls_id = map(DeclKind::Class, *cls_id)?;
+                map_type_ids(type_params, map)?;
+            }
+            FileType::Struct(struct_id, type_params) => {
+                *struct_id = map(DeclKind::Struct, *struct_id)?;
+                map_type_ids(type_params, map)?;
+            }
+            FileType::Enum(enum_id, type_params) => {
+                *enum_id = map(DeclKind::Enum, *enum_id)?;
+                map_type_ids(type_params, map)?;
+            }
+            FileType::Tuple(subtypes) => map_type_ids(subtypes, map)?,
+            FileType::Trait(trait_id) => *trait_id = map(DeclKind::Trait, *trait_id)?,
+            _ => {}
+        }
+    }
+
+    Some(())
+}
+
+// drops a cache when its file doesn't match the declarations of the
+// program, called before the bodies of functions are type checked
+pub fn verify(vm: &mut VM) {
+    let stdlib_snapshot = vm.stdlib_snapshot.take();
+    vm.stdlib_snapshot = verify_cache(vm, stdlib_snapshot);
+
+    let bytecode_cache = vm.bytecode_cache.take();
+    vm.bytecode_cache = verify_cache(vm, bytecode_cache);
+}
+
+fn verify_cache(vm: &VM, cache: Option<BytecodeCache>) -> Option<BytecodeCache> {
+    let mut cache = cache?;
+
+    // all declarations exist now
+    if let Some(files) = cache.stdlib_files {
+        let decls = StdlibDecls::new(vm, files);
+        map_decl_ids(&mut cache.file, &|kind, idx| decls.id(kind, idx))?;
+    }
+
+    cache.fcts = verify_file(vm, &cache.file)?;
+    Some(cache)
+}
+
+fn verify_file(vm: &VM, file: &BytecodeFile) -> Option<HashSet<FctId>> {
+    for (kind, id, name) in &file.decls {
+        if decl_name(vm, *kind, *id)? != *name {
+            return None;
+        }
+    }
+
+    let mut fcts = HashSet::new();
+
+    for (fct_id, bytecode_fct) in &file.functions {
+        let fct_id: FctId = (*fct_id as usize).into();
+
+        if fct_id.to_usize() >= vm.fcts.len() || !is_cacheable(vm, &vm.fcts.idx(fct_id).read()) {
+            return None;
+        }
+
+        let symbols_len = |kind| match kind {
+            RelocationKind::FctDef => file.fct_defs.len(),
+            RelocationKind::ClassDef => file.class_defs.len(),
+            RelocationKind::Tuple => file.tuples.len(),
+            RelocationKind::StructDef => file.struct_defs.len(),
+            RelocationKind::Global => file.globals.len(),
+        };
+
+        let valid = bytecode_fct
+            .relocate(|kind, idx| {
+                if (idx as usize) < symbols_len(kind) {
+                    Some(idx)
+                } else {
+                    None
+                }
+            })
+            .is_some();
+
+        if !valid {
+            return None;
+        }
+
+        fcts.insert(fct_id);
+    }
+
+    Some(fcts)
+}
+
+fn decl_name(vm: &VM, kind: DeclKind, id: u32) -> Option<String> {
+    let id = id as usize;
+
+    let name = match kind {
+        DeclKind::Fct if id < vm.fcts.len() => {
+            return Some(vm.fcts.idx(id.into()).read().full_name(vm))
+        }
+        DeclKind::Class if id < vm.classes.len() => vm.classes.idx(id.into()).read().name,
+        DeclKind::Struct if id < vm.structs.len() => vm.structs.idx((id as u32).into()).lock().name,
+        DeclKind::Enum if id < vm.enums.len() => vm.enums[EnumId::from(id)].read().name,
+        DeclKind::Trait if id < vm.traits.len() => vm.traits[TraitId::from(id as u32)].read().name,
+        DeclKind::Global if id < vm.globals.len() => vm.globals.idx((id as u32).into()).read().name,
+        _ => return None,
+    };
+
+    Some(vm.interner.str(name).to_string())
+}
+
+// creates the symbols of the file in this VM and replaces the symbol
+// indices in the cached bytecode, called after semck
+pub fn relocate(vm: &mut VM) {
+    if let Some(mut stdlib_snapshot) = vm.stdlib_snapshot.take() {
+        stdlib_snapshot.bytecode = relocate_file(vm, &stdlib_snapshot.file);
+        vm.stdlib_snapshot = Some(stdlib_snapshot);
+    }
+
+    if let Some(mut bytecode_cache) = vm.bytecode_cache.take() {
+        bytecode_cache.bytecode = relocate_file(vm, &bytecode_cache.file);
+        vm.bytecode_cache = Some(bytecode_cache);
+    }
+}
+
+fn relocate_file(vm: &VM, file: &BytecodeFile) -> HashMap<FctId, BytecodeFunction> {
+    let fct_defs = file
+        .fct_defs
+        .iter()
+        .map(|fct_def| {
+            let cls_type_params = decode_type_list(vm, &fct_def.cls_type_params);
+            let fct_type_params = decode_type_list(vm, &fct_def.fct_type_params);
+            let fct_id = (fct_def.fct_id as usize).into();
+            FctDef::fct_id_types(vm, fct_id, cls_type_params, fct_type_params).to_usize() as u32
+        })
+        .collect::<Vec<_>>();
+
+    let class_defs = file
+        .class_defs
+        .iter()
+        .map(|class_def| {
+            let cls_def_id = match class_def {
+                FileClassDef::Class(cls_id, type_params) => {
+                    let type_params = decode_type_list(vm, type_params);
+                    specialize_class_id_params(vm, (*cls_id as usize).into(), &type_params)
+                }
+
+                FileClassDef::EnumVariant(enum_id, type_params, variant) => {
+                    let type_params = decode_type_list(vm, type_params);
+                    let enum_def_id =
+                        specialize_enum_id_params(vm, (*enum_id as usize).into(), type_params);
+                    let enum_def = vm.enum_defs.idx(enum_def_id);
+                    let enum_def = enum_def.read();
+                    enum_def.variants[*variant as usize]
+                        .cls_def_id
+                        .expect("enum variant without class")
+                }
+            };
+
+            cls_def_id.to_usize() as u32
+        })
+        .collect::<Vec<_>>();
+
+    let tuples = file
+        .tuples
+        .iter()
+        .map(|subtypes| ensure_tuple(vm, decode_types(vm, subtypes)).to_usize() as u32)
+        .collect::<Vec<_>>();
+
+    let struct_defs = file
+        .struct_defs
+        .iter()
+        .map(|(struct_id, type_params)| {
+            let type_params = decode_type_list(vm, type_params);
+            specialize_struct_id_params(vm, (*struct_id).into(), type_params).to_usize() as u32
+        })
+        .collect::<Vec<_>>();
+
+    let mut bytecode = HashMap::new();
+
+    for (fct_id, bytecode_fct) in &file.functions {
+        let bytecode_fct = bytecode_fct
+            .relocate(|kind, idx| {
+                let idx = idx as usize;
+
+                let id = match kind {
+                    RelocationKind::FctDef => fct_defs[idx],
+                    RelocationKind::ClassDef => class_defs[idx],
+                    RelocationKind::Tuple => tuples[idx],
+                    RelocationKind::StructDef => struct_defs[idx],
+                    // ids of globals are the same for the same sources
+                    RelocationKind::Global => file.globals[idx],
+                };
+
+                Some(id)
+            })
+            .expect("symbols were verified");
+
+        bytecode.insert((*fct_id as usize).into(), bytecode_fct);
+    }
+
+    bytecode
+}
+
+fn decode_type_list(vm: &VM, types: &[FileType]) -> TypeList {
+    TypeList::with(decode_types(vm, types))
+}
+
+fn decode_types(vm: &VM, types: &[FileType]) -> Vec<BuiltinType> {
+    types.iter().map(|ty| decode_type(vm, ty)).collect()
+}
+
+fn decode_type(vm: &VM, ty: &FileType) -> BuiltinType {
+    match ty {
+        FileType::Unit => BuiltinType::Unit,
+        FileType::Bool => BuiltinType::Bool,
+        FileType::Char => BuiltinType::Char,
+        FileType::UInt8 => BuiltinType::UInt8,
+        FileType::Int32 => BuiltinType::Int32,
+        FileType::Int64 => BuiltinType::Int64,
+        FileType::Float32 => BuiltinType::Float32,
+        FileType::Float64 => BuiltinType::Float64,
+        FileType::Class(cls_id, type_params) => {
+            let type_params = decode_type_list(vm, type_params);
+            let list_id = vm.lists.lock().insert(type_params);
+            BuiltinType::Class((*cls_id as usize).into(), list_id)
+        }
+        FileType::Struct(struct_id, type_params) => {
+            let type_params = decode_type_list(vm, type_params);
+            let list_id = vm.lists.lock().insert(type_params);
+            BuiltinType::Struct((*struct_id).into(), list_id)
+        }
+        FileType::Enum(enum_id, type_params) => {
+            let type_params = decode_type_list(vm, type_params);
+            let list_id = vm.lists.lock().insert(type_params);
+            BuiltinType::Enum((*enum_id as usize).into(), list_id)
+        }
+        FileType::Tuple(subtypes) => {
+            BuiltinType::Tuple(ensure_tuple(vm, decode_types(vm, subtypes)))
+        }
+        FileType::Trait(trait_id) => BuiltinType::Trait((*trait_id).into()),
+    }
+}
+
+// collects the symbols referenced by relocatable bytecode, each symbol is
+// described by declaration ids and types only
+struct Symbols<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    file: BytecodeFile,
+    decls: HashSet<(DeclKind, u32)>,
+    indices: HashMap<(RelocationKind, u32), u32>,
+}
+
+impl<'a, 'ast> Symbols<'a, 'ast> {
+    fn new(vm: &'a VM<'ast>, key: u64) -> Symbols<'a, 'ast> {
+        Symbols {
+            vm,
+            file: BytecodeFile::new(key),
+            decls: HashSet::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    fn symbol(&mut self, kind: RelocationKind, id: u32) -> Option<u32> {
+        if let Some(&idx) = self.indices.get(&(kind, id)) {
+            return Some(idx);
+        }
+
+        let idx = match kind {
+            RelocationKind::FctDef => {
+                let fct_def = self.fct_def(id)?;
+                self.file.fct_defs.push(fct_def);
+                self.file.fct_defs.len()
+            }
+            RelocationKind::ClassDef => {
+                let class_def = self.class_def((id as usize).into())?;
+                self.file.class_defs.push(class_def);
+                self.file.class_defs.len()
+            }
+            RelocationKind::Tuple => {
+                let subtypes = self.vm.tuples.lock().get(TupleId::from(id));
+                let subtypes = self.encode_types(subtypes.iter().cloned())?;
+                self.file.tuples.push(subtypes);
+                self.file.tuples.len()
+            }
+            RelocationKind::StructDef => {
+                let struct_def = self.struct_def((id as usize).into())?;
+                self.file.struct_defs.push(struct_def);
+                self.file.struct_defs.len()
+            }
+            RelocationKind::Global => {
+                self.decl(DeclKind::Global, GlobalId::from(id).to_usize() as u32);
+                self.file.globals.push(id);
+                self.file.globals.len()
+            }
+        } as u32
+            - 1;
+
+        self.indices.insert((kind, id), idx);
+        Some(idx)
+    }
+
+    fn fct_def(&mut self, id: u32) -> Option<FileFctDef> {
+        let fct_def = self.vm.fct_defs.idx(FctDefId::from(id as usize));
+        let fct_def = fct_def.read();
+
+        if self.vm.fcts.idx(fct_def.fct_id).read().is_lambda() {
+            return None;
+        }
+
+        let fct_id = fct_def.fct_id.to_usize() as u32;
+        self.decl(DeclKind::Fct, fct_id);
+
+        Some(FileFctDef {
+            fct_id,
+            cls_type_params: self.encode_types(fct_def.cls_type_params.iter())?,
+            fct_type_params: self.encode_types(fct_def.fct_type_params.iter())?,
+        })
+    }
+
+    fn class_def(&mut self, id: ClassDefId) -> Option<FileClassDef> {
+        let class_def = self.vm.class_defs.idx(id);
+        let class_def = class_def.read();
+
+        if let Some(cls_id) = class_def.cls_id {
+            let cls_id = usize::from(cls_id) as u32;
+            self.decl(DeclKind::Class, cls_id);
+            let type_params = self.encode_types(class_def.type_params.iter())?;
+            return Some(FileClassDef::Class(cls_id, type_params));
+        }
+
+        // classes of lambdas and boxes can't be described
+        for enum_def in self.vm.enum_defs.iter() {
+            let enum_def = enum_def.read();
+
+            let variant = enum_def
+                .variants
+                .iter()
+                .position(|variant| variant.cls_def_id == Some(id));
+
+            if let Some(variant) = variant {
+                let enum_id = enum_def.enum_id.to_usize() as u32;
+                self.decl(DeclKind::Enum, enum_id);
+                let type_params = self.encode_types(enum_def.type_params.iter())?;
+                return Some(FileClassDef::EnumVariant(
+                    enum_id,
+                    type_params,
+                    variant as u32,
+                ));
+            }
+        }
+
+        None
+    }
+
+    fn struct_def(&mut self, id: StructDefId) -> Option<(u32, Vec<FileType>)> {
+        let struct_def = self.vm.struct_defs.idx(id);
+        let struct_def = struct_def.lock();
+
+        let struct_id = struct_def.struct_id.to_usize() as u32;
+        self.decl(DeclKind::Struct, struct_id);
+        let type_params = self.encode_types(struct_def.type_params.iter())?;
+
+        Some((struct_id, type_params))
+    }
+
+    fn encode_types(&mut self, types: impl Iterator<Item = BuiltinType>) -> Option<Vec<FileType>> {
+        types.map(|ty| self.encode_type(ty)).collect()
+    }
+
+    fn encode_type(&mut self, ty: BuiltinType) -> Option<FileType> {
+        let ty = match ty {
+            BuiltinType::Unit => FileType::Unit,
+            BuiltinType::Bool => FileType::Bool,
+            BuiltinType::Char => FileType::Char,
+            BuiltinType::UInt8 => FileType::UInt8,
+            BuiltinType::Int32 => FileType::Int32,
+            BuiltinType::Int64 => FileType::Int64,
+            BuiltinType::Float32 => FileType::Float32,
+            BuiltinType::Float64 => FileType::Float64,
+            BuiltinType::Class(cls_id, list_id) => {
+                let cls_id = usize::from(cls_id) as u32;
+                self.decl(DeclKind::Class, cls_id);
+                let type_params = self.vm.lists.lock().get(list_id);
+                FileType::Class(cls_id, self.encode_types(type_params.iter())?)
+            }
+            BuiltinType::Struct(struct_id, list_id) => {
+                let struct_id = struct_id.to_usize() as u32;
+                self.decl(DeclKind::Struct, struct_id);
+                let type_params = self.vm.lists.lock().get(list_id);
+                FileType::Struct(struct_id, self.encode_types(type_params.iter())?)
+            }
+            BuiltinType::Enum(enum_id, list_id) => {
+                let enum_id = enum_id.to_usize() as u32;
+                self.decl(DeclKind::Enum, enum_id);
+                let type_params = self.vm.lists.lock().get(list_id);
+                FileType::Enum(enum_id, self.encode_types(type_params.iter())?)
+            }
+            BuiltinType::Tuple(tuple_id) => {
+                let subtypes = self.vm.tuples.lock().get(tuple_id);
+                FileType::Tuple(self.encode_types(subtypes.iter().cloned())?)
+            }
+            BuiltinType::Trait(trait_id) => {
+                let trait_id = trait_id.to_usize() as u32;
+                self.decl(DeclKind::Trait, trait_id);
+                FileType::Trait(trait_id)
+            }
+            _ => return None,
+        };
+
+        Some(ty)
+    }
+
+    fn decl(&mut self, kind: DeclKind, id: u32) {
+        if self.decls.insert((kind, id)) {
+            let name = decl_name(self.vm, kind, id).expect("declaration not found");
+            self.file.decls.push((kind, id, name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::cmd::Args;
+    use crate::test;
+    use std::env;
+
+    const PROGRAM: &str = "
+        class Foo(let a: Int32)
+        struct Bar { x: Int32, y: Int64 }
+        var counter: Int32;
+        fun main() {
+            let foo = Foo(1);
+            let bar = Bar(2, 3L);
+            let pair = (foo.a, bar.y);
+            counter = inc(pair.0);
+        }
+        fun inc(x: Int32) -> Int32 { x + 1 }
+    ";
+
+    fn emit_file(code: &'static str) -> BytecodeFile {
+        let data = test::parse(code, |vm| {
+            let files = vm.files.len();
+            file::write(&emit(vm, files - 1..files))
+        });
+        file::read(&data).expect("invalid bytecode file")
+    }
+
+    #[test]
+    fn load_cached_bytecode() {
+        let bytecode_file = emit_file(PROGRAM);
+        // stdlib functions are not part of the file
+        assert!(bytecode_file.function(0).is_none());
+
+        test::parse_with_bytecode_file(PROGRAM, bytecode_file, |vm| {
+            let main_id = vm.fct_by_name("main").unwrap();
+            let inc_id = vm.fct_by_name("inc").unwrap();
+            assert!(vm.has_cached_bytecode(main_id));
+            assert!(vm.has_cached_bytecode(inc_id));
+
+            // bodies of cached functions are not type checked
+            let main = vm.fcts.idx(main_id);
+            assert!(main.read().src().read().map_calls.iter().next().is_none());
+
+            // the call to `inc` refers to a FctDef of this VM
+            assert!(vm
+                .fct_defs
+                .iter()
+                .any(|fct_def| fct_def.read().fct_id == inc_id));
+            assert!(vm.cached_bytecode(main_id).is_some());
+        });
+    }
+
+    #[test]
+    fn drop_cache_for_changed_source() {
+        let bytecode_file = emit_file(PROGRAM);
+
+        test::parse_with_bytecode_file("fun main() {}", bytecode_file, |vm| {
+            assert!(vm.bytecode_cache.is_none());
+            let main_id = vm.fct_by_name("main").unwrap();
+            assert!(vm.cached_bytecode(main_id).is_none());
+        });
+    }
+
+    #[test]
+    fn drop_cache_for_changed_decls() {
+        let mut bytecode_file = emit_file(PROGRAM);
+
+        for decl in &mut bytecode_file.decls {
+            if decl.0 == DeclKind::Class {
+                decl.2 = "Baz".into();
+            }
+        }
+
+        test::parse_with_bytecode_file(PROGRAM, bytecode_file, |vm| {
+            assert!(vm.bytecode_cache.is_none());
+            let main = vm.fcts.idx(vm.fct_by_name("main").unwrap());
+            assert!(main.read().src().read().map_calls.iter().next().is_some());
+        });
+    }
+
+    #[test]
+    fn load_stdlib_snapshot() {
+        let data = test::parse("", |vm| {
+            let stdlib_files = vm.files.len() - 1;
+            file::write(&emit_stdlib_snapshot(vm, stdlib_files))
+        });
+        let snapshot = file::read(&data).expect("invalid bytecode file");
+        let functions = snapshot.functions.len();
+        assert!(functions > 0);
+
+        // the ids of the stdlib functions are different with this program,
+        // the snapshot can be used nonetheless
+        test::parse_with_stdlib_snapshot(PROGRAM, snapshot, |vm| {
+            let stdlib_files = vm.files.len() - 1;
+            let cached = vm
+                .fcts
+                .iter()
+                .filter(|fct| vm.cached_bytecode(fct.read().id).is_some())
+                .inspect(|fct| assert!(fct.read().file.to_usize() < stdlib_files))
+                .count();
+            assert_eq!(functions, cached);
+
+            let main_id = vm.fct_by_name("main").unwrap();
+            assert!(!vm.has_cached_bytecode(main_id));
+        });
+    }
+
+    #[test]
+    fn key_depends_on_bytecode_args() {
+        let default_key = test::parse(PROGRAM, |vm| key(vm, vm.files.len()));
+
+        let mut args: Args = Default::default();
+        args.flag_clear_regs = true;
+        let clear_regs_key = test::parse_with_args(PROGRAM, args, |vm| key(vm, vm.files.len()));
+
+        assert_ne!(default_key, clear_regs_key);
+    }
+
+    #[test]
+    fn cache_path() {
+        let dir = env::temp_dir().join("dora-cache-path");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.dora");
+        fs::write(&file, "fun main() {}").unwrap();
+
+        assert_eq!(path(file.to_str().unwrap()), Some(dir.join("main.dorabc")));
+        assert_eq!(
+            path(dir.to_str().unwrap()),
+            Some(dir.join("dora-cache-path.dorabc"))
+        );
+        assert_eq!(path(dir.join("missing.dora").to_str().unwrap()), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,12 @@
 use std::fmt;
 
 use crate::mem::ptr_width;
+use crate::semck::specialize::specialize_struct_id_params;
 use crate::ty::{BuiltinType, MachineMode};
-use crate::vm::{get_vm, TupleId};
+use crate::vm::{get_vm, ClassDefId, EnumLayout, StructDefId, TupleId, VM};
 use dora_parser::lexer::position::Position;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BytecodeOffset(pub u32);
 
 impl BytecodeOffset {
@@ -29,6 +30,7 @@ pub enum BytecodeTypeKind {
     Float64,
     Ptr,
     Tuple,
+    Struct,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -42,6 +44,7 @@ pub enum BytecodeType {
     Float64,
     Ptr,
     Tuple(TupleId),
+    Struct(StructDefId),
 }
 
 impl BytecodeType {
@@ -59,6 +62,13 @@ impl BytecodeType {
                 let vm = get_vm();
                 vm.tuples.lock().get_tuple(tuple_id).size()
             }
+            BytecodeType::Struct(struct_def_id) => {
+                let vm = get_vm();
+                let struct_def = vm.struct_defs.idx(struct_def_id);
+                let struct_def = struct_def.lock();
+
+                struct_def.size
+            }
         }
     }
 
@@ -73,6 +83,7 @@ impl BytecodeType {
             BytecodeType::Float64 => BytecodeTypeKind::Float64,
             BytecodeType::Ptr => BytecodeTypeKind::Ptr,
             BytecodeType::Tuple(_) => BytecodeTypeKind::Tuple,
+            BytecodeType::Struct(_) => BytecodeTypeKind::Struct,
         }
     }
 
@@ -86,7 +97,7 @@ impl BytecodeType {
             BytecodeType::Float32 => MachineMode::Float32,
             BytecodeType::Float64 => MachineMode::Float64,
             BytecodeType::Ptr => MachineMode::Ptr,
-            BytecodeType::Tuple(_) => unreachable!(),
+            BytecodeType::Tuple(_) | BytecodeType::Struct(_) => unreachable!(),
         }
     }
 
@@ -103,10 +114,24 @@ impl BytecodeType {
             _ => None,
         }
     }
+
+    pub fn struct_def_id(&self) -> Option<StructDefId> {
+        match *self {
+            BytecodeType::Struct(struct_def_id) => Some(struct_def_id),
+            _ => None,
+        }
+    }
+
+    pub fn is_tuple_or_struct(&self) -> bool {
+        match self {
+            BytecodeType::Tuple(_) | BytecodeType::Struct(_) => true,
+            _ => false,
+        }
+    }
 }
 
-impl From<BuiltinType> for BytecodeType {
-    fn from(ty: BuiltinType) -> Self {
+impl BytecodeType {
+    pub fn from_ty(vm: &VM, ty: BuiltinType) -> BytecodeType {
         match ty {
             BuiltinType::Bool => BytecodeType::Bool,
             BuiltinType::UInt8 => BytecodeType::UInt8,
@@ -116,8 +141,17 @@ impl From<BuiltinType> for BytecodeType {
             BuiltinType::Float32 => BytecodeType::Float32,
             BuiltinType::Float64 => BytecodeType::Float64,
             BuiltinType::Class(_, _) => BytecodeType::Ptr,
-            BuiltinType::Enum(_, _) => BytecodeType::Int32,
+            BuiltinType::Lambda(_) | BuiltinType::Trait(_) | BuiltinType::Ptr => BytecodeType::Ptr,
+            BuiltinType::Enum(enum_id, _) => match vm.enums[enum_id].read().layout() {
+                EnumLayout::Int => BytecodeType::Int32,
+                EnumLayout::Tagged => BytecodeType::Ptr,
+            },
             BuiltinType::Tuple(tuple_id) => BytecodeType::Tuple(tuple_id),
+            BuiltinType::Struct(struct_id, list_id) => {
+                let type_params = vm.lists.lock().get(list_id);
+                let struct_def_id = specialize_struct_id_params(vm, struct_id, type_params);
+                BytecodeType::Struct(struct_def_id)
+            }
             _ => panic!("BuiltinType {:?} cannot converted to BytecodeType", ty),
         }
     }
@@ -135,6 +169,13 @@ impl From<BytecodeType> for BuiltinType {
             BytecodeType::Float64 => BuiltinType::Float64,
             BytecodeType::Ptr => BuiltinType::Ptr,
             BytecodeType::Tuple(tuple_id) => BuiltinType::Tuple(tuple_id),
+            BytecodeType::Struct(struct_def_id) => {
+                let vm = get_vm();
+                let struct_def = vm.struct_defs.idx(struct_def_id);
+                let struct_def = struct_def.lock();
+                let list_id = vm.lists.lock().insert(struct_def.type_params.clone());
+                BuiltinType::Struct(struct_def.struct_id, list_id)
+            }
         }
     }
 }
@@ -242,10 +283,13 @@ pub enum BytecodeOpcode {
     MovFloat64,
     MovPtr,
     MovTuple,
+    MovStruct,
 
     LoadTupleElement,
     StoreTupleElement,
 
+    LoadStructField,
+
     LoadField,
     StoreField,
 
@@ -342,12 +386,20 @@ pub enum BytecodeOpcode {
     InvokeVirtualVoid,
     InvokeVirtual,
 
+    InvokeTraitVoid,
+    InvokeTrait,
+
     InvokeStaticVoid,
     InvokeStatic,
 
+    InvokeLambdaVoid,
+    InvokeLambda,
+
     NewObject,
     NewArray,
     NewTuple,
+    NewStruct,
+    NewLambda,
 
     NilCheck,
 
@@ -363,6 +415,7 @@ pub enum BytecodeOpcode {
     LoadArrayFloat64,
     LoadArrayPtr,
     LoadArrayTuple,
+    LoadArrayStruct,
 
     StoreArrayBool,
     StoreArrayUInt8,
@@ -373,9 +426,12 @@ pub enum BytecodeOpcode {
     StoreArrayFloat64,
     StoreArrayPtr,
     StoreArrayTuple,
+    StoreArrayStruct,
 
     RetVoid,
     Ret,
+
+    Throw,
 }
 
 impl BytecodeOpcode {
@@ -392,10 +448,15 @@ impl BytecodeOpcode {
             | BytecodeOpcode::InvokeDirect
             | BytecodeOpcode::InvokeVirtualVoid
             | BytecodeOpcode::InvokeVirtual
+            | BytecodeOpcode::InvokeTraitVoid
+            | BytecodeOpcode::InvokeTrait
             | BytecodeOpcode::InvokeStaticVoid
             | BytecodeOpcode::InvokeStatic
+            | BytecodeOpcode::InvokeLambdaVoid
+            | BytecodeOpcode::InvokeLambda
             | BytecodeOpcode::NewObject
             | BytecodeOpcode::NewArray
+            | BytecodeOpcode::NewLambda
             | BytecodeOpcode::NilCheck
             | BytecodeOpcode::ArrayLength
             | BytecodeOpcode::ArrayBoundCheck
@@ -408,6 +469,7 @@ impl BytecodeOpcode {
             | BytecodeOpcode::LoadArrayFloat64
             | BytecodeOpcode::LoadArrayPtr
             | BytecodeOpcode::LoadArrayTuple
+            | BytecodeOpcode::LoadArrayStruct
             | BytecodeOpcode::StoreArrayBool
             | BytecodeOpcode::StoreArrayUInt8
             | BytecodeOpcode::StoreArrayChar
@@ -417,7 +479,9 @@ impl BytecodeOpcode {
             | BytecodeOpcode::StoreArrayFloat64
             | BytecodeOpcode::StoreArrayPtr
             | BytecodeOpcode::StoreArrayTuple
-            | BytecodeOpcode::Assert => true,
+            | BytecodeOpcode::StoreArrayStruct
+            | BytecodeOpcode::Assert
+            | BytecodeOpcode::Throw => true,
             _ => false,
         }
     }
@@ -454,12 +518,15 @@ impl fmt::Display for Register {
     }
 }
 
+#[derive(Clone)]
 pub struct BytecodeFunction {
     code: Vec<u8>,
     registers: Vec<BytecodeType>,
     const_pool: Vec<ConstPoolEntry>,
     arguments: u32,
     positions: Vec<(u32, Position)>,
+    exception_handlers: Vec<ExceptionHandler>,
+    relocations: Vec<(BytecodeOffset, RelocationKind)>,
 }
 
 impl BytecodeFunction {
@@ -469,6 +536,8 @@ impl BytecodeFunction {
         registers: Vec<BytecodeType>,
         arguments: u32,
         positions: Vec<(u32, Position)>,
+        exception_handlers: Vec<ExceptionHandler>,
+        relocations: Vec<(BytecodeOffset, RelocationKind)>,
     ) -> BytecodeFunction {
         BytecodeFunction {
             code,
@@ -476,12 +545,19 @@ impl BytecodeFunction {
             registers,
             arguments,
             positions,
+            exception_handlers,
+            relocations,
         }
     }
     pub fn code(&self) -> &[u8] {
         &self.code
     }
 
+    // handlers are ordered from innermost to outermost
+    pub fn exception_handlers(&self) -> &[ExceptionHandler] {
+        &self.exception_handlers
+    }
+
     pub fn registers(&self) -> &[BytecodeType] {
         &self.registers
     }
@@ -514,6 +590,95 @@ impl BytecodeFunction {
         };
         self.positions[index].1
     }
+
+    // operands holding ids that are only valid within the VM that generated
+    // the bytecode, only recorded for relocatable bytecode
+    pub fn relocations(&self) -> &[(BytecodeOffset, RelocationKind)] {
+        &self.relocations
+    }
+
+    // replaces all ids in code, register types and exception handlers,
+    // fails as soon as `map` finds no replacement for an id
+    pub fn relocate<F>(&self, mut map: F) -> Option<BytecodeFunction>
+    where
+        F: FnMut(RelocationKind, u32) -> Option<u32>,
+    {
+        let mut code = self.code.clone();
+
+        for &(offset, kind) in &self.relocations {
+            let offset = offset.to_usize();
+            let mut value = [0; 4];
+            value.copy_from_slice(&code[offset..offset + 4]);
+            let value = map(kind, u32::from_le_bytes(value))?;
+            code[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let mut registers = Vec::with_capacity(self.registers.len());
+
+        for &ty in &self.registers {
+            let ty = match ty {
+                BytecodeType::Tuple(tuple_id) => {
+                    let tuple_id = map(RelocationKind::Tuple, tuple_id.to_usize() as u32)?;
+                    BytecodeType::Tuple(tuple_id.into())
+                }
+                BytecodeType::Struct(struct_def_id) => {
+                    let struct_def_id =
+                        map(RelocationKind::StructDef, struct_def_id.to_usize() as u32)?;
+                    BytecodeType::Struct((struct_def_id as usize).into())
+                }
+                _ => ty,
+            };
+
+            registers.push(ty);
+        }
+
+        let mut exception_handlers = Vec::with_capacity(self.exception_handlers.len());
+
+        for handler in &self.exception_handlers {
+            let catch_type = match handler.catch_type {
+                Some(cls_def_id) => {
+                    let cls_def_id = map(RelocationKind::ClassDef, cls_def_id.to_usize() as u32)?;
+                    Some((cls_def_id as usize).into())
+                }
+                None => None,
+            };
+
+            exception_handlers.push(ExceptionHandler {
+                catch_type,
+                ..*handler
+            });
+        }
+
+        Some(BytecodeFunction {
+            code,
+            registers,
+            const_pool: self.const_pool.clone(),
+            arguments: self.arguments,
+            positions: self.positions.clone(),
+            exception_handlers,
+            relocations: self.relocations.clone(),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+pub enum RelocationKind {
+    FctDef,
+    ClassDef,
+    Tuple,
+    StructDef,
+    Global,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionHandler {
+    pub try_start: BytecodeOffset,
+    pub try_end: BytecodeOffset,
+    pub catch: BytecodeOffset,
+    // None catches all exceptions, used for defer blocks
+    pub catch_type: Option<ClassDefId>,
+    // register the exception is stored into
+    pub register: Register,
 }
 
 #[derive(FromPrimitive, ToPrimitive)]
@@ -526,6 +691,7 @@ pub enum ConstPoolOpcode {
     Char,
 }
 
+#[derive(Clone)]
 pub enum ConstPoolEntry {
     String(String),
     Float32(f32),
@@ -3,7 +3,7 @@ use std::io;
 use crate::bytecode::{
     read, BytecodeFunction, BytecodeOffset, BytecodeVisitor, ConstPoolEntry, ConstPoolIdx, Register,
 };
-use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, TupleId};
+use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, StructDefId, StructFieldId, TupleId};
 
 pub fn dump(bc: &BytecodeFunction) {
     let mut stdout = io::stdout();
@@ -38,6 +38,20 @@ pub fn dump(bc: &BytecodeFunction) {
     for (bc_offset, pos) in bc.positions().iter() {
         println!(" offset {} => {}", bc_offset, pos);
     }
+    if !bc.exception_handlers().is_empty() {
+        println!();
+        println!("Exception Handlers:");
+        for handler in bc.exception_handlers() {
+            println!(
+                " {}-{} => {} catch {:?} into {}",
+                handler.try_start.to_u32(),
+                handler.try_end.to_u32(),
+                handler.catch.to_u32(),
+                handler.catch_type,
+                handler.register
+            );
+        }
+    }
     println!();
 }
 
@@ -81,6 +95,37 @@ impl<'a> BytecodeDumper<'a> {
             .expect("write! failed");
     }
 
+    fn emit_reg2_struct(
+        &mut self,
+        name: &str,
+        r1: Register,
+        r2: Register,
+        struct_def_id: StructDefId,
+    ) {
+        self.emit_start(name);
+        writeln!(self.w, " {}, {}, {}", r1, r2, struct_def_id.to_usize()).expect("write! failed");
+    }
+
+    fn emit_struct_load(
+        &mut self,
+        name: &str,
+        r1: Register,
+        r2: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        self.emit_start(name);
+        writeln!(
+            self.w,
+            " {}, {}, {}, {}",
+            r1,
+            r2,
+            struct_def_id.to_usize(),
+            field_id.to_usize()
+        )
+        .expect("write! failed");
+    }
+
     fn emit_reg2_cls(&mut self, name: &str, r1: Register, r2: Register, cls_id: ClassDefId) {
         self.emit_start(name);
         writeln!(self.w, " {}, {}, {}", r1, r2, cls_id.to_usize()).expect("write! failed");
@@ -167,6 +212,11 @@ impl<'a> BytecodeDumper<'a> {
         writeln!(self.w, " {}, {}", r1, tuple_id.to_usize()).expect("write! failed");
     }
 
+    fn emit_new_struct(&mut self, name: &str, r1: Register, struct_def_id: StructDefId) {
+        self.emit_start(name);
+        writeln!(self.w, " {}, {}", r1, struct_def_id.to_usize()).expect("write! failed");
+    }
+
     fn emit_start(&mut self, name: &str) {
         write!(self.w, "{:3}: {}", self.pos.to_usize(), name).expect("write! failed");
     }
@@ -439,6 +489,20 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
         self.emit_tuple_load("LoadTupleElement", dest, src, tuple_id, element);
     }
 
+    fn visit_mov_struct(&mut self, dest: Register, src: Register, struct_def_id: StructDefId) {
+        self.emit_reg2_struct("MovStruct", dest, src, struct_def_id);
+    }
+
+    fn visit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        self.emit_struct_load("LoadStructField", dest, src, struct_def_id, field_id);
+    }
+
     fn visit_load_field(&mut self, dest: Register, obj: Register, cls: ClassDefId, field: FieldId) {
         self.emit_field("LoadField", dest, obj, cls, field);
     }
@@ -685,6 +749,13 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
         self.emit_fct("InvokeVirtual", dest, fctdef);
     }
 
+    fn visit_invoke_trait_void(&mut self, fctdef: FctDefId) {
+        self.emit_fct_void("InvokeTraitVoid", fctdef);
+    }
+    fn visit_invoke_trait(&mut self, dest: Register, fctdef: FctDefId) {
+        self.emit_fct("InvokeTrait", dest, fctdef);
+    }
+
     fn visit_invoke_static_void(&mut self, fctdef: FctDefId) {
         self.emit_fct_void("InvokeStaticVoid", fctdef);
     }
@@ -692,6 +763,13 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
         self.emit_fct("InvokeStatic", dest, fctdef);
     }
 
+    fn visit_invoke_lambda_void(&mut self) {
+        self.emit_inst("InvokeLambdaVoid");
+    }
+    fn visit_invoke_lambda(&mut self, dest: Register) {
+        self.emit_reg1("InvokeLambda", dest);
+    }
+
     fn visit_new_object(&mut self, dest: Register, cls: ClassDefId) {
         self.emit_new("NewObject", dest, cls);
     }
@@ -701,6 +779,12 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
     fn visit_new_tuple(&mut self, dest: Register, tuple_id: TupleId) {
         self.emit_new_tuple("NewTuple", dest, tuple_id);
     }
+    fn visit_new_struct(&mut self, dest: Register, struct_def_id: StructDefId) {
+        self.emit_new_struct("NewStruct", dest, struct_def_id);
+    }
+    fn visit_new_lambda(&mut self, dest: Register, fctdef: FctDefId) {
+        self.emit_fct("NewLambda", dest, fctdef);
+    }
 
     fn visit_nil_check(&mut self, obj: Register) {
         self.emit_reg1("NilCheck", obj);
@@ -733,6 +817,9 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
     fn visit_load_array_tuple(&mut self, dest: Register, arr: Register, idx: Register) {
         self.emit_reg3("LoadArrayTuple", dest, arr, idx);
     }
+    fn visit_load_array_struct(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.emit_reg3("LoadArrayStruct", dest, arr, idx);
+    }
 
     fn visit_store_array_bool(&mut self, src: Register, arr: Register, idx: Register) {
         self.emit_reg3("StoreArrayBool", src, arr, idx);
@@ -761,6 +848,9 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
     fn visit_store_array_tuple(&mut self, src: Register, arr: Register, idx: Register) {
         self.emit_reg3("StoreArrayTuple", src, arr, idx);
     }
+    fn visit_store_array_struct(&mut self, src: Register, arr: Register, idx: Register) {
+        self.emit_reg3("StoreArrayStruct", src, arr, idx);
+    }
 
     fn visit_array_length(&mut self, dest: Register, arr: Register) {
         self.emit_reg2("ArrayLength", dest, arr);
@@ -775,4 +865,8 @@ impl<'a> BytecodeVisitor for BytecodeDumper<'a> {
     fn visit_ret(&mut self, opnd: Register) {
         self.emit_reg1("Ret", opnd);
     }
+
+    fn visit_throw(&mut self, opnd: Register) {
+        self.emit_reg1("Throw", opnd);
+    }
 }
@@ -0,0 +1,844 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_traits::cast::FromPrimitive;
+
+use std::fmt;
+use std::io::Cursor;
+
+use crate::bytecode::{
+    BytecodeFunction, BytecodeOffset, BytecodeType, BytecodeTypeKind, ConstPoolEntry,
+    ConstPoolOpcode, ExceptionHandler, Register,
+};
+use crate::vm::ClassDefId;
+
+use dora_parser::lexer::position::Position;
+
+// on-disk container for bytecode functions, the layout is:
+//
+//   magic, version, key (hash of the sources, the compiler build and the
+//   arguments changing the bytecode),
+//   declarations (kind, id, name) referenced by the symbols,
+//   symbols for fct defs, class defs, tuples, struct defs and globals,
+//   functions (fct id, arguments, registers, const pool, positions,
+//   exception handlers, relocations, code)
+//
+// ids of declarations are stable for the same sources, they are stored
+// together with their names to detect a mismatch. ids that are allocated
+// while running (fct defs, class defs, tuples, struct defs) are replaced by
+// an index into the corresponding symbol table, see `RelocationKind`.
+//
+// all integers are stored in little endian.
+const MAGIC: &[u8; 4] = b"DRBC";
+pub const VERSION: u32 = 2;
+
+// catch_type of exception handlers that catch all exceptions
+const CATCH_ALL: u32 = u32::max_value();
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileType {
+    Unit,
+    Bool,
+    Char,
+    UInt8,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Class(u32, Vec<FileType>),
+    Struct(u32, Vec<FileType>),
+    Enum(u32, Vec<FileType>),
+    Tuple(Vec<FileType>),
+    Trait(u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+pub enum DeclKind {
+    Fct,
+    Class,
+    Struct,
+    Enum,
+    Trait,
+    Global,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileFctDef {
+    pub fct_id: u32,
+    pub cls_type_params: Vec<FileType>,
+    pub fct_type_params: Vec<FileType>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileClassDef {
+    Class(u32, Vec<FileType>),
+    // class holding the payload of an enum variant: enum, type params, variant
+    EnumVariant(u32, Vec<FileType>, u32),
+}
+
+pub struct BytecodeFile {
+    pub key: u64,
+    pub decls: Vec<(DeclKind, u32, String)>,
+    pub fct_defs: Vec<FileFctDef>,
+    pub class_defs: Vec<FileClassDef>,
+    pub tuples: Vec<Vec<FileType>>,
+    pub struct_defs: Vec<(u32, Vec<FileType>)>,
+    pub globals: Vec<u32>,
+    pub functions: Vec<(u32, BytecodeFunction)>,
+}
+
+impl BytecodeFile {
+    pub fn new(key: u64) -> BytecodeFile {
+        BytecodeFile {
+            key,
+            decls: Vec::new(),
+            fct_defs: Vec::new(),
+            class_defs: Vec::new(),
+            tuples: Vec::new(),
+            struct_defs: Vec::new(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn function(&self, fct_id: u32) -> Option<&BytecodeFunction> {
+        self.functions
+            .iter()
+            .find(|&&(id, _)| id == fct_id)
+            .map(|(_, fct)| fct)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BytecodeFileError {
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    UnexpectedEnd,
+    InvalidData(&'static str),
+}
+
+impl fmt::Display for BytecodeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BytecodeFileError::InvalidMagic => write!(f, "not a bytecode file"),
+            BytecodeFileError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bytecode file version {} (expected {})",
+                version, VERSION
+            ),
+            BytecodeFileError::UnexpectedEnd => write!(f, "unexpected end of bytecode file"),
+            BytecodeFileError::InvalidData(what) => write!(f, "invalid {} in bytecode file", what),
+        }
+    }
+}
+
+// FNV-1a, used to detect whether the cached bytecode is still up-to-date
+pub fn hash_strings<'a>(strings: impl Iterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for string in strings {
+        for byte in string.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    hash
+}
+
+pub fn write(file: &BytecodeFile) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    buffer.extend_from_slice(MAGIC);
+    buffer.write_u32::<LittleEndian>(VERSION).unwrap();
+    buffer.write_u64::<LittleEndian>(file.key).unwrap();
+
+    write_u32(&mut buffer, file.decls.len());
+
+    for (kind, id, name) in &file.decls {
+        buffer.push(*kind as u8);
+        buffer.write_u32::<LittleEndian>(*id).unwrap();
+        write_string(&mut buffer, name);
+    }
+
+    write_u32(&mut buffer, file.fct_defs.len());
+
+    for fct_def in &file.fct_defs {
+        buffer.write_u32::<LittleEndian>(fct_def.fct_id).unwrap();
+        write_types(&mut buffer, &fct_def.cls_type_params);
+        write_types(&mut buffer, &fct_def.fct_type_params);
+    }
+
+    write_u32(&mut buffer, file.class_defs.len());
+
+    for class_def in &file.class_defs {
+        match class_def {
+            FileClassDef::Class(cls_id, type_params) => {
+                buffer.push(CLASS_DEF_CLASS);
+                buffer.write_u32::<LittleEndian>(*cls_id).unwrap();
+                write_types(&mut buffer, type_params);
+            }
+            FileClassDef::EnumVariant(enum_id, type_params, variant) => {
+                buffer.push(CLASS_DEF_ENUM_VARIANT);
+                buffer.write_u32::<LittleEndian>(*enum_id).unwrap();
+                write_types(&mut buffer, type_params);
+                buffer.write_u32::<LittleEndian>(*variant).unwrap();
+            }
+        }
+    }
+
+    write_u32(&mut buffer, file.tuples.len());
+
+    for subtypes in &file.tuples {
+        write_types(&mut buffer, subtypes);
+    }
+
+    write_u32(&mut buffer, file.struct_defs.len());
+
+    for (struct_id, type_params) in &file.struct_defs {
+        buffer.write_u32::<LittleEndian>(*struct_id).unwrap();
+        write_types(&mut buffer, type_params);
+    }
+
+    write_u32(&mut buffer, file.globals.len());
+
+    for &global_id in &file.globals {
+        buffer.write_u32::<LittleEndian>(global_id).unwrap();
+    }
+
+    write_u32(&mut buffer, file.functions.len());
+
+    for (fct_id, fct) in &file.functions {
+        buffer.write_u32::<LittleEndian>(*fct_id).unwrap();
+        write_function(&mut buffer, fct);
+    }
+
+    buffer
+}
+
+const CLASS_DEF_CLASS: u8 = 0;
+const CLASS_DEF_ENUM_VARIANT: u8 = 1;
+
+fn write_function(buffer: &mut Vec<u8>, fct: &BytecodeFunction) {
+    buffer.write_u32::<LittleEndian>(fct.arguments()).unwrap();
+
+    write_u32(buffer, fct.registers().len());
+
+    for ty in fct.registers() {
+        buffer.push(ty.kind() as u8);
+
+        match ty {
+            BytecodeType::Tuple(tuple_id) => write_u32(buffer, tuple_id.to_usize()),
+            BytecodeType::Struct(struct_def_id) => write_u32(buffer, struct_def_id.to_usize()),
+            _ => {}
+        }
+    }
+
+    let mut const_pool = Vec::new();
+    encode_const_pool(fct.const_pool_entries(), &mut const_pool);
+    write_bytes(buffer, &const_pool);
+
+    write_u32(buffer, fct.positions().len());
+
+    for &(offset, pos) in fct.positions() {
+        buffer.write_u32::<LittleEndian>(offset).unwrap();
+        buffer.write_u32::<LittleEndian>(pos.line).unwrap();
+        buffer.write_u32::<LittleEndian>(pos.column).unwrap();
+    }
+
+    write_u32(buffer, fct.exception_handlers().len());
+
+    for handler in fct.exception_handlers() {
+        buffer
+            .write_u32::<LittleEndian>(handler.try_start.to_u32())
+            .unwrap();
+        buffer
+            .write_u32::<LittleEndian>(handler.try_end.to_u32())
+            .unwrap();
+        buffer
+            .write_u32::<LittleEndian>(handler.catch.to_u32())
+            .unwrap();
+        let catch_type = handler
+            .catch_type
+            .map(|cls_def_id| cls_def_id.to_usize() as u32)
+            .unwrap_or(CATCH_ALL);
+        buffer.write_u32::<LittleEndian>(catch_type).unwrap();
+        write_u32(buffer, handler.register.0);
+    }
+
+    write_u32(buffer, fct.relocations().len());
+
+    for &(offset, kind) in fct.relocations() {
+        buffer.write_u32::<LittleEndian>(offset.to_u32()).unwrap();
+        buffer.push(kind as u8);
+    }
+
+    write_bytes(buffer, fct.code());
+}
+
+fn write_types(buffer: &mut Vec<u8>, types: &[FileType]) {
+    write_u32(buffer, types.len());
+
+    for ty in types {
+        write_type(buffer, ty);
+    }
+}
+
+fn write_type(buffer: &mut Vec<u8>, ty: &FileType) {
+    match ty {
+        FileType::Unit => buffer.push(TYPE_UNIT),
+        FileType::Bool => buffer.push(TYPE_BOOL),
+        FileType::Char => buffer.push(TYPE_CHAR),
+        FileType::UInt8 => buffer.push(TYPE_UINT8),
+        FileType::Int32 => buffer.push(TYPE_INT32),
+        FileType::Int64 => buffer.push(TYPE_INT64),
+        FileType::Float32 => buffer.push(TYPE_FLOAT32),
+        FileType::Float64 => buffer.push(TYPE_FLOAT64),
+        FileType::Class(id, type_params) => {
+            buffer.push(TYPE_CLASS);
+            buffer.write_u32::<LittleEndian>(*id).unwrap();
+            write_types(buffer, type_params);
+        }
+        FileType::Struct(id, type_params) => {
+            buffer.push(TYPE_STRUCT);
+            buffer.write_u32::<LittleEndian>(*id).unwrap();
+            write_types(buffer, type_params);
+        }
+        FileType::Enum(id, type_params) => {
+            buffer.push(TYPE_ENUM);
+            buffer.write_u32::<LittleEndian>(*id).unwrap();
+            write_types(buffer, type_params);
+        }
+        FileType::Tuple(subtypes) => {
+            buffer.push(TYPE_TUPLE);
+            write_types(buffer, subtypes);
+        }
+        FileType::Trait(id) => {
+            buffer.push(TYPE_TRAIT);
+            buffer.write_u32::<LittleEndian>(*id).unwrap();
+        }
+    }
+}
+
+const TYPE_UNIT: u8 = 0;
+const TYPE_BOOL: u8 = 1;
+const TYPE_CHAR: u8 = 2;
+const TYPE_UINT8: u8 = 3;
+const TYPE_INT32: u8 = 4;
+const TYPE_INT64: u8 = 5;
+const TYPE_FLOAT32: u8 = 6;
+const TYPE_FLOAT64: u8 = 7;
+const TYPE_CLASS: u8 = 8;
+const TYPE_STRUCT: u8 = 9;
+const TYPE_ENUM: u8 = 10;
+const TYPE_TUPLE: u8 = 11;
+const TYPE_TRAIT: u8 = 12;
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_bytes(buffer, value.as_bytes());
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, value: &[u8]) {
+    write_u32(buffer, value.len());
+    buffer.extend_from_slice(value);
+}
+
+fn write_u32(buffer: &mut Vec<u8>, value: usize) {
+    assert!(value <= u32::max_value() as usize);
+    buffer.write_u32::<LittleEndian>(value as u32).unwrap();
+}
+
+// also used for passing the const pool to dora-boots, see `decodeConstPool`
+pub fn encode_const_pool(entries: &[ConstPoolEntry], buffer: &mut Vec<u8>) {
+    for const_entry in entries {
+        match const_entry {
+            ConstPoolEntry::String(ref value) => {
+                buffer.push(ConstPoolOpcode::String as u8);
+                buffer
+                    .write_u32::<LittleEndian>(value.len() as u32)
+                    .unwrap();
+                buffer.extend_from_slice(value.as_bytes());
+            }
+            &ConstPoolEntry::Float32(value) => {
+                buffer.push(ConstPoolOpcode::Float32 as u8);
+                buffer.write_u32::<LittleEndian>(value.to_bits()).unwrap();
+            }
+            &ConstPoolEntry::Float64(value) => {
+                buffer.push(ConstPoolOpcode::Float64 as u8);
+                buffer.write_u64::<LittleEndian>(value.to_bits()).unwrap();
+            }
+            &ConstPoolEntry::Int32(value) => {
+                buffer.push(ConstPoolOpcode::Int32 as u8);
+                buffer.write_u32::<LittleEndian>(value as u32).unwrap();
+            }
+            &ConstPoolEntry::Int64(value) => {
+                buffer.push(ConstPoolOpcode::Int64 as u8);
+                buffer.write_u64::<LittleEndian>(value as u64).unwrap();
+            }
+            &ConstPoolEntry::Char(value) => {
+                buffer.push(ConstPoolOpcode::Char as u8);
+                buffer.write_u32::<LittleEndian>(value as u32).unwrap();
+            }
+        }
+    }
+}
+
+pub fn decode_const_pool(data: &[u8]) -> Result<Vec<ConstPoolEntry>, BytecodeFileError> {
+    let mut reader = FileReader::new(data);
+    let mut entries = Vec::new();
+
+    while reader.more_bytes() {
+        let opcode = reader.read_u8()?;
+
+        let entry = match FromPrimitive::from_u8(opcode) {
+            Some(ConstPoolOpcode::String) => ConstPoolEntry::String(reader.read_string()?),
+            Some(ConstPoolOpcode::Float32) => {
+                ConstPoolEntry::Float32(f32::from_bits(reader.read_u32()?))
+            }
+            Some(ConstPoolOpcode::Float64) => {
+                ConstPoolEntry::Float64(f64::from_bits(reader.read_u64()?))
+            }
+            Some(ConstPoolOpcode::Int32) => ConstPoolEntry::Int32(reader.read_u32()? as i32),
+            Some(ConstPoolOpcode::Int64) => ConstPoolEntry::Int64(reader.read_u64()? as i64),
+            Some(ConstPoolOpcode::Char) => {
+                let value = std::char::from_u32(reader.read_u32()?)
+                    .ok_or(BytecodeFileError::InvalidData("char"))?;
+                ConstPoolEntry::Char(value)
+            }
+            None => return Err(BytecodeFileError::InvalidData("const pool opcode")),
+        };
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+pub fn read(data: &[u8]) -> Result<BytecodeFile, BytecodeFileError> {
+    let mut reader = FileReader::new(data);
+
+    if reader.read_slice(MAGIC.len())? != MAGIC {
+        return Err(BytecodeFileError::InvalidMagic);
+    }
+
+    let version = reader.read_u32()?;
+
+    if version != VERSION {
+        return Err(BytecodeFileError::UnsupportedVersion(version));
+    }
+
+    let mut file = BytecodeFile::new(reader.read_u64()?);
+
+    for _ in 0..reader.read_u32()? {
+        let kind = FromPrimitive::from_u8(reader.read_u8()?)
+            .ok_or(BytecodeFileError::InvalidData("declaration kind"))?;
+        let id = reader.read_u32()?;
+        let name = reader.read_string()?;
+        file.decls.push((kind, id, name));
+    }
+
+    for _ in 0..reader.read_u32()? {
+        let fct_id = reader.read_u32()?;
+        let cls_type_params = reader.read_types()?;
+        let fct_type_params = reader.read_types()?;
+        file.fct_defs.push(FileFctDef {
+            fct_id,
+            cls_type_params,
+            fct_type_params,
+        });
+    }
+
+    for _ in 0..reader.read_u32()? {
+        let class_def = match reader.read_u8()? {
+            CLASS_DEF_CLASS => {
+                let cls_id = reader.read_u32()?;
+                FileClassDef::Class(cls_id, reader.read_types()?)
+            }
+            CLASS_DEF_ENUM_VARIANT => {
+                let enum_id = reader.read_u32()?;
+                let type_params = reader.read_types()?;
+                FileClassDef::EnumVariant(enum_id, type_params, reader.read_u32()?)
+            }
+            _ => return Err(BytecodeFileError::InvalidData("class def")),
+        };
+        file.class_defs.push(class_def);
+    }
+
+    for _ in 0..reader.read_u32()? {
+        file.tuples.push(reader.read_types()?);
+    }
+
+    for _ in 0..reader.read_u32()? {
+        let struct_id = reader.read_u32()?;
+        file.struct_defs.push((struct_id, reader.read_types()?));
+    }
+
+    for _ in 0..reader.read_u32()? {
+        file.globals.push(reader.read_u32()?);
+    }
+
+    for _ in 0..reader.read_u32()? {
+        let fct_id = reader.read_u32()?;
+        let fct = reader.read_function()?;
+        file.functions.push((fct_id, fct));
+    }
+
+    if reader.more_bytes() {
+        return Err(BytecodeFileError::InvalidData("trailing data"));
+    }
+
+    Ok(file)
+}
+
+struct FileReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> FileReader<'a> {
+    fn new(data: &'a [u8]) -> FileReader<'a> {
+        FileReader {
+            cursor: Cursor::new(data),
+        }
+    }
+
+    fn more_bytes(&self) -> bool {
+        (self.cursor.position() as usize) < self.cursor.get_ref().len()
+    }
+
+    fn read_function(&mut self) -> Result<BytecodeFunction, BytecodeFileError> {
+        let arguments = self.read_u32()?;
+
+        let registers_len = self.read_u32()?;
+        let mut registers = Vec::new();
+
+        for _ in 0..registers_len {
+            registers.push(self.read_register_type()?);
+        }
+
+        let const_pool = decode_const_pool(&self.read_bytes()?)?;
+
+        let positions_len = self.read_u32()?;
+        let mut positions = Vec::new();
+
+        for _ in 0..positions_len {
+            let offset = self.read_u32()?;
+            let line = self.read_u32()?;
+            let column = self.read_u32()?;
+            positions.push((offset, Position::new(line, column)));
+        }
+
+        let handlers_len = self.read_u32()?;
+        let mut exception_handlers = Vec::new();
+
+        for _ in 0..handlers_len {
+            let try_start = BytecodeOffset(self.read_u32()?);
+            let try_end = BytecodeOffset(self.read_u32()?);
+            let catch = BytecodeOffset(self.read_u32()?);
+            let catch_type = match self.read_u32()? {
+                CATCH_ALL => None,
+                cls_def_id => Some(ClassDefId::from(cls_def_id as usize)),
+            };
+            let register = Register(self.read_u32()? as usize);
+
+            exception_handlers.push(ExceptionHandler {
+                try_start,
+                try_end,
+                catch,
+                catch_type,
+                register,
+            });
+        }
+
+        let relocations_len = self.read_u32()?;
+        let mut relocations = Vec::new();
+
+        for _ in 0..relocations_len {
+            let offset = BytecodeOffset(self.read_u32()?);
+            let kind = FromPrimitive::from_u8(self.read_u8()?)
+                .ok_or(BytecodeFileError::InvalidData("relocation kind"))?;
+            relocations.push((offset, kind));
+        }
+
+        let code = self.read_bytes()?;
+
+        if relocations
+            .iter()
+            .any(|(offset, _)| offset.to_usize() + 4 > code.len())
+        {
+            return Err(BytecodeFileError::InvalidData("relocation offset"));
+        }
+
+        Ok(BytecodeFunction::new(
+            code,
+            const_pool,
+            registers,
+            arguments,
+            positions,
+            exception_handlers,
+            relocations,
+        ))
+    }
+
+    fn read_types(&mut self) -> Result<Vec<FileType>, BytecodeFileError> {
+        let len = self.read_u32()?;
+        let mut types = Vec::new();
+
+        for _ in 0..len {
+            types.push(self.read_type()?);
+        }
+
+        Ok(types)
+    }
+
+    fn read_type(&mut self) -> Result<FileType, BytecodeFileError> {
+        let ty = match self.read_u8()? {
+            TYPE_UNIT => FileType::Unit,
+            TYPE_BOOL => FileType::Bool,
+            TYPE_CHAR => FileType::Char,
+            TYPE_UINT8 => FileType::UInt8,
+            TYPE_INT32 => FileType::Int32,
+            TYPE_INT64 => FileType::Int64,
+            TYPE_FLOAT32 => FileType::Float32,
+            TYPE_FLOAT64 => FileType::Float64,
+            TYPE_CLASS => {
+                let id = self.read_u32()?;
+                FileType::Class(id, self.read_types()?)
+            }
+            TYPE_STRUCT => {
+                let id = self.read_u32()?;
+                FileType::Struct(id, self.read_types()?)
+            }
+            TYPE_ENUM => {
+                let id = self.read_u32()?;
+                FileType::Enum(id, self.read_types()?)
+            }
+            TYPE_TUPLE => FileType::Tuple(self.read_types()?),
+            TYPE_TRAIT => FileType::Trait(self.read_u32()?),
+            _ => return Err(BytecodeFileError::InvalidData("type")),
+        };
+
+        Ok(ty)
+    }
+
+    fn read_register_type(&mut self) -> Result<BytecodeType, BytecodeFileError> {
+        let kind = self.read_u8()?;
+
+        let ty = match kind {
+            kind if kind == BytecodeTypeKind::Bool as u8 => BytecodeType::Bool,
+            kind if kind == BytecodeTypeKind::UInt8 as u8 => BytecodeType::UInt8,
+            kind if kind == BytecodeTypeKind::Char as u8 => BytecodeType::Char,
+            kind if kind == BytecodeTypeKind::Int32 as u8 => BytecodeType::Int32,
+            kind if kind == BytecodeTypeKind::Int64 as u8 => BytecodeType::Int64,
+            kind if kind == BytecodeTypeKind::Float32 as u8 => BytecodeType::Float32,
+            kind if kind == BytecodeTypeKind::Float64 as u8 => BytecodeType::Float64,
+            kind if kind == BytecodeTypeKind::Ptr as u8 => BytecodeType::Ptr,
+            kind if kind == BytecodeTypeKind::Tuple as u8 => {
+                BytecodeType::Tuple(self.read_u32()?.into())
+            }
+            kind if kind == BytecodeTypeKind::Struct as u8 => {
+                BytecodeType::Struct((self.read_u32()? as usize).into())
+            }
+            _ => return Err(BytecodeFileError::InvalidData("register type")),
+        };
+
+        Ok(ty)
+    }
+
+    fn read_string(&mut self) -> Result<String, BytecodeFileError> {
+        let bytes = self.read_bytes()?;
+        String::from_utf8(bytes).map_err(|_| BytecodeFileError::InvalidData("string"))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, BytecodeFileError> {
+        let len = self.read_u32()? as usize;
+        Ok(self.read_slice(len)?.to_vec())
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], BytecodeFileError> {
+        let data: &'a [u8] = *self.cursor.get_ref();
+        let start = self.cursor.position() as usize;
+
+        if data.len() - start < len {
+            return Err(BytecodeFileError::UnexpectedEnd);
+        }
+
+        self.cursor.set_position((start + len) as u64);
+        Ok(&data[start..start + len])
+    }
+
+    fn read_u8(&mut self) -> Result<u8, BytecodeFileError> {
+        self.cursor
+            .read_u8()
+            .map_err(|_| BytecodeFileError::UnexpectedEnd)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, BytecodeFileError> {
+        self.cursor
+            .read_u32::<LittleEndian>()
+            .map_err(|_| BytecodeFileError::UnexpectedEnd)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, BytecodeFileError> {
+        self.cursor
+            .read_u64::<LittleEndian>()
+            .map_err(|_| BytecodeFileError::UnexpectedEnd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{BytecodeWriter, RelocationKind};
+
+    fn example_fct() -> BytecodeFunction {
+        let mut writer = BytecodeWriter::new();
+        writer.set_relocatable();
+        writer.set_arguments(1);
+        let r0 = writer.add_register(BytecodeType::Int32);
+        let r1 = writer.add_register(BytecodeType::Ptr);
+        let r2 = writer.add_register(BytecodeType::Tuple(0.into()));
+        writer.emit_const_int32(r0, 42);
+        writer.emit_const_string(r1, "hello".into());
+        writer.set_position(Position::new(2, 5));
+        writer.emit_div_int32(r0, r0, r0);
+        writer.emit_new_tuple(r2, 0.into());
+        writer.set_position(Position::new(3, 5));
+        writer.emit_invoke_static(r0, 0.into());
+        writer.emit_ret(r0);
+        writer.generate()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut file = BytecodeFile::new(hash_strings(["fun main() {}"].iter().cloned()));
+        file.decls.push((DeclKind::Fct, 7, "main".into()));
+        file.decls.push((DeclKind::Class, 3, "Foo".into()));
+        file.fct_defs.push(FileFctDef {
+            fct_id: 7,
+            cls_type_params: vec![FileType::Class(3, Vec::new())],
+            fct_type_params: vec![FileType::Tuple(vec![FileType::Int32, FileType::Bool])],
+        });
+        file.class_defs
+            .push(FileClassDef::Class(3, vec![FileType::Float64]));
+        file.class_defs.push(FileClassDef::EnumVariant(
+            1,
+            vec![FileType::Enum(2, Vec::new())],
+            4,
+        ));
+        file.tuples.push(vec![FileType::Char, FileType::Trait(5)]);
+        file.struct_defs
+            .push((6, vec![FileType::Struct(6, vec![FileType::UInt8])]));
+        file.globals.push(8);
+        file.functions.push((7, example_fct()));
+
+        let data = write(&file);
+        let read = read(&data).expect("valid bytecode file");
+
+        assert_eq!(file.key, read.key);
+        assert_eq!(file.decls, read.decls);
+        assert_eq!(file.fct_defs, read.fct_defs);
+        assert_eq!(file.class_defs, read.class_defs);
+        assert_eq!(file.tuples, read.tuples);
+        assert_eq!(file.struct_defs, read.struct_defs);
+        assert_eq!(file.globals, read.globals);
+
+        let expected = file.function(7).unwrap();
+        let fct = read.function(7).unwrap();
+        assert_eq!(expected.code(), fct.code());
+        assert_eq!(expected.registers(), fct.registers());
+        assert_eq!(1, fct.arguments());
+        assert_eq!(expected.positions(), fct.positions());
+        assert_eq!(expected.relocations(), fct.relocations());
+        assert_eq!(Some(42), fct.const_pool_entries()[0].to_int32());
+        assert_eq!(Some("hello"), fct.const_pool_entries()[1].to_string());
+    }
+
+    #[test]
+    fn relocate() {
+        let fct = example_fct();
+        assert_eq!(
+            vec![RelocationKind::Tuple, RelocationKind::FctDef],
+            fct.relocations()
+                .iter()
+                .map(|&(_, kind)| kind)
+                .collect::<Vec<_>>()
+        );
+
+        // ids are not restricted to a single byte after relocation
+        let relocated = fct
+            .relocate(|kind, id| match kind {
+                RelocationKind::Tuple => Some(id + 300),
+                RelocationKind::FctDef => Some(id + 1000),
+                _ => None,
+            })
+            .expect("all ids relocated");
+
+        assert_eq!(fct.code().len(), relocated.code().len());
+        assert_eq!(
+            BytecodeType::Tuple(300.into()),
+            relocated.register_type(Register(2))
+        );
+
+        let (offset, _) = relocated.relocations()[1];
+        let offset = offset.to_usize();
+        assert_eq!(
+            &1000u32.to_le_bytes(),
+            &relocated.code()[offset..offset + 4]
+        );
+
+        assert!(fct
+            .relocate(|kind, id| match kind {
+                RelocationKind::Tuple => Some(id),
+                _ => None,
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn roundtrip_const_pool() {
+        let entries = vec![
+            ConstPoolEntry::String("abc".into()),
+            ConstPoolEntry::Float32(1.5),
+            ConstPoolEntry::Float64(2.5),
+            ConstPoolEntry::Int32(-1),
+            ConstPoolEntry::Int64(-2),
+            ConstPoolEntry::Char('x'),
+        ];
+
+        let mut buffer = Vec::new();
+        encode_const_pool(&entries, &mut buffer);
+        assert_eq!(ConstPoolOpcode::String as u8, buffer[0]);
+
+        let decoded = decode_const_pool(&buffer).expect("valid const pool");
+        assert_eq!(Some("abc"), decoded[0].to_string());
+        assert_eq!(Some(1.5), decoded[1].to_float32());
+        assert_eq!(Some(2.5), decoded[2].to_float64());
+        assert_eq!(Some(-1), decoded[3].to_int32());
+        assert_eq!(Some(-2), decoded[4].to_int64());
+        assert_eq!(Some('x'), decoded[5].to_char());
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert_eq!(Some(BytecodeFileError::InvalidMagic), read(b"ABCD").err());
+        assert_eq!(Some(BytecodeFileError::UnexpectedEnd), read(b"DR").err());
+
+        let mut data = write(&BytecodeFile::new(0));
+        data[4] = 99;
+        assert_eq!(
+            Some(BytecodeFileError::UnsupportedVersion(99)),
+            read(&data).err()
+        );
+
+        let mut data = write(&BytecodeFile::new(0));
+        data.pop();
+        assert_eq!(Some(BytecodeFileError::UnexpectedEnd), read(&data).err());
+    }
+
+    #[test]
+    fn hash_strings_changes() {
+        let a = hash_strings(["fun main() {}"].iter().cloned());
+        let b = hash_strings(["fun main() { }"].iter().cloned());
+        let c = hash_strings(["fun main()", " {}"].iter().cloned());
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+}
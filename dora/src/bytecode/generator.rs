@@ -6,26 +6,59 @@ use dora_parser::ast::Stmt::*;
 use dora_parser::ast::*;
 
 use crate::bytecode::{BytecodeBuilder, BytecodeFunction, BytecodeType, Label, Register};
-use crate::semck::specialize::{specialize_class_ty, specialize_type};
+use crate::semck::specialize::{
+    specialize_box, specialize_class_ty, specialize_enum_id_params, specialize_lambda,
+    specialize_struct_id_params, specialize_type,
+};
 use crate::semck::{expr_always_returns, expr_block_always_returns};
 use crate::size::InstanceSize;
 use crate::ty::{BuiltinType, TypeList, TypeParamId};
 use crate::vm::{
-    CallType, ConstId, Fct, FctDef, FctDefId, FctId, FctKind, FctSrc, GlobalId, IdentType,
-    Intrinsic, TraitId, TupleId, VarId, VM,
+    enum_payload_field, CallType, ClassDefId, ConstId, EnumId, EnumLayout, Fct, FctDef, FctDefId,
+    FctId, FctKind, FctSrc, FieldId, GlobalId, IdentType, Intrinsic, StructFieldId, StructId,
+    TraitId, TupleId, VarId, ENUM_TAG_FIELD, VM,
 };
 
 pub struct LoopLabels {
     cond: Label,
     end: Label,
+    // number of active exception handlers outside of the loop
+    handlers: usize,
 }
 
 impl LoopLabels {
-    fn new(cond: Label, end: Label) -> LoopLabels {
-        LoopLabels { cond, end }
+    fn new(cond: Label, end: Label, handlers: usize) -> LoopLabels {
+        LoopLabels {
+            cond,
+            end,
+            handlers,
+        }
     }
 }
 
+#[derive(Clone)]
+struct ActiveHandler<'ast> {
+    // start of the currently open try range
+    start: Label,
+    kind: HandlerKind<'ast>,
+}
+
+#[derive(Clone)]
+enum HandlerKind<'ast> {
+    // catch label, exception class and register for each catch block
+    Catch(Vec<(Label, ClassDefId, Register)>),
+    // deferred block gets executed and the exception rethrown
+    Defer(&'ast Stmt, Label, Register),
+}
+
+struct LambdaContext {
+    // register with the context object passed as first argument
+    reg: Register,
+    cls_def_id: ClassDefId,
+    // field of each captured variable in the context object
+    fields: HashMap<VarId, FieldId>,
+}
+
 pub fn generate_fct<'ast>(
     vm: &VM<'ast>,
     id: FctId,
@@ -40,12 +73,45 @@ pub fn generate_fct<'ast>(
     generate(vm, &fct, &mut src, cls_type_params, fct_type_params)
 }
 
+// ids in relocatable bytecode are listed in `BytecodeFunction::relocations`,
+// used for writing the bytecode into a `.dorabc` file
+pub fn generate_relocatable_fct<'ast>(vm: &VM<'ast>, id: FctId) -> BytecodeFunction {
+    let fct = vm.fcts.idx(id);
+    let fct = fct.read();
+    let src = fct.src();
+    let src = src.read();
+
+    let mut gen = BytecodeBuilder::new(&vm.args);
+    gen.set_relocatable();
+
+    generate_with(vm, &fct, &src, &TypeList::empty(), &TypeList::empty(), gen)
+}
+
 pub fn generate<'ast>(
     vm: &VM<'ast>,
     fct: &Fct<'ast>,
     src: &FctSrc,
     cls_type_params: &TypeList,
     fct_type_params: &TypeList,
+) -> BytecodeFunction {
+    // the body of a function loaded from a `.dorabc` file wasn't type checked
+    if cls_type_params.len() == 0 && fct_type_params.len() == 0 {
+        if let Some(bytecode_fct) = vm.cached_bytecode(fct.id) {
+            return bytecode_fct;
+        }
+    }
+
+    let gen = BytecodeBuilder::new(&vm.args);
+    generate_with(vm, fct, src, cls_type_params, fct_type_params, gen)
+}
+
+fn generate_with<'ast>(
+    vm: &VM<'ast>,
+    fct: &Fct<'ast>,
+    src: &FctSrc,
+    cls_type_params: &TypeList,
+    fct_type_params: &TypeList,
+    gen: BytecodeBuilder,
 ) -> BytecodeFunction {
     let ast_bytecode_generator = AstBytecodeGen {
         vm,
@@ -56,9 +122,11 @@ pub fn generate<'ast>(
         cls_type_params,
         fct_type_params,
 
-        gen: BytecodeBuilder::new(&vm.args),
+        gen,
         loops: Vec::new(),
+        handlers: Vec::new(),
         var_registers: HashMap::new(),
+        lambda: None,
     };
     ast_bytecode_generator.generate()
 }
@@ -74,7 +142,9 @@ struct AstBytecodeGen<'a, 'ast: 'a> {
 
     gen: BytecodeBuilder,
     loops: Vec<LoopLabels>,
+    handlers: Vec<ActiveHandler<'ast>>,
     var_registers: HashMap<VarId, Register>,
+    lambda: Option<LambdaContext>,
 }
 
 impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
@@ -82,11 +152,15 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let mut arguments = 0;
         self.push_scope();
 
-        if self.fct.has_self() {
+        if self.fct.is_lambda() {
+            let reg = self.alloc_var(BytecodeType::Ptr);
+            self.lambda = Some(self.lambda_context(reg));
+            arguments += 1;
+        } else if self.fct.has_self() {
             let var_self = self.src.var_self();
             let var_ty = self.specialize_type(var_self.ty);
             let var_id = var_self.id;
-            let reg = self.alloc_var(var_ty.into());
+            let reg = self.alloc_var(BytecodeType::from_ty(self.vm, var_ty));
             self.var_registers.insert(var_id, reg);
             arguments += 1;
         }
@@ -98,7 +172,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             if ty.is_unit() {
                 // no register needed for unit
             } else {
-                let ty: BytecodeType = ty.into();
+                let ty = BytecodeType::from_ty(self.vm, ty);
                 let reg = self.alloc_var(ty);
                 self.var_registers.insert(var_id, reg);
                 arguments += 1;
@@ -126,14 +200,39 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
 
         if self.fct.return_type.is_unit() {
-            self.gen.emit_ret_void();
+            self.emit_ret_void();
         }
 
+        self.pop_handlers(0, false);
         self.pop_scope();
         self.gen.generate()
     }
 
-    fn visit_stmt(&mut self, stmt: &Stmt) {
+    fn lambda_context(&self, reg: Register) -> LambdaContext {
+        let fct_def_id = FctDef::with(
+            self.vm,
+            self.fct,
+            self.cls_type_params.clone(),
+            self.fct_type_params.clone(),
+        );
+        let cls_def_id = specialize_lambda(self.vm, self.src, fct_def_id);
+
+        let lambda = self.src.map_lambdas.get(self.ast.id).unwrap();
+        let fields = lambda
+            .captures
+            .iter()
+            .enumerate()
+            .map(|(idx, &var_id)| (var_id, idx.into()))
+            .collect();
+
+        LambdaContext {
+            reg,
+            cls_def_id,
+            fields,
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
         match *stmt {
             StmtReturn(ref ret) => self.visit_stmt_return(ret),
             StmtBreak(ref stmt) => self.visit_stmt_break(stmt),
@@ -142,10 +241,174 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             StmtVar(ref stmt) => self.visit_stmt_var(stmt),
             StmtWhile(ref stmt) => self.visit_stmt_while(stmt),
             StmtFor(ref stmt) => self.visit_stmt_for(stmt),
+            StmtThrow(ref stmt) => self.visit_stmt_throw(stmt),
+            StmtDo(ref stmt) => self.visit_stmt_do(stmt),
+            StmtDefer(ref stmt) => self.visit_stmt_defer(stmt),
+        }
+    }
+
+    fn visit_stmt_throw(&mut self, stmt: &'ast StmtThrowType) {
+        let exception_reg = self.visit_expr(&stmt.expr, DataDest::Alloc);
+        self.gen.emit_throw(exception_reg, stmt.pos);
+        self.free_if_temp(exception_reg);
+    }
+
+    fn visit_stmt_do(&mut self, stmt: &'ast StmtDoType) {
+        self.push_scope();
+        let end_lbl = self.gen.create_label();
+
+        let catches: Vec<(Label, ClassDefId, Register)> = stmt
+            .catch_blocks
+            .iter()
+            .map(|catch| {
+                let var_id = *self.src.map_vars.get(catch.id).unwrap();
+                let ty = self.var_ty(var_id);
+                let cls_def_id = specialize_class_ty(self.vm, ty);
+                let reg = self.alloc_var(BytecodeType::Ptr);
+
+                (self.gen.create_label(), cls_def_id, reg)
+            })
+            .collect();
+
+        let start = self.gen.define_label();
+        self.handlers.push(ActiveHandler {
+            start,
+            kind: HandlerKind::Catch(catches.clone()),
+        });
+
+        self.visit_stmt(&stmt.do_block);
+
+        let depth = self.handlers.len() - 1;
+        let handler = self.handlers.pop().unwrap();
+        self.close_handler(&handler, depth);
+        self.gen.emit_jump(end_lbl);
+
+        for (idx, (catch, &(lbl, _, reg))) in stmt.catch_blocks.iter().zip(&catches).enumerate() {
+            self.gen.bind_label(lbl);
+            self.push_scope();
+
+            let var_id = *self.src.map_vars.get(catch.id).unwrap();
+
+            if self.is_boxed(var_id) {
+                let ty = self.var_ty(var_id);
+                let box_def_id = specialize_box(self.vm, ty);
+                let box_reg = self.alloc_var(BytecodeType::Ptr);
+                self.gen.emit_new_object(box_reg, box_def_id, catch.pos);
+                self.gen
+                    .emit_store_field(reg, box_reg, box_def_id, 0.into(), catch.pos);
+                self.var_registers.insert(var_id, box_reg);
+            } else {
+                self.var_registers.insert(var_id, reg);
+            }
+
+            self.visit_stmt(&catch.block);
+            self.pop_scope();
+
+            if idx + 1 < catches.len() {
+                self.gen.emit_jump(end_lbl);
+            }
+        }
+
+        self.gen.bind_label(end_lbl);
+        self.pop_scope();
+    }
+
+    fn visit_stmt_defer(&mut self, stmt: &'ast StmtDeferType) {
+        let exception_reg = self.alloc_var(BytecodeType::Ptr);
+        let catch_lbl = self.gen.create_label();
+        let start = self.gen.define_label();
+
+        self.handlers.push(ActiveHandler {
+            start,
+            kind: HandlerKind::Defer(&stmt.block, catch_lbl, exception_reg),
+        });
+    }
+
+    fn close_handler(&mut self, handler: &ActiveHandler<'ast>, depth: usize) {
+        let end = self.gen.define_label();
+
+        match handler.kind {
+            HandlerKind::Catch(ref catches) => {
+                for &(lbl, cls_def_id, reg) in catches {
+                    self.gen.add_exception_handler(
+                        handler.start,
+                        end,
+                        lbl,
+                        Some(cls_def_id),
+                        reg,
+                        depth,
+                    );
+                }
+            }
+
+            HandlerKind::Defer(_, lbl, reg) => {
+                self.gen
+                    .add_exception_handler(handler.start, end, lbl, None, reg, depth);
+            }
+        }
+    }
+
+    // Closes all handlers above `boundary` before leaving them through
+    // return, break or continue and emits their deferred blocks. The
+    // returned handlers need to be reopened after the jump.
+    fn exit_handlers(&mut self, boundary: usize) -> Vec<ActiveHandler<'ast>> {
+        let handlers = self.handlers.split_off(boundary);
+
+        for (idx, handler) in handlers.iter().enumerate().rev() {
+            // deferred blocks only run with the outer handlers active
+            self.handlers.extend_from_slice(&handlers[..idx]);
+            self.close_handler(handler, boundary + idx);
+
+            if let HandlerKind::Defer(block, _, _) = handler.kind {
+                self.visit_stmt(block);
+            }
+
+            self.handlers.truncate(boundary);
+        }
+
+        handlers
+    }
+
+    fn reopen_handlers(&mut self, handlers: Vec<ActiveHandler<'ast>>) {
+        for mut handler in handlers {
+            handler.start = self.gen.define_label();
+            self.handlers.push(handler);
         }
     }
 
-    fn visit_stmt_for(&mut self, stmt: &StmtForType) {
+    // Leaves all deferred blocks above `boundary` at the end of their block:
+    // the normal path runs the block inline, the exceptional path runs the
+    // block and rethrows the exception. Without fallthrough all normal paths
+    // already left the function.
+    fn pop_handlers(&mut self, boundary: usize, fallthrough: bool) {
+        while self.handlers.len() > boundary {
+            let depth = self.handlers.len() - 1;
+            let handler = self.handlers.pop().unwrap();
+            self.close_handler(&handler, depth);
+
+            let (block, catch_lbl, exception_reg) = match handler.kind {
+                HandlerKind::Defer(block, lbl, reg) => (block, lbl, reg),
+                HandlerKind::Catch(_) => unreachable!(),
+            };
+
+            let skip_lbl = self.gen.create_label();
+
+            if fallthrough {
+                self.visit_stmt(block);
+                self.gen.emit_jump(skip_lbl);
+            }
+
+            self.gen.bind_label(catch_lbl);
+            self.visit_stmt(block);
+            self.gen.emit_throw(exception_reg, block.pos());
+
+            if fallthrough {
+                self.gen.bind_label(skip_lbl);
+            }
+        }
+    }
+
+    fn visit_stmt_for(&mut self, stmt: &'ast StmtForType) {
         if self.src.map_fors.get(stmt.id).is_some() {
             self.visit_stmt_for_iterator(stmt);
         } else {
@@ -153,7 +416,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_stmt_for_array(&mut self, stmt: &StmtForType) {
+    fn visit_stmt_for_array(&mut self, stmt: &'ast StmtForType) {
         self.push_scope();
 
         let array_reg = self.alloc_var(BytecodeType::Ptr);
@@ -162,7 +425,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
         let for_var_id = *self.src.map_vars.get(stmt.id).unwrap();
         let var_ty = self.var_ty(for_var_id);
-        let var_ty: BytecodeType = var_ty.into();
+        let var_ty = BytecodeType::from_ty(self.vm, var_ty);
         let var_reg = self.alloc_var(var_ty);
         self.var_registers.insert(for_var_id, var_reg);
 
@@ -188,7 +451,8 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         // load current array element
         self.emit_load_array(var_ty, var_reg, array_reg, index_reg, stmt.expr.pos());
 
-        self.loops.push(LoopLabels::new(lbl_cond, lbl_end));
+        self.loops
+            .push(LoopLabels::new(lbl_cond, lbl_end, self.handlers.len()));
         self.visit_stmt(&stmt.block);
         self.loops.pop().unwrap();
 
@@ -205,7 +469,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.pop_scope();
     }
 
-    fn visit_stmt_for_iterator(&mut self, stmt: &StmtForType) {
+    fn visit_stmt_for_iterator(&mut self, stmt: &'ast StmtForType) {
         self.push_scope();
         let for_type_info = self.src.map_fors.get(stmt.id).unwrap().clone();
 
@@ -240,7 +504,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let var_id = *self.src.map_vars.get(stmt.id).unwrap();
         let var_ty = self.var_ty(var_id);
 
-        let ty: BytecodeType = var_ty.into();
+        let ty = BytecodeType::from_ty(self.vm, var_ty);
         let var_reg = self.alloc_var(ty);
         self.var_registers.insert(var_id, var_reg);
 
@@ -253,7 +517,8 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             stmt.expr.pos(),
         );
 
-        self.loops.push(LoopLabels::new(lbl_cond, lbl_end));
+        self.loops
+            .push(LoopLabels::new(lbl_cond, lbl_end, self.handlers.len()));
         self.visit_stmt(&stmt.block);
         self.loops.pop().unwrap();
 
@@ -265,14 +530,19 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.free_if_temp(object_reg);
     }
 
-    fn visit_stmt_var(&mut self, stmt: &StmtVarType) {
+    fn visit_stmt_var(&mut self, stmt: &'ast StmtVarType) {
         let var_id = *self.src.map_vars.get(stmt.id).unwrap();
         let ty = self.var_ty(var_id);
 
+        if self.is_boxed(var_id) {
+            self.visit_stmt_var_boxed(stmt, var_id, ty);
+            return;
+        }
+
         let dest = if ty.is_unit() {
             DataDest::Effect
         } else {
-            let ty: BytecodeType = ty.into();
+            let ty = BytecodeType::from_ty(self.vm, ty);
             let var_reg = self.alloc_var(ty);
 
             self.var_registers.insert(var_id, var_reg);
@@ -285,31 +555,47 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_stmt_while(&mut self, stmt: &StmtWhileType) {
+    fn visit_stmt_var_boxed(&mut self, stmt: &'ast StmtVarType, var_id: VarId, ty: BuiltinType) {
+        let box_def_id = specialize_box(self.vm, ty);
+        let box_reg = self.alloc_var(BytecodeType::Ptr);
+        self.var_registers.insert(var_id, box_reg);
+
+        self.gen.emit_new_object(box_reg, box_def_id, stmt.pos);
+
+        if let Some(ref expr) = stmt.expr {
+            let value_reg = self.visit_expr(expr, DataDest::Alloc);
+            self.gen
+                .emit_store_field(value_reg, box_reg, box_def_id, 0.into(), stmt.pos);
+            self.free_if_temp(value_reg);
+        }
+    }
+
+    fn visit_stmt_while(&mut self, stmt: &'ast StmtWhileType) {
         let cond_lbl = self.gen.define_label();
         let end_lbl = self.gen.create_label();
         let cond_reg = self.visit_expr(&stmt.cond, DataDest::Alloc);
         self.gen.emit_jump_if_false(cond_reg, end_lbl);
         self.free_if_temp(cond_reg);
-        self.loops.push(LoopLabels::new(cond_lbl, end_lbl));
+        self.loops
+            .push(LoopLabels::new(cond_lbl, end_lbl, self.handlers.len()));
         self.visit_stmt(&stmt.block);
         self.loops.pop().unwrap();
         self.gen.emit_jump_loop(cond_lbl);
         self.gen.bind_label(end_lbl);
     }
 
-    fn visit_stmt_expr(&mut self, stmt: &StmtExprType) {
+    fn visit_stmt_expr(&mut self, stmt: &'ast StmtExprType) {
         let reg = self.visit_expr(&stmt.expr, DataDest::Effect);
         self.free_if_temp(reg);
     }
 
-    fn visit_stmt_return(&mut self, ret: &StmtReturnType) {
+    fn visit_stmt_return(&mut self, ret: &'ast StmtReturnType) {
         if let Some(ref expr) = ret.expr {
             let result_reg = self.visit_expr(expr, DataDest::Alloc);
             self.emit_ret_value(result_reg);
             self.free_if_temp(result_reg);
         } else {
-            self.gen.emit_ret_void();
+            self.emit_ret_void();
         }
     }
 
@@ -317,24 +603,56 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let ret_ty = self.specialize_type(self.fct.return_type);
 
         if ret_ty.is_unit() {
-            self.gen.emit_ret_void();
+            self.emit_ret_void();
             return;
         }
 
-        self.gen.emit_ret(result_reg);
+        if self.handlers.is_empty() {
+            self.gen.emit_ret(result_reg);
+            return;
+        }
+
+        // deferred blocks must not change the already evaluated result
+        let ty = BytecodeType::from_ty(self.vm, ret_ty);
+        let tmp_reg = self.alloc_temp(ty);
+        self.emit_mov(ty, tmp_reg, result_reg);
+
+        let handlers = self.exit_handlers(0);
+        self.gen.emit_ret(tmp_reg);
+        self.reopen_handlers(handlers);
+
+        self.free_temp(tmp_reg);
+    }
+
+    fn emit_ret_void(&mut self) {
+        let handlers = self.exit_handlers(0);
+        self.gen.emit_ret_void();
+        self.reopen_handlers(handlers);
     }
 
-    fn visit_stmt_break(&mut self, _stmt: &StmtBreakType) {
-        let end = self.loops.last().unwrap().end;
+    fn visit_stmt_break(&mut self, _stmt: &'ast StmtBreakType) {
+        let (end, boundary) = {
+            let loop_labels = self.loops.last().unwrap();
+            (loop_labels.end, loop_labels.handlers)
+        };
+
+        let handlers = self.exit_handlers(boundary);
         self.gen.emit_jump(end);
+        self.reopen_handlers(handlers);
     }
 
-    fn visit_stmt_continue(&mut self, _stmt: &StmtContinueType) {
-        let cond = self.loops.last().unwrap().cond;
+    fn visit_stmt_continue(&mut self, _stmt: &'ast StmtContinueType) {
+        let (cond, boundary) = {
+            let loop_labels = self.loops.last().unwrap();
+            (loop_labels.cond, loop_labels.handlers)
+        };
+
+        let handlers = self.exit_handlers(boundary);
         self.gen.emit_jump_loop(cond);
+        self.reopen_handlers(handlers);
     }
 
-    fn visit_expr(&mut self, expr: &Expr, dest: DataDest) -> Register {
+    fn visit_expr(&mut self, expr: &'ast Expr, dest: DataDest) -> Register {
         match *expr {
             ExprUn(ref un) => self.visit_expr_un(un, dest),
             ExprBin(ref bin) => self.visit_expr_bin(bin, dest),
@@ -352,21 +670,23 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             ExprIdent(ref ident) => self.visit_expr_ident(ident, dest),
             ExprCall(ref call) => self.visit_expr_call(call, dest),
             ExprDelegation(ref call) => self.visit_expr_delegation(call, dest),
-            ExprSelf(_) => self.visit_expr_self(dest),
-            ExprSuper(_) => self.visit_expr_self(dest),
+            ExprSelf(ref expr) => self.visit_expr_self(dest, expr.pos),
+            ExprSuper(ref expr) => self.visit_expr_self(dest, expr.pos),
             ExprConv(ref conv) => self.visit_expr_conv(conv, dest),
             ExprNil(ref nil) => self.visit_expr_nil(nil, dest),
+            ExprTry(ref expr) => self.visit_expr(&expr.expr, dest),
             ExprTuple(ref tuple) => self.visit_expr_tuple(tuple, dest),
-            ExprLambda(_) => unimplemented!(),
+            ExprLambda(ref lambda) => self.visit_expr_lambda(lambda, dest),
+            ExprMatch(ref expr) => self.visit_expr_match(expr, dest),
         }
     }
 
-    fn emit_expr_for_effect(&mut self, expr: &Expr) {
+    fn emit_expr_for_effect(&mut self, expr: &'ast Expr) {
         let reg = self.visit_expr(expr, DataDest::Effect);
         self.free_if_temp(reg);
     }
 
-    fn visit_expr_template(&mut self, expr: &ExprTemplateType, dest: DataDest) -> Register {
+    fn visit_expr_template(&mut self, expr: &'ast ExprTemplateType, dest: DataDest) -> Register {
         let buffer_register = self.ensure_register(dest, BytecodeType::Ptr);
 
         // build StringBuffer::empty() call
@@ -398,7 +718,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                         .find_trait_method(self.vm, self.vm.vips.stringable_trait, name, false)
                         .expect("toString() method not found");
 
-                    if ty.reference_type() {
+                    if ty.reference_type(self.vm) {
                         self.gen.emit_invoke_direct(
                             part_register,
                             FctDef::fct_id(self.vm, to_string_id),
@@ -435,21 +755,86 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         buffer_register
     }
 
-    fn visit_expr_path(&mut self, expr: &ExprPathType, dest: DataDest) -> Register {
+    fn visit_expr_path(&mut self, expr: &'ast ExprPathType, dest: DataDest) -> Register {
         let ident_type = self.src.map_idents.get(expr.id).unwrap();
 
         match ident_type {
-            &IdentType::EnumValue(_, value) => {
+            &IdentType::EnumValue(enum_id, variant_id) => {
+                self.emit_new_enum(enum_id, variant_id, &[], dest, expr.pos)
+            }
+
+            &IdentType::Global(gid) => self.visit_expr_ident_global(gid, dest),
+            &IdentType::Const(cid) => self.visit_expr_ident_const(cid, dest),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn emit_new_enum(
+        &mut self,
+        enum_id: EnumId,
+        variant_id: u32,
+        arguments: &[Register],
+        dest: DataDest,
+        pos: Position,
+    ) -> Register {
+        let enum_def_id = specialize_enum_id_params(self.vm, enum_id, TypeList::empty());
+        let (layout, cls_def_id) = {
+            let enum_def = self.vm.enum_defs.idx(enum_def_id);
+            let enum_def = enum_def.read();
+            let variant = &enum_def.variants[variant_id as usize];
+            (enum_def.layout, variant.cls_def_id)
+        };
+
+        match layout {
+            EnumLayout::Int => {
                 let dest = self.ensure_register(dest, BytecodeType::Int32);
-                self.gen.emit_const_int32(dest, value as i32);
+                self.gen.emit_const_int32(dest, variant_id as i32);
                 dest
             }
 
-            _ => unreachable!(),
+            EnumLayout::Tagged => {
+                let cls_def_id = cls_def_id.expect("enum variant without class");
+
+                // allocate into a temporary, dest might be one of the arguments
+                let object_reg = self.alloc_temp(BytecodeType::Ptr);
+                self.gen.emit_new_object(object_reg, cls_def_id, pos);
+
+                let tag_reg = self.alloc_temp(BytecodeType::Int32);
+                self.gen.emit_const_int32(tag_reg, variant_id as i32);
+                self.gen.emit_store_field(
+                    tag_reg,
+                    object_reg,
+                    cls_def_id,
+                    ENUM_TAG_FIELD.into(),
+                    pos,
+                );
+                self.free_temp(tag_reg);
+
+                for (idx, &arg_reg) in arguments.iter().enumerate() {
+                    if arg_reg.is_invalid() {
+                        continue;
+                    }
+
+                    let field_id = enum_payload_field(idx);
+                    self.gen
+                        .emit_store_field(arg_reg, object_reg, cls_def_id, field_id, pos);
+                }
+
+                match dest {
+                    DataDest::Reg(dest) => {
+                        self.gen.emit_mov_ptr(dest, object_reg);
+                        self.free_temp(object_reg);
+                        dest
+                    }
+
+                    DataDest::Effect | DataDest::Alloc => object_reg,
+                }
+            }
         }
     }
 
-    fn visit_expr_conv(&mut self, expr: &ExprConvType, dest: DataDest) -> Register {
+    fn visit_expr_conv(&mut self, expr: &'ast ExprConvType, dest: DataDest) -> Register {
         let conv = *self.src.map_convs.get(expr.id).unwrap();
         let ty = self.specialize_type(conv.check_type);
         let cls_def_id = specialize_class_ty(self.vm, ty);
@@ -473,14 +858,14 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_if(&mut self, expr: &ExprIfType, dest: DataDest) -> Register {
+    fn visit_expr_if(&mut self, expr: &'ast ExprIfType, dest: DataDest) -> Register {
         let ty = self.ty(expr.id);
 
         if let Some(ref else_block) = expr.else_block {
             let dest = if ty.is_unit() {
                 Register::invalid()
             } else {
-                self.ensure_register(dest, ty.into())
+                self.ensure_register(dest, BytecodeType::from_ty(self.vm, ty))
             };
 
             let else_lbl = self.gen.create_label();
@@ -517,8 +902,281 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_block(&mut self, block: &ExprBlockType, dest: DataDest) -> Register {
+    fn visit_expr_match(&mut self, expr: &'ast ExprMatchType, dest: DataDest) -> Register {
+        let ty = self.ty(expr.id);
+
+        let dest = if ty.is_unit() {
+            Register::invalid()
+        } else {
+            self.ensure_register(dest, BytecodeType::from_ty(self.vm, ty))
+        };
+
+        let value_ty = self.ty(expr.expr.id());
+        let value_reg = self.visit_expr(&expr.expr, DataDest::Alloc);
+
+        let end_lbl = self.gen.create_label();
+
+        // arms are tested in order, a failing pattern jumps to the next arm
+        for (idx, case) in expr.cases.iter().enumerate() {
+            // match is exhaustive, the last arm doesn't need to be tested: only nil
+            // can fall through all arms, class patterns in the last arm trap on it
+            let next_lbl = if idx + 1 < expr.cases.len() {
+                Some(self.gen.create_label())
+            } else {
+                None
+            };
+
+            self.push_scope();
+            self.emit_match_pattern(&case.pattern, value_reg, value_ty, next_lbl);
+            self.visit_expr(&case.value, DataDest::Reg(dest));
+            self.pop_scope();
+
+            if !expr_always_returns(&case.value) {
+                self.gen.emit_jump(end_lbl);
+            }
+
+            if let Some(next_lbl) = next_lbl {
+                self.gen.bind_label(next_lbl);
+            }
+        }
+
+        self.gen.bind_label(end_lbl);
+        self.free_if_temp(value_reg);
+
+        dest
+    }
+
+    fn emit_match_pattern(
+        &mut self,
+        pattern: &'ast MatchPattern,
+        value: Register,
+        ty: BuiltinType,
+        fail_lbl: Option<Label>,
+    ) {
+        match *pattern {
+            MatchPattern::Underscore(_) => {}
+
+            MatchPattern::Ident(ref ident) => {
+                if let Some(&var_id) = self.src.map_vars.get(ident.id) {
+                    if !ty.is_unit() {
+                        let ty = BytecodeType::from_ty(self.vm, ty);
+                        let var_reg = self.alloc_var(ty);
+                        self.var_registers.insert(var_id, var_reg);
+                        self.emit_mov(ty, var_reg, value);
+                    }
+                } else {
+                    self.emit_match_pattern_class(ident.id, ident.pos, None, value, fail_lbl);
+                }
+            }
+
+            MatchPattern::Lit(ref lit) => self.emit_match_pattern_lit(lit, value, ty, fail_lbl),
+
+            MatchPattern::Tuple(ref tuple) => {
+                if ty.is_unit() {
+                    return;
+                }
+
+                let tuple_id = ty.tuple_id().expect("tuple expected");
+
+                for (idx, param) in tuple.params.iter().enumerate() {
+                    let (subtype, _) = self.vm.tuples.lock().get_at(tuple_id, idx);
+
+                    if subtype.is_unit() {
+                        self.emit_match_pattern(param, Register::invalid(), subtype, fail_lbl);
+                        continue;
+                    }
+
+                    let element_reg = self.alloc_temp(BytecodeType::from_ty(self.vm, subtype));
+                    self.gen
+                        .emit_load_tuple_element(element_reg, value, tuple_id, idx as u32);
+                    self.emit_match_pattern(param, element_reg, subtype, fail_lbl);
+                    self.free_temp(element_reg);
+                }
+            }
+
+            MatchPattern::Variant(ref variant) => {
+                match *self.src.map_idents.get(variant.id).unwrap() {
+                    IdentType::EnumValue(enum_id, variant_id) => {
+                        self.emit_match_pattern_enum(variant, enum_id, variant_id, value, fail_lbl)
+                    }
+
+                    IdentType::Class(_) => self.emit_match_pattern_class(
+                        variant.id,
+                        variant.pos,
+                        variant.params.as_ref(),
+                        value,
+                        fail_lbl,
+                    ),
+
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    fn emit_match_pattern_enum(
+        &mut self,
+        variant: &'ast MatchPatternVariant,
+        enum_id: EnumId,
+        variant_id: u32,
+        value: Register,
+        fail_lbl: Option<Label>,
+    ) {
+        let enum_def_id = specialize_enum_id_params(self.vm, enum_id, TypeList::empty());
+        let (layout, cls_def_id, types) = {
+            let enum_def = self.vm.enum_defs.idx(enum_def_id);
+            let enum_def = enum_def.read();
+            let variant = &enum_def.variants[variant_id as usize];
+            (enum_def.layout, variant.cls_def_id, variant.types.clone())
+        };
+
+        if let Some(fail_lbl) = fail_lbl {
+            let cond_reg = self.alloc_temp(BytecodeType::Bool);
+            let variant_reg = self.alloc_temp(BytecodeType::Int32);
+            self.gen.emit_const_int32(variant_reg, variant_id as i32);
+
+            match layout {
+                EnumLayout::Int => self.gen.emit_test_eq_enum(cond_reg, value, variant_reg),
+                EnumLayout::Tagged => {
+                    let cls_def_id = cls_def_id.expect("enum variant without class");
+                    let tag_reg = self.alloc_temp(BytecodeType::Int32);
+                    self.gen.emit_load_field(
+                        tag_reg,
+                        value,
+                        cls_def_id,
+                        ENUM_TAG_FIELD.into(),
+                        variant.pos,
+                    );
+                    self.gen.emit_test_eq_int32(cond_reg, tag_reg, variant_reg);
+                    self.free_temp(tag_reg);
+                }
+            }
+
+            self.gen.emit_jump_if_false(cond_reg, fail_lbl);
+            self.free_temp(variant_reg);
+            self.free_temp(cond_reg);
+        }
+
+        for (idx, param) in variant.params.iter().flatten().enumerate() {
+            if let MatchPattern::Underscore(_) = **param {
+                continue;
+            }
+
+            let field_ty = types[idx];
+
+            if field_ty.is_unit() {
+                self.emit_match_pattern(param, Register::invalid(), field_ty, fail_lbl);
+                continue;
+            }
+
+            let cls_def_id = cls_def_id.expect("enum variant without class");
+            let field_reg = self.alloc_temp(BytecodeType::from_ty(self.vm, field_ty));
+            self.gen.emit_load_field(
+                field_reg,
+                value,
+                cls_def_id,
+                enum_payload_field(idx),
+                param.pos(),
+            );
+            self.emit_match_pattern(param, field_reg, field_ty, fail_lbl);
+            self.free_temp(field_reg);
+        }
+    }
+
+    fn emit_match_pattern_class(
+        &mut self,
+        id: NodeId,
+        pos: Position,
+        params: Option<&'ast Vec<Box<MatchPattern>>>,
+        value: Register,
+        fail_lbl: Option<Label>,
+    ) {
+        let cls_ty = self.ty(id);
+        let cls_def_id = specialize_class_ty(self.vm, cls_ty);
+
+        if let Some(fail_lbl) = fail_lbl {
+            let cond_reg = self.alloc_temp(BytecodeType::Bool);
+            self.gen.emit_instance_of(cond_reg, value, cls_def_id);
+            self.gen.emit_jump_if_false(cond_reg, fail_lbl);
+            self.free_temp(cond_reg);
+        } else {
+            self.gen.emit_nil_check(value, pos);
+        }
+
+        for (idx, param) in params.into_iter().flatten().enumerate() {
+            if let MatchPattern::Underscore(_) = **param {
+                continue;
+            }
+
+            let field_ty = {
+                let cls = self.vm.class_defs.idx(cls_def_id);
+                let cls = cls.read();
+                cls.fields[idx].ty
+            };
+
+            if field_ty.is_unit() {
+                self.emit_match_pattern(param, Register::invalid(), field_ty, fail_lbl);
+                continue;
+            }
+
+            let field_reg = self.alloc_temp(BytecodeType::from_ty(self.vm, field_ty));
+            self.gen
+                .emit_load_field(field_reg, value, cls_def_id, idx.into(), pos);
+            self.emit_match_pattern(param, field_reg, field_ty, fail_lbl);
+            self.free_temp(field_reg);
+        }
+    }
+
+    fn emit_match_pattern_lit(
+        &mut self,
+        lit: &'ast MatchPatternLit,
+        value: Register,
+        ty: BuiltinType,
+        fail_lbl: Option<Label>,
+    ) {
+        let fail_lbl = match fail_lbl {
+            Some(fail_lbl) => fail_lbl,
+            None => return,
+        };
+
+        let lit_reg = self.visit_expr(&lit.expr, DataDest::Alloc);
+        let cond_reg = self.alloc_temp(BytecodeType::Bool);
+
+        match BytecodeType::from_ty(self.vm, ty) {
+            BytecodeType::Bool => self.gen.emit_test_eq_bool(cond_reg, value, lit_reg),
+            BytecodeType::UInt8 => self.gen.emit_test_eq_uint8(cond_reg, value, lit_reg),
+            BytecodeType::Char => self.gen.emit_test_eq_char(cond_reg, value, lit_reg),
+            BytecodeType::Int32 => self.gen.emit_test_eq_int32(cond_reg, value, lit_reg),
+            BytecodeType::Int64 => self.gen.emit_test_eq_int64(cond_reg, value, lit_reg),
+            BytecodeType::Float32 => self.gen.emit_test_eq_float32(cond_reg, value, lit_reg),
+            BytecodeType::Float64 => self.gen.emit_test_eq_float64(cond_reg, value, lit_reg),
+
+            BytecodeType::Ptr => {
+                let call_type = self.src.map_calls.get(lit.id).unwrap().clone();
+                let callee_id = self.determine_callee(&call_type);
+
+                let callee = self.vm.fcts.idx(callee_id);
+                let callee = callee.read();
+                let callee_def_id = self.specialize_call(&callee, &call_type);
+
+                self.gen.emit_push_register(value);
+                self.gen.emit_push_register(lit_reg);
+                self.gen
+                    .emit_invoke_direct(cond_reg, callee_def_id, lit.pos);
+            }
+
+            BytecodeType::Tuple(_) | BytecodeType::Struct(_) => unreachable!(),
+        }
+
+        self.gen.emit_jump_if_false(cond_reg, fail_lbl);
+
+        self.free_temp(cond_reg);
+        self.free_if_temp(lit_reg);
+    }
+
+    fn visit_expr_block(&mut self, block: &'ast ExprBlockType, dest: DataDest) -> Register {
         self.push_scope();
+        let handlers = self.handlers.len();
 
         for stmt in &block.stmts {
             self.visit_stmt(stmt);
@@ -530,18 +1188,24 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             Register::invalid()
         };
 
+        self.pop_handlers(handlers, true);
         self.pop_scope();
 
         result
     }
 
-    fn visit_expr_dot(&mut self, expr: &ExprDotType, dest: DataDest) -> Register {
+    fn visit_expr_dot(&mut self, expr: &'ast ExprDotType, dest: DataDest) -> Register {
         let object_ty = self.ty(expr.lhs.id());
 
         if let Some(tuple_id) = object_ty.tuple_id() {
             return self.visit_expr_dot_tuple(expr, tuple_id, dest);
         }
 
+        if let Some(&IdentType::StructField(struct_ty, field_id)) = self.src.map_idents.get(expr.id)
+        {
+            return self.visit_expr_dot_struct(expr, struct_ty, field_id, dest);
+        }
+
         let (cls_ty, field_id) = {
             let ident_type = self.src.map_idents.get(expr.id).unwrap();
 
@@ -570,7 +1234,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             return Register::invalid();
         }
 
-        let field_bc_ty: BytecodeType = field_ty.into();
+        let field_bc_ty = BytecodeType::from_ty(self.vm, field_ty);
 
         let dest = self.ensure_register(dest, field_bc_ty);
         let obj = self.visit_expr(&expr.lhs, DataDest::Alloc);
@@ -584,7 +1248,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn visit_expr_dot_tuple(
         &mut self,
-        expr: &ExprDotType,
+        expr: &'ast ExprDotType,
         tuple_id: TupleId,
         dest: DataDest,
     ) -> Register {
@@ -599,7 +1263,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             return Register::invalid();
         }
 
-        let ty: BytecodeType = ty.into();
+        let ty = BytecodeType::from_ty(self.vm, ty);
         let dest = self.ensure_register(dest, ty);
         self.gen.emit_load_tuple_element(dest, tuple, tuple_id, idx);
 
@@ -608,14 +1272,70 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_assert(&mut self, expr: &ExprCallType, dest: DataDest) {
+    fn visit_expr_dot_struct(
+        &mut self,
+        expr: &'ast ExprDotType,
+        struct_ty: BuiltinType,
+        field_id: StructFieldId,
+        dest: DataDest,
+    ) -> Register {
+        let struct_def_id = match struct_ty {
+            BuiltinType::Struct(struct_id, list_id) => {
+                let type_params = self.vm.lists.lock().get(list_id);
+                specialize_struct_id_params(self.vm, struct_id, type_params)
+            }
+            _ => unreachable!(),
+        };
+
+        let field_ty = {
+            let struct_def = self.vm.struct_defs.idx(struct_def_id);
+            let struct_def = struct_def.lock();
+            struct_def.fields[field_id.to_usize()].ty
+        };
+
+        let struc = self.visit_expr(&expr.lhs, DataDest::Alloc);
+
+        if field_ty.is_unit() {
+            assert!(dest.is_unit());
+            self.free_if_temp(struc);
+            return Register::invalid();
+        }
+
+        let field_ty = BytecodeType::from_ty(self.vm, field_ty);
+        let dest = self.ensure_register(dest, field_ty);
+        self.gen
+            .emit_load_struct_field(dest, struc, struct_def_id, field_id);
+
+        self.free_if_temp(struc);
+
+        dest
+    }
+
+    fn visit_expr_assert(&mut self, expr: &'ast ExprCallType, dest: DataDest) {
         assert!(dest.is_unit());
         let assert_reg = self.visit_expr(&*expr.args[0], DataDest::Alloc);
         self.gen.emit_assert(assert_reg, expr.pos);
         self.free_if_temp(assert_reg);
     }
 
-    fn visit_expr_call(&mut self, expr: &ExprCallType, dest: DataDest) -> Register {
+    fn visit_expr_call(&mut self, expr: &'ast ExprCallType, dest: DataDest) -> Register {
+        match self.src.map_idents.get(expr.callee.id()) {
+            Some(&IdentType::EnumValue(enum_id, variant_id)) => {
+                return self.visit_expr_call_enum(expr, enum_id, variant_id, dest);
+            }
+
+            Some(&IdentType::Struct(struct_id))
+            | Some(&IdentType::StructAndModule(struct_id, _)) => {
+                return self.visit_expr_call_struct(expr, struct_id, dest);
+            }
+
+            _ => {}
+        }
+
+        if let CallType::Lambda(ty) = **self.src.map_calls.get(expr.id).unwrap() {
+            return self.visit_expr_call_lambda(expr, ty, dest);
+        }
+
         if let Some(info) = self.get_intrinsic(expr.id) {
             return self.emit_intrinsic_call(expr, info, dest);
         }
@@ -639,7 +1359,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let return_reg = if return_type.is_unit() {
             Register::invalid()
         } else {
-            self.ensure_register(dest, return_type.into())
+            self.ensure_register(dest, BytecodeType::from_ty(self.vm, return_type))
         };
 
         // Evaluate object/self argument
@@ -686,6 +1406,171 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         result_reg
     }
 
+    fn visit_expr_call_enum(
+        &mut self,
+        expr: &'ast ExprCallType,
+        enum_id: EnumId,
+        variant_id: u32,
+        dest: DataDest,
+    ) -> Register {
+        let mut arguments = Vec::with_capacity(expr.args.len());
+
+        for arg in &expr.args {
+            if self.ty(arg.id()).is_unit() {
+                self.emit_expr_for_effect(arg);
+                arguments.push(Register::invalid());
+            } else {
+                arguments.push(self.visit_expr(arg, DataDest::Alloc));
+            }
+        }
+
+        let dest = self.emit_new_enum(enum_id, variant_id, &arguments, dest, expr.pos);
+
+        for arg_reg in arguments {
+            self.free_if_temp(arg_reg);
+        }
+
+        dest
+    }
+
+    fn visit_expr_call_struct(
+        &mut self,
+        expr: &'ast ExprCallType,
+        struct_id: StructId,
+        dest: DataDest,
+    ) -> Register {
+        let mut arguments = Vec::with_capacity(expr.args.len());
+
+        for arg in &expr.args {
+            if self.ty(arg.id()).is_unit() {
+                self.emit_expr_for_effect(arg);
+            } else {
+                arguments.push(self.visit_expr(arg, DataDest::Alloc));
+            }
+        }
+
+        let dest = self.emit_new_struct(struct_id, &arguments, dest);
+
+        for arg_reg in arguments {
+            self.free_if_temp(arg_reg);
+        }
+
+        dest
+    }
+
+    fn emit_new_struct(
+        &mut self,
+        struct_id: StructId,
+        arguments: &[Register],
+        dest: DataDest,
+    ) -> Register {
+        let struct_def_id = specialize_struct_id_params(self.vm, struct_id, TypeList::empty());
+        let dest = self.ensure_register(dest, BytecodeType::Struct(struct_def_id));
+
+        for &arg_reg in arguments {
+            self.gen.emit_push_register(arg_reg);
+        }
+
+        self.gen.emit_new_struct(dest, struct_def_id);
+
+        dest
+    }
+
+    fn visit_expr_call_lambda(
+        &mut self,
+        expr: &'ast ExprCallType,
+        lambda_ty: BuiltinType,
+        dest: DataDest,
+    ) -> Register {
+        let lambda_id = match self.specialize_type(lambda_ty) {
+            BuiltinType::Lambda(lambda_id) => lambda_id,
+            _ => unreachable!(),
+        };
+        let lambda = self.vm.lambda_types.lock().get(lambda_id);
+        let return_type = lambda.ret();
+
+        // Allocate register for result
+        let return_reg = if return_type.is_unit() {
+            Register::invalid()
+        } else {
+            self.ensure_register(dest, BytecodeType::from_ty(self.vm, return_type))
+        };
+
+        // Evaluate lambda object, it is passed as first argument
+        let lambda_reg = self.visit_expr(&expr.callee, DataDest::Alloc);
+
+        // Evaluate function arguments
+        let mut arguments = Vec::new();
+
+        for (arg, ty) in expr.args.iter().zip(lambda.params()) {
+            if ty.is_unit() {
+                self.emit_expr_for_effect(arg);
+            } else {
+                arguments.push(self.visit_expr(arg, DataDest::Alloc));
+            }
+        }
+
+        self.gen.emit_push_register(lambda_reg);
+        for &arg_reg in &arguments {
+            self.gen.emit_push_register(arg_reg);
+        }
+
+        if return_type.is_unit() {
+            self.gen.emit_invoke_lambda_void(expr.pos);
+        } else {
+            self.gen.emit_invoke_lambda(return_reg, expr.pos);
+        }
+
+        self.free_if_temp(lambda_reg);
+
+        for arg_reg in arguments {
+            self.free_if_temp(arg_reg);
+        }
+
+        return_reg
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &'ast ExprLambdaType, dest: DataDest) -> Register {
+        if dest.is_effect() {
+            return Register::invalid();
+        }
+
+        let lambda = self.src.map_lambdas.get(expr.fct.id).unwrap().clone();
+        let fct = self.vm.fcts.idx(lambda.fct_id);
+        let fct = fct.read();
+
+        let fct_def_id = FctDef::with(
+            self.vm,
+            &*fct,
+            self.cls_type_params.clone(),
+            self.fct_type_params.clone(),
+        );
+        let cls_def_id = specialize_lambda(self.vm, self.src, fct_def_id);
+
+        let dest = self.ensure_register(dest, BytecodeType::Ptr);
+        self.gen.emit_new_lambda(dest, fct_def_id, expr.pos);
+
+        // store captured variables in the context object,
+        // reassignable variables are shared through their box
+        for (idx, &var_id) in lambda.captures.iter().enumerate() {
+            if self.var_ty(var_id).is_unit() {
+                continue;
+            }
+
+            let value_reg = if self.is_boxed(var_id) {
+                self.load_var_box(var_id, expr.pos)
+            } else {
+                self.visit_expr_ident_var(var_id, DataDest::Alloc, expr.pos)
+            };
+
+            self.gen
+                .emit_store_field(value_reg, dest, cls_def_id, idx.into(), expr.pos);
+            self.free_if_temp(value_reg);
+        }
+
+        dest
+    }
+
     fn determine_callee(&mut self, call_type: &CallType) -> FctId {
         match *call_type {
             CallType::Method(_, fct_id, _) => {
@@ -707,12 +1592,11 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 }
             }
             CallType::TraitStatic(tp_id, trait_id, trait_fct_id) => {
-                let list_id = match tp_id {
-                    TypeParamId::Fct(list_id) => list_id,
-                    TypeParamId::Class(_) => unimplemented!(),
+                let ty = match tp_id {
+                    TypeParamId::Fct(list_id) => self.fct_type_params[list_id.idx()],
+                    TypeParamId::Class(list_id) => self.cls_type_params[list_id.idx()],
                 };
 
-                let ty = self.fct_type_params[list_id.idx()];
                 let cls_id = ty.cls_id(self.vm).expect("no cls_id for type");
 
                 let cls = self.vm.classes.idx(cls_id);
@@ -760,7 +1644,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let arg_bytecode_types = arg_types
             .iter()
             .filter(|ty| !ty.is_unit())
-            .map(|&ty| ty.into())
+            .map(|&ty| BytecodeType::from_ty(self.vm, ty))
             .collect::<Vec<BytecodeType>>();
 
         (arg_types, arg_bytecode_types, return_type)
@@ -768,11 +1652,11 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_call_object_argument(
         &mut self,
-        expr: &ExprCallType,
+        expr: &'ast ExprCallType,
         call_type: &CallType,
     ) -> Option<Register> {
         match *call_type {
-            CallType::Method(_, _, _) => {
+            CallType::Method(_, _, _) | CallType::Trait(_, _) => {
                 let obj_expr = expr.object().expect("method target required");
                 let reg = self.visit_expr(obj_expr, DataDest::Alloc);
 
@@ -790,7 +1674,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_call_arguments(
         &mut self,
-        expr: &ExprCallType,
+        expr: &'ast ExprCallType,
         callee: &Fct,
         call_type: &CallType,
         arg_types: &[BuiltinType],
@@ -799,7 +1683,10 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
         // self was already emitted, needs to be ignored here.
         let arg_start_offset = match *call_type {
-            CallType::Ctor(_, _) | CallType::Expr(_, _) | CallType::Method(_, _, _) => 1,
+            CallType::Ctor(_, _)
+            | CallType::Expr(_, _)
+            | CallType::Method(_, _, _)
+            | CallType::Trait(_, _) => 1,
             _ => 0,
         };
 
@@ -837,7 +1724,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_array_with_variadic_arguments(
         &mut self,
-        expr: &ExprCallType,
+        expr: &'ast ExprCallType,
         arg_types: &[BuiltinType],
         non_variadic_arguments: usize,
         dest: DataDest,
@@ -859,7 +1746,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.gen
             .emit_new_array(array_reg, cls_def_id, length_reg, expr.pos);
 
-        let bytecode_ty: BytecodeType = element_ty.into();
+        let bytecode_ty = BytecodeType::from_ty(self.vm, element_ty);
         let index_reg = self.alloc_temp(BytecodeType::Int64);
 
         // Evaluate rest arguments and store them in array
@@ -907,7 +1794,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_call_inst(
         &mut self,
-        expr: &ExprCallType,
+        expr: &'ast ExprCallType,
         fct: &Fct,
         call_type: &CallType,
         arg_bytecode_types: &[BytecodeType],
@@ -956,11 +1843,13 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                     self.emit_invoke_direct(return_type, return_reg, fct_def_id, pos);
                 }
             }
-            CallType::Trait(_, _) => unimplemented!(),
+            CallType::Trait(_, _) => {
+                self.emit_invoke_trait(return_type, return_reg, fct_def_id, pos);
+            }
             CallType::TraitStatic(_, _, _) => {
                 self.emit_invoke_static(return_type, return_reg, fct_def_id, pos);
             }
-            CallType::Intrinsic(_) => unreachable!(),
+            CallType::Intrinsic(_) | CallType::Lambda(_) => unreachable!(),
         }
     }
 
@@ -1001,6 +1890,9 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             BytecodeType::Int64 => self.gen.emit_mov_int64(dest, src),
             BytecodeType::Ptr => self.gen.emit_mov_ptr(dest, src),
             BytecodeType::Tuple(tuple_id) => self.gen.emit_mov_tuple(dest, src, tuple_id),
+            BytecodeType::Struct(struct_def_id) => {
+                self.gen.emit_mov_struct(dest, src, struct_def_id)
+            }
         }
     }
 
@@ -1022,6 +1914,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             BytecodeType::Float64 => self.gen.emit_load_array_float64(dest, arr, idx, pos),
             BytecodeType::Ptr => self.gen.emit_load_array_ptr(dest, arr, idx, pos),
             BytecodeType::Tuple(_) => self.gen.emit_load_array_tuple(dest, arr, idx, pos),
+            BytecodeType::Struct(_) => self.gen.emit_load_array_struct(dest, arr, idx, pos),
         }
     }
 
@@ -1039,6 +1932,20 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
+    fn emit_invoke_trait(
+        &mut self,
+        return_type: BuiltinType,
+        return_reg: Register,
+        callee_id: FctDefId,
+        pos: Position,
+    ) {
+        if return_type.is_unit() {
+            self.gen.emit_invoke_trait_void(callee_id, pos);
+        } else {
+            self.gen.emit_invoke_trait(return_reg, callee_id, pos);
+        }
+    }
+
     fn emit_invoke_direct(
         &mut self,
         return_type: BuiltinType,
@@ -1067,7 +1974,11 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_delegation(&mut self, expr: &ExprDelegationType, dest: DataDest) -> Register {
+    fn visit_expr_delegation(
+        &mut self,
+        expr: &'ast ExprDelegationType,
+        dest: DataDest,
+    ) -> Register {
         assert!(dest.is_unit());
         let call_type = self.src.map_calls.get(expr.id).unwrap().clone();
         let fct_id = call_type.fct_id().unwrap();
@@ -1082,7 +1993,9 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let arg_types = callee
             .params_with_self()
             .iter()
-            .map(|&arg| self.specialize_type_for_call(&call_type, arg).into())
+            .map(|&arg| {
+                BytecodeType::from_ty(self.vm, self.specialize_type_for_call(&call_type, arg))
+            })
             .collect::<Vec<BytecodeType>>();
         let num_args = arg_types.len();
 
@@ -1117,7 +2030,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         Register::invalid()
     }
 
-    fn visit_expr_nil(&mut self, _nil: &ExprNilType, dest: DataDest) -> Register {
+    fn visit_expr_nil(&mut self, _nil: &'ast ExprNilType, dest: DataDest) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1129,12 +2042,17 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_self(&mut self, dest: DataDest) -> Register {
+    fn visit_expr_self(&mut self, dest: DataDest, pos: Position) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
 
         let var_id = self.src.var_self().id;
+
+        if self.lambda.is_some() {
+            // self is captured by the lambda
+            return self.visit_expr_ident_var(var_id, dest, pos);
+        }
         let var_reg = self.var_reg(var_id);
 
         if dest.is_alloc() {
@@ -1142,7 +2060,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
 
         let dest = dest.reg();
-        let ty: BytecodeType = self.src.var_self().ty.into();
+        let ty = BytecodeType::from_ty(self.vm, self.src.var_self().ty);
 
         match ty {
             BytecodeType::Bool => self.gen.emit_mov_bool(dest, var_reg),
@@ -1154,11 +2072,14 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             BytecodeType::Int64 => self.gen.emit_mov_int64(dest, var_reg),
             BytecodeType::Ptr => self.gen.emit_mov_ptr(dest, var_reg),
             BytecodeType::Tuple(tuple_id) => self.gen.emit_mov_tuple(dest, var_reg, tuple_id),
+            BytecodeType::Struct(struct_def_id) => {
+                self.gen.emit_mov_struct(dest, var_reg, struct_def_id)
+            }
         }
         dest
     }
 
-    fn visit_expr_lit_char(&mut self, lit: &ExprLitCharType, dest: DataDest) -> Register {
+    fn visit_expr_lit_char(&mut self, lit: &'ast ExprLitCharType, dest: DataDest) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1170,7 +2091,12 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_lit_int(&mut self, lit: &ExprLitIntType, dest: DataDest, neg: bool) -> Register {
+    fn visit_expr_lit_int(
+        &mut self,
+        lit: &'ast ExprLitIntType,
+        dest: DataDest,
+        neg: bool,
+    ) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1211,7 +2137,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_lit_float(&mut self, lit: &ExprLitFloatType, dest: DataDest) -> Register {
+    fn visit_expr_lit_float(&mut self, lit: &'ast ExprLitFloatType, dest: DataDest) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1243,7 +2169,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_lit_string(&mut self, lit: &ExprLitStrType, dest: DataDest) -> Register {
+    fn visit_expr_lit_string(&mut self, lit: &'ast ExprLitStrType, dest: DataDest) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1254,7 +2180,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_lit_bool(&mut self, lit: &ExprLitBoolType, dest: DataDest) -> Register {
+    fn visit_expr_lit_bool(&mut self, lit: &'ast ExprLitBoolType, dest: DataDest) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -1270,7 +2196,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_tuple(&mut self, e: &ExprTupleType, dest: DataDest) -> Register {
+    fn visit_expr_tuple(&mut self, e: &'ast ExprTupleType, dest: DataDest) -> Register {
         if e.values.is_empty() {
             assert!(dest.is_unit());
             return Register::invalid();
@@ -1306,7 +2232,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         result
     }
 
-    fn visit_expr_un(&mut self, expr: &ExprUnType, dest: DataDest) -> Register {
+    fn visit_expr_un(&mut self, expr: &'ast ExprUnType, dest: DataDest) -> Register {
         if expr.op == UnOp::Neg && expr.opnd.is_lit_int() {
             self.visit_expr_lit_int(expr.opnd.to_lit_int().unwrap(), dest, true)
         } else if let Some(intrinsic) = self.get_intrinsic(expr.id) {
@@ -1316,7 +2242,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_un_method(&mut self, expr: &ExprUnType, dest: DataDest) -> Register {
+    fn visit_expr_un_method(&mut self, expr: &'ast ExprUnType, dest: DataDest) -> Register {
         let opnd = self.visit_expr(&expr.opnd, DataDest::Alloc);
 
         let call_type = self.src.map_calls.get(expr.id).unwrap();
@@ -1331,7 +2257,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let function_return_type: BuiltinType =
             self.specialize_type_for_call(call_type, callee.return_type);
 
-        let function_return_type_bc: BytecodeType = function_return_type.into();
+        let function_return_type_bc = BytecodeType::from_ty(self.vm, function_return_type);
 
         let dest = self.ensure_register(dest, function_return_type_bc);
 
@@ -1348,7 +2274,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_bin(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
+    fn visit_expr_bin(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
         if expr.op.is_any_assign() {
             self.visit_expr_assign(expr, dest)
         } else if expr.op == BinOp::Cmp(CmpOp::Is) || expr.op == BinOp::Cmp(CmpOp::IsNot) {
@@ -1364,8 +2290,8 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_bin_method(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
-        let lhs_type: BytecodeType = self.ty(expr.lhs.id()).into();
+    fn visit_expr_bin_method(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
+        let lhs_type = BytecodeType::from_ty(self.vm, self.ty(expr.lhs.id()));
 
         let lhs = self.visit_expr(&expr.lhs, DataDest::Alloc);
         let rhs = self.visit_expr(&expr.rhs, DataDest::Alloc);
@@ -1382,7 +2308,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let function_return_type: BuiltinType =
             self.specialize_type_for_call(call_type, callee.return_type);
 
-        let function_return_type_bc: BytecodeType = function_return_type.into();
+        let function_return_type_bc = BytecodeType::from_ty(self.vm, function_return_type);
 
         let return_type = match expr.op {
             BinOp::Cmp(_) => BytecodeType::Bool,
@@ -1440,7 +2366,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_intrinsic_call(
         &mut self,
-        expr: &ExprCallType,
+        expr: &'ast ExprCallType,
         info: IntrinsicInfo,
         dest: DataDest,
     ) -> Register {
@@ -1488,20 +2414,9 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                         return Register::invalid();
                     }
 
-                    let ty: BytecodeType = ty.into();
+                    let ty = BytecodeType::from_ty(self.vm, ty);
                     let dest = self.ensure_register(dest, ty);
-
-                    match ty {
-                        BytecodeType::Bool => self.gen.emit_const_false(dest),
-                        BytecodeType::UInt8 => self.gen.emit_const_uint8(dest, 0),
-                        BytecodeType::Int32 => self.gen.emit_const_int32(dest, 0),
-                        BytecodeType::Int64 => self.gen.emit_const_int64(dest, 0),
-                        BytecodeType::Char => self.gen.emit_const_char(dest, '\0'),
-                        BytecodeType::Float32 => self.gen.emit_const_float32(dest, 0.0),
-                        BytecodeType::Float64 => self.gen.emit_const_float64(dest, 0.0),
-                        BytecodeType::Ptr => self.gen.emit_const_nil(dest),
-                        BytecodeType::Tuple(_) => unimplemented!(),
-                    }
+                    self.emit_default_value(dest, ty);
 
                     dest
                 }
@@ -1511,7 +2426,73 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn emit_intrinsic_new_array(&mut self, expr: &ExprCallType, dest: DataDest) -> Register {
+    // zero value of the type: false, 0, nil, or a tuple or struct
+    // of zero values
+    fn emit_default_value(&mut self, dest: Register, ty: BytecodeType) {
+        match ty {
+            BytecodeType::Bool => self.gen.emit_const_false(dest),
+            BytecodeType::UInt8 => self.gen.emit_const_uint8(dest, 0),
+            BytecodeType::Int32 => self.gen.emit_const_int32(dest, 0),
+            BytecodeType::Int64 => self.gen.emit_const_int64(dest, 0),
+            BytecodeType::Char => self.gen.emit_const_char(dest, '\0'),
+            BytecodeType::Float32 => self.gen.emit_const_float32(dest, 0.0),
+            BytecodeType::Float64 => self.gen.emit_const_float64(dest, 0.0),
+            BytecodeType::Ptr => self.gen.emit_const_nil(dest),
+
+            BytecodeType::Tuple(tuple_id) => {
+                let subtypes = self.vm.tuples.lock().get(tuple_id);
+                let values = self.emit_default_values(&subtypes);
+                self.gen.emit_new_tuple(dest, tuple_id);
+                self.free_default_values(values);
+            }
+
+            BytecodeType::Struct(struct_def_id) => {
+                let fields = {
+                    let struct_def = self.vm.struct_defs.idx(struct_def_id);
+                    let struct_def = struct_def.lock();
+                    struct_def
+                        .fields
+                        .iter()
+                        .map(|field| field.ty)
+                        .collect::<Vec<_>>()
+                };
+
+                let values = self.emit_default_values(&fields);
+                self.gen.emit_new_struct(dest, struct_def_id);
+                self.free_default_values(values);
+            }
+        }
+    }
+
+    // pushes zero values for all non-unit types as arguments
+    fn emit_default_values(&mut self, types: &[BuiltinType]) -> Vec<Register> {
+        let mut values = Vec::with_capacity(types.len());
+
+        for &ty in types {
+            if ty.is_unit() {
+                continue;
+            }
+
+            let ty = BytecodeType::from_ty(self.vm, ty);
+            let value = self.alloc_temp(ty);
+            self.emit_default_value(value, ty);
+            values.push(value);
+        }
+
+        for &value in &values {
+            self.gen.emit_push_register(value);
+        }
+
+        values
+    }
+
+    fn free_default_values(&mut self, values: Vec<Register>) {
+        for value in values {
+            self.free_temp(value);
+        }
+    }
+
+    fn emit_intrinsic_new_array(&mut self, expr: &'ast ExprCallType, dest: DataDest) -> Register {
         // We need array of elements
         let element_ty = self.ty(expr.id);
         let cls_def_id = specialize_class_ty(self.vm, element_ty);
@@ -1527,7 +2508,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         array_reg
     }
 
-    fn emit_bin_is(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
+    fn emit_bin_is(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
         if dest.is_effect() {
             self.emit_expr_for_effect(&expr.lhs);
             self.emit_expr_for_effect(&expr.rhs);
@@ -1544,7 +2525,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             BuiltinType::Nil => BytecodeType::Ptr,
             BuiltinType::Float32 => BytecodeType::Int32,
             BuiltinType::Float64 => BytecodeType::Int64,
-            _ => builtin_type.into(),
+            _ => BytecodeType::from_ty(self.vm, builtin_type),
         };
 
         let cmp_lhs_reg;
@@ -1601,7 +2582,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn emit_bin_or(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
+    fn emit_bin_or(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
         if dest.is_effect() {
             let end_lbl = self.gen.create_label();
             let dest = self.visit_expr(&expr.lhs, DataDest::Alloc);
@@ -1625,7 +2606,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn emit_bin_and(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
+    fn emit_bin_and(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
         if dest.is_effect() {
             let end_lbl = self.gen.create_label();
             let dest = self.visit_expr(&expr.lhs, DataDest::Alloc);
@@ -1651,9 +2632,9 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
     fn emit_intrinsic_array_set(
         &mut self,
-        arr: &Expr,
-        idx: &Expr,
-        src: &Expr,
+        arr: &'ast Expr,
+        idx: &'ast Expr,
+        src: &'ast Expr,
         pos: Position,
         dest: DataDest,
     ) -> Register {
@@ -1662,7 +2643,11 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let ty = self.ty(arr.id());
         let ty = ty.type_params(self.vm);
         let ty = ty[0];
-        let ty: Option<BytecodeType> = if ty.is_unit() { None } else { Some(ty.into()) };
+        let ty: Option<BytecodeType> = if ty.is_unit() {
+            None
+        } else {
+            Some(BytecodeType::from_ty(self.vm, ty))
+        };
 
         let arr = self.visit_expr(arr, DataDest::Alloc);
         let idx = self.visit_expr(idx, DataDest::Alloc);
@@ -1706,12 +2691,13 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             BytecodeType::Float64 => self.gen.emit_store_array_float64(src, arr, idx, pos),
             BytecodeType::Ptr => self.gen.emit_store_array_ptr(src, arr, idx, pos),
             BytecodeType::Tuple(_) => self.gen.emit_store_array_tuple(src, arr, idx, pos),
+            BytecodeType::Struct(_) => self.gen.emit_store_array_struct(src, arr, idx, pos),
         }
     }
 
     fn emit_intrinsic_un(
         &mut self,
-        opnd: &Expr,
+        opnd: &'ast Expr,
         info: IntrinsicInfo,
         pos: Position,
         dest: DataDest,
@@ -1859,10 +2845,45 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
+    // -1, 0 or 1, NaN compares equal to all values
+    fn emit_float_compare_to(
+        &mut self,
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+        ty: BytecodeType,
+    ) {
+        let end_lbl = self.gen.create_label();
+        let cond = self.alloc_temp(BytecodeType::Bool);
+
+        self.gen.emit_const_int32(dest, -1);
+
+        if ty == BytecodeType::Float32 {
+            self.gen.emit_test_lt_float32(cond, lhs, rhs);
+        } else {
+            self.gen.emit_test_lt_float64(cond, lhs, rhs);
+        }
+
+        self.gen.emit_jump_if_true(cond, end_lbl);
+        self.gen.emit_const_int32(dest, 1);
+
+        if ty == BytecodeType::Float32 {
+            self.gen.emit_test_gt_float32(cond, lhs, rhs);
+        } else {
+            self.gen.emit_test_gt_float64(cond, lhs, rhs);
+        }
+
+        self.gen.emit_jump_if_true(cond, end_lbl);
+        self.gen.emit_const_int32(dest, 0);
+        self.gen.bind_label(end_lbl);
+
+        self.free_temp(cond);
+    }
+
     fn emit_intrinsic_bin(
         &mut self,
-        lhs: &Expr,
-        rhs: &Expr,
+        lhs: &'ast Expr,
+        rhs: &'ast Expr,
         info: IntrinsicInfo,
         op: Option<BinOp>,
         pos: Position,
@@ -1884,7 +2905,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                             assert!(dest.is_unit());
                             None
                         } else {
-                            Some(ty.into())
+                            Some(BytecodeType::from_ty(self.vm, ty))
                         }
                     };
 
@@ -1915,6 +2936,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                     BytecodeType::Float64 => self.gen.emit_load_array_float64(dest, arr, idx, pos),
                     BytecodeType::Ptr => self.gen.emit_load_array_ptr(dest, arr, idx, pos),
                     BytecodeType::Tuple(_) => self.gen.emit_load_array_tuple(dest, arr, idx, pos),
+                    BytecodeType::Struct(_) => self.gen.emit_load_array_struct(dest, arr, idx, pos),
                 }
 
                 self.free_if_temp(arr);
@@ -1967,7 +2989,16 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 Some(BinOp::Cmp(CmpOp::Le)) => self.gen.emit_test_le_uint8(dest, lhs_reg, rhs_reg),
                 Some(BinOp::Cmp(CmpOp::Gt)) => self.gen.emit_test_gt_uint8(dest, lhs_reg, rhs_reg),
                 Some(BinOp::Cmp(CmpOp::Ge)) => self.gen.emit_test_ge_uint8(dest, lhs_reg, rhs_reg),
-                _ => unreachable!(),
+                Some(_) => unreachable!(),
+                None => {
+                    let lhs_int = self.alloc_temp(BytecodeType::Int32);
+                    let rhs_int = self.alloc_temp(BytecodeType::Int32);
+                    self.gen.emit_extend_byte_to_int32(lhs_int, lhs_reg);
+                    self.gen.emit_extend_byte_to_int32(rhs_int, rhs_reg);
+                    self.gen.emit_sub_int32(dest, lhs_int, rhs_int);
+                    self.free_temp(lhs_int);
+                    self.free_temp(rhs_int);
+                }
             },
             Intrinsic::CharEq => match op {
                 Some(BinOp::Cmp(CmpOp::Eq)) => self.gen.emit_test_eq_char(dest, lhs_reg, rhs_reg),
@@ -2039,7 +3070,8 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 Some(BinOp::Cmp(CmpOp::Ge)) => {
                     self.gen.emit_test_ge_float32(dest, lhs_reg, rhs_reg)
                 }
-                _ => unreachable!(),
+                Some(_) => unreachable!(),
+                None => self.emit_float_compare_to(dest, lhs_reg, rhs_reg, BytecodeType::Float32),
             },
             Intrinsic::Float64Eq => match op {
                 Some(BinOp::Cmp(CmpOp::Eq)) => {
@@ -2064,7 +3096,8 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 Some(BinOp::Cmp(CmpOp::Ge)) => {
                     self.gen.emit_test_ge_float64(dest, lhs_reg, rhs_reg)
                 }
-                _ => unreachable!(),
+                Some(_) => unreachable!(),
+                None => self.emit_float_compare_to(dest, lhs_reg, rhs_reg, BytecodeType::Float64),
             },
             Intrinsic::Int32Add => self.gen.emit_add_int32(dest, lhs_reg, rhs_reg),
             Intrinsic::Int32Sub => self.gen.emit_sub_int32(dest, lhs_reg, rhs_reg),
@@ -2109,7 +3142,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_assign(&mut self, expr: &ExprBinType, dest: DataDest) -> Register {
+    fn visit_expr_assign(&mut self, expr: &'ast ExprBinType, dest: DataDest) -> Register {
         assert!(dest.is_unit());
 
         if expr.lhs.is_ident() {
@@ -2130,7 +3163,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         Register::invalid()
     }
 
-    fn visit_expr_assign_call(&mut self, expr: &ExprBinType, call_expr: &ExprCallType) {
+    fn visit_expr_assign_call(&mut self, expr: &'ast ExprBinType, call_expr: &'ast ExprCallType) {
         let object = &call_expr.callee;
         let index = &call_expr.args[0];
         let value = &expr.rhs;
@@ -2170,7 +3203,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
-    fn visit_expr_assign_dot(&mut self, expr: &ExprBinType, dot: &ExprDotType) {
+    fn visit_expr_assign_dot(&mut self, expr: &'ast ExprBinType, dot: &'ast ExprDotType) {
         let (class, field_id) = {
             let ident_type = self.src.map_idents.get(dot.id).unwrap();
             match ident_type {
@@ -2186,7 +3219,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let ty: Option<BytecodeType> = if field.ty.is_unit() {
             None
         } else {
-            Some(field.ty.into())
+            Some(BytecodeType::from_ty(self.vm, field.ty))
         };
 
         let obj = self.visit_expr(&dot.lhs, DataDest::Alloc);
@@ -2204,9 +3237,22 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.free_if_temp(src);
     }
 
-    fn visit_expr_assign_var(&mut self, expr: &ExprBinType, var_id: VarId) {
+    fn visit_expr_assign_var(&mut self, expr: &'ast ExprBinType, var_id: VarId) {
         let ty = self.var_ty(var_id);
 
+        if self.is_boxed(var_id) {
+            let box_def_id = specialize_box(self.vm, ty);
+            let value_reg = self.visit_expr(&expr.rhs, DataDest::Alloc);
+            let box_reg = self.load_var_box(var_id, expr.pos);
+
+            self.gen
+                .emit_store_field(value_reg, box_reg, box_def_id, 0.into(), expr.pos);
+
+            self.free_if_temp(box_reg);
+            self.free_if_temp(value_reg);
+            return;
+        }
+
         let dest = if ty.is_unit() {
             DataDest::Effect
         } else {
@@ -2217,7 +3263,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.visit_expr(&expr.rhs, dest);
     }
 
-    fn visit_expr_assign_global(&mut self, expr: &ExprBinType, gid: GlobalId) {
+    fn visit_expr_assign_global(&mut self, expr: &'ast ExprBinType, gid: GlobalId) {
         let glob = self.vm.globals.idx(gid);
         let glob = glob.read();
 
@@ -2236,15 +3282,16 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         self.free_if_temp(src);
     }
 
-    fn visit_expr_ident(&mut self, ident: &ExprIdentType, dest: DataDest) -> Register {
+    fn visit_expr_ident(&mut self, ident: &'ast ExprIdentType, dest: DataDest) -> Register {
         let ident_type = self.src.map_idents.get(ident.id).unwrap();
 
         match ident_type {
-            &IdentType::Var(varid) => self.visit_expr_ident_var(varid, dest),
+            &IdentType::Var(varid) => self.visit_expr_ident_var(varid, dest, ident.pos),
             &IdentType::Global(gid) => self.visit_expr_ident_global(gid, dest),
 
             &IdentType::Field(_, _) => unimplemented!(),
-            &IdentType::Struct(_) => unimplemented!(),
+            &IdentType::Struct(struct_id) => self.emit_new_struct(struct_id, &[], dest),
+            &IdentType::StructField(_, _) => unreachable!(),
             &IdentType::Const(cid) => self.visit_expr_ident_const(cid, dest),
 
             &IdentType::Enum(_) | &IdentType::EnumValue(_, _) => unreachable!(),
@@ -2269,7 +3316,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         let xconst = xconst.lock();
         let ty = xconst.ty;
 
-        let dest = self.ensure_register(dest, ty.into());
+        let dest = self.ensure_register(dest, BytecodeType::from_ty(self.vm, ty));
 
         match ty {
             BuiltinType::Bool => {
@@ -2325,7 +3372,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             return Register::invalid();
         }
 
-        let ty: BytecodeType = glob.ty.into();
+        let ty = BytecodeType::from_ty(self.vm, glob.ty);
         let dest = self.ensure_register(dest, ty);
 
         self.gen.emit_load_global(dest, gid);
@@ -2333,7 +3380,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         dest
     }
 
-    fn visit_expr_ident_var(&mut self, var_id: VarId, dest: DataDest) -> Register {
+    fn visit_expr_ident_var(&mut self, var_id: VarId, dest: DataDest, pos: Position) -> Register {
         if dest.is_effect() {
             return Register::invalid();
         }
@@ -2345,8 +3392,33 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             return Register::invalid();
         }
 
+        if self.is_boxed(var_id) {
+            let box_def_id = specialize_box(self.vm, ty);
+            let box_reg = self.load_var_box(var_id, pos);
+            let dest = self.ensure_register(dest, BytecodeType::from_ty(self.vm, ty));
+
+            self.gen
+                .emit_load_field(dest, box_reg, box_def_id, 0.into(), pos);
+            self.free_if_temp(box_reg);
+
+            return dest;
+        }
+
+        if let Some(field_id) = self.captured_field(var_id) {
+            let (context_reg, cls_def_id) = {
+                let lambda = self.lambda.as_ref().unwrap();
+                (lambda.reg, lambda.cls_def_id)
+            };
+            let dest = self.ensure_register(dest, BytecodeType::from_ty(self.vm, ty));
+
+            self.gen
+                .emit_load_field(dest, context_reg, cls_def_id, field_id, pos);
+
+            return dest;
+        }
+
         let var_reg = self.var_reg(var_id);
-        let ty: BytecodeType = self.specialize_type(ty).into();
+        let ty = BytecodeType::from_ty(self.vm, self.specialize_type(ty));
 
         if dest.is_alloc() {
             return var_reg;
@@ -2365,6 +3437,9 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 BytecodeType::Float64 => self.gen.emit_mov_float64(dest, var_reg),
                 BytecodeType::Ptr => self.gen.emit_mov_ptr(dest, var_reg),
                 BytecodeType::Tuple(tuple_id) => self.gen.emit_mov_tuple(dest, var_reg, tuple_id),
+                BytecodeType::Struct(struct_def_id) => {
+                    self.gen.emit_mov_struct(dest, var_reg, struct_def_id)
+                }
             }
         }
 
@@ -2396,6 +3471,35 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         panic!("no impl found for generic trait call")
     }
 
+    // reassignable variables captured by a lambda are stored in a box
+    fn is_boxed(&self, var_id: VarId) -> bool {
+        let var = &self.src.vars[var_id];
+        var.reassignable && var.captured && !self.var_ty(var_id).is_unit()
+    }
+
+    fn captured_field(&self, var_id: VarId) -> Option<FieldId> {
+        self.lambda
+            .as_ref()
+            .and_then(|lambda| lambda.fields.get(&var_id).cloned())
+    }
+
+    fn load_var_box(&mut self, var_id: VarId, pos: Position) -> Register {
+        if let Some(field_id) = self.captured_field(var_id) {
+            let (context_reg, cls_def_id) = {
+                let lambda = self.lambda.as_ref().unwrap();
+                (lambda.reg, lambda.cls_def_id)
+            };
+            let box_reg = self.alloc_temp(BytecodeType::Ptr);
+
+            self.gen
+                .emit_load_field(box_reg, context_reg, cls_def_id, field_id, pos);
+
+            box_reg
+        } else {
+            self.var_reg(var_id)
+        }
+    }
+
     fn var_reg(&self, var_id: VarId) -> Register {
         *self
             .var_registers
@@ -2446,14 +3550,12 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 fct_type_params = TypeList::empty();
             }
 
-            CallType::Trait(_, _) => unimplemented!(),
-
-            CallType::TraitStatic(_, _, _) => {
+            CallType::Trait(_, _) | CallType::TraitStatic(_, _, _) => {
                 cls_type_params = TypeList::empty();
                 fct_type_params = TypeList::empty();
             }
 
-            CallType::Intrinsic(_) => unreachable!(),
+            CallType::Intrinsic(_) | CallType::Lambda(_) => unreachable!(),
         }
 
         (cls_type_params, fct_type_params)
@@ -2505,11 +3607,11 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 specialize_type(self.vm, ty, &type_params, &TypeList::empty())
             }
 
-            CallType::Trait(_, _) => unimplemented!(),
-            CallType::TraitStatic(_, _, _) => {
+            CallType::Trait(trait_id, _) if ty == BuiltinType::This => BuiltinType::Trait(trait_id),
+            CallType::Trait(_, _) | CallType::TraitStatic(_, _, _) => {
                 specialize_type(self.vm, ty, &TypeList::empty(), &TypeList::empty())
             }
-            CallType::Intrinsic(_) => unreachable!(),
+            CallType::Intrinsic(_) | CallType::Lambda(_) => unreachable!(),
         };
 
         self.specialize_type(ty)
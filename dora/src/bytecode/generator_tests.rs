@@ -7,7 +7,10 @@ use crate::bytecode::{
 };
 use crate::test;
 use crate::ty::{BuiltinType, TypeList};
-use crate::vm::{ensure_tuple, ClassDefId, FctDef, FctDefId, FieldId, GlobalId, TupleId, VM};
+use crate::vm::{
+    ensure_tuple, ClassDefId, FctDef, FctDefId, FieldId, GlobalId, StructDefId, StructFieldId,
+    TupleId, VM,
+};
 use dora_parser::lexer::position::Position;
 
 fn code(code: &'static str) -> Vec<Bytecode> {
@@ -252,6 +255,70 @@ fn gen_stmt_while() {
     assert_eq!(code, result);
 }
 
+#[test]
+fn gen_stmt_throw() {
+    let result = code("fun f(a: Exception) throws { throw a; }");
+    let expected = vec![Throw(r(0)), RetVoid];
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn gen_expr_try() {
+    let result = code("fun g() throws -> Int32 { 1 } fun f() throws -> Int32 { try g() }");
+    let expected = vec![InvokeStatic(r(0), FctDefId(0)), Ret(r(0))];
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn gen_stmt_do_catch() {
+    gen_fct(
+        "fun g() throws {}
+        fun f() -> Int32 { do { try g(); } catch e: Exception { return 1; } return 0; }",
+        |vm, code, fct| {
+            let expected = vec![
+                InvokeStaticVoid(FctDefId(0)),
+                Jump(4),
+                ConstInt32(r(1), 1),
+                Ret(r(1)),
+                ConstZeroInt32(r(1)),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+
+            let handlers = fct.exception_handlers();
+            assert_eq!(1, handlers.len());
+            assert_eq!(
+                Some(vm.cls_def_by_name("Exception")),
+                handlers[0].catch_type
+            );
+            assert_eq!(r(0), handlers[0].register);
+        },
+    );
+}
+
+#[test]
+fn gen_stmt_defer() {
+    gen_fct(
+        "fun g() throws {} fun h() {}
+        fun f() throws { defer { h(); } try g(); }",
+        |_vm, code, fct| {
+            let expected = vec![
+                InvokeStaticVoid(FctDefId(0)),
+                InvokeStaticVoid(FctDefId(1)),
+                RetVoid,
+                InvokeStaticVoid(FctDefId(1)),
+                Throw(r(0)),
+            ];
+            assert_eq!(expected, code);
+
+            let handlers = fct.exception_handlers();
+            assert_eq!(1, handlers.len());
+            assert_eq!(None, handlers[0].catch_type);
+            assert_eq!(r(0), handlers[0].register);
+        },
+    );
+}
+
 #[test]
 fn gen_stmt_if() {
     let result = code("fun f(a: Bool) -> Int32 { if a { return 1; } return 0; }");
@@ -670,6 +737,62 @@ fn gen_expr_test_notequal_enum() {
     assert_eq!(expected, result);
 }
 
+#[test]
+fn gen_enum_variant_with_payload() {
+    gen(
+        "enum Foo { A(Int32), B } fun f(x: Int32) -> Foo { Foo::A(x) }",
+        |vm, code| {
+            let cls_id = vm.enum_variant_cls_def_by_name("Foo", "A");
+            let expected = vec![
+                NewObject(r(1), cls_id),
+                ConstInt32(r(2), 0),
+                StoreField(r(2), r(1), cls_id, FieldId::from(0)),
+                StoreField(r(0), r(1), cls_id, FieldId::from(1)),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+
+    gen(
+        "enum Foo { A(Int32), B } fun f() -> Foo { Foo::B }",
+        |vm, code| {
+            let cls_id = vm.enum_variant_cls_def_by_name("Foo", "B");
+            let expected = vec![
+                NewObject(r(0), cls_id),
+                ConstInt32(r(1), 1),
+                StoreField(r(1), r(0), cls_id, FieldId::from(0)),
+                Ret(r(0)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_match_enum_with_payload() {
+    gen(
+        "enum Foo { A(Int32), B } fun f(x: Foo) -> Int32 { match x { Foo::A(a) => a, Foo::B => 0 } }",
+        |vm, code| {
+            let cls_id = vm.enum_variant_cls_def_by_name("Foo", "A");
+            let expected = vec![
+                ConstInt32(r(3), 0),
+                LoadField(r(4), r(0), cls_id, FieldId::from(0)),
+                TestEqInt32(r(2), r(4), r(3)),
+                JumpIfFalse(r(2), 8),
+                LoadField(r(3), r(0), cls_id, FieldId::from(1)),
+                MovInt32(r(4), r(3)),
+                MovInt32(r(1), r(4)),
+                Jump(10),
+                ConstZeroInt32(r(1)),
+                Jump(10),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
 #[test]
 fn gen_expr_test_equal_int() {
     let result = code("fun f(a: Int32, b: Int32) -> Bool { return a == b; }");
@@ -2013,6 +2136,46 @@ fn gen_virtual_method_call_int_with_3_args() {
     );
 }
 
+#[test]
+fn gen_trait_object_method_call_void() {
+    gen(
+        "
+            trait Foo { fun g(); }
+            fun f(foo: Foo) { foo.g(); }
+            ",
+        |vm, code| {
+            let fct_id = vm
+                .trait_method_def_by_name("Foo", "g")
+                .expect("g not found");
+            let expected = vec![PushRegister(r(0)), InvokeTraitVoid(fct_id), RetVoid];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_trait_object_method_call_int_with_1_arg() {
+    gen(
+        "
+            trait Foo { fun g(a: Int32) -> Int32; }
+            fun f(foo: Foo) -> Int32 { return foo.g(1); }
+            ",
+        |vm, code| {
+            let fct_id = vm
+                .trait_method_def_by_name("Foo", "g")
+                .expect("g not found");
+            let expected = vec![
+                ConstInt32(r(2), 1),
+                PushRegister(r(0)),
+                PushRegister(r(2)),
+                InvokeTrait(r(1), fct_id),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
 #[test]
 fn gen_new_object() {
     gen("fun f() -> Object { return Object(); }", |vm, code| {
@@ -2727,6 +2890,50 @@ fn gen_enum_value() {
     assert_eq!(expected, result);
 }
 
+#[test]
+fn gen_match_enum() {
+    let result = code(
+        "enum MyEnum { A, B }
+        fun f(x: MyEnum) -> Int32 { match x { MyEnum::A => 1, MyEnum::B => 2 } }",
+    );
+    let expected = vec![
+        ConstInt32(r(3), 0),
+        TestEqEnum(r(2), r(0), r(3)),
+        JumpIfFalse(r(2), 5),
+        ConstInt32(r(1), 1),
+        Jump(7),
+        ConstInt32(r(1), 2),
+        Jump(7),
+        Ret(r(1)),
+    ];
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn gen_match_class_nil_check_in_last_arm() {
+    gen(
+        "@open @abstract class A class B: A class C(let x: Int32): A
+        fun f(x: A) -> Int32 { match x { B => 1, C(y) => y } }",
+        |vm, code| {
+            let b_id = vm.cls_def_by_name("B");
+            let c_id = vm.cls_def_by_name("C");
+            let expected = vec![
+                InstanceOf(r(2), r(0), b_id),
+                JumpIfFalse(r(2), 4),
+                ConstInt32(r(1), 1),
+                Jump(9),
+                NilCheck(r(0)),
+                LoadField(r(3), r(0), c_id, 0.into()),
+                MovInt32(r(4), r(3)),
+                MovInt32(r(1), r(4)),
+                Jump(9),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
 #[test]
 fn gen_string_length() {
     let result = code("fun f(x: String) -> Int64 { x.size() }");
@@ -3034,6 +3241,162 @@ fn gen_tuple_element() {
     });
 }
 
+#[test]
+fn gen_struct_var() {
+    gen(
+        "struct Foo { a: Int32, b: Int32 } fun f() { let x = Foo(1, 2); }",
+        |vm, code| {
+            let struct_def_id = vm.struct_def_by_name("Foo");
+            let expected = vec![
+                ConstInt32(r(1), 1),
+                ConstInt32(r(2), 2),
+                PushRegister(r(1)),
+                PushRegister(r(2)),
+                NewStruct(r(0), struct_def_id),
+                RetVoid,
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_struct_default_value() {
+    gen(
+        "struct Foo { a: Int32, b: String } fun f() -> Foo { defaultValue[Foo]() }",
+        |vm, code| {
+            let struct_def_id = vm.struct_def_by_name("Foo");
+            let expected = vec![
+                ConstInt32(r(1), 0),
+                ConstNil(r(2)),
+                PushRegister(r(1)),
+                PushRegister(r(2)),
+                NewStruct(r(0), struct_def_id),
+                Ret(r(0)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_struct_move() {
+    gen(
+        "struct Foo { a: Int32 } fun f(x: Foo) { let y = x; }",
+        |vm, code| {
+            let struct_def_id = vm.struct_def_by_name("Foo");
+            let expected = vec![MovStruct(r(1), r(0), struct_def_id), RetVoid];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_struct_field() {
+    gen(
+        "struct Foo { a: Int32, b: Int64 } fun f(x: Foo) -> Int64 { x.b }",
+        |vm, code| {
+            let struct_def_id = vm.struct_def_by_name("Foo");
+            let expected = vec![
+                LoadStructField(r(1), r(0), struct_def_id, 1.into()),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_match_tuple() {
+    gen(
+        "fun f(x: (Int32, Bool)) -> Int32 { match x { (a, true) => a, _ => 0 } }",
+        |vm, code| {
+            let tuple_id = ensure_tuple(vm, vec![BuiltinType::Int32, BuiltinType::Bool]);
+            let expected = vec![
+                LoadTupleElement(r(2), r(0), tuple_id, 0),
+                MovInt32(r(3), r(2)),
+                LoadTupleElement(r(4), r(0), tuple_id, 1),
+                ConstTrue(r(5)),
+                TestEqBool(r(6), r(4), r(5)),
+                JumpIfFalse(r(6), 8),
+                MovInt32(r(1), r(3)),
+                Jump(10),
+                ConstZeroInt32(r(1)),
+                Jump(10),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_lambda_call() {
+    gen("fun f(x: (Int32) -> Int32) -> Int32 { x(1) }", |_, code| {
+        let expected = vec![
+            ConstInt32(r(2), 1),
+            PushRegister(r(0)),
+            PushRegister(r(2)),
+            InvokeLambda(r(1)),
+            Ret(r(1)),
+        ];
+        assert_eq!(expected, code);
+    });
+}
+
+#[test]
+fn gen_lambda_call_void() {
+    gen("fun f(x: () -> ()) { x(); }", |_, code| {
+        let expected = vec![PushRegister(r(0)), InvokeLambdaVoid, RetVoid];
+        assert_eq!(expected, code);
+    });
+}
+
+#[test]
+fn gen_lambda_capture() {
+    gen(
+        "fun f(a: Int32) -> (Int32) -> Int32 { |b: Int32| -> Int32 { a + b } }",
+        |vm, code| {
+            let fct_def_id = match code[0] {
+                NewLambda(_, fct_def_id) => fct_def_id,
+                _ => unreachable!(),
+            };
+            let cls_def_id = vm.lambda_defs.lock()[&fct_def_id];
+            let expected = vec![
+                NewLambda(r(1), fct_def_id),
+                StoreField(r(0), r(1), cls_def_id, 0.into()),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
+#[test]
+fn gen_lambda_capture_var() {
+    gen(
+        "fun f() -> Int32 { var a = 1; let g = || { a = 2; }; a }",
+        |vm, code| {
+            let box_def_id = vm.box_defs.lock()[&BuiltinType::Int32];
+            let fct_def_id = match code[3] {
+                NewLambda(_, fct_def_id) => fct_def_id,
+                _ => unreachable!(),
+            };
+            let cls_def_id = vm.lambda_defs.lock()[&fct_def_id];
+            let expected = vec![
+                NewObject(r(0), box_def_id),
+                ConstInt32(r(1), 1),
+                StoreField(r(1), r(0), box_def_id, 0.into()),
+                NewLambda(r(2), fct_def_id),
+                StoreField(r(0), r(2), cls_def_id, 0.into()),
+                LoadField(r(1), r(0), box_def_id, 0.into()),
+                Ret(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
 fn p(line: u32, column: u32) -> Position {
     Position { line, column }
 }
@@ -3135,8 +3498,10 @@ pub enum Bytecode {
     MovFloat64(Register, Register),
     MovPtr(Register, Register),
     MovTuple(Register, Register, TupleId),
+    MovStruct(Register, Register, StructDefId),
 
     LoadTupleElement(Register, Register, TupleId, u32),
+    LoadStructField(Register, Register, StructDefId, StructFieldId),
 
     LoadField(Register, Register, ClassDefId, FieldId),
     StoreField(Register, Register, ClassDefId, FieldId),
@@ -3227,12 +3592,20 @@ pub enum Bytecode {
     InvokeVirtualVoid(FctDefId),
     InvokeVirtual(Register, FctDefId),
 
+    InvokeTraitVoid(FctDefId),
+    InvokeTrait(Register, FctDefId),
+
     InvokeStaticVoid(FctDefId),
     InvokeStatic(Register, FctDefId),
 
+    InvokeLambdaVoid,
+    InvokeLambda(Register),
+
     NewObject(Register, ClassDefId),
     NewArray(Register, ClassDefId, Register),
     NewTuple(Register, TupleId),
+    NewStruct(Register, StructDefId),
+    NewLambda(Register, FctDefId),
 
     NilCheck(Register),
 
@@ -3259,6 +3632,7 @@ pub enum Bytecode {
 
     RetVoid,
     Ret(Register),
+    Throw(Register),
     RetBool(Register),
     RetUInt8(Register),
     RetChar(Register),
@@ -3573,6 +3947,9 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
     fn visit_mov_tuple(&mut self, dest: Register, src: Register, tuple_id: TupleId) {
         self.emit(Bytecode::MovTuple(dest, src, tuple_id))
     }
+    fn visit_mov_struct(&mut self, dest: Register, src: Register, struct_def_id: StructDefId) {
+        self.emit(Bytecode::MovStruct(dest, src, struct_def_id))
+    }
 
     fn visit_load_tuple_element(
         &mut self,
@@ -3584,6 +3961,21 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
         self.emit(Bytecode::LoadTupleElement(src, dest, tuple_id, element));
     }
 
+    fn visit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        self.emit(Bytecode::LoadStructField(
+            dest,
+            src,
+            struct_def_id,
+            field_id,
+        ));
+    }
+
     fn visit_load_field(&mut self, dest: Register, obj: Register, cls: ClassDefId, field: FieldId) {
         self.emit(Bytecode::LoadField(dest, obj, cls, field));
     }
@@ -3858,6 +4250,13 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
         self.emit(Bytecode::InvokeVirtual(dest, fctdef));
     }
 
+    fn visit_invoke_trait_void(&mut self, fctdef: FctDefId) {
+        self.emit(Bytecode::InvokeTraitVoid(fctdef));
+    }
+    fn visit_invoke_trait(&mut self, dest: Register, fctdef: FctDefId) {
+        self.emit(Bytecode::InvokeTrait(dest, fctdef));
+    }
+
     fn visit_invoke_static_void(&mut self, fctdef: FctDefId) {
         self.emit(Bytecode::InvokeStaticVoid(fctdef));
     }
@@ -3865,6 +4264,14 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
         self.emit(Bytecode::InvokeStatic(dest, fctdef));
     }
 
+    fn visit_invoke_lambda_void(&mut self) {
+        self.emit(Bytecode::InvokeLambdaVoid);
+    }
+
+    fn visit_invoke_lambda(&mut self, dest: Register) {
+        self.emit(Bytecode::InvokeLambda(dest));
+    }
+
     fn visit_new_object(&mut self, dest: Register, cls: ClassDefId) {
         self.emit(Bytecode::NewObject(dest, cls));
     }
@@ -3874,6 +4281,13 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
     fn visit_new_tuple(&mut self, dest: Register, tuple_id: TupleId) {
         self.emit(Bytecode::NewTuple(dest, tuple_id));
     }
+    fn visit_new_struct(&mut self, dest: Register, struct_def_id: StructDefId) {
+        self.emit(Bytecode::NewStruct(dest, struct_def_id));
+    }
+
+    fn visit_new_lambda(&mut self, dest: Register, fctdef: FctDefId) {
+        self.emit(Bytecode::NewLambda(dest, fctdef));
+    }
 
     fn visit_nil_check(&mut self, obj: Register) {
         self.emit(Bytecode::NilCheck(obj));
@@ -3942,4 +4356,7 @@ impl<'a> BytecodeVisitor for BytecodeArrayBuilder<'a> {
     fn visit_ret(&mut self, opnd: Register) {
         self.emit(Bytecode::Ret(opnd));
     }
+    fn visit_throw(&mut self, opnd: Register) {
+        self.emit(Bytecode::Throw(opnd));
+    }
 }
@@ -1,7 +1,7 @@
 use num_traits::cast::FromPrimitive;
 
 use crate::bytecode::{BytecodeOffset, BytecodeOpcode, ConstPoolIdx, Register};
-use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, TupleId};
+use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, StructDefId, StructFieldId, TupleId};
 
 pub fn read<T: BytecodeVisitor>(data: &[u8], visitor: &mut T) {
     BytecodeReader::new(data, visitor).read();
@@ -478,6 +478,12 @@ where
                 let tuple = self.read_tuple(wide);
                 self.visitor.visit_mov_tuple(dest, src, tuple);
             }
+            BytecodeOpcode::MovStruct => {
+                let dest = self.read_register(wide);
+                let src = self.read_register(wide);
+                let struct_def_id = self.read_struct(wide);
+                self.visitor.visit_mov_struct(dest, src, struct_def_id);
+            }
 
             BytecodeOpcode::LoadTupleElement => {
                 let dest = self.read_register(wide);
@@ -497,6 +503,15 @@ where
                     .visit_store_tuple_element(src, dest, tuple, element);
             }
 
+            BytecodeOpcode::LoadStructField => {
+                let dest = self.read_register(wide);
+                let src = self.read_register(wide);
+                let struct_def_id = self.read_struct(wide);
+                let field_id = self.read_struct_field(wide);
+                self.visitor
+                    .visit_load_struct_field(dest, src, struct_def_id, field_id);
+            }
+
             BytecodeOpcode::LoadField => {
                 let dest = self.read_register(wide);
                 let obj = self.read_register(wide);
@@ -917,6 +932,16 @@ where
                 self.visitor.visit_invoke_virtual(dest, fct);
             }
 
+            BytecodeOpcode::InvokeTraitVoid => {
+                let fct = self.read_fct(wide);
+                self.visitor.visit_invoke_trait_void(fct);
+            }
+            BytecodeOpcode::InvokeTrait => {
+                let dest = self.read_register(wide);
+                let fct = self.read_fct(wide);
+                self.visitor.visit_invoke_trait(dest, fct);
+            }
+
             BytecodeOpcode::InvokeStaticVoid => {
                 let fct = self.read_fct(wide);
                 self.visitor.visit_invoke_static_void(fct);
@@ -927,6 +952,14 @@ where
                 self.visitor.visit_invoke_static(dest, fct);
             }
 
+            BytecodeOpcode::InvokeLambdaVoid => {
+                self.visitor.visit_invoke_lambda_void();
+            }
+            BytecodeOpcode::InvokeLambda => {
+                let dest = self.read_register(wide);
+                self.visitor.visit_invoke_lambda(dest);
+            }
+
             BytecodeOpcode::NewObject => {
                 let dest = self.read_register(wide);
                 let cls = self.read_class(wide);
@@ -943,6 +976,16 @@ where
                 let tuple = self.read_tuple(wide);
                 self.visitor.visit_new_tuple(dest, tuple);
             }
+            BytecodeOpcode::NewStruct => {
+                let dest = self.read_register(wide);
+                let struct_def_id = self.read_struct(wide);
+                self.visitor.visit_new_struct(dest, struct_def_id);
+            }
+            BytecodeOpcode::NewLambda => {
+                let dest = self.read_register(wide);
+                let fct = self.read_fct(wide);
+                self.visitor.visit_new_lambda(dest, fct);
+            }
 
             BytecodeOpcode::NilCheck => {
                 let obj = self.read_register(wide);
@@ -1014,6 +1057,12 @@ where
                 let index = self.read_register(wide);
                 self.visitor.visit_load_array_tuple(dest, array, index);
             }
+            BytecodeOpcode::LoadArrayStruct => {
+                let dest = self.read_register(wide);
+                let array = self.read_register(wide);
+                let index = self.read_register(wide);
+                self.visitor.visit_load_array_struct(dest, array, index);
+            }
 
             BytecodeOpcode::StoreArrayBool => {
                 let src = self.read_register(wide);
@@ -1069,6 +1118,12 @@ where
                 let index = self.read_register(wide);
                 self.visitor.visit_store_array_tuple(src, array, index);
             }
+            BytecodeOpcode::StoreArrayStruct => {
+                let src = self.read_register(wide);
+                let array = self.read_register(wide);
+                let index = self.read_register(wide);
+                self.visitor.visit_store_array_struct(src, array, index);
+            }
 
             BytecodeOpcode::RetVoid => {
                 self.visitor.visit_ret_void();
@@ -1077,6 +1132,11 @@ where
                 let opnd = self.read_register(wide);
                 self.visitor.visit_ret(opnd);
             }
+
+            BytecodeOpcode::Throw => {
+                let opnd = self.read_register(wide);
+                self.visitor.visit_throw(opnd);
+            }
         }
     }
 
@@ -1100,6 +1160,14 @@ where
         self.read_index(wide).into()
     }
 
+    fn read_struct(&mut self, wide: bool) -> StructDefId {
+        (self.read_index(wide) as usize).into()
+    }
+
+    fn read_struct_field(&mut self, wide: bool) -> StructFieldId {
+        self.read_index(wide).into()
+    }
+
     fn read_global(&mut self, wide: bool) -> GlobalId {
         self.read_index(wide).into()
     }
@@ -1411,6 +1479,9 @@ pub trait BytecodeVisitor {
     fn visit_mov_tuple(&mut self, _dest: Register, _src: Register, _tuple_id: TupleId) {
         unimplemented!();
     }
+    fn visit_mov_struct(&mut self, _dest: Register, _src: Register, _struct_def_id: StructDefId) {
+        unimplemented!();
+    }
 
     fn visit_load_tuple_element(
         &mut self,
@@ -1432,6 +1503,16 @@ pub trait BytecodeVisitor {
         unimplemented!();
     }
 
+    fn visit_load_struct_field(
+        &mut self,
+        _dest: Register,
+        _src: Register,
+        _struct_def_id: StructDefId,
+        _field_id: StructFieldId,
+    ) {
+        unimplemented!();
+    }
+
     fn visit_load_field(
         &mut self,
         _dest: Register,
@@ -1686,6 +1767,13 @@ pub trait BytecodeVisitor {
         unimplemented!();
     }
 
+    fn visit_invoke_trait_void(&mut self, _fctdef: FctDefId) {
+        unimplemented!();
+    }
+    fn visit_invoke_trait(&mut self, _dest: Register, _fctdef: FctDefId) {
+        unimplemented!();
+    }
+
     fn visit_invoke_static_void(&mut self, _fctdef: FctDefId) {
         unimplemented!();
     }
@@ -1693,6 +1781,13 @@ pub trait BytecodeVisitor {
         unimplemented!();
     }
 
+    fn visit_invoke_lambda_void(&mut self) {
+        unimplemented!();
+    }
+    fn visit_invoke_lambda(&mut self, _dest: Register) {
+        unimplemented!();
+    }
+
     fn visit_new_object(&mut self, _dest: Register, _cls: ClassDefId) {
         unimplemented!();
     }
@@ -1702,6 +1797,12 @@ pub trait BytecodeVisitor {
     fn visit_new_tuple(&mut self, _dest: Register, _tuple: TupleId) {
         unimplemented!();
     }
+    fn visit_new_struct(&mut self, _dest: Register, _struct_def_id: StructDefId) {
+        unimplemented!();
+    }
+    fn visit_new_lambda(&mut self, _dest: Register, _fctdef: FctDefId) {
+        unimplemented!();
+    }
 
     fn visit_nil_check(&mut self, _obj: Register) {
         unimplemented!();
@@ -1741,6 +1842,9 @@ pub trait BytecodeVisitor {
     fn visit_load_array_tuple(&mut self, _dest: Register, _arr: Register, _idx: Register) {
         unimplemented!();
     }
+    fn visit_load_array_struct(&mut self, _dest: Register, _arr: Register, _idx: Register) {
+        unimplemented!();
+    }
 
     fn visit_store_array_bool(&mut self, _src: Register, _arr: Register, _idx: Register) {
         unimplemented!();
@@ -1769,6 +1873,9 @@ pub trait BytecodeVisitor {
     fn visit_store_array_tuple(&mut self, _src: Register, _arr: Register, _idx: Register) {
         unimplemented!();
     }
+    fn visit_store_array_struct(&mut self, _src: Register, _arr: Register, _idx: Register) {
+        unimplemented!();
+    }
 
     fn visit_ret_void(&mut self) {
         unimplemented!();
@@ -1776,4 +1883,8 @@ pub trait BytecodeVisitor {
     fn visit_ret(&mut self, _opnd: Register) {
         unimplemented!();
     }
+
+    fn visit_throw(&mut self, _opnd: Register) {
+        unimplemented!();
+    }
 }
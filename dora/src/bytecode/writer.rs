@@ -4,9 +4,9 @@ use std::mem;
 
 use crate::bytecode::{
     BytecodeFunction, BytecodeOffset, BytecodeOpcode, BytecodeType, ConstPoolEntry, ConstPoolIdx,
-    Register,
+    ExceptionHandler, Register, RelocationKind,
 };
-use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, TupleId};
+use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, StructDefId, StructFieldId, TupleId};
 
 use dora_parser::lexer::position::Position;
 
@@ -21,11 +21,19 @@ pub struct BytecodeWriter {
     unresolved_jump_offsets: Vec<(BytecodeOffset, BytecodeOffset, Label)>,
     unresolved_jump_consts: Vec<(BytecodeOffset, ConstPoolIdx, Label)>,
 
+    // (try start, try end, catch, catch type, register, nesting depth)
+    exception_handlers: Vec<(Label, Label, Label, Option<ClassDefId>, Register, usize)>,
+
     registers: Vec<BytecodeType>,
     const_pool: Vec<ConstPoolEntry>,
 
     positions: Vec<(u32, Position)>,
     position: Option<Position>,
+
+    // relocatable bytecode encodes all operands with 32 bits, so that ids
+    // can be replaced in place when loading the bytecode into another VM
+    relocatable: bool,
+    relocations: Vec<(BytecodeOffset, RelocationKind)>,
 }
 
 impl BytecodeWriter {
@@ -38,14 +46,24 @@ impl BytecodeWriter {
             unresolved_jump_offsets: Vec::new(),
             unresolved_jump_consts: Vec::new(),
 
+            exception_handlers: Vec::new(),
+
             registers: Vec::new(),
             const_pool: Vec::new(),
 
             positions: Vec::new(),
             position: None,
+
+            relocatable: false,
+            relocations: Vec::new(),
         }
     }
 
+    pub fn set_relocatable(&mut self) {
+        assert!(self.code.is_empty());
+        self.relocatable = true;
+    }
+
     pub fn add_register(&mut self, ty: BytecodeType) -> Register {
         self.registers.push(ty);
         Register(self.registers.len() - 1)
@@ -69,6 +87,19 @@ impl BytecodeWriter {
         self.label_offsets[lbl.0] = Some(self.offset());
     }
 
+    pub fn add_exception_handler(
+        &mut self,
+        try_start: Label,
+        try_end: Label,
+        catch: Label,
+        catch_type: Option<ClassDefId>,
+        register: Register,
+        depth: usize,
+    ) {
+        self.exception_handlers
+            .push((try_start, try_end, catch, catch_type, register, depth));
+    }
+
     fn lookup_label(&self, lbl: Label) -> Option<BytecodeOffset> {
         self.label_offsets[lbl.0]
     }
@@ -513,6 +544,31 @@ impl BytecodeWriter {
         );
     }
 
+    pub fn emit_mov_struct(&mut self, dest: Register, src: Register, struct_def_id: StructDefId) {
+        self.emit_reg2_struct(BytecodeOpcode::MovStruct, dest, src, struct_def_id);
+    }
+
+    pub fn emit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        let values = [
+            dest.to_usize() as u32,
+            src.to_usize() as u32,
+            struct_def_id.to_usize() as u32,
+            field_id.to_usize() as u32,
+        ];
+        self.emit_values_with_id(
+            BytecodeOpcode::LoadStructField,
+            &values,
+            2,
+            RelocationKind::StructDef,
+        );
+    }
+
     pub fn emit_ret(&mut self, src: Register) {
         self.emit_reg1(BytecodeOpcode::Ret, src);
     }
@@ -521,6 +577,10 @@ impl BytecodeWriter {
         self.emit_op(BytecodeOpcode::RetVoid);
     }
 
+    pub fn emit_throw(&mut self, src: Register) {
+        self.emit_reg1(BytecodeOpcode::Throw, src);
+    }
+
     pub fn emit_test_eq_bool(&mut self, dest: Register, lhs: Register, rhs: Register) {
         self.emit_reg3(BytecodeOpcode::TestEqBool, dest, lhs, rhs);
     }
@@ -721,6 +781,14 @@ impl BytecodeWriter {
         self.emit_fct(BytecodeOpcode::InvokeVirtual, dest, fid);
     }
 
+    pub fn emit_invoke_trait_void(&mut self, fid: FctDefId) {
+        self.emit_fct_void(BytecodeOpcode::InvokeTraitVoid, fid);
+    }
+
+    pub fn emit_invoke_trait(&mut self, dest: Register, fid: FctDefId) {
+        self.emit_fct(BytecodeOpcode::InvokeTrait, dest, fid);
+    }
+
     pub fn emit_invoke_static_void(&mut self, fid: FctDefId) {
         self.emit_fct_void(BytecodeOpcode::InvokeStaticVoid, fid);
     }
@@ -729,6 +797,14 @@ impl BytecodeWriter {
         self.emit_fct(BytecodeOpcode::InvokeStatic, dest, fid);
     }
 
+    pub fn emit_invoke_lambda_void(&mut self) {
+        self.emit_values(BytecodeOpcode::InvokeLambdaVoid, &[]);
+    }
+
+    pub fn emit_invoke_lambda(&mut self, dest: Register) {
+        self.emit_reg1(BytecodeOpcode::InvokeLambda, dest);
+    }
+
     pub fn emit_new_object(&mut self, dest: Register, cls_id: ClassDefId) {
         self.emit_new(BytecodeOpcode::NewObject, dest, cls_id);
     }
@@ -737,7 +813,19 @@ impl BytecodeWriter {
     }
     pub fn emit_new_tuple(&mut self, dest: Register, tuple_id: TupleId) {
         let values = [dest.to_usize() as u32, tuple_id.to_usize() as u32];
-        self.emit_values(BytecodeOpcode::NewTuple, &values);
+        self.emit_values_with_id(BytecodeOpcode::NewTuple, &values, 1, RelocationKind::Tuple);
+    }
+    pub fn emit_new_struct(&mut self, dest: Register, struct_def_id: StructDefId) {
+        let values = [dest.to_usize() as u32, struct_def_id.to_usize() as u32];
+        self.emit_values_with_id(
+            BytecodeOpcode::NewStruct,
+            &values,
+            1,
+            RelocationKind::StructDef,
+        );
+    }
+    pub fn emit_new_lambda(&mut self, dest: Register, fid: FctDefId) {
+        self.emit_fct(BytecodeOpcode::NewLambda, dest, fid);
     }
 
     pub fn emit_nil_check(&mut self, obj: Register) {
@@ -778,6 +866,9 @@ impl BytecodeWriter {
     pub fn emit_store_array_tuple(&mut self, src: Register, array: Register, index: Register) {
         self.emit_reg3(BytecodeOpcode::StoreArrayTuple, src, array, index);
     }
+    pub fn emit_store_array_struct(&mut self, src: Register, array: Register, index: Register) {
+        self.emit_reg3(BytecodeOpcode::StoreArrayStruct, src, array, index);
+    }
 
     pub fn emit_load_array_uint8(&mut self, dest: Register, array: Register, idx: Register) {
         self.emit_reg3(BytecodeOpcode::LoadArrayUInt8, dest, array, idx);
@@ -806,9 +897,13 @@ impl BytecodeWriter {
     pub fn emit_load_array_tuple(&mut self, dest: Register, array: Register, index: Register) {
         self.emit_reg3(BytecodeOpcode::LoadArrayTuple, dest, array, index);
     }
+    pub fn emit_load_array_struct(&mut self, dest: Register, array: Register, index: Register) {
+        self.emit_reg3(BytecodeOpcode::LoadArrayStruct, dest, array, index);
+    }
 
     pub fn generate(mut self) -> BytecodeFunction {
         self.resolve_forward_jumps();
+        let exception_handlers = self.resolve_exception_handlers();
 
         BytecodeFunction::new(
             self.code,
@@ -816,11 +911,14 @@ impl BytecodeWriter {
             self.registers,
             self.arguments,
             self.positions,
+            exception_handlers,
+            self.relocations,
         )
     }
 
     pub fn generate_with_registers(mut self, registers: Vec<BytecodeType>) -> BytecodeFunction {
         self.resolve_forward_jumps();
+        let exception_handlers = self.resolve_exception_handlers();
 
         assert!(self.registers.is_empty());
 
@@ -830,9 +928,36 @@ impl BytecodeWriter {
             registers,
             self.arguments,
             self.positions,
+            exception_handlers,
+            self.relocations,
         )
     }
 
+    fn resolve_exception_handlers(&mut self) -> Vec<ExceptionHandler> {
+        let mut handlers = mem::replace(&mut self.exception_handlers, Vec::new());
+
+        // the innermost handler needs to be found first
+        handlers.sort_by_key(|&(_, _, _, _, _, depth)| std::cmp::Reverse(depth));
+
+        handlers
+            .into_iter()
+            .map(|(try_start, try_end, catch, catch_type, register, _)| {
+                let try_start = self.lookup_label(try_start).expect("label not bound");
+                let try_end = self.lookup_label(try_end).expect("label not bound");
+                let catch = self.lookup_label(catch).expect("label not bound");
+
+                ExceptionHandler {
+                    try_start,
+                    try_end,
+                    catch,
+                    catch_type,
+                    register,
+                }
+            })
+            .filter(|handler| handler.try_start < handler.try_end)
+            .collect()
+    }
+
     fn resolve_forward_jumps(&mut self) {
         let unresolved_jumps = mem::replace(&mut self.unresolved_jump_offsets, Vec::new());
 
@@ -899,7 +1024,22 @@ impl BytecodeWriter {
             r2.to_usize() as u32,
             tuple_id.to_usize() as u32,
         ];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 2, RelocationKind::Tuple);
+    }
+
+    fn emit_reg2_struct(
+        &mut self,
+        inst: BytecodeOpcode,
+        r1: Register,
+        r2: Register,
+        struct_def_id: StructDefId,
+    ) {
+        let values = [
+            r1.to_usize() as u32,
+            r2.to_usize() as u32,
+            struct_def_id.to_usize() as u32,
+        ];
+        self.emit_values_with_id(inst, &values, 2, RelocationKind::StructDef);
     }
 
     fn emit_reg2_cls(
@@ -914,7 +1054,7 @@ impl BytecodeWriter {
             r2.to_usize() as u32,
             cls_id.to_usize() as u32,
         ];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 2, RelocationKind::ClassDef);
     }
 
     fn emit_reg1(&mut self, inst: BytecodeOpcode, r1: Register) {
@@ -924,7 +1064,7 @@ impl BytecodeWriter {
 
     fn emit_reg1_cls(&mut self, inst: BytecodeOpcode, r1: Register, cls_id: ClassDefId) {
         let values = [r1.to_usize() as u32, cls_id.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::ClassDef);
     }
 
     fn emit_reg1_idx(&mut self, inst: BytecodeOpcode, r1: Register, idx: ConstPoolIdx) {
@@ -946,7 +1086,7 @@ impl BytecodeWriter {
 
     fn emit_new(&mut self, inst: BytecodeOpcode, r1: Register, cid: ClassDefId) {
         let values = [r1.to_usize() as u32, cid.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::ClassDef);
     }
 
     fn emit_new_arr(&mut self, inst: BytecodeOpcode, r1: Register, cid: ClassDefId, lth: Register) {
@@ -955,17 +1095,17 @@ impl BytecodeWriter {
             cid.to_usize() as u32,
             lth.to_usize() as u32,
         ];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::ClassDef);
     }
 
     fn emit_fct_void(&mut self, inst: BytecodeOpcode, fid: FctDefId) {
         let values = [fid.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 0, RelocationKind::FctDef);
     }
 
     fn emit_fct(&mut self, inst: BytecodeOpcode, r1: Register, fid: FctDefId) {
         let values = [r1.to_usize() as u32, fid.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::FctDef);
     }
 
     fn emit_access_field(
@@ -982,7 +1122,7 @@ impl BytecodeWriter {
             cid.to_usize() as u32,
             fid.to_usize() as u32,
         ];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 2, RelocationKind::ClassDef);
     }
 
     fn emit_access_tuple(
@@ -999,17 +1139,17 @@ impl BytecodeWriter {
             tuple_id.to_usize() as u32,
             element,
         ];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 2, RelocationKind::Tuple);
     }
 
     fn emit_load_global_inst(&mut self, inst: BytecodeOpcode, r1: Register, gid: GlobalId) {
         let values = [r1.to_usize() as u32, gid.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::Global);
     }
 
     fn emit_store_global_inst(&mut self, inst: BytecodeOpcode, r1: Register, gid: GlobalId) {
         let values = [r1.to_usize() as u32, gid.to_usize() as u32];
-        self.emit_values(inst, &values);
+        self.emit_values_with_id(inst, &values, 1, RelocationKind::Global);
     }
 
     pub fn set_position(&mut self, pos: Position) {
@@ -1048,7 +1188,7 @@ impl BytecodeWriter {
             assert!(self.position.is_none());
         }
 
-        let is_wide = values.iter().any(|&val| val > u8::max_value() as u32);
+        let is_wide = self.relocatable || values.iter().any(|&val| val > u8::max_value() as u32);
 
         if is_wide {
             self.emit_wide();
@@ -1064,6 +1204,22 @@ impl BytecodeWriter {
         }
     }
 
+    // `id` is the index of the operand holding an id
+    fn emit_values_with_id(
+        &mut self,
+        op: BytecodeOpcode,
+        values: &[u32],
+        id: usize,
+        kind: RelocationKind,
+    ) {
+        self.emit_values(op, values);
+
+        if self.relocatable {
+            let offset = self.code.len() - (values.len() - id) * 4;
+            self.relocations.push((BytecodeOffset(offset as u32), kind));
+        }
+    }
+
     fn emit_opcode(&mut self, code: u32) {
         if code >= 255 {
             self.emit_u8(255);
@@ -1093,7 +1249,8 @@ impl BytecodeWriter {
         debug_assert!(fits_u8(inst_const as u32));
         let start = self.offset();
 
-        if (cond.is_some() && !fits_u8(cond.unwrap().to_usize() as u32))
+        if self.relocatable
+            || (cond.is_some() && !fits_u8(cond.unwrap().to_usize() as u32))
             || !fits_u8(self.const_pool.len() as u32)
         {
             self.emit_wide();
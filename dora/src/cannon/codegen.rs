@@ -16,12 +16,12 @@ use crate::gc::Address;
 use crate::masm::*;
 use crate::mem::{self, align_i32};
 use crate::object::{offset_of_array_data, Header, Str};
-use crate::semck::specialize::specialize_type;
+use crate::semck::specialize::{specialize_lambda, specialize_type};
 use crate::size::InstanceSize;
 use crate::ty::{BuiltinType, MachineMode, TypeList};
 use crate::vm::{
-    ClassDefId, Fct, FctDef, FctDefId, FctId, FctKind, FctSrc, FieldId, GlobalId, Intrinsic, Trap,
-    TupleId, VM,
+    ClassDef, ClassDefId, Fct, FctDef, FctDefId, FctId, FctKind, FctSrc, FieldDef, FieldId,
+    GlobalId, Intrinsic, StructDefId, StructFieldId, Trap, TupleId, VM,
 };
 use crate::vtable::{VTable, DISPLAY_SIZE};
 
@@ -140,7 +140,11 @@ where
 
         bytecode::read(self.bytecode.code(), &mut self);
 
+        let code_end = BytecodeOffset(self.bytecode.code().len() as u32);
+        self.offset_to_address.insert(code_end, self.asm.pos());
+
         self.resolve_forward_jumps();
+        self.emit_exception_handlers();
 
         let jit_fct = self
             .asm
@@ -183,15 +187,10 @@ where
     fn initialize_references(&mut self) {
         assert!(self.references.is_empty());
         for (idx, &ty) in self.bytecode.registers().iter().enumerate() {
-            if ty.is_ptr() {
-                let offset = self.register_offset(Register(idx));
-                self.references.push(offset);
-            } else if let Some(tuple_id) = ty.tuple_id() {
-                let offset = self.register_offset(Register(idx));
-                let tuples = self.vm.tuples.lock();
-                for &ref_offset in tuples.get_tuple(tuple_id).references() {
-                    self.references.push(offset + ref_offset);
-                }
+            let offset = self.register_offset(Register(idx));
+
+            for ref_offset in self.reference_offsets(ty) {
+                self.references.push(offset + ref_offset);
             }
         }
     }
@@ -202,7 +201,7 @@ where
 
     fn has_result_address(&self) -> bool {
         let return_type = self.specialize_type(self.fct.return_type);
-        return_type.is_tuple()
+        return_type.is_tuple_or_struct()
     }
 
     fn store_params_on_stack(&mut self) {
@@ -234,29 +233,35 @@ where
                 assert_eq!(self.bytecode.register_type(dest), BytecodeType::Ptr);
                 BuiltinType::Ptr
             } else {
-                assert_eq!(self.bytecode.register_type(dest), param_ty.into());
+                assert_eq!(
+                    self.bytecode.register_type(dest),
+                    BytecodeType::from_ty(self.vm, param_ty)
+                );
                 param_ty
             };
 
-            if let Some(tuple_id) = param_ty.tuple_id() {
+            if param_ty.is_tuple_or_struct() {
+                let bytecode_type = self.bytecode.register_type(dest);
                 let dest_offset = self.register_offset(dest);
 
+                // tuples and structs are passed as pointer to a copy owned by the caller
                 if reg_idx < REG_PARAMS.len() {
                     self.asm.copy(
                         MachineMode::Ptr,
                         REG_TMP1.into(),
                         REG_PARAMS[reg_idx].into(),
                     );
-                    self.copy_tuple(
-                        tuple_id,
+                    self.copy_value(
+                        bytecode_type,
                         RegOrOffset::Offset(dest_offset),
                         RegOrOffset::Reg(REG_TMP1),
                     );
+                    reg_idx += 1;
                 } else {
                     self.asm
                         .load_mem(MachineMode::Ptr, REG_TMP1.into(), Mem::Local(sp_offset));
-                    self.copy_tuple(
-                        tuple_id,
+                    self.copy_value(
+                        bytecode_type,
                         RegOrOffset::Offset(dest_offset),
                         RegOrOffset::Reg(REG_TMP1),
                     );
@@ -1054,17 +1059,18 @@ where
         self.emit_store_register(reg.into(), dest);
     }
 
-    fn emit_mov_tuple(&mut self, dest: Register, src: Register, tuple_id: TupleId) {
+    fn emit_mov_value(&mut self, dest: Register, src: Register) {
         assert_eq!(
             self.bytecode.register_type(src),
             self.bytecode.register_type(dest)
         );
 
+        let bytecode_type = self.bytecode.register_type(src);
         let src_offset = self.register_offset(src);
         let dest_offset = self.register_offset(dest);
 
-        self.copy_tuple(
-            tuple_id,
+        self.copy_value(
+            bytecode_type,
             RegOrOffset::Offset(dest_offset),
             RegOrOffset::Offset(src_offset),
         );
@@ -1077,15 +1083,35 @@ where
         tuple_id: TupleId,
         idx: u32,
     ) {
-        let dest_type = self.bytecode.register_type(dest);
         let (_ty, offset) = self.vm.tuples.lock().get_at(tuple_id, idx as usize);
+        self.emit_load_value_element(dest, src, offset);
+    }
+
+    fn emit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        let offset = {
+            let struct_def = self.vm.struct_defs.idx(struct_def_id);
+            let struct_def = struct_def.lock();
+            struct_def.fields[field_id.to_usize()].offset
+        };
+
+        self.emit_load_value_element(dest, src, offset);
+    }
+
+    fn emit_load_value_element(&mut self, dest: Register, src: Register, offset: i32) {
+        let dest_type = self.bytecode.register_type(dest);
         let src_offset = self.register_offset(src);
 
-        if let Some(_tuple_id) = dest_type.tuple_id() {
+        if dest_type.is_tuple_or_struct() {
             let dest_offset = self.register_offset(dest);
 
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                dest_type,
                 RegOrOffset::Offset(dest_offset),
                 RegOrOffset::Offset(src_offset + offset),
             );
@@ -1098,59 +1124,54 @@ where
         }
     }
 
-    fn copy_tuple(&mut self, tuple_id: TupleId, dest: RegOrOffset, src: RegOrOffset) {
-        let subtypes = self.vm.tuples.lock().get(tuple_id);
-        let offsets = self
-            .vm
-            .tuples
-            .lock()
-            .get_tuple(tuple_id)
-            .offsets()
-            .to_owned();
-
-        for (&subtype, &subtype_offset) in subtypes.iter().zip(&offsets) {
-            if let Some(tuple_id) = subtype.tuple_id() {
-                let src = match src {
-                    RegOrOffset::Reg(reg) => RegOrOffset::RegWithOffset(reg, subtype_offset),
-                    RegOrOffset::RegWithOffset(reg, tuple_offset) => {
-                        RegOrOffset::RegWithOffset(reg, tuple_offset + subtype_offset)
-                    }
-                    RegOrOffset::Offset(tuple_offset) => {
-                        RegOrOffset::Offset(tuple_offset + subtype_offset)
-                    }
-                };
-                let dest = match dest {
-                    RegOrOffset::Reg(reg) => RegOrOffset::RegWithOffset(reg, subtype_offset),
-                    RegOrOffset::RegWithOffset(reg, tuple_offset) => {
-                        RegOrOffset::RegWithOffset(reg, tuple_offset + subtype_offset)
-                    }
-                    RegOrOffset::Offset(tuple_offset) => {
-                        RegOrOffset::Offset(tuple_offset + subtype_offset)
-                    }
-                };
-                self.copy_tuple(tuple_id, dest, src);
-            } else if subtype.is_unit() {
-                // nothing
+    fn copy_value(&mut self, ty: BytecodeType, dest: RegOrOffset, src: RegOrOffset) {
+        for (subtype, subtype_offset) in self.value_fields(ty) {
+            if subtype.is_unit() {
+                continue;
+            }
+
+            let subtype = BytecodeType::from_ty(self.vm, subtype);
+            let src = src.offset(subtype_offset);
+            let dest = dest.offset(subtype_offset);
+
+            if subtype.is_tuple_or_struct() {
+                self.copy_value(subtype, dest, src);
             } else {
                 let mode = subtype.mode();
                 let tmp = result_reg_mode(mode);
-                let src = match src {
-                    RegOrOffset::Reg(reg) => Mem::Base(reg, subtype_offset),
-                    RegOrOffset::RegWithOffset(reg, tuple_offset) => {
-                        Mem::Base(reg, tuple_offset + subtype_offset)
-                    }
-                    RegOrOffset::Offset(tuple_offset) => Mem::Local(tuple_offset + subtype_offset),
-                };
-                self.asm.load_mem(mode, tmp, src);
-                let dest = match dest {
-                    RegOrOffset::Reg(reg) => Mem::Base(reg, subtype_offset),
-                    RegOrOffset::RegWithOffset(reg, tuple_offset) => {
-                        Mem::Base(reg, tuple_offset + subtype_offset)
-                    }
-                    RegOrOffset::Offset(tuple_offset) => Mem::Local(tuple_offset + subtype_offset),
-                };
-                self.asm.store_mem(mode, dest, tmp);
+                self.asm.load_mem(mode, tmp, src.mem());
+                self.asm.store_mem(mode, dest.mem(), tmp);
+            }
+        }
+    }
+
+    // types and offsets of all elements of a tuple or all fields of a struct
+    fn value_fields(&self, ty: BytecodeType) -> Vec<(BuiltinType, i32)> {
+        match ty {
+            BytecodeType::Tuple(tuple_id) => {
+                let tuples = self.vm.tuples.lock();
+                let tuple = tuples.get_tuple(tuple_id);
+
+                tuple
+                    .args()
+                    .iter()
+                    .cloned()
+                    .zip(tuple.offsets().iter().cloned())
+                    .collect()
+            }
+
+            BytecodeType::Struct(struct_def_id) => {
+                let struct_def = self.vm.struct_defs.idx(struct_def_id);
+                let struct_def = struct_def.lock();
+
+                struct_def
+                    .fields
+                    .iter()
+                    .map(|field| (field.ty, field.offset))
+                    .collect()
             }
+
+            _ => unreachable!(),
         }
     }
 
@@ -1167,16 +1188,14 @@ where
         let cls = cls.read();
         let field = &cls.fields[field_id.idx()];
 
-        assert_eq!(self.bytecode.register_type(dest), field.ty.into());
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::from_ty(self.vm, field.ty)
+        );
 
         {
             let cname = cls.name(self.vm);
-
-            let cls_id = cls.cls_id.expect("no corresponding class");
-            let class = self.vm.classes.idx(cls_id);
-            let class = class.read();
-            let field = &class.fields[field_id.idx()];
-            let fname = self.vm.interner.str(field.name);
+            let fname = self.field_name(&*cls, field_id);
 
             self.asm
                 .emit_comment(format!("load field {}.{}", cname, fname));
@@ -1193,10 +1212,20 @@ where
 
         self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
 
-        if let Some(tuple_id) = bytecode_type.tuple_id() {
+        // Weak fields aren't traced by concurrent marking, the referent
+        // might get stored into an object that was traced already.
+        // Recording it keeps the referent alive.
+        if self.vm.gc.needs_pre_write_barrier() && self.is_weak_field(&*cls, field) {
+            let gcpoint = self.create_gcpoint();
+            self.asm
+                .emit_pre_barrier(Mem::Base(obj_reg, field.offset), pos, gcpoint);
+            self.emit_load_register(obj, obj_reg.into());
+        }
+
+        if bytecode_type.is_tuple_or_struct() {
             let dest_offset = self.register_offset(dest);
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                bytecode_type,
                 RegOrOffset::Offset(dest_offset),
                 RegOrOffset::RegWithOffset(obj_reg, field.offset),
             );
@@ -1222,16 +1251,14 @@ where
         let cls = cls.read();
         let field = &cls.fields[field_id.idx()];
 
-        assert_eq!(self.bytecode.register_type(src), field.ty.into());
+        assert_eq!(
+            self.bytecode.register_type(src),
+            BytecodeType::from_ty(self.vm, field.ty)
+        );
 
         {
             let cname = cls.name(self.vm);
-
-            let cls_id = cls.cls_id.expect("no corresponding class");
-            let class = self.vm.classes.idx(cls_id);
-            let class = class.read();
-            let field = &class.fields[field_id.idx()];
-            let fname = self.vm.interner.str(field.name);
+            let fname = self.field_name(&*cls, field_id);
 
             self.asm
                 .emit_comment(format!("store field {}.{}", cname, fname));
@@ -1247,28 +1274,33 @@ where
         let pos = self.bytecode.offset_position(self.current_offset.to_u32());
         self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
 
-        let needs_write_barrier = if let Some(tuple_id) = bytecode_type.tuple_id() {
+        let references = self.reference_offsets(bytecode_type);
+        let needs_write_barrier = !references.is_empty();
+
+        if self.vm.gc.needs_pre_write_barrier() {
+            // the slow path clobbers obj_reg, reload it after every barrier
+            for offset in references {
+                let gcpoint = self.create_gcpoint();
+                self.asm
+                    .emit_pre_barrier(Mem::Base(obj_reg, field.offset + offset), pos, gcpoint);
+                self.emit_load_register(obj, obj_reg.into());
+            }
+        }
+
+        if bytecode_type.is_tuple_or_struct() {
             let src_offset = self.register_offset(src);
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                bytecode_type,
                 RegOrOffset::RegWithOffset(obj_reg, field.offset),
                 RegOrOffset::Offset(src_offset),
             );
-
-            self.vm
-                .tuples
-                .lock()
-                .get_tuple(tuple_id)
-                .contains_references()
         } else {
             let value = result_reg(bytecode_type);
 
             self.emit_load_register(src, value.into());
             self.asm
                 .store_mem(field.ty.mode(), Mem::Base(obj_reg, field.offset), value);
-
-            field.ty.reference_type()
-        };
+        }
 
         if self.vm.gc.needs_write_barrier() && needs_write_barrier {
             let card_table_offset = self.vm.gc.card_table_offset();
@@ -1276,11 +1308,45 @@ where
         }
     }
 
+    // offsets of all references in a value of the given type
+    fn reference_offsets(&self, ty: BytecodeType) -> Vec<i32> {
+        BuiltinType::from(ty).reference_offsets(self.vm)
+    }
+
+    fn field_name(&self, cls: &ClassDef, field_id: FieldId) -> String {
+        if let Some(cls_id) = cls.cls_id {
+            let class = self.vm.classes.idx(cls_id);
+            let class = class.read();
+            let field = &class.fields[field_id.idx()];
+
+            self.vm.interner.str(field.name).to_string()
+        } else {
+            // context objects and boxes only have anonymous fields
+            format!("{}", field_id.idx())
+        }
+    }
+
+    // mirrors the reference fields excluded from tracing in specialize_class
+    fn is_weak_field(&self, cls: &ClassDef, field: &FieldDef) -> bool {
+        let is_weak = match cls.cls_id {
+            Some(cls_id) => self.vm.classes.idx(cls_id).read().is_weak,
+            None => false,
+        };
+
+        is_weak
+            && self.vm.gc.weak_refs().is_some()
+            && cls.fields[0].ty.reference_type(self.vm)
+            && field.ty.reference_type(self.vm)
+    }
+
     fn emit_load_global(&mut self, dest: Register, global_id: GlobalId) {
         let glob = self.vm.globals.idx(global_id);
         let glob = glob.read();
 
-        assert_eq!(self.bytecode.register_type(dest), glob.ty.into());
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::from_ty(self.vm, glob.ty)
+        );
 
         let name = self.vm.interner.str(glob.name);
         self.asm.emit_comment(format!("load global {}", name));
@@ -1298,10 +1364,10 @@ where
 
         let bytecode_type = self.bytecode.register_type(dest);
 
-        if let Some(tuple_id) = bytecode_type.tuple_id() {
+        if bytecode_type.is_tuple_or_struct() {
             let dest_offset = self.register_offset(dest);
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                bytecode_type,
                 RegOrOffset::Offset(dest_offset),
                 RegOrOffset::Reg(REG_TMP1),
             );
@@ -1319,7 +1385,10 @@ where
         let glob = self.vm.globals.idx(global_id);
         let glob = glob.read();
 
-        assert_eq!(self.bytecode.register_type(src), glob.ty.into());
+        assert_eq!(
+            self.bytecode.register_type(src),
+            BytecodeType::from_ty(self.vm, glob.ty)
+        );
 
         let disp = self.asm.add_addr(glob.address_value.to_ptr());
         let pos = self.asm.pos() as i32;
@@ -1330,10 +1399,10 @@ where
 
         let bytecode_type = self.bytecode.register_type(src);
 
-        if let Some(tuple_id) = bytecode_type.tuple_id() {
+        if bytecode_type.is_tuple_or_struct() {
             let src_offset = self.register_offset(src);
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                bytecode_type,
                 RegOrOffset::Reg(REG_TMP1),
                 RegOrOffset::Offset(src_offset),
             );
@@ -1498,7 +1567,7 @@ where
     fn emit_return_generic(&mut self, src: Register) {
         let bytecode_type = self.bytecode.register_type(src);
 
-        if let Some(tuple_id) = bytecode_type.tuple_id() {
+        if bytecode_type.is_tuple_or_struct() {
             let src_offset = self.register_offset(src);
 
             self.asm.load_mem(
@@ -1507,8 +1576,8 @@ where
                 Mem::Local(result_address_offset()),
             );
 
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                bytecode_type,
                 RegOrOffset::Reg(REG_TMP1),
                 RegOrOffset::Offset(src_offset),
             );
@@ -1520,6 +1589,18 @@ where
         self.emit_epilog();
     }
 
+    fn emit_exception_handlers(&mut self) {
+        for handler in self.bytecode.exception_handlers() {
+            let try_start = self.offset_to_address[&handler.try_start];
+            let try_end = self.offset_to_address[&handler.try_end];
+            let catch = self.offset_to_address[&handler.catch];
+            let offset = self.register_offset(handler.register);
+
+            self.asm
+                .emit_exception_handler(try_start, try_end, catch, offset, handler.catch_type);
+        }
+    }
+
     fn resolve_forward_jumps(&mut self) {
         for jump in &self.forward_jumps {
             let offset = *self
@@ -1696,47 +1777,42 @@ where
             .fill_zero_dynamic(array_data_start, array_data_limit);
     }
 
-    fn emit_new_tuple(&mut self, dest: Register, tuple_id: TupleId) {
-        let subtypes = self.vm.tuples.lock().get(tuple_id);
-        let offsets = self
-            .vm
-            .tuples
-            .lock()
-            .get_tuple(tuple_id)
-            .offsets()
-            .to_owned();
+    fn emit_new_value(&mut self, dest: Register) {
+        let dest_type = self.bytecode.register_type(dest);
         let dest_offset = self.register_offset(dest);
-        let mut arg_idx = 0;
-        let arguments = std::mem::replace(&mut self.argument_stack, Vec::new());
+        let mut arguments = std::mem::replace(&mut self.argument_stack, Vec::new()).into_iter();
+
+        for (subtype, subtype_offset) in self.value_fields(dest_type) {
+            if subtype.is_unit() {
+                continue;
+            }
+
+            let src = arguments.next().expect("missing argument");
+            let src_type = self.bytecode.register_type(src);
+            assert_eq!(src_type, BytecodeType::from_ty(self.vm, subtype));
 
-        for (&subtype, &subtype_offset) in subtypes.iter().zip(&offsets) {
-            if let Some(tuple_id) = subtype.tuple_id() {
-                let src = arguments[arg_idx];
+            if src_type.is_tuple_or_struct() {
                 let src_offset = self.register_offset(src);
 
-                self.copy_tuple(
-                    tuple_id,
+                self.copy_value(
+                    src_type,
                     RegOrOffset::Offset(dest_offset + subtype_offset),
                     RegOrOffset::Offset(src_offset),
                 );
-            } else if subtype.is_unit() {
-                // nothing
             } else {
-                let subtype_reg = arguments[arg_idx];
-                let dest_type = self.bytecode.register_type(subtype_reg);
-                let tmp = result_reg(dest_type);
+                let tmp = result_reg(src_type);
 
-                self.emit_load_register(arguments[arg_idx], tmp);
+                self.emit_load_register(src, tmp);
 
                 self.asm.store_mem(
-                    dest_type.mode(),
+                    src_type.mode(),
                     Mem::Local(dest_offset + subtype_offset),
                     tmp,
                 );
-
-                arg_idx += 1;
             }
         }
+
+        assert!(arguments.next().is_none());
     }
 
     fn emit_nil_check(&mut self, obj: Register) {
@@ -1804,25 +1880,41 @@ where
         }
 
         let src_type = self.bytecode.register_type(src);
+        let references = self.reference_offsets(src_type);
+
+        if self.vm.gc.needs_pre_write_barrier() {
+            let element_size = if src_type.is_tuple_or_struct() {
+                src_type.size()
+            } else {
+                src_type.mode().size()
+            };
 
-        if let Some(tuple_id) = src_type.tuple_id() {
-            let element_size = self.vm.tuples.lock().get_tuple(tuple_id).size();
+            // the slow path clobbers all registers, reload array and index
+            // after every barrier
+            for offset in references {
+                self.asm
+                    .array_address(REG_TMP2, REG_RESULT, REG_TMP1, element_size);
+                let gcpoint = self.create_gcpoint();
+                self.asm
+                    .emit_pre_barrier(Mem::Base(REG_TMP2, offset), position, gcpoint);
+                self.emit_load_register(arr, REG_RESULT.into());
+                self.emit_load_register(idx, REG_TMP1.into());
+            }
+        }
+
+        if src_type.is_tuple_or_struct() {
+            let element_size = src_type.size();
             self.asm
                 .array_address(REG_TMP1, REG_RESULT, REG_TMP1, element_size);
             let src_offset = self.register_offset(src);
 
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                src_type,
                 RegOrOffset::Reg(REG_TMP1),
                 RegOrOffset::Offset(src_offset),
             );
 
-            let needs_write_barrier = self
-                .vm
-                .tuples
-                .lock()
-                .get_tuple(tuple_id)
-                .contains_references();
+            let needs_write_barrier = !self.reference_offsets(src_type).is_empty();
 
             if self.vm.gc.needs_write_barrier() && needs_write_barrier {
                 let card_table_offset = self.vm.gc.card_table_offset();
@@ -1886,14 +1978,14 @@ where
 
         let dest_type = self.bytecode.register_type(dest);
 
-        if let Some(tuple_id) = dest_type.tuple_id() {
-            let element_size = self.vm.tuples.lock().get_tuple(tuple_id).size();
+        if dest_type.is_tuple_or_struct() {
+            let element_size = dest_type.size();
             self.asm
                 .array_address(REG_TMP1, REG_RESULT, REG_TMP1, element_size);
             let dest_offset = self.register_offset(dest);
 
-            self.copy_tuple(
-                tuple_id,
+            self.copy_value(
+                dest_type,
                 RegOrOffset::Offset(dest_offset),
                 RegOrOffset::Reg(REG_TMP1),
             );
@@ -1937,7 +2029,9 @@ where
         );
 
         let result_register = match fct_return_type {
-            BuiltinType::Tuple(_) => Some(dest.expect("need register for tuple result")),
+            ty if ty.is_tuple_or_struct() => {
+                Some(dest.expect("need register for tuple or struct result"))
+            }
             _ => None,
         };
 
@@ -1949,7 +2043,9 @@ where
         let gcpoint = self.create_gcpoint();
 
         let (reg, ty) = match bytecode_type {
-            Some(BytecodeType::Tuple(_)) => (REG_RESULT.into(), BuiltinType::Unit),
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => {
+                (REG_RESULT.into(), BuiltinType::Unit)
+            }
             Some(bytecode_type) => (result_reg(bytecode_type), bytecode_type.into()),
             None => (REG_RESULT.into(), BuiltinType::Unit),
         };
@@ -1968,7 +2064,121 @@ where
         self.asm.decrease_stack_frame(argsize);
 
         if let Some(dest) = dest {
-            if !fct_return_type.is_tuple() {
+            if !fct_return_type.is_tuple_or_struct() {
+                self.emit_store_register(reg, dest);
+            }
+        }
+    }
+
+    fn emit_invoke_trait(&mut self, dest: Option<Register>, fct_def_id: FctDefId) {
+        let bytecode_type = if let Some(dest) = dest {
+            Some(self.bytecode.register_type(dest))
+        } else {
+            None
+        };
+
+        let arguments = std::mem::replace(&mut self.argument_stack, Vec::new());
+        let self_register = arguments[0];
+
+        let bytecode_type_self = self.bytecode.register_type(self_register);
+        let position = self.bytecode.offset_position(self.current_offset.to_u32());
+        assert_eq!(bytecode_type_self, BytecodeType::Ptr);
+
+        let fct_def = self.vm.fct_defs.idx(fct_def_id);
+        let fct_def = fct_def.read();
+
+        let fct_id = fct_def.fct_id;
+        let fct = self.vm.fcts.idx(fct_id);
+        let fct = fct.read();
+
+        let trait_id = fct.trait_id();
+        let method_index = self.vm.traits[trait_id]
+            .read()
+            .methods
+            .iter()
+            .position(|&method_id| method_id == fct_id)
+            .expect("method not found in trait") as u32;
+
+        let result_register = match bytecode_type {
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => dest,
+            _ => None,
+        };
+
+        let argsize = self.emit_invoke_arguments(result_register, arguments);
+
+        let name = fct.full_name(self.vm);
+        self.asm.emit_comment(format!("call trait {}", name));
+        let gcpoint = self.create_gcpoint();
+
+        let (reg, ty) = match bytecode_type {
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => {
+                (REG_RESULT.into(), BuiltinType::Unit)
+            }
+            Some(bytecode_type) => (result_reg(bytecode_type), bytecode_type.into()),
+            None => (REG_RESULT.into(), BuiltinType::Unit),
+        };
+
+        let self_index = if result_register.is_some() { 1 } else { 0 };
+        self.asm.trait_call(
+            trait_id,
+            method_index,
+            self_index,
+            position,
+            gcpoint,
+            ty,
+            reg,
+        );
+
+        self.asm.decrease_stack_frame(argsize);
+
+        if let Some(dest) = dest {
+            if result_register.is_none() {
+                self.emit_store_register(reg, dest);
+            }
+        }
+    }
+
+    fn emit_invoke_lambda(&mut self, dest: Option<Register>) {
+        let bytecode_type = if let Some(dest) = dest {
+            Some(self.bytecode.register_type(dest))
+        } else {
+            None
+        };
+
+        let arguments = std::mem::replace(&mut self.argument_stack, Vec::new());
+        let lambda_register = arguments[0];
+
+        let bytecode_type_lambda = self.bytecode.register_type(lambda_register);
+        let position = self.bytecode.offset_position(self.current_offset.to_u32());
+        assert_eq!(bytecode_type_lambda, BytecodeType::Ptr);
+
+        let result_register = match bytecode_type {
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => dest,
+            _ => None,
+        };
+
+        let argsize = self.emit_invoke_arguments(result_register, arguments);
+
+        self.asm.emit_comment("call lambda".into());
+        let gcpoint = self.create_gcpoint();
+
+        let (reg, ty) = match bytecode_type {
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => {
+                (REG_RESULT.into(), BuiltinType::Unit)
+            }
+            Some(bytecode_type) => (result_reg(bytecode_type), bytecode_type.into()),
+            None => (REG_RESULT.into(), BuiltinType::Unit),
+        };
+
+        // code of the lambda is the only entry in the vtable of its context object
+        let self_index = if result_register.is_some() { 1 } else { 0 };
+        self.asm
+            .indirect_call(0, self_index, position, gcpoint, ty, TypeList::empty(), reg);
+
+        self.asm.decrease_stack_frame(argsize);
+
+        if let Some(dest) = dest {
+            if result_register.is_none() {
                 self.emit_store_register(reg, dest);
             }
         }
@@ -2007,7 +2217,9 @@ where
             specialize_type(self.vm, fct.return_type, &cls_type_params, &fct_type_params);
 
         let result_register = match fct_return_type {
-            BuiltinType::Tuple(_) => Some(dest.expect("need register for tuple result")),
+            ty if ty.is_tuple_or_struct() => {
+                Some(dest.expect("need register for tuple or struct result"))
+            }
             _ => None,
         };
 
@@ -2019,7 +2231,9 @@ where
         let gcpoint = self.create_gcpoint();
 
         let (reg, ty) = match bytecode_type {
-            Some(BytecodeType::Tuple(_)) => (REG_RESULT.into(), BuiltinType::Unit),
+            Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => {
+                (REG_RESULT.into(), BuiltinType::Unit)
+            }
             Some(bytecode_type) => (result_reg(bytecode_type), bytecode_type.into()),
             None => (REG_RESULT.into(), BuiltinType::Unit),
         };
@@ -2037,7 +2251,7 @@ where
         self.asm.decrease_stack_frame(argsize);
 
         if let Some(dest) = dest {
-            if !fct_return_type.is_tuple() {
+            if !fct_return_type.is_tuple_or_struct() {
                 self.emit_store_register(reg, dest);
             }
         }
@@ -2069,7 +2283,9 @@ where
             };
 
             let result_register = match fct_return_type {
-                BuiltinType::Tuple(_) => Some(dest.expect("need register for tuple result")),
+                ty if ty.is_tuple_or_struct() => {
+                    Some(dest.expect("need register for tuple or struct result"))
+                }
                 _ => None,
             };
 
@@ -2083,7 +2299,9 @@ where
             let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
             let (reg, ty) = match bytecode_type {
-                Some(BytecodeType::Tuple(_)) => (REG_RESULT.into(), BuiltinType::Unit),
+                Some(BytecodeType::Tuple(_)) | Some(BytecodeType::Struct(_)) => {
+                    (REG_RESULT.into(), BuiltinType::Unit)
+                }
                 Some(bytecode_type) => (result_reg(bytecode_type), bytecode_type.into()),
                 None => (REG_RESULT.into(), BuiltinType::Unit),
             };
@@ -2104,7 +2322,7 @@ where
         };
 
         if let Some(dest) = dest {
-            if !fct_return_type.is_tuple() {
+            if !fct_return_type.is_tuple_or_struct() {
                 self.emit_store_register(reg, dest);
             }
         }
@@ -2221,7 +2439,7 @@ where
         result_register: Option<Register>,
         arguments: Vec<Register>,
     ) -> i32 {
-        let argsize = self.determine_argsize(&arguments);
+        let argsize = self.determine_argsize(result_register.is_some(), &arguments);
 
         self.asm.increase_stack_frame(argsize);
 
@@ -2244,7 +2462,7 @@ where
             let offset = self.register_offset(src);
 
             match bytecode_type {
-                BytecodeType::Tuple(_tuple_id) => {
+                BytecodeType::Tuple(_) | BytecodeType::Struct(_) => {
                     if reg_idx < REG_PARAMS.len() {
                         let reg = REG_PARAMS[reg_idx];
                         self.asm.lea(reg, Mem::Local(offset));
@@ -2296,8 +2514,9 @@ where
         argsize
     }
 
-    fn determine_argsize(&mut self, arguments: &Vec<Register>) -> i32 {
-        let mut reg_idx = 0;
+    fn determine_argsize(&mut self, has_result_address: bool, arguments: &Vec<Register>) -> i32 {
+        // the address for the result is passed in the first register
+        let mut reg_idx = if has_result_address { 1 } else { 0 };
         let mut freg_idx = 0;
         let mut argsize = 0;
 
@@ -2628,7 +2847,18 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
         self.emit_mov_generic(dest, src);
     }
     fn visit_mov_tuple(&mut self, dest: Register, src: Register, tuple_id: TupleId) {
-        self.emit_mov_tuple(dest, src, tuple_id);
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::Tuple(tuple_id)
+        );
+        self.emit_mov_value(dest, src);
+    }
+    fn visit_mov_struct(&mut self, dest: Register, src: Register, struct_def_id: StructDefId) {
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::Struct(struct_def_id)
+        );
+        self.emit_mov_value(dest, src);
     }
 
     fn visit_load_tuple_element(
@@ -2641,6 +2871,16 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
         self.emit_load_tuple_element(dest, src, tuple_id, idx);
     }
 
+    fn visit_load_struct_field(
+        &mut self,
+        dest: Register,
+        src: Register,
+        struct_def_id: StructDefId,
+        field_id: StructFieldId,
+    ) {
+        self.emit_load_struct_field(dest, src, struct_def_id, field_id);
+    }
+
     fn visit_load_field(&mut self, dest: Register, obj: Register, cls: ClassDefId, field: FieldId) {
         self.emit_load_field(dest, obj, cls, field);
     }
@@ -2940,6 +3180,13 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
         self.emit_invoke_virtual(Some(dest), fctdef);
     }
 
+    fn visit_invoke_trait_void(&mut self, fctdef: FctDefId) {
+        self.emit_invoke_trait(None, fctdef);
+    }
+    fn visit_invoke_trait(&mut self, dest: Register, fctdef: FctDefId) {
+        self.emit_invoke_trait(Some(dest), fctdef);
+    }
+
     fn visit_invoke_static_void(&mut self, fctdef: FctDefId) {
         self.emit_invoke_static(None, fctdef)
     }
@@ -2947,6 +3194,14 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
         self.emit_invoke_static(Some(dest), fctdef);
     }
 
+    fn visit_invoke_lambda_void(&mut self) {
+        self.emit_invoke_lambda(None);
+    }
+
+    fn visit_invoke_lambda(&mut self, dest: Register) {
+        self.emit_invoke_lambda(Some(dest));
+    }
+
     fn visit_new_object(&mut self, dest: Register, cls: ClassDefId) {
         self.emit_new_object(dest, cls)
     }
@@ -2954,7 +3209,22 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
         self.emit_new_array(dest, cls, length);
     }
     fn visit_new_tuple(&mut self, dest: Register, tuple_id: TupleId) {
-        self.emit_new_tuple(dest, tuple_id);
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::Tuple(tuple_id)
+        );
+        self.emit_new_value(dest);
+    }
+    fn visit_new_struct(&mut self, dest: Register, struct_def_id: StructDefId) {
+        assert_eq!(
+            self.bytecode.register_type(dest),
+            BytecodeType::Struct(struct_def_id)
+        );
+        self.emit_new_value(dest);
+    }
+    fn visit_new_lambda(&mut self, dest: Register, fctdef: FctDefId) {
+        let cls_def_id = specialize_lambda(self.vm, self.src, fctdef);
+        self.emit_new_object(dest, cls_def_id);
     }
 
     fn visit_nil_check(&mut self, obj: Register) {
@@ -2995,6 +3265,9 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
     fn visit_load_array_tuple(&mut self, dest: Register, arr: Register, idx: Register) {
         self.emit_load_array(dest, arr, idx);
     }
+    fn visit_load_array_struct(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.emit_load_array(dest, arr, idx);
+    }
 
     fn visit_store_array_bool(&mut self, src: Register, arr: Register, idx: Register) {
         self.emit_store_array(src, arr, idx);
@@ -3023,6 +3296,9 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
     fn visit_store_array_tuple(&mut self, src: Register, arr: Register, idx: Register) {
         self.emit_store_array(src, arr, idx);
     }
+    fn visit_store_array_struct(&mut self, src: Register, arr: Register, idx: Register) {
+        self.emit_store_array(src, arr, idx);
+    }
 
     fn visit_ret_void(&mut self) {
         self.emit_epilog();
@@ -3030,6 +3306,15 @@ impl<'a, 'ast: 'a> BytecodeVisitor for CannonCodeGen<'a, 'ast> {
     fn visit_ret(&mut self, opnd: Register) {
         self.emit_return_generic(opnd);
     }
+
+    fn visit_throw(&mut self, opnd: Register) {
+        assert_eq!(self.bytecode.register_type(opnd), BytecodeType::Ptr);
+        let position = self.bytecode.offset_position(self.current_offset.to_u32());
+        let gcpoint = self.create_gcpoint();
+
+        self.emit_load_register(opnd, REG_PARAMS[0].into());
+        self.asm.throw(REG_PARAMS[0], position, gcpoint);
+    }
 }
 
 fn result_reg(bytecode_type: BytecodeType) -> AnyReg {
@@ -3078,12 +3363,31 @@ fn determine_offsets(registers: &[BytecodeType], start: i32) -> (Vec<i32>, i32)
     (offset, stacksize)
 }
 
+#[derive(Copy, Clone)]
 enum RegOrOffset {
     Reg(Reg),
     RegWithOffset(Reg, i32),
     Offset(i32),
 }
 
+impl RegOrOffset {
+    fn offset(self, offset: i32) -> RegOrOffset {
+        match self {
+            RegOrOffset::Reg(reg) => RegOrOffset::RegWithOffset(reg, offset),
+            RegOrOffset::RegWithOffset(reg, base) => RegOrOffset::RegWithOffset(reg, base + offset),
+            RegOrOffset::Offset(base) => RegOrOffset::Offset(base + offset),
+        }
+    }
+
+    fn mem(self) -> Mem {
+        match self {
+            RegOrOffset::Reg(reg) => Mem::Base(reg, 0),
+            RegOrOffset::RegWithOffset(reg, offset) => Mem::Base(reg, offset),
+            RegOrOffset::Offset(offset) => Mem::Local(offset),
+        }
+    }
+}
+
 fn result_address_offset() -> i32 {
     -mem::ptr_width()
 }
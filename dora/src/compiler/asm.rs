@@ -15,7 +15,7 @@ use crate::stdlib;
 use crate::threads::ThreadLocalData;
 use crate::ty::{BuiltinType, MachineMode, TypeList};
 use crate::vm::FctId;
-use crate::vm::{GlobalData, Trap, VM};
+use crate::vm::{ClassDefId, GlobalData, TraitId, Trap, VM};
 
 pub struct BaselineAssembler<'a, 'ast: 'a> {
     masm: MacroAssembler,
@@ -126,6 +126,29 @@ where
         self.masm.pos()
     }
 
+    pub fn throw(&mut self, exception: Reg, pos: Position, gcpoint: GcPoint) {
+        if exception != REG_PARAMS[0] {
+            self.masm
+                .copy_reg(MachineMode::Ptr, REG_PARAMS[0], exception);
+        }
+
+        self.masm.raw_call(self.vm.throw_stub().to_ptr());
+        self.masm.emit_gcpoint(gcpoint);
+        self.masm.emit_position(pos);
+    }
+
+    pub fn emit_exception_handler(
+        &mut self,
+        try_start: usize,
+        try_end: usize,
+        catch: usize,
+        offset: i32,
+        catch_type: Option<ClassDefId>,
+    ) {
+        self.masm
+            .emit_exception_handler(try_start, try_end, catch, offset, catch_type);
+    }
+
     pub fn store_mem(&mut self, mode: MachineMode, mem: Mem, src: AnyReg) {
         self.masm.store_mem(mode, mem, src);
     }
@@ -182,6 +205,34 @@ where
         self.masm.emit_barrier(src, card_table_offset);
     }
 
+    // records the current value of the slot while concurrent marking is
+    // active, clobbers all caller-saved registers in the slow path.
+    pub fn emit_pre_barrier(&mut self, slot: Mem, pos: Position, gcpoint: GcPoint) {
+        let lbl_slow_path = self.masm.create_label();
+        let lbl_return = self.masm.create_label();
+
+        {
+            let scratch = self.masm.get_scratch();
+            self.masm.load_mem(
+                MachineMode::Int8,
+                (*scratch).into(),
+                Mem::Base(REG_THREAD, ThreadLocalData::concurrent_marking_offset()),
+            );
+            self.masm
+                .test_and_jump_if(CondCode::NonZero, *scratch, lbl_slow_path);
+        }
+
+        self.masm.bind_label(lbl_return);
+
+        self.slow_paths.push(SlowPathKind::PreWriteBarrier(
+            lbl_slow_path,
+            lbl_return,
+            slot,
+            pos,
+            gcpoint,
+        ));
+    }
+
     pub fn emit_bailout(&mut self, lbl: Label, trap: Trap, pos: Position) {
         self.masm.emit_bailout(lbl, trap, pos);
     }
@@ -504,6 +555,21 @@ where
         self.call_epilog(pos, return_type, dest, gcpoint);
     }
 
+    pub fn trait_call(
+        &mut self,
+        trait_id: TraitId,
+        method_index: u32,
+        self_index: u32,
+        pos: Position,
+        gcpoint: GcPoint,
+        return_type: BuiltinType,
+        dest: AnyReg,
+    ) {
+        self.masm
+            .trait_call(pos, trait_id, method_index, self_index);
+        self.call_epilog(pos, return_type, dest, gcpoint);
+    }
+
     fn call_epilog(&mut self, pos: Position, ty: BuiltinType, dest: AnyReg, gcpoint: GcPoint) {
         self.masm.emit_position(pos);
         self.masm.emit_gcpoint(gcpoint);
@@ -745,6 +811,10 @@ where
                 SlowPathKind::Assert(lbl_start, pos) => {
                     self.slow_path_assert(lbl_start, pos);
                 }
+
+                SlowPathKind::PreWriteBarrier(lbl_start, lbl_return, slot, pos, gcpoint) => {
+                    self.slow_path_pre_write_barrier(lbl_start, lbl_return, slot, pos, gcpoint);
+                }
             }
         }
 
@@ -806,6 +876,34 @@ where
         self.masm.jump(lbl_return);
     }
 
+    fn slow_path_pre_write_barrier(
+        &mut self,
+        lbl_start: Label,
+        lbl_return: Label,
+        slot: Mem,
+        pos: Position,
+        gcpoint: GcPoint,
+    ) {
+        self.masm.bind_label(lbl_start);
+        self.masm.emit_comment("slow path pre-write barrier".into());
+        self.masm
+            .load_mem(MachineMode::Ptr, REG_PARAMS[0].into(), slot);
+
+        // nothing to record when the slot is still nil
+        self.masm
+            .test_and_jump_if(CondCode::Zero, REG_PARAMS[0], lbl_return);
+
+        let internal_fct = NativeFct {
+            ptr: Address::from_ptr(stdlib::gc_pre_write_barrier as *const u8),
+            args: &[BuiltinType::Ptr],
+            return_type: BuiltinType::Unit,
+            desc: NativeFctDescriptor::BarrierStub,
+        };
+
+        self.native_call(internal_fct, pos, gcpoint, REG_RESULT.into());
+        self.masm.jump(lbl_return);
+    }
+
     fn slow_path_assert(&mut self, lbl_assert: Label, pos: Position) {
         self.masm.bind_label(lbl_assert);
         self.masm.emit_comment("slow path assert".into());
@@ -822,4 +920,5 @@ enum SlowPathKind {
     StackOverflow(Label, Label, Position, GcPoint),
     Assert(Label, Position),
     InitializeGlobal(Label, Label, FctId, Address, Position, GcPoint),
+    PreWriteBarrier(Label, Label, Mem, Position, GcPoint),
 }
@@ -70,6 +70,10 @@ pub fn generate_fct<'ast>(
         os::perf::register_with_perf(&code, vm, fct.ast.name);
     }
 
+    if vm.args.flag_enable_gdb_jit {
+        os::gdb::register_with_gdb(&code, vm, fct);
+    }
+
     if should_emit_asm(vm, &*fct) {
         disassembler::disassemble(
             vm,
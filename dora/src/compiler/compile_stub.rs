@@ -14,8 +14,8 @@ use crate::object::Obj;
 use crate::stack::DoraToNativeInfo;
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
-use crate::vm::FctId;
 use crate::vm::{get_vm, VM};
+use crate::vm::{FctId, TraitId};
 
 // This code generates the compiler stub, there should only be one instance
 // of this function be used in Dora. It is necessary for lazy compilation, where
@@ -238,6 +238,17 @@ fn compile_request(ra: usize, receiver1: Address, receiver2: Address) -> Address
             cls_tps,
             fct_tps,
         ),
+
+        LazyCompilationSite::TraitCompile(receiver_is_first, trait_id, method_index) => {
+            patch_itable_call(
+                vm,
+                receiver_is_first,
+                receiver1,
+                receiver2,
+                trait_id,
+                method_index,
+            )
+        }
     }
 }
 
@@ -258,12 +269,26 @@ fn patch_vtable_call(
 
     let obj = unsafe { &mut *receiver.to_mut_ptr::<Obj>() };
     let vtable = obj.header().vtbl();
-    let cls_id = vtable.class().cls_id.expect("no corresponding class");
-    let cls = vm.classes.idx(cls_id);
-    let cls = cls.read();
 
-    let fct_id = cls.virtual_fcts[vtable_index as usize];
-    let fct_ptr = compiler::generate(vm, fct_id, cls_tps, fct_tps);
+    let fct_ptr = if let Some(fct_def_id) = vtable.class().lambda {
+        // context objects of lambdas know the specialization of their lambda
+        let fct_def = vm.fct_defs.idx(fct_def_id);
+        let fct_def = fct_def.read();
+
+        compiler::generate(
+            vm,
+            fct_def.fct_id,
+            &fct_def.cls_type_params,
+            &fct_def.fct_type_params,
+        )
+    } else {
+        let cls_id = vtable.class().cls_id.expect("no corresponding class");
+        let cls = vm.classes.idx(cls_id);
+        let cls = cls.read();
+
+        let fct_id = cls.virtual_fcts[vtable_index as usize];
+        compiler::generate(vm, fct_id, cls_tps, fct_tps)
+    };
 
     let methodtable = vtable.table_mut();
     methodtable[vtable_index as usize] = fct_ptr.to_usize();
@@ -271,6 +296,57 @@ fn patch_vtable_call(
     fct_ptr
 }
 
+fn patch_itable_call(
+    vm: &VM,
+    receiver_is_first: bool,
+    receiver1: Address,
+    receiver2: Address,
+    trait_id: TraitId,
+    method_index: u32,
+) -> Address {
+    let receiver = if receiver_is_first {
+        receiver1
+    } else {
+        receiver2
+    };
+
+    let obj = unsafe { &mut *receiver.to_mut_ptr::<Obj>() };
+    let vtable = obj.header().vtbl();
+
+    let trait_fct_id = vm.traits[trait_id].read().methods[method_index as usize];
+
+    // the impl of the trait might be defined in one of the super classes
+    let mut cls_def_id = vtable.class().id;
+
+    let fct_ptr = loop {
+        let cls_def = vm.class_defs.idx(cls_def_id);
+        let cls_def = cls_def.read();
+
+        let cls_id = cls_def.cls_id.expect("no corresponding class");
+        let cls = vm.classes.idx(cls_id);
+        let cls = cls.read();
+
+        if let Some(impl_id) = cls.find_impl_for_trait(vm, trait_id) {
+            let ximpl = vm.impls[impl_id].read();
+            let fct_id = ximpl
+                .find_implements(vm, trait_fct_id)
+                .expect("no impl for trait method");
+
+            break compiler::generate(vm, fct_id, &cls_def.type_params, &TypeList::empty());
+        }
+
+        cls_def_id = cls_def.parent_id.expect("trait not implemented");
+    };
+
+    vtable.set_itable_entry(
+        trait_id.to_usize(),
+        method_index as usize,
+        fct_ptr.to_usize(),
+    );
+
+    fct_ptr
+}
+
 fn patch_fct_call(
     vm: &VM,
     ra: usize,
@@ -8,12 +8,12 @@ use crate::dseg::DSeg;
 use crate::gc::Address;
 use crate::ty::TypeList;
 use crate::utils::GrowableVec;
-use crate::vm::FctId;
 use crate::vm::VM;
+use crate::vm::{ClassDefId, FctId, TraitId};
 
 use dora_parser::Position;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct JitFctId(usize);
 
 impl JitFctId {
@@ -128,10 +128,12 @@ pub enum JitDescriptor {
     CompileStub,
     TrapStub,
     AllocStub,
+    BarrierStub,
     VerifyStub,
     NativeStub(FctId),
     DoraStub,
     GuardCheckStub,
+    ThrowStub,
 }
 
 pub struct Code {
@@ -149,6 +151,7 @@ pub struct Code {
     gcpoints: GcPoints,
     comments: Comments,
     positions: PositionTable,
+    exception_handlers: Vec<ExHandler>,
 }
 
 impl Code {
@@ -164,6 +167,7 @@ impl Code {
             0,
             Comments::new(),
             PositionTable::new(),
+            Vec::new(),
             desc,
         )
     }
@@ -177,6 +181,7 @@ impl Code {
         framesize: i32,
         comments: Comments,
         positions: PositionTable,
+        exception_handlers: Vec<ExHandler>,
         desc: JitDescriptor,
     ) -> Code {
         let size = dseg.size() as usize + buffer.len();
@@ -211,6 +216,7 @@ impl Code {
             instruction_start,
             instruction_end,
             positions,
+            exception_handlers,
             desc,
         }
     }
@@ -219,6 +225,10 @@ impl Code {
         self.positions.get(offset)
     }
 
+    pub fn positions(&self) -> &PositionTable {
+        &self.positions
+    }
+
     pub fn gcpoint_for_offset(&self, offset: u32) -> Option<&GcPoint> {
         self.gcpoints.get(offset)
     }
@@ -258,6 +268,10 @@ impl Code {
     pub fn lazy_for_offset(&self, offset: u32) -> Option<&LazyCompilationSite> {
         self.lazy_compilation.get(offset)
     }
+
+    pub fn exception_handlers(&self) -> &[ExHandler] {
+        &self.exception_handlers
+    }
 }
 
 impl fmt::Debug for Code {
@@ -272,6 +286,20 @@ impl fmt::Debug for Code {
     }
 }
 
+#[derive(Debug)]
+pub struct ExHandler {
+    // code offsets of the protected range and the handler
+    pub try_start: usize,
+    pub try_end: usize,
+    pub catch: usize,
+
+    // frame offset where the exception gets stored
+    pub offset: i32,
+
+    // None catches every exception
+    pub catch_type: Option<ClassDefId>,
+}
+
 #[derive(Debug)]
 pub struct GcPoints {
     entries: Vec<(u32, GcPoint)>,
@@ -386,6 +414,10 @@ impl PositionTable {
         self.entries.push((offset, position));
     }
 
+    pub fn entries(&self) -> &[(u32, Position)] {
+        &self.entries
+    }
+
     pub fn get(&self, offset: u32) -> Option<Position> {
         let result = self
             .entries
@@ -434,4 +466,5 @@ impl LazyCompilationData {
 pub enum LazyCompilationSite {
     Compile(FctId, i32, TypeList, TypeList),
     VirtCompile(bool, u32, TypeList, TypeList),
+    TraitCompile(bool, TraitId, u32),
 }
@@ -33,6 +33,7 @@ impl CodeMap {
                 &CodeDescriptor::CompileStub => println!("compile_stub"),
                 &CodeDescriptor::TrapStub => println!("trap_stub"),
                 &CodeDescriptor::AllocStub => println!("alloc_stub"),
+                &CodeDescriptor::BarrierStub => println!("barrier_stub"),
                 &CodeDescriptor::VerifyStub => println!("verify_stub"),
                 &CodeDescriptor::NativeStub(jit_fct_id) => {
                     let jit_fct = vm.jit_fcts.idx(jit_fct_id);
@@ -43,6 +44,7 @@ impl CodeMap {
                 }
                 &CodeDescriptor::DoraStub => println!("dora_stub"),
                 &CodeDescriptor::GuardCheckStub => println!("guard_check_stub"),
+                &CodeDescriptor::ThrowStub => println!("throw_stub"),
             }
         }
 
@@ -67,10 +69,12 @@ pub enum CodeDescriptor {
     CompileStub,
     TrapStub,
     AllocStub,
+    BarrierStub,
     VerifyStub,
     NativeStub(JitFctId),
     DoraStub,
     GuardCheckStub,
+    ThrowStub,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -11,7 +11,7 @@ use crate::cpu::{
 use crate::gc::Address;
 use crate::masm::MacroAssembler;
 use crate::mem;
-use crate::stack::DoraToNativeInfo;
+use crate::stack::{DoraToNativeInfo, ThrowResume};
 use crate::threads::ThreadLocalData;
 use crate::ty::{BuiltinType, MachineMode};
 use crate::vm::FctId;
@@ -41,9 +41,11 @@ impl NativeStubs {
 pub enum NativeFctDescriptor {
     NativeStub(FctId),
     AllocStub,
+    BarrierStub,
     VerifyStub,
     TrapStub,
     GuardCheckStub,
+    ThrowStub,
 }
 
 pub struct NativeFct<'a> {
@@ -73,7 +75,9 @@ pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>, fct: NativeFct, dbg: bool) -> Ji
         NativeFctDescriptor::TrapStub => CodeDescriptor::TrapStub,
         NativeFctDescriptor::VerifyStub => CodeDescriptor::VerifyStub,
         NativeFctDescriptor::AllocStub => CodeDescriptor::AllocStub,
+        NativeFctDescriptor::BarrierStub => CodeDescriptor::BarrierStub,
         NativeFctDescriptor::GuardCheckStub => CodeDescriptor::GuardCheckStub,
+        NativeFctDescriptor::ThrowStub => CodeDescriptor::ThrowStub,
     };
 
     vm.insert_code_map(jit_start, jit_end, code_desc);
@@ -96,14 +100,25 @@ where
     pub fn generate(mut self) -> Code {
         let save_return = self.fct.return_type != BuiltinType::Unit;
         let dtn_size = size_of::<DoraToNativeInfo>() as i32;
+        let is_throw = match self.fct.desc {
+            NativeFctDescriptor::ThrowStub => true,
+            _ => false,
+        };
 
-        let (stack_args, temporaries, temporaries_desc, args_desc) = analyze(self.fct.args);
+        let (stack_args, temporaries, temporaries_desc, args_desc) =
+            analyze(self.vm, self.fct.args);
 
         let offset_args = 0;
         let offset_temporaries = offset_args + stack_args as i32 * mem::ptr_width();
         let offset_dtn = offset_temporaries + temporaries as i32 * mem::ptr_width();
         let offset_return = offset_dtn + dtn_size;
-        let framesize = offset_return + if save_return { mem::ptr_width() } else { 0 };
+        let offset_resume = offset_return + if save_return { mem::ptr_width() } else { 0 };
+        let framesize = offset_resume
+            + if is_throw {
+                size_of::<ThrowResume>() as i32
+            } else {
+                0
+            };
         let framesize = mem::align_i32(framesize, 16);
 
         if self.dbg || self.vm.args.flag_emit_debug_native {
@@ -228,6 +243,12 @@ where
             }
         }
 
+        if is_throw {
+            // the throw stub passes the location of the handler as last argument
+            let reg = CCALL_REG_PARAMS[self.fct.args.len()];
+            self.masm.lea(reg, Mem::Base(REG_SP, offset_resume));
+        }
+
         self.masm.raw_call(self.fct.ptr.to_ptr());
         self.masm.emit_only_gcpoint(GcPoint::from_offsets(offsets));
 
@@ -248,15 +269,39 @@ where
             REG_TMP1.into(),
         );
 
-        self.masm.epilog();
+        if is_throw {
+            // continue in the exception handler, sp is restored last since it
+            // is the base register for the loads
+            self.masm.load_mem(
+                MachineMode::Ptr,
+                REG_TMP1.into(),
+                Mem::Base(REG_SP, offset_resume + ThrowResume::pc_offset()),
+            );
+            self.masm.load_mem(
+                MachineMode::Ptr,
+                REG_FP.into(),
+                Mem::Base(REG_SP, offset_resume + ThrowResume::fp_offset()),
+            );
+            self.masm.load_mem(
+                MachineMode::Ptr,
+                REG_SP.into(),
+                Mem::Base(REG_SP, offset_resume + ThrowResume::sp_offset()),
+            );
+            self.masm.jump_reg(REG_TMP1);
+        } else {
+            self.masm.epilog();
+        }
+
         self.masm.nop();
 
         let desc = match self.fct.desc {
             NativeFctDescriptor::NativeStub(fid) => JitDescriptor::NativeStub(fid),
             NativeFctDescriptor::AllocStub => JitDescriptor::AllocStub,
+            NativeFctDescriptor::BarrierStub => JitDescriptor::BarrierStub,
             NativeFctDescriptor::VerifyStub => JitDescriptor::VerifyStub,
             NativeFctDescriptor::TrapStub => JitDescriptor::TrapStub,
             NativeFctDescriptor::GuardCheckStub => JitDescriptor::GuardCheckStub,
+            NativeFctDescriptor::ThrowStub => JitDescriptor::ThrowStub,
         };
 
         self.masm.jit(self.vm, framesize, desc)
@@ -264,6 +309,7 @@ where
 }
 
 fn analyze(
+    vm: &VM,
     args: &[BuiltinType],
 ) -> (
     u32,
@@ -326,7 +372,7 @@ fn analyze(
                     stack_args += 1;
                 }
 
-                if ty.reference_type() {
+                if ty.reference_type(vm) {
                     ArgumentDestination::HandleRegister(CCALL_REG_PARAMS[reg_idx])
                 } else {
                     ArgumentDestination::Register(ty.mode(), CCALL_REG_PARAMS[reg_idx])
@@ -334,7 +380,7 @@ fn analyze(
             } else {
                 stack_args += 1;
 
-                if ty.reference_type() {
+                if ty.reference_type(vm) {
                     ArgumentDestination::HandleOffset(stack_args - 1)
                 } else {
                     ArgumentDestination::Offset(ty.mode(), stack_args - 1)
@@ -1,4 +1,7 @@
 pub use self::start::*;
 
 pub mod cmd;
+pub mod heap_analyze;
+pub mod repl;
 pub mod start;
+pub mod test_runner;
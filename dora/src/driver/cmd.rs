@@ -8,6 +8,7 @@ use crate::gc::M;
 use docopt::Docopt;
 use serde::{de, Deserialize, Deserializer};
 
+use crate::gc::allocprof::DEFAULT_ALLOC_SAMPLE_INTERVAL;
 use crate::gc::{DEFAULT_CODE_SPACE_LIMIT, DEFAULT_PERM_SPACE_LIMIT};
 
 pub fn parse() -> Args {
@@ -19,6 +20,10 @@ pub fn parse() -> Args {
 // Write the Docopt usage string.
 static USAGE: &'static str = "
 Usage: dora test [options] <file>
+       dora compile [options] <file>
+       dora stdlib-snapshot [options]
+       dora repl [options]
+       dora heap-analyze <file>
        dora [options] <file> [--] [<argument>...]
        dora (--version | --help)
 
@@ -30,6 +35,9 @@ Options:
     --emit-asm=<fct>        Emits assembly code to stdout.
     --emit-asm-file         Emits assembly code into file `dora-<pid>.asm`.
     --emit-bytecode=<fct>   Emits bytecode to stdout.
+    --emit-bytecode-file    Writes bytecode of the program into a `.dorabc` file, which is
+                            used instead of checking the unchanged program again.
+                            Lint warnings are not reported for programs loaded from it.
     --emit-stubs            Emits generated stubs.
     --emit-debug=<fct>      Emits debug instruction at beginning of functions.
     --emit-debug-native     Emits debug instruction at beginning of native stub.
@@ -37,16 +45,24 @@ Options:
     --emit-debug-entry      Emits debug instruction at beginning of entry stub.
     --omit-bounds-check     Omit array index out of bounds checks.
     --check                 Only type check given program.
+    --error-format=<format>
+                            Output format of compiler diagnostics.
+                            Possible values: human (default), json.
+    --warn-unused           Warn about unused variables, parameters, functions and classes,
+                            shadowed variables and `var`s that are never reassigned.
     --asm-syntax TYPE       Emits assembly with Intel or AT&T syntax.
                             Allowed values: intel, att.
     --enable-perf           Enable dump for perf.
+    --enable-gdb-jit        Register generated code with the JIT interface of GDB.
     --gc-events             Dump GC events.
     --gc-stress             Collect garbage at every allocation.
     --gc-stress-minor       Minor collection at every allocation.
     --gc-parallel-full      Enable parallel full collection.
     --gc-parallel-minor     Enable parallel minor collection.
     --gc-parallel           Enable both parallel minor and full collection.
+    --gc-concurrent-mark    Mark old generation concurrently to the mutator.
     --gc-stats              Print GC statistics.
+    --heap-dump-on-oom      Write heap dump into `dora-<pid>.heapdump` when running out of memory.
     --gc-verbose            Verbose GC.
     --gc-dev-verbose        Verbose GC for developers.
     --gc-verify             Verify heap before and after collections.
@@ -56,9 +72,19 @@ Options:
     --gc-young-size=<SIZE>  Use fixed size for young generation.
     --gc-young-appel        Use Appel dynamic resizing of young generation.
     --gc-semi-ratio=<num>   Use fixed ratio of semi space in young generation.
+    --gc-max-pause=<ms>     Size generations to keep GC pauses below the given time.
+    --gc-log=<file>         Write one JSON object per collection into file.
+    --gc-old=<name>         Full collection of old generation. Possible values: compact (default), immix.
+    --alloc-profile=<file>  Sample allocations and write their stacks as folded stacks into file.
+    --alloc-sample-interval=<SIZE>
+                            Take an allocation sample every SIZE allocated bytes (default 512K).
+    --profile=<file>        Sample the CPU usage of Dora threads and write folded stacks into file.
 
     --compiler=<name>       Switch default compiler. Possible values: cannon [default: cannon].
     --test-filter=<name>    Filter tests.
+    --test-exact=<name>     Run only the test with this name in the current process.
+    --test-format=<format>  Output format of test results.
+                            Possible values: text (default), tap, junit, json.
     --clear-regs            Clear register when freeing.
 
     --disable-tlab          Disable tlab allocation.
@@ -71,9 +97,15 @@ Options:
 
     --stdlib=<path>         Load standard library from the given path.
     --boots=<path>          Load boots source from the given path.
+    --stdlib-snapshot=<file>
+                            Bytecode of the stdlib written by `dora stdlib-snapshot`,
+                            defaults to `stdlib.dorabc` next to the binary. Bodies of
+                            stdlib functions in an up-to-date snapshot are not type
+                            checked again, the stdlib is still parsed and its
+                            declarations are checked.
 ";
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Args {
     pub arg_argument: Option<Vec<String>>,
     pub arg_file: String,
@@ -82,9 +114,11 @@ pub struct Args {
     pub flag_emit_asm: Option<String>,
     pub flag_emit_asm_file: bool,
     pub flag_emit_bytecode: Option<String>,
+    pub flag_emit_bytecode_file: bool,
     pub flag_emit_llvm: bool,
     pub flag_emit_stubs: bool,
     pub flag_enable_perf: bool,
+    pub flag_enable_gdb_jit: bool,
     pub flag_omit_bounds_check: bool,
     pub flag_version: bool,
     pub flag_emit_debug: Option<String>,
@@ -98,13 +132,21 @@ pub struct Args {
     flag_gc_parallel_full: bool,
     flag_gc_parallel_minor: bool,
     flag_gc_parallel: bool,
+    pub flag_gc_concurrent_mark: bool,
     pub flag_gc_stats: bool,
+    pub flag_heap_dump_on_oom: bool,
     pub flag_gc_verbose: bool,
     pub flag_gc_dev_verbose: bool,
     pub flag_gc_verify: bool,
     pub flag_gc_worker: usize,
     flag_gc_young_size: Option<MemSize>,
     pub flag_gc_semi_ratio: Option<usize>,
+    pub flag_gc_max_pause: Option<f32>,
+    pub flag_gc_log: Option<String>,
+    pub flag_gc_old: Option<OldGenName>,
+    pub flag_alloc_profile: Option<String>,
+    flag_alloc_sample_interval: Option<MemSize>,
+    pub flag_profile: Option<String>,
     pub flag_gc: Option<CollectorName>,
     pub flag_compiler: Option<CompilerName>,
     pub flag_min_heap_size: Option<MemSize>,
@@ -116,10 +158,19 @@ pub struct Args {
     pub flag_disable_barrier: bool,
     pub flag_stdlib: Option<String>,
     pub flag_boots: Option<String>,
+    pub flag_stdlib_snapshot: Option<String>,
     pub flag_test_filter: Option<String>,
+    pub flag_test_exact: Option<String>,
+    pub flag_test_format: Option<TestFormat>,
+    pub flag_error_format: Option<ErrorFormat>,
+    pub flag_warn_unused: bool,
     pub flag_clear_regs: bool,
 
     pub cmd_test: bool,
+    pub cmd_compile: bool,
+    pub cmd_stdlib_snapshot: bool,
+    pub cmd_repl: bool,
+    pub cmd_heap_analyze: bool,
 }
 
 impl Args {
@@ -164,6 +215,11 @@ impl Args {
         self.flag_gc_young_size.is_none()
     }
 
+    // phase timings are needed for statistics and the pause target
+    pub fn gc_phases(&self) -> bool {
+        self.flag_gc_stats || self.flag_gc_max_pause.is_some() || self.flag_gc_log.is_some()
+    }
+
     pub fn parallel_minor(&self) -> bool {
         self.flag_gc_parallel_minor || self.flag_gc_parallel
     }
@@ -172,9 +228,27 @@ impl Args {
         self.flag_gc_parallel_full || self.flag_gc_parallel
     }
 
+    pub fn gc_old(&self) -> OldGenName {
+        self.flag_gc_old.unwrap_or(OldGenName::Compact)
+    }
+
+    pub fn alloc_sample_interval(&self) -> usize {
+        self.flag_alloc_sample_interval
+            .map(|s| *s)
+            .unwrap_or(DEFAULT_ALLOC_SAMPLE_INTERVAL)
+    }
+
     pub fn compiler(&self) -> CompilerName {
         self.flag_compiler.unwrap_or(CompilerName::Cannon)
     }
+
+    pub fn test_format(&self) -> TestFormat {
+        self.flag_test_format.unwrap_or(TestFormat::Text)
+    }
+
+    pub fn error_format(&self) -> ErrorFormat {
+        self.flag_error_format.unwrap_or(ErrorFormat::Human)
+    }
 }
 
 impl Default for Args {
@@ -187,6 +261,7 @@ impl Default for Args {
             flag_emit_asm: None,
             flag_emit_asm_file: false,
             flag_emit_bytecode: None,
+            flag_emit_bytecode_file: false,
             flag_emit_llvm: false,
             flag_emit_stubs: false,
             flag_emit_debug: None,
@@ -194,6 +269,7 @@ impl Default for Args {
             flag_emit_debug_native: false,
             flag_emit_debug_entry: false,
             flag_enable_perf: false,
+            flag_enable_gdb_jit: false,
             flag_omit_bounds_check: false,
             flag_version: false,
             flag_asm_syntax: None,
@@ -203,13 +279,21 @@ impl Default for Args {
             flag_gc_parallel_full: false,
             flag_gc_parallel_minor: false,
             flag_gc_parallel: false,
+            flag_gc_concurrent_mark: false,
             flag_gc_stats: false,
+            flag_heap_dump_on_oom: false,
             flag_gc_verbose: false,
             flag_gc_dev_verbose: false,
             flag_gc_verify: false,
             flag_gc_worker: 0,
             flag_gc_young_size: None,
             flag_gc_semi_ratio: None,
+            flag_gc_max_pause: None,
+            flag_gc_log: None,
+            flag_gc_old: None,
+            flag_alloc_profile: None,
+            flag_alloc_sample_interval: None,
+            flag_profile: None,
             flag_gc: None,
             flag_compiler: None,
             flag_min_heap_size: None,
@@ -221,10 +305,19 @@ impl Default for Args {
             flag_disable_barrier: false,
             flag_stdlib: None,
             flag_boots: None,
+            flag_stdlib_snapshot: None,
             flag_test_filter: None,
+            flag_test_exact: None,
+            flag_test_format: None,
+            flag_error_format: None,
+            flag_warn_unused: false,
             flag_clear_regs: false,
 
             cmd_test: false,
+            cmd_compile: false,
+            cmd_stdlib_snapshot: false,
+            cmd_repl: false,
+            cmd_heap_analyze: false,
         }
     }
 }
@@ -239,12 +332,32 @@ pub enum CollectorName {
     SweepSwiper,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+pub enum OldGenName {
+    Compact,
+    Immix,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub enum CompilerName {
     Cannon,
     Boots,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+pub enum TestFormat {
+    Text,
+    Tap,
+    Junit,
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub enum AsmSyntax {
     Intel,
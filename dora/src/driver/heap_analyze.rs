@@ -0,0 +1,418 @@
+use std::fs;
+
+use crate::gc::heapdump::{self, HeapDump, HeapDumpError};
+
+const TOP_ENTRIES: usize = 20;
+
+pub fn start(path: &str) -> i32 {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            println!("unable to read heap dump `{}`: {}", path, err);
+            return 1;
+        }
+    };
+
+    let dump = match heapdump::read(&data) {
+        Ok(dump) => dump,
+        Err(err) => {
+            println!("unable to read heap dump `{}`: {}", path, err);
+            return 1;
+        }
+    };
+
+    let analysis = match analyze(&dump) {
+        Ok(analysis) => analysis,
+        Err(err) => {
+            println!("unable to analyze heap dump `{}`: {}", path, err);
+            return 1;
+        }
+    };
+
+    print_report(&dump, &analysis);
+
+    0
+}
+
+struct ClassStats {
+    class: u32,
+    count: u64,
+    shallow: u64,
+    retained: u64,
+}
+
+struct Analysis {
+    // immediate dominator of each object, None for objects only dominated by
+    // the virtual root that references all roots
+    idom: Vec<Option<u32>>,
+    retained: Vec<u64>,
+    classes: Vec<ClassStats>,
+}
+
+fn analyze(dump: &HeapDump) -> Result<Analysis, HeapDumpError> {
+    let idom = dominators(dump)?;
+    let retained = retained_sizes(dump, &idom);
+    let classes = class_histogram(dump, &idom, &retained);
+
+    Ok(Analysis {
+        idom,
+        retained,
+        classes,
+    })
+}
+
+// Computes immediate dominators with the iterative algorithm from Cooper,
+// Harvey and Kennedy ("A Simple, Fast Dominance Algorithm"). Node n (one
+// past the last object) is a virtual root with edges to all roots. Fails
+// for references to objects that aren't part of the dump.
+fn dominators(dump: &HeapDump) -> Result<Vec<Option<u32>>, HeapDumpError> {
+    let n = dump.objects.len();
+    let root = n;
+
+    let successors = |node: usize| -> &[u32] {
+        if node == root {
+            &dump.roots
+        } else {
+            &dump.objects[node].references
+        }
+    };
+
+    // postorder of all nodes reachable from the virtual root
+    let mut postorder = Vec::with_capacity(n + 1);
+    let mut visited = vec![false; n + 1];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+
+    while let Some(top) = stack.last_mut() {
+        let (node, next) = *top;
+        top.1 += 1;
+
+        let succs = successors(node);
+
+        if next < succs.len() {
+            let succ = succs[next] as usize;
+
+            if succ >= n {
+                return Err(HeapDumpError::InvalidData("object index"));
+            }
+
+            if !visited[succ] {
+                visited[succ] = true;
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(node);
+            stack.pop();
+        }
+    }
+
+    let mut order = vec![usize::max_value(); n + 1];
+
+    for (idx, &node) in postorder.iter().enumerate() {
+        order[node] = idx;
+    }
+
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n + 1];
+
+    for &node in &postorder {
+        for &succ in successors(node) {
+            predecessors[succ as usize].push(node);
+        }
+    }
+
+    let undefined = usize::max_value();
+    let mut doms = vec![undefined; n + 1];
+    doms[root] = root;
+
+    let intersect = |doms: &[usize], mut a: usize, mut b: usize| {
+        while a != b {
+            while order[a] < order[b] {
+                a = doms[a];
+            }
+
+            while order[b] < order[a] {
+                b = doms[b];
+            }
+        }
+
+        a
+    };
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for &node in postorder.iter().rev() {
+            if node == root {
+                continue;
+            }
+
+            let mut new_idom = undefined;
+
+            for &pred in &predecessors[node] {
+                if doms[pred] == undefined {
+                    continue;
+                }
+
+                new_idom = if new_idom == undefined {
+                    pred
+                } else {
+                    intersect(&doms, pred, new_idom)
+                };
+            }
+
+            if doms[node] != new_idom {
+                doms[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(doms[..n]
+        .iter()
+        .map(|&dom| {
+            if dom == root || dom == undefined {
+                None
+            } else {
+                Some(dom as u32)
+            }
+        })
+        .collect())
+}
+
+// the retained size of an object is its own size plus the retained size
+// of all objects it immediately dominates
+fn retained_sizes(dump: &HeapDump, idom: &[Option<u32>]) -> Vec<u64> {
+    let n = dump.objects.len();
+    let mut retained: Vec<u64> = dump.objects.iter().map(|o| o.size).collect();
+
+    // children need to be processed before their dominator
+    let mut depth = vec![usize::max_value(); n];
+
+    for node in 0..n {
+        compute_depth(node, idom, &mut depth);
+    }
+
+    let mut nodes: Vec<usize> = (0..n).collect();
+    nodes.sort_by(|&a, &b| depth[b].cmp(&depth[a]));
+
+    for node in nodes {
+        if let Some(dom) = idom[node] {
+            retained[dom as usize] += retained[node];
+        }
+    }
+
+    retained
+}
+
+fn compute_depth(node: usize, idom: &[Option<u32>], depth: &mut [usize]) -> usize {
+    let mut path = Vec::new();
+    let mut current = node;
+
+    // walk up until a node with known depth is found
+    let base = loop {
+        if depth[current] != usize::max_value() {
+            break depth[current];
+        }
+
+        path.push(current);
+
+        match idom[current] {
+            Some(dom) => current = dom as usize,
+            None => break 0,
+        }
+    };
+
+    let mut value = base;
+
+    for &node in path.iter().rev() {
+        value += 1;
+        depth[node] = value;
+    }
+
+    depth[node]
+}
+
+// per class statistics, the retained size of a class only counts objects
+// not dominated by another object of the same class
+fn class_histogram(dump: &HeapDump, idom: &[Option<u32>], retained: &[u64]) -> Vec<ClassStats> {
+    let mut classes: Vec<ClassStats> = (0..dump.classes.len())
+        .map(|class| ClassStats {
+            class: class as u32,
+            count: 0,
+            shallow: 0,
+            retained: 0,
+        })
+        .collect();
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); dump.objects.len()];
+    let mut stack = Vec::new();
+
+    for (idx, object) in dump.objects.iter().enumerate() {
+        let stats = &mut classes[object.class as usize];
+        stats.count += 1;
+        stats.shallow += object.size;
+
+        match idom[idx] {
+            Some(dom) => children[dom as usize].push(idx),
+            None => stack.push((idx, false)),
+        }
+    }
+
+    // walk the dominator tree and track the classes of all dominators
+    let mut active = vec![0; dump.classes.len()];
+
+    while let Some((idx, exit)) = stack.pop() {
+        let class = dump.objects[idx].class as usize;
+
+        if exit {
+            active[class] -= 1;
+            continue;
+        }
+
+        if active[class] == 0 {
+            classes[class].retained += retained[idx];
+        }
+
+        active[class] += 1;
+        stack.push((idx, true));
+
+        for &child in &children[idx] {
+            stack.push((child, false));
+        }
+    }
+
+    classes.retain(|c| c.count > 0);
+    classes.sort_by(|a, b| b.shallow.cmp(&a.shallow));
+    classes
+}
+
+fn print_report(dump: &HeapDump, analysis: &Analysis) {
+    let total: u64 = dump.objects.iter().map(|o| o.size).sum();
+
+    println!(
+        "{} objects, {} bytes, {} classes, {} roots",
+        dump.objects.len(),
+        total,
+        dump.classes.len(),
+        dump.roots.len()
+    );
+
+    println!();
+    println!("Class histogram:");
+    println!("     count      shallow     retained  class");
+
+    for stats in analysis.classes.iter().take(TOP_ENTRIES) {
+        println!(
+            "{:>10} {:>12} {:>12}  {}",
+            stats.count, stats.shallow, stats.retained, dump.classes[stats.class as usize]
+        );
+    }
+
+    let mut dominators: Vec<usize> = (0..dump.objects.len())
+        .filter(|&idx| analysis.retained[idx] > dump.objects[idx].size)
+        .collect();
+    dominators.sort_by(|&a, &b| analysis.retained[b].cmp(&analysis.retained[a]));
+
+    println!();
+    println!("Largest dominators:");
+    println!("    retained      shallow            address  class");
+
+    for &idx in dominators.iter().take(TOP_ENTRIES) {
+        let object = &dump.objects[idx];
+        let dominated_by_root = analysis.idom[idx].is_none();
+
+        println!(
+            "{:>12} {:>12} {:>#18x}  {}{}",
+            analysis.retained[idx],
+            object.size,
+            object.address,
+            dump.classes[object.class as usize],
+            if dominated_by_root { " (root)" } else { "" }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gc::heapdump::HeapObject;
+
+    fn object(class: u32, size: u64, references: Vec<u32>) -> HeapObject {
+        HeapObject {
+            address: 0,
+            class,
+            size,
+            references,
+        }
+    }
+
+    #[test]
+    fn dominators_and_retained_sizes() {
+        // 0 -> 1 -> 2, 0 -> 3 -> 2, 4 -> 3
+        let dump = HeapDump {
+            classes: vec!["A".into(), "B".into()],
+            objects: vec![
+                object(0, 10, vec![1, 3]),
+                object(1, 20, vec![2]),
+                object(1, 30, vec![]),
+                object(1, 40, vec![2]),
+                object(0, 50, vec![3]),
+            ],
+            roots: vec![0, 4],
+        };
+
+        let analysis = analyze(&dump).unwrap();
+
+        assert_eq!(vec![None, Some(0), None, None, None], analysis.idom);
+        assert_eq!(vec![30, 20, 30, 40, 50], analysis.retained);
+
+        let a = analysis.classes.iter().find(|c| c.class == 0).unwrap();
+        assert_eq!(2, a.count);
+        assert_eq!(60, a.shallow);
+        assert_eq!(80, a.retained);
+
+        let b = analysis.classes.iter().find(|c| c.class == 1).unwrap();
+        assert_eq!(3, b.count);
+        assert_eq!(90, b.shallow);
+        assert_eq!(90, b.retained);
+    }
+
+    #[test]
+    fn dominator_chain() {
+        let dump = HeapDump {
+            classes: vec!["A".into()],
+            objects: vec![
+                object(0, 8, vec![1]),
+                object(0, 8, vec![2]),
+                object(0, 8, vec![0]),
+            ],
+            roots: vec![0],
+        };
+
+        let analysis = analyze(&dump).unwrap();
+        assert_eq!(vec![None, Some(0), Some(1)], analysis.idom);
+        assert_eq!(vec![24, 16, 8], analysis.retained);
+        assert_eq!(24, analysis.classes[0].retained);
+    }
+
+    #[test]
+    fn reference_outside_of_dump() {
+        let dump = HeapDump {
+            classes: vec!["A".into()],
+            objects: vec![object(0, 8, vec![1]), object(0, 8, vec![7])],
+            roots: vec![0],
+        };
+
+        assert!(analyze(&dump).is_err());
+
+        let dump = HeapDump {
+            classes: vec!["A".into()],
+            objects: vec![object(0, 8, vec![])],
+            roots: vec![0, 1],
+        };
+
+        assert!(analyze(&dump).is_err());
+    }
+}
@@ -0,0 +1,564 @@
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use crate::driver::cmd::Args;
+use crate::driver::start::parse_stdlib;
+use crate::error::diag::Diagnostic;
+use crate::semck;
+use crate::sym::SymTable;
+use crate::vm::{FctId, VM};
+use dora_parser::ast::Ast;
+use dora_parser::interner::Name;
+use dora_parser::lexer::reader::Reader;
+use dora_parser::lexer::File;
+use dora_parser::parser::Parser;
+
+// All inputs share one VM. The VM borrows the AST for its whole lifetime, so
+// each input is parsed into an AST of its own that lives as long as the
+// process and semck only checks this newest file. An input with errors is
+// rolled back and leaves the VM as it was before.
+pub fn start(args: Args) -> i32 {
+    let mut repl = match Repl::new(args) {
+        Ok(repl) => repl,
+        Err(code) => return code,
+    };
+
+    repl.vm.threads.attach_current_thread();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    while let Some(input) = read_input(&mut lines) {
+        let input = input.trim();
+
+        if input.is_empty() {
+            continue;
+        }
+
+        if input == ":quit" || input == ":q" {
+            break;
+        }
+
+        if let Err(errors) = repl.eval(input) {
+            report_errors(&errors);
+        }
+    }
+
+    repl.vm.threads.detach_current_thread();
+    repl.vm.threads.join_all();
+
+    0
+}
+
+fn read_input(lines: &mut impl Iterator<Item = io::Result<String>>) -> Option<String> {
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { ">>> " } else { "... " });
+        io::stdout().flush().expect("flushing stdout failed");
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => {
+                println!();
+                return if input.is_empty() { None } else { Some(input) };
+            }
+        };
+
+        input.push_str(&line);
+        input.push('\n');
+
+        // continue reading while brackets are still open
+        if nesting(&input) <= 0 {
+            return Some(input);
+        }
+    }
+}
+
+fn nesting(input: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for ch in input.chars() {
+        if in_string {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match ch {
+            '"' => in_string = true,
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth
+}
+
+struct Repl {
+    vm: Box<VM<'static>>,
+    // number of functions generated for running inputs
+    fcts: usize,
+}
+
+impl Repl {
+    fn new(args: Args) -> Result<Repl, i32> {
+        let mut ast = Ast::new();
+        let mut vm = VM::new(args, leak(Ast::new()));
+
+        parse_stdlib(&mut vm, &mut ast)?;
+        vm.ast = leak(ast);
+
+        semck::check(&mut vm);
+
+        if vm.diag.lock().has_errors() {
+            vm.diag.lock().dump(&vm);
+            return Err(1);
+        }
+
+        semck::prelude::install_conditional_intrinsics(&mut vm);
+
+        Ok(Repl { vm, fcts: 0 })
+    }
+
+    fn eval(&mut self, input: &str) -> Result<(), Vec<String>> {
+        if let Some(fct_id) = self.compile(input)? {
+            self.vm.run(fct_id);
+        }
+
+        Ok(())
+    }
+
+    // checks the input and returns the function that runs it, declarations
+    // only need to be added to the VM
+    fn compile(&mut self, input: &str) -> Result<Option<FctId>, Vec<String>> {
+        if let Some((name, reassignable)) = self.binding(input) {
+            return self.bind(input, name, reassignable).map(Some);
+        }
+
+        if let Ok(file) = self.parse(input.to_string()) {
+            self.check(file)?;
+            return Ok(None);
+        }
+
+        let fct_name = format!("repl{}", self.fcts);
+
+        // a trailing semicolon suppresses printing the result
+        if !input.ends_with(';') {
+            let source = format!(
+                "fun {}() {{\nlet repl_result = (\n{}\n);\nprintln(repl_result.toString());\n}}\n",
+                fct_name, input
+            );
+
+            if let Ok(file) = self.parse(source) {
+                if self.check(file).is_ok() {
+                    return Ok(Some(self.fct(&fct_name)));
+                }
+            }
+        }
+
+        let source = format!("fun {}() {{\n{{\n{}\n}};\n}}\n", fct_name, input);
+        let file = self.parse(source).map_err(|error| vec![error])?;
+        self.check(file)?;
+
+        Ok(Some(self.fct(&fct_name)))
+    }
+
+    // returns the name of the variable when the input is a single `let` or
+    // `var` statement with an initializer
+    fn binding(&mut self, input: &str) -> Option<(Name, bool)> {
+        let (ast, _) = self.parse(format!("fun repl() {{\n{}\n}}\n", input)).ok()?;
+        let block = ast.files[0].elements[0].to_function()?.block();
+
+        if block.stmts.len() != 1 || block.expr.is_some() {
+            return None;
+        }
+
+        let var = block.stmts[0].to_var()?;
+        var.expr.as_ref().map(|_| (var.name, var.reassignable))
+    }
+
+    // the variable becomes a global initialized by a function that runs the
+    // input, its type is taken from checking the input as a local first
+    fn bind(&mut self, input: &str, name: Name, reassignable: bool) -> Result<FctId, Vec<String>> {
+        let fct_name = format!("repl{}", self.fcts);
+        let source = format!("fun {}() {{\n{}\n}}\n", fct_name, input);
+        let file = self.parse(source).map_err(|error| vec![error])?;
+        let snapshot = self.check(file)?;
+
+        let ty = {
+            let fct = self.vm.fcts.idx(self.vm.fct_by_name(&fct_name).unwrap());
+            let fct = fct.read();
+            let src = fct.src();
+            let src = src.read();
+            let var = src.vars.iter().find(|var| var.name == name).unwrap();
+            var.ty.name(&self.vm)
+        };
+
+        snapshot.restore(&mut self.vm);
+
+        let name = self.vm.interner.str(name).to_string();
+        let source = format!(
+            "{} {}: {} = {}_value();\n\
+             fun {}_value() -> {} {{\n{}\n{}\n}}\n\
+             fun {}() {{\n{};\n}}\n",
+            if reassignable { "var" } else { "let" },
+            name,
+            ty,
+            fct_name,
+            fct_name,
+            ty,
+            input,
+            name,
+            fct_name,
+            name
+        );
+        let file = self.parse(source).map_err(|error| vec![error])?;
+        self.check(file)?;
+
+        Ok(self.fct(&fct_name))
+    }
+
+    fn fct(&mut self, name: &str) -> FctId {
+        self.fcts += 1;
+        self.vm.fct_by_name(name).expect("function missing")
+    }
+
+    // parse errors are reported by the caller, the input might still be
+    // valid in another form
+    fn parse(&mut self, source: String) -> Result<(Ast, File), String> {
+        let mut ast = Ast::new();
+        let reader = Reader::from_string("<<repl>>", &source);
+        let parser = Parser::new(
+            reader,
+            &self.vm.id_generator,
+            &mut ast,
+            &mut self.vm.interner,
+        );
+
+        match parser.parse() {
+            Ok(file) => Ok((ast, file)),
+            Err(error) => Err(error.error.message()),
+        }
+    }
+
+    fn check(&mut self, (ast, file): (Ast, File)) -> Result<Snapshot<'static>, Vec<String>> {
+        let snapshot = Snapshot::new(&self.vm);
+
+        self.vm.files.push(file);
+        self.vm.ast = leak(ast);
+
+        semck::check(&mut self.vm);
+
+        let diag = mem::replace(&mut *self.vm.diag.lock(), Diagnostic::new());
+
+        if diag.has_errors() {
+            snapshot.restore(&mut self.vm);
+            return Err(diag.errors().iter().map(|e| e.msg.message()).collect());
+        }
+
+        Ok(snapshot)
+    }
+}
+
+fn leak(ast: Ast) -> &'static Ast {
+    Box::leak(Box::new(ast))
+}
+
+// everything an input adds to the VM, code is only generated when running
+// an input and isn't part of it. Every table is rolled back to its own
+// length, semck also specializes classes and functions and registers impls
+// and extensions with declarations that existed before the input.
+struct Snapshot<'ast> {
+    ast: &'ast Ast,
+    files: usize,
+    sym: SymTable,
+    consts: usize,
+    structs: usize,
+    struct_defs: usize,
+    classes: usize,
+    class_defs: usize,
+    extensions: usize,
+    modules: usize,
+    module_defs: usize,
+    fcts: usize,
+    fct_defs: usize,
+    enums: usize,
+    enum_defs: usize,
+    traits: usize,
+    impls: usize,
+    globals: usize,
+    tuples: usize,
+    lists: usize,
+    lambda_types: usize,
+    // lengths of `impls`, `traits` and `extensions` of each class
+    class_lists: Vec<(usize, usize, usize)>,
+    // length of `extensions` of each enum
+    enum_extensions: Vec<usize>,
+}
+
+impl<'ast> Snapshot<'ast> {
+    fn new(vm: &VM<'ast>) -> Snapshot<'ast> {
+        let class_lists = vm
+            .classes
+            .iter()
+            .map(|cls| {
+                let cls = cls.read();
+                (cls.impls.len(), cls.traits.len(), cls.extensions.len())
+            })
+            .collect();
+
+        let enum_extensions = vm
+            .enums
+            .iter()
+            .map(|xenum| xenum.read().extensions.len())
+            .collect();
+
+        Snapshot {
+            ast: vm.ast,
+            files: vm.files.len(),
+            sym: vm.sym.lock().clone(),
+            consts: vm.consts.len(),
+            structs: vm.structs.len(),
+            struct_defs: vm.struct_defs.len(),
+            classes: vm.classes.len(),
+            class_defs: vm.class_defs.len(),
+            extensions: vm.extensions.len(),
+            modules: vm.modules.len(),
+            module_defs: vm.module_defs.len(),
+            fcts: vm.fcts.len(),
+            fct_defs: vm.fct_defs.len(),
+            enums: vm.enums.len(),
+            enum_defs: vm.enum_defs.len(),
+            traits: vm.traits.len(),
+            impls: vm.impls.len(),
+            globals: vm.globals.len(),
+            tuples: vm.tuples.lock().len(),
+            lists: vm.lists.lock().len(),
+            lambda_types: vm.lambda_types.lock().len(),
+            class_lists,
+            enum_extensions,
+        }
+    }
+
+    fn restore(self, vm: &mut VM<'ast>) {
+        vm.ast = self.ast;
+        vm.files.truncate(self.files);
+        *vm.sym.lock() = self.sym;
+        vm.consts.truncate(self.consts);
+        vm.structs.truncate(self.structs);
+        vm.struct_defs.truncate(self.struct_defs);
+        vm.classes.truncate(self.classes);
+        vm.class_defs.truncate(self.class_defs);
+        vm.extensions.truncate(self.extensions);
+        vm.modules.truncate(self.modules);
+        vm.module_defs.truncate(self.module_defs);
+        vm.fcts.truncate(self.fcts);
+        vm.fct_defs.truncate(self.fct_defs);
+        vm.enums.truncate(self.enums);
+        vm.enum_defs.truncate(self.enum_defs);
+        vm.traits.truncate(self.traits);
+        vm.impls.truncate(self.impls);
+        vm.globals.truncate(self.globals);
+        vm.tuples.lock().truncate(self.tuples);
+        vm.lists.lock().truncate(self.lists);
+        vm.lambda_types.lock().truncate(self.lambda_types);
+
+        let (class_defs, fct_defs) = (self.class_defs, self.fct_defs);
+        let (struct_defs, enum_defs) = (self.struct_defs, self.enum_defs);
+
+        vm.lambda_defs.lock().retain(|fct_id, cls_id| {
+            fct_id.to_usize() < fct_defs && cls_id.to_usize() < class_defs
+        });
+        vm.box_defs
+            .lock()
+            .retain(|_, cls_id| cls_id.to_usize() < class_defs);
+
+        for (cls, &(impls, traits, extensions)) in vm.classes.iter().zip(&self.class_lists) {
+            let mut cls = cls.write();
+            cls.impls.truncate(impls);
+            cls.traits.truncate(traits);
+            cls.extensions.truncate(extensions);
+            cls.specializations
+                .write()
+                .retain(|_, id| id.to_usize() < class_defs);
+        }
+
+        for (xenum, &extensions) in vm.enums.iter().zip(&self.enum_extensions) {
+            let mut xenum = xenum.write();
+            xenum.extensions.truncate(extensions);
+            xenum
+                .specializations
+                .write()
+                .retain(|_, id| id.to_usize() < enum_defs);
+        }
+
+        for xstruct in vm.structs.iter() {
+            let xstruct = xstruct.lock();
+            xstruct
+                .specializations
+                .write()
+                .retain(|_, id| id.to_usize() < struct_defs);
+        }
+
+        for fct in vm.fcts.iter() {
+            let fct = fct.read();
+            fct.specializations
+                .write()
+                .retain(|_, id| id.to_usize() < fct_defs);
+        }
+    }
+}
+
+fn report_errors(errors: &[String]) {
+    for error in errors {
+        eprintln!("error: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ty::BuiltinType;
+
+    // inputs are only compiled, other tests create VMs concurrently while
+    // running code needs the VM of the process
+    fn repl() -> Repl {
+        Repl::new(Default::default())
+            .ok()
+            .expect("stdlib has errors")
+    }
+
+    #[test]
+    fn use_declaration_of_earlier_input() {
+        let mut repl = repl();
+
+        assert_eq!(
+            repl.compile("fun twice(x: Int32) -> Int32 { 2 * x }"),
+            Ok(None)
+        );
+        assert!(repl.vm.fct_by_name("twice").is_some());
+
+        let fct_id = repl.compile("twice(21)").unwrap().unwrap();
+        assert_eq!(repl.vm.fct_by_name("repl0"), Some(fct_id));
+
+        assert!(repl.compile("class Foo(let a: Int32)").is_ok());
+        assert!(repl.compile("Foo(twice(1)).a").unwrap().is_some());
+        assert!(repl.compile("twice(\"a\")").is_err());
+    }
+
+    #[test]
+    fn binding_survives_inputs() {
+        let mut repl = repl();
+
+        assert!(repl.compile("let x = 1 + 2;").unwrap().is_some());
+        let global_id = repl.vm.global_by_name("x");
+        let global = repl.vm.globals.idx(global_id);
+        assert_eq!(global.read().ty, BuiltinType::Int32);
+        assert!(!global.read().reassignable);
+        assert!(global.read().initializer.is_some());
+
+        assert!(repl.compile("x * 2").unwrap().is_some());
+        assert!(repl.compile("x = 4;").is_err());
+
+        assert!(repl.compile("var y = \"a\";").unwrap().is_some());
+        assert!(repl.compile("y = y + x.toString();").unwrap().is_some());
+        assert!(
+            repl.vm
+                .globals
+                .idx(repl.vm.global_by_name("y"))
+                .read()
+                .reassignable
+        );
+    }
+
+    #[test]
+    fn input_with_errors_leaves_state_unchanged() {
+        let mut repl = repl();
+        assert!(repl.compile("fun foo() -> Int32 { 1 }").is_ok());
+        assert!(repl.compile("trait T { fun f() -> Int32; }").is_ok());
+        assert!(repl.compile("class A").is_ok());
+
+        let files = repl.vm.files.len();
+        let fcts = repl.vm.fcts.len();
+        let classes = repl.vm.classes.len();
+        let impls = repl.vm.impls.len();
+        let globals = repl.vm.globals.len();
+
+        assert!(repl
+            .compile("class Bar\nfun foo() -> Int32 { true }")
+            .is_err());
+        assert!(repl.compile("fun baz() -> Int32 { true }").is_err());
+        assert!(repl.compile("let z = undefined();").is_err());
+        assert!(repl
+            .compile("impl T for A { fun f() -> Int32 { false } }")
+            .is_err());
+        assert!(repl.compile("bar(").is_err());
+
+        assert_eq!(repl.vm.files.len(), files);
+        assert_eq!(repl.vm.fcts.len(), fcts);
+        assert_eq!(repl.vm.classes.len(), classes);
+        assert_eq!(repl.vm.impls.len(), impls);
+        assert_eq!(repl.vm.globals.len(), globals);
+        assert!(repl.vm.fct_by_name("baz").is_none());
+
+        let cls_id = repl.vm.cls_by_name("A");
+        assert!(repl.vm.classes.idx(cls_id).read().impls.is_empty());
+
+        // the names of the failed inputs can be declared again
+        assert!(repl.compile("class Bar").is_ok());
+        assert!(repl.compile("let z = foo();").is_ok());
+        assert!(repl
+            .compile("impl T for A { fun f() -> Int32 { 7 } }")
+            .is_ok());
+        assert!(repl.compile("A().f() + foo() + z").unwrap().is_some());
+    }
+
+    #[test]
+    fn input_with_errors_leaves_definitions_unchanged() {
+        let mut repl = repl();
+        assert!(repl.compile("class Box[T](let value: T)").is_ok());
+
+        let class_defs = repl.vm.class_defs.len();
+        let fct_defs = repl.vm.fct_defs.len();
+        let lambda_types = repl.vm.lambda_types.lock().len();
+        let lists = repl.vm.lists.lock().len();
+
+        assert!(repl
+            .compile(
+                "fun bad() -> Int32 {\n\
+                 let f = |x: Int32, y: Float64| -> Int64 { 1L };\n\
+                 let b = Box[(Int32, Float64)]((1, 2.0));\n\
+                 true\n\
+                 }"
+            )
+            .is_err());
+
+        assert_eq!(repl.vm.class_defs.len(), class_defs);
+        assert_eq!(repl.vm.fct_defs.len(), fct_defs);
+        assert_eq!(repl.vm.lambda_types.lock().len(), lambda_types);
+        assert_eq!(repl.vm.lists.lock().len(), lists);
+
+        let cls_id = repl.vm.cls_by_name("Box");
+        let cls = repl.vm.classes.idx(cls_id);
+        assert!(cls
+            .read()
+            .specializations
+            .read()
+            .values()
+            .all(|id| id.to_usize() < class_defs));
+
+        assert!(repl
+            .compile("Box[(Int32, Float64)]((1, 2.0)).value.0")
+            .unwrap()
+            .is_some());
+    }
+}
@@ -1,18 +1,22 @@
 use std::fs;
 use std::path::Path;
 
+use crate::error::diag::{Report, Severity, Snippet};
 use crate::error::msg::SemError;
+use crate::vm::FctId;
 use crate::vm::VM;
-use crate::vm::{Fct, FctId};
 use dora_parser::ast::{self, Ast};
+use dora_parser::interner::Name;
 
-use crate::driver::cmd;
-use crate::object;
+use crate::bytecode::file::BytecodeFile;
+use crate::bytecode::{self, cache};
+use crate::driver::cmd::{self, ErrorFormat};
+use crate::driver::{heap_analyze, repl, test_runner};
 use crate::timer::Timer;
 use dora_parser::lexer::reader::Reader;
 
+use crate::profiler;
 use crate::semck;
-use crate::semck::specialize::specialize_class_id;
 use crate::ty::BuiltinType;
 use dora_parser::parser::Parser;
 
@@ -30,13 +34,27 @@ pub fn start(content: Option<&str>) -> i32 {
         return 0;
     }
 
+    if args.cmd_repl {
+        return repl::start(args);
+    }
+
+    if args.cmd_heap_analyze {
+        return heap_analyze::start(&args.arg_file);
+    }
+
+    if args.cmd_compile && !args.flag_emit_bytecode_file {
+        println!("error: nothing to compile, use `dora compile --emit-bytecode-file <file>`");
+        return 1;
+    }
+
     let mut ast = Ast::new();
     let empty = Ast::new();
     let mut vm = VM::new(args, &empty);
 
-    if let Err(code) = parse_all_files(&mut vm, &mut ast, content) {
-        return code;
-    }
+    let first_program_file = match parse_all_files(&mut vm, &mut ast, content) {
+        Ok(first_program_file) => first_program_file,
+        Err(code) => return code,
+    };
 
     vm.ast = &ast;
 
@@ -44,11 +62,34 @@ pub fn start(content: Option<&str>) -> i32 {
         ast::dump::dump(&vm.ast, &vm.interner);
     }
 
+    // `dora compile` always writes a new bytecode file
+    if !fuzzing && !vm.args.cmd_compile && !vm.args.cmd_stdlib_snapshot {
+        let files = vm.files.len();
+        vm.bytecode_cache =
+            cache::path(&vm.args.arg_file).and_then(|path| cache::open(&vm, &path, files));
+    }
+
+    // stdlib and boots are parsed and their declarations checked on every
+    // start. the snapshot of their bytecode only saves type checking and
+    // bytecode generation of non-generic function bodies, the checked
+    // declarations refer to the AST and can't be loaded without it.
+    if !vm.args.cmd_stdlib_snapshot {
+        vm.stdlib_snapshot = cache::stdlib_snapshot_path(&vm.args)
+            .and_then(|path| cache::open_stdlib_snapshot(&vm, &path, first_program_file));
+    }
+
     semck::check(&mut vm);
 
+    // lints need the type information of function bodies, which isn't
+    // computed for a program loaded from its `.dorabc` file. lint warnings
+    // are therefore only reported when the program is checked from source.
+    if !vm.diag.lock().has_errors() && vm.bytecode_cache.is_none() {
+        semck::lintck::check(&vm, first_program_file);
+    }
+
     semck::prelude::install_conditional_intrinsics(&mut vm);
 
-    let main = if vm.args.cmd_test {
+    let main = if vm.args.cmd_test || vm.args.cmd_stdlib_snapshot {
         None
     } else {
         find_main(&vm)
@@ -58,15 +99,31 @@ pub fn start(content: Option<&str>) -> i32 {
         vm.diag.lock().dump(&vm);
         let no_errors = vm.diag.lock().errors().len();
 
-        if no_errors == 1 {
-            eprintln!("{} error found.", no_errors);
-        } else {
-            eprintln!("{} errors found.", no_errors);
+        if vm.args.error_format() == ErrorFormat::Human {
+            if no_errors == 1 {
+                eprintln!("{} error found.", no_errors);
+            } else {
+                eprintln!("{} errors found.", no_errors);
+            }
         }
 
         return 1;
     }
 
+    // a single test run by the test runner in its own process doesn't show
+    // the warnings again
+    if vm.diag.lock().has_warnings() && vm.args.flag_test_exact.is_none() {
+        vm.diag.lock().dump(&vm);
+    }
+
+    if vm.args.cmd_compile {
+        return compile(&vm, first_program_file);
+    }
+
+    if vm.args.cmd_stdlib_snapshot {
+        return write_stdlib_snapshot(&vm);
+    }
+
     if !vm.args.cmd_test && main.is_none() {
         println!("error: no `main` function found in the program");
         return 1;
@@ -80,9 +137,10 @@ pub fn start(content: Option<&str>) -> i32 {
     let mut timer = Timer::new(vm.args.flag_gc_stats);
 
     vm.threads.attach_current_thread();
+    profiler::start(&vm);
 
     let code = if vm.args.cmd_test {
-        run_tests(&vm)
+        test_runner::run_tests(&vm, !fuzzing)
     } else {
         run_main(&vm, main.unwrap())
     };
@@ -90,6 +148,9 @@ pub fn start(content: Option<&str>) -> i32 {
     vm.threads.detach_current_thread();
     vm.threads.join_all();
 
+    profiler::stop(&vm);
+    vm.gc.write_alloc_profile(&vm);
+
     if vm.args.flag_gc_stats {
         let duration = timer.stop();
         vm.dump_gc_summary(duration);
@@ -98,112 +159,92 @@ pub fn start(content: Option<&str>) -> i32 {
     code
 }
 
-fn parse_all_files(vm: &mut VM, ast: &mut Ast, content: Option<&str>) -> Result<(), i32> {
+// returns the index of the first file that belongs to the program itself
+fn parse_all_files(vm: &mut VM, ast: &mut Ast, content: Option<&str>) -> Result<usize, i32> {
     let fuzzing = content.is_some();
 
-    let stdlib_dir = vm.args.flag_stdlib.clone();
-
-    if let Some(stdlib) = stdlib_dir {
-        parse_dir(&stdlib, vm, ast)?;
-    } else {
-        parse_bundled_stdlib(vm, ast)?;
-    }
+    parse_stdlib(vm, ast)?;
 
-    let boots_dir = vm.args.flag_boots.clone();
+    let first_program_file = ast.files.len();
 
-    if let Some(boots) = boots_dir {
-        parse_dir(&boots, vm, ast)?;
+    if vm.args.cmd_stdlib_snapshot {
+        return Ok(first_program_file);
     }
 
     if fuzzing {
-        return parse_str(content.unwrap(), vm, ast);
+        parse_str(content.unwrap(), vm, ast)?;
+        return Ok(first_program_file);
     }
 
     let arg_file = vm.args.arg_file.clone();
     let path = Path::new(&arg_file);
 
     if path.is_file() {
-        parse_file(&arg_file, vm, ast)
+        parse_file(&arg_file, vm, ast)?;
     } else if path.is_dir() {
-        parse_dir(&arg_file, vm, ast)
+        parse_package_dir(path, &mut Vec::new(), vm, ast)?;
     } else {
         println!("file or directory `{}` does not exist.", &arg_file);
-        Err(1)
+        return Err(1);
     }
-}
 
-fn run_tests<'ast>(vm: &VM<'ast>) -> i32 {
-    let mut tests = 0;
-    let mut passed = 0;
-
-    for fct in vm.fcts.iter() {
-        let fct = fct.read();
-
-        if !is_test_fct(vm, &*fct) || !test_filter_matches(vm, &*fct) {
-            continue;
-        }
-
-        tests += 1;
+    Ok(first_program_file)
+}
 
-        print!("test {} ... ", vm.interner.str(fct.name));
+// parses the stdlib and, with `--boots`, the boots compiler
+pub fn parse_stdlib(vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
+    let stdlib_dir = vm.args.flag_stdlib.clone();
 
-        if run_test(vm, fct.id) {
-            passed += 1;
-            println!("ok");
-        } else {
-            println!("failed");
-        }
+    if let Some(stdlib) = stdlib_dir {
+        parse_dir(&stdlib, vm, ast)?;
+    } else {
+        parse_bundled_stdlib(vm, ast)?;
     }
 
-    println!(
-        "{} tests executed; {} passed; {} failed.",
-        tests,
-        passed,
-        tests - passed
-    );
+    let boots_dir = vm.args.flag_boots.clone();
 
-    // if all tests passed exit with 0, otherwise 1
-    if tests == passed {
-        0
-    } else {
-        1
+    if let Some(boots) = boots_dir {
+        parse_dir(&boots, vm, ast)?;
     }
+
+    Ok(())
 }
 
-fn run_test<'ast>(vm: &VM<'ast>, fct: FctId) -> bool {
-    let testing_class = vm.vips.testing_class;
-    let testing_class = specialize_class_id(vm, testing_class);
-    let testing = object::alloc(vm, testing_class).cast();
-    vm.run_test(fct, testing);
+fn compile<'ast>(vm: &VM<'ast>, first_program_file: usize) -> i32 {
+    let path = match cache::path(&vm.args.arg_file) {
+        Some(path) => path,
+        None => {
+            println!("unable to determine name of bytecode file");
+            return 1;
+        }
+    };
 
-    !testing.has_failed()
+    let file = cache::emit(vm, first_program_file..vm.files.len());
+    write_bytecode_file(&path, &file)
 }
 
-fn is_test_fct<'ast>(vm: &VM<'ast>, fct: &Fct<'ast>) -> bool {
-    // tests need to be standalone functions, with no return type and a single parameter
-    if !fct.parent.is_none() || !fct.return_type.is_unit() || fct.param_types.len() != 1 {
-        return false;
-    }
-
-    // parameter needs to be of type Testing
-    let testing_cls = vm.cls(vm.vips.testing_class);
-    if fct.param_types[0] != testing_cls {
-        return false;
-    }
+// the snapshot covers all files parsed before the program, which are the
+// stdlib and, with `--boots`, the boots compiler
+fn write_stdlib_snapshot<'ast>(vm: &VM<'ast>) -> i32 {
+    let path = match cache::stdlib_snapshot_path(&vm.args) {
+        Some(path) => path,
+        None => {
+            println!("unable to determine name of stdlib snapshot");
+            return 1;
+        }
+    };
 
-    // the function needs to be marked with the @test annotation
-    fct.is_test
+    let file = cache::emit_stdlib_snapshot(vm, vm.files.len());
+    write_bytecode_file(&path, &file)
 }
 
-fn test_filter_matches(vm: &VM, fct: &Fct) -> bool {
-    if vm.args.flag_test_filter.is_none() {
-        return true;
+fn write_bytecode_file(path: &Path, file: &BytecodeFile) -> i32 {
+    if let Err(error) = fs::write(path, bytecode::file::write(file)) {
+        println!("unable to write file `{}`: {}", path.display(), error);
+        return 1;
     }
 
-    let filter = vm.args.flag_test_filter.as_ref().unwrap();
-    let name = fct.full_name(vm);
-
-    name.contains(filter)
+    0
 }
 
 fn run_main<'ast>(vm: &VM<'ast>, main: FctId) -> i32 {
@@ -242,6 +283,36 @@ fn parse_dir(dirname: &str, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
     }
 }
 
+// files in subdirectories of the program directory belong to the package
+// named after the relative path, e.g. `foo/bar` becomes `foo::bar`
+fn parse_package_dir(
+    path: &Path,
+    package: &mut Vec<Name>,
+    vm: &mut VM,
+    ast: &mut Ast,
+) -> Result<(), i32> {
+    for entry in fs::read_dir(path).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            let name = path.file_name().unwrap().to_string_lossy();
+
+            if name.starts_with('.') {
+                continue;
+            }
+
+            package.push(vm.interner.intern(&name));
+            parse_package_dir(&path, package, vm, ast)?;
+            package.pop();
+        } else if should_file_be_parsed(&path) {
+            parse_file(path.to_str().unwrap(), vm, ast)?;
+            ast.files.last_mut().unwrap().package = package.clone();
+        }
+    }
+
+    Ok(())
+}
+
 fn should_file_be_parsed(path: &Path) -> bool {
     if !path.is_file() {
         return false;
@@ -313,6 +384,7 @@ fn parse_str(file: &str, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
 
 fn parse_reader(reader: Reader, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
     let filename: String = reader.path().into();
+    let content: String = reader.content().into();
     let parser = Parser::new(reader, &vm.id_generator, ast, &mut vm.interner);
 
     match parser.parse() {
@@ -323,13 +395,19 @@ fn parse_reader(reader: Reader, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
         }
 
         Err(error) => {
-            println!(
-                "error in {} at {}: {}",
-                filename,
-                error.pos,
-                error.error.message()
-            );
-            println!("error during parsing.");
+            let report = Report {
+                severity: Severity::Error,
+                code: None,
+                message: error.error.message(),
+                primary: Snippet::new(&filename, &content, error.pos, None),
+                labels: Vec::new(),
+            };
+
+            report.emit(vm.args.error_format());
+
+            if vm.args.error_format() == ErrorFormat::Human {
+                eprintln!("error during parsing.");
+            }
 
             Err(1)
         }
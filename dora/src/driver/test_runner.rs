@@ -0,0 +1,409 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use crate::driver::cmd::TestFormat;
+use crate::object;
+use crate::semck::specialize::specialize_class_id;
+use crate::timer::Timer;
+use crate::utils::json_escape;
+use crate::vm::{Fct, FctId, VM};
+
+struct TestResult {
+    name: String,
+    passed: bool,
+    duration: f32,
+    output: String,
+}
+
+// `isolated` is false when the program isn't given on the command line,
+// tests then run in this process and an abort ends the whole run
+pub fn run_tests<'ast>(vm: &VM<'ast>, isolated: bool) -> i32 {
+    if let Some(ref name) = vm.args.flag_test_exact {
+        return run_exact_test(vm, name);
+    }
+
+    let format = vm.args.test_format();
+    let mut results = Vec::new();
+
+    if format == TestFormat::Tap {
+        println!("TAP version 13");
+    }
+
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        if !is_test_fct(vm, &*fct) || !test_filter_matches(vm, &*fct) {
+            continue;
+        }
+
+        let name = vm.interner.str(fct.name).to_string();
+
+        if format == TestFormat::Text {
+            print!("test {} ... ", name);
+            io::stdout().flush().expect("flushing stdout failed");
+        }
+
+        let result = if isolated {
+            run_test_isolated(name, &fct.full_name(vm))
+        } else {
+            run_test_in_process(vm, name, fct.id)
+        };
+
+        match format {
+            TestFormat::Text => print_text(&result),
+            TestFormat::Tap => print_tap(results.len() + 1, &result),
+            TestFormat::Junit | TestFormat::Json => {}
+        }
+
+        results.push(result);
+    }
+
+    let tests = results.len();
+    let passed = results.iter().filter(|r| r.passed).count();
+
+    match format {
+        TestFormat::Text => println!(
+            "{} tests executed; {} passed; {} failed.",
+            tests,
+            passed,
+            tests - passed
+        ),
+        TestFormat::Tap => println!("1..{}", tests),
+        TestFormat::Junit => print_junit(&results),
+        TestFormat::Json => print_json(&results),
+    }
+
+    // if all tests passed exit with 0, otherwise 1
+    if tests == passed {
+        0
+    } else {
+        1
+    }
+}
+
+fn print_text(result: &TestResult) {
+    print!("{}", result.output);
+
+    let status = if result.passed { "ok" } else { "failed" };
+    println!("{} ({:.1}ms)", status, result.duration);
+}
+
+fn print_tap(idx: usize, result: &TestResult) {
+    let status = if result.passed { "ok" } else { "not ok" };
+    println!(
+        "{} {} - {} # time={:.1}ms",
+        status, idx, result.name, result.duration
+    );
+
+    for line in result.output.lines() {
+        println!("# {}", line);
+    }
+}
+
+fn print_junit(results: &[TestResult]) {
+    let failures = results.iter().filter(|r| !r.passed).count();
+    let duration: f32 = results.iter().map(|r| r.duration).sum();
+
+    println!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    println!(
+        "<testsuite name=\"dora\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failures,
+        duration / 1000.0
+    );
+
+    for result in results {
+        print!(
+            "  <testcase name=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.name),
+            result.duration / 1000.0
+        );
+
+        if result.passed && result.output.is_empty() {
+            println!("/>");
+            continue;
+        }
+
+        println!(">");
+
+        if !result.passed {
+            println!(
+                "    <failure message=\"test failed\">{}</failure>",
+                xml_escape(&result.output)
+            );
+        } else {
+            println!(
+                "    <system-out>{}</system-out>",
+                xml_escape(&result.output)
+            );
+        }
+
+        println!("  </testcase>");
+    }
+
+    println!("</testsuite>");
+}
+
+fn print_json(results: &[TestResult]) {
+    let passed = results.iter().filter(|r| r.passed).count();
+
+    let tests = results
+        .iter()
+        .map(|r| {
+            format!(
+                "{{\"name\":\"{}\",\"passed\":{},\"duration_ms\":{:.3},\"output\":\"{}\"}}",
+                json_escape(&r.name),
+                r.passed,
+                r.duration,
+                json_escape(&r.output)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    println!(
+        "{{\"tests\":{},\"passed\":{},\"failed\":{},\"results\":[{}]}}",
+        results.len(),
+        passed,
+        results.len() - passed,
+        tests
+    );
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        match ch {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(ch),
+        }
+    }
+
+    result
+}
+
+// Each test runs in a new process of the same program, so that an abort
+// (e.g. a failed assert or fatalError) only fails this test instead of ending
+// the whole run. Forking isn't an option: helper threads of the VM (GC
+// workers, concurrent marker, profiler) don't exist in a forked child, a lock
+// held by them would never be released there. Output of the process is
+// captured and returned together with the result.
+fn run_test_isolated(name: String, full_name: &str) -> TestResult {
+    let exe = env::current_exe().expect("unknown path of executable");
+    let mut args = child_args(env::args_os().skip(1), &name);
+    args.push(format!("--test-exact={}", full_name).into());
+
+    let duration_file = env::temp_dir().join(format!("dora-test-{}-{}", process::id(), name));
+    let mut timer = Timer::new(true);
+
+    let process = Command::new(exe)
+        .args(&args)
+        .env(DURATION_FILE_VAR, &duration_file)
+        .stdin(Stdio::null())
+        .output()
+        .expect("starting test process failed");
+
+    let mut duration = timer.stop();
+
+    // use the duration of the test without starting the process, the file
+    // is missing when the test aborted
+    if let Ok(content) = fs::read_to_string(&duration_file) {
+        if let Ok(test_duration) = content.trim().parse() {
+            duration = test_duration;
+        }
+
+        let _ = fs::remove_file(&duration_file);
+    }
+
+    let mut output = String::from_utf8_lossy(&process.stdout).into_owned();
+    output.push_str(&String::from_utf8_lossy(&process.stderr));
+
+    TestResult {
+        name,
+        passed: process.status.success(),
+        duration,
+        output,
+    }
+}
+
+// options writing into a file get a path of their own for each test, all
+// processes would write into the same file otherwise. heap dumps are already
+// named after the id of the process.
+const FILE_OPTIONS: &[&str] = &["--profile", "--gc-log", "--alloc-profile"];
+
+fn child_args(args: impl Iterator<Item = OsString>, test: &str) -> Vec<OsString> {
+    let mut result = Vec::new();
+    let mut file_option = None;
+
+    for arg in args {
+        if let Some(option) = file_option.take() {
+            result.push(option);
+            result.push(test_path(&arg, test));
+            continue;
+        }
+
+        let value = arg.to_string_lossy().into_owned();
+
+        // the path is the next argument for `--profile <file>`
+        if FILE_OPTIONS.contains(&value.as_str()) {
+            file_option = Some(arg);
+            continue;
+        }
+
+        match FILE_OPTIONS
+            .iter()
+            .find(|&&option| value.starts_with(&format!("{}=", option)))
+        {
+            Some(&option) => {
+                let path = test_path(value[option.len() + 1..].as_ref(), test);
+                let mut arg = OsString::from(format!("{}=", option));
+                arg.push(path);
+                result.push(arg);
+            }
+
+            None => result.push(arg),
+        }
+    }
+
+    result.extend(file_option);
+    result
+}
+
+fn test_path(path: &OsStr, test: &str) -> OsString {
+    let mut path = path.to_os_string();
+    path.push(format!(".{}", test));
+    path
+}
+
+// names the file the test process writes its duration into
+const DURATION_FILE_VAR: &str = "DORA_TEST_DURATION_FILE";
+
+fn run_test_in_process<'ast>(vm: &VM<'ast>, name: String, fct_id: FctId) -> TestResult {
+    let mut timer = Timer::new(true);
+    let passed = run_test(vm, fct_id);
+
+    TestResult {
+        name,
+        passed,
+        duration: timer.stop(),
+        output: String::new(),
+    }
+}
+
+// runs the test started by `run_test_isolated`
+fn run_exact_test<'ast>(vm: &VM<'ast>, name: &str) -> i32 {
+    let fct_id = vm.fcts.iter().find_map(|fct| {
+        let fct = fct.read();
+
+        if is_test_fct(vm, &*fct) && fct.full_name(vm) == name {
+            Some(fct.id)
+        } else {
+            None
+        }
+    });
+
+    let fct_id = match fct_id {
+        Some(fct_id) => fct_id,
+        None => {
+            println!("error: no test named `{}` found", name);
+            return 1;
+        }
+    };
+
+    let mut timer = Timer::new(true);
+    let passed = run_test(vm, fct_id);
+
+    if let Some(path) = env::var_os(DURATION_FILE_VAR) {
+        let _ = fs::write(PathBuf::from(path), timer.stop().to_string());
+    }
+
+    if passed {
+        0
+    } else {
+        1
+    }
+}
+
+fn run_test<'ast>(vm: &VM<'ast>, fct: FctId) -> bool {
+    let testing_class = vm.vips.testing_class;
+    let testing_class = specialize_class_id(vm, testing_class);
+    let testing = object::alloc(vm, testing_class).cast();
+    vm.run_test(fct, testing);
+
+    !testing.has_failed()
+}
+
+fn is_test_fct<'ast>(vm: &VM<'ast>, fct: &Fct<'ast>) -> bool {
+    // tests need to be standalone functions, with no return type and a single parameter
+    if !fct.parent.is_none() || !fct.return_type.is_unit() || fct.param_types.len() != 1 {
+        return false;
+    }
+
+    // parameter needs to be of type Testing
+    let testing_cls = vm.cls(vm.vips.testing_class);
+    if fct.param_types[0] != testing_cls {
+        return false;
+    }
+
+    // the function needs to be marked with the @test annotation
+    fct.is_test
+}
+
+fn test_filter_matches(vm: &VM, fct: &Fct) -> bool {
+    if vm.args.flag_test_filter.is_none() {
+        return true;
+    }
+
+    let filter = vm.args.flag_test_filter.as_ref().unwrap();
+    let name = fct.full_name(vm);
+
+    name.contains(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(|&arg| arg.into()).collect()
+    }
+
+    #[test]
+    fn child_args_use_own_files() {
+        let result = child_args(
+            args(&[
+                "test",
+                "--profile=prof.folded",
+                "--gc-log",
+                "gc.json",
+                "--alloc-profile=alloc.folded",
+                "--gc-stats",
+                "foo.dora",
+            ])
+            .into_iter(),
+            "testFoo",
+        );
+
+        assert_eq!(
+            result,
+            args(&[
+                "test",
+                "--profile=prof.folded.testFoo",
+                "--gc-log",
+                "gc.json.testFoo",
+                "--alloc-profile=alloc.folded.testFoo",
+                "--gc-stats",
+                "foo.dora",
+            ])
+        );
+    }
+}
@@ -1,32 +1,409 @@
+use std::fmt::Write;
+
+use crate::driver::cmd::ErrorFormat;
 use crate::error::msg::{SemError, SemErrorAndPos};
+use crate::utils::json_escape;
 use crate::vm::{FileId, VM};
 
-use dora_parser::lexer::position::Position;
+use dora_parser::lexer::position::{Position, Span};
+
+// columns are counted like the lexer does
+const TAB_WIDTH: u32 = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
 
 pub struct Diagnostic {
     errors: Vec<SemErrorAndPos>,
+    warnings: Vec<SemErrorAndPos>,
 }
 
 impl Diagnostic {
     pub fn new() -> Diagnostic {
-        Diagnostic { errors: Vec::new() }
+        Diagnostic {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
     }
 
     pub fn errors(&self) -> &[SemErrorAndPos] {
         &self.errors
     }
 
+    pub fn warnings(&self) -> &[SemErrorAndPos] {
+        &self.warnings
+    }
+
     pub fn report(&mut self, file: FileId, pos: Position, msg: SemError) {
         self.errors.push(SemErrorAndPos::new(file, pos, msg));
     }
 
+    pub fn report_span(&mut self, file: FileId, pos: Position, span: Span, msg: SemError) {
+        self.errors
+            .push(SemErrorAndPos::new(file, pos, msg).with_span(span));
+    }
+
+    pub fn report_error(&mut self, error: SemErrorAndPos) {
+        self.errors.push(error);
+    }
+
+    pub fn warn(&mut self, file: FileId, pos: Position, msg: SemError) {
+        self.warnings.push(SemErrorAndPos::new(file, pos, msg));
+    }
+
+    pub fn report_warning(&mut self, warning: SemErrorAndPos) {
+        self.warnings.push(warning);
+    }
+
     pub fn has_errors(&self) -> bool {
         !self.errors.is_empty()
     }
 
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    // prints errors and warnings ordered by their position in the source
     pub fn dump(&self, vm: &VM) {
-        for err in &self.errors {
-            eprintln!("{}", &err.message(vm));
+        let errors = self.errors.iter().map(|e| (Severity::Error, e));
+        let warnings = self.warnings.iter().map(|w| (Severity::Warning, w));
+        let mut all: Vec<_> = errors.chain(warnings).collect();
+        all.sort_by_key(|(_, e)| (e.file.to_usize(), e.pos.line, e.pos.column));
+
+        let format = vm.args.error_format();
+
+        for (severity, err) in all {
+            let file = vm.file(err.file);
+
+            let labels = err
+                .labels
+                .iter()
+                .map(|label| {
+                    let file = vm.file(label.file);
+                    let snippet = Snippet::new(&file.name, &file.content, label.pos, None);
+                    (snippet, label.message.as_str())
+                })
+                .collect();
+
+            let report = Report {
+                severity,
+                code: Some(err.msg.code()),
+                message: err.msg.message(),
+                primary: Snippet::new(&file.name, &file.content, err.pos, err.span),
+                labels,
+            };
+
+            report.emit(format);
+        }
+    }
+}
+
+// a diagnostic ready for printing, parse errors are reported before the file
+// is known to the VM and use this directly
+pub struct Report<'a> {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub primary: Snippet<'a>,
+    pub labels: Vec<(Snippet<'a>, &'a str)>,
+}
+
+impl<'a> Report<'a> {
+    pub fn emit(&self, format: ErrorFormat) {
+        let output = match format {
+            ErrorFormat::Human => self.human(),
+            ErrorFormat::Json => self.json(),
+        };
+
+        eprintln!("{}", output);
+    }
+
+    // rustc-like output: the message, its location and the source line with
+    // the span underlined, followed by the secondary labels
+    fn human(&self) -> String {
+        let mut out = String::new();
+        let underline = self.primary.underline();
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(snippet, message)| (snippet, snippet.underline(), message))
+            .collect();
+
+        let width = labels
+            .iter()
+            .map(|(_, underline, _)| underline.line)
+            .chain(Some(underline.line))
+            .max()
+            .unwrap()
+            .to_string()
+            .len();
+        let gutter = " ".repeat(width);
+
+        match self.code {
+            Some(code) => write!(out, "{}[{}]: ", self.severity.name(), code).unwrap(),
+            None => write!(out, "{}: ", self.severity.name()).unwrap(),
+        }
+
+        writeln!(out, "{}", self.message).unwrap();
+        writeln!(
+            out,
+            "{}--> {}:{}",
+            gutter, self.primary.path, self.primary.pos
+        )
+        .unwrap();
+        writeln!(out, "{} |", gutter).unwrap();
+        underline.write(&mut out, width, '^', "");
+
+        for (snippet, underline, message) in labels {
+            if snippet.path != self.primary.path {
+                writeln!(out, "{}::: {}:{}", gutter, snippet.path, snippet.pos).unwrap();
+            }
+
+            writeln!(out, "{} |", gutter).unwrap();
+            underline.write(&mut out, width, '-', message);
+        }
+
+        out
+    }
+
+    // one object per line, so that editors can read diagnostics as they come
+    fn json(&self) -> String {
+        let underline = self.primary.underline();
+
+        let labels = self
+            .labels
+            .iter()
+            .map(|(snippet, message)| {
+                format!(
+                    "{{\"file\":\"{}\",\"line\":{},\"column\":{},\"message\":\"{}\"}}",
+                    json_escape(snippet.path),
+                    snippet.pos.line,
+                    snippet.pos.column,
+                    json_escape(message)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let code = match self.code {
+            Some(code) => format!("\"{}\"", code),
+            None => "null".into(),
+        };
+
+        format!(
+            "{{\"severity\":\"{}\",\"code\":{},\"message\":\"{}\",\"file\":\"{}\",\
+             \"line\":{},\"column\":{},\"span\":{{\"line\":{},\"column\":{},\"end_column\":{}}},\
+             \"labels\":[{}]}}",
+            self.severity.name(),
+            code,
+            json_escape(&self.message),
+            json_escape(self.primary.path),
+            self.primary.pos.line,
+            self.primary.pos.column,
+            underline.line,
+            underline.column,
+            underline.column + underline.width,
+            labels
+        )
+    }
+}
+
+// location in a source file
+pub struct Snippet<'a> {
+    pub path: &'a str,
+    pub content: &'a str,
+    pub pos: Position,
+    pub span: Option<Span>,
+}
+
+impl<'a> Snippet<'a> {
+    pub fn new(path: &'a str, content: &'a str, pos: Position, span: Option<Span>) -> Snippet<'a> {
+        Snippet {
+            path,
+            content,
+            pos,
+            span,
+        }
+    }
+
+    // the first line of the span, without a span the token starting at
+    // the position is underlined
+    fn underline(&self) -> Underline {
+        if let Some(span) = self.span {
+            let start = span.start() as usize;
+
+            if start <= self.content.len() && self.content.is_char_boundary(start) {
+                let line_start = self.content[..start].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = self.content[start..]
+                    .find('\n')
+                    .map_or(self.content.len(), |i| start + i);
+                let end = (span.end() as usize).min(line_end).max(start);
+                let end = if self.content.is_char_boundary(end) {
+                    end
+                } else {
+                    line_end
+                };
+
+                let line = self.content[..start].matches('\n').count() as u32 + 1;
+                let column = expand_tabs(&self.content[line_start..start])
+                    .chars()
+                    .count() as u32
+                    + 1;
+                let width = self.content[start..end].chars().count().max(1) as u32;
+
+                return Underline {
+                    line,
+                    column,
+                    width,
+                    text: expand_tabs(&self.content[line_start..line_end]),
+                };
+            }
+        }
+
+        let line = self.pos.line;
+        let text = self
+            .content
+            .split('\n')
+            .nth(line as usize - 1)
+            .map(expand_tabs)
+            .unwrap_or_default();
+
+        let column = self.pos.column;
+        let mut token = text.chars().skip(column as usize - 1);
+
+        let width = match token.next() {
+            Some(ch) if is_identifier(ch) => 1 + token.take_while(|&ch| is_identifier(ch)).count(),
+            _ => 1,
+        } as u32;
+
+        Underline {
+            line,
+            column,
+            width,
+            text,
+        }
+    }
+}
+
+struct Underline {
+    line: u32,
+    column: u32,
+    width: u32,
+    text: String,
+}
+
+impl Underline {
+    fn write(&self, out: &mut String, width: usize, mark: char, message: &str) {
+        writeln!(
+            out,
+            "{:>width$} | {}",
+            self.line,
+            self.text.trim_end(),
+            width = width
+        )
+        .unwrap();
+
+        let marks: String = std::iter::repeat(mark).take(self.width as usize).collect();
+        let line = format!(
+            "{} | {}{} {}",
+            " ".repeat(width),
+            " ".repeat(self.column as usize - 1),
+            marks,
+            message
+        );
+
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+}
+
+fn is_identifier(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+
+    for ch in line.trim_end_matches('\r').chars() {
+        if ch == '\t' {
+            let column = result.chars().count() as u32;
+            let next = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+            result.extend(std::iter::repeat(' ').take((next - column) as usize));
+        } else {
+            result.push(ch);
         }
     }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(content: &str, pos: Position, span: Option<Span>) -> String {
+        let report = Report {
+            severity: Severity::Error,
+            code: Some("E0004"),
+            message: "unknown identifier `foo`.".into(),
+            primary: Snippet::new("f.dora", content, pos, span),
+            labels: Vec::new(),
+        };
+
+        report.human()
+    }
+
+    #[test]
+    fn underline_token() {
+        let out = render("fun main() {\n  foo + 1;\n}\n", Position::new(2, 3), None);
+        assert_eq!(
+            "error[E0004]: unknown identifier `foo`.\n --> f.dora:2:3\n  |\n2 |   foo + 1;\n  |   ^^^\n",
+            out
+        );
+    }
+
+    #[test]
+    fn underline_span() {
+        let content = "fun main() {\n\tfoo + 1;\n}\n";
+        let out = render(content, Position::new(2, 9), Some(Span::new(14, 7)));
+        assert!(out.ends_with("2 |     foo + 1;\n  |     ^^^^^^^\n"));
+    }
+
+    #[test]
+    fn labels() {
+        let content = "fun foo() {}\nfun foo() {}\n";
+        let report = Report {
+            severity: Severity::Warning,
+            code: None,
+            message: "duplicate".into(),
+            primary: Snippet::new("f.dora", content, Position::new(2, 5), None),
+            labels: vec![(
+                Snippet::new("f.dora", content, Position::new(1, 5), None),
+                "first defined here",
+            )],
+        };
+
+        assert_eq!(
+            "warning: duplicate\n --> f.dora:2:5\n  |\n2 | fun foo() {}\n  |     ^^^\n  |\n1 | fun foo() {}\n  |     --- first defined here\n",
+            report.human()
+        );
+
+        assert_eq!(
+            "{\"severity\":\"warning\",\"code\":null,\"message\":\"duplicate\",\"file\":\"f.dora\",\
+             \"line\":2,\"column\":5,\"span\":{\"line\":2,\"column\":5,\"end_column\":8},\
+             \"labels\":[{\"file\":\"f.dora\",\"line\":1,\"column\":5,\"message\":\"first defined here\"}]}",
+            report.json()
+        );
+    }
 }
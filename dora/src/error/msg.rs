@@ -1,5 +1,5 @@
 use crate::vm::{FileId, VM};
-use dora_parser::lexer::position::Position;
+use dora_parser::lexer::position::{Position, Span};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum SemError {
@@ -140,6 +140,39 @@ pub enum SemError {
     NameExpected,
     IndexExpected,
     IllegalTupleIndex(u64, String),
+    MatchBranchTypesIncompatible(String, String),
+    PatternTypeMismatch(String),
+    PatternWrongNumberOfParams(usize, usize),
+    MatchNotExhaustive(String),
+    TraitObjectMethodUsesSelf(String, String),
+    ThrowNotException(String),
+    CatchTypeNotException(String),
+    ThrowingCallWithoutTry,
+    TryNeedsCall,
+    TryCallNonThrowing,
+    UncheckedThrow,
+    ReturnInDefer,
+    ThrowsDifference(String),
+    UnknownPackage(String),
+    UnknownPackageMember(String, String),
+    NotPublic(String, String),
+    ShadowImport(String),
+    WhereBoundTypeParamExpected(String),
+    WhereClauseOnVirtualMethod,
+    CannotInferTypeParam(String, String),
+    StructArgsIncompatible(String, Vec<String>, Vec<String>),
+
+    // warnings
+    UnusedVariable(String),
+    UnusedParam(String),
+    VarNeverRead(String),
+    DeadStore(String),
+    VarNeverReassigned(String),
+    ShadowedVariable(String),
+    UnreachableCode,
+    UnusedFunction(String),
+    UnusedClass(String),
+    UnknownLint(String),
 }
 
 impl SemError {
@@ -486,6 +519,268 @@ impl SemError {
             SemError::IllegalTupleIndex(idx, ref ty) => {
                 format!("illegal index `{}` for type `{}`", idx, ty)
             }
+            SemError::MatchBranchTypesIncompatible(ref expected, ref value) => format!(
+                "match arms have incompatible types `{}` and `{}`.",
+                expected, value
+            ),
+            SemError::PatternTypeMismatch(ref ty) => {
+                format!("pattern cannot match value of type `{}`.", ty)
+            }
+            SemError::PatternWrongNumberOfParams(expected, given) => format!(
+                "pattern expects {} sub-pattern(s) but got {}.",
+                expected, given
+            ),
+            SemError::MatchNotExhaustive(ref pattern) => format!(
+                "match is not exhaustive: pattern `{}` not covered.",
+                pattern
+            ),
+            SemError::TraitObjectMethodUsesSelf(ref xtrait, ref name) => format!(
+                "method `{}` of trait `{}` uses `Self` and cannot be called on a trait object.",
+                name, xtrait
+            ),
+            SemError::ThrowNotException(ref ty) => format!(
+                "only subclasses of `Exception` can be thrown but got `{}`.",
+                ty
+            ),
+            SemError::CatchTypeNotException(ref ty) => format!(
+                "only subclasses of `Exception` can be caught but got `{}`.",
+                ty
+            ),
+            SemError::ThrowingCallWithoutTry => {
+                "call to throwing function needs to be marked with `try`.".into()
+            }
+            SemError::TryNeedsCall => "`try` expects a function or method call.".into(),
+            SemError::TryCallNonThrowing => "`try` used on call that does not throw.".into(),
+            SemError::UncheckedThrow => {
+                "exception needs to be caught or function needs to be marked with `throws`.".into()
+            }
+            SemError::ReturnInDefer => "`return` not allowed inside `defer`.".into(),
+            SemError::ThrowsDifference(ref name) => format!(
+                "use of `throws` in `{}` does not match trait definition.",
+                name
+            ),
+            SemError::UnknownPackage(ref name) => format!("package `{}` does not exist.", name),
+            SemError::UnknownPackageMember(ref package, ref name) => {
+                format!("package `{}` has no member `{}`.", package, name)
+            }
+            SemError::NotPublic(ref package, ref name) => {
+                format!("`{}` in package `{}` is not public.", name, package)
+            }
+            SemError::ShadowImport(ref name) => format!("can not shadow import `{}`.", name),
+            SemError::WhereBoundTypeParamExpected(ref name) => format!(
+                "where clause can only constrain type params but got `{}`.",
+                name
+            ),
+            SemError::WhereClauseOnVirtualMethod => {
+                "where clause not allowed on open, abstract or overriding method.".into()
+            }
+            SemError::CannotInferTypeParam(ref name, ref fct) => format!(
+                "cannot infer type param `{}` of `{}`, type params need to be given explicitly.",
+                name, fct
+            ),
+            SemError::StructArgsIncompatible(ref name, ref def, ref expr) => {
+                let def = def.join(", ");
+                let expr = expr.join(", ");
+
+                format!(
+                    "struct `{}({})` cannot be called as `{}({})`",
+                    name, def, name, expr
+                )
+            }
+            SemError::UnusedVariable(ref name) => format!("unused variable `{}`.", name),
+            SemError::UnusedParam(ref name) => format!("unused parameter `{}`.", name),
+            SemError::VarNeverRead(ref name) => {
+                format!("variable `{}` is assigned but never read.", name)
+            }
+            SemError::DeadStore(ref name) => format!(
+                "value assigned to `{}` is overwritten before it is read.",
+                name
+            ),
+            SemError::VarNeverReassigned(ref name) => format!(
+                "variable `{}` is never reassigned, use `let` instead.",
+                name
+            ),
+            SemError::ShadowedVariable(ref name) => {
+                format!("variable `{}` shadows an earlier variable.", name)
+            }
+            SemError::UnreachableCode => "unreachable code.".into(),
+            SemError::UnusedFunction(ref name) => format!("function `{}` is never used.", name),
+            SemError::UnusedClass(ref name) => format!("class `{}` is never used.", name),
+            SemError::UnknownLint(ref name) => format!("unknown lint `{}`.", name),
+        }
+    }
+
+    // stable identifier of the error, never reuse or renumber codes
+    pub fn code(&self) -> &'static str {
+        match *self {
+            SemError::Unimplemented => "E0001",
+            SemError::UnknownClass(..) => "E0002",
+            SemError::UnknownType(..) => "E0003",
+            SemError::UnknownIdentifier(..) => "E0004",
+            SemError::UnknownStruct(..) => "E0005",
+            SemError::UnknownFunction(..) => "E0006",
+            SemError::UnknownField(..) => "E0007",
+            SemError::UnknownMethod(..) => "E0008",
+            SemError::UnknownEnumValue(..) => "E0009",
+            SemError::MultipleCandidatesForMethod(..) => "E0010",
+            SemError::VariadicParameterNeedsToBeLast => "E0011",
+            SemError::UnknownMethodForTypeParam(..) => "E0012",
+            SemError::MultipleCandidatesForTypeParam(..) => "E0013",
+            SemError::MultipleCandidatesForStaticMethodWithTypeParam => "E0014",
+            SemError::UnknownStaticMethodWithTypeParam => "E0015",
+            SemError::UnknownStaticMethod(..) => "E0016",
+            SemError::UnknownCtor(..) => "E0017",
+            SemError::MethodExists(..) => "E0018",
+            SemError::IncompatibleWithNil(..) => "E0019",
+            SemError::IdentifierExists(..) => "E0020",
+            SemError::ShadowFunction(..) => "E0021",
+            SemError::ShadowParam(..) => "E0022",
+            SemError::ShadowClass(..) => "E0023",
+            SemError::ShadowClassConstructor(..) => "E0024",
+            SemError::ShadowStruct(..) => "E0025",
+            SemError::ShadowStructConstructor(..) => "E0026",
+            SemError::ShadowTrait(..) => "E0027",
+            SemError::ShadowField(..) => "E0028",
+            SemError::ShadowGlobal(..) => "E0029",
+            SemError::ShadowConst(..) => "E0030",
+            SemError::ShadowModule(..) => "E0031",
+            SemError::ShadowEnum(..) => "E0032",
+            SemError::ShadowEnumValue(..) => "E0033",
+            SemError::InvalidLhsAssignment => "E0034",
+            SemError::NoEnumValue => "E0035",
+            SemError::EnumArgsIncompatible(..) => "E0036",
+            SemError::EnumArgsNoParens(..) => "E0037",
+            SemError::VarNeedsTypeInfo(..) => "E0038",
+            SemError::ParamTypesIncompatible(..) => "E0039",
+            SemError::WhileCondType(..) => "E0040",
+            SemError::IfCondType(..) => "E0041",
+            SemError::ReturnType(..) => "E0042",
+            SemError::LvalueExpected => "E0043",
+            SemError::AssignType(..) => "E0044",
+            SemError::AssignField(..) => "E0045",
+            SemError::UnOpType(..) => "E0046",
+            SemError::BinOpType(..) => "E0047",
+            SemError::ConstValueExpected => "E0048",
+            SemError::OutsideLoop => "E0049",
+            SemError::NoReturnValue => "E0050",
+            SemError::MainNotFound => "E0051",
+            SemError::WrongMainDefinition => "E0052",
+            SemError::ThisUnavailable => "E0053",
+            SemError::SelfTypeUnavailable => "E0054",
+            SemError::SuperUnavailable => "E0055",
+            SemError::SuperNeedsMethodCall => "E0056",
+            SemError::ReferenceTypeExpected(..) => "E0057",
+            SemError::LetMissingInitialization => "E0058",
+            SemError::LetReassigned => "E0059",
+            SemError::FctReassigned => "E0060",
+            SemError::ClassReassigned => "E0061",
+            SemError::TypeParamReassigned => "E0062",
+            SemError::FctUsedAsIdentifier => "E0063",
+            SemError::ClsUsedAsIdentifier => "E0064",
+            SemError::ModuleUsedAsIdentifier => "E0065",
+            SemError::TypeParamUsedAsIdentifier => "E0066",
+            SemError::EnumUsedAsIdentifier => "E0067",
+            SemError::TypeParamUsedAsCallee => "E0068",
+            SemError::UnderivableType(..) => "E0069",
+            SemError::CycleInHierarchy => "E0070",
+            SemError::SuperfluousOverride(..) => "E0071",
+            SemError::SuperfluousOpen(..) => "E0072",
+            SemError::MissingOverride(..) => "E0073",
+            SemError::MethodNotOverridable(..) => "E0074",
+            SemError::TypesIncompatible(..) => "E0075",
+            SemError::ReturnTypeMismatch(..) => "E0076",
+            SemError::OverrideMismatch => "E0077",
+            SemError::UnresolvedInternal => "E0078",
+            SemError::UnclosedComment => "E0079",
+            SemError::UnknownChar(..) => "E0080",
+            SemError::UnclosedChar => "E0081",
+            SemError::UnclosedString => "E0082",
+            SemError::NumberOverflow(..) => "E0083",
+            SemError::ExpectedClass(..) => "E0084",
+            SemError::ExpectedFactor(..) => "E0085",
+            SemError::ExpectedToken(..) => "E0086",
+            SemError::ExpectedTopLevelElement(..) => "E0087",
+            SemError::ExpectedTrait(..) => "E0088",
+            SemError::ExpectedType(..) => "E0089",
+            SemError::ExpectedIdentifier(..) => "E0090",
+            SemError::ExpectedStringable(..) => "E0091",
+            SemError::ExpectedSomeIdentifier => "E0092",
+            SemError::MisplacedElse => "E0093",
+            SemError::IoError => "E0094",
+            SemError::ExpectedClassElement(..) => "E0095",
+            SemError::MisplacedAnnotation(..) => "E0096",
+            SemError::RedundantAnnotation(..) => "E0097",
+            SemError::UnknownAnnotation(..) => "E0098",
+            SemError::InvalidEscapeSequence(..) => "E0099",
+            SemError::MissingFctBody => "E0100",
+            SemError::FctCallExpected => "E0101",
+            SemError::ThisOrSuperExpected(..) => "E0102",
+            SemError::NoSuperDelegationWithPrimaryCtor(..) => "E0103",
+            SemError::NoSuperClass(..) => "E0104",
+            SemError::RecursiveStructure => "E0105",
+            SemError::TraitMethodWithBody => "E0106",
+            SemError::TypeParamsExpected => "E0107",
+            SemError::TypeParamNameNotUnique(..) => "E0108",
+            SemError::StaticMethodNotInTrait(..) => "E0109",
+            SemError::MethodNotInTrait(..) => "E0110",
+            SemError::StaticMethodMissingFromTrait(..) => "E0111",
+            SemError::MethodMissingFromTrait(..) => "E0112",
+            SemError::WrongNumberTypeParams(..) => "E0113",
+            SemError::ClassExpected => "E0114",
+            SemError::ClassExpectedAsTypeParam => "E0115",
+            SemError::AssignmentToConst => "E0116",
+            SemError::BoundExpected => "E0117",
+            SemError::NoTypeParamsExpected => "E0118",
+            SemError::DuplicateTraitBound => "E0119",
+            SemError::TraitBoundNotSatisfied(..) => "E0120",
+            SemError::AbstractMethodNotInAbstractClass => "E0121",
+            SemError::AbstractMethodWithImplementation => "E0122",
+            SemError::NewAbstractClass => "E0123",
+            SemError::MissingAbstractOverride(..) => "E0124",
+            SemError::ModifierNotAllowedForStaticMethod(..) => "E0125",
+            SemError::GlobalInitializerNotSupported => "E0126",
+            SemError::MakeIteratorNotFound(..) => "E0127",
+            SemError::MakeIteratorReturnType(..) => "E0128",
+            SemError::UnknownStructField(..) => "E0129",
+            SemError::StructFieldNotInitialized(..) => "E0130",
+            SemError::InvalidLeftSideOfSeparator => "E0131",
+            SemError::InvalidUseOfTypeParams => "E0132",
+            SemError::NameOfStaticMethodExpected => "E0133",
+            SemError::IfBranchTypesIncompatible(..) => "E0134",
+            SemError::NameExpected => "E0135",
+            SemError::IndexExpected => "E0136",
+            SemError::IllegalTupleIndex(..) => "E0137",
+            SemError::MatchBranchTypesIncompatible(..) => "E0138",
+            SemError::PatternTypeMismatch(..) => "E0139",
+            SemError::PatternWrongNumberOfParams(..) => "E0140",
+            SemError::MatchNotExhaustive(..) => "E0141",
+            SemError::TraitObjectMethodUsesSelf(..) => "E0142",
+            SemError::ThrowNotException(..) => "E0143",
+            SemError::CatchTypeNotException(..) => "E0144",
+            SemError::ThrowingCallWithoutTry => "E0145",
+            SemError::TryNeedsCall => "E0146",
+            SemError::TryCallNonThrowing => "E0147",
+            SemError::UncheckedThrow => "E0148",
+            SemError::ReturnInDefer => "E0149",
+            SemError::ThrowsDifference(..) => "E0150",
+            SemError::UnknownPackage(..) => "E0151",
+            SemError::UnknownPackageMember(..) => "E0152",
+            SemError::NotPublic(..) => "E0153",
+            SemError::ShadowImport(..) => "E0154",
+            SemError::WhereBoundTypeParamExpected(..) => "E0155",
+            SemError::WhereClauseOnVirtualMethod => "E0156",
+            SemError::CannotInferTypeParam(..) => "E0157",
+            SemError::StructArgsIncompatible(..) => "E0158",
+            SemError::UnusedVariable(..) => "W0001",
+            SemError::UnusedParam(..) => "W0002",
+            SemError::VarNeverRead(..) => "W0003",
+            SemError::DeadStore(..) => "W0004",
+            SemError::VarNeverReassigned(..) => "W0005",
+            SemError::ShadowedVariable(..) => "W0006",
+            SemError::UnreachableCode => "W0007",
+            SemError::UnusedFunction(..) => "W0008",
+            SemError::UnusedClass(..) => "W0009",
+            SemError::UnknownLint(..) => "W0010",
         }
     }
 }
@@ -495,11 +790,34 @@ pub struct SemErrorAndPos {
     pub file: FileId,
     pub pos: Position,
     pub msg: SemError,
+
+    // source range underlined in the snippet, the token at `pos` otherwise
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
 }
 
 impl SemErrorAndPos {
     pub fn new(file: FileId, pos: Position, msg: SemError) -> SemErrorAndPos {
-        SemErrorAndPos { file, pos, msg }
+        SemErrorAndPos {
+            file,
+            pos,
+            msg,
+            span: None,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> SemErrorAndPos {
+        if span.is_valid() {
+            self.span = Some(span);
+        }
+
+        self
+    }
+
+    pub fn with_label(mut self, file: FileId, pos: Position, message: String) -> SemErrorAndPos {
+        self.labels.push(Label { file, pos, message });
+        self
     }
 
     pub fn message(&self, vm: &VM) -> String {
@@ -512,3 +830,12 @@ impl SemErrorAndPos {
         )
     }
 }
+
+// secondary location of a diagnostic, e.g. the previous definition of a
+// shadowed name
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub file: FileId,
+    pub pos: Position,
+    pub message: String,
+}
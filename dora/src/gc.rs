@@ -1,14 +1,18 @@
+use parking_lot::Mutex;
 use std::cmp::{Ord, Ordering, PartialOrd};
 use std::fmt;
 
 use crate::driver::cmd::{Args, CollectorName};
+use crate::gc::allocprof::AllocProfile;
 use crate::gc::compact::MarkCompactCollector;
 use crate::gc::copy::CopyCollector;
+use crate::gc::root::Slot;
 use crate::gc::space::{Space, SpaceConfig};
 use crate::gc::sweep::SweepCollector;
 use crate::gc::swiper::sweep::SweepSwiper;
-use crate::gc::swiper::{Swiper, CARD_SIZE};
+use crate::gc::swiper::{Swiper, CARD_SIZE, CARD_SIZE_BITS};
 use crate::gc::tlab::TLAB_OBJECT_SIZE;
+use crate::gc::weak::WeakRefs;
 use crate::gc::zero::ZeroCollector;
 use crate::mem;
 use crate::object::{Header, Obj};
@@ -16,10 +20,12 @@ use crate::os;
 use crate::vm::VM;
 use crate::vtable::VTable;
 
+pub mod allocprof;
 pub mod bump;
 pub mod compact;
 pub mod copy;
 pub mod freelist;
+pub mod heapdump;
 pub mod marking;
 pub mod pmarking;
 pub mod root;
@@ -27,6 +33,7 @@ pub mod space;
 pub mod sweep;
 pub mod swiper;
 pub mod tlab;
+pub mod weak;
 pub mod zero;
 
 pub const K: usize = 1024;
@@ -46,6 +53,12 @@ pub struct Gc {
 
     code_space: Space,
     perm_space: Space,
+
+    // handler registered with `onOutOfMemory`, run at most once
+    oom_handler: Mutex<Address>,
+
+    // sampled allocation sites for --alloc-profile
+    alloc_profile: Option<AllocProfile>,
 }
 
 impl Gc {
@@ -77,12 +90,27 @@ impl Gc {
 
         let supports_tlab = !args.flag_disable_tlab && collector.supports_tlab();
 
+        let alloc_profile =
+            args.flag_alloc_profile.as_ref().map(|path| {
+                match AllocProfile::create(path, args.alloc_sample_interval()) {
+                    Ok(alloc_profile) => alloc_profile,
+                    Err(err) => {
+                        eprintln!("opening allocation profile {} failed: {}", path, err);
+                        std::process::exit(1);
+                    }
+                }
+            });
+
         Gc {
             collector,
             supports_tlab,
 
             code_space: Space::new(code_config, "code"),
             perm_space: Space::new(perm_config, "perm"),
+
+            oom_handler: Mutex::new(Address::null()),
+
+            alloc_profile,
         }
     }
 
@@ -94,6 +122,70 @@ impl Gc {
         self.collector.card_table_offset()
     }
 
+    pub fn needs_pre_write_barrier(&self) -> bool {
+        self.collector.needs_pre_write_barrier()
+    }
+
+    pub fn pre_write_barrier(&self, object: Address) {
+        if object.is_null() {
+            return;
+        }
+
+        self.collector.pre_write_barrier(object);
+    }
+
+    pub fn concurrent_marking_active(&self) -> bool {
+        self.collector.concurrent_marking_active()
+    }
+
+    // stores a reference into a field of a heap object from native code,
+    // runs the barriers the compiler emits for field stores
+    pub fn write_field(&self, object: Address, field: Address, value: Address) {
+        let slot = Slot::at(field);
+
+        if self.needs_pre_write_barrier() {
+            self.pre_write_barrier(slot.get());
+        }
+
+        slot.set(value);
+
+        if self.needs_write_barrier() {
+            let card = self.card_table_offset() + (object.to_usize() >> CARD_SIZE_BITS);
+            unsafe {
+                *(card as *mut u8) = 0;
+            }
+        }
+    }
+
+    pub fn weak_refs(&self) -> Option<&WeakRefs> {
+        self.collector.weak_refs()
+    }
+
+    pub fn set_oom_handler(&self, handler: Address) {
+        *self.oom_handler.lock() = handler;
+    }
+
+    // removes the handler, returns null if there is none
+    pub fn take_oom_handler(&self) -> Address {
+        std::mem::replace(&mut *self.oom_handler.lock(), Address::null())
+    }
+
+    // the handler is a root until it runs
+    pub fn visit_oom_handler<F>(&self, mut f: F)
+    where
+        F: FnMut(Slot),
+    {
+        let mut handler = self.oom_handler.lock();
+
+        if handler.is_non_null() {
+            f(Slot::at(Address::from_ptr(&mut *handler as *mut Address)));
+        }
+    }
+
+    pub fn in_code_space(&self, addr: Address) -> bool {
+        self.code_space.contains(addr)
+    }
+
     pub fn alloc_code(&self, size: usize) -> Address {
         self.code_space.alloc(size)
     }
@@ -114,7 +206,9 @@ impl Gc {
         if size < TLAB_OBJECT_SIZE && self.supports_tlab {
             self.alloc_tlab(vm, size, array_ref)
         } else {
-            self.collector.alloc(vm, size, array_ref)
+            let object = self.collector.alloc(vm, size, array_ref);
+            self.sample_allocation(vm, object, size);
+            object
         }
     }
 
@@ -124,6 +218,16 @@ impl Gc {
             return addr;
         }
 
+        if let Some(ref alloc_profile) = self.alloc_profile {
+            // the tlab might only end at the next sample point
+            alloc_profile.refund(tlab::restore_end());
+
+            if let Some(addr) = tlab::allocate(size) {
+                self.sample_tlab_allocation(vm, alloc_profile, addr, size);
+                return addr;
+            }
+        }
+
         // if there is not enough space, make heap iterable by filling tlab with unused objects
         tlab::make_iterable_current(vm);
 
@@ -135,6 +239,10 @@ impl Gc {
             // initialize TLAB to new boundaries
             tlab::initialize(tlab);
 
+            if let Some(ref alloc_profile) = self.alloc_profile {
+                self.sample_tlab_allocation(vm, alloc_profile, object_start, size);
+            }
+
             // object is allocated before TLAB
             object_start
         } else {
@@ -143,6 +251,51 @@ impl Gc {
         }
     }
 
+    fn sample_tlab_allocation(
+        &self,
+        vm: &VM,
+        alloc_profile: &AllocProfile,
+        object: Address,
+        size: usize,
+    ) {
+        alloc_profile.record(vm, object, size);
+
+        // allocations up to the next sample point stay in the inline path
+        let window = tlab::lower_end(alloc_profile.next_sample());
+        alloc_profile.skip(window);
+    }
+
+    fn sample_allocation(&self, vm: &VM, object: Address, size: usize) {
+        if let Some(ref alloc_profile) = self.alloc_profile {
+            if object.is_non_null() {
+                alloc_profile.record(vm, object, size);
+            }
+        }
+    }
+
+    // the TLAB of the given thread is discarded, the bytes left before its
+    // lowered end were charged but never allocated
+    pub fn discard_tlab_window(&self, thread_id: usize, size: usize) {
+        if let Some(ref alloc_profile) = self.alloc_profile {
+            alloc_profile.refund_thread(thread_id, size);
+        }
+    }
+
+    // called when all threads are stopped, before objects can be moved
+    pub fn resolve_alloc_samples(&self) {
+        if let Some(ref alloc_profile) = self.alloc_profile {
+            alloc_profile.resolve_pending();
+        }
+    }
+
+    pub fn write_alloc_profile(&self, vm: &VM) {
+        if let Some(ref alloc_profile) = self.alloc_profile {
+            if let Err(err) = alloc_profile.write(vm) {
+                eprintln!("writing allocation profile failed: {}", err);
+            }
+        }
+    }
+
     pub fn collect(&self, vm: &VM, reason: GcReason) {
         self.collector.collect(vm, reason);
     }
@@ -155,6 +308,10 @@ impl Gc {
         self.collector.dump_summary(runtime);
     }
 
+    pub fn dump_heap_usage(&self) {
+        self.collector.dump_heap_usage();
+    }
+
     pub fn verify_ref(&self, vm: &VM, reference: Address) {
         if reference.is_null() {
             return;
@@ -182,6 +339,24 @@ trait Collector {
         false
     }
 
+    // decides whether to emit barriers that record the previous value
+    // of a field for concurrent marking
+    fn needs_pre_write_barrier(&self) -> bool {
+        false
+    }
+
+    // records previous value of a field that is overwritten while
+    // concurrent marking is active
+    fn pre_write_barrier(&self, _object: Address) {
+        // do nothing
+    }
+
+    // gives true while concurrent marking runs, new threads need
+    // to enable their pre-write barrier then
+    fn concurrent_marking_active(&self) -> bool {
+        false
+    }
+
     // gives true when collector supports tlab allocation.
     fn supports_tlab(&self) -> bool;
 
@@ -190,9 +365,20 @@ trait Collector {
         0
     }
 
+    // collectors without support for weak references treat
+    // WeakRef and Ephemeron fields as strong references
+    fn weak_refs(&self) -> Option<&WeakRefs> {
+        None
+    }
+
     // prints GC summary: minor/full collections, etc.
     fn dump_summary(&self, _runtime: f32);
 
+    // prints memory usage of the heap when running out of memory
+    fn dump_heap_usage(&self) {
+        // do nothing
+    }
+
     // verify reference
     fn verify_ref(&self, _vm: &VM, _addr: Address) {
         // do nothing
@@ -472,6 +658,7 @@ pub enum GcReason {
     ForceMinorCollect,
     Stress,
     StressMinor,
+    MarkingFinished,
 }
 
 impl GcReason {
@@ -483,6 +670,7 @@ impl GcReason {
             GcReason::ForceMinorCollect => "force minor collect",
             GcReason::Stress => "stress",
             GcReason::StressMinor => "stress minor",
+            GcReason::MarkingFinished => "marking finished",
         }
     }
 }
@@ -0,0 +1,175 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::gc::{Address, K};
+use crate::stack::{self, NativeStacktrace};
+use crate::threads::THREAD;
+use crate::vm::VM;
+use crate::vtable::VTable;
+
+pub const DEFAULT_ALLOC_SAMPLE_INTERVAL: usize = 512 * K;
+
+// Sampling allocation profiler enabled with --alloc-profile. A sample is taken
+// every `interval` allocated bytes of a thread, the allocation containing that
+// byte is recorded with its stack and weighted with the interval. The end of
+// the TLAB is lowered to the next sample point, so that the allocation crossing
+// it leaves the inline allocation path.
+pub struct AllocProfile {
+    interval: usize,
+    state: Mutex<ProfileState>,
+}
+
+struct ProfileState {
+    file: Option<File>,
+
+    // bytes left until the next sample by thread id
+    remaining: HashMap<usize, usize>,
+
+    // the header of a sampled object is only written after the allocation
+    // returns, the class is determined at the next safepoint
+    pending: Vec<PendingSample>,
+
+    // sampled bytes by stack and vtable of the allocated class
+    samples: HashMap<(NativeStacktrace, Address), usize>,
+}
+
+impl ProfileState {
+    fn remaining(&mut self, interval: usize) -> &mut usize {
+        let thread_id = THREAD.with(|thread| thread.borrow().id());
+        self.remaining_of(thread_id, interval)
+    }
+
+    fn remaining_of(&mut self, thread_id: usize, interval: usize) -> &mut usize {
+        self.remaining.entry(thread_id).or_insert(interval)
+    }
+}
+
+struct PendingSample {
+    object: Address,
+    stack: NativeStacktrace,
+    bytes: usize,
+}
+
+impl AllocProfile {
+    pub fn create(path: &str, interval: usize) -> io::Result<AllocProfile> {
+        let file = File::create(path)?;
+
+        Ok(AllocProfile {
+            interval: interval.max(1),
+            state: Mutex::new(ProfileState {
+                file: Some(file),
+                remaining: HashMap::new(),
+                pending: Vec::new(),
+                samples: HashMap::new(),
+            }),
+        })
+    }
+
+    // bytes the current thread can allocate before the next sample
+    pub fn next_sample(&self) -> usize {
+        let mut state = self.state.lock();
+        *state.remaining(self.interval)
+    }
+
+    // charges bytes that are allocated without being seen, they may not
+    // reach the next sample point
+    pub fn skip(&self, size: usize) {
+        let mut state = self.state.lock();
+        let remaining = state.remaining(self.interval);
+        assert!(size <= *remaining);
+        *remaining -= size;
+    }
+
+    // returns skipped bytes that weren't allocated
+    pub fn refund(&self, size: usize) {
+        let mut state = self.state.lock();
+        *state.remaining(self.interval) += size;
+    }
+
+    // same as `refund` for the TLAB of another thread
+    pub fn refund_thread(&self, thread_id: usize, size: usize) {
+        let mut state = self.state.lock();
+        *state.remaining_of(thread_id, self.interval) += size;
+    }
+
+    // charges the allocation of `size` bytes at `object` and samples it
+    // when it crosses the next sample point
+    pub fn record(&self, vm: &VM, object: Address, size: usize) {
+        let bytes = {
+            let mut state = self.state.lock();
+            let interval = self.interval;
+            let remaining = state.remaining(interval);
+
+            if size <= *remaining {
+                *remaining -= size;
+                return;
+            }
+
+            // large objects can cross several sample points
+            let samples = (size - *remaining - 1) / interval + 1;
+            *remaining = *remaining + samples * interval - size;
+            samples * interval
+        };
+
+        let stack = stack::stacktrace_from_last_dtn(vm);
+
+        let mut state = self.state.lock();
+        state.pending.push(PendingSample {
+            object,
+            stack,
+            bytes,
+        });
+    }
+
+    // needs to run while all mutators are stopped and before the
+    // collector moves objects
+    pub fn resolve_pending(&self) {
+        let mut state = self.state.lock();
+        let pending = std::mem::replace(&mut state.pending, Vec::new());
+
+        for sample in pending {
+            let vtblptr = sample.object.to_obj().header().vtblptr();
+            *state.samples.entry((sample.stack, vtblptr)).or_insert(0) += sample.bytes;
+        }
+    }
+
+    // writes one line `frame;...;frame;Class bytes` per allocation site,
+    // the outermost frame comes first
+    pub fn write(&self, vm: &VM) -> io::Result<()> {
+        self.resolve_pending();
+
+        let mut state = self.state.lock();
+        let mut lines: HashMap<String, usize> = HashMap::new();
+
+        for ((stack, vtblptr), &bytes) in &state.samples {
+            let vtable = unsafe { &*vtblptr.to_ptr::<VTable>() };
+            let class = vtable.class().name(vm);
+
+            let line = if stack.len() > 0 {
+                format!("{};{}", stack.folded(vm), class)
+            } else {
+                class
+            };
+
+            *lines.entry(line).or_insert(0) += bytes;
+        }
+
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort();
+
+        let file = match state.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        let mut file = BufWriter::new(file);
+
+        for (line, bytes) in lines {
+            writeln!(file, "{} {}", line, bytes)?;
+        }
+
+        file.flush()
+    }
+}
@@ -0,0 +1,359 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::sync::Arc;
+
+use crate::gc::root::get_rootset;
+use crate::gc::Address;
+use crate::safepoint;
+use crate::threads::{DoraThread, THREAD};
+use crate::vm::VM;
+use crate::vtable::VTable;
+
+// Heap dumps contain all objects reachable from the root set. All integers
+// are stored in little endian:
+//
+//   magic "DRHD", version: u32
+//   classes: u32 count, for each class its name (u32 length + UTF-8 bytes)
+//   objects: u32 count, for each object:
+//     address: u64, class: u32 (index into classes), size: u64 (in bytes),
+//     references: u32 count + u32 object index for each outgoing reference
+//   roots: u32 count + u32 object index for each object referenced by a root
+const MAGIC: &[u8; 4] = b"DRHD";
+pub const VERSION: u32 = 1;
+
+pub struct HeapDump {
+    pub classes: Vec<String>,
+    pub objects: Vec<HeapObject>,
+    pub roots: Vec<u32>,
+}
+
+pub struct HeapObject {
+    pub address: u64,
+    pub class: u32,
+    pub size: u64,
+    pub references: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum HeapDumpError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    InvalidData(&'static str),
+}
+
+impl fmt::Display for HeapDumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeapDumpError::Io(err) => write!(f, "{}", err),
+            HeapDumpError::InvalidMagic => write!(f, "not a heap dump"),
+            HeapDumpError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported heap dump version {} (expected {})",
+                version, VERSION
+            ),
+            HeapDumpError::InvalidData(what) => write!(f, "invalid {} in heap dump", what),
+        }
+    }
+}
+
+impl From<io::Error> for HeapDumpError {
+    fn from(err: io::Error) -> HeapDumpError {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            HeapDumpError::InvalidData("length")
+        } else {
+            HeapDumpError::Io(err)
+        }
+    }
+}
+
+pub fn dump_heap(vm: &VM, path: &str) -> io::Result<()> {
+    let dump = safepoint::stop_the_world(vm, |threads| snapshot(vm, threads));
+    fs::write(path, write(&dump))
+}
+
+// called when an allocation from mutator code fails, the stack of the current
+// thread is still walkable at this point
+pub fn dump_heap_on_oom(vm: &VM) {
+    let path = format!("dora-{}.heapdump", std::process::id());
+
+    match dump_heap(vm, &path) {
+        Ok(()) => eprintln!("heap dump written to {}", path),
+        Err(err) => eprintln!("writing heap dump {} failed: {}", path, err),
+    }
+}
+
+// the heap is only consistent when running mutator code, an OOM during a
+// collection happens while objects are being moved
+pub fn oom_during_collection() -> bool {
+    THREAD.with(|thread| !thread.borrow().state().is_running())
+}
+
+fn snapshot(vm: &VM, threads: &[Arc<DoraThread>]) -> HeapDump {
+    let rootset = get_rootset(vm, threads);
+
+    let mut snapshot = Snapshot {
+        vm,
+        dump: HeapDump {
+            classes: Vec::new(),
+            objects: Vec::new(),
+            roots: Vec::new(),
+        },
+        classes: HashMap::new(),
+        objects: HashMap::new(),
+        worklist: Vec::new(),
+    };
+
+    for root in rootset {
+        let obj = root.get();
+
+        if obj.is_non_null() {
+            let idx = snapshot.index_of(obj);
+
+            if !snapshot.dump.roots.contains(&idx) {
+                snapshot.dump.roots.push(idx);
+            }
+        }
+    }
+
+    while let Some((obj, idx)) = snapshot.worklist.pop() {
+        let mut references = Vec::new();
+
+        obj.to_mut_obj().visit_reference_fields(|slot| {
+            let field = slot.get();
+
+            if field.is_non_null() {
+                references.push(snapshot.index_of(field));
+            }
+        });
+
+        snapshot.dump.objects[idx as usize].references = references;
+    }
+
+    snapshot.dump
+}
+
+struct Snapshot<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    dump: HeapDump,
+    classes: HashMap<Address, u32>,
+    objects: HashMap<Address, u32>,
+    worklist: Vec<(Address, u32)>,
+}
+
+impl<'a, 'ast> Snapshot<'a, 'ast> {
+    fn index_of(&mut self, obj: Address) -> u32 {
+        if let Some(&idx) = self.objects.get(&obj) {
+            return idx;
+        }
+
+        let object = obj.to_obj();
+        let class = self.class_index(object.header().vtblptr());
+
+        self.dump.objects.push(HeapObject {
+            address: obj.to_usize() as u64,
+            class,
+            size: object.size() as u64,
+            references: Vec::new(),
+        });
+
+        let idx = (self.dump.objects.len() - 1) as u32;
+        self.objects.insert(obj, idx);
+        self.worklist.push((obj, idx));
+
+        idx
+    }
+
+    fn class_index(&mut self, vtblptr: Address) -> u32 {
+        if let Some(&idx) = self.classes.get(&vtblptr) {
+            return idx;
+        }
+
+        let vtable = unsafe { &*vtblptr.to_ptr::<VTable>() };
+        self.dump.classes.push(vtable.class().name(self.vm));
+
+        let idx = (self.dump.classes.len() - 1) as u32;
+        self.classes.insert(vtblptr, idx);
+
+        idx
+    }
+}
+
+pub fn write(dump: &HeapDump) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    buffer.extend_from_slice(MAGIC);
+    buffer.write_u32::<LittleEndian>(VERSION).unwrap();
+
+    write_u32(&mut buffer, dump.classes.len());
+
+    for class in &dump.classes {
+        write_u32(&mut buffer, class.len());
+        buffer.extend_from_slice(class.as_bytes());
+    }
+
+    write_u32(&mut buffer, dump.objects.len());
+
+    for object in &dump.objects {
+        buffer.write_u64::<LittleEndian>(object.address).unwrap();
+        buffer.write_u32::<LittleEndian>(object.class).unwrap();
+        buffer.write_u64::<LittleEndian>(object.size).unwrap();
+        write_indices(&mut buffer, &object.references);
+    }
+
+    write_indices(&mut buffer, &dump.roots);
+
+    buffer
+}
+
+fn write_indices(buffer: &mut Vec<u8>, indices: &[u32]) {
+    write_u32(buffer, indices.len());
+
+    for &idx in indices {
+        buffer.write_u32::<LittleEndian>(idx).unwrap();
+    }
+}
+
+fn write_u32(buffer: &mut Vec<u8>, value: usize) {
+    assert!(value <= u32::max_value() as usize);
+    buffer.write_u32::<LittleEndian>(value as u32).unwrap();
+}
+
+pub fn read(data: &[u8]) -> Result<HeapDump, HeapDumpError> {
+    let mut reader = Cursor::new(data);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+        return Err(HeapDumpError::InvalidMagic);
+    }
+
+    let version = reader.read_u32::<LittleEndian>()?;
+
+    if version != VERSION {
+        return Err(HeapDumpError::UnsupportedVersion(version));
+    }
+
+    let mut classes = Vec::new();
+
+    for _ in 0..reader.read_u32::<LittleEndian>()? {
+        let len = reader.read_u32::<LittleEndian>()? as usize;
+
+        if len > data.len() {
+            return Err(HeapDumpError::InvalidData("class name"));
+        }
+
+        let mut name = vec![0; len];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name).map_err(|_| HeapDumpError::InvalidData("class name"))?;
+        classes.push(name);
+    }
+
+    let mut objects = Vec::new();
+    let objects_len = reader.read_u32::<LittleEndian>()?;
+
+    for _ in 0..objects_len {
+        let address = reader.read_u64::<LittleEndian>()?;
+        let class = reader.read_u32::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()?;
+        let references = read_indices(&mut reader, objects_len)?;
+
+        if class as usize >= classes.len() {
+            return Err(HeapDumpError::InvalidData("class index"));
+        }
+
+        objects.push(HeapObject {
+            address,
+            class,
+            size,
+            references,
+        });
+    }
+
+    let roots = read_indices(&mut reader, objects_len)?;
+
+    if (reader.position() as usize) < data.len() {
+        return Err(HeapDumpError::InvalidData("trailing data"));
+    }
+
+    Ok(HeapDump {
+        classes,
+        objects,
+        roots,
+    })
+}
+
+fn read_indices(reader: &mut Cursor<&[u8]>, objects: u32) -> Result<Vec<u32>, HeapDumpError> {
+    let mut indices = Vec::new();
+
+    for _ in 0..reader.read_u32::<LittleEndian>()? {
+        let idx = reader.read_u32::<LittleEndian>()?;
+
+        if idx >= objects {
+            return Err(HeapDumpError::InvalidData("object index"));
+        }
+
+        indices.push(idx);
+    }
+
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let dump = HeapDump {
+            classes: vec!["Foo".into(), "Array[Int32]".into()],
+            objects: vec![
+                HeapObject {
+                    address: 0x1000,
+                    class: 0,
+                    size: 24,
+                    references: vec![1],
+                },
+                HeapObject {
+                    address: 0x1018,
+                    class: 1,
+                    size: 40,
+                    references: Vec::new(),
+                },
+            ],
+            roots: vec![0],
+        };
+
+        let read = read(&write(&dump)).expect("valid heap dump");
+
+        assert_eq!(dump.classes, read.classes);
+        assert_eq!(vec![0], read.roots);
+        assert_eq!(2, read.objects.len());
+        assert_eq!(0x1018, read.objects[1].address);
+        assert_eq!(1, read.objects[1].class);
+        assert_eq!(40, read.objects[1].size);
+        assert_eq!(vec![1], read.objects[0].references);
+    }
+
+    #[test]
+    fn invalid_dumps() {
+        assert!(match read(b"ABCD") {
+            Err(HeapDumpError::InvalidMagic) => true,
+            _ => false,
+        });
+
+        let mut data = write(&HeapDump {
+            classes: Vec::new(),
+            objects: Vec::new(),
+            roots: Vec::new(),
+        });
+        data.pop();
+
+        assert!(read(&data).is_err());
+    }
+}
@@ -9,8 +9,15 @@ use scoped_threadpool::Pool;
 
 use crate::gc::root::Slot;
 use crate::gc::{Address, Region};
-
-pub fn start(rootset: &[Slot], heap: Region, perm: Region, threadpool: &mut Pool) {
+use crate::timer::WorkerTimes;
+
+pub fn start(
+    rootset: &[Slot],
+    heap: Region,
+    perm: Region,
+    threadpool: &mut Pool,
+    worker_times: &WorkerTimes,
+) {
     let number_workers = threadpool.thread_count() as usize;
     let mut workers = Vec::with_capacity(number_workers);
     let mut stealers = Vec::with_capacity(number_workers);
@@ -62,7 +69,7 @@ pub fn start(rootset: &[Slot], heap: Region, perm: Region, threadpool: &mut Pool
                     marked: 0,
                 };
 
-                task.run();
+                worker_times.measure(|| task.run());
             });
         }
     });
@@ -13,6 +13,8 @@ pub fn get_rootset(vm: &VM, threads: &[Arc<DoraThread>]) -> Vec<Slot> {
     determine_rootset_from_handles(&mut rootset, threads);
 
     determine_rootset_from_globals(&mut rootset, vm);
+    determine_rootset_from_cleanups(&mut rootset, vm);
+    determine_rootset_from_oom_handler(&mut rootset, vm);
 
     rootset
 }
@@ -30,22 +32,24 @@ fn determine_rootset_from_globals(rootset: &mut Vec<Slot>, vm: &VM) {
     for glob in vm.globals.iter() {
         let glob = glob.read();
 
-        if glob.ty.reference_type() {
-            let slot = Slot::at(glob.address_value);
+        for offset in glob.ty.reference_offsets(vm) {
+            let slot_address = glob.address_value.offset(offset as usize);
+            let slot = Slot::at(slot_address);
             rootset.push(slot);
-        } else if let Some(tuple_id) = glob.ty.tuple_id() {
-            let tuples = vm.tuples.lock();
-            let tuple = tuples.get_tuple(tuple_id);
-
-            for &offset in tuple.offsets() {
-                let slot_address = glob.address_value.offset(offset as usize);
-                let slot = Slot::at(slot_address);
-                rootset.push(slot);
-            }
         }
     }
 }
 
+fn determine_rootset_from_cleanups(rootset: &mut Vec<Slot>, vm: &VM) {
+    if let Some(weak_refs) = vm.gc.weak_refs() {
+        weak_refs.visit_roots(|slot| rootset.push(slot));
+    }
+}
+
+fn determine_rootset_from_oom_handler(rootset: &mut Vec<Slot>, vm: &VM) {
+    vm.gc.visit_oom_handler(|slot| rootset.push(slot));
+}
+
 fn determine_rootset_from_stack(rootset: &mut Vec<Slot>, vm: &VM, threads: &[Arc<DoraThread>]) {
     for thread in threads {
         let dtn = Address::from_ptr(thread.dtn());
@@ -117,6 +121,7 @@ fn determine_rootset(rootset: &mut Vec<Slot>, vm: &VM, fp: usize, pc: usize) ->
         }
 
         Some(CodeDescriptor::AllocStub) => true,
+        Some(CodeDescriptor::BarrierStub) => true,
         Some(CodeDescriptor::DoraStub) => false,
         Some(CodeDescriptor::GuardCheckStub) => true,
         Some(CodeDescriptor::CompileStub) => true,
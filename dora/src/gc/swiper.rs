@@ -4,12 +4,14 @@ use std::fmt;
 use std::mem::size_of;
 use std::sync::Arc;
 
-use crate::driver::cmd::Args;
+use crate::driver::cmd::{Args, OldGenName};
 use crate::gc::root::{get_rootset, Slot};
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::compact::FullCollector;
+use crate::gc::swiper::concurrent::{ConcurrentMarker, PausedMarker};
 use crate::gc::swiper::controller::{HeapConfig, SharedHeapConfig};
 use crate::gc::swiper::crossing::CrossingMap;
+use crate::gc::swiper::immix::ImmixCollector;
 use crate::gc::swiper::large::LargeSpace;
 use crate::gc::swiper::minor::MinorCollector;
 use crate::gc::swiper::old::OldGen;
@@ -18,6 +20,7 @@ use crate::gc::swiper::pminor::ParallelMinorCollector;
 use crate::gc::swiper::verify::{Verifier, VerifierPhase};
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::tlab;
+use crate::gc::weak::WeakRefs;
 use crate::gc::Collector;
 use crate::gc::{align_gen, fill_region, formatted_size, Address, Region, K};
 use crate::gc::{GcReason, GEN_SIZE};
@@ -25,13 +28,17 @@ use crate::mem;
 use crate::object::Obj;
 use crate::os;
 use crate::safepoint;
+use crate::threads::DoraThread;
 use crate::vm::VM;
 
 pub mod card;
 mod compact;
+mod concurrent;
 mod controller;
 mod crossing;
+mod immix;
 mod large;
+mod log;
 mod minor;
 pub mod old;
 mod pcompact;
@@ -79,6 +86,11 @@ pub struct Swiper {
 
     threadpool: Option<Mutex<Pool>>,
     config: SharedHeapConfig,
+
+    weak_refs: WeakRefs,
+
+    // marks old generation concurrently to the mutator
+    marker: Option<ConcurrentMarker>,
 }
 
 impl Swiper {
@@ -186,6 +198,15 @@ impl Swiper {
             None
         };
 
+        let marker = if args.flag_gc_concurrent_mark {
+            Some(ConcurrentMarker::new(
+                Region::new(heap_start, heap_end),
+                Region::new(young_start, young_end),
+            ))
+        } else {
+            None
+        };
+
         Swiper {
             heap: Region::new(heap_start, heap_end),
             reserved_area,
@@ -206,10 +227,23 @@ impl Swiper {
             max_heap_size,
 
             threadpool,
+
+            weak_refs: WeakRefs::new(),
+            marker,
         }
     }
 
     fn perform_collection_and_choose(&self, vm: &VM, reason: GcReason) -> CollectionKind {
+        if let Some(ref marker) = self.marker {
+            if marker.is_finished() {
+                return self.perform_collection(
+                    vm,
+                    CollectionKind::Full,
+                    GcReason::MarkingFinished,
+                );
+            }
+        }
+
         let kind = controller::choose_collection_kind(&self.config, &vm.args, &self.young);
         self.perform_collection(vm, kind, reason)
     }
@@ -221,18 +255,29 @@ impl Swiper {
         mut reason: GcReason,
     ) -> CollectionKind {
         safepoint::stop_the_world(vm, |threads| {
-            controller::start(&self.config, &self.young, &self.old, &self.large);
+            // the marker thread must not trace while the heap is modified
+            let mut marker = self.marker.as_ref().map(|marker| marker.pause());
+
+            controller::start(
+                &self.config,
+                kind,
+                reason,
+                &self.young,
+                &self.old,
+                &self.large,
+            );
 
             tlab::make_iterable_all(vm, threads);
             let rootset = get_rootset(vm, threads);
 
             let kind = match kind {
                 CollectionKind::Minor => {
-                    let promotion_failed = self.minor_collect(vm, reason, &rootset);
+                    let promotion_failed =
+                        self.minor_collect(vm, reason, &rootset, marker.as_mut());
 
                     if promotion_failed {
                         reason = GcReason::PromotionFailure;
-                        self.full_collect(vm, reason, &rootset);
+                        self.full_collect(vm, reason, threads, &rootset, marker.as_mut());
                         CollectionKind::Full
                     } else {
                         CollectionKind::Minor
@@ -240,7 +285,7 @@ impl Swiper {
                 }
 
                 CollectionKind::Full => {
-                    self.full_collect(vm, reason, &rootset);
+                    self.full_collect(vm, reason, threads, &rootset, marker.as_mut());
                     CollectionKind::Full
                 }
             };
@@ -255,11 +300,30 @@ impl Swiper {
                 reason,
             );
 
+            if let Some(ref mut marker) = marker {
+                if kind.is_minor()
+                    && !marker.is_active()
+                    && controller::should_start_marking(&self.config)
+                {
+                    marker.start(threads, &rootset, &self.young);
+
+                    if vm.args.flag_gc_verbose {
+                        println!("GC: start concurrent marking");
+                    }
+                }
+            }
+
             kind
         })
     }
 
-    fn minor_collect(&self, vm: &VM, reason: GcReason, rootset: &[Slot]) -> bool {
+    fn minor_collect(
+        &self,
+        vm: &VM,
+        reason: GcReason,
+        rootset: &[Slot],
+        marker: Option<&mut PausedMarker>,
+    ) -> bool {
         self.verify(
             vm,
             VerifierPhase::PreMinor,
@@ -289,6 +353,7 @@ impl Swiper {
                 &self.card_table,
                 &self.crossing_map,
                 rootset,
+                &self.weak_refs,
                 reason,
                 self.min_heap_size,
                 self.max_heap_size,
@@ -298,7 +363,7 @@ impl Swiper {
 
             let promotion_failed = collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phases() {
                 let mut config = self.config.lock();
                 config.add_minor(collector.phases());
             }
@@ -313,6 +378,7 @@ impl Swiper {
                 &self.card_table,
                 &self.crossing_map,
                 rootset,
+                &self.weak_refs,
                 reason,
                 self.min_heap_size,
                 self.max_heap_size,
@@ -321,7 +387,7 @@ impl Swiper {
 
             let promotion_failed = collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phases() {
                 let mut config = self.config.lock();
                 config.add_minor(collector.phases());
             }
@@ -329,6 +395,12 @@ impl Swiper {
             promotion_failed
         };
 
+        if let Some(marker) = marker {
+            if marker.is_active() {
+                marker.mark_promoted(&self.old, &init_old_top);
+            }
+        }
+
         self.verify(
             vm,
             VerifierPhase::PostMinor,
@@ -342,7 +414,29 @@ impl Swiper {
         promotion_failed
     }
 
-    fn full_collect(&self, vm: &VM, reason: GcReason, rootset: &[Slot]) {
+    fn full_collect(
+        &self,
+        vm: &VM,
+        reason: GcReason,
+        threads: &[Arc<DoraThread>],
+        rootset: &[Slot],
+        marker: Option<&mut PausedMarker>,
+    ) {
+        if let Some(marker) = marker {
+            if marker.is_active() {
+                marker.finish(
+                    threads,
+                    rootset,
+                    &self.old,
+                    &self.large,
+                    &self.card_table,
+                    &self.crossing_map,
+                    &self.weak_refs,
+                    vm.args.flag_gc_verify,
+                );
+            }
+        }
+
         self.verify(
             vm,
             VerifierPhase::PreFull,
@@ -353,7 +447,29 @@ impl Swiper {
             Vec::new(),
         );
 
-        if vm.args.parallel_full() {
+        if vm.args.gc_old() == OldGenName::Immix {
+            let mut collector = ImmixCollector::new(
+                vm,
+                self.heap.clone(),
+                &self.young,
+                &self.old,
+                &self.large,
+                &self.card_table,
+                &self.crossing_map,
+                &vm.gc.perm_space,
+                rootset,
+                &self.weak_refs,
+                reason,
+                self.min_heap_size,
+                self.max_heap_size,
+            );
+            collector.collect();
+
+            if vm.args.gc_phases() {
+                let mut config = self.config.lock();
+                config.add_full(collector.phases());
+            }
+        } else if vm.args.parallel_full() {
             let pool = self.threadpool.as_ref().unwrap();
             let mut pool = pool.lock();
             let mut collector = ParallelFullCollector::new(
@@ -366,6 +482,7 @@ impl Swiper {
                 &self.crossing_map,
                 &vm.gc.perm_space,
                 rootset,
+                &self.weak_refs,
                 reason,
                 pool.thread_count() as usize,
                 self.min_heap_size,
@@ -373,7 +490,7 @@ impl Swiper {
             );
             collector.collect(&mut pool);
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phases() {
                 let mut config = self.config.lock();
                 config.add_full(collector.phases());
             }
@@ -388,13 +505,14 @@ impl Swiper {
                 &self.crossing_map,
                 &vm.gc.perm_space,
                 rootset,
+                &self.weak_refs,
                 reason,
                 self.min_heap_size,
                 self.max_heap_size,
             );
             collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phases() {
                 let mut config = self.config.lock();
                 config.add_full(collector.phases());
             }
@@ -462,15 +580,20 @@ impl Swiper {
     }
 
     fn alloc_large(&self, vm: &VM, size: usize, _: bool) -> Address {
-        let ptr = self.large.alloc(size);
+        let mut ptr = self.large.alloc(size);
 
-        if !ptr.is_null() {
-            return ptr;
+        if ptr.is_null() {
+            self.perform_collection(vm, CollectionKind::Full, GcReason::AllocationFailure);
+            ptr = self.large.alloc(size);
         }
 
-        self.perform_collection(vm, CollectionKind::Full, GcReason::AllocationFailure);
+        if let Some(ref marker) = self.marker {
+            if ptr.is_non_null() {
+                marker.record_large(ptr);
+            }
+        }
 
-        self.large.alloc(size)
+        ptr
     }
 }
 
@@ -524,10 +647,35 @@ impl Collector for Swiper {
         self.emit_write_barrier
     }
 
+    fn needs_pre_write_barrier(&self) -> bool {
+        self.marker.is_some() && self.emit_write_barrier
+    }
+
+    fn pre_write_barrier(&self, object: Address) {
+        if let Some(ref marker) = self.marker {
+            marker.record(object);
+        }
+    }
+
+    fn concurrent_marking_active(&self) -> bool {
+        self.marker
+            .as_ref()
+            .map(|marker| marker.is_active())
+            .unwrap_or(false)
+    }
+
     fn card_table_offset(&self) -> usize {
         self.card_table_offset
     }
 
+    fn weak_refs(&self) -> Option<&WeakRefs> {
+        Some(&self.weak_refs)
+    }
+
+    fn dump_heap_usage(&self) {
+        controller::print_heap_usage(&self.config, &self.young, &self.old, &self.large);
+    }
+
     fn dump_summary(&self, runtime: f32) {
         let config = self.config.lock();
         let total_gc = config.total_minor_pause + config.total_full_pause;
@@ -571,6 +719,15 @@ impl Collector for Swiper {
         println!("GC stats: minor-tracing={}", config.minor_tracing_all());
         println!("");
 
+        if let Some(max_pause) = config.max_pause {
+            println!("GC stats: max-pause={:.1}", max_pause);
+            println!(
+                "GC stats: pause-target-missed={}",
+                config.pause_target_missed
+            );
+            println!("");
+        }
+
         println!(
             "GC summary: {:.1}ms minor ({}), {:.1}ms full ({}), {:.1}ms collection, {:.1}ms mutator, {:.1}ms total ({}% mutator, {}% GC)",
             config.total_minor_pause,
@@ -614,6 +771,10 @@ impl Collector for Swiper {
 
 impl Drop for Swiper {
     fn drop(&mut self) {
+        if let Some(ref marker) = self.marker {
+            marker.shutdown();
+        }
+
         os::free(
             self.unaligned_reserved.start,
             self.unaligned_reserved.size(),
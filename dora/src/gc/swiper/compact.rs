@@ -11,6 +11,7 @@ use crate::gc::swiper::large::LargeSpace;
 use crate::gc::swiper::old::{OldGen, OldGenProtected};
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{walk_region, walk_region_and_skip_garbage};
+use crate::gc::weak::WeakRefs;
 use crate::gc::{Address, GcReason, Region};
 use crate::object::Obj;
 use crate::stdlib;
@@ -25,6 +26,7 @@ pub struct FullCollector<'a, 'ast: 'a> {
     old_protected: MutexGuard<'a, OldGenProtected>,
     large_space: &'a LargeSpace,
     rootset: &'a [Slot],
+    weak_refs: &'a WeakRefs,
     card_table: &'a CardTable,
     crossing_map: &'a CrossingMap,
     perm_space: &'a Space,
@@ -53,6 +55,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
         crossing_map: &'a CrossingMap,
         perm_space: &'a Space,
         rootset: &'a [Slot],
+        weak_refs: &'a WeakRefs,
         reason: GcReason,
         min_heap_size: usize,
         max_heap_size: usize,
@@ -67,6 +70,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
             old_protected: old.protected(),
             large_space,
             rootset,
+            weak_refs,
             card_table,
             crossing_map,
             perm_space,
@@ -91,7 +95,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
 
     pub fn collect(&mut self) {
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phases();
         self.init_old_top = self.old_protected.regions.iter().map(|r| r.top()).collect();
 
         let mut timer = Timer::new(stats);
@@ -179,7 +183,27 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
     }
 
     fn mark_live(&mut self) {
-        marking::start(self.rootset, self.heap, self.perm_space.total());
+        let heap = self.heap;
+        let perm = self.perm_space.total();
+
+        marking::start(self.rootset, heap, perm);
+
+        // values of ephemerons are only marked when both ephemeron and key
+        // are live, this might make more ephemerons reachable.
+        loop {
+            let values = self
+                .weak_refs
+                .ephemeron_values(|object| forward_marked(&heap, object));
+
+            if values.is_empty() {
+                break;
+            }
+
+            marking::start(&values, heap, perm);
+        }
+
+        self.weak_refs
+            .process(|object| forward_marked(&heap, object));
     }
 
     fn compute_forward(&mut self) {
@@ -194,7 +218,8 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
         });
 
         if !self.fits_into_heap() {
-            stdlib::trap(Trap::OOM.int());
+            // the OOM report reads the old generation
+            MutexGuard::unlocked(&mut self.old_protected, || stdlib::trap(Trap::OOM.int()));
         }
 
         self.old_protected.commit_single_region(self.old_top);
@@ -202,12 +227,12 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
     }
 
     fn fits_into_heap(&mut self) -> bool {
-        let (eden_size, semi_size) = self.young.committed_size();
-        let young_size = eden_size + semi_size;
+        // the young generation is empty after a full collection,
+        // the controller sizes it with the memory that is left
         let old_size = self.old_top.align_gen().offset_from(self.old.total_start());
         let large_size = self.large_space.committed_size();
 
-        (young_size + old_size + large_size) <= self.max_heap_size
+        (old_size + large_size) <= self.max_heap_size
     }
 
     fn update_references(&mut self) {
@@ -223,6 +248,9 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
             self.forward_reference(*root);
         }
 
+        let weak_refs = self.weak_refs;
+        weak_refs.visit_weak_slots(|slot| self.forward_reference(slot));
+
         self.large_space.remove_objects(|object_start| {
             let object = object_start.to_mut_obj();
 
@@ -397,6 +425,15 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
     }
 }
 
+// objects are not moved during marking, only unmarked objects are dead
+pub fn forward_marked(heap: &Region, object: Address) -> Option<Address> {
+    if heap.contains(object) && !object.to_obj().header().is_marked_non_atomic() {
+        None
+    } else {
+        Some(object)
+    }
+}
+
 pub fn verify_marking(
     young: &YoungGen,
     old_protected: &OldGenProtected,
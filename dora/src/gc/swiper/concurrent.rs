@@ -0,0 +1,488 @@
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::cmp;
+use std::collections::HashSet;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::gc::root::Slot;
+use crate::gc::swiper::card::CardTable;
+use crate::gc::swiper::compact::forward_marked;
+use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
+use crate::gc::swiper::large::LargeSpace;
+use crate::gc::swiper::old::OldGen;
+use crate::gc::swiper::young::YoungGen;
+use crate::gc::swiper::{walk_region, CARD_SIZE};
+use crate::gc::weak::WeakRefs;
+use crate::gc::{Address, Region};
+use crate::object::offset_of_array_data;
+use crate::threads::DoraThread;
+
+// number of objects traced before the marker thread gives
+// waiting collections the chance to pause marking
+const MARKING_CHUNK: usize = 256;
+
+// Marks the old generation and the large space concurrently to the mutator.
+// Marking maintains a snapshot-at-the-beginning: all objects reachable when
+// marking started get marked. The pre-write barrier records references
+// before they are overwritten, objects promoted or allocated during marking
+// are marked as well. Young objects are only marked in the remark pause
+// right before the full collection. Weak fields are not traced, loading
+// one runs the pre-write barrier as keep-alive barrier for the referent.
+pub struct ConcurrentMarker {
+    shared: Arc<Shared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Shared {
+    heap: Region,
+    young: Region,
+
+    // enables recording of overwritten references
+    active: AtomicBool,
+
+    // set by the marker thread as soon as there are no gray objects left
+    finished: AtomicBool,
+
+    // marker thread holds the lock while tracing, collections take the
+    // lock to pause marking while they modify the heap
+    work: Mutex<Work>,
+    cond: Condvar,
+
+    // references recorded by the pre-write barrier
+    satb: Mutex<Vec<Address>>,
+
+    // large objects allocated while marking
+    large: Mutex<Vec<Address>>,
+}
+
+struct Work {
+    gray: Vec<Address>,
+    running: bool,
+    shutdown: bool,
+}
+
+impl ConcurrentMarker {
+    pub fn new(heap: Region, young: Region) -> ConcurrentMarker {
+        let shared = Arc::new(Shared {
+            heap,
+            young,
+            active: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            work: Mutex::new(Work {
+                gray: Vec::new(),
+                running: false,
+                shutdown: false,
+            }),
+            cond: Condvar::new(),
+            satb: Mutex::new(Vec::new()),
+            large: Mutex::new(Vec::new()),
+        });
+
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name("concurrent marker".into())
+            .spawn(move || run(thread_shared))
+            .expect("could not start concurrent marker");
+
+        ConcurrentMarker {
+            shared,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.shared.active.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_active() && self.shared.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, object: Address) {
+        let shared = &self.shared;
+
+        if shared.active.load(Ordering::Relaxed)
+            && shared.is_old(object)
+            && !object.to_obj().header().is_marked_non_atomic()
+        {
+            shared.satb.lock().push(object);
+        }
+    }
+
+    pub fn record_large(&self, object: Address) {
+        if self.is_active() {
+            self.shared.large.lock().push(object);
+        }
+    }
+
+    // stops marking until the returned guard is dropped
+    pub fn pause(&self) -> PausedMarker {
+        PausedMarker {
+            shared: &self.shared,
+            work: self.shared.work.lock(),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        {
+            let mut work = self.shared.work.lock();
+            work.shutdown = true;
+            self.shared.cond.notify_all();
+        }
+
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().expect("concurrent marker failed");
+        }
+    }
+}
+
+pub struct PausedMarker<'a> {
+    shared: &'a Shared,
+    work: MutexGuard<'a, Work>,
+}
+
+impl<'a> PausedMarker<'a> {
+    pub fn is_active(&self) -> bool {
+        self.work.running
+    }
+
+    // initial mark: grays all old objects referenced from roots or from the
+    // young generation, needs to run right after a minor collection.
+    pub fn start(&mut self, threads: &[Arc<DoraThread>], rootset: &[Slot], young: &YoungGen) {
+        assert!(!self.work.running);
+        assert!(young.eden_active().empty());
+        assert!(young.from_active().empty());
+
+        let shared = self.shared;
+        let gray = &mut self.work.gray;
+        assert!(gray.is_empty());
+
+        for root in rootset {
+            shared.mark(root.get(), gray, false);
+        }
+
+        walk_region(young.to_active(), |object, _, _| {
+            object.visit_reference_fields(|field| shared.mark(field.get(), gray, false));
+        });
+
+        for thread in threads {
+            thread.tld.set_concurrent_marking(true);
+        }
+
+        shared.active.store(true, Ordering::Relaxed);
+        shared.finished.store(false, Ordering::Relaxed);
+        self.work.running = true;
+        shared.cond.notify_all();
+    }
+
+    // Objects promoted by the last minor collection are gray: they were
+    // reachable from young objects, which aren't part of the snapshot.
+    pub fn mark_promoted(&mut self, old: &OldGen, init_old_top: &[Address]) {
+        let shared = self.shared;
+        let gray = &mut self.work.gray;
+        let old = old.protected();
+
+        for (region, &init_top) in old.regions.iter().zip(init_old_top) {
+            let promoted = Region::new(init_top, region.top());
+
+            walk_region(promoted, |_, address, _| {
+                shared.mark(address, gray, false);
+            });
+        }
+    }
+
+    // Remark: stops marking and marks all remaining live objects, including
+    // the young generation. All live objects are marked afterwards, the full
+    // collection can skip straight to computing forwarding addresses.
+    pub fn finish(
+        &mut self,
+        threads: &[Arc<DoraThread>],
+        rootset: &[Slot],
+        old: &OldGen,
+        large: &LargeSpace,
+        card_table: &CardTable,
+        crossing_map: &CrossingMap,
+        weak_refs: &WeakRefs,
+        verify: bool,
+    ) {
+        assert!(self.work.running);
+        let shared = self.shared;
+
+        for thread in threads {
+            thread.tld.set_concurrent_marking(false);
+        }
+
+        shared.active.store(false, Ordering::Relaxed);
+        self.work.running = false;
+
+        let mut gray = mem::replace(&mut self.work.gray, Vec::new());
+
+        for object in mem::replace(&mut *shared.large.lock(), Vec::new()) {
+            shared.mark(object, &mut gray, false);
+        }
+
+        shared.drain_satb(&mut gray, true);
+
+        for root in rootset {
+            shared.mark(root.get(), &mut gray, true);
+        }
+
+        // Old objects traced concurrently skipped their young references.
+        // All references from old to young objects are on dirty cards.
+        for region in old.protected().regions.iter() {
+            shared.mark_dirty_cards_in_old(
+                region.active_region(),
+                card_table,
+                crossing_map,
+                &mut gray,
+            );
+        }
+
+        shared.mark_dirty_cards_in_large(large, card_table, &mut gray);
+
+        shared.trace_all(&mut gray, true);
+
+        // values of ephemerons are only marked when both ephemeron and key
+        // are live, this might make more ephemerons reachable.
+        loop {
+            let values = weak_refs.ephemeron_values(|object| forward_marked(&shared.heap, object));
+
+            if values.is_empty() {
+                break;
+            }
+
+            for value in values {
+                shared.mark(value.get(), &mut gray, true);
+            }
+
+            shared.trace_all(&mut gray, true);
+        }
+
+        // Weak fields were never traced. Referents loaded by the mutator
+        // while marking were recorded by the keep-alive barrier, all other
+        // unmarked referents are dead.
+        weak_refs.process(|object| forward_marked(&shared.heap, object));
+
+        if verify {
+            shared.verify_marking(rootset);
+        }
+    }
+}
+
+impl Shared {
+    fn is_old(&self, object: Address) -> bool {
+        self.heap.contains(object) && !self.young.contains(object)
+    }
+
+    // marks object and pushes it onto the gray stack, young objects
+    // are only marked in the remark pause
+    fn mark(&self, object: Address, gray: &mut Vec<Address>, young: bool) {
+        if !self.heap.contains(object) || (!young && self.young.contains(object)) {
+            return;
+        }
+
+        if object.to_obj().header().try_mark_non_atomic() {
+            gray.push(object);
+        }
+    }
+
+    fn trace(&self, object: Address, gray: &mut Vec<Address>, young: bool) {
+        object
+            .to_mut_obj()
+            .visit_reference_fields(|field| self.mark(field.get(), gray, young));
+    }
+
+    fn trace_all(&self, gray: &mut Vec<Address>, young: bool) {
+        while let Some(object) = gray.pop() {
+            self.trace(object, gray, young);
+        }
+    }
+
+    fn drain_satb(&self, gray: &mut Vec<Address>, young: bool) {
+        let satb = mem::replace(&mut *self.satb.lock(), Vec::new());
+
+        for object in satb {
+            self.mark(object, gray, young);
+        }
+    }
+
+    // marks young objects referenced from dirty cards of an old region,
+    // unmarked old objects are skipped
+    fn mark_dirty_cards_in_old(
+        &self,
+        region: Region,
+        card_table: &CardTable,
+        crossing_map: &CrossingMap,
+        gray: &mut Vec<Address>,
+    ) {
+        card_table.visit_dirty_in_old(region.start, region.end, |card_idx| {
+            let card_start = card_table.to_address(card_idx);
+            let card_end = cmp::min(card_start.offset(CARD_SIZE), region.end);
+
+            let first_object = if card_idx == card_table.card_idx(region.start) {
+                region.start
+            } else {
+                match crossing_map.get(card_idx) {
+                    CrossingEntry::NoRefs => panic!("card dirty without any refs"),
+
+                    CrossingEntry::LeadingRefs(refs) => {
+                        // elements of an array starting on an earlier card
+                        let first_object = card_start.add_ptr(refs as usize);
+                        let ref_start = cmp::max(card_start, region.start);
+                        let ref_end = cmp::min(first_object, card_end);
+                        self.mark_young_refs(ref_start, ref_end, gray);
+
+                        first_object
+                    }
+
+                    CrossingEntry::FirstObject(offset) => card_start.add_ptr(offset as usize),
+                    CrossingEntry::ArrayStart(offset) => card_start.sub_ptr(offset as usize),
+
+                    CrossingEntry::PreviousObjectWords(_) => unimplemented!(),
+                    CrossingEntry::PreviousObjectCards(_) => unimplemented!(),
+                }
+            };
+
+            let mut ptr = cmp::max(first_object, region.start);
+
+            while ptr < card_end {
+                let object = ptr.to_mut_obj();
+
+                if object.header().vtblptr().is_null() {
+                    ptr = ptr.add_ptr(1);
+                    continue;
+                }
+
+                if object.header().is_marked_non_atomic() {
+                    let range = Region::new(ptr, card_end);
+                    object.visit_reference_fields_within(range, |field| {
+                        self.mark_young(field.get(), gray);
+                    });
+                }
+
+                ptr = ptr.offset(object.size());
+            }
+        });
+    }
+
+    // marks young objects referenced from dirty cards of live large objects
+    fn mark_dirty_cards_in_large(
+        &self,
+        large: &LargeSpace,
+        card_table: &CardTable,
+        gray: &mut Vec<Address>,
+    ) {
+        large.visit_objects(|object_start| {
+            let object = object_start.to_mut_obj();
+
+            if !object.header().is_marked_non_atomic() {
+                return;
+            }
+
+            if !object.is_array_ref() {
+                if card_table.get(card_table.card_idx(object_start)).is_dirty() {
+                    object.visit_reference_fields(|field| self.mark_young(field.get(), gray));
+                }
+
+                return;
+            }
+
+            let object_end = object_start.offset(object.size() as usize);
+            let (start_card_idx, end_card_idx) = card_table.card_indices(object_start, object_end);
+
+            for card_idx in start_card_idx..end_card_idx {
+                let card_idx = card_idx.into();
+
+                if card_table.get(card_idx).is_clean() {
+                    continue;
+                }
+
+                let card_start = card_table.to_address(card_idx);
+                let ref_start = object_start.offset(offset_of_array_data() as usize);
+                let ref_start = cmp::max(ref_start, card_start);
+                let ref_end = cmp::min(card_start.offset(CARD_SIZE), object_end);
+                self.mark_young_refs(ref_start, ref_end, gray);
+            }
+        });
+    }
+
+    fn mark_young_refs(&self, start: Address, end: Address, gray: &mut Vec<Address>) {
+        let mut ptr = start;
+
+        while ptr < end {
+            self.mark_young(Slot::at(ptr).get(), gray);
+            ptr = ptr.add_ptr(1);
+        }
+    }
+
+    fn mark_young(&self, object: Address, gray: &mut Vec<Address>) {
+        if self.young.contains(object) {
+            self.mark(object, gray, true);
+        }
+    }
+
+    // checks that all objects reachable from the roots are marked
+    fn verify_marking(&self, rootset: &[Slot]) {
+        let mut visited = HashSet::new();
+        let mut stack = Vec::new();
+
+        for root in rootset {
+            let object = root.get();
+
+            if self.heap.contains(object) && visited.insert(object) {
+                stack.push(object);
+            }
+        }
+
+        while let Some(object) = stack.pop() {
+            assert!(
+                object.to_obj().header().is_marked_non_atomic(),
+                "concurrent marking missed live object {}",
+                object
+            );
+
+            object.to_mut_obj().visit_reference_fields(|field| {
+                let field = field.get();
+
+                if self.heap.contains(field) && visited.insert(field) {
+                    stack.push(field);
+                }
+            });
+        }
+    }
+}
+
+fn run(shared: Arc<Shared>) {
+    let mut work = shared.work.lock();
+
+    loop {
+        if work.shutdown {
+            return;
+        }
+
+        if !work.running {
+            shared.cond.wait(&mut work);
+            continue;
+        }
+
+        shared.drain_satb(&mut work.gray, false);
+
+        if work.gray.is_empty() {
+            shared.finished.store(true, Ordering::Relaxed);
+            shared.cond.wait_for(&mut work, Duration::from_millis(1));
+            continue;
+        }
+
+        for _ in 0..MARKING_CHUNK {
+            match work.gray.pop() {
+                Some(object) => shared.trace(object, &mut work.gray, false),
+                None => break,
+            }
+        }
+
+        MutexGuard::bump(&mut work);
+    }
+}
@@ -1,11 +1,13 @@
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use std::cmp::{max, min};
+use std::collections::VecDeque;
 use std::f32;
 use std::fmt;
 use std::sync::Arc;
 
 use crate::driver::cmd::Args;
 use crate::gc::swiper::large::LargeSpace;
+use crate::gc::swiper::log::{self, GcLog, SpaceSizes};
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{CollectionKind, CommonOldGen};
 use crate::gc::{align_gen, align_gen_down, formatted_size, AllNumbers, GcReason, GEN_SIZE, M};
@@ -18,6 +20,25 @@ const INIT_HEAP_SIZE_RATIO: usize = 2;
 const INIT_YOUNG_RATIO: usize = 4;
 const INIT_SEMI_RATIO: usize = 3;
 
+// concurrent marking starts as soon as the old generation
+// is filled to this percentage of its limit
+const MARKING_THRESHOLD: usize = 60;
+
+// generations are sized for pauses of this fraction of the pause target,
+// which leaves some slack for pauses varying between collections
+const PAUSE_TARGET_RATIO: f32 = 0.8;
+
+// the pause target never shrinks the young generation below this size,
+// smaller young generations only trigger full collections
+const MIN_PAUSE_YOUNG_SIZE: usize = 2 * M;
+
+// the pause target never shrinks the old generation limit below this
+// multiple of the live old objects, collections would be too frequent
+const MIN_PAUSE_OLD_RATIO: usize = 2;
+
+// number of collections listed when running out of memory
+const RECENT_COLLECTIONS: usize = 8;
+
 pub fn init(config: &mut HeapConfig, args: &Args) {
     assert!(config.min_heap_size <= config.max_heap_size);
 
@@ -49,6 +70,17 @@ pub fn init(config: &mut HeapConfig, args: &Args) {
 
     config.old_size = 0;
     config.old_limit = old_limit;
+    config.max_pause = args.flag_gc_max_pause;
+
+    if let Some(ref path) = args.flag_gc_log {
+        match GcLog::create(path) {
+            Ok(gc_log) => config.gc_log = Some(gc_log),
+            Err(err) => {
+                eprintln!("opening GC log {} failed: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
 fn calculate_young_size(args: &Args, young_size: usize, min_semi_size: usize) -> (usize, usize) {
@@ -81,17 +113,35 @@ pub fn choose_collection_kind(
     };
 }
 
+pub fn should_start_marking(config: &SharedHeapConfig) -> bool {
+    let config = config.lock();
+    config.old_size * 100 >= config.old_limit * MARKING_THRESHOLD
+}
+
 pub fn start(
     config: &SharedHeapConfig,
+    kind: CollectionKind,
+    reason: GcReason,
     young: &YoungGen,
     old: &dyn CommonOldGen,
     large: &LargeSpace,
 ) {
     let mut config = config.lock();
 
+    // replaced by `stop` since the kind of collection might change
+    if config.recent_collections.len() == RECENT_COLLECTIONS {
+        config.recent_collections.pop_front();
+    }
+
+    config.recent_collections.push_back((kind, reason));
+
     config.gc_start = timer::timestamp();
     config.start_object_size = object_size(young, old, large);
     config.start_memory_size = memory_size(young, old, large);
+
+    if config.gc_log.is_some() {
+        config.start_sizes = SpaceSizes::of(young, old, large);
+    }
 }
 
 pub fn stop(
@@ -114,6 +164,10 @@ pub fn stop(
     let old_size = old.committed_size() + large.committed_size();
     config.old_size = old_size;
 
+    if let Some(max_pause) = config.max_pause {
+        adapt_to_pause_target(&mut *config, kind, young, max_pause);
+    }
+
     let max_young_size = if let Some(young_size) = args.young_size() {
         align_gen(young_size)
     } else {
@@ -128,15 +182,47 @@ pub fn stop(
     let to_size = young.to_active().size();
     let min_semi_size = align_gen(mem::page_align(to_size) * 2);
 
+    let target_young_size = if config.max_pause.is_some() {
+        // survivors of this collection limit how far the
+        // young generation can shrink
+        let target_young_size = min(target_young_size, config.young_pause_limit);
+        max(target_young_size, min_semi_size + GEN_SIZE)
+    } else {
+        target_young_size
+    };
+
     let (eden_size, semi_size) = calculate_young_size(args, target_young_size, min_semi_size);
     let young_size = eden_size + semi_size;
 
-    if old_size + young_size > config.max_heap_size {
-        stdlib::trap(Trap::OOM.int());
-    }
+    let (eden_size, semi_size) = if old_size + young_size > config.max_heap_size {
+        // The heap is exhausted: the young generation only gets the memory
+        // that is left, allocations fail as soon as it is full. This gives
+        // the program the chance to release memory.
+        let rest = config.max_heap_size.saturating_sub(old_size);
+        let semi_size = max(min(semi_size, align_gen_down(rest)), min_semi_size);
+
+        if semi_size > rest {
+            // the OOM report reads the configuration
+            MutexGuard::unlocked(&mut config, || stdlib::trap(Trap::OOM.int()));
+        }
+
+        (align_gen_down(rest - semi_size), semi_size)
+    } else {
+        (eden_size, semi_size)
+    };
+    let young_size = eden_size + semi_size;
 
     young.set_limit(eden_size, semi_size);
     config.old_limit = config.max_heap_size - young_size;
+
+    if config.max_pause.is_some() {
+        // start full collections earlier to keep their pauses short, all
+        // objects surviving a minor collection still need to fit
+        let min_old_limit = old_size + semi_size;
+        let old_limit = max(config.old_pause_limit, min_old_limit);
+        config.old_limit = min(config.old_limit, old_limit);
+    }
+
     assert!(config.old_limit >= old_size);
 
     config.end_object_size = object_size(young, old, large);
@@ -144,12 +230,14 @@ pub fn stop(
 
     assert!(young_size + config.old_limit <= config.max_heap_size);
 
+    *config.recent_collections.back_mut().unwrap() = (kind, reason);
+
     match kind {
         CollectionKind::Minor => {
             config.total_minor_collections += 1;
             config.total_minor_pause += config.gc_duration;
 
-            if args.flag_gc_stats {
+            if args.gc_phases() {
                 config.minor_phases.last_mut().unwrap().total = config.gc_duration;
             }
         }
@@ -158,17 +246,142 @@ pub fn stop(
             config.total_full_collections += 1;
             config.total_full_pause += config.gc_duration;
 
-            if args.flag_gc_stats {
+            if args.gc_phases() {
                 config.full_phases.last_mut().unwrap().total = config.gc_duration;
             }
         }
     }
 
+    // misses are reported by --gc-stats and the GC log
+    if let Some(max_pause) = config.max_pause {
+        if config.gc_duration > max_pause {
+            config.pause_target_missed += 1;
+        }
+    }
+
+    if config.gc_log.is_some() {
+        let record = log_record(&*config, kind, reason, young, old, large);
+        config.gc_log.as_mut().unwrap().write(&record);
+    }
+
     if args.flag_gc_verbose {
         print(&*config, kind, reason);
     }
 }
 
+fn log_record(
+    config: &HeapConfig,
+    kind: CollectionKind,
+    reason: GcReason,
+    young: &YoungGen,
+    old: &dyn CommonOldGen,
+    large: &LargeSpace,
+) -> String {
+    let mut record = format!(
+        "{{\"gc\":{},\"kind\":\"{}\",\"reason\":\"{}\",\"time\":{:.3},\"pause\":{:.3}",
+        config.total_minor_collections + config.total_full_collections,
+        kind,
+        reason,
+        timer::in_ms(config.gc_start - config.vm_start),
+        config.gc_duration,
+    );
+
+    if let Some(max_pause) = config.max_pause {
+        record.push_str(&format!(
+            ",\"max_pause\":{:.3},\"target_missed\":{}",
+            max_pause,
+            config.gc_duration > max_pause
+        ));
+    }
+
+    record.push_str(&format!(
+        ",\"before\":{},\"after\":{}",
+        config.start_sizes.to_json(),
+        SpaceSizes::of(young, old, large).to_json()
+    ));
+
+    match kind {
+        CollectionKind::Minor => {
+            record.push_str(&format!(
+                ",\"promoted\":{},\"copied\":{},\"garbage\":{}",
+                config.minor_promoted, config.minor_copied, config.minor_dead
+            ));
+
+            if let Some(phases) = config.minor_phases.last() {
+                record.push_str(&log::minor_phases(phases));
+            }
+        }
+
+        CollectionKind::Full => {
+            if let Some(phases) = config.full_phases.last() {
+                record.push_str(&log::full_phases(phases));
+            }
+        }
+    }
+
+    record.push('}');
+    record
+}
+
+// Minor pauses grow with the size of the young generation: it shrinks when
+// pauses exceed the target and slowly grows again while they stay well below.
+// Full pauses grow with the size of the heap, the time per byte of the last
+// full collection's phases determines the old generation limit.
+fn adapt_to_pause_target(
+    config: &mut HeapConfig,
+    kind: CollectionKind,
+    young: &YoungGen,
+    max_pause: f32,
+) {
+    let target = max_pause * PAUSE_TARGET_RATIO;
+
+    match kind {
+        CollectionKind::Minor => {
+            let (eden_size, semi_size) = young.committed_size();
+
+            config.young_pause_limit = young_pause_limit(
+                config.young_pause_limit,
+                eden_size + semi_size,
+                config.gc_duration,
+                target,
+                config.max_heap_size,
+            );
+        }
+
+        CollectionKind::Full => {
+            let phases = config.full_phases.last().expect("missing phases");
+            let pause =
+                phases.marking + phases.compute_forward + phases.update_refs + phases.relocate;
+            let heap_size = config.start_object_size;
+
+            if pause > 0f32 && heap_size > 0 {
+                let old_limit = (heap_size as f32 * target / pause) as usize;
+                let old_limit = max(old_limit, config.old_size * MIN_PAUSE_OLD_RATIO);
+                config.old_pause_limit = align_gen(old_limit);
+            }
+        }
+    }
+}
+
+// shrinks the young generation proportionally to the pause exceeding the
+// target, grows it by a quarter while pauses stay below half the target
+fn young_pause_limit(
+    limit: usize,
+    young_size: usize,
+    pause: f32,
+    target: f32,
+    max_heap_size: usize,
+) -> usize {
+    if pause > target {
+        let young_size = (young_size as f32 * target / pause) as usize;
+        max(align_gen_down(young_size), MIN_PAUSE_YOUNG_SIZE)
+    } else if pause < target / 2f32 && limit < max_heap_size {
+        align_gen(limit + limit / 4)
+    } else {
+        limit
+    }
+}
+
 fn print(config: &HeapConfig, kind: CollectionKind, reason: GcReason) {
     match kind {
         CollectionKind::Minor => {
@@ -202,6 +415,50 @@ fn print(config: &HeapConfig, kind: CollectionKind, reason: GcReason) {
     }
 }
 
+// prints used and committed memory of each space together with the most
+// recent collections, used to diagnose running out of memory
+pub fn print_heap_usage(
+    config: &SharedHeapConfig,
+    young: &YoungGen,
+    old: &dyn CommonOldGen,
+    large: &LargeSpace,
+) {
+    let config = config.lock();
+    let (eden_size, semi_size) = young.committed_size();
+
+    eprintln!("heap usage (max {}):", formatted_size(config.max_heap_size));
+    eprintln!(
+        "  eden: {} of {}",
+        formatted_size(young.eden_active().size()),
+        formatted_size(eden_size)
+    );
+    eprintln!(
+        "  survivor: {} of {}",
+        formatted_size(young.from_active().size() + young.to_active().size()),
+        formatted_size(semi_size)
+    );
+    eprintln!(
+        "  old: {} of {} (limit {})",
+        formatted_size(old.active_size()),
+        formatted_size(old.committed_size()),
+        formatted_size(config.old_limit)
+    );
+    eprintln!("  large: {}", formatted_size(large.committed_size()));
+
+    let recent: Vec<String> = config
+        .recent_collections
+        .iter()
+        .rev()
+        .map(|(kind, reason)| format!("{} ({})", kind, reason))
+        .collect();
+
+    if recent.is_empty() {
+        eprintln!("recent collections: none");
+    } else {
+        eprintln!("recent collections: {}", recent.join(", "));
+    }
+}
+
 fn object_size(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> usize {
     young.active_size() + old.active_size() + large.committed_size()
 }
@@ -222,6 +479,16 @@ pub struct HeapConfig {
     pub old_size: usize,
     pub old_limit: usize,
 
+    // pause target in ms and the generation limits derived from it
+    pub max_pause: Option<f32>,
+    young_pause_limit: usize,
+    old_pause_limit: usize,
+    pub pause_target_missed: usize,
+
+    gc_log: Option<GcLog>,
+    vm_start: u64,
+    start_sizes: SpaceSizes,
+
     gc_start: u64,
     gc_duration: f32,
 
@@ -239,6 +506,9 @@ pub struct HeapConfig {
     pub total_full_collections: usize,
     pub total_full_pause: f32,
 
+    // kind and reason of the last collections, most recent last
+    recent_collections: VecDeque<(CollectionKind, GcReason)>,
+
     full_phases: Vec<FullCollectorPhases>,
     minor_phases: Vec<MinorCollectorPhases>,
 }
@@ -256,6 +526,15 @@ impl HeapConfig {
             old_size: 0,
             old_limit: 0,
 
+            max_pause: None,
+            young_pause_limit: std::usize::MAX,
+            old_pause_limit: std::usize::MAX,
+            pause_target_missed: 0,
+
+            gc_log: None,
+            vm_start: timer::timestamp(),
+            start_sizes: SpaceSizes::default(),
+
             gc_start: 0,
             gc_duration: 0f32,
 
@@ -273,6 +552,8 @@ impl HeapConfig {
             total_full_collections: 0,
             total_full_pause: 0f32,
 
+            recent_collections: VecDeque::new(),
+
             full_phases: Vec::new(),
             minor_phases: Vec::new(),
         }
@@ -445,6 +726,9 @@ pub struct FullCollectorPhases {
     pub relocate: f32,
     pub reset_cards: f32,
     pub total: f32,
+
+    // busy time of each worker thread for parallel collections
+    pub workers: Vec<f32>,
 }
 
 impl FullCollectorPhases {
@@ -456,6 +740,7 @@ impl FullCollectorPhases {
             relocate: 0f32,
             reset_cards: 0f32,
             total: 0f32,
+            workers: Vec::new(),
         }
     }
 }
@@ -465,6 +750,9 @@ pub struct MinorCollectorPhases {
     pub roots: f32,
     pub tracing: f32,
     pub total: f32,
+
+    // busy time of each worker thread for parallel collections
+    pub workers: Vec<f32>,
 }
 
 impl MinorCollectorPhases {
@@ -473,6 +761,48 @@ impl MinorCollectorPhases {
             roots: 0f32,
             tracing: 0f32,
             total: 0f32,
+            workers: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{young_pause_limit, MIN_PAUSE_YOUNG_SIZE};
+    use crate::gc::M;
+
+    #[test]
+    fn test_young_shrinks_with_pause() {
+        assert_eq!(
+            young_pause_limit(std::usize::MAX, 8 * M, 16f32, 8f32, 64 * M),
+            4 * M
+        );
+        assert_eq!(
+            young_pause_limit(4 * M, 4 * M, 10f32, 8f32, 64 * M),
+            3200 * 1024
+        );
+    }
+
+    #[test]
+    fn test_young_keeps_min_size() {
+        assert_eq!(
+            young_pause_limit(4 * M, 4 * M, 100f32, 8f32, 64 * M),
+            MIN_PAUSE_YOUNG_SIZE
+        );
+    }
+
+    #[test]
+    fn test_young_grows_below_half_target() {
+        assert_eq!(young_pause_limit(4 * M, 4 * M, 1f32, 8f32, 64 * M), 5 * M);
+        assert_eq!(young_pause_limit(4 * M, 4 * M, 6f32, 8f32, 64 * M), 4 * M);
+    }
+
+    #[test]
+    fn test_young_growth_limited_by_heap() {
+        assert_eq!(young_pause_limit(64 * M, 8 * M, 1f32, 8f32, 64 * M), 64 * M);
+        assert_eq!(
+            young_pause_limit(std::usize::MAX, 8 * M, 1f32, 8f32, 64 * M),
+            std::usize::MAX
+        );
+    }
+}
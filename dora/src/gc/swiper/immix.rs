@@ -0,0 +1,647 @@
+use fixedbitset::FixedBitSet;
+use parking_lot::MutexGuard;
+use std::cmp;
+
+use crate::gc::marking;
+use crate::gc::root::Slot;
+use crate::gc::space::Space;
+use crate::gc::swiper::card::CardTable;
+use crate::gc::swiper::compact::{forward_marked, verify_marking};
+use crate::gc::swiper::controller::FullCollectorPhases;
+use crate::gc::swiper::crossing::CrossingMap;
+use crate::gc::swiper::large::LargeSpace;
+use crate::gc::swiper::old::{OldGen, OldGenProtected, OldGenRegion};
+use crate::gc::swiper::walk_region;
+use crate::gc::swiper::young::YoungGen;
+use crate::gc::weak::WeakRefs;
+use crate::gc::{fill_region, Address, GcReason, Region, K};
+use crate::mem;
+use crate::stdlib;
+use crate::timer::Timer;
+use crate::vm::{Trap, VM};
+
+// the old generation is divided into blocks, each block into lines
+const BLOCK_SIZE: usize = 32 * K;
+const LINE_SIZE: usize = 256;
+
+// blocks with less than this percentage of live bytes are evacuated
+const EVACUATE_THRESHOLD: usize = 50;
+
+// Objects larger than a line that do not fit into the current hole are
+// allocated at the end of the old generation. The hole stays available
+// for smaller objects.
+const MEDIUM_OBJECT_SIZE: usize = LINE_SIZE;
+
+// Full collection that only moves objects out of fragmented blocks and out
+// of the young generation. All other old objects stay where they are, runs
+// of free lines between them become holes: the old generation is split into
+// one region per hole, promotion then allocates into these holes.
+pub struct ImmixCollector<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    heap: Region,
+    young: &'a YoungGen,
+    old: &'a OldGen,
+    old_protected: MutexGuard<'a, OldGenProtected>,
+    large_space: &'a LargeSpace,
+    rootset: &'a [Slot],
+    weak_refs: &'a WeakRefs,
+    card_table: &'a CardTable,
+    crossing_map: &'a CrossingMap,
+    perm_space: &'a Space,
+
+    old_total: Region,
+    init_old_top: Address,
+
+    // live bytes of objects starting in each block
+    live_bytes: Vec<usize>,
+
+    // blocks whose objects get evacuated
+    evacuate: FixedBitSet,
+
+    // free space between live objects outside of evacuated blocks
+    holes: Vec<Region>,
+    next_hole: usize,
+    hole_top: Address,
+    hole_limit: Address,
+
+    // end of old generation, used for medium objects and when there
+    // are no holes left
+    overflow_top: Address,
+
+    // old and young objects that get moved
+    moved: Vec<Address>,
+
+    // memory occupied by live objects after the collection
+    spans: Vec<Region>,
+
+    reason: GcReason,
+
+    min_heap_size: usize,
+    max_heap_size: usize,
+
+    phases: FullCollectorPhases,
+}
+
+impl<'a, 'ast> ImmixCollector<'a, 'ast> {
+    pub fn new(
+        vm: &'a VM<'ast>,
+        heap: Region,
+        young: &'a YoungGen,
+        old: &'a OldGen,
+        large_space: &'a LargeSpace,
+        card_table: &'a CardTable,
+        crossing_map: &'a CrossingMap,
+        perm_space: &'a Space,
+        rootset: &'a [Slot],
+        weak_refs: &'a WeakRefs,
+        reason: GcReason,
+        min_heap_size: usize,
+        max_heap_size: usize,
+    ) -> ImmixCollector<'a, 'ast> {
+        let old_total = old.total();
+        assert!(old_total.start.to_usize() % BLOCK_SIZE == 0);
+
+        let number_blocks = old_total.size() / BLOCK_SIZE;
+
+        ImmixCollector {
+            vm,
+            heap,
+            young,
+            old,
+            old_protected: old.protected(),
+            large_space,
+            rootset,
+            weak_refs,
+            card_table,
+            crossing_map,
+            perm_space,
+
+            old_total,
+            init_old_top: old_total.start,
+
+            live_bytes: vec![0; number_blocks],
+            evacuate: FixedBitSet::with_capacity(number_blocks),
+
+            holes: Vec::new(),
+            next_hole: 0,
+            hole_top: Address::null(),
+            hole_limit: Address::null(),
+
+            overflow_top: Address::null(),
+
+            moved: Vec::new(),
+            spans: Vec::new(),
+
+            reason,
+
+            min_heap_size,
+            max_heap_size,
+
+            phases: FullCollectorPhases::new(),
+        }
+    }
+
+    pub fn phases(&self) -> FullCollectorPhases {
+        self.phases.clone()
+    }
+
+    pub fn collect(&mut self) {
+        let dev_verbose = self.vm.args.flag_gc_dev_verbose;
+        let stats = self.vm.args.gc_phases();
+        self.init_old_top = self.old_protected.regions.last().unwrap().top();
+
+        let mut timer = Timer::new(stats);
+
+        if dev_verbose {
+            println!("Full GC: Start");
+        }
+
+        self.mark_live();
+
+        if stats {
+            let duration = timer.stop();
+            self.phases.marking = duration;
+        }
+
+        if dev_verbose {
+            println!("Full GC: Phase 1 (marking)");
+        }
+
+        if self.vm.args.flag_gc_verify {
+            verify_marking(
+                self.young,
+                &*self.old_protected,
+                self.large_space,
+                self.heap,
+            );
+
+            if stats {
+                timer.stop();
+            }
+
+            if dev_verbose {
+                println!("Full GC: Phase 1b (verify marking)");
+            }
+        }
+
+        self.compute_forward();
+
+        if stats {
+            let duration = timer.stop();
+            self.phases.compute_forward = duration;
+        }
+
+        if dev_verbose {
+            println!("Full GC: Phase 2 (compute forward)");
+        }
+
+        self.update_references();
+
+        if stats {
+            let duration = timer.stop();
+            self.phases.update_refs = duration;
+        }
+
+        if dev_verbose {
+            println!("Full GC: Phase 3 (update refs)");
+        }
+
+        self.relocate();
+
+        if stats {
+            let duration = timer.stop();
+            self.phases.relocate = duration;
+        }
+
+        if dev_verbose {
+            println!("Full GC: Phase 4 (relocate)");
+        }
+
+        self.reset_cards();
+
+        if stats {
+            let duration = timer.stop();
+            self.phases.reset_cards = duration;
+        }
+
+        if dev_verbose {
+            println!("Full GC: Phase 5 (reset cards)");
+        }
+
+        self.young.clear();
+        self.young.protect_from();
+    }
+
+    fn mark_live(&mut self) {
+        let heap = self.heap;
+        let perm = self.perm_space.total();
+
+        marking::start(self.rootset, heap, perm);
+
+        // values of ephemerons are only marked when both ephemeron and key
+        // are live, this might make more ephemerons reachable.
+        loop {
+            let values = self
+                .weak_refs
+                .ephemeron_values(|object| forward_marked(&heap, object));
+
+            if values.is_empty() {
+                break;
+            }
+
+            marking::start(&values, heap, perm);
+        }
+
+        self.weak_refs
+            .process(|object| forward_marked(&heap, object));
+    }
+
+    fn compute_forward(&mut self) {
+        self.compute_live_bytes();
+        self.select_evacuated_blocks();
+
+        if !self.compute_destinations() {
+            // evacuation needs additional space until the next collection,
+            // try again with all old objects staying in place
+            self.evacuate.clear();
+
+            if !self.compute_destinations() {
+                // the OOM report reads the old generation
+                MutexGuard::unlocked(&mut self.old_protected, || stdlib::trap(Trap::OOM.int()));
+            }
+        }
+
+        let used_top = self.used_top();
+        self.old_protected.commit_single_region(used_top);
+    }
+
+    fn compute_live_bytes(&mut self) {
+        let old_start = self.old_total.start;
+        let live_bytes = &mut self.live_bytes;
+
+        for region in self.old_protected.regions.iter() {
+            walk_region(region.active_region(), |object, address, size| {
+                if object.header().is_marked_non_atomic() {
+                    live_bytes[block_idx(old_start, address)] += size;
+                }
+            });
+        }
+    }
+
+    fn select_evacuated_blocks(&mut self) {
+        for (block, &live) in self.live_bytes.iter().enumerate() {
+            if live > 0 && live * 100 < BLOCK_SIZE * EVACUATE_THRESHOLD {
+                self.evacuate.insert(block);
+            }
+        }
+    }
+
+    // Assigns a new address to every live object, objects outside of
+    // evacuated blocks keep their address. Returns false if the
+    // objects don't fit into the heap.
+    fn compute_destinations(&mut self) -> bool {
+        self.holes.clear();
+        self.moved.clear();
+        self.spans.clear();
+
+        let mut live_end = self.old_total.start;
+        let regions = self
+            .old_protected
+            .regions
+            .iter()
+            .map(|r| r.active_region())
+            .collect::<Vec<_>>();
+
+        for region in regions {
+            walk_region(region, |object, address, size| {
+                if !object.header().is_marked_non_atomic() {
+                    return;
+                }
+
+                self.add_holes(live_end, address);
+                live_end = address.offset(size);
+
+                if self.is_evacuated(address) {
+                    self.moved.push(address);
+                } else {
+                    object.header_mut().set_fwdptr_non_atomic(address);
+                    add_span(&mut self.spans, address, size);
+                }
+            });
+        }
+
+        self.overflow_top = self.add_holes(live_end, self.old_total.end);
+        self.next_hole = 0;
+        self.hole_top = self.old_total.start;
+        self.hole_limit = self.old_total.start;
+
+        for idx in 0..self.moved.len() {
+            let object = self.moved[idx].to_mut_obj();
+            let fwd = self.allocate(object.size());
+            object.header_mut().set_fwdptr_non_atomic(fwd);
+        }
+
+        let young_regions = [
+            self.young.eden_active(),
+            // from-space is only non-empty after a failed promotion
+            self.young.from_active(),
+            self.young.to_active(),
+        ];
+
+        for &region in &young_regions {
+            walk_region(region, |object, address, size| {
+                if object.header().is_marked_non_atomic() {
+                    let fwd = self.allocate(size);
+                    object.header_mut().set_fwdptr_non_atomic(fwd);
+                    self.moved.push(address);
+                }
+            });
+        }
+
+        self.fits_into_heap()
+    }
+
+    // Adds the free space between two live objects to the holes, parts in
+    // evacuated blocks are skipped. Returns the start of the last part.
+    fn add_holes(&mut self, start: Address, end: Address) -> Address {
+        let mut hole_start = start;
+        let mut block_start = block_start(start);
+
+        while block_start < end {
+            let block_end = block_start.offset(BLOCK_SIZE);
+
+            if self.is_evacuated(block_start) {
+                self.add_hole(hole_start, block_start);
+                hole_start = cmp::min(block_end, end);
+            }
+
+            block_start = block_end;
+        }
+
+        if end == self.old_total.end {
+            return cmp::max(hole_start, start);
+        }
+
+        self.add_hole(hole_start, end);
+        end
+    }
+
+    fn add_hole(&mut self, start: Address, end: Address) {
+        if start < end && is_hole(start, end) {
+            self.holes.push(Region::new(start, end));
+        }
+    }
+
+    fn is_evacuated(&self, address: Address) -> bool {
+        self.evacuate
+            .contains(block_idx(self.old_total.start, address))
+    }
+
+    fn allocate(&mut self, size: usize) -> Address {
+        loop {
+            if self.hole_top.offset(size) <= self.hole_limit {
+                let address = self.hole_top;
+                self.hole_top = address.offset(size);
+                add_span(&mut self.spans, address, size);
+                return address;
+            }
+
+            if size > MEDIUM_OBJECT_SIZE || self.next_hole == self.holes.len() {
+                break;
+            }
+
+            let hole = self.holes[self.next_hole];
+            self.hole_top = hole.start;
+            self.hole_limit = hole.end;
+            self.next_hole += 1;
+        }
+
+        let address = self.overflow_top;
+        self.overflow_top = address.offset(size);
+        add_span(&mut self.spans, address, size);
+        address
+    }
+
+    fn used_top(&self) -> Address {
+        self.spans
+            .iter()
+            .map(|span| span.end)
+            .max()
+            .unwrap_or(self.old_total.start)
+    }
+
+    fn fits_into_heap(&mut self) -> bool {
+        // young generation is sized afterwards, see `FullCollector::fits_into_heap`
+        let old_top = self.used_top();
+
+        if old_top > self.old_total.end {
+            return false;
+        }
+
+        let old_size = old_top.align_gen().offset_from(self.old_total.start);
+        let large_size = self.large_space.committed_size();
+
+        (old_size + large_size) <= self.max_heap_size
+    }
+
+    fn update_references(&mut self) {
+        let old_regions = self
+            .old_protected
+            .regions
+            .iter()
+            .map(|r| r.active_region())
+            .collect::<Vec<_>>();
+
+        let young_regions = [
+            self.young.eden_active(),
+            self.young.from_active(),
+            self.young.to_active(),
+        ];
+
+        for region in old_regions.into_iter().chain(young_regions.iter().cloned()) {
+            walk_region(region, |object, _address, _size| {
+                if object.header().is_marked_non_atomic() {
+                    object.visit_reference_fields(|field| {
+                        self.forward_reference(field);
+                    });
+                }
+            });
+        }
+
+        for root in self.rootset {
+            self.forward_reference(*root);
+        }
+
+        let weak_refs = self.weak_refs;
+        weak_refs.visit_weak_slots(|slot| self.forward_reference(slot));
+
+        self.large_space.remove_objects(|object_start| {
+            let object = object_start.to_mut_obj();
+
+            // reset cards for object, also do this for dead objects
+            // to reset card entries to clean.
+            if object.is_array_ref() {
+                let object_end = object_start.offset(object.size());
+                self.card_table.reset_region(object_start, object_end);
+            } else {
+                self.card_table.reset_addr(object_start);
+            }
+
+            if object.header().is_marked_non_atomic() {
+                object.visit_reference_fields(|field| {
+                    self.forward_reference(field);
+                });
+
+                // unmark object for next collection
+                object.header_mut().unmark_non_atomic();
+
+                // keep object
+                false
+            } else {
+                // object is unmarked -> free it
+                true
+            }
+        });
+    }
+
+    fn relocate(&mut self) {
+        // destinations are never occupied by live objects,
+        // objects can be copied in any order
+        for &address in &self.moved {
+            let object = address.to_mut_obj();
+            let dest = object.header().fwdptr_non_atomic();
+            object.copy_to(dest, object.size());
+        }
+
+        let regions = self.compute_old_regions();
+        self.old_protected.update_regions(regions);
+
+        self.crossing_map.set_first_object(0.into(), 0);
+
+        let old_regions = self
+            .old_protected
+            .regions
+            .iter()
+            .map(|r| r.active_region())
+            .collect::<Vec<_>>();
+
+        for region in old_regions {
+            walk_region(region, |object, address, size| {
+                // unmark object for next collection
+                object.header_mut().unmark_non_atomic();
+
+                self.old
+                    .update_crossing(address, address.offset(size), object.is_array_ref());
+            });
+        }
+    }
+
+    // Creates one region per hole: objects from the start of the region up
+    // to its top, the rest of the region is free. Free space too small
+    // for a hole is filled.
+    fn compute_old_regions(&mut self) -> Vec<OldGenRegion> {
+        let vm = self.vm;
+        let mut spans = std::mem::replace(&mut self.spans, Vec::new());
+        spans.sort_by_key(|span| span.start);
+
+        let mut used = vec![Region::new(self.old_total.start, self.old_total.start)];
+
+        for span in spans {
+            let last = used.last_mut().unwrap();
+
+            // promotion might have left a single word at the end of a
+            // region, which is too small for a filler object
+            let gap = span.start.offset_from(last.end);
+
+            if gap == mem::ptr_width_usize() || is_hole(last.end, span.start) {
+                used.push(span);
+            } else {
+                fill_region(vm, last.end, span.start);
+                last.end = span.end;
+            }
+        }
+
+        let mut regions = Vec::with_capacity(used.len());
+        let limit = used.last().unwrap().end.align_gen();
+
+        for (idx, span) in used.iter().enumerate() {
+            let mapping_start = if idx == 0 {
+                self.old_total.start
+            } else {
+                span.start.align_page()
+            };
+
+            if let Some(next) = used.get(idx + 1) {
+                let total = Region::new(span.start, next.start);
+                let mapping_end = next.start.align_page();
+                let total_mapping = Region::new(mapping_start, mapping_end);
+                regions.push(OldGenRegion::new(
+                    total,
+                    span.end,
+                    total_mapping,
+                    mapping_end,
+                ));
+            } else {
+                let total = Region::new(span.start, self.old_total.end);
+                let total_mapping = Region::new(mapping_start, self.old_total.end);
+                regions.push(OldGenRegion::new(total, span.end, total_mapping, limit));
+            }
+        }
+
+        regions
+    }
+
+    fn reset_cards(&mut self) {
+        let top = self.old_protected.regions.last().unwrap().top();
+        let end = cmp::max(top, self.init_old_top);
+        self.card_table.reset_region(self.old_total.start, end);
+    }
+
+    fn forward_reference(&mut self, slot: Slot) {
+        let object_addr = slot.get();
+
+        if self.heap.contains(object_addr) {
+            debug_assert!(object_addr.to_obj().header().is_marked_non_atomic());
+
+            if self.large_space.contains(object_addr) {
+                // large objects do not move in memory
+                return;
+            }
+
+            let fwd_addr = object_addr.to_obj().header().fwdptr_non_atomic();
+            debug_assert!(self.heap.contains(fwd_addr));
+            slot.set(fwd_addr);
+        } else {
+            debug_assert!(object_addr.is_null() || self.perm_space.contains(object_addr));
+        }
+    }
+}
+
+// free space is only reused if it contains at least one unmarked line
+fn is_hole(start: Address, end: Address) -> bool {
+    let first_free_line = align_line(start.to_usize());
+    first_free_line + LINE_SIZE <= end.to_usize()
+}
+
+fn add_span(spans: &mut Vec<Region>, address: Address, size: usize) {
+    if let Some(last) = spans.last_mut() {
+        if last.end == address {
+            last.end = address.offset(size);
+            return;
+        }
+    }
+
+    spans.push(address.region_start(size));
+}
+
+fn align_line(value: usize) -> usize {
+    (value + LINE_SIZE - 1) & !(LINE_SIZE - 1)
+}
+
+fn block_idx(old_start: Address, address: Address) -> usize {
+    address.offset_from(old_start) / BLOCK_SIZE
+}
+
+fn block_start(address: Address) -> Address {
+    (address.to_usize() & !(BLOCK_SIZE - 1)).into()
+}
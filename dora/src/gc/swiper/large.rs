@@ -65,15 +65,18 @@ impl LargeSpace {
     ) {
         let mut space = self.space.lock();
 
-        let old_head = space.head;
-        space.head = head;
+        // chain is empty when all objects were freed
+        if head.is_non_null() {
+            let old_head = space.head;
+            space.head = head;
 
-        if old_head.is_non_null() {
-            let old_head = LargeAlloc::from_address(old_head);
-            old_head.prev = tail;
+            if old_head.is_non_null() {
+                let old_head = LargeAlloc::from_address(old_head);
+                old_head.prev = tail;
 
-            let tail = LargeAlloc::from_address(tail);
-            tail.next = old_head.address();
+                let tail = LargeAlloc::from_address(tail);
+                tail.next = old_head.address();
+            }
         }
 
         space.committed_size -= freed;
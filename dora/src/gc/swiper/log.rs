@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::gc::swiper::controller::{FullCollectorPhases, MinorCollectorPhases};
+use crate::gc::swiper::large::LargeSpace;
+use crate::gc::swiper::young::YoungGen;
+use crate::gc::swiper::CommonOldGen;
+
+// Writes one JSON object per line and collection into the file given
+// with --gc-log. Sizes are in bytes, times in milliseconds.
+pub struct GcLog {
+    file: BufWriter<File>,
+}
+
+impl GcLog {
+    pub fn create(path: &str) -> io::Result<GcLog> {
+        let file = File::create(path)?;
+
+        Ok(GcLog {
+            file: BufWriter::new(file),
+        })
+    }
+
+    // flushes every record, the log should be complete even
+    // when the program crashes
+    pub fn write(&mut self, record: &str) {
+        self.file
+            .write_all(record.as_bytes())
+            .and_then(|_| self.file.write_all(b"\n"))
+            .and_then(|_| self.file.flush())
+            .expect("writing GC log failed");
+    }
+}
+
+pub fn minor_phases(phases: &MinorCollectorPhases) -> String {
+    format!(
+        ",\"phases\":{{\"roots\":{:.3},\"tracing\":{:.3}}},\"workers\":{}",
+        phases.roots,
+        phases.tracing,
+        workers(&phases.workers)
+    )
+}
+
+pub fn full_phases(phases: &FullCollectorPhases) -> String {
+    format!(
+        ",\"phases\":{{\"marking\":{:.3},\"compute_forward\":{:.3},\"update_refs\":{:.3},\"relocate\":{:.3},\"reset_cards\":{:.3}}},\"workers\":{}",
+        phases.marking,
+        phases.compute_forward,
+        phases.update_refs,
+        phases.relocate,
+        phases.reset_cards,
+        workers(&phases.workers)
+    )
+}
+
+fn workers(times: &[f32]) -> String {
+    let times: Vec<String> = times.iter().map(|time| format!("{:.3}", time)).collect();
+    format!("[{}]", times.join(","))
+}
+
+// used bytes of each space
+#[derive(Copy, Clone, Default)]
+pub struct SpaceSizes {
+    eden: usize,
+    survivor: usize,
+    old: usize,
+    large: usize,
+}
+
+impl SpaceSizes {
+    pub fn of(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> SpaceSizes {
+        SpaceSizes {
+            eden: young.eden_active().size(),
+            survivor: young.from_active().size() + young.to_active().size(),
+            old: old.active_size(),
+            large: large.committed_size(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"eden\":{},\"survivor\":{},\"old\":{},\"large\":{}}}",
+            self.eden, self.survivor, self.old, self.large
+        )
+    }
+}
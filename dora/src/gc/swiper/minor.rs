@@ -10,6 +10,7 @@ use crate::gc::swiper::old::{OldGen, OldGenProtected};
 use crate::gc::swiper::on_different_cards;
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{CardIdx, CARD_SIZE};
+use crate::gc::weak::WeakRefs;
 use crate::gc::{Address, GcReason, Region};
 use crate::object::{offset_of_array_data, Obj};
 use crate::timer::Timer;
@@ -26,12 +27,16 @@ pub struct MinorCollector<'a, 'ast: 'a> {
     crossing_map: &'a CrossingMap,
 
     rootset: &'a [Slot],
+    weak_refs: &'a WeakRefs,
     reason: GcReason,
 
     young_top: Address,
     young_limit: Address,
     init_old_top: Vec<Address>,
 
+    young_scan: Address,
+    old_scan: Vec<Address>,
+
     promotion_failed: bool,
     promoted_size: usize,
 
@@ -54,6 +59,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         card_table: &'a CardTable,
         crossing_map: &'a CrossingMap,
         rootset: &'a [Slot],
+        weak_refs: &'a WeakRefs,
         reason: GcReason,
         min_heap_size: usize,
         max_heap_size: usize,
@@ -66,6 +72,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
             old_protected: old.protected(),
             large,
             rootset,
+            weak_refs,
             card_table,
             crossing_map,
 
@@ -73,6 +80,9 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
             young_limit: Address::null(),
             init_old_top: Vec::new(),
 
+            young_scan: Address::null(),
+            old_scan: Vec::new(),
+
             promotion_failed: false,
             promoted_size: 0,
 
@@ -97,7 +107,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         self.init_old_top = self.old_protected.regions.iter().map(|r| r.top()).collect();
 
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let mut timer = Timer::new(self.vm.args.flag_gc_stats);
+        let mut timer = Timer::new(self.vm.args.gc_phases());
 
         self.young.unprotect_from();
         self.young.swap_semi();
@@ -105,6 +115,8 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         let to_committed = self.young.to_committed();
         self.young_top = to_committed.start;
         self.young_limit = to_committed.end;
+        self.young_scan = to_committed.start;
+        self.old_scan = self.init_old_top.clone();
 
         self.from_active = self.young.from_active();
         let init_young_size = self.young.eden_active().size() + self.young.from_active().size();
@@ -121,7 +133,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
 
         self.visit_dirty_cards();
 
-        if self.vm.args.flag_gc_stats {
+        if self.vm.args.gc_phases() {
             let duration = timer.stop();
             self.phases.roots = duration;
         }
@@ -131,8 +143,9 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         }
 
         self.trace_gray_objects();
+        self.trace_ephemeron_values();
 
-        if self.vm.args.flag_gc_stats {
+        if self.vm.args.gc_phases() {
             let duration = timer.stop();
             self.phases.tracing = duration;
         }
@@ -149,6 +162,14 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
             return true;
         }
 
+        if dev_verbose {
+            println!("Minor GC: Phase 4 (weak references)");
+        }
+
+        let (eden, from) = (self.eden_active, self.from_active);
+        self.weak_refs
+            .process(|object| forward_young(&eden, &from, object));
+
         self.young.minor_success(self.young_top);
 
         assert!(self.young.eden_active().empty());
@@ -240,8 +261,8 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
     }
 
     fn trace_gray_objects(&mut self) {
-        let mut young_scan = self.young.to_committed().start;
-        let mut old_scan = self.init_old_top.clone();
+        let mut young_scan = self.young_scan;
+        let mut old_scan = std::mem::replace(&mut self.old_scan, Vec::new());
 
         let mut work_done = true;
 
@@ -264,6 +285,30 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         }
 
         assert!(young_scan == self.young_top);
+
+        self.young_scan = young_scan;
+        self.old_scan = old_scan;
+    }
+
+    // values of ephemerons are only copied when both ephemeron and key
+    // survive, this might make more ephemerons reachable.
+    fn trace_ephemeron_values(&mut self) {
+        loop {
+            let (eden, from) = (self.eden_active, self.from_active);
+            let values = self
+                .weak_refs
+                .ephemeron_values(|object| forward_young(&eden, &from, object));
+
+            if values.is_empty() || self.promotion_failed {
+                break;
+            }
+
+            for value in values {
+                value.set(self.copy(value.get()));
+            }
+
+            self.trace_gray_objects();
+        }
     }
 
     fn trace_young_object(&mut self, addr: Address) -> Address {
@@ -595,3 +640,12 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         assert!(scan == region.end);
     }
 }
+
+// objects in eden and from-space are only alive when they were copied
+pub fn forward_young(eden: &Region, from: &Region, object: Address) -> Option<Address> {
+    if eden.contains(object) || from.contains(object) {
+        object.to_obj().header().vtblptr_forwarded()
+    } else {
+        Some(object)
+    }
+}
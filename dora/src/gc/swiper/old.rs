@@ -103,13 +103,18 @@ impl OldGenProtected {
     }
 
     pub fn contains_slow(&self, addr: Address) -> bool {
-        for old_region in &self.regions {
-            if old_region.total.start <= addr && addr < old_region.top {
-                return true;
-            }
-        }
+        // regions are sorted by address
+        let idx = match self
+            .regions
+            .binary_search_by(|region| region.total.start.cmp(&addr))
+        {
+            Ok(idx) => idx,
+            Err(0) => return false,
+            Err(idx) => idx - 1,
+        };
 
-        false
+        let old_region = &self.regions[idx];
+        old_region.total.start <= addr && addr < old_region.top
     }
 
     pub fn with_single_region(&mut self, top: Address) -> usize {
@@ -6,7 +6,7 @@ use crate::gc::pmarking;
 use crate::gc::root::Slot;
 use crate::gc::space::Space;
 use crate::gc::swiper::card::CardTable;
-use crate::gc::swiper::compact::verify_marking;
+use crate::gc::swiper::compact::{forward_marked, verify_marking};
 use crate::gc::swiper::controller::FullCollectorPhases;
 use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
 use crate::gc::swiper::large::{LargeAlloc, LargeSpace};
@@ -14,10 +14,11 @@ use crate::gc::swiper::old::{OldGen, OldGenProtected, OldGenRegion};
 use crate::gc::swiper::verify::verify_mapped_regions;
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{walk_region, walk_region_and_skip_garbage, CardIdx, CARD_REFS};
+use crate::gc::weak::WeakRefs;
 use crate::gc::{Address, GcReason, Region};
 use crate::os;
 use crate::stdlib;
-use crate::timer::Timer;
+use crate::timer::{Timer, WorkerTimes};
 use crate::vm::{Trap, VM};
 
 pub struct ParallelFullCollector<'a, 'ast: 'a> {
@@ -28,6 +29,7 @@ pub struct ParallelFullCollector<'a, 'ast: 'a> {
     old_protected: MutexGuard<'a, OldGenProtected>,
     large_space: &'a LargeSpace,
     rootset: &'a [Slot],
+    weak_refs: &'a WeakRefs,
     card_table: &'a CardTable,
     crossing_map: &'a CrossingMap,
     perm_space: &'a Space,
@@ -45,6 +47,7 @@ pub struct ParallelFullCollector<'a, 'ast: 'a> {
     regions: Vec<CollectRegion>,
 
     phases: FullCollectorPhases,
+    worker_times: WorkerTimes,
 }
 
 impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
@@ -58,6 +61,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
         crossing_map: &'a CrossingMap,
         perm_space: &'a Space,
         rootset: &'a [Slot],
+        weak_refs: &'a WeakRefs,
         reason: GcReason,
         number_workers: usize,
         min_heap_size: usize,
@@ -71,6 +75,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
             old_protected: old.protected(),
             large_space,
             rootset,
+            weak_refs,
             card_table,
             crossing_map,
             perm_space,
@@ -88,16 +93,19 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
             regions: Vec::new(),
 
             phases: FullCollectorPhases::new(),
+            worker_times: WorkerTimes::new(vm.args.gc_phases()),
         }
     }
 
     pub fn phases(&self) -> FullCollectorPhases {
-        self.phases.clone()
+        let mut phases = self.phases.clone();
+        phases.workers = self.worker_times.times();
+        phases
     }
 
     pub fn collect(&mut self, pool: &mut Pool) {
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phases();
 
         let mut timer = Timer::new(stats);
 
@@ -225,12 +233,27 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn mark_live(&mut self, pool: &mut Pool) {
-        pmarking::start(
-            self.rootset,
-            self.heap.clone(),
-            self.perm_space.total(),
-            pool,
-        );
+        let heap = self.heap;
+        let perm = self.perm_space.total();
+
+        pmarking::start(self.rootset, heap, perm, pool, &self.worker_times);
+
+        // values of ephemerons are only marked when both ephemeron and key
+        // are live, this might make more ephemerons reachable.
+        loop {
+            let values = self
+                .weak_refs
+                .ephemeron_values(|object| forward_marked(&heap, object));
+
+            if values.is_empty() {
+                break;
+            }
+
+            pmarking::start(&values, heap, perm, pool, &self.worker_times);
+        }
+
+        self.weak_refs
+            .process(|object| forward_marked(&heap, object));
     }
 
     fn compute_forward(&mut self, pool: &mut Pool) {
@@ -247,7 +270,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
         let regions: Vec<Region> = self.regions.iter().map(|r| r.mapping).collect();
 
         if !self.fits_into_heap(&regions) {
-            stdlib::trap(Trap::OOM.int());
+            self.out_of_memory();
         }
 
         self.compute_actual_forward(pool);
@@ -272,13 +295,17 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
         verify_mapped_regions(self.old.total(), &all_regions);
     }
 
+    // the OOM report needs the lock on the old generation
+    fn out_of_memory(&mut self) {
+        MutexGuard::unlocked(&mut self.old_protected, || stdlib::trap(Trap::OOM.int()));
+    }
+
     fn fits_into_heap(&mut self, regions: &[Region]) -> bool {
-        let (eden_size, semi_size) = self.young.committed_size();
-        let young_size = eden_size + semi_size;
+        // young generation is sized afterwards, see `FullCollector::fits_into_heap`
         let old_size: usize = regions.iter().map(|r| r.size()).sum();
         let large_size = self.large_space.committed_size();
 
-        (young_size + old_size + large_size) <= self.max_heap_size
+        (old_size + large_size) <= self.max_heap_size
     }
 
     fn check_units_disjoint(&self) {
@@ -388,18 +415,25 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn compute_live_bytes(&mut self, pool: &mut Pool) {
-        pool.scoped(|scoped| {
-            let vm = self.vm;
+        let vm = self.vm;
+        let worker_times = &self.worker_times;
+        let units = &mut self.units;
+        let young_units = &mut self.young_units;
 
-            for unit in &mut self.units {
+        pool.scoped(|scoped| {
+            for unit in units {
                 scoped.execute(move || {
-                    compute_live_bytes_in_unit(vm, unit);
+                    worker_times.measure(|| {
+                        compute_live_bytes_in_unit(vm, unit);
+                    })
                 });
             }
 
-            for unit in &mut self.young_units {
+            for unit in young_units {
                 scoped.execute(move || {
-                    compute_live_bytes_in_unit(vm, unit);
+                    worker_times.measure(|| {
+                        compute_live_bytes_in_unit(vm, unit);
+                    })
                 });
             }
         });
@@ -455,7 +489,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
 
         if let Some(last) = regions.last_mut() {
             if last.compact.end > self.old_total.end {
-                panic!("OOM");
+                self.out_of_memory();
             }
 
             last.span.end = self.old_total.end;
@@ -540,7 +574,8 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
             }
         }
 
-        panic!("OOM: no space for young found!");
+        // no region has enough space left
+        self.out_of_memory();
     }
 
     fn add_collect_region(
@@ -586,6 +621,8 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn compute_actual_forward(&mut self, pool: &mut Pool) {
+        let worker_times = &self.worker_times;
+
         pool.scoped(|scope| {
             let pfull = &self;
 
@@ -593,7 +630,9 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
                 let mut fwd = region.compact.start;
                 for unit in &self.units[region.start_idx..region.start_idx + region.units] {
                     scope.execute(move || {
-                        pfull.compute_forward_unit(unit, fwd);
+                        worker_times.measure(|| {
+                            pfull.compute_forward_unit(unit, fwd);
+                        })
                     });
                     fwd = fwd.offset(unit.live);
                 }
@@ -602,7 +641,9 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
                 let mut fwd = region.young_start();
                 for unit in &region.young_units {
                     scope.execute(move || {
-                        pfull.compute_forward_unit(unit, fwd);
+                        worker_times.measure(|| {
+                            pfull.compute_forward_unit(unit, fwd);
+                        })
                     });
                     fwd = fwd.offset(unit.live);
                 }
@@ -625,29 +666,39 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn update_references(&mut self, pool: &mut Pool) {
+        self.weak_refs
+            .visit_weak_slots(|slot| self.forward_reference(slot));
+
         let next_large = Mutex::new(Address::null());
         let next_large = &next_large;
+        let worker_times = &self.worker_times;
 
         pool.scoped(|scope| {
             let rootset = self.rootset;
             let pfull = &self;
 
             scope.execute(move || {
-                for root in rootset {
-                    pfull.forward_reference(*root);
-                }
+                worker_times.measure(|| {
+                    for root in rootset {
+                        pfull.forward_reference(*root);
+                    }
+                })
             });
 
             for unit in &self.units {
                 scope.execute(move || {
-                    pfull.update_references_unit(unit);
+                    worker_times.measure(|| {
+                        pfull.update_references_unit(unit);
+                    })
                 });
             }
 
             for region in &self.regions {
                 for unit in &region.young_units {
                     scope.execute(move || {
-                        pfull.update_references_unit(unit);
+                        worker_times.measure(|| {
+                            pfull.update_references_unit(unit);
+                        })
                     });
                 }
             }
@@ -666,63 +717,65 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
                 let large = self.large_space;
 
                 scope.execute(move || {
-                    let mut head = Address::null();
-                    let mut tail = Address::null();
-                    let mut free_regions = Vec::new();
-                    let mut freed = 0;
-
-                    let mut addr = next(next_large);
-
-                    while let Some(large_alloc) = addr {
-                        let large_alloc = LargeAlloc::from_address(large_alloc);
-                        let object_start = large_alloc.object_address();
-                        let object = object_start.to_mut_obj();
-
-                        // reset cards for object, also do this for dead objects
-                        // to reset card entries to clean.
-                        if object.is_array_ref() {
-                            let object_end = object_start.offset(object.size());
-                            card_table.reset_region(object_start, object_end);
-                        } else {
-                            card_table.reset_addr(object_start);
-                        }
+                    worker_times.measure(|| {
+                        let mut head = Address::null();
+                        let mut tail = Address::null();
+                        let mut free_regions = Vec::new();
+                        let mut freed = 0;
+
+                        let mut addr = next(next_large);
+
+                        while let Some(large_alloc) = addr {
+                            let large_alloc = LargeAlloc::from_address(large_alloc);
+                            let object_start = large_alloc.object_address();
+                            let object = object_start.to_mut_obj();
+
+                            // reset cards for object, also do this for dead objects
+                            // to reset card entries to clean.
+                            if object.is_array_ref() {
+                                let object_end = object_start.offset(object.size());
+                                card_table.reset_region(object_start, object_end);
+                            } else {
+                                card_table.reset_addr(object_start);
+                            }
 
-                        addr = next(next_large);
+                            addr = next(next_large);
 
-                        if object.header().is_marked_non_atomic() {
-                            object.visit_reference_fields(|field| {
-                                pfull.forward_reference(field);
-                            });
+                            if object.header().is_marked_non_atomic() {
+                                object.visit_reference_fields(|field| {
+                                    pfull.forward_reference(field);
+                                });
 
-                            if head.is_null() {
-                                head = large_alloc.address();
-                            }
+                                if head.is_null() {
+                                    head = large_alloc.address();
+                                }
 
-                            if tail.is_non_null() {
-                                let tail = LargeAlloc::from_address(tail);
-                                tail.next = large_alloc.address();
-                            }
+                                if tail.is_non_null() {
+                                    let tail = LargeAlloc::from_address(tail);
+                                    tail.next = large_alloc.address();
+                                }
 
-                            large_alloc.prev = tail;
-                            large_alloc.next = Address::null();
+                                large_alloc.prev = tail;
+                                large_alloc.next = Address::null();
 
-                            // unmark object for next collection
-                            object.header_mut().unmark_non_atomic();
+                                // unmark object for next collection
+                                object.header_mut().unmark_non_atomic();
 
-                            tail = large_alloc.address();
-                        } else {
-                            let free_start = large_alloc.address();
-                            let free_size = large_alloc.size;
+                                tail = large_alloc.address();
+                            } else {
+                                let free_start = large_alloc.address();
+                                let free_size = large_alloc.size;
 
-                            os::discard(free_start, free_size);
-                            free_regions.push(free_start.region_start(free_size));
-                            freed += free_size;
+                                os::discard(free_start, free_size);
+                                free_regions.push(free_start.region_start(free_size));
+                                freed += free_size;
+                            }
                         }
-                    }
 
-                    if head.is_non_null() {
-                        large.append_chain(head, tail, freed, free_regions);
-                    }
+                        if head.is_non_null() || freed > 0 {
+                            large.append_chain(head, tail, freed, free_regions);
+                        }
+                    })
                 });
             }
         });
@@ -759,42 +812,47 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn relocate_and_reset_cards(&mut self, pool: &mut Pool) {
+        let worker_times = &self.worker_times;
+
         pool.scoped(|scope| {
             for region in &self.regions {
                 let units = &self.units;
                 let pfull = &self;
 
                 scope.execute(move || {
-                    let units_in_region = &units[region.start_idx..region.start_idx + region.units];
+                    worker_times.measure(|| {
+                        let units_in_region =
+                            &units[region.start_idx..region.start_idx + region.units];
 
-                    if region.slide_start {
-                        for unit in units_in_region {
-                            pfull.relocate_unit(unit);
-                        }
-                    } else {
-                        for unit in units_in_region.iter().rev() {
-                            if unit.live == 0 {
-                                continue;
+                        if region.slide_start {
+                            for unit in units_in_region {
+                                pfull.relocate_unit(unit);
                             }
+                        } else {
+                            for unit in units_in_region.iter().rev() {
+                                if unit.live == 0 {
+                                    continue;
+                                }
 
-                            let mut objects = Vec::new();
+                                let mut objects = Vec::new();
 
-                            walk_region(unit.region, |object, address, _| {
-                                if object.header().is_marked_non_atomic() {
-                                    objects.push(address);
-                                }
-                            });
+                                walk_region(unit.region, |object, address, _| {
+                                    if object.header().is_marked_non_atomic() {
+                                        objects.push(address);
+                                    }
+                                });
 
-                            while let Some(object) = objects.pop() {
-                                let size = object.to_obj().size() as usize;
-                                pfull.relocate_object(object, size);
+                                while let Some(object) = objects.pop() {
+                                    let size = object.to_obj().size() as usize;
+                                    pfull.relocate_object(object, size);
+                                }
                             }
                         }
-                    }
 
-                    for unit in &region.young_units {
-                        pfull.relocate_unit(unit);
-                    }
+                        for unit in &region.young_units {
+                            pfull.relocate_unit(unit);
+                        }
+                    })
                 });
             }
 
@@ -809,7 +867,9 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
                 let card_table = self.card_table;
 
                 scope.execute(move || {
-                    card_table.reset_region(start, end);
+                    worker_times.measure(|| {
+                        card_table.reset_region(start, end);
+                    })
                 });
             }
         });
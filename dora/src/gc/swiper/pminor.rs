@@ -9,14 +9,16 @@ use crate::gc::swiper::card::{CardEntry, CardTable};
 use crate::gc::swiper::controller::{MinorCollectorPhases, SharedHeapConfig};
 use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
 use crate::gc::swiper::large::{LargeAlloc, LargeSpace};
+use crate::gc::swiper::minor::forward_young;
 use crate::gc::swiper::old::OldGen;
 use crate::gc::swiper::on_different_cards;
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{CardIdx, CARD_SIZE, LARGE_OBJECT_SIZE};
 use crate::gc::tlab::{TLAB_OBJECT_SIZE, TLAB_SIZE};
+use crate::gc::weak::WeakRefs;
 use crate::gc::{fill_region, Address, GcReason, Region};
 use crate::object::{offset_of_array_data, Obj};
-use crate::timer::Timer;
+use crate::timer::{Timer, WorkerTimes};
 use crate::vm::VM;
 use crate::vtable::VTable;
 
@@ -35,6 +37,7 @@ pub struct ParallelMinorCollector<'a, 'ast: 'a> {
     crossing_map: &'a CrossingMap,
 
     rootset: &'a [Slot],
+    weak_refs: &'a WeakRefs,
     reason: GcReason,
 
     young_top: Address,
@@ -55,6 +58,7 @@ pub struct ParallelMinorCollector<'a, 'ast: 'a> {
     worklist: Vec<Address>,
     config: &'a SharedHeapConfig,
     phases: MinorCollectorPhases,
+    worker_times: WorkerTimes,
 }
 
 impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
@@ -66,6 +70,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         card_table: &'a CardTable,
         crossing_map: &'a CrossingMap,
         rootset: &'a [Slot],
+        weak_refs: &'a WeakRefs,
         reason: GcReason,
         min_heap_size: usize,
         max_heap_size: usize,
@@ -78,6 +83,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
             old,
             large,
             rootset,
+            weak_refs,
             card_table,
             crossing_map,
 
@@ -102,11 +108,14 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
             worklist: Vec::new(),
             config,
             phases: MinorCollectorPhases::new(),
+            worker_times: WorkerTimes::new(vm.args.gc_phases()),
         }
     }
 
     pub fn phases(&self) -> MinorCollectorPhases {
-        self.phases.clone()
+        let mut phases = self.phases.clone();
+        phases.workers = self.worker_times.times();
+        phases
     }
 
     pub fn collect(&mut self) -> bool {
@@ -123,6 +132,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         self.young_limit = to_committed.end;
 
         self.from_active = self.young.from_active();
+        let init_young_size = self.eden_active.size() + self.from_active.size();
 
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
 
@@ -130,7 +140,9 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
             println!("Minor GC: Worker threads started");
         }
 
-        self.run_threads();
+        let rootset = self.rootset;
+        self.run_threads(rootset, true);
+        self.trace_ephemeron_values();
 
         if dev_verbose {
             println!("Minor GC: Worker threads finished");
@@ -144,6 +156,10 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
             return true;
         }
 
+        let (eden, from) = (self.eden_active, self.from_active);
+        self.weak_refs
+            .process(|object| forward_young(&eden, &from, object));
+
         self.young.minor_success(self.young_top);
 
         assert!(self.young.eden_active().empty());
@@ -151,17 +167,37 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
 
         let mut config = self.config.lock();
         config.minor_promoted = self.promoted_size;
-        config.minor_copied = self.young.from_active().size();
+        let copied_size = self.young.to_active().size();
+        config.minor_copied = copied_size;
+        // to-space includes unused rests of the workers' allocation buffers
+        config.minor_dead = init_young_size.saturating_sub(self.promoted_size + copied_size);
 
         self.promotion_failed
     }
 
-    fn run_threads(&mut self) {
+    // values of ephemerons are only copied when both ephemeron and key
+    // survive, each round uses these values as roots.
+    fn trace_ephemeron_values(&mut self) {
+        loop {
+            let (eden, from) = (self.eden_active, self.from_active);
+            let values = self
+                .weak_refs
+                .ephemeron_values(|object| forward_young(&eden, &from, object));
+
+            if values.is_empty() || self.promotion_failed {
+                break;
+            }
+
+            self.run_threads(&values, false);
+        }
+    }
+
+    fn run_threads(&mut self, rootset: &[Slot], visit_cards: bool) {
         let mut workers = Vec::with_capacity(self.number_workers);
         let mut stealers = Vec::with_capacity(self.number_workers);
         let injector = Injector::new();
 
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phases();
         let timer = Timer::new(stats);
 
         for _ in 0..self.number_workers {
@@ -191,7 +227,6 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         let young = self.young;
         let old = self.old;
         let large = self.large;
-        let rootset = self.rootset;
         let init_old_top = &self.init_old_top;
         let old_region_start = {
             let protected = self.old.protected();
@@ -214,11 +249,17 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         let next_root_stride = AtomicUsize::new(0);
         let next_root_stride = &next_root_stride;
 
-        let next_card_stride = AtomicUsize::new(0);
-        let next_card_stride = &next_card_stride;
         let strides = 4 * self.number_workers;
 
-        let head = self.large.head();
+        // dirty cards and large objects are only visited once
+        let next_card_stride = AtomicUsize::new(if visit_cards { 0 } else { strides });
+        let next_card_stride = &next_card_stride;
+
+        let head = if visit_cards {
+            self.large.head()
+        } else {
+            Address::null()
+        };
         let next_large = Mutex::new(head);
         let next_large = &next_large;
 
@@ -228,6 +269,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
             None
         };
         let prot_timer = &prot_timer;
+        let worker_times = &self.worker_times;
 
         self.threadpool.scoped(|scoped| {
             for (task_id, worker) in workers.into_iter().enumerate() {
@@ -283,7 +325,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
                         timer: prot_timer,
                     };
 
-                    task.run();
+                    worker_times.measure(|| task.run());
 
                     if task.promoted_size > 0 {
                         promoted_size.fetch_add(task.promoted_size, Ordering::SeqCst);
@@ -299,8 +341,8 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         if let Some(ref mutex) = prot_timer {
             let mut mutex = mutex.lock();
             let (ref mut timer, duration_roots) = *mutex;
-            self.phases.roots = duration_roots;
-            self.phases.tracing = timer.stop();
+            self.phases.roots += duration_roots;
+            self.phases.tracing += timer.stop();
         }
 
         self.young_top = *young_top.lock();
@@ -200,6 +200,10 @@ impl Collector for SweepSwiper {
         self.card_table_offset
     }
 
+    fn dump_heap_usage(&self) {
+        controller::print_heap_usage(&self.config, &self.young, &self.old, &self.large);
+    }
+
     fn dump_summary(&self, _runtime: f32) {
         unimplemented!()
     }
@@ -222,7 +226,14 @@ impl SweepSwiper {
         mut reason: GcReason,
     ) -> CollectionKind {
         safepoint::stop_the_world(vm, |threads| {
-            controller::start(&self.config, &self.young, &self.old, &self.large);
+            controller::start(
+                &self.config,
+                kind,
+                reason,
+                &self.young,
+                &self.old,
+                &self.large,
+            );
 
             tlab::make_iterable_all(vm, threads);
             let rootset = get_rootset(vm, threads);
@@ -1,3 +1,4 @@
+use std::cmp::min;
 use std::sync::Arc;
 
 use crate::gc::{fill_region, Address, Region, K};
@@ -25,9 +26,9 @@ pub fn allocate(size: usize) -> Option<Address> {
         let tlab = thread.tld.tlab_region();
 
         if size <= tlab.size() {
-            thread
-                .tld
-                .tlab_initialize(tlab.start.offset(size), tlab.end);
+            let limit = thread.tld.tlab_limit();
+            thread.tld.tlab_initialize(tlab.start.offset(size), limit);
+            thread.tld.tlab_set_end(tlab.end);
             Some(tlab.start)
         } else {
             None
@@ -35,10 +36,39 @@ pub fn allocate(size: usize) -> Option<Address> {
     })
 }
 
+// lowers the end of the TLAB to at most `size` bytes after its top, allocations
+// crossing it take the slow path. Returns the bytes available before the new end.
+pub fn lower_end(size: usize) -> usize {
+    THREAD.with(|thread| {
+        let thread = thread.borrow();
+        let tlab = thread.tld.tlab_region();
+        let limit = thread.tld.tlab_limit();
+
+        let available = limit.offset_from(tlab.start);
+        let size = min(size, available);
+        thread.tld.tlab_set_end(tlab.start.offset(size));
+
+        size
+    })
+}
+
+// resets the end of the TLAB to its limit, returns the bytes that were left
+// before the lowered end
+pub fn restore_end() -> usize {
+    THREAD.with(|thread| {
+        let thread = thread.borrow();
+        let tlab = thread.tld.tlab_region();
+        thread.tld.tlab_set_end(thread.tld.tlab_limit());
+
+        tlab.size()
+    })
+}
+
 pub fn make_iterable_all(vm: &VM, threads: &[Arc<DoraThread>]) {
     for thread in threads {
         let tlab = thread.tld.tlab_region();
-        fill_region(vm, tlab.start, tlab.end);
+        vm.gc.discard_tlab_window(thread.id(), tlab.size());
+        fill_region(vm, tlab.start, thread.tld.tlab_limit());
 
         let n = Address::null();
         thread.tld.tlab_initialize(n, n);
@@ -50,7 +80,7 @@ pub fn make_iterable_current(vm: &VM) {
         let thread = thread.borrow();
         let tlab = thread.tld.tlab_region();
 
-        fill_region(vm, tlab.start, tlab.end);
+        fill_region(vm, tlab.start, thread.tld.tlab_limit());
 
         let n = Address::null();
         thread.tld.tlab_initialize(n, n);
@@ -0,0 +1,241 @@
+use parking_lot::Mutex;
+
+use crate::gc::root::Slot;
+use crate::gc::Address;
+
+// Weak references are instances of WeakRef and Ephemeron. Their reference
+// fields are not visited by the collector, instead every instance is
+// registered here and processed after all live objects were found: fields
+// referencing dead objects are cleared, all others updated.
+pub struct WeakRefs {
+    entries: Mutex<Vec<WeakEntry>>,
+    cleanups: Mutex<Vec<CleanupEntry>>,
+}
+
+#[derive(Copy, Clone)]
+pub enum WeakKind {
+    // referent is cleared as soon as it is dead
+    WeakRef { referent: i32 },
+
+    // value is only kept alive as long as both ephemeron and key are alive
+    Ephemeron { key: i32, value: i32 },
+}
+
+struct WeakEntry {
+    holder: Address,
+    kind: WeakKind,
+}
+
+struct CleanupEntry {
+    // weak reference, null as soon as the object is dead
+    object: Address,
+
+    // strong reference to the Cleanup instance
+    cleanup: Address,
+}
+
+impl WeakRefs {
+    pub fn new() -> WeakRefs {
+        WeakRefs {
+            entries: Mutex::new(Vec::new()),
+            cleanups: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, holder: Address, kind: WeakKind) {
+        let mut entries = self.entries.lock();
+        entries.push(WeakEntry { holder, kind });
+    }
+
+    pub fn register_cleanup(&self, object: Address, cleanup: Address) {
+        let mut cleanups = self.cleanups.lock();
+        cleanups.push(CleanupEntry { object, cleanup });
+    }
+
+    // returns the next cleanup whose object is dead or null if there is none
+    pub fn next_cleanup(&self) -> Address {
+        let mut cleanups = self.cleanups.lock();
+
+        if let Some(idx) = cleanups.iter().position(|c| c.object.is_null()) {
+            cleanups.remove(idx).cleanup
+        } else {
+            Address::null()
+        }
+    }
+
+    // Cleanups are roots. Entries are only removed by the mutator, so the
+    // slots stay valid for the whole collection.
+    pub fn visit_roots<F>(&self, mut f: F)
+    where
+        F: FnMut(Slot),
+    {
+        let mut cleanups = self.cleanups.lock();
+
+        for entry in cleanups.iter_mut() {
+            f(slot_of(&mut entry.cleanup));
+        }
+    }
+
+    // Returns values of ephemerons that need to be traced: both ephemeron
+    // and key are alive but the value wasn't reached yet. Collectors trace
+    // these values and repeat until there are none left.
+    //
+    // `forward` returns the current address of a live object or None if the
+    // object wasn't reached (yet).
+    pub fn ephemeron_values<F>(&self, mut forward: F) -> Vec<Slot>
+    where
+        F: FnMut(Address) -> Option<Address>,
+    {
+        let entries = self.entries.lock();
+        let mut values = Vec::new();
+
+        for entry in entries.iter() {
+            if let WeakKind::Ephemeron { key, value } = entry.kind {
+                let holder = match forward(entry.holder) {
+                    Some(holder) => holder,
+                    None => continue,
+                };
+
+                let key = Slot::at(holder.offset(key as usize)).get();
+
+                if key.is_null() || forward(key).is_none() {
+                    continue;
+                }
+
+                let value = Slot::at(holder.offset(value as usize));
+
+                if value.get().is_non_null() && forward(value.get()).is_none() {
+                    values.push(value);
+                }
+            }
+        }
+
+        values
+    }
+
+    // Clears all weak references to dead objects and updates all others.
+    // Entries of dead holders are removed. Fields are read at the current
+    // address of the holder, so objects need to be copied already.
+    pub fn process<F>(&self, mut forward: F)
+    where
+        F: FnMut(Address) -> Option<Address>,
+    {
+        let mut entries = self.entries.lock();
+        let mut live = Vec::with_capacity(entries.len());
+
+        for entry in entries.drain(..) {
+            let holder = match forward(entry.holder) {
+                Some(holder) => holder,
+                None => continue,
+            };
+
+            match entry.kind {
+                WeakKind::WeakRef { referent } => {
+                    forward_field(Slot::at(holder.offset(referent as usize)), &mut forward);
+                }
+
+                WeakKind::Ephemeron { key, value } => {
+                    let key = Slot::at(holder.offset(key as usize));
+                    let value = Slot::at(holder.offset(value as usize));
+
+                    if forward_field(key, &mut forward) {
+                        forward_field(value, &mut forward);
+                    } else {
+                        value.set(Address::null());
+                    }
+                }
+            }
+
+            live.push(WeakEntry {
+                holder,
+                kind: entry.kind,
+            });
+        }
+
+        *entries = live;
+
+        let mut cleanups = self.cleanups.lock();
+
+        for entry in cleanups.iter_mut() {
+            if entry.object.is_non_null() {
+                entry.object = forward(entry.object).unwrap_or_else(Address::null);
+            }
+        }
+    }
+
+    // Visits all weak slots for compacting collectors, which update them
+    // before objects are relocated. Only contains live objects after
+    // `process`.
+    pub fn visit_weak_slots<F>(&self, mut f: F)
+    where
+        F: FnMut(Slot),
+    {
+        let mut entries = self.entries.lock();
+
+        for entry in entries.iter_mut() {
+            let holder = entry.holder;
+
+            match entry.kind {
+                WeakKind::WeakRef { referent } => {
+                    visit_field(Slot::at(holder.offset(referent as usize)), &mut f);
+                }
+
+                WeakKind::Ephemeron { key, value } => {
+                    visit_field(Slot::at(holder.offset(key as usize)), &mut f);
+                    visit_field(Slot::at(holder.offset(value as usize)), &mut f);
+                }
+            }
+
+            f(slot_of(&mut entry.holder));
+        }
+
+        let mut cleanups = self.cleanups.lock();
+
+        for entry in cleanups.iter_mut() {
+            visit_field(slot_of(&mut entry.object), &mut f);
+        }
+    }
+}
+
+impl Default for WeakRefs {
+    fn default() -> WeakRefs {
+        WeakRefs::new()
+    }
+}
+
+fn slot_of(address: &mut Address) -> Slot {
+    Slot::at(Address::from_ptr(address as *mut Address))
+}
+
+// returns true if the field still references an object
+fn forward_field<F>(slot: Slot, forward: &mut F) -> bool
+where
+    F: FnMut(Address) -> Option<Address>,
+{
+    let object = slot.get();
+
+    if object.is_null() {
+        return false;
+    }
+
+    match forward(object) {
+        Some(object) => {
+            slot.set(object);
+            true
+        }
+
+        None => {
+            slot.set(Address::null());
+            false
+        }
+    }
+}
+
+fn visit_field<F>(slot: Slot, f: &mut F)
+where
+    F: FnMut(Slot),
+{
+    if slot.get().is_non_null() {
+        f(slot);
+    }
+}
@@ -35,6 +35,7 @@ mod masm;
 mod mem;
 mod object;
 mod os;
+mod profiler;
 mod safepoint;
 mod semck;
 mod size;
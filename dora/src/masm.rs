@@ -5,15 +5,15 @@ use std::rc::Rc;
 use crate::asm::Assembler;
 use crate::compiler::codegen::AnyReg;
 use crate::compiler::fct::{
-    Code, Comments, GcPoint, GcPoints, JitDescriptor, LazyCompilationData, LazyCompilationSite,
-    PositionTable,
+    Code, Comments, ExHandler, GcPoint, GcPoints, JitDescriptor, LazyCompilationData,
+    LazyCompilationSite, PositionTable,
 };
 use crate::cpu::{Mem, Reg, SCRATCH};
 use crate::dseg::DSeg;
 use crate::mem;
 use crate::object::Header;
 use crate::ty::MachineMode;
-use crate::vm::{Trap, VM};
+use crate::vm::{ClassDefId, Trap, VM};
 use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use dora_parser::lexer::position::Position;
 
@@ -39,6 +39,7 @@ pub struct MacroAssembler {
     gcpoints: GcPoints,
     comments: Comments,
     positions: PositionTable,
+    exception_handlers: Vec<ExHandler>,
     scratch_registers: ScratchRegisters,
 }
 
@@ -54,6 +55,7 @@ impl MacroAssembler {
             gcpoints: GcPoints::new(),
             comments: Comments::new(),
             positions: PositionTable::new(),
+            exception_handlers: Vec::new(),
             scratch_registers: ScratchRegisters::new(),
         }
     }
@@ -74,6 +76,7 @@ impl MacroAssembler {
             stacksize,
             self.comments,
             self.positions,
+            self.exception_handlers,
             desc,
         )
     }
@@ -153,6 +156,23 @@ impl MacroAssembler {
         self.gcpoints.insert(0, gcpoint);
     }
 
+    pub fn emit_exception_handler(
+        &mut self,
+        try_start: usize,
+        try_end: usize,
+        catch: usize,
+        offset: i32,
+        catch_type: Option<ClassDefId>,
+    ) {
+        self.exception_handlers.push(ExHandler {
+            try_start,
+            try_end,
+            catch,
+            offset,
+            catch_type,
+        });
+    }
+
     pub fn emit_lazy_compilation_site(&mut self, info: LazyCompilationSite) {
         let pos = self.pos() as u32;
         self.lazy_compilation.insert(pos, info);
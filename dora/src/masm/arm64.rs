@@ -13,7 +13,7 @@ use crate::mem::ptr_width;
 use crate::object::{offset_of_array_data, offset_of_array_length, Header};
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
-use crate::vm::{get_vm, FctId, Trap};
+use crate::vm::{get_vm, FctId, TraitId, Trap};
 use crate::vtable::VTable;
 
 impl MacroAssembler {
@@ -216,6 +216,55 @@ impl MacroAssembler {
         ));
     }
 
+    pub fn trait_call(
+        &mut self,
+        pos: Position,
+        trait_id: TraitId,
+        method_index: u32,
+        self_index: u32,
+    ) {
+        let obj = REG_PARAMS[self_index as usize];
+        self.test_if_nil_bailout(pos, obj, Trap::NIL);
+
+        // need to use scratch register instead of REG_RESULT for calculations
+        // since REG_RESULT (x0) is also the first parameter
+        let scratch = self.get_scratch();
+
+        // scratch = [obj] (load vtable)
+        self.load_mem(MachineMode::Ptr, (*scratch).into(), Mem::Base(obj, 0));
+
+        // scratch = vtable.itables
+        self.load_mem(
+            MachineMode::Ptr,
+            scratch.reg().into(),
+            Mem::Base(*scratch, VTable::offset_of_itables()),
+        );
+
+        // load itable of trait into scratch
+        let disp = (trait_id.to_usize() as i32) * ptr_width();
+        self.load_mem(
+            MachineMode::Ptr,
+            scratch.reg().into(),
+            Mem::Base(*scratch, disp),
+        );
+
+        // load itable entry into scratch
+        let disp = (method_index as i32) * ptr_width();
+        self.load_mem(
+            MachineMode::Ptr,
+            scratch.reg().into(),
+            Mem::Base(*scratch, disp),
+        );
+
+        // call *scratch
+        self.emit_u32(asm::blr(*scratch));
+        self.emit_lazy_compilation_site(LazyCompilationSite::TraitCompile(
+            self_index == 0,
+            trait_id,
+            method_index,
+        ));
+    }
+
     pub fn load_array_elem(&mut self, mode: MachineMode, dest: AnyReg, array: Reg, index: Reg) {
         self.load_mem(
             mode,
@@ -13,7 +13,7 @@ use crate::mem::{fits_i32, ptr_width};
 use crate::object::{offset_of_array_data, offset_of_array_length, Header};
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
-use crate::vm::{get_vm, FctId, Trap};
+use crate::vm::{get_vm, FctId, TraitId, Trap};
 use crate::vtable::VTable;
 
 impl MacroAssembler {
@@ -150,6 +150,51 @@ impl MacroAssembler {
         ));
     }
 
+    pub fn trait_call(
+        &mut self,
+        pos: Position,
+        trait_id: TraitId,
+        method_index: u32,
+        self_index: u32,
+    ) {
+        let obj = REG_PARAMS[self_index as usize];
+        self.test_if_nil_bailout(pos, obj, Trap::NIL);
+
+        // REG_RESULT = [obj] (load vtable)
+        self.load_mem(MachineMode::Ptr, REG_RESULT.into(), Mem::Base(obj, 0));
+
+        // REG_RESULT = vtable.itables
+        self.load_mem(
+            MachineMode::Ptr,
+            REG_RESULT.into(),
+            Mem::Base(REG_RESULT, VTable::offset_of_itables()),
+        );
+
+        // load itable of trait
+        let disp = (trait_id.to_usize() as i32) * ptr_width();
+        self.load_mem(
+            MachineMode::Ptr,
+            REG_RESULT.into(),
+            Mem::Base(REG_RESULT, disp),
+        );
+
+        // load itable entry
+        let disp = (method_index as i32) * ptr_width();
+        self.load_mem(
+            MachineMode::Ptr,
+            REG_RESULT.into(),
+            Mem::Base(REG_RESULT, disp),
+        );
+
+        // call *REG_RESULT
+        self.call_reg(REG_RESULT);
+        self.emit_lazy_compilation_site(LazyCompilationSite::TraitCompile(
+            self_index == 0,
+            trait_id,
+            method_index,
+        ));
+    }
+
     pub fn load_array_elem(&mut self, mode: MachineMode, dest: AnyReg, array: Reg, index: Reg) {
         self.load_mem(
             mode,
@@ -841,7 +841,7 @@ pub fn write_ref(vm: &VM, obj: Ref<Obj>, cls_id: ClassDefId, fid: FieldId, value
     let cls_def = cls_def.read();
     let field = &cls_def.fields[fid.idx()];
     let slot = obj.address().offset(field.offset as usize);
-    assert!(field.ty.reference_type());
+    assert!(field.ty.reference_type(vm));
 
     unsafe {
         *slot.to_mut_ptr::<Address>() = value.address();
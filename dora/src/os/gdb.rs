@@ -0,0 +1,573 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::env;
+use std::ptr;
+
+use crate::compiler::Code;
+use crate::vm::{Fct, VM};
+
+// Implements the JIT compilation interface of GDB: every function is
+// described by an in-memory ELF object with a symbol and DWARF line table
+// for the generated code. Objects are linked into the list of
+// `__jit_debug_descriptor`, GDB sets a breakpoint in
+// `__jit_debug_register_code` and reads the new object when it is hit.
+
+const JIT_REGISTER_FN: u32 = 1;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+#[no_mangle]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: 0,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // keeps calls to this function from being optimized away
+    unsafe {
+        ptr::read_volatile(&__jit_debug_descriptor.action_flag);
+    }
+}
+
+lazy_static! {
+    // serializes modifications of the descriptor
+    static ref REGISTER: Mutex<()> = Mutex::new(());
+}
+
+pub fn register_with_gdb(code: &Code, vm: &VM, fct: &Fct) {
+    let start = code.instruction_start().to_usize() as u64;
+    let size = code.instruction_end().offset_from(code.instruction_start()) as u64;
+
+    let lines = line_rows(code, fct);
+    let comp_dir = env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let object = build_object(
+        &fct.full_name(vm),
+        &vm.file(fct.file).name,
+        &comp_dir,
+        start,
+        size,
+        &lines,
+    );
+
+    // generated code is never freed, neither is its object
+    let object = Box::leak(object.into_boxed_slice());
+
+    let _lock = REGISTER.lock();
+
+    unsafe {
+        let first = __jit_debug_descriptor.first_entry;
+
+        let entry = Box::leak(Box::new(JitCodeEntry {
+            next_entry: first,
+            prev_entry: ptr::null_mut(),
+            symfile_addr: object.as_ptr(),
+            symfile_size: object.len() as u64,
+        }));
+
+        if !first.is_null() {
+            (*first).prev_entry = entry;
+        }
+
+        __jit_debug_descriptor.first_entry = entry;
+        __jit_debug_descriptor.relevant_entry = entry;
+        __jit_debug_descriptor.action_flag = JIT_REGISTER_FN;
+
+        __jit_debug_register_code();
+    }
+}
+
+// Positions are recorded for return addresses of calls. A row covers the
+// code from the previous position up to and including the call, so that
+// the return address of the caller's frame minus one finds its line.
+fn line_rows(code: &Code, fct: &Fct) -> Vec<(u64, u32)> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+
+    for &(offset, position) in code.positions().entries() {
+        rows.push((start, position.line));
+        start = offset as u64;
+    }
+
+    if rows.is_empty() {
+        rows.push((0, fct.ast.pos.line));
+    }
+
+    rows
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+#[cfg(target_arch = "x86_64")]
+const EM_MACHINE: u16 = 62;
+
+#[cfg(target_arch = "aarch64")]
+const EM_MACHINE: u16 = 183;
+
+const ELF_HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    data: Vec<u8>,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Section {
+    fn new(name: u32, kind: u32, data: Vec<u8>) -> Section {
+        Section {
+            name,
+            kind,
+            flags: 0,
+            addr: 0,
+            size: data.len() as u64,
+            data,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        }
+    }
+}
+
+struct StringTable {
+    data: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        StringTable { data: vec![0] }
+    }
+
+    fn add(&mut self, value: &str) -> u32 {
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+
+        offset
+    }
+}
+
+// builds a relocatable ELF object for a single function at `start`, its
+// sections already carry their final addresses
+fn build_object(
+    name: &str,
+    file: &str,
+    comp_dir: &str,
+    start: u64,
+    size: u64,
+    lines: &[(u64, u32)],
+) -> Vec<u8> {
+    let mut shstrtab = StringTable::new();
+    let mut strtab = StringTable::new();
+
+    let mut text = Section::new(shstrtab.add(".text"), SHT_NOBITS, Vec::new());
+    text.flags = SHF_ALLOC | SHF_EXECINSTR;
+    text.addr = start;
+    text.size = size;
+    text.align = 16;
+
+    let shstrtab_name = shstrtab.add(".shstrtab");
+    let symtab_name = shstrtab.add(".symtab");
+    let strtab_name = shstrtab.add(".strtab");
+    let abbrev_name = shstrtab.add(".debug_abbrev");
+    let info_name = shstrtab.add(".debug_info");
+    let line_name = shstrtab.add(".debug_line");
+
+    let mut symbols = vec![0; SYMBOL_SIZE];
+    symbols.write_u32::<LittleEndian>(strtab.add(name)).unwrap();
+    // STB_GLOBAL and STT_FUNC
+    symbols.write_u8(0x12).unwrap();
+    symbols.write_u8(0).unwrap();
+    // defined in .text
+    symbols.write_u16::<LittleEndian>(1).unwrap();
+    symbols.write_u64::<LittleEndian>(start).unwrap();
+    symbols.write_u64::<LittleEndian>(size).unwrap();
+
+    let mut symtab = Section::new(symtab_name, SHT_SYMTAB, symbols);
+    // .strtab follows and all symbols after the null symbol are global
+    symtab.link = 4;
+    symtab.info = 1;
+    symtab.align = 8;
+    symtab.entsize = SYMBOL_SIZE as u64;
+
+    let strtab = Section::new(strtab_name, SHT_STRTAB, strtab.data);
+    let abbrev = Section::new(abbrev_name, SHT_PROGBITS, debug_abbrev());
+    let info = Section::new(
+        info_name,
+        SHT_PROGBITS,
+        debug_info(name, file, comp_dir, start, size),
+    );
+    let line = Section::new(
+        line_name,
+        SHT_PROGBITS,
+        debug_line(file, start, size, lines),
+    );
+
+    let shstrtab = Section::new(shstrtab_name, SHT_STRTAB, shstrtab.data);
+
+    let null = Section::new(0, 0, Vec::new());
+    let sections = vec![null, text, shstrtab, symtab, strtab, abbrev, info, line];
+
+    let mut object = vec![0; ELF_HEADER_SIZE];
+    let mut offsets = Vec::new();
+
+    for section in &sections {
+        if section.data.is_empty() {
+            offsets.push(0);
+            continue;
+        }
+
+        align(&mut object, 8);
+        offsets.push(object.len() as u64);
+        object.extend_from_slice(&section.data);
+    }
+
+    align(&mut object, 8);
+    let shoff = object.len() as u64;
+
+    for (section, &offset) in sections.iter().zip(&offsets) {
+        object.write_u32::<LittleEndian>(section.name).unwrap();
+        object.write_u32::<LittleEndian>(section.kind).unwrap();
+        object.write_u64::<LittleEndian>(section.flags).unwrap();
+        object.write_u64::<LittleEndian>(section.addr).unwrap();
+        object.write_u64::<LittleEndian>(offset).unwrap();
+        object.write_u64::<LittleEndian>(section.size).unwrap();
+        object.write_u32::<LittleEndian>(section.link).unwrap();
+        object.write_u32::<LittleEndian>(section.info).unwrap();
+        object.write_u64::<LittleEndian>(section.align).unwrap();
+        object.write_u64::<LittleEndian>(section.entsize).unwrap();
+    }
+
+    let mut header = Vec::with_capacity(ELF_HEADER_SIZE);
+    // magic, 64 bit, little endian, version 1
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    header.resize(16, 0);
+    // ET_REL
+    header.write_u16::<LittleEndian>(1).unwrap();
+    header.write_u16::<LittleEndian>(EM_MACHINE).unwrap();
+    header.write_u32::<LittleEndian>(1).unwrap();
+    // no entry point and program headers
+    header.write_u64::<LittleEndian>(0).unwrap();
+    header.write_u64::<LittleEndian>(0).unwrap();
+    header.write_u64::<LittleEndian>(shoff).unwrap();
+    header.write_u32::<LittleEndian>(0).unwrap();
+    header
+        .write_u16::<LittleEndian>(ELF_HEADER_SIZE as u16)
+        .unwrap();
+    header.write_u16::<LittleEndian>(0).unwrap();
+    header.write_u16::<LittleEndian>(0).unwrap();
+    header
+        .write_u16::<LittleEndian>(SECTION_HEADER_SIZE as u16)
+        .unwrap();
+    header
+        .write_u16::<LittleEndian>(sections.len() as u16)
+        .unwrap();
+    // index of .shstrtab
+    header.write_u16::<LittleEndian>(2).unwrap();
+
+    object[..ELF_HEADER_SIZE].copy_from_slice(&header);
+    object
+}
+
+const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_STMT_LIST: u64 = 0x10;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_HIGH_PC: u64 = 0x12;
+const DW_AT_COMP_DIR: u64 = 0x1b;
+
+const DW_FORM_ADDR: u64 = 0x01;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_STRING: u64 = 0x08;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+// abbreviation 1 is the compilation unit, 2 the function
+fn debug_abbrev() -> Vec<u8> {
+    let mut data = Vec::new();
+
+    uleb128(&mut data, 1);
+    uleb128(&mut data, DW_TAG_COMPILE_UNIT);
+    data.push(1);
+
+    for &(attr, form) in &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_COMP_DIR, DW_FORM_STRING),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_HIGH_PC, DW_FORM_ADDR),
+        (DW_AT_STMT_LIST, DW_FORM_DATA4),
+    ] {
+        uleb128(&mut data, attr);
+        uleb128(&mut data, form);
+    }
+
+    data.extend_from_slice(&[0, 0]);
+
+    uleb128(&mut data, 2);
+    uleb128(&mut data, DW_TAG_SUBPROGRAM);
+    data.push(0);
+
+    for &(attr, form) in &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_HIGH_PC, DW_FORM_ADDR),
+    ] {
+        uleb128(&mut data, attr);
+        uleb128(&mut data, form);
+    }
+
+    data.extend_from_slice(&[0, 0, 0]);
+    data
+}
+
+fn debug_info(name: &str, file: &str, comp_dir: &str, start: u64, size: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    // unit length is patched at the end, DWARF version 2
+    data.write_u32::<LittleEndian>(0).unwrap();
+    data.write_u16::<LittleEndian>(2).unwrap();
+    data.write_u32::<LittleEndian>(0).unwrap();
+    data.push(8);
+
+    uleb128(&mut data, 1);
+    cstring(&mut data, file);
+    cstring(&mut data, comp_dir);
+    data.write_u64::<LittleEndian>(start).unwrap();
+    data.write_u64::<LittleEndian>(start + size).unwrap();
+    data.write_u32::<LittleEndian>(0).unwrap();
+
+    uleb128(&mut data, 2);
+    cstring(&mut data, name);
+    data.write_u64::<LittleEndian>(start).unwrap();
+    data.write_u64::<LittleEndian>(start + size).unwrap();
+
+    // end of the children of the compilation unit
+    data.push(0);
+
+    patch_length(&mut data, 0);
+    data
+}
+
+fn debug_line(file: &str, start: u64, size: u64, lines: &[(u64, u32)]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    // unit and header length are patched later, DWARF version 2
+    data.write_u32::<LittleEndian>(0).unwrap();
+    data.write_u16::<LittleEndian>(2).unwrap();
+    data.write_u32::<LittleEndian>(0).unwrap();
+    let header_start = data.len();
+
+    data.push(1);
+    data.push(1);
+    data.push(LINE_BASE as u8);
+    data.push(LINE_RANGE);
+    data.push(OPCODE_BASE);
+    data.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+
+    // no include directories, a single file
+    data.push(0);
+    cstring(&mut data, file);
+    uleb128(&mut data, 0);
+    uleb128(&mut data, 0);
+    uleb128(&mut data, 0);
+    data.push(0);
+
+    let header_length = data.len() - header_start;
+    (&mut data[6..10])
+        .write_u32::<LittleEndian>(header_length as u32)
+        .unwrap();
+
+    data.push(0);
+    uleb128(&mut data, 9);
+    data.push(DW_LNE_SET_ADDRESS);
+    data.write_u64::<LittleEndian>(start).unwrap();
+
+    let mut address = 0;
+    let mut line = 1;
+
+    for &(offset, row_line) in lines {
+        if offset > address {
+            data.push(DW_LNS_ADVANCE_PC);
+            uleb128(&mut data, offset - address);
+            address = offset;
+        }
+
+        if row_line != line {
+            data.push(DW_LNS_ADVANCE_LINE);
+            sleb128(&mut data, row_line as i64 - line as i64);
+            line = row_line;
+        }
+
+        data.push(DW_LNS_COPY);
+    }
+
+    if size > address {
+        data.push(DW_LNS_ADVANCE_PC);
+        uleb128(&mut data, size - address);
+    }
+
+    data.push(0);
+    uleb128(&mut data, 1);
+    data.push(DW_LNE_END_SEQUENCE);
+
+    patch_length(&mut data, 0);
+    data
+}
+
+// stores the length of the data following the 4 byte length field
+fn patch_length(data: &mut Vec<u8>, offset: usize) {
+    let length = (data.len() - offset - 4) as u32;
+    (&mut data[offset..offset + 4])
+        .write_u32::<LittleEndian>(length)
+        .unwrap();
+}
+
+fn align(data: &mut Vec<u8>, alignment: usize) {
+    while data.len() % alignment != 0 {
+        data.push(0);
+    }
+}
+
+fn cstring(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(value.as_bytes());
+    data.push(0);
+}
+
+fn uleb128(data: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            data.push(byte);
+            return;
+        }
+
+        data.push(byte | 0x80);
+    }
+}
+
+fn sleb128(data: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+
+        if done {
+            data.push(byte);
+            return;
+        }
+
+        data.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let mut data = Vec::new();
+        uleb128(&mut data, 624_485);
+        assert_eq!(vec![0xe5, 0x8e, 0x26], data);
+
+        let mut data = Vec::new();
+        sleb128(&mut data, -123_456);
+        assert_eq!(vec![0xc0, 0xbb, 0x78], data);
+
+        let mut data = Vec::new();
+        sleb128(&mut data, 63);
+        sleb128(&mut data, -64);
+        assert_eq!(vec![0x3f, 0x40], data);
+    }
+
+    #[test]
+    fn elf_object() {
+        let object = build_object(
+            "main()",
+            "test.dora",
+            "/tmp",
+            0x1000,
+            0x40,
+            &[(0, 3), (0x10, 5)],
+        );
+
+        assert_eq!(&[0x7f, b'E', b'L', b'F'], &object[0..4]);
+
+        let shoff = read_u64(&object, 40) as usize;
+        let shnum = read_u16(&object, 60) as usize;
+        assert_eq!(8, shnum);
+        assert_eq!(object.len(), shoff + shnum * SECTION_HEADER_SIZE);
+
+        // .text has the address and size of the function
+        let text = shoff + SECTION_HEADER_SIZE;
+        assert_eq!(SHT_NOBITS, read_u32(&object, text + 4));
+        assert_eq!(0x1000, read_u64(&object, text + 16));
+        assert_eq!(0x40, read_u64(&object, text + 32));
+    }
+
+    fn read_u16(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&data[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn read_u64(data: &[u8], offset: usize) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&data[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    }
+}
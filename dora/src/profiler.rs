@@ -0,0 +1,324 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::gc::Address;
+use crate::stack;
+use crate::threads::ThreadLocalData;
+use crate::vm::VM;
+
+// CPU time between two samples in microseconds
+const SAMPLE_INTERVAL: i64 = 1000;
+
+// innermost frames kept per sample, deeper stacks are truncated
+const MAX_FRAMES: usize = 64;
+
+// samples after this are dropped
+const MAX_SAMPLES: usize = 64 * 1024;
+
+// words per sample: state, native flag and the return addresses
+const SAMPLE_WORDS: usize = MAX_FRAMES + 2;
+
+// marks a sample taken while the thread executed native code
+const NATIVE_FRAME: &str = "[native]";
+
+thread_local! {
+    // ThreadLocalData and stack top of the Dora thread running on this
+    // native thread, the signal handler ignores all other threads
+    static SAMPLED_THREAD: Cell<(usize, usize)> = Cell::new((0, 0));
+}
+
+pub fn register_thread(tld: &ThreadLocalData, stack_top: Address) {
+    let tld = tld as *const _ as usize;
+    SAMPLED_THREAD.with(|thread| thread.set((tld, stack_top.to_usize())));
+}
+
+pub fn unregister_thread() {
+    SAMPLED_THREAD.with(|thread| thread.set((0, 0)));
+}
+
+// Sampling CPU profiler enabled with --profile. The process-wide ITIMER_PROF
+// timer sends SIGPROF to the thread that consumed the CPU time. The signal
+// handler may interrupt the thread anywhere, so it only copies the return
+// addresses of the Dora frames into a preallocated buffer without taking
+// locks. Addresses are symbolized through the code map at exit.
+pub struct Profiler {
+    path: String,
+
+    // a sample starts with its state: 0 while it is written, otherwise the
+    // number of addresses plus 1. Addresses are stored innermost first.
+    // Words are atomic because the signal handler writes them through a
+    // shared reference.
+    buffer: Vec<AtomicUsize>,
+    next: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl Profiler {
+    pub fn new(path: &str) -> Profiler {
+        Profiler {
+            path: path.into(),
+            buffer: zeroed_buffer(MAX_SAMPLES * SAMPLE_WORDS),
+            next: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    // called from the signal handler, must neither allocate nor lock
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn record(&self, frames: &[usize], native: bool) {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed);
+
+        if idx >= MAX_SAMPLES {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let sample = &self.buffer[idx * SAMPLE_WORDS..(idx + 1) * SAMPLE_WORDS];
+        sample[1].store(native as usize, Ordering::Relaxed);
+
+        for (word, &pc) in sample[2..].iter().zip(frames) {
+            word.store(pc, Ordering::Relaxed);
+        }
+
+        sample[0].store(frames.len() + 1, Ordering::Release);
+    }
+
+    // writes one line `frame;...;frame samples` per stack, the outermost
+    // frame comes first
+    pub fn write(&self, vm: &VM) -> io::Result<()> {
+        let mut stacks: HashMap<String, usize> = HashMap::new();
+        let samples = self.next.load(Ordering::Relaxed).min(MAX_SAMPLES);
+
+        for sample in self.buffer.chunks(SAMPLE_WORDS).take(samples) {
+            let state = sample[0].load(Ordering::Acquire);
+
+            if state == 0 {
+                continue;
+            }
+
+            let pcs: Vec<usize> = sample[2..state + 1]
+                .iter()
+                .map(|word| word.load(Ordering::Relaxed))
+                .collect();
+            let stacktrace = stack::stacktrace_from_pcs(vm, &pcs);
+            let mut line = stacktrace.folded(vm);
+
+            if sample[1].load(Ordering::Relaxed) != 0 {
+                if !line.is_empty() {
+                    line.push(';');
+                }
+
+                line.push_str(NATIVE_FRAME);
+            }
+
+            if !line.is_empty() {
+                *stacks.entry(line).or_insert(0) += 1;
+            }
+        }
+
+        let mut stacks: Vec<_> = stacks.into_iter().collect();
+        stacks.sort();
+
+        let mut file = BufWriter::new(File::create(&self.path)?);
+
+        for (stack, count) in stacks {
+            writeln!(file, "{} {}", stack, count)?;
+        }
+
+        file.flush()?;
+
+        let dropped = self.dropped.load(Ordering::Relaxed);
+
+        if dropped > 0 {
+            eprintln!("profiler: {} samples dropped", dropped);
+        }
+
+        Ok(())
+    }
+}
+
+// zeroed memory is only committed when written, AtomicUsize has the same
+// in-memory representation as usize
+fn zeroed_buffer(len: usize) -> Vec<AtomicUsize> {
+    let mut buffer = mem::ManuallyDrop::new(vec![0usize; len]);
+    let (ptr, len, capacity) = (buffer.as_mut_ptr(), buffer.len(), buffer.capacity());
+    unsafe { Vec::from_raw_parts(ptr as *mut AtomicUsize, len, capacity) }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub fn start(vm: &VM) {
+    if vm.profiler.is_none() {
+        return;
+    }
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigprof as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        if libc::sigaction(libc::SIGPROF, &action, std::ptr::null_mut()) != 0 {
+            panic!("installing SIGPROF handler failed");
+        }
+    }
+
+    set_timer(SAMPLE_INTERVAL);
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn start(vm: &VM) {
+    if vm.profiler.is_some() {
+        eprintln!("profiler: --profile is not supported on this platform");
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub fn stop(vm: &VM) {
+    if let Some(ref profiler) = vm.profiler {
+        set_timer(0);
+
+        unsafe {
+            libc::signal(libc::SIGPROF, libc::SIG_IGN);
+        }
+
+        if let Err(err) = profiler.write(vm) {
+            eprintln!("writing profile {} failed: {}", profiler.path, err);
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn stop(_vm: &VM) {
+    // nothing to do
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn set_timer(interval: i64) {
+    let interval = libc::timeval {
+        tv_sec: interval / 1_000_000,
+        tv_usec: interval % 1_000_000,
+    };
+
+    let timer = libc::itimerval {
+        it_interval: interval,
+        it_value: interval,
+    };
+
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_setitimer,
+            libc::ITIMER_PROF,
+            &timer as *const libc::itimerval,
+            std::ptr::null_mut::<libc::itimerval>(),
+        )
+    };
+
+    if res != 0 {
+        panic!("setting profiling timer failed");
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+extern "C" fn handle_sigprof(
+    _signo: libc::c_int,
+    _info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let (tld, stack_top) = match SAMPLED_THREAD.try_with(|thread| thread.get()) {
+        Ok(thread) => thread,
+        Err(_) => return,
+    };
+
+    if tld == 0 {
+        return;
+    }
+
+    let vm = crate::vm::get_vm();
+
+    let profiler = match vm.profiler {
+        Some(ref profiler) => profiler,
+        None => return,
+    };
+
+    let tld = unsafe { &*(tld as *const ThreadLocalData) };
+
+    let (pc, fp, sp) = unsafe {
+        let context = &*(context as *const libc::ucontext_t);
+        let regs = &context.uc_mcontext.gregs;
+
+        (
+            regs[libc::REG_RIP as usize] as usize,
+            regs[libc::REG_RBP as usize] as usize,
+            regs[libc::REG_RSP as usize] as usize,
+        )
+    };
+
+    let mut walker = FrameWalker {
+        vm,
+        stack: (sp, stack_top),
+        frames: [0; MAX_FRAMES],
+        len: 0,
+        last_fp: 0,
+    };
+
+    let native = !vm.gc.in_code_space(pc.into());
+
+    if !native {
+        walker.walk(pc, fp);
+    }
+
+    let mut dtn = tld.dtn();
+
+    while !dtn.is_null() && walker.on_stack(dtn as usize) {
+        let info = unsafe { &*dtn };
+
+        // the interrupted frames might already contain this native stub
+        if info.fp > walker.last_fp {
+            walker.walk(info.pc, info.fp);
+        }
+
+        dtn = info.last;
+    }
+
+    profiler.record(&walker.frames[..walker.len], native);
+}
+
+// Follows the frame pointers of Dora frames. Every frame pointer is checked
+// to lie on the stack of the interrupted thread before it is read.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+struct FrameWalker<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    stack: (usize, usize),
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+    last_fp: usize,
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl<'a, 'ast> FrameWalker<'a, 'ast> {
+    fn on_stack(&self, address: usize) -> bool {
+        address % 8 == 0 && self.stack.0 <= address && address + 16 <= self.stack.1
+    }
+
+    fn walk(&mut self, mut pc: usize, mut fp: usize) {
+        while self.len < MAX_FRAMES && self.vm.gc.in_code_space(pc.into()) {
+            self.frames[self.len] = pc;
+            self.len += 1;
+
+            if !self.on_stack(fp) {
+                return;
+            }
+
+            self.last_fp = fp;
+
+            unsafe {
+                pc = *((fp + 8) as *const usize);
+                fp = *(fp as *const usize);
+            }
+        }
+    }
+}
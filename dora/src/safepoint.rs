@@ -12,12 +12,14 @@ where
 
     let threads = vm.threads.threads.lock();
     if threads.len() == 1 {
+        vm.gc.resolve_alloc_samples();
         let ret = f(&*threads);
         THREAD.with(|thread| thread.borrow().unpark(vm));
         return ret;
     }
 
     let safepoint_id = stop_threads(vm, &*threads);
+    vm.gc.resolve_alloc_samples();
     let ret = f(&*threads);
     resume_threads(vm, &*threads, safepoint_id);
     THREAD.with(|thread| thread.borrow().unpark(vm));
@@ -1,3 +1,4 @@
+use crate::bytecode;
 use crate::error::msg::SemError;
 use crate::mem;
 use crate::sym::TypeSym::{
@@ -8,7 +9,7 @@ use crate::typeck;
 use crate::vm::{ensure_tuple, ClassId, FileId, NodeMap, VM};
 use dora_parser::ast::Type::{TypeBasic, TypeLambda, TypeSelf, TypeTuple};
 use dora_parser::ast::{
-    Expr, ExprBlockType, Stmt, Type, TypeBasicType, TypeLambdaType, TypeTupleType,
+    Expr, ExprBlockType, Stmt, Type, TypeBasicType, TypeLambdaType, TypeTupleType, WhereBound,
 };
 
 mod abstractck;
@@ -22,6 +23,9 @@ mod globaldef;
 mod globaldefck;
 mod implck;
 mod impldefck;
+mod importck;
+pub mod lintck;
+pub mod matchck;
 mod moduledefck;
 mod nameck;
 pub(crate) mod prelude;
@@ -51,6 +55,9 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
     let mut map_enum_defs = NodeMap::new(); // get EnumId from ast node
     let mut map_extension_defs = NodeMap::new(); // get ExtensionId from ast node
 
+    // assign files to their packages
+    importck::register_files(vm, &vm.ast);
+
     // add user defined fcts and classes to vm
     // this check does not look into fct or class bodies
     globaldef::check(
@@ -67,8 +74,16 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
     );
     return_on_error!(vm);
 
-    // define internal classes
-    prelude::internal_classes(vm);
+    // resolve imported names of each file
+    importck::check(vm, &vm.ast);
+    return_on_error!(vm);
+
+    // define internal classes, they are part of the first files checked
+    let check_prelude = vm.first_checked_file() == 0;
+
+    if check_prelude {
+        prelude::internal_classes(vm);
+    }
 
     // find all trait implementations for classes
     impldefck::check(vm, &vm.ast, &map_impl_defs);
@@ -106,13 +121,22 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
     return_on_error!(vm);
 
     // define internal functions & methods
-    prelude::internal_functions(vm);
-    prelude::known_methods(vm);
+    if check_prelude {
+        prelude::internal_functions(vm);
+        prelude::known_methods(vm);
+    }
+
+    // functions loaded from a `.dorabc` file are not checked again
+    bytecode::cache::verify(vm);
 
     // check types of expressions in functions
     typeck::check(vm);
     return_on_error!(vm);
 
+    // do match expressions cover all values?
+    matchck::check(vm);
+    return_on_error!(vm);
+
     // are break and continue used in the right places?
     flowck::check(vm);
 
@@ -131,13 +155,19 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
 
     // initialize addresses for global variables
     init_global_addresses(vm);
+
+    if !check_prelude {
+        specialize::update_itables(vm);
+    }
+
+    bytecode::cache::relocate(vm);
 }
 
 fn internalck<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if fct.in_class() {
+        if fct.in_class() || !vm.is_checked_file(fct.file) {
             continue;
         }
 
@@ -157,6 +187,10 @@ fn internalck<'ast>(vm: &VM<'ast>) {
     for cls in vm.classes.iter() {
         let cls = cls.read();
 
+        if !vm.is_checked_file(cls.file) {
+            continue;
+        }
+
         if cls.internal && !cls.internal_resolved {
             vm.diag
                 .lock()
@@ -184,6 +218,13 @@ fn internalck<'ast>(vm: &VM<'ast>) {
 
 fn init_global_addresses<'ast>(vm: &VM<'ast>) {
     let globals = vm.globals.lock();
+
+    // globals of files checked before keep their values
+    let globals = globals
+        .iter()
+        .filter(|glob| vm.is_checked_file(glob.read().file))
+        .collect::<Vec<_>>();
+
     let mut size = 0;
     let mut offsets = Vec::with_capacity(globals.len());
 
@@ -221,12 +262,26 @@ pub fn read_type<'ast>(vm: &VM<'ast>, file: FileId, t: &'ast Type) -> Option<Bui
     }
 }
 
+// impls and extensions don't have type params a `where` clause could constrain
+pub fn check_where_bounds_without_type_params<'ast>(
+    vm: &VM<'ast>,
+    file: FileId,
+    where_bounds: &'ast [WhereBound],
+) {
+    for where_bound in where_bounds {
+        if let Some(ty) = read_type(vm, file, &where_bound.ty) {
+            let msg = SemError::WhereBoundTypeParamExpected(ty.name(vm));
+            vm.diag.lock().report(file, where_bound.ty.pos(), msg);
+        }
+    }
+}
+
 fn read_type_basic<'ast>(
     vm: &VM<'ast>,
     file: FileId,
     basic: &'ast TypeBasicType,
 ) -> Option<BuiltinType> {
-    let sym = vm.sym.lock().get_type(basic.name);
+    let sym = vm.sym.lock().get_type_in(file, basic.name);
 
     if sym.is_none() {
         let name = vm.interner.str(basic.name).to_string();
@@ -465,6 +520,32 @@ pub mod tests {
         });
     }
 
+    pub fn ok_files(files: &[(&'static str, &'static str)]) {
+        test::parse_files_with_errors(files, |vm| {
+            let diag = vm.diag.lock();
+            let errors = diag.errors();
+
+            for e in errors {
+                println!("{}", e.message(vm));
+            }
+
+            assert!(!diag.has_errors());
+        });
+    }
+
+    pub fn err_files(files: &[(&'static str, &'static str)], pos: Position, msg: SemError) {
+        test::parse_files_with_errors(files, |vm| {
+            let diag = vm.diag.lock();
+            let errors = diag.errors();
+
+            println!("errors = {:?}", errors);
+
+            assert_eq!(1, errors.len(), "found {} errors instead", errors.len());
+            assert_eq!(pos, errors[0].pos);
+            assert_eq!(msg, errors[0].msg);
+        });
+    }
+
     pub fn errors(code: &'static str, vec: &[(Position, SemError)]) {
         test::parse_with_errors(code, |vm| {
             let diag = vm.diag.lock();
@@ -13,7 +13,7 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
         // we are only interested in non-abstract classes
         // with abstract super classes
 
-        if cls.is_abstract {
+        if cls.is_abstract || !vm.is_checked_file(cls.file) {
             continue;
         }
 
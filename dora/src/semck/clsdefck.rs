@@ -16,12 +16,13 @@ use dora_parser::interner::Name;
 use dora_parser::lexer::position::Position;
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_cls_defs: &NodeMap<ClassId>) {
+    let file_id = vm.first_checked_file();
     let mut clsck = ClsDefCheck {
         vm,
         ast,
         cls_id: None,
         map_cls_defs,
-        file_id: 0,
+        file_id,
     };
 
     clsck.check();
@@ -117,7 +118,11 @@ impl<'x, 'ast> ClsDefCheck<'x, 'ast> {
 
     fn check_parent_class(&mut self, parent_class: &'ast ast::ParentClass) {
         let name = self.vm.interner.str(parent_class.name).to_string();
-        let sym = self.vm.sym.lock().get_type(parent_class.name);
+        let sym = self
+            .vm
+            .sym
+            .lock()
+            .get_type_in(self.file_id.into(), parent_class.name);
 
         match sym {
             Some(TypeSym::SymClass(cls_id)) => {
@@ -275,6 +280,7 @@ impl<'x, 'ast> Visitor<'ast> for ClsDefCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: f.is_constructor,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
@@ -282,6 +288,8 @@ impl<'x, 'ast> Visitor<'ast> for ClsDefCheck<'x, 'ast> {
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
@@ -329,12 +337,15 @@ impl<'x, 'ast> Visitor<'ast> for ClsDefCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
             file: self.file_id.into(),
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
@@ -357,12 +368,13 @@ pub fn check_super_definition<'ast>(
     ast: &'ast Ast,
     map_cls_defs: &NodeMap<ClassId>,
 ) {
+    let file_id = vm.first_checked_file();
     let mut clsck = ClsSuperDefinitionCheck {
         vm,
         ast,
         cls_id: None,
         map_cls_defs,
-        file_id: 0,
+        file_id,
     };
 
     clsck.check();
@@ -398,7 +410,13 @@ impl<'x, 'ast> Visitor<'ast> for ClsSuperDefinitionCheck<'x, 'ast> {
             let cls = self.vm.classes.idx(self.cls_id.unwrap());
             let cls = cls.read();
             let super_class = cls.parent_class.expect("parent_class missing");
-            typeparamck::check_type(self.vm, self.file_id.into(), parent_class.pos, super_class);
+            typeparamck::check_type(
+                self.vm,
+                self.file_id.into(),
+                parent_class.pos,
+                super_class,
+                None,
+            );
         }
 
         self.cls_id = None;
@@ -7,11 +7,12 @@ use crate::ty::BuiltinType;
 use crate::vm::{EnumId, EnumVariant, NodeMap, VM};
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_enum_defs: &NodeMap<EnumId>) {
+    let file_id = vm.first_checked_file();
     let mut enumck = EnumCheck {
         vm,
         ast,
         map_enum_defs,
-        file_id: 0,
+        file_id,
     };
 
     enumck.check();
@@ -71,6 +72,8 @@ impl<'x, 'ast> Visitor<'ast> for EnumCheck<'x, 'ast> {
             next_variant_id += 1;
         }
 
+        xenum.simple_enumeration = xenum.variants.iter().all(|v| v.types.is_empty());
+
         if e.variants.is_empty() {
             self.vm
                 .diag
@@ -181,6 +184,24 @@ mod tests {
         ");
     }
 
+    #[test]
+    fn enum_recursive() {
+        ok("
+            enum Expr { Lit(Int64), Add(Expr, Expr) }
+            fun f() -> Expr { Expr::Add(Expr::Lit(1L), Expr::Lit(2L)) }
+        ");
+    }
+
+    #[test]
+    fn enum_compare_with_payload() {
+        ok("enum Foo { A, B } fun f(x: Foo) -> Bool { x == Foo::A }");
+        err(
+            "enum Foo { A(Int32), B } fun f(x: Foo) -> Bool { x == Foo::B }",
+            pos(1, 52),
+            SemError::BinOpType("equals".into(), "Foo".into(), "Foo".into()),
+        );
+    }
+
     #[test]
     #[ignore]
     fn enum_generic() {
@@ -11,12 +11,13 @@ use dora_parser::ast::{self, Ast};
 use dora_parser::lexer::position::Position;
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_extension_defs: &NodeMap<ExtensionId>) {
+    let file_id = vm.first_checked_file();
     let mut clsck = ExtensionCheck {
         vm,
         ast,
         extension_id: None,
         map_extension_defs,
-        file_id: 0,
+        file_id,
         extension_ty: BuiltinType::Error,
     };
 
@@ -48,6 +49,12 @@ impl<'x, 'ast> ExtensionCheck<'x, 'ast> {
             self.check_type_params(self.extension_id.unwrap(), type_params);
         }
 
+        semck::check_where_bounds_without_type_params(
+            self.vm,
+            self.file_id.into(),
+            &i.where_bounds,
+        );
+
         if let Some(class_ty) = semck::read_type(self.vm, self.file_id.into(), &i.class_type) {
             self.extension_ty = class_ty;
 
@@ -204,6 +211,7 @@ impl<'x, 'ast> Visitor<'ast> for ExtensionCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
@@ -211,6 +219,8 @@ impl<'x, 'ast> Visitor<'ast> for ExtensionCheck<'x, 'ast> {
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
@@ -15,11 +15,16 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
         let mut fct = fct.write();
         let ast = fct.ast;
 
+        if !vm.is_checked_file(fct.file) {
+            continue;
+        }
+
         // check modifiers for function
         check_abstract(vm, &*fct);
         check_static(vm, &*fct);
 
-        if !(fct.is_src() || fct.kind.is_definition()) {
+        // lambdas are checked together with their enclosing function
+        if !(fct.is_src() || fct.kind.is_definition()) || fct.is_lambda() {
             continue;
         }
 
@@ -68,7 +73,7 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
                 }
             }
 
-            FctParent::None => {}
+            FctParent::Function(_) | FctParent::None => {}
         }
 
         if let Some(ref type_params) = ast.type_params {
@@ -117,6 +122,15 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
             }
         }
 
+        if !ast.where_bounds.is_empty() && (fct.has_open || fct.has_override || fct.is_abstract) {
+            let msg = SemError::WhereClauseOnVirtualMethod;
+            vm.diag.lock().report(fct.file, fct.pos, msg);
+        }
+
+        for where_bound in &ast.where_bounds {
+            check_where_bound(vm, &mut *fct, where_bound);
+        }
+
         for (ind, p) in ast.params.iter().enumerate() {
             if fct.variadic_arguments {
                 vm.diag
@@ -261,6 +275,62 @@ fn check_static<'ast>(vm: &VM<'ast>, fct: &Fct<'ast>) {
     }
 }
 
+fn check_where_bound<'ast>(vm: &VM<'ast>, fct: &mut Fct<'ast>, where_bound: &'ast WhereBound) {
+    let ty = match semck::read_type(vm, fct.file, &where_bound.ty) {
+        Some(ty) => ty,
+        None => return,
+    };
+
+    match ty {
+        BuiltinType::ClassTypeParam(_, _) | BuiltinType::FctTypeParam(_, _) => {}
+
+        _ => {
+            let msg = SemError::WhereBoundTypeParamExpected(ty.name(vm));
+            vm.diag.lock().report(fct.file, where_bound.ty.pos(), msg);
+            return;
+        }
+    }
+
+    for bound in &where_bound.bounds {
+        let trait_id = match semck::read_type(vm, fct.file, bound) {
+            Some(BuiltinType::Trait(trait_id)) => trait_id,
+
+            None => {
+                // unknown type, error is already thrown
+                continue;
+            }
+
+            _ => {
+                let msg = SemError::BoundExpected;
+                vm.diag.lock().report(fct.file, bound.pos(), msg);
+                continue;
+            }
+        };
+
+        let inserted = match ty {
+            BuiltinType::ClassTypeParam(_, tp_id) => {
+                if fct.where_bounds.contains(&(tp_id, trait_id)) {
+                    false
+                } else {
+                    fct.where_bounds.push((tp_id, trait_id));
+                    true
+                }
+            }
+
+            BuiltinType::FctTypeParam(_, tp_id) => {
+                fct.type_params[tp_id.idx()].trait_bounds.insert(trait_id)
+            }
+
+            _ => unreachable!(),
+        };
+
+        if !inserted {
+            let msg = SemError::DuplicateTraitBound;
+            vm.diag.lock().report(fct.file, where_bound.pos, msg);
+        }
+    }
+}
+
 fn check_against_methods(vm: &VM, fct: &Fct, methods: &[FctId]) {
     for &method in methods {
         if method == fct.id {
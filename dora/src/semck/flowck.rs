@@ -2,6 +2,7 @@ use crate::error::msg::SemError;
 use crate::vm::{Fct, FctSrc, VM};
 
 use dora_parser::ast::visit::*;
+use dora_parser::ast::Expr::*;
 use dora_parser::ast::Stmt::*;
 use dora_parser::ast::*;
 
@@ -9,7 +10,12 @@ pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        // lambdas are checked together with their enclosing function
+        if !fct.is_src()
+            || fct.is_lambda()
+            || !vm.is_checked_file(fct.file)
+            || vm.has_cached_bytecode(fct.id)
+        {
             continue;
         }
 
@@ -23,6 +29,7 @@ pub fn check<'ast>(vm: &VM<'ast>) {
             src: &mut src,
             ast,
             in_loop: false,
+            in_defer: false,
         };
 
         flowck.check();
@@ -35,6 +42,7 @@ struct FlowCheck<'a, 'ast: 'a> {
     src: &'a mut FctSrc,
     ast: &'ast Function,
     in_loop: bool,
+    in_defer: bool,
 }
 
 impl<'a, 'ast> FlowCheck<'a, 'ast> {
@@ -50,6 +58,29 @@ impl<'a, 'ast> FlowCheck<'a, 'ast> {
         self.in_loop = old_in_loop;
     }
 
+    fn handle_defer(&mut self, block: &'ast Stmt) {
+        let old_in_loop = self.in_loop;
+        let old_in_defer = self.in_defer;
+
+        // break, continue and return can't leave the deferred block
+        self.in_loop = false;
+        self.in_defer = true;
+        visit::walk_stmt(self, block);
+        self.in_loop = old_in_loop;
+        self.in_defer = old_in_defer;
+    }
+
+    fn handle_return(&mut self, s: &'ast Stmt) {
+        if self.in_defer {
+            self.vm
+                .diag
+                .lock()
+                .report(self.fct.file, s.pos(), SemError::ReturnInDefer);
+        }
+
+        visit::walk_stmt(self, s);
+    }
+
     fn handle_flow(&mut self, s: &'ast Stmt) {
         if !self.in_loop {
             self.vm
@@ -67,10 +98,30 @@ impl<'a, 'ast> Visitor<'ast> for FlowCheck<'a, 'ast> {
             StmtFor(_) => self.handle_loop(s),
             StmtBreak(_) => self.handle_flow(s),
             StmtContinue(_) => self.handle_flow(s),
+            StmtDefer(_) => self.handle_defer(s),
+            StmtReturn(_) => self.handle_return(s),
 
             _ => visit::walk_stmt(self, s),
         }
     }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match *e {
+            ExprLambda(_) => {
+                // break and continue can't leave the lambda
+                let old_in_loop = self.in_loop;
+                let old_in_defer = self.in_defer;
+
+                self.in_loop = false;
+                self.in_defer = false;
+                visit::walk_expr(self, e);
+                self.in_loop = old_in_loop;
+                self.in_defer = old_in_defer;
+            }
+
+            _ => visit::walk_expr(self, e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -111,4 +162,35 @@ mod tests {
             SemError::OutsideLoop,
         );
     }
+
+    #[test]
+    fn flowck_lambda() {
+        ok("fun a() { || { while true { break; } }; }");
+        err(
+            "fun a() { while true { || { break; }; } }",
+            pos(1, 29),
+            SemError::OutsideLoop,
+        );
+        err(
+            "fun a() { while true { || { continue; }; } }",
+            pos(1, 29),
+            SemError::OutsideLoop,
+        );
+    }
+
+    #[test]
+    fn flowck_defer() {
+        ok("fun a() { defer { 1; } return; }");
+        ok("fun a() { defer { || { return; }; } }");
+        err(
+            "fun a() { defer { return; } }",
+            pos(1, 19),
+            SemError::ReturnInDefer,
+        );
+        err(
+            "fun a() { while true { defer { break; } } }",
+            pos(1, 32),
+            SemError::OutsideLoop,
+        );
+    }
 }
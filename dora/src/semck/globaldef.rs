@@ -2,7 +2,7 @@ use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::error::msg::SemError;
+use crate::error::msg::{SemError, SemErrorAndPos};
 use crate::gc::Address;
 use crate::sym::TermSym::{
     SymClassConstructor, SymClassConstructorAndModule, SymConst, SymFct, SymGlobal, SymModule,
@@ -35,9 +35,10 @@ pub fn check<'ast>(
     map_extension_defs: &mut NodeMap<ExtensionId>,
 ) {
     let ast = vm.ast;
+    let file_id = vm.first_checked_file();
     let mut gdef = GlobalDef {
         vm,
-        file_id: 0,
+        file_id,
         map_cls_defs,
         map_struct_defs,
         map_trait_defs,
@@ -87,7 +88,7 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
         self.map_trait_defs.insert(t.id, id);
 
         let sym = SymTrait(id);
-        if let Some(sym) = self.vm.sym.lock().insert_type(t.name, sym) {
+        if let Some(sym) = self.insert_type(t.name, sym, t.is_pub) {
             report_type_shadow(self.vm, t.name, self.file_id.into(), t.pos, sym);
         }
     }
@@ -116,7 +117,7 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
         self.map_global_defs.insert(g.id, id);
 
         let sym = SymGlobal(id);
-        if let Some(sym) = self.vm.sym.lock().insert_term(g.name, sym) {
+        if let Some(sym) = self.insert_term(g.name, sym, g.is_pub) {
             report_term_shadow(self.vm, g.name, self.file_id.into(), g.pos, sym);
         }
     }
@@ -182,13 +183,18 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
 
         self.map_module_defs.insert(m.id, id);
 
+        let file: FileId = self.file_id.into();
         let mut sym_table = self.vm.sym.lock();
-        match sym_table.get_term(m.name) {
+        if m.is_pub {
+            sym_table.mark_public(file, m.name);
+        }
+        let level = sym_table.decl_level_mut(file);
+        match level.get_term(m.name).cloned() {
             None => {
-                sym_table.insert_term(m.name, SymModule(id));
+                level.insert_term(m.name, SymModule(id));
             }
             Some(SymClassConstructor(class_id)) => {
-                sym_table.insert_term(m.name, SymClassConstructorAndModule(class_id, id));
+                level.insert_term(m.name, SymClassConstructorAndModule(class_id, id));
             }
             Some(sym) => report_term_shadow(self.vm, m.name, self.file_id.into(), m.pos, sym),
         }
@@ -216,7 +222,7 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
         self.map_const_defs.insert(c.id, id);
 
         let sym = SymConst(id);
-        if let Some(sym) = self.vm.sym.lock().insert_term(c.name, sym) {
+        if let Some(sym) = self.insert_term(c.name, sym, c.is_pub) {
             report_term_shadow(self.vm, c.name, self.file_id.into(), c.pos, sym);
         }
     }
@@ -254,6 +260,7 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
 
                 is_array: false,
                 is_str: false,
+                is_weak: false,
             };
 
             if let Some(ref type_params) = c.type_params {
@@ -270,18 +277,19 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
         self.map_cls_defs.insert(c.id, id);
 
         let sym = SymClass(id);
-        if let Some(sym) = self.vm.sym.lock().insert_type(c.name, sym) {
+        if let Some(sym) = self.insert_type(c.name, sym, c.is_pub) {
             report_type_shadow(self.vm, c.name, self.file_id.into(), c.pos, sym);
             return;
         }
 
         let mut sym_table = self.vm.sym.lock();
-        match sym_table.get_term(c.name) {
+        let level = sym_table.decl_level_mut(self.file_id.into());
+        match level.get_term(c.name).cloned() {
             None => {
-                sym_table.insert_term(c.name, SymClassConstructor(id));
+                level.insert_term(c.name, SymClassConstructor(id));
             }
             Some(SymModule(module_id)) => {
-                sym_table.insert_term(c.name, SymClassConstructorAndModule(id, module_id));
+                level.insert_term(c.name, SymClassConstructorAndModule(id, module_id));
             }
             Some(sym) => report_term_shadow(self.vm, c.name, self.file_id.into(), c.pos, sym),
         }
@@ -308,18 +316,19 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
         self.map_struct_defs.insert(s.id, id);
 
         let sym = SymStruct(id);
-        if let Some(sym) = self.vm.sym.lock().insert_type(s.name, sym) {
+        if let Some(sym) = self.insert_type(s.name, sym, s.is_pub) {
             report_type_shadow(self.vm, s.name, self.file_id.into(), s.pos, sym);
             return;
         }
 
         let mut sym_table = self.vm.sym.lock();
-        match sym_table.get_term(s.name) {
+        let level = sym_table.decl_level_mut(self.file_id.into());
+        match level.get_term(s.name).cloned() {
             None => {
-                sym_table.insert_term(s.name, SymStructConstructor(id));
+                level.insert_term(s.name, SymStructConstructor(id));
             }
             Some(SymModule(module_id)) => {
-                sym_table.insert_term(s.name, SymStructConstructorAndModule(id, module_id));
+                level.insert_term(s.name, SymStructConstructorAndModule(id, module_id));
             }
             Some(sym) => report_term_shadow(self.vm, s.name, self.file_id.into(), s.pos, sym),
         }
@@ -354,17 +363,24 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
         };
 
+        if f.is_pub {
+            self.vm.sym.lock().mark_public(self.file_id.into(), f.name);
+        }
+
         if let Err(sym) = self.vm.add_fct_to_sym(fct) {
             report_term_shadow(self.vm, f.name, self.file_id.into(), f.pos, sym);
         }
@@ -382,18 +398,44 @@ impl<'x, 'ast> Visitor<'ast> for GlobalDef<'x, 'ast> {
             name_to_value: HashMap::new(),
             extensions: Vec::new(),
             specializations: RwLock::new(HashMap::new()),
+            simple_enumeration: true,
         };
 
         self.vm.enums.push(RwLock::new(xenum));
         self.map_enum_defs.insert(e.id, id);
 
         let sym = SymEnum(id);
-        if let Some(sym) = self.vm.sym.lock().insert_type(e.name, sym) {
+        if let Some(sym) = self.insert_type(e.name, sym, e.is_pub) {
             report_type_shadow(self.vm, e.name, self.file_id.into(), e.pos, sym);
         }
     }
 }
 
+impl<'x, 'ast> GlobalDef<'x, 'ast> {
+    // adds top-level declaration to the package of the current file
+    fn insert_type(&mut self, name: Name, sym: TypeSym, is_pub: bool) -> Option<TypeSym> {
+        let file: FileId = self.file_id.into();
+        let mut sym_table = self.vm.sym.lock();
+
+        if is_pub {
+            sym_table.mark_public(file, name);
+        }
+
+        sym_table.decl_level_mut(file).insert_type(name, sym)
+    }
+
+    fn insert_term(&mut self, name: Name, sym: TermSym, is_pub: bool) -> Option<TermSym> {
+        let file: FileId = self.file_id.into();
+        let mut sym_table = self.vm.sym.lock();
+
+        if is_pub {
+            sym_table.mark_public(file, name);
+        }
+
+        sym_table.decl_level_mut(file).insert_term(name, sym)
+    }
+}
+
 pub fn report_type_shadow(vm: &VM, name: Name, file: FileId, pos: Position, sym: TypeSym) {
     let name = vm.interner.str(name).to_string();
 
@@ -405,7 +447,7 @@ pub fn report_type_shadow(vm: &VM, name: Name, file: FileId, pos: Position, sym:
         _ => unimplemented!(),
     };
 
-    vm.diag.lock().report(file, pos, msg);
+    report_shadow(vm, file, pos, msg, type_definition(vm, &sym));
 }
 
 pub fn report_term_shadow(vm: &VM, name: Name, file: FileId, pos: Position, sym: TermSym) {
@@ -426,7 +468,80 @@ pub fn report_term_shadow(vm: &VM, name: Name, file: FileId, pos: Position, sym:
         x => unimplemented!("{:?}", x),
     };
 
-    vm.diag.lock().report(file, pos, msg);
+    report_shadow(vm, file, pos, msg, term_definition(vm, &sym));
+}
+
+fn report_shadow(
+    vm: &VM,
+    file: FileId,
+    pos: Position,
+    msg: SemError,
+    definition: Option<(FileId, Position)>,
+) {
+    let mut error = SemErrorAndPos::new(file, pos, msg);
+
+    if let Some((def_file, def_pos)) = definition {
+        error = error.with_label(def_file, def_pos, "first defined here".into());
+    }
+
+    vm.diag.lock().report_error(error);
+}
+
+// location of the declaration a symbol refers to
+fn type_definition(vm: &VM, sym: &TypeSym) -> Option<(FileId, Position)> {
+    match *sym {
+        SymClass(id) => {
+            let cls = vm.classes.idx(id);
+            let cls = cls.read();
+            Some((cls.file, cls.pos))
+        }
+        SymStruct(id) => {
+            let xstruct = vm.structs.idx(id);
+            let xstruct = xstruct.lock();
+            Some((xstruct.file, xstruct.pos))
+        }
+        SymTrait(id) => {
+            let xtrait = vm.traits[id].read();
+            Some((xtrait.file, xtrait.pos))
+        }
+        SymEnum(id) => {
+            let xenum = vm.enums[id].read();
+            Some((xenum.file, xenum.pos))
+        }
+        _ => None,
+    }
+}
+
+fn term_definition(vm: &VM, sym: &TermSym) -> Option<(FileId, Position)> {
+    match *sym {
+        SymFct(id) => {
+            let fct = vm.fcts.idx(id);
+            let fct = fct.read();
+            Some((fct.file, fct.pos))
+        }
+        SymGlobal(id) => {
+            let global = vm.globals.idx(id);
+            let global = global.read();
+            Some((global.file, global.pos))
+        }
+        SymConst(id) => {
+            let xconst = vm.consts.idx(id);
+            let xconst = xconst.lock();
+            Some((xconst.file, xconst.pos))
+        }
+        SymModule(id) => {
+            let module = vm.modules.idx(id);
+            let module = module.read();
+            Some((module.file, module.pos))
+        }
+        SymClassConstructor(id) | SymClassConstructorAndModule(id, _) => {
+            type_definition(vm, &SymClass(id))
+        }
+        SymStructConstructor(id) | SymStructConstructorAndModule(id, _) => {
+            type_definition(vm, &SymStruct(id))
+        }
+        _ => None,
+    }
 }
 
 #[cfg(test)]
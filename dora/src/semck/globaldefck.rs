@@ -65,6 +65,7 @@ impl<'a, 'ast> Visitor<'ast> for GlobalDefCheck<'a, 'ast> {
                 internal_resolved: false,
                 overrides: None,
                 is_constructor: initializer.is_constructor,
+                throws: initializer.throws,
                 vtable_index: None,
                 initialized: false,
                 impl_for: None,
@@ -72,6 +73,8 @@ impl<'a, 'ast> Visitor<'ast> for GlobalDefCheck<'a, 'ast> {
                 variadic_arguments: false,
 
                 type_params: Vec::new(),
+
+                where_bounds: Vec::new(),
                 kind: FctKind::Source(RwLock::new(FctSrc::new())),
 
                 specializations: RwLock::new(HashMap::new()),
@@ -9,6 +9,11 @@ use dora_parser::lexer::position::Position;
 pub fn check<'ast>(vm: &mut VM<'ast>) {
     for ximpl in &vm.impls {
         let ximpl = ximpl.read();
+
+        if !vm.is_checked_file(ximpl.file) {
+            continue;
+        }
+
         let xtrait = vm.traits[ximpl.trait_id()].read();
         let cls = vm.classes.idx(ximpl.cls_id(vm));
         let cls = cls.read();
@@ -48,6 +53,12 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
                     let msg = SemError::ReturnTypeMismatch(impl_return_type, trait_return_type);
                     vm.diag.lock().report(ximpl.file, method.pos, msg);
                 }
+
+                if method.throws != trait_method.throws {
+                    let mtd_name = vm.interner.str(method.name).to_string();
+                    let msg = SemError::ThrowsDifference(mtd_name);
+                    vm.diag.lock().report(ximpl.file, method.pos, msg);
+                }
             } else {
                 let args = method
                     .params_without_self()
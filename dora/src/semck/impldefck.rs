@@ -12,12 +12,13 @@ use dora_parser::ast::{self, Ast};
 use dora_parser::lexer::position::Position;
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_impl_defs: &NodeMap<ImplId>) {
+    let file_id = vm.first_checked_file();
     let mut clsck = ImplCheck {
         vm,
         ast,
         impl_id: None,
         map_impl_defs,
-        file_id: 0,
+        file_id,
     };
 
     clsck.check();
@@ -51,9 +52,13 @@ impl<'x, 'ast> ImplCheck<'x, 'ast> {
             return;
         }
 
+        semck::check_where_bounds_without_type_params(self.vm, ximpl.file, &i.where_bounds);
+
         if let Some(ref trait_type) = i.trait_type {
             if let Some(trait_name) = trait_type.to_basic_without_type_params() {
-                if let Some(TypeSym::SymTrait(trait_id)) = self.vm.sym.lock().get_type(trait_name) {
+                if let Some(TypeSym::SymTrait(trait_id)) =
+                    self.vm.sym.lock().get_type_in(ximpl.file, trait_name)
+                {
                     ximpl.trait_id = Some(trait_id);
                 } else {
                     let name = self.vm.interner.str(trait_name).to_string();
@@ -153,6 +158,7 @@ impl<'x, 'ast> Visitor<'ast> for ImplCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
@@ -160,6 +166,8 @@ impl<'x, 'ast> Visitor<'ast> for ImplCheck<'x, 'ast> {
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
@@ -0,0 +1,201 @@
+use crate::error::msg::SemError;
+use crate::sym::PackageId;
+use crate::vm::{FileId, VM};
+use dora_parser::ast::visit::Visitor;
+use dora_parser::ast::{Ast, File, Import};
+use dora_parser::interner::Name;
+use dora_parser::lexer::position::Position;
+
+pub fn register_files<'ast>(vm: &VM<'ast>, ast: &'ast Ast) {
+    let mut sym = vm.sym.lock();
+
+    let first_file = vm.first_checked_file();
+
+    for (idx, file) in ast.files.iter().enumerate() {
+        let file_id: FileId = (first_file + idx as u32).into();
+        sym.add_file(file_id, &file.package);
+    }
+}
+
+pub fn check<'ast>(vm: &VM<'ast>, ast: &'ast Ast) {
+    let file_id = vm.first_checked_file();
+    let mut importck = ImportCheck { vm, file_id };
+
+    importck.visit_ast(ast);
+}
+
+struct ImportCheck<'x, 'ast: 'x> {
+    vm: &'x VM<'ast>,
+    file_id: u32,
+}
+
+impl<'x, 'ast> ImportCheck<'x, 'ast> {
+    fn import_name(&mut self, package_id: PackageId, name: Name, pos: Position) {
+        let file: FileId = self.file_id.into();
+
+        if let Err(msg) = check_package_member(self.vm, file, package_id, name) {
+            self.vm.diag.lock().report(file, pos, msg);
+            return;
+        }
+
+        let mut sym = self.vm.sym.lock();
+        let (type_sym, term_sym) = {
+            let package = &sym.package(package_id).table;
+            (
+                package.get_type(name).cloned(),
+                package.get_term(name).cloned(),
+            )
+        };
+
+        let imports = sym.imports_mut(file);
+
+        if imports.contains_type(name) || imports.contains_term(name) {
+            let name = self.vm.interner.str(name).to_string();
+            let msg = SemError::ShadowImport(name);
+            self.vm.diag.lock().report(file, pos, msg);
+            return;
+        }
+
+        if let Some(type_sym) = type_sym {
+            imports.insert_type(name, type_sym);
+        }
+
+        if let Some(term_sym) = term_sym {
+            imports.insert_term(name, term_sym);
+        }
+    }
+}
+
+impl<'x, 'ast> Visitor<'ast> for ImportCheck<'x, 'ast> {
+    fn visit_file(&mut self, f: &'ast File) {
+        for elem in &f.elements {
+            if let Some(import) = elem.to_import() {
+                self.visit_import(import);
+            }
+        }
+
+        self.file_id += 1;
+    }
+
+    fn visit_import(&mut self, i: &'ast Import) {
+        let package_id = self.vm.sym.lock().find_package(&i.package);
+
+        let package_id = if let Some(package_id) = package_id {
+            package_id
+        } else {
+            let package = package_name(self.vm, &i.package);
+            let msg = SemError::UnknownPackage(package);
+            self.vm.diag.lock().report(self.file_id.into(), i.pos, msg);
+            return;
+        };
+
+        for import_name in &i.names {
+            self.import_name(package_id, import_name.name, import_name.pos);
+        }
+    }
+}
+
+pub fn package_name(vm: &VM, path: &[Name]) -> String {
+    path.iter()
+        .map(|&name| vm.interner.str(name).to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+// members of other packages are only accessible when declared with `@pub`
+pub fn check_package_member(
+    vm: &VM,
+    file: FileId,
+    package_id: PackageId,
+    name: Name,
+) -> Result<(), SemError> {
+    let sym = vm.sym.lock();
+    let package = sym.package(package_id);
+
+    if !package.table.contains_type(name) && !package.table.contains_term(name) {
+        let package = package_name(vm, &package.path);
+        let name = vm.interner.str(name).to_string();
+        return Err(SemError::UnknownPackageMember(package, name));
+    }
+
+    if sym.file_package(file) != Some(package_id) && !package.is_public(name) {
+        let package = package_name(vm, &package.path);
+        let name = vm.interner.str(name).to_string();
+        return Err(SemError::NotPublic(package, name));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::msg::SemError;
+    use crate::semck::tests::*;
+
+    #[test]
+    fn import_pub_class() {
+        ok_files(&[
+            ("foo::bar", "@pub class Baz"),
+            ("", "import foo::bar::Baz; fun f() -> Baz { Baz() }"),
+        ]);
+        ok_files(&[
+            ("foo", "@pub class A @pub fun g() -> Int32 { 1 }"),
+            ("", "import foo::{A, g}; fun f() -> A { g(); A() }"),
+        ]);
+    }
+
+    #[test]
+    fn import_unknown_package() {
+        err_files(
+            &[("", "import foo::Baz;")],
+            pos(1, 1),
+            SemError::UnknownPackage("foo".into()),
+        );
+    }
+
+    #[test]
+    fn import_unknown_member() {
+        err_files(
+            &[("foo", "class A"), ("", "import foo::B;")],
+            pos(1, 13),
+            SemError::UnknownPackageMember("foo".into(), "B".into()),
+        );
+    }
+
+    #[test]
+    fn import_private_member() {
+        err_files(
+            &[("foo", "class A"), ("", "import foo::A;")],
+            pos(1, 13),
+            SemError::NotPublic("foo".into(), "A".into()),
+        );
+    }
+
+    #[test]
+    fn import_twice() {
+        err_files(
+            &[("foo", "@pub class A"), ("", "import foo::{A, A};")],
+            pos(1, 17),
+            SemError::ShadowImport("A".into()),
+        );
+    }
+
+    #[test]
+    fn packages_do_not_shadow_each_other() {
+        ok_files(&[
+            ("foo", "class A fun f() {}"),
+            ("bar", "class A fun f() {}"),
+            ("", "class A fun f() {}"),
+        ]);
+        ok_files(&[("foo", "class A"), ("foo", "fun f() -> A { A() }")]);
+    }
+
+    #[test]
+    fn package_member_not_visible_without_import() {
+        err_files(
+            &[("foo", "@pub class A"), ("", "fun f() -> A { nil }")],
+            pos(1, 12),
+            SemError::UnknownType("A".into()),
+        );
+    }
+}
@@ -0,0 +1,699 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::msg::{SemError, SemErrorAndPos};
+use crate::semck::returnck;
+use crate::vm::{Fct, FctId, FctParent, FctSrc, FileId, IdentType, VarId, VM};
+
+use dora_parser::ast::visit::*;
+use dora_parser::ast::Expr::*;
+use dora_parser::ast::Stmt::*;
+use dora_parser::ast::*;
+use dora_parser::interner::Name;
+use dora_parser::lexer::position::{Position, Span};
+
+// lints that can be disabled with `@allow(...)`
+const LINTS: &[&str] = &[
+    "unused",
+    "dead_store",
+    "unreachable",
+    "never_reassigned",
+    "shadow",
+];
+
+// lints only reported with --warn-unused, tests and examples commonly
+// declare things they never use and redeclare variables with `let`
+const OPT_IN_LINTS: &[&str] = &["unused", "never_reassigned", "shadow"];
+
+// Reports warnings for the program files, the standard library is never
+// linted. Runs after a successful semantic check.
+pub fn check<'ast>(vm: &VM<'ast>, first_program_file: usize) {
+    let mut names = NameCheck {
+        vm,
+        file: FileId::from(0),
+        cls: None,
+        cls_allow: HashMap::new(),
+        used_names: HashSet::new(),
+        classes: Vec::new(),
+    };
+
+    for (idx, file) in vm.ast.files.iter().enumerate().skip(first_program_file) {
+        names.file = FileId::from(idx as u32);
+        names.visit_file(file);
+    }
+
+    for &(file, cls) in &names.classes {
+        if !names.used_names.contains(&cls.name) && !is_allowed(vm, &cls.allow, "unused") {
+            let name = vm.interner.str(cls.name).to_string();
+            vm.diag
+                .lock()
+                .warn(file, cls.pos, SemError::UnusedClass(name));
+        }
+    }
+
+    // initializers of globals are called implicitly
+    let mut called: HashSet<FctId> = vm
+        .globals
+        .lock()
+        .iter()
+        .filter_map(|glob| glob.read().initializer)
+        .collect();
+
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        if !fct.is_src() {
+            continue;
+        }
+
+        let src = fct.src();
+        let src = src.read();
+
+        for (_, call) in src.map_calls.iter() {
+            if let Some(fct_id) = call.fct_id() {
+                called.insert(fct_id);
+            }
+        }
+    }
+
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        if fct.file.to_usize() < first_program_file || !fct.is_src() || fct.is_lambda() {
+            continue;
+        }
+
+        let mut allow = fct.ast.allow.clone();
+
+        if let Some(cls_allow) = names.cls_allow.get(&fct.ast.id) {
+            allow.extend_from_slice(cls_allow);
+        }
+
+        if is_unused_fct(vm, &fct, &called) && !is_allowed(vm, &allow, "unused") {
+            let name = vm.interner.str(fct.name).to_string();
+            vm.diag
+                .lock()
+                .warn(fct.file, fct.pos, SemError::UnusedFunction(name));
+        }
+
+        let src = fct.src();
+        let src = src.read();
+
+        let mut lintck = LintCheck {
+            vm,
+            fct: &fct,
+            src: &src,
+            allow,
+            reads: vec![0; src.vars.len()],
+            writes: vec![0; src.vars.len()],
+            lambda_params: HashSet::new(),
+            dead_stores: Vec::new(),
+        };
+
+        lintck.check();
+    }
+}
+
+fn is_allowed(vm: &VM, allow: &[Name], lint: &str) -> bool {
+    if OPT_IN_LINTS.contains(&lint) && !vm.args.flag_warn_unused {
+        return true;
+    }
+
+    allow
+        .iter()
+        .any(|&name| vm.interner.str(name).as_str() == lint)
+}
+
+fn is_unused_fct(vm: &VM, fct: &Fct, called: &HashSet<FctId>) -> bool {
+    match fct.parent {
+        FctParent::None => {}
+        _ => return false,
+    }
+
+    !fct.ast.is_pub
+        && !fct.is_test
+        && !fct.internal
+        && vm.interner.str(fct.name).as_str() != "main"
+        && !called.contains(&fct.id)
+}
+
+// Collects the names of classes, functions and types used in the program
+// and validates `@allow` annotations. Classes are tracked by name, uses
+// within the class itself don't count.
+struct NameCheck<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    file: FileId,
+    cls: Option<Name>,
+
+    // lints allowed by the class of a method or constructor
+    cls_allow: HashMap<NodeId, Vec<Name>>,
+    used_names: HashSet<Name>,
+
+    classes: Vec<(FileId, &'ast Class)>,
+}
+
+impl<'a, 'ast> NameCheck<'a, 'ast> {
+    fn check_allow(&mut self, allow: &[Name], pos: Position) {
+        for &name in allow {
+            let lint = self.vm.interner.str(name);
+
+            if !LINTS.contains(&lint.as_str()) {
+                let msg = SemError::UnknownLint(lint.to_string());
+                self.vm.diag.lock().warn(self.file, pos, msg);
+            }
+        }
+    }
+
+    fn use_name(&mut self, name: Name) {
+        if self.cls != Some(name) {
+            self.used_names.insert(name);
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for NameCheck<'a, 'ast> {
+    fn visit_class(&mut self, c: &'ast Class) {
+        self.check_allow(&c.allow, c.pos);
+
+        if !c.is_pub && !c.internal {
+            self.classes.push((self.file, c));
+        }
+
+        if let Some(ref parent) = c.parent_class {
+            self.use_name(parent.name);
+
+            for ty in &parent.type_params {
+                self.visit_type(ty);
+            }
+
+            for arg in &parent.params {
+                self.visit_expr(arg);
+            }
+        }
+
+        for method in c.constructor.iter().chain(c.methods.iter()) {
+            self.cls_allow.insert(method.id, c.allow.clone());
+        }
+
+        let old_cls = self.cls.replace(c.name);
+        walk_class(self, c);
+
+        for stmt in &c.initializers {
+            self.visit_stmt(stmt);
+        }
+
+        self.cls = old_cls;
+    }
+
+    fn visit_impl(&mut self, i: &'ast Impl) {
+        if let Some(ref ty) = i.trait_type {
+            self.visit_type(ty);
+        }
+
+        self.visit_type(&i.class_type);
+        walk_impl(self, i);
+    }
+
+    fn visit_fct(&mut self, f: &'ast Function) {
+        self.check_allow(&f.allow, f.pos);
+        walk_fct(self, f);
+    }
+
+    fn visit_method(&mut self, f: &'ast Function) {
+        self.check_allow(&f.allow, f.pos);
+        walk_fct(self, f);
+    }
+
+    fn visit_type(&mut self, t: &'ast Type) {
+        if let Type::TypeBasic(ref basic) = *t {
+            self.use_name(basic.name);
+
+            for param in &basic.params {
+                self.visit_type(param);
+            }
+        }
+
+        walk_type(self, t);
+    }
+
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        if let StmtDo(ref value) = *s {
+            for catch in &value.catch_blocks {
+                self.visit_type(&catch.data_type);
+            }
+        }
+
+        walk_stmt(self, s);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        if let ExprIdent(ref ident) = *e {
+            self.use_name(ident.name);
+        }
+
+        walk_expr(self, e);
+    }
+}
+
+// Counts reads and writes of the local variables of a function. Lambdas
+// share the variables of their enclosing function and are checked with it.
+struct LintCheck<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    fct: &'a Fct<'ast>,
+    src: &'a FctSrc,
+    allow: Vec<Name>,
+
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+
+    // lambda params may be unused to fit the expected signature
+    lambda_params: HashSet<NodeId>,
+
+    // overwritten stores, only reported for variables that are read
+    dead_stores: Vec<(VarId, Position)>,
+}
+
+impl<'a, 'ast> LintCheck<'a, 'ast> {
+    fn check(&mut self) {
+        let block = self.fct.ast.block();
+
+        self.check_block(&block.stmts, block.expr.as_deref());
+        walk_fct(self, self.fct.ast);
+
+        for &(var_id, pos) in &self.dead_stores {
+            if self.reads[var_id.0] > 0 {
+                let name = self.vm.interner.str(self.src.vars[var_id.0].name);
+                let msg = SemError::DeadStore(name.to_string());
+                self.warn("dead_store", pos, msg);
+            }
+        }
+
+        for var in &self.src.vars {
+            self.check_var(var.id);
+        }
+    }
+
+    fn warn(&self, lint: &str, pos: Position, msg: SemError) {
+        if !is_allowed(self.vm, &self.allow, lint) {
+            self.vm.diag.lock().warn(self.fct.file, pos, msg);
+        }
+    }
+
+    fn warn_unreachable(&self, pos: Position, span: Span) {
+        if !is_allowed(self.vm, &self.allow, "unreachable") {
+            let warning =
+                SemErrorAndPos::new(self.fct.file, pos, SemError::UnreachableCode).with_span(span);
+            self.vm.diag.lock().report_warning(warning);
+        }
+    }
+
+    fn var_ident(&self, e: &Expr) -> Option<VarId> {
+        let ident = e.to_ident()?;
+
+        match self.src.map_idents.get(ident.id) {
+            Some(&IdentType::Var(var_id)) => Some(var_id),
+            _ => None,
+        }
+    }
+
+    // the variable assigned by a statement `x = <expr>` or `let x = <expr>`
+    fn stored_var(&self, s: &'ast Stmt) -> Option<(VarId, Option<&'ast Expr>)> {
+        match *s {
+            StmtVar(ref var) if var.expr.is_some() => {
+                self.src.map_vars.get(var.id).map(|&var_id| (var_id, None))
+            }
+
+            StmtExpr(ref stmt) => {
+                let assign = stmt.expr.to_bin()?;
+
+                if assign.op != BinOp::Assign {
+                    return None;
+                }
+
+                self.var_ident(&assign.lhs)
+                    .map(|var_id| (var_id, Some(&*assign.rhs)))
+            }
+
+            _ => None,
+        }
+    }
+
+    fn check_block(&mut self, stmts: &'ast [Box<Stmt>], expr: Option<&'ast Expr>) {
+        let mut exits = false;
+
+        for (idx, stmt) in stmts.iter().enumerate() {
+            if exits {
+                self.warn_unreachable(stmt.pos(), stmt.span());
+                return;
+            }
+
+            self.check_dead_store(stmt, &stmts[idx + 1..]);
+
+            exits = match **stmt {
+                StmtBreak(_) | StmtContinue(_) => true,
+                _ => returnck::returns_value(stmt).is_ok(),
+            };
+        }
+
+        if let Some(expr) = expr {
+            if exits {
+                self.warn_unreachable(expr.pos(), expr.span());
+            }
+        }
+    }
+
+    // a stored value is dead when the variable is overwritten by a later
+    // statement of the same block before anything could read it
+    fn check_dead_store(&mut self, stmt: &'ast Stmt, rest: &'ast [Box<Stmt>]) {
+        let var_id = match self.stored_var(stmt) {
+            Some((var_id, _)) => var_id,
+            None => return,
+        };
+
+        // lambdas could read captured variables anywhere
+        if self.src.vars[var_id.0].captured {
+            return;
+        }
+
+        for next in rest {
+            if let Some((next_var_id, Some(rhs))) = self.stored_var(next) {
+                if next_var_id == var_id && !self.may_read(var_id, |v| v.visit_expr(rhs)) {
+                    self.dead_stores.push((var_id, stmt.pos()));
+                    return;
+                }
+            }
+
+            if self.may_read(var_id, |v| v.visit_stmt(next)) {
+                return;
+            }
+        }
+    }
+
+    fn may_read<F>(&self, var_id: VarId, f: F) -> bool
+    where
+        F: FnOnce(&mut MayRead<'a>),
+    {
+        let mut may_read = MayRead {
+            src: self.src,
+            var_id,
+            found: false,
+        };
+
+        f(&mut may_read);
+        may_read.found
+    }
+
+    fn check_var(&self, var_id: VarId) {
+        let var = &self.src.vars[var_id.0];
+        let name = self.vm.interner.str(var.name);
+
+        if var.node_id == self.fct.ast.id || self.lambda_params.contains(&var.node_id) {
+            return;
+        }
+
+        if let Some(shadowed) = var.shadows {
+            let previous = self.src.vars[shadowed.0].pos;
+
+            if !is_allowed(self.vm, &self.allow, "shadow") {
+                let msg = SemError::ShadowedVariable(name.to_string());
+                let warning = SemErrorAndPos::new(self.fct.file, var.pos, msg).with_label(
+                    self.fct.file,
+                    previous,
+                    "previously defined here".into(),
+                );
+                self.vm.diag.lock().report_warning(warning);
+            }
+        }
+
+        if name.starts_with('_') {
+            return;
+        }
+
+        let reads = self.reads[var_id.0];
+        let writes = self.writes[var_id.0];
+
+        if self.fct.ast.params.iter().any(|p| p.id == var.node_id) {
+            if reads == 0 && writes == 0 && self.checks_params() {
+                let msg = SemError::UnusedParam(name.to_string());
+                self.warn("unused", var.pos, msg);
+            }
+
+            return;
+        }
+
+        if reads == 0 {
+            let msg = if writes == 0 {
+                SemError::UnusedVariable(name.to_string())
+            } else {
+                SemError::VarNeverRead(name.to_string())
+            };
+
+            self.warn("unused", var.pos, msg);
+        } else if var.reassignable && writes == 0 {
+            let msg = SemError::VarNeverReassigned(name.to_string());
+            self.warn("never_reassigned", var.pos, msg);
+        }
+    }
+
+    // the signature of overridable and trait methods is given by others
+    fn checks_params(&self) -> bool {
+        let fct = self.fct;
+
+        match fct.parent {
+            FctParent::Trait(_) | FctParent::Impl(_) => false,
+            _ => {
+                !fct.is_constructor
+                    && !fct.has_open
+                    && !fct.has_override
+                    && !fct.is_abstract
+                    && !fct.internal
+            }
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for LintCheck<'a, 'ast> {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match *e {
+            ExprIdent(_) => {
+                if let Some(var_id) = self.var_ident(e) {
+                    self.reads[var_id.0] += 1;
+                }
+            }
+
+            ExprBin(ref bin) if bin.op == BinOp::Assign => {
+                if let Some(var_id) = self.var_ident(&bin.lhs) {
+                    self.writes[var_id.0] += 1;
+                    self.visit_expr(&bin.rhs);
+                    return;
+                }
+            }
+
+            ExprBlock(ref block) => {
+                self.check_block(&block.stmts, block.expr.as_deref());
+            }
+
+            ExprLambda(ref lambda) => {
+                let block = lambda.fct.block();
+                self.check_block(&block.stmts, block.expr.as_deref());
+
+                for param in &lambda.fct.params {
+                    self.lambda_params.insert(param.id);
+                }
+            }
+
+            _ => {}
+        }
+
+        walk_expr(self, e);
+    }
+}
+
+// Conservatively answers whether code might read a variable. Exceptions
+// could leave to a handler that reads the variable, so `try` counts too.
+struct MayRead<'a> {
+    src: &'a FctSrc,
+    var_id: VarId,
+    found: bool,
+}
+
+impl<'a, 'ast> Visitor<'ast> for MayRead<'a> {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match *e {
+            ExprIdent(ref ident) => {
+                if let Some(&IdentType::Var(var_id)) = self.src.map_idents.get(ident.id) {
+                    self.found |= var_id == self.var_id;
+                }
+            }
+
+            ExprTry(_) => self.found = true,
+
+            _ => {}
+        }
+
+        walk_expr(self, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::cmd::Args;
+    use crate::error::msg::SemError;
+    use crate::test;
+    use crate::vm::VM;
+    use dora_parser::lexer::position::Position;
+
+    fn lint(code: &'static str) -> Vec<(Position, SemError)> {
+        let mut args: Args = Default::default();
+        args.flag_warn_unused = true;
+        lint_with_args(code, args)
+    }
+
+    fn lint_with_args(code: &'static str, args: Args) -> Vec<(Position, SemError)> {
+        test::parse_with_args(code, args, |vm: &VM| {
+            super::check(vm, vm.files.len() - 1);
+
+            vm.diag
+                .lock()
+                .warnings()
+                .iter()
+                .map(|w| (w.pos, w.msg.clone()))
+                .collect()
+        })
+    }
+
+    fn warn(code: &'static str, line: u32, col: u32, msg: SemError) {
+        assert_eq!(vec![(Position::new(line, col), msg)], lint(code));
+    }
+
+    fn ok(code: &'static str) {
+        assert_eq!(Vec::<(Position, SemError)>::new(), lint(code));
+    }
+
+    #[test]
+    fn lint_unused_variable() {
+        warn(
+            "fun main() { let x = 1; }",
+            1,
+            14,
+            SemError::UnusedVariable("x".into()),
+        );
+        ok("fun main() { let _x = 1; }");
+        ok("fun main() { let x = 1; println(x.toString()); }");
+        ok("@allow(unused) fun main() { let x = 1; }");
+    }
+
+    #[test]
+    fn lint_unused_param() {
+        warn(
+            "fun main() { f(1); } fun f(a: Int32) {}",
+            1,
+            28,
+            SemError::UnusedParam("a".into()),
+        );
+        ok("fun main() { f(1); } fun f(_a: Int32) {}");
+        ok("fun main() { let f = |a: Int32| {}; f(1); }");
+        ok("trait T { fun f(a: Int32); }
+            class A
+            impl T for A { fun f(a: Int32) {} }
+            fun main() { A().f(1); }");
+    }
+
+    #[test]
+    fn lint_var_never_read() {
+        warn(
+            "fun main() { var x = 1; x = 2; }",
+            1,
+            14,
+            SemError::VarNeverRead("x".into()),
+        );
+    }
+
+    #[test]
+    fn lint_dead_store() {
+        warn(
+            "fun main() { var x = 1; x = 2; println(x.toString()); }",
+            1,
+            14,
+            SemError::DeadStore("x".into()),
+        );
+        ok("fun main() { var x = 1; x = x + 1; println(x.toString()); }");
+        ok("fun main() { var x = 1; if true { x = 2; } println(x.toString()); }");
+    }
+
+    #[test]
+    fn lint_never_reassigned() {
+        warn(
+            "fun main() { var x = 1; println(x.toString()); }",
+            1,
+            14,
+            SemError::VarNeverReassigned("x".into()),
+        );
+        ok("@allow(never_reassigned) fun main() { var x = 1; println(x.toString()); }");
+    }
+
+    #[test]
+    fn lint_unreachable() {
+        warn(
+            "fun f() -> Int32 { return 1; println(\"a\"); } fun main() { f(); }",
+            1,
+            37,
+            SemError::UnreachableCode,
+        );
+        warn(
+            "fun main() { while true { break; println(\"a\"); } }",
+            1,
+            41,
+            SemError::UnreachableCode,
+        );
+        ok("fun main() { if true { return; } println(\"a\"); }");
+    }
+
+    #[test]
+    fn lint_shadow() {
+        let warnings = lint("fun main() { let x = 1; if true { let x = 2; println(x.toString()); } println(x.toString()); }");
+        assert_eq!(
+            vec![(Position::new(1, 35), SemError::ShadowedVariable("x".into()))],
+            warnings
+        );
+    }
+
+    #[test]
+    fn lint_unused_fct_and_class() {
+        warn(
+            "fun main() {} fun f() {}",
+            1,
+            15,
+            SemError::UnusedFunction("f".into()),
+        );
+        ok("fun main() { f(); } fun f() {}");
+        ok("fun main() {} @pub fun f() {}");
+        warn(
+            "fun main() {} class Foo { fun f() -> Foo { self } }",
+            1,
+            15,
+            SemError::UnusedClass("Foo".into()),
+        );
+        ok("fun main() { Foo(); } class Foo");
+        ok("fun main() {} @allow(unused) class Foo");
+    }
+
+    #[test]
+    fn lint_opt_in_needs_flag() {
+        let code = "fun main() { let x = 1; var y = 1; let y = y; println(y.toString()); }
+                    fun f(a: Int32) {} class Foo";
+        assert_eq!(
+            Vec::<(Position, SemError)>::new(),
+            lint_with_args(code, Default::default())
+        );
+    }
+
+    #[test]
+    fn lint_unknown() {
+        warn(
+            "@allow(foo) fun main() {}",
+            1,
+            13,
+            SemError::UnknownLint("foo".into()),
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use crate::error::msg::SemError;
+use crate::semck::specialize::replace_type_param;
+use crate::ty::{BuiltinType, TypeList};
+use crate::vm::{ClassId, Fct, FctSrc, IdentType, VM};
+
+use dora_parser::ast::visit::*;
+use dora_parser::ast::Expr::*;
+use dora_parser::ast::*;
+
+pub fn check<'ast>(vm: &VM<'ast>) {
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        // lambdas are checked together with their enclosing function
+        if !fct.is_src()
+            || fct.is_lambda()
+            || !vm.is_checked_file(fct.file)
+            || vm.has_cached_bytecode(fct.id)
+        {
+            continue;
+        }
+
+        let src = fct.src();
+        let src = src.read();
+        let ast = fct.ast;
+
+        let mut matchck = MatchCheck {
+            vm,
+            fct: &fct,
+            src: &src,
+            ast,
+        };
+
+        matchck.check();
+    }
+}
+
+// Determines the type of a class pattern like `Some(x)` when matching a value of type `ty`.
+// The pattern class needs to be the class of `ty` itself or one of its subclasses. Type params
+// of generic subclasses are inferred when they are passed to the super class in order
+// (e.g. `class Some[T]: Option[T]`).
+pub fn pattern_class_type(vm: &VM, cls_id: ClassId, ty: BuiltinType) -> Option<BuiltinType> {
+    let value_cls_id = ty.cls_id(vm)?;
+
+    if cls_id == value_cls_id {
+        return Some(ty);
+    }
+
+    let cls = vm.classes.idx(cls_id);
+    let cls = cls.read();
+
+    if !cls.subclass_from(vm, value_cls_id) {
+        return None;
+    }
+
+    if cls.type_params.is_empty() {
+        return Some(cls.ty);
+    }
+
+    let parent_class = cls.parent_class?;
+
+    if parent_class.cls_id(vm) != Some(value_cls_id) {
+        return None;
+    }
+
+    let parent_params = parent_class.type_params(vm);
+
+    if parent_params.len() != cls.type_params.len() {
+        return None;
+    }
+
+    for (idx, param) in parent_params.iter().enumerate() {
+        match param {
+            BuiltinType::ClassTypeParam(param_cls_id, tpid)
+                if param_cls_id == cls_id && tpid.idx() == idx => {}
+            _ => return None,
+        }
+    }
+
+    let type_params = ty.type_params(vm);
+    let list_id = vm.lists.lock().insert(type_params);
+    Some(BuiltinType::Class(cls_id, list_id))
+}
+
+struct MatchCheck<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    fct: &'a Fct<'ast>,
+    src: &'a FctSrc,
+    ast: &'ast Function,
+}
+
+// Constructors of values that can be distinguished by patterns.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Ctor {
+    Variant(u32),
+    Bool(bool),
+    Tuple,
+    Class(ClassId),
+    // literals of types with an infinite number of values
+    Lit(NodeId),
+}
+
+#[derive(Clone)]
+enum Pat {
+    Wildcard,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+impl<'a, 'ast> MatchCheck<'a, 'ast> {
+    fn check(&mut self) {
+        self.visit_fct(self.ast);
+    }
+
+    fn check_expr_match(&mut self, expr: &'ast ExprMatchType) {
+        let ty = self.src.ty(expr.expr.id());
+
+        let rows = expr
+            .cases
+            .iter()
+            .map(|case| vec![self.lower_pattern(&case.pattern)])
+            .collect::<Vec<_>>();
+
+        if let Some(witness) = self.useful(&rows, &[ty]) {
+            let pattern = self.witness_name(&witness[0], ty);
+            self.vm.diag.lock().report(
+                self.fct.file,
+                expr.pos,
+                SemError::MatchNotExhaustive(pattern),
+            );
+        }
+    }
+
+    fn lower_pattern(&self, pattern: &MatchPattern) -> Pat {
+        match *pattern {
+            MatchPattern::Underscore(_) => Pat::Wildcard,
+
+            MatchPattern::Ident(ref ident) => {
+                if self.src.map_vars.get(ident.id).is_some() {
+                    Pat::Wildcard
+                } else {
+                    self.lower_pattern_with_params(ident.id, None)
+                }
+            }
+
+            MatchPattern::Lit(ref lit) => match *lit.expr {
+                ExprLitBool(ref value) => Pat::Ctor(Ctor::Bool(value.value), Vec::new()),
+                _ => Pat::Ctor(Ctor::Lit(lit.id), Vec::new()),
+            },
+
+            MatchPattern::Tuple(ref tuple) => {
+                let params = tuple.params.iter().map(|p| self.lower_pattern(p)).collect();
+                Pat::Ctor(Ctor::Tuple, params)
+            }
+
+            MatchPattern::Variant(ref variant) => {
+                self.lower_pattern_with_params(variant.id, variant.params.as_ref())
+            }
+        }
+    }
+
+    fn lower_pattern_with_params(
+        &self,
+        id: NodeId,
+        params: Option<&Vec<Box<MatchPattern>>>,
+    ) -> Pat {
+        let ctor = match self.src.map_idents.get(id) {
+            Some(&IdentType::EnumValue(_, value)) => Ctor::Variant(value),
+            Some(&IdentType::Class(cls_id)) => Ctor::Class(cls_id),
+            _ => unreachable!(),
+        };
+
+        let params = if let Some(params) = params {
+            params.iter().map(|p| self.lower_pattern(p)).collect()
+        } else {
+            let arity = self.subtypes(ctor, self.src.ty(id)).len();
+            vec![Pat::Wildcard; arity]
+        };
+
+        Pat::Ctor(ctor, params)
+    }
+
+    // Returns all constructors for values of the given type or None
+    // if the type has too many values to enumerate.
+    fn all_ctors(&self, ty: BuiltinType, used: &[Ctor]) -> Option<Vec<Ctor>> {
+        match ty {
+            BuiltinType::Enum(enum_id, _) => {
+                let xenum = self.vm.enums[enum_id].read();
+                let ctors = (0..xenum.variants.len() as u32).map(Ctor::Variant);
+                Some(ctors.collect())
+            }
+
+            BuiltinType::Bool => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+
+            BuiltinType::Unit | BuiltinType::Tuple(_) => Some(vec![Ctor::Tuple]),
+
+            BuiltinType::Class(cls_id, _) => {
+                if used.contains(&Ctor::Class(cls_id)) {
+                    return Some(vec![Ctor::Class(cls_id)]);
+                }
+
+                let is_abstract = self.vm.classes.idx(cls_id).read().is_abstract;
+
+                if !is_abstract {
+                    return None;
+                }
+
+                let mut subclasses = Vec::new();
+                self.subclass_ctors(cls_id, used, &mut subclasses);
+
+                if subclasses.is_empty() {
+                    None
+                } else {
+                    Some(subclasses)
+                }
+            }
+
+            _ => None,
+        }
+    }
+
+    // Collects the subclasses of an abstract class as constructors. Abstract
+    // subclasses not used as a pattern themselves are replaced by their own
+    // subclasses.
+    fn subclass_ctors(&self, cls_id: ClassId, used: &[Ctor], ctors: &mut Vec<Ctor>) {
+        for subclass in self.vm.classes.iter() {
+            let subclass = subclass.read();
+            let parent_id = subclass.parent_class.and_then(|p| p.cls_id(self.vm));
+
+            if parent_id != Some(cls_id) {
+                continue;
+            }
+
+            let ctor = Ctor::Class(subclass.id);
+
+            if subclass.is_abstract && !used.contains(&ctor) {
+                self.subclass_ctors(subclass.id, used, ctors);
+            } else {
+                ctors.push(ctor);
+            }
+        }
+    }
+
+    fn subtypes(&self, ctor: Ctor, ty: BuiltinType) -> Vec<BuiltinType> {
+        match ctor {
+            Ctor::Variant(value) => {
+                let enum_id = ty.enum_id().unwrap();
+                let xenum = self.vm.enums[enum_id].read();
+                xenum.variants[value as usize].types.clone()
+            }
+
+            Ctor::Tuple => match ty {
+                BuiltinType::Tuple(tuple_id) => (*self.vm.tuples.lock().get(tuple_id)).clone(),
+                _ => Vec::new(),
+            },
+
+            Ctor::Class(cls_id) => {
+                let cls_ty = pattern_class_type(self.vm, cls_id, ty).unwrap_or(ty);
+                let type_params = cls_ty.type_params(self.vm);
+
+                let cls = self.vm.classes.idx(cls_id);
+                let cls = cls.read();
+
+                cls.fields
+                    .iter()
+                    .map(|f| {
+                        replace_type_param(self.vm, f.ty, &type_params, &TypeList::empty(), None)
+                    })
+                    .collect()
+            }
+
+            Ctor::Bool(_) | Ctor::Lit(_) => Vec::new(),
+        }
+    }
+
+    // Checks whether a value exists that is matched by none of the rows. If there is such a
+    // value, a pattern for it is returned for each column.
+    fn useful(&self, rows: &[Vec<Pat>], tys: &[BuiltinType]) -> Option<Vec<Pat>> {
+        if tys.is_empty() {
+            return if rows.is_empty() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+        }
+
+        let ty = tys[0];
+        let used = rows
+            .iter()
+            .filter_map(|row| match row[0] {
+                Pat::Ctor(ctor, _) => Some(ctor),
+                Pat::Wildcard => None,
+            })
+            .collect::<Vec<_>>();
+
+        let all_ctors = self.all_ctors(ty, &used);
+
+        if let Some(ref all_ctors) = all_ctors {
+            if all_ctors.iter().all(|ctor| used.contains(ctor)) {
+                for &ctor in all_ctors {
+                    let subtypes = self.subtypes(ctor, ty);
+                    let arity = subtypes.len();
+
+                    let rows = specialize(rows, ctor, arity);
+                    let tys = subtypes
+                        .iter()
+                        .chain(tys[1..].iter())
+                        .cloned()
+                        .collect::<Vec<_>>();
+
+                    if let Some(mut witness) = self.useful(&rows, &tys) {
+                        let rest = witness.split_off(arity);
+                        let mut result = vec![Pat::Ctor(ctor, witness)];
+                        result.extend(rest);
+                        return Some(result);
+                    }
+                }
+
+                return None;
+            }
+        }
+
+        // not all constructors are covered: only rows starting with a wildcard can match
+        // the remaining values
+        let rows = rows
+            .iter()
+            .filter(|row| match row[0] {
+                Pat::Wildcard => true,
+                Pat::Ctor(_, _) => false,
+            })
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>();
+
+        let witness = self.useful(&rows, &tys[1..])?;
+
+        let missing = all_ctors
+            .unwrap_or_default()
+            .into_iter()
+            .find(|ctor| !used.contains(ctor));
+
+        let head = if let Some(ctor) = missing {
+            let arity = self.subtypes(ctor, ty).len();
+            Pat::Ctor(ctor, vec![Pat::Wildcard; arity])
+        } else {
+            Pat::Wildcard
+        };
+
+        let mut result = vec![head];
+        result.extend(witness);
+        Some(result)
+    }
+
+    fn witness_name(&self, pat: &Pat, ty: BuiltinType) -> String {
+        let (ctor, params) = match pat {
+            Pat::Wildcard => return "_".into(),
+            Pat::Ctor(ctor, params) => (*ctor, params),
+        };
+
+        let subtypes = self.subtypes(ctor, ty);
+        let params = params
+            .iter()
+            .zip(subtypes)
+            .map(|(param, ty)| self.witness_name(param, ty))
+            .collect::<Vec<_>>();
+
+        let name = match ctor {
+            Ctor::Variant(value) => {
+                let enum_id = ty.enum_id().unwrap();
+                let xenum = self.vm.enums[enum_id].read();
+                let variant = &xenum.variants[value as usize];
+
+                format!(
+                    "{}::{}",
+                    self.vm.interner.str(xenum.name),
+                    self.vm.interner.str(variant.name)
+                )
+            }
+
+            Ctor::Bool(value) => return value.to_string(),
+            Ctor::Tuple => return format!("({})", params.join(", ")),
+
+            Ctor::Class(cls_id) => {
+                let cls = self.vm.classes.idx(cls_id);
+                let cls = cls.read();
+                self.vm.interner.str(cls.name).to_string()
+            }
+
+            Ctor::Lit(_) => unreachable!(),
+        };
+
+        if params.is_empty() {
+            name
+        } else {
+            format!("{}({})", name, params.join(", "))
+        }
+    }
+}
+
+// Keeps all rows that match the given constructor and replaces their first
+// column with the sub-patterns.
+fn specialize(rows: &[Vec<Pat>], ctor: Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut result = match row[0] {
+                Pat::Wildcard => vec![Pat::Wildcard; arity],
+                Pat::Ctor(row_ctor, ref params) if row_ctor == ctor => params.clone(),
+                Pat::Ctor(_, _) => return None,
+            };
+
+            result.extend_from_slice(&row[1..]);
+            Some(result)
+        })
+        .collect()
+}
+
+impl<'a, 'ast> Visitor<'ast> for MatchCheck<'a, 'ast> {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        if let ExprMatch(ref expr) = *e {
+            self.check_expr_match(expr);
+        }
+
+        visit::walk_expr(self, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::msg::SemError;
+    use crate::semck::tests::*;
+
+    #[test]
+    fn match_enum() {
+        ok("enum Foo { A, B, C }
+            fun f(x: Foo) -> Int32 { match x { Foo::A => 1, Foo::B => 2, Foo::C => 3 } }");
+        ok("enum Foo { A, B, C }
+            fun f(x: Foo) -> Int32 { match x { Foo::A => 1, _ => 2 } }");
+        err(
+            "enum Foo { A, B, C }
+            fun f(x: Foo) -> Int32 { match x { Foo::A => 1, Foo::C => 3 } }",
+            pos(2, 38),
+            SemError::MatchNotExhaustive("Foo::B".into()),
+        );
+    }
+
+    #[test]
+    fn match_enum_with_payload() {
+        ok("enum Foo { A(Bool), B }
+            fun f(x: Foo) -> Int32 { match x { Foo::A(true) => 1, Foo::A(false) => 2, Foo::B => 3 } }");
+        err(
+            "enum Foo { A(Bool), B }
+            fun f(x: Foo) -> Int32 { match x { Foo::A(true) => 1, Foo::B => 3 } }",
+            pos(2, 38),
+            SemError::MatchNotExhaustive("Foo::A(false)".into()),
+        );
+    }
+
+    #[test]
+    fn match_bool_and_literals() {
+        ok("fun f(x: Bool) -> Int32 { match x { true => 1, false => 0 } }");
+        ok("fun f(x: Int32) -> Int32 { match x { 0 => 1, y => y } }");
+        err(
+            "fun f(x: Bool) -> Int32 { match x { true => 1 } }",
+            pos(1, 27),
+            SemError::MatchNotExhaustive("false".into()),
+        );
+        err(
+            "fun f(x: Int32) -> Int32 { match x { 0 => 1, 1 => 2 } }",
+            pos(1, 28),
+            SemError::MatchNotExhaustive("_".into()),
+        );
+    }
+
+    #[test]
+    fn match_tuple() {
+        ok("fun f(x: (Bool, Bool)) -> Int32 {
+            match x { (true, _) => 1, (_, true) => 2, (false, false) => 3 }
+        }");
+        err(
+            "fun f(x: (Bool, Bool)) -> Int32 {
+                match x { (true, _) => 1, (_, true) => 2 }
+            }",
+            pos(2, 17),
+            SemError::MatchNotExhaustive("(false, false)".into()),
+        );
+    }
+
+    #[test]
+    fn match_option() {
+        ok("fun f(x: Option[Int32]) -> Int32 { match x { Some(y) => y, None => 0 } }");
+        ok("fun f(x: Option[Int32]) -> Int32 { match x { Some(1) => 1, _ => 0 } }");
+        err(
+            "fun f(x: Option[Int32]) -> Int32 { match x { Some(y) => y } }",
+            pos(1, 36),
+            SemError::MatchNotExhaustive("None".into()),
+        );
+        err(
+            "fun f(x: Option[Bool]) -> Int32 { match x { Some(true) => 1, None => 0 } }",
+            pos(1, 35),
+            SemError::MatchNotExhaustive("Some(false)".into()),
+        );
+    }
+
+    #[test]
+    fn match_nested_abstract_class() {
+        ok("@open @abstract class Shape
+            @open @abstract class Round: Shape
+            class Circle(let r: Int32): Round
+            class Ellipse(let a: Int32): Round
+            class Square(let s: Int32): Shape
+            fun f(x: Shape) -> Int32 { match x { Circle(r) => r, Ellipse(a) => a, Square(s) => s } }");
+        ok("@open @abstract class Shape
+            @open @abstract class Round: Shape
+            class Circle(let r: Int32): Round
+            class Ellipse(let a: Int32): Round
+            class Square(let s: Int32): Shape
+            fun f(x: Shape) -> Int32 { match x { Round() => 0, Square(s) => s } }");
+        err(
+            "@open @abstract class Shape
+            @open @abstract class Round: Shape
+            class Circle(let r: Int32): Round
+            class Ellipse(let a: Int32): Round
+            class Square(let s: Int32): Shape
+            fun f(x: Shape) -> Int32 { match x { Circle(r) => r, Square(s) => s } }",
+            pos(6, 40),
+            SemError::MatchNotExhaustive("Ellipse(_)".into()),
+        );
+    }
+}
@@ -14,12 +14,13 @@ use dora_parser::interner::Name;
 use dora_parser::lexer::position::Position;
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_module_defs: &NodeMap<ModuleId>) {
+    let file_id = vm.first_checked_file();
     let mut module_check = ModuleCheck {
         vm,
         ast,
         module_id: None,
         map_module_defs,
-        file_id: 0,
+        file_id,
     };
 
     module_check.check();
@@ -66,7 +67,11 @@ impl<'x, 'ast> ModuleCheck<'x, 'ast> {
 
     fn check_parent_class(&mut self, parent_class: &'ast ast::ParentClass) {
         let name = self.vm.interner.str(parent_class.name).to_string();
-        let sym = self.vm.sym.lock().get_type(parent_class.name);
+        let sym = self
+            .vm
+            .sym
+            .lock()
+            .get_type_in(self.file_id.into(), parent_class.name);
 
         match sym {
             Some(TypeSym::SymClass(cls_id)) => {
@@ -202,6 +207,7 @@ impl<'x, 'ast> Visitor<'ast> for ModuleCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: f.is_constructor,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
@@ -209,6 +215,8 @@ impl<'x, 'ast> Visitor<'ast> for ModuleCheck<'x, 'ast> {
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
@@ -257,12 +265,15 @@ impl<'x, 'ast> Visitor<'ast> for ModuleCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
             file: self.file_id.into(),
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind,
 
             specializations: RwLock::new(HashMap::new()),
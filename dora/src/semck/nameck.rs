@@ -1,3 +1,7 @@
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use crate::error::msg::SemError;
 use crate::vm::*;
 
@@ -9,18 +13,20 @@ use dora_parser::interner::Name;
 use dora_parser::lexer::position::Position;
 
 use crate::semck::globaldef::report_term_shadow;
+use crate::semck::importck::check_package_member;
 use crate::sym::TermSym::{
     SymClassConstructor, SymClassConstructorAndModule, SymConst, SymFct, SymGlobal, SymModule,
     SymStructConstructor, SymStructConstructorAndModule, SymVar,
 };
 use crate::sym::TypeSym::{SymClass, SymClassTypeParam, SymEnum, SymFctTypeParam, SymStruct};
+use crate::sym::{PackageId, TermSym, TypeSym};
 use crate::ty::BuiltinType;
 
 pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        if !fct.is_src() || fct.is_lambda() || !vm.is_checked_file(fct.file) {
             continue;
         }
 
@@ -33,6 +39,7 @@ pub fn check<'ast>(vm: &VM<'ast>) {
             fct: &fct,
             src: &mut src,
             ast,
+            lambdas: Vec::new(),
         };
 
         nameck.check();
@@ -44,6 +51,13 @@ struct NameCheck<'a, 'ast: 'a> {
     fct: &'a Fct<'ast>,
     src: &'a mut FctSrc,
     ast: &'ast Function,
+    lambdas: Vec<LambdaScope>,
+}
+
+struct LambdaScope {
+    // all variables with smaller ids were defined outside the lambda
+    first_var: usize,
+    captures: Vec<VarId>,
 }
 
 impl<'a, 'ast> NameCheck<'a, 'ast> {
@@ -128,21 +142,32 @@ impl<'a, 'ast> NameCheck<'a, 'ast> {
             name,
             ty,
             reassignable: false,
+            captured: false,
             node_id: ast_id,
+            pos: self.fct.pos,
+            shadows: None,
         };
 
         self.src.vars.push(var);
     }
 
-    pub fn add_var(&mut self, mut var: Var, pos: Position) -> VarId {
+    pub fn add_var(&mut self, mut var: Var) -> VarId {
         let name = var.name;
         let var_id = VarId(self.src.vars.len());
 
         var.id = var_id;
 
-        match self.vm.sym.lock().insert_term(name, SymVar(var_id)) {
-            Some(SymVar(_)) | None => {}
-            Some(sym) => report_term_shadow(self.vm, name, self.fct.file, pos, sym),
+        let mut sym = self.vm.sym.lock();
+
+        if let Some(SymVar(shadowed)) = sym.get_term(name) {
+            var.shadows = Some(shadowed);
+        }
+
+        match sym.insert_term(name, SymVar(var_id)) {
+            // redeclaring a variable in the same scope is not shadowing
+            Some(SymVar(_)) => var.shadows = None,
+            None => {}
+            Some(conflict) => report_term_shadow(self.vm, name, self.fct.file, var.pos, conflict),
         }
         self.src.vars.push(var);
 
@@ -154,18 +179,133 @@ impl<'a, 'ast> NameCheck<'a, 'ast> {
             id: VarId(0),
             name: var.name,
             reassignable: var.reassignable,
+            captured: false,
             ty: BuiltinType::Unit,
             node_id: var.id,
+            pos: var.pos,
+            shadows: None,
         };
 
         if let Some(ref expr) = var.expr {
             self.visit_expr(expr);
         }
 
-        let var_id = self.add_var(var_ctxt, var.pos);
+        let var_id = self.add_var(var_ctxt);
         self.src.map_vars.insert(var.id, var_id)
     }
 
+    fn check_expr_lambda(&mut self, lambda: &'ast ExprLambdaType) {
+        let ast = &*lambda.fct;
+
+        let fct = Fct {
+            id: FctId(0),
+            file: self.fct.file,
+            pos: ast.pos,
+            ast,
+            name: ast.name,
+            param_types: Vec::new(),
+            return_type: BuiltinType::Unit,
+            parent: FctParent::Function(self.fct.id),
+            has_override: false,
+            has_open: false,
+            has_final: false,
+            has_optimize_immediately: false,
+            is_pub: false,
+            is_static: false,
+            is_abstract: false,
+            is_test: false,
+            use_cannon: true,
+            internal: false,
+            internal_resolved: false,
+            overrides: None,
+            is_constructor: false,
+            throws: ast.throws,
+            vtable_index: None,
+            initialized: false,
+            impl_for: None,
+            variadic_arguments: false,
+
+            type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
+            kind: FctKind::Source(RwLock::new(FctSrc::new())),
+
+            specializations: RwLock::new(HashMap::new()),
+        };
+
+        let fct_id = {
+            let mut fcts = self.vm.fcts.lock();
+            let mut fct = fct;
+            let fct_id = FctId(fcts.len());
+            fct.id = fct_id;
+            fcts.push(Arc::new(RwLock::new(fct)));
+
+            fct_id
+        };
+
+        self.vm.sym.lock().push_level();
+        self.lambdas.push(LambdaScope {
+            first_var: self.src.vars.len(),
+            captures: Vec::new(),
+        });
+
+        for p in &ast.params {
+            let var_ctxt = Var {
+                id: VarId(0),
+                name: p.name,
+                reassignable: false,
+                captured: false,
+                ty: BuiltinType::Unit,
+                node_id: p.id,
+                pos: p.pos,
+                shadows: None,
+            };
+
+            // params of lambdas are allowed to shadow variables
+            let var_id = self.add_var(var_ctxt);
+            self.src.map_vars.insert(p.id, var_id);
+        }
+
+        let block = ast.block();
+
+        for stmt in &block.stmts {
+            self.visit_stmt(stmt);
+        }
+
+        if let Some(ref value) = block.expr {
+            self.visit_expr(value);
+        }
+
+        let scope = self.lambdas.pop().unwrap();
+        self.vm.sym.lock().pop_level();
+
+        self.src.map_lambdas.insert(
+            ast.id,
+            LambdaInfo {
+                fct_id,
+                captures: scope.captures,
+            },
+        );
+    }
+
+    fn capture_var(&mut self, var_id: VarId) {
+        let mut captured = false;
+
+        for lambda in &mut self.lambdas {
+            if var_id.0 < lambda.first_var {
+                if !lambda.captures.contains(&var_id) {
+                    lambda.captures.push(var_id);
+                }
+
+                captured = true;
+            }
+        }
+
+        if captured {
+            self.src.vars[var_id].captured = true;
+        }
+    }
+
     fn check_stmt_for(&mut self, fl: &'ast StmtForType) {
         self.visit_expr(&fl.expr);
 
@@ -175,106 +315,253 @@ impl<'a, 'ast> NameCheck<'a, 'ast> {
             id: VarId(0),
             name: fl.name,
             reassignable: false,
+            captured: false,
             ty: BuiltinType::Unit,
             node_id: fl.id,
+            pos: fl.pos,
+            shadows: None,
         };
 
-        let var_id = self.add_var(var_ctxt, fl.pos);
+        let var_id = self.add_var(var_ctxt);
         self.src.map_vars.insert(fl.id, var_id);
 
         self.visit_stmt(&fl.block);
         self.vm.sym.lock().pop_level();
     }
 
+    fn check_stmt_do(&mut self, stmt: &'ast StmtDoType) {
+        self.visit_stmt(&stmt.do_block);
+
+        for catch in &stmt.catch_blocks {
+            self.vm.sym.lock().push_level();
+
+            let var_ctxt = Var {
+                id: VarId(0),
+                name: catch.name,
+                reassignable: false,
+                captured: false,
+                ty: BuiltinType::Unit,
+                node_id: catch.id,
+                pos: catch.pos,
+                shadows: None,
+            };
+
+            let var_id = self.add_var(var_ctxt);
+            self.src.map_vars.insert(catch.id, var_id);
+
+            self.visit_stmt(&catch.block);
+            self.vm.sym.lock().pop_level();
+        }
+    }
+
     fn check_expr_ident(&mut self, ident: &'ast ExprIdentType) {
-        let term_sym = self.vm.sym.lock().get_term(ident.name);
-        let type_sym = self.vm.sym.lock().get_type(ident.name);
+        let term_sym = self.vm.sym.lock().get_term_in(self.fct.file, ident.name);
+        let type_sym = self.vm.sym.lock().get_type_in(self.fct.file, ident.name);
+
+        if term_sym.is_none() && type_sym.is_none() {
+            let name = self.vm.interner.str(ident.name).to_string();
+            report(
+                self.vm,
+                self.fct.file,
+                ident.pos,
+                SemError::UnknownIdentifier(name),
+            );
+            return;
+        }
+
+        self.map_ident(ident.id, term_sym, type_sym);
+    }
 
+    fn map_ident(&mut self, node_id: NodeId, term_sym: Option<TermSym>, type_sym: Option<TypeSym>) {
         match (term_sym, type_sym) {
             (Some(SymVar(id)), None) => {
-                self.src.map_idents.insert(ident.id, IdentType::Var(id));
+                self.src.map_idents.insert(node_id, IdentType::Var(id));
+                self.capture_var(id);
             }
 
             (Some(SymGlobal(id)), None) => {
-                self.src.map_idents.insert(ident.id, IdentType::Global(id));
+                self.src.map_idents.insert(node_id, IdentType::Global(id));
             }
 
             (Some(SymConst(id)), None) => {
-                self.src.map_idents.insert(ident.id, IdentType::Const(id));
+                self.src.map_idents.insert(node_id, IdentType::Const(id));
             }
 
             (Some(SymFct(id)), None) => {
-                self.src.map_idents.insert(ident.id, IdentType::Fct(id));
+                self.src.map_idents.insert(node_id, IdentType::Fct(id));
             }
 
             (Some(SymModule(id)), None) => {
-                self.src.map_idents.insert(ident.id, IdentType::Module(id));
+                self.src.map_idents.insert(node_id, IdentType::Module(id));
             }
 
             (None, Some(SymStruct(id))) => {
-                self.src.map_idents.insert(ident.id, IdentType::Struct(id));
+                self.src.map_idents.insert(node_id, IdentType::Struct(id));
             }
 
             (None, Some(SymClass(id))) => {
-                self.src.map_idents.insert(ident.id, IdentType::Class(id));
+                self.src.map_idents.insert(node_id, IdentType::Class(id));
             }
 
             (None, Some(SymFctTypeParam(fct_id, id))) => {
                 let ty = BuiltinType::FctTypeParam(fct_id, id);
                 self.src
                     .map_idents
-                    .insert(ident.id, IdentType::TypeParam(ty))
+                    .insert(node_id, IdentType::TypeParam(ty))
             }
 
             (None, Some(SymClassTypeParam(cls_id, id))) => {
                 let ty = BuiltinType::ClassTypeParam(cls_id, id);
                 self.src
                     .map_idents
-                    .insert(ident.id, IdentType::TypeParam(ty))
+                    .insert(node_id, IdentType::TypeParam(ty))
             }
 
-            (None, Some(SymEnum(id))) => self.src.map_idents.insert(ident.id, IdentType::Enum(id)),
+            (None, Some(SymEnum(id))) => self.src.map_idents.insert(node_id, IdentType::Enum(id)),
 
             (Some(SymModule(module_id)), Some(SymClass(class_id)))
             | (Some(SymClassConstructorAndModule(_, module_id)), Some(SymClass(class_id))) => self
                 .src
                 .map_idents
-                .insert(ident.id, IdentType::ClassAndModule(class_id, module_id)),
+                .insert(node_id, IdentType::ClassAndModule(class_id, module_id)),
 
             (Some(SymClassConstructor(id)), _) => {
-                self.src.map_idents.insert(ident.id, IdentType::Class(id))
+                self.src.map_idents.insert(node_id, IdentType::Class(id))
             }
 
             (Some(SymModule(module_id)), Some(SymStruct(struct_id)))
             | (Some(SymStructConstructorAndModule(_, module_id)), Some(SymStruct(struct_id))) => {
                 self.src
                     .map_idents
-                    .insert(ident.id, IdentType::StructAndModule(struct_id, module_id))
+                    .insert(node_id, IdentType::StructAndModule(struct_id, module_id))
             }
 
             (Some(SymStructConstructor(id)), _) => {
-                self.src.map_idents.insert(ident.id, IdentType::Struct(id))
+                self.src.map_idents.insert(node_id, IdentType::Struct(id))
             }
 
-            (None, None) => {
-                let name = self.vm.interner.str(ident.name).to_string();
-                report(
-                    self.vm,
-                    self.fct.file,
-                    ident.pos,
-                    SemError::UnknownIdentifier(name),
-                );
+            (term_sym, type_sym) => unreachable!(format!("{:?} {:?}", term_sym, type_sym)),
+        }
+    }
+
+    fn check_expr_match(&mut self, expr: &'ast ExprMatchType) {
+        self.visit_expr(&expr.expr);
+
+        for case in &expr.cases {
+            self.vm.sym.lock().push_level();
+            self.check_match_pattern(&case.pattern);
+            self.visit_expr(&case.value);
+            self.vm.sym.lock().pop_level();
+        }
+    }
+
+    fn check_match_pattern(&mut self, pattern: &'ast MatchPattern) {
+        match *pattern {
+            MatchPattern::Ident(ref ident) => {
+                // classes without fields are matched by name, e.g. `None`
+                let type_sym = self.vm.sym.lock().get_type_in(self.fct.file, ident.name);
+
+                if let Some(SymClass(_)) = type_sym {
+                    return;
+                }
+
+                let var_ctxt = Var {
+                    id: VarId(0),
+                    name: ident.name,
+                    reassignable: false,
+                    captured: false,
+                    ty: BuiltinType::Unit,
+                    node_id: ident.id,
+                    pos: ident.pos,
+                    shadows: None,
+                };
+
+                let var_id = self.add_var(var_ctxt);
+                self.src.map_vars.insert(ident.id, var_id);
             }
 
-            (term_sym, type_sym) => unreachable!(format!("{:?} {:?}", term_sym, type_sym)),
+            MatchPattern::Tuple(ref tuple) => {
+                for param in &tuple.params {
+                    self.check_match_pattern(param);
+                }
+            }
+
+            MatchPattern::Variant(ref variant) => {
+                for param in variant.params.iter().flatten() {
+                    self.check_match_pattern(param);
+                }
+            }
+
+            MatchPattern::Underscore(_) | MatchPattern::Lit(_) => {}
         }
     }
 
     fn check_expr_path(&mut self, path: &'ast ExprPathType) {
+        if let Some(package_id) = self.package_of_path(&path.lhs) {
+            self.check_expr_path_package(path, package_id);
+            return;
+        }
+
         self.visit_expr(&path.lhs);
         // do not check right hand site of path
     }
 
+    // returns the package if the left hand side of the path names one,
+    // local names take precedence over package names
+    fn package_of_path(&self, lhs: &'ast Expr) -> Option<PackageId> {
+        let mut names = Vec::new();
+        let mut expr = lhs;
+
+        while let Some(path) = expr.to_path() {
+            names.push(path.rhs.to_ident()?.name);
+            expr = &path.lhs;
+        }
+
+        let first = expr.to_ident()?.name;
+        names.push(first);
+        names.reverse();
+
+        let sym = self.vm.sym.lock();
+
+        if sym.get_term_in(self.fct.file, first).is_some()
+            || sym.get_type_in(self.fct.file, first).is_some()
+        {
+            return None;
+        }
+
+        sym.find_package(&names)
+    }
+
+    fn check_expr_path_package(&mut self, path: &'ast ExprPathType, package_id: PackageId) {
+        let name = if let Some(ident) = path.rhs.to_ident() {
+            ident.name
+        } else {
+            report(
+                self.vm,
+                self.fct.file,
+                path.rhs.pos(),
+                SemError::ExpectedSomeIdentifier,
+            );
+            return;
+        };
+
+        if let Err(msg) = check_package_member(self.vm, self.fct.file, package_id, name) {
+            report(self.vm, self.fct.file, path.pos, msg);
+            return;
+        }
+
+        let (term_sym, type_sym) = {
+            let sym = self.vm.sym.lock();
+            let package = &sym.package(package_id).table;
+            (
+                package.get_term(name).cloned(),
+                package.get_type(name).cloned(),
+            )
+        };
+
+        self.map_ident(path.id, term_sym, type_sym);
+    }
+
     fn check_expr_dot(&mut self, dot: &'ast ExprDotType) {
         self.visit_expr(&dot.lhs);
         // do not check right hand site of dot
@@ -301,15 +588,18 @@ impl<'a, 'ast> Visitor<'ast> for NameCheck<'a, 'ast> {
             id: VarId(0),
             name: p.name,
             reassignable: false,
+            captured: false,
             ty: BuiltinType::Unit,
             node_id: p.id,
+            pos: p.pos,
+            shadows: None,
         };
 
         // params are only allowed to replace functions, vars cannot be replaced
-        let term_sym = self.vm.sym.lock().get_term(p.name);
+        let term_sym = self.vm.sym.lock().get_term_in(self.fct.file, p.name);
         match term_sym {
             Some(SymFct(_)) | None => {
-                let var_id = self.add_var(var_ctxt, p.pos);
+                let var_id = self.add_var(var_ctxt);
                 self.src.map_vars.insert(p.id, var_id)
             }
             Some(conflict_sym) => {
@@ -322,6 +612,7 @@ impl<'a, 'ast> Visitor<'ast> for NameCheck<'a, 'ast> {
         match *s {
             StmtVar(ref stmt) => self.check_stmt_var(stmt),
             StmtFor(ref stmt) => self.check_stmt_for(stmt),
+            StmtDo(ref stmt) => self.check_stmt_do(stmt),
 
             // no need to handle rest of statements
             _ => visit::walk_stmt(self, s),
@@ -334,6 +625,12 @@ impl<'a, 'ast> Visitor<'ast> for NameCheck<'a, 'ast> {
             &ExprPath(ref path) => self.check_expr_path(path),
             &ExprDot(ref dot) => self.check_expr_dot(dot),
             &ExprBlock(ref block) => self.check_expr_block(block),
+            &ExprLambda(ref lambda) => self.check_expr_lambda(lambda),
+            &ExprMatch(ref expr) => self.check_expr_match(expr),
+            &ExprSelf(_) | &ExprSuper(_) if self.fct.has_self() => {
+                let var_id = self.src.var_self().id;
+                self.capture_var(var_id);
+            }
 
             // no need to handle rest of expressions
             _ => visit::walk_expr(self, e),
@@ -387,6 +684,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lambda_params_and_captures() {
+        ok("fun f() { let a = 1; || -> Int32 { a }; }");
+        ok("fun f() { let a = 1; |a: Int32| -> Int32 { a }; }");
+        ok("fun f(a: Int32) { || { || -> Int32 { a }; }; }");
+        err(
+            "fun f() { |a: Int32| {}; a; }",
+            pos(1, 26),
+            SemError::UnknownIdentifier("a".into()),
+        );
+    }
+
     #[test]
     fn shadow_type_with_var() {
         ok("fun test() { let String = 3; }");
@@ -478,4 +787,61 @@ mod tests {
     fn for_var() {
         ok("fun f() { for i in range(0, 4) { i; } }");
     }
+
+    #[test]
+    fn catch_var() {
+        ok("fun f() { do { } catch e: Exception { e; } }");
+        err(
+            "fun f() { do { } catch e: Exception { } e; }",
+            pos(1, 41),
+            SemError::UnknownIdentifier("e".into()),
+        );
+    }
+
+    #[test]
+    fn qualified_package_path() {
+        ok_files(&[
+            (
+                "foo::bar",
+                "@pub fun g() -> Int32 { 1 } @pub class A @pub const C: Int32 = 2;",
+            ),
+            (
+                "",
+                "fun f() -> Int32 { foo::bar::A(); foo::bar::g() + foo::bar::C }",
+            ),
+        ]);
+        ok_files(&[
+            (
+                "foo",
+                "@pub enum E { A, B } @pub class X { @static fun make() -> X { X() } }",
+            ),
+            ("", "fun f() { foo::E::A; foo::X::make(); }"),
+        ]);
+    }
+
+    #[test]
+    fn qualified_package_path_private() {
+        err_files(
+            &[("foo", "fun g() {}"), ("", "fun f() { foo::g(); }")],
+            pos(1, 14),
+            SemError::NotPublic("foo".into(), "g".into()),
+        );
+        err_files(
+            &[("foo", "fun g() {}"), ("", "fun f() { foo::h(); }")],
+            pos(1, 14),
+            SemError::UnknownPackageMember("foo".into(), "h".into()),
+        );
+    }
+
+    #[test]
+    fn local_shadows_package() {
+        err_files(
+            &[
+                ("foo", "@pub fun g() {}"),
+                ("", "fun f(foo: Int32) { foo::g(); }"),
+            ],
+            pos(1, 21),
+            SemError::InvalidLeftSideOfSeparator,
+        );
+    }
 }
@@ -47,6 +47,16 @@ pub fn internal_classes<'ast>(vm: &mut VM<'ast>) {
 
     vm.vips.stacktrace_class = internal_class(vm, "Stacktrace", None);
     vm.vips.stacktrace_element_class = internal_class(vm, "StacktraceElement", None);
+    vm.vips.exception_class = internal_class(vm, "Exception", None);
+
+    vm.vips.weak_ref_class = internal_class(vm, "WeakRef", None);
+    vm.vips.ephemeron_class = internal_class(vm, "Ephemeron", None);
+
+    for &cls_id in &[vm.vips.weak_ref_class, vm.vips.ephemeron_class] {
+        let cls = vm.classes.idx(cls_id);
+        let mut cls = cls.write();
+        cls.is_weak = true;
+    }
 
     vm.vips.stringable_trait = find_trait(vm, "Stringable");
     vm.vips.zero_trait = find_trait(vm, "Zero");
@@ -76,6 +86,7 @@ fn internal_free_classes<'ast>(vm: &mut VM<'ast>) {
         class_defs.push(Arc::new(RwLock::new(ClassDef {
             id: free_object,
             cls_id: None,
+            lambda: None,
             type_params: TypeList::empty(),
             parent_id: None,
             size: InstanceSize::Fixed(Header::size()),
@@ -87,6 +98,7 @@ fn internal_free_classes<'ast>(vm: &mut VM<'ast>) {
         class_defs.push(Arc::new(RwLock::new(ClassDef {
             id: free_array,
             cls_id: None,
+            lambda: None,
             type_params: TypeList::empty(),
             parent_id: None,
             size: InstanceSize::FreeArray,
@@ -184,6 +196,10 @@ pub fn internal_functions<'ast>(vm: &mut VM<'ast>) {
     native_fct(vm, "argc", stdlib::argc as *const u8);
     native_fct(vm, "argv", stdlib::argv as *const u8);
     native_fct(vm, "forceCollect", stdlib::gc_collect as *const u8);
+    native_fct(vm, "dumpHeap", stdlib::dump_heap as *const u8);
+    native_fct(vm, "registerCleanup", stdlib::register_cleanup as *const u8);
+    native_fct(vm, "nextCleanup", stdlib::next_cleanup as *const u8);
+    native_fct(vm, "onOutOfMemory", stdlib::on_out_of_memory as *const u8);
     native_fct(vm, "timestamp", stdlib::timestamp as *const u8);
     native_fct(
         vm,
@@ -424,6 +440,21 @@ pub fn internal_functions<'ast>(vm: &mut VM<'ast>) {
     if let Some(clsid) = clsid {
         native_class_method(vm, clsid, "start", stdlib::spawn_thread as *const u8);
     }
+
+    for &clsid in &[vm.vips.weak_ref_class, vm.vips.ephemeron_class] {
+        native_class_method(
+            vm,
+            clsid,
+            "register",
+            stdlib::register_weak_ref as *const u8,
+        );
+        native_class_method(
+            vm,
+            clsid,
+            "isCleared",
+            stdlib::weak_ref_cleared as *const u8,
+        );
+    }
 }
 
 fn native_class_method<'ast>(vm: &mut VM<'ast>, clsid: ClassId, name: &str, fctptr: *const u8) {
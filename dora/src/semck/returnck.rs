@@ -9,7 +9,7 @@ pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        if !fct.is_src() || !vm.is_checked_file(fct.file) || vm.has_cached_bytecode(fct.id) {
             continue;
         }
 
@@ -64,13 +64,27 @@ pub fn returns_value(s: &Stmt) -> Result<(), Position> {
         StmtContinue(ref stmt) => Err(stmt.pos),
         StmtVar(ref stmt) => Err(stmt.pos),
         StmtExpr(ref stmt) => expr_returns_value(&stmt.expr),
+        StmtThrow(_) => Ok(()),
+        StmtDo(ref stmt) => do_returns_value(stmt),
+        StmtDefer(ref stmt) => Err(stmt.pos),
     }
 }
 
+fn do_returns_value(s: &StmtDoType) -> Result<(), Position> {
+    returns_value(&s.do_block)?;
+
+    for catch in &s.catch_blocks {
+        returns_value(&catch.block)?;
+    }
+
+    Ok(())
+}
+
 pub fn expr_returns_value(e: &Expr) -> Result<(), Position> {
     match *e {
         Expr::ExprBlock(ref block) => expr_block_returns_value(block),
         Expr::ExprIf(ref expr) => expr_if_returns_value(expr),
+        Expr::ExprMatch(ref expr) => expr_match_returns_value(expr),
         _ => Err(e.pos()),
     }
 }
@@ -101,6 +115,18 @@ fn expr_if_returns_value(e: &ExprIfType) -> Result<(), Position> {
     }
 }
 
+fn expr_match_returns_value(e: &ExprMatchType) -> Result<(), Position> {
+    if e.cases.is_empty() {
+        return Err(e.pos);
+    }
+
+    for case in &e.cases {
+        expr_returns_value(&case.value)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::error::msg::SemError;
@@ -137,6 +163,27 @@ mod tests {
             "fun f() -> Int32 { if true { return 1; } else { return 2; } }",
             true,
         );
+        test_always_returns(
+            "fun f(x: Bool) -> Int32 { match x { true => { return 1; }, _ => { return 2; } } }",
+            true,
+        );
+        test_always_returns(
+            "fun f(x: Bool) -> Int32 { match x { true => { return 1; }, _ => 2 } }",
+            false,
+        );
+        test_always_returns(
+            "fun f() throws -> Int32 { throw Exception(\"fail\"); }",
+            true,
+        );
+        test_always_returns(
+            "fun f() -> Int32 { do { return 1; } catch e: Exception { return 2; } }",
+            true,
+        );
+        test_always_returns(
+            "fun f() -> Int32 { do { return 1; } catch e: Exception { } return 2; }",
+            true,
+        );
+        test_always_returns("fun f() { do { return; } catch e: Exception { } }", false);
     }
 
     #[test]
@@ -9,7 +9,8 @@ use crate::size::InstanceSize;
 use crate::ty::{BuiltinType, TypeList};
 use crate::vm::{
     ensure_tuple, CallType, Class, ClassDef, ClassDefId, ClassId, EnumData, EnumDef, EnumDefId,
-    EnumId, EnumLayout, FieldDef, StructData, StructDef, StructDefId, StructFieldDef, StructId, VM,
+    EnumDefVariant, EnumId, EnumLayout, FctDefId, FctSrc, FieldDef, StructData, StructDef,
+    StructDefId, StructFieldDef, StructId, VM,
 };
 use crate::vtable::{VTableBox, DISPLAY_SIZE};
 
@@ -64,6 +65,8 @@ fn create_specialized_struct(vm: &VM, struc: &StructData, type_params: TypeList)
         assert!(old.is_none());
 
         struct_defs.push(Arc::new(Mutex::new(StructDef {
+            struct_id: struc.id,
+            type_params: type_params.clone(),
             size: 0,
             align: 0,
             fields: Vec::new(),
@@ -91,11 +94,13 @@ fn create_specialized_struct(vm: &VM, struc: &StructData, type_params: TypeList)
         size = offset + field_size;
         align = max(align, field_align);
 
-        if ty.reference_type() {
-            ref_fields.push(offset);
+        for ref_offset in ty.reference_offsets(vm) {
+            ref_fields.push(offset + ref_offset);
         }
     }
 
+    size = mem::align_i32(size, align);
+
     let struct_def = vm.struct_defs.idx(id);
     let mut struct_def = struct_def.lock();
     struct_def.size = size;
@@ -134,20 +139,51 @@ fn create_specialized_enum(vm: &VM, xenum: &EnumData, type_params: TypeList) ->
         enum_defs.push(Arc::new(RwLock::new(EnumDef {
             id,
             enum_id: xenum.id,
-            type_params,
+            type_params: type_params.clone(),
             size: 0,
             align: 0,
             layout: EnumLayout::Int,
+            variants: Vec::new(),
         })));
 
         id
     };
 
+    let layout = xenum.layout();
+    let mut variants = Vec::with_capacity(xenum.variants.len());
+
+    for variant in &xenum.variants {
+        let types = variant
+            .types
+            .iter()
+            .map(|&ty| specialize_type(vm, ty, &type_params, &TypeList::empty()))
+            .collect::<Vec<_>>();
+
+        let cls_def_id = match layout {
+            EnumLayout::Int => None,
+            EnumLayout::Tagged => {
+                let mut field_types = Vec::with_capacity(types.len() + 1);
+                field_types.push(BuiltinType::Int32);
+                field_types.extend_from_slice(&types);
+
+                Some(create_class_def_with_fields(vm, None, &field_types, 0))
+            }
+        };
+
+        variants.push(EnumDefVariant { types, cls_def_id });
+    }
+
+    let (size, align) = match layout {
+        EnumLayout::Int => (4, 4),
+        EnumLayout::Tagged => (mem::ptr_width(), mem::ptr_width()),
+    };
+
     let enum_def = vm.enum_defs.idx(id);
     let mut enum_def = enum_def.write();
-    enum_def.size = 4;
-    enum_def.align = 4;
-    enum_def.layout = EnumLayout::Int;
+    enum_def.size = size;
+    enum_def.align = align;
+    enum_def.layout = layout;
+    enum_def.variants = variants;
 
     id
 }
@@ -196,6 +232,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
         class_defs.push(Arc::new(RwLock::new(ClassDef {
             id,
             cls_id: Some(cls.id),
+            lambda: None,
             type_params: type_params.clone(),
             parent_id: None,
             size: InstanceSize::Fixed(0),
@@ -221,20 +258,15 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
 
             if element_ty.is_unit() {
                 InstanceSize::UnitArray
-            } else if element_ty.reference_type() {
+            } else if element_ty.reference_type(vm) {
                 InstanceSize::ObjArray
-            } else if let Some(tuple_id) = element_ty.tuple_id() {
-                let tuples = vm.tuples.lock();
-                let tuple = tuples.get_tuple(tuple_id);
-
-                if tuple.contains_references() {
-                    for &offset in tuple.references() {
-                        ref_fields.push(offset);
-                    }
+            } else if element_ty.is_tuple_or_struct() {
+                ref_fields = element_ty.reference_offsets(vm);
 
-                    InstanceSize::TupleArray(tuple.size())
+                if ref_fields.is_empty() {
+                    InstanceSize::PrimitiveArray(element_ty.size(vm))
                 } else {
-                    InstanceSize::PrimitiveArray(tuple.size())
+                    InstanceSize::TupleArray(element_ty.size(vm))
                 }
             } else {
                 InstanceSize::PrimitiveArray(element_ty.size(vm))
@@ -283,23 +315,26 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
 
             csize = offset + field_size;
 
-            if let Some(tuple_id) = ty.tuple_id() {
-                let tuples = vm.tuples.lock();
-                let tuple = tuples.get_tuple(tuple_id);
-
-                for &ref_offset in tuple.references() {
-                    ref_fields.push(offset + ref_offset);
-                }
-            } else if ty.reference_type() {
-                ref_fields.push(offset);
+            for ref_offset in ty.reference_offsets(vm) {
+                ref_fields.push(offset + ref_offset);
             }
         }
 
+        // weak fields are processed by the GC, see gc::weak
+        if cls.is_weak && vm.gc.weak_refs().is_some() && fields[0].ty.reference_type(vm) {
+            ref_fields.retain(|&offset| {
+                !fields
+                    .iter()
+                    .any(|f| f.offset == offset && f.ty.reference_type(vm))
+            });
+        }
+
         size = InstanceSize::Fixed(mem::align_i32(csize, mem::ptr_width()));
     }
 
     let stub = vm.compile_stub().to_usize();
     let vtable_entries = vec![stub; cls.virtual_fcts.len()];
+    let itable_method_counts = itable_method_counts(vm, cls);
 
     let cls_def = vm.class_defs.idx(id);
     let mut cls_def = cls_def.write();
@@ -319,7 +354,189 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
     };
 
     let clsptr = (&*cls_def) as *const ClassDef as *mut ClassDef;
-    let vtable = VTableBox::new(clsptr, instance_size, element_size, &vtable_entries);
+    let mut vtable = VTableBox::new(clsptr, instance_size, element_size, &vtable_entries);
+    vtable.allocate_itables(&itable_method_counts, stub);
+    cls_def.vtable = Some(vtable);
+
+    ensure_display(vm, &mut cls_def);
+
+    id
+}
+
+// Classes specialized by an earlier check, e.g. of a REPL input, need
+// itables for traits implemented by impls of the newly checked files.
+pub fn update_itables(vm: &VM) {
+    let stub = vm.compile_stub().to_usize();
+
+    for cls_def in vm.class_defs.iter() {
+        let mut cls_def = cls_def.write();
+
+        let cls_id = match cls_def.cls_id {
+            Some(cls_id) => cls_id,
+            None => continue,
+        };
+
+        let method_counts = {
+            let cls = vm.classes.idx(cls_id);
+            let cls = cls.read();
+            itable_method_counts(vm, &cls)
+        };
+
+        let vtable = cls_def.vtable.as_mut().expect("missing vtable");
+
+        let missing = method_counts
+            .iter()
+            .enumerate()
+            .any(|(idx, &methods)| methods > 0 && !vtable.has_itable(idx));
+
+        if missing {
+            vtable.reallocate_itables(&method_counts, stub);
+        }
+    }
+}
+
+// number of methods for each trait implemented by the class or one of its
+// super classes, all other traits get an empty itable
+fn itable_method_counts(vm: &VM, cls: &Class) -> Vec<usize> {
+    let mut method_counts = vec![0; vm.traits.len()];
+    let mut impls = cls.impls.clone();
+    let mut parent_class = cls.parent_class;
+
+    while let Some(parent) = parent_class {
+        let parent_id = parent.cls_id(vm).expect("no class");
+        let parent = vm.classes.idx(parent_id);
+        let parent = parent.read();
+
+        impls.extend_from_slice(&parent.impls);
+        parent_class = parent.parent_class;
+    }
+
+    for impl_id in impls {
+        let ximpl = vm.impls[impl_id].read();
+
+        if let Some(trait_id) = ximpl.trait_id {
+            let xtrait = vm.traits[trait_id].read();
+            method_counts[trait_id.to_usize()] = xtrait.methods.len();
+        }
+    }
+
+    method_counts
+}
+
+pub fn specialize_lambda(vm: &VM, src: &FctSrc, fct_def_id: FctDefId) -> ClassDefId {
+    let mut lambda_defs = vm.lambda_defs.lock();
+
+    if let Some(&id) = lambda_defs.get(&fct_def_id) {
+        return id;
+    }
+
+    let fct_def = vm.fct_defs.idx(fct_def_id);
+    let fct_def = fct_def.read();
+
+    let fct = vm.fcts.idx(fct_def.fct_id);
+    let fct = fct.read();
+
+    let lambda = src.map_lambdas.get(fct.ast.id).expect("lambda not found");
+
+    let field_types = lambda
+        .captures
+        .iter()
+        .map(|&var_id| {
+            let var = &src.vars[var_id];
+            let ty = specialize_type(
+                vm,
+                var.ty,
+                &fct_def.cls_type_params,
+                &fct_def.fct_type_params,
+            );
+
+            if var.reassignable && !ty.is_unit() {
+                // reassignable variables are captured by reference through their box
+                BuiltinType::Ptr
+            } else {
+                ty
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the only vtable entry is the code of the lambda
+    let id = create_class_def_with_fields(vm, Some(fct_def_id), &field_types, 1);
+    lambda_defs.insert(fct_def_id, id);
+
+    id
+}
+
+pub fn specialize_box(vm: &VM, ty: BuiltinType) -> ClassDefId {
+    let mut box_defs = vm.box_defs.lock();
+
+    if let Some(&id) = box_defs.get(&ty) {
+        return id;
+    }
+
+    let id = create_class_def_with_fields(vm, None, &[ty], 0);
+    box_defs.insert(ty, id);
+
+    id
+}
+
+fn create_class_def_with_fields(
+    vm: &VM,
+    lambda: Option<FctDefId>,
+    field_types: &[BuiltinType],
+    vtable_len: usize,
+) -> ClassDefId {
+    debug_assert!(field_types.iter().all(|ty| ty.is_concrete_type(vm)));
+
+    let id = {
+        let mut class_defs = vm.class_defs.lock();
+        let id: ClassDefId = class_defs.len().into();
+
+        class_defs.push(Arc::new(RwLock::new(ClassDef {
+            id,
+            cls_id: None,
+            lambda,
+            type_params: TypeList::empty(),
+            parent_id: None,
+            size: InstanceSize::Fixed(0),
+            fields: Vec::new(),
+            ref_fields: Vec::new(),
+            vtable: None,
+        })));
+
+        id
+    };
+
+    let mut fields = Vec::with_capacity(field_types.len());
+    let mut ref_fields = Vec::new();
+    let mut csize = Header::size();
+
+    for &ty in field_types {
+        let field_size = ty.size(vm);
+        let field_align = ty.align(vm);
+
+        let offset = mem::align_i32(csize, field_align);
+        fields.push(FieldDef { offset, ty });
+
+        csize = offset + field_size;
+
+        for ref_offset in ty.reference_offsets(vm) {
+            ref_fields.push(offset + ref_offset);
+        }
+    }
+
+    let size = mem::align_i32(csize, mem::ptr_width());
+
+    let stub = vm.compile_stub().to_usize();
+    let vtable_entries = vec![stub; vtable_len];
+
+    let cls_def = vm.class_defs.idx(id);
+    let mut cls_def = cls_def.write();
+    cls_def.size = InstanceSize::Fixed(size);
+    cls_def.fields = fields;
+    cls_def.ref_fields = ref_fields;
+
+    let clsptr = (&*cls_def) as *const ClassDef as *mut ClassDef;
+    let vtable = VTableBox::new(clsptr, size as usize, 0, &vtable_entries);
     cls_def.vtable = Some(vtable);
 
     ensure_display(vm, &mut cls_def);
@@ -410,7 +627,19 @@ pub fn replace_type_param(
 
         BuiltinType::This => self_ty.expect("no type for Self given"),
 
-        BuiltinType::Lambda(_) => unimplemented!(),
+        BuiltinType::Lambda(lambda_id) => {
+            let lambda = vm.lambda_types.lock().get(lambda_id);
+
+            let params = lambda
+                .params()
+                .iter()
+                .map(|&p| replace_type_param(vm, p, cls_tp, fct_tp, self_ty))
+                .collect::<Vec<_>>();
+            let ret = replace_type_param(vm, lambda.ret(), cls_tp, fct_tp, self_ty);
+
+            let lambda_id = vm.lambda_types.lock().insert(params, ret);
+            BuiltinType::Lambda(lambda_id)
+        }
 
         BuiltinType::Tuple(tuple_id) => {
             let subtypes = {
@@ -467,10 +696,20 @@ pub fn specialize_for_call_type(call_type: &CallType, ty: BuiltinType, vm: &VM)
             specialize_type(vm, ty, &cls_type_params, &TypeList::empty())
         }
 
-        CallType::Trait(_, _) => unimplemented!(),
+        // trait methods have no type params, only `Self` refers to the trait object
+        CallType::Trait(trait_id, _) => {
+            if ty == BuiltinType::This {
+                BuiltinType::Trait(trait_id)
+            } else {
+                ty
+            }
+        }
 
         CallType::Intrinsic(_) => unimplemented!(),
 
+        // lambda types are already specialized
+        CallType::Lambda(_) => ty,
+
         CallType::TraitStatic(_, _, _) => {
             assert_ne!(ty, BuiltinType::This);
 
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use crate::error::msg::SemError;
 use crate::semck;
 use crate::ty::BuiltinType;
@@ -28,6 +30,75 @@ struct StructCheck<'x, 'ast: 'x> {
 impl<'x, 'ast> StructCheck<'x, 'ast> {
     fn check(&mut self) {
         self.visit_ast(self.ast);
+        self.check_recursive_structs();
+    }
+
+    fn check_recursive_structs(&mut self) {
+        let fields = self
+            .vm
+            .structs
+            .iter()
+            .filter(|struc| self.vm.is_checked_file(struc.lock().file))
+            .flat_map(|struc| {
+                let struc = struc.lock();
+                struc
+                    .fields
+                    .iter()
+                    .map(|field| (struc.id, struc.file, field.pos, field.ty))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for (struct_id, file, pos, ty) in fields {
+            let mut visited = HashSet::new();
+
+            if contains_struct(self.vm, ty, struct_id, &mut visited) {
+                self.vm
+                    .diag
+                    .lock()
+                    .report(file, pos, SemError::RecursiveStructure);
+            }
+        }
+    }
+}
+
+// value types are stored inline, a struct can therefore neither contain itself
+// directly nor through another struct or tuple.
+fn contains_struct(
+    vm: &VM,
+    ty: BuiltinType,
+    struct_id: StructId,
+    visited: &mut HashSet<StructId>,
+) -> bool {
+    match ty {
+        BuiltinType::Struct(id, _) => {
+            if id == struct_id {
+                return true;
+            }
+
+            if !visited.insert(id) {
+                return false;
+            }
+
+            let field_types = {
+                let struc = vm.structs.idx(id);
+                let struc = struc.lock();
+                struc.fields.iter().map(|f| f.ty).collect::<Vec<_>>()
+            };
+
+            field_types
+                .into_iter()
+                .any(|ty| contains_struct(vm, ty, struct_id, visited))
+        }
+
+        BuiltinType::Tuple(tuple_id) => {
+            let subtypes = vm.tuples.lock().get(tuple_id);
+            subtypes
+                .iter()
+                .any(|&ty| contains_struct(vm, ty, struct_id, visited))
+        }
+
+        _ => false,
     }
 }
 
@@ -93,4 +164,27 @@ mod tests {
             SemError::ShadowField("a".into()),
         );
     }
+
+    #[test]
+    fn struct_recursive() {
+        ok("struct Foo { a: Int32, bar: Bar } struct Bar { a: Int32 }");
+        ok("struct Foo { a: Array[Foo] }");
+        err(
+            "struct Foo { a: Int32, foo: Foo }",
+            pos(1, 24),
+            SemError::RecursiveStructure,
+        );
+        errors(
+            "struct Foo { bar: Bar } struct Bar { foo: Foo }",
+            &[
+                (pos(1, 14), SemError::RecursiveStructure),
+                (pos(1, 38), SemError::RecursiveStructure),
+            ],
+        );
+        err(
+            "struct Foo { a: (Int32, Foo) }",
+            pos(1, 14),
+            SemError::RecursiveStructure,
+        );
+    }
 }
@@ -20,6 +20,10 @@ fn cycle_detection<'ast>(vm: &mut VM<'ast>) {
     for cls in vm.classes.iter() {
         let cls = cls.read();
 
+        if !vm.is_checked_file(cls.file) {
+            continue;
+        }
+
         let mut map: HashSet<ClassId> = HashSet::new();
         map.insert(cls.id);
 
@@ -45,6 +49,15 @@ fn cycle_detection<'ast>(vm: &mut VM<'ast>) {
 fn determine_vtables<'ast>(vm: &VM<'ast>) {
     let mut lens = HashSet::new();
 
+    // vtables of classes checked before are already known
+    for cls in vm.classes.iter() {
+        let cls = cls.read();
+
+        if !vm.is_checked_file(cls.file) {
+            lens.insert(cls.id);
+        }
+    }
+
     for cls in vm.classes.iter() {
         let mut cls = cls.write();
         if !lens.contains(&cls.id) {
@@ -158,6 +171,10 @@ pub fn check_override<'ast>(vm: &VM<'ast>) {
     for cls in vm.classes.iter() {
         let cls = cls.read();
 
+        if !vm.is_checked_file(cls.file) {
+            continue;
+        }
+
         for &fct_id in &cls.methods {
             let fct = vm.fcts.idx(fct_id);
 
@@ -235,6 +252,7 @@ fn check_fct_modifier<'ast>(vm: &VM<'ast>, cls: &Class, fct: &Fct<'ast>) -> Opti
 
         if super_method_params != fct.params_without_self()
             || super_method_return_type != fct.return_type
+            || super_method.throws != fct.throws
         {
             vm.diag
                 .lock()
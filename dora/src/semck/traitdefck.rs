@@ -9,12 +9,13 @@ use dora_parser::ast::visit::{self, Visitor};
 use dora_parser::ast::{self, Ast};
 
 pub fn check<'ast>(vm: &mut VM<'ast>, ast: &'ast Ast, map_trait_defs: &NodeMap<TraitId>) {
+    let file_id = vm.first_checked_file();
     let mut clsck = TraitCheck {
         vm,
         ast,
         trait_id: None,
         map_trait_defs,
-        file_id: 0,
+        file_id,
     };
 
     clsck.check();
@@ -82,6 +83,7 @@ impl<'x, 'ast> Visitor<'ast> for TraitCheck<'x, 'ast> {
             internal_resolved: false,
             overrides: None,
             is_constructor: false,
+            throws: f.throws,
             vtable_index: None,
             initialized: false,
             impl_for: None,
@@ -89,6 +91,8 @@ impl<'x, 'ast> Visitor<'ast> for TraitCheck<'x, 'ast> {
             variadic_arguments: false,
 
             type_params: Vec::new(),
+
+            where_bounds: Vec::new(),
             kind: FctKind::Definition,
 
             specializations: RwLock::new(HashMap::new()),
@@ -4,9 +4,15 @@ use std::collections::hash_set::HashSet;
 
 use crate::error::msg::SemError;
 use crate::ty::{BuiltinType, TypeList};
-use crate::vm::{FileId, TraitId, TypeParam, VM};
+use crate::vm::{Fct, FileId, TraitId, TypeParam, VM};
 
-pub fn check_type(vm: &VM, file: FileId, pos: Position, object_type: BuiltinType) -> bool {
+pub fn check_type<'ast>(
+    vm: &VM<'ast>,
+    file: FileId,
+    pos: Position,
+    object_type: BuiltinType,
+    caller: Option<&Fct<'ast>>,
+) -> bool {
     let tp_defs = {
         let cls_id = object_type.cls_id(vm).expect("no class");
         let cls = vm.classes.idx(cls_id);
@@ -19,6 +25,7 @@ pub fn check_type(vm: &VM, file: FileId, pos: Position, object_type: BuiltinType
         file,
         pos,
         tp_defs: &tp_defs,
+        caller,
     };
 
     let params = object_type.type_params(vm);
@@ -26,28 +33,57 @@ pub fn check_type(vm: &VM, file: FileId, pos: Position, object_type: BuiltinType
     checker.check(&params)
 }
 
-pub fn check_params(
-    vm: &VM,
+pub fn check_params<'ast>(
+    vm: &VM<'ast>,
     file: FileId,
     pos: Position,
     tp_defs: &[TypeParam],
     params: &TypeList,
+    caller: Option<&Fct<'ast>>,
 ) -> bool {
     let checker = TypeParamCheck {
         vm,
         file,
         pos,
         tp_defs,
+        caller,
     };
 
     checker.check(params)
 }
 
+// checks the bounds a `where` clause of `callee` adds to the class type params
+pub fn check_where_bounds<'ast>(
+    vm: &VM<'ast>,
+    file: FileId,
+    pos: Position,
+    callee: &Fct,
+    cls_tps: &TypeList,
+    caller: Option<&Fct<'ast>>,
+) -> bool {
+    let cls_id = callee.cls_id();
+    let mut tp_defs = {
+        let cls = vm.classes.idx(cls_id);
+        let cls = cls.read();
+        cls.type_params
+            .iter()
+            .map(|tp| TypeParam::new(tp.name))
+            .collect::<Vec<_>>()
+    };
+
+    for &(tp_id, trait_id) in &callee.where_bounds {
+        tp_defs[tp_id.idx()].trait_bounds.insert(trait_id);
+    }
+
+    check_params(vm, file, pos, &tp_defs, cls_tps, caller)
+}
+
 struct TypeParamCheck<'a, 'ast: 'a> {
     vm: &'a VM<'ast>,
     file: FileId,
     pos: Position,
     tp_defs: &'a [TypeParam],
+    caller: Option<&'a Fct<'ast>>,
 }
 
 impl<'a, 'ast> TypeParamCheck<'a, 'ast> {
@@ -62,20 +98,25 @@ impl<'a, 'ast> TypeParamCheck<'a, 'ast> {
 
         for (tp, ty) in self.tp_defs.iter().zip(tps.iter()) {
             if ty.is_type_param() {
-                let ok = match ty {
-                    BuiltinType::ClassTypeParam(cls_id, tpid) => {
-                        let cls = self.vm.classes.idx(cls_id);
-                        let cls = cls.read();
-                        self.tp_against_definition(tp, &cls.type_params[tpid.idx()], ty)
-                    }
-
-                    BuiltinType::FctTypeParam(fct_id, tpid) => {
-                        let fct = self.vm.fcts.idx(fct_id);
-                        let fct = fct.read();
-                        self.tp_against_definition(tp, &fct.type_params[tpid.idx()], ty)
+                let ok = if let Some(caller) = self.caller {
+                    let arg = caller.type_param_def(self.vm, ty);
+                    self.tp_against_definition(tp, &arg, ty)
+                } else {
+                    match ty {
+                        BuiltinType::ClassTypeParam(cls_id, tpid) => {
+                            let cls = self.vm.classes.idx(cls_id);
+                            let cls = cls.read();
+                            self.tp_against_definition(tp, &cls.type_params[tpid.idx()], ty)
+                        }
+
+                        BuiltinType::FctTypeParam(fct_id, tpid) => {
+                            let fct = self.vm.fcts.idx(fct_id);
+                            let fct = fct.read();
+                            self.tp_against_definition(tp, &fct.type_params[tpid.idx()], ty)
+                        }
+
+                        _ => unreachable!(),
                     }
-
-                    _ => unreachable!(),
                 };
 
                 if !ok {
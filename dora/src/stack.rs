@@ -1,12 +1,15 @@
+use std::io::{self, Write};
 use std::ptr;
 
 use crate::compiler::fct::JitFctId;
 use crate::compiler::map::CodeDescriptor;
+use crate::gc::Address;
 use crate::handle::{root, Handle};
-use crate::object::{alloc, Array, Int32Array, Ref, Stacktrace, StacktraceElement, Str};
+use crate::object::{alloc, Array, Int32Array, Obj, Ref, Stacktrace, StacktraceElement, Str};
 use crate::threads::THREAD;
-use crate::vm::{get_vm, FctParent, VM};
+use crate::vm::{get_vm, ClassDefId, FctParent, VM};
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct NativeStacktrace {
     elems: Vec<StackElem>,
 }
@@ -24,6 +27,29 @@ impl NativeStacktrace {
         self.elems.push(StackElem { fct_id, lineno });
     }
 
+    // frames from outermost to innermost separated by semicolons, the
+    // folded stack format read by flame graph tools
+    pub fn folded(&self, vm: &VM) -> String {
+        let frames: Vec<String> = self
+            .elems
+            .iter()
+            .rev()
+            .map(|elem| {
+                let jit_fct = vm.jit_fcts.idx(elem.fct_id);
+                let fct = vm.fcts.idx(jit_fct.fct_id());
+                let fct = fct.read();
+
+                if elem.lineno == 0 {
+                    fct.full_name(vm)
+                } else {
+                    format!("{}:{}", fct.full_name(vm), elem.lineno)
+                }
+            })
+            .collect();
+
+        frames.join(";")
+    }
+
     pub fn dump(&self, vm: &VM) {
         let frames = self.elems.len();
         for (ind, elem) in self.elems.iter().enumerate() {
@@ -61,6 +87,7 @@ impl NativeStacktrace {
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct StackElem {
     fct_id: JitFctId,
     lineno: i32,
@@ -106,6 +133,41 @@ pub fn stacktrace_from_last_dtn(vm: &VM) -> NativeStacktrace {
     return stacktrace;
 }
 
+// symbolizes return addresses sampled by the profiler, innermost first. The
+// innermost address can be anywhere in a function, its line is unknown.
+pub fn stacktrace_from_pcs(vm: &VM, pcs: &[usize]) -> NativeStacktrace {
+    let mut stacktrace = NativeStacktrace::new();
+    let code_map = vm.code_map.lock();
+
+    for (ind, &pc) in pcs.iter().enumerate() {
+        match code_map.get(pc.into()) {
+            Some(CodeDescriptor::DoraFct(fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(fct_id);
+
+                let lineno = if ind == 0 {
+                    0
+                } else {
+                    let offset = pc - jit_fct.instruction_start().to_usize();
+                    jit_fct
+                        .position_for_offset(offset as u32)
+                        .map(|position| position.line as i32)
+                        .unwrap_or(0)
+                };
+
+                stacktrace.push_entry(fct_id, lineno);
+            }
+
+            Some(CodeDescriptor::NativeStub(fct_id)) => {
+                stacktrace.push_entry(fct_id, 0);
+            }
+
+            _ => {}
+        }
+    }
+
+    stacktrace
+}
+
 fn frames_from_dtns(stacktrace: &mut NativeStacktrace, vm: &VM) {
     let mut dtn_ptr = THREAD.with(|thread| {
         let thread = thread.borrow();
@@ -171,9 +233,11 @@ fn determine_stack_entry(stacktrace: &mut NativeStacktrace, vm: &VM, pc: usize)
         }
 
         Some(CodeDescriptor::TrapStub) => true,
+        Some(CodeDescriptor::ThrowStub) => true,
         Some(CodeDescriptor::GuardCheckStub) => true,
         Some(CodeDescriptor::CompileStub) => true,
         Some(CodeDescriptor::AllocStub) => true,
+        Some(CodeDescriptor::BarrierStub) => true,
         Some(CodeDescriptor::DoraStub) => false,
 
         _ => {
@@ -184,12 +248,114 @@ fn determine_stack_entry(stacktrace: &mut NativeStacktrace, vm: &VM, pc: usize)
     }
 }
 
+#[repr(C)]
 pub struct ThrowResume {
     pc: usize,
     sp: usize,
     fp: usize,
 }
 
+impl ThrowResume {
+    pub fn pc_offset() -> i32 {
+        offset_of!(ThrowResume, pc) as i32
+    }
+
+    pub fn sp_offset() -> i32 {
+        offset_of!(ThrowResume, sp) as i32
+    }
+
+    pub fn fp_offset() -> i32 {
+        offset_of!(ThrowResume, fp) as i32
+    }
+}
+
+pub extern "C" fn throw_exception(exception: Handle<Obj>, resume: *mut ThrowResume) {
+    let vm = get_vm();
+    let exception_cls_id = exception.header().vtbl().class().id;
+
+    let dtn = THREAD.with(|thread| thread.borrow().dtn());
+    let dtn = unsafe { &*dtn };
+
+    // starts with the frame of the throw stub
+    let mut fp = dtn.fp;
+
+    while fp != 0 {
+        let ra = unsafe { *((fp + 8) as *const usize) };
+        let caller_fp = unsafe { *(fp as *const usize) };
+
+        let data = {
+            let code_map = vm.code_map.lock();
+            code_map.get(ra.into())
+        };
+
+        match data {
+            Some(CodeDescriptor::DoraFct(jit_fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                let code = jit_fct.to_code().expect("code expected");
+                let offset = ra - code.instruction_start().to_usize();
+
+                for handler in code.exception_handlers() {
+                    // the return address is right after the call
+                    if handler.try_start < offset
+                        && offset <= handler.try_end
+                        && catches(vm, handler.catch_type, exception_cls_id)
+                    {
+                        unsafe {
+                            let slot = (caller_fp as isize + handler.offset as isize) as *mut usize;
+                            *slot = exception.direct().address().to_usize();
+
+                            let resume = &mut *resume;
+                            resume.pc = code.instruction_start().to_usize() + handler.catch;
+                            resume.fp = caller_fp;
+                            resume.sp = caller_fp - code.framesize() as usize;
+                        }
+
+                        return;
+                    }
+                }
+            }
+
+            Some(CodeDescriptor::DoraStub) | None => break,
+            _ => {}
+        }
+
+        fp = caller_fp;
+    }
+
+    let cls_def = vm.class_defs.idx(exception_cls_id);
+    let cls_def = cls_def.read();
+    eprintln!("uncaught exception {}", cls_def.name(vm));
+    let stacktrace = stacktrace_from_last_dtn(vm);
+    stacktrace.dump_err(vm);
+
+    // _exit skips the buffered output of the program
+    io::stdout().flush().expect("flushing stdout failed");
+    io::stderr().flush().expect("flushing stderr failed");
+
+    unsafe {
+        libc::_exit(108);
+    }
+}
+
+fn catches(vm: &VM, catch_type: Option<ClassDefId>, cls_def_id: ClassDefId) -> bool {
+    let catch_type = match catch_type {
+        Some(catch_type) => catch_type,
+        None => return true,
+    };
+
+    let mut current = Some(cls_def_id);
+
+    while let Some(cls_def_id) = current {
+        if cls_def_id == catch_type {
+            return true;
+        }
+
+        current = vm.class_defs.idx(cls_def_id).read().parent_id;
+    }
+
+    false
+}
+
 pub extern "C" fn retrieve_stack_trace(obj: Handle<Stacktrace>) {
     let vm = get_vm();
     set_backtrace(vm, obj, true);
@@ -214,12 +380,17 @@ pub extern "C" fn stack_element(obj: Handle<Stacktrace>, ind: i32) -> Ref<Stackt
     let fct = vm.fcts.idx(jit_fct.fct_id());
     let fct = fct.read();
     let name = fct.full_name(vm);
-    ste.name = Str::from_buffer(vm, name.as_bytes());
+    let name = Str::from_buffer(vm, name.as_bytes());
+    vm.gc.write_field(
+        ste.direct().address(),
+        Address::from_ptr(&ste.name),
+        name.address(),
+    );
 
     ste.direct()
 }
 
-fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
+fn set_backtrace(vm: &VM, obj: Handle<Stacktrace>, via_retrieve: bool) {
     let stacktrace = stacktrace_from_last_dtn(vm);
     let mut skip = 0;
 
@@ -298,5 +469,10 @@ fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
         array.set_at(i + 1, elem.fct_id.idx() as i32);
         i += 2;
     }
-    obj.backtrace = array.direct();
+    // the allocation might have promoted obj already
+    vm.gc.write_field(
+        obj.direct().address(),
+        Address::from_ptr(&obj.backtrace),
+        array.direct().address(),
+    );
 }
@@ -8,14 +8,18 @@ use std::thread;
 use std::time::Duration;
 
 use crate::boots;
+use crate::gc::heapdump;
+use crate::gc::root::Slot;
+use crate::gc::weak::WeakKind;
 use crate::gc::{Address, GcReason};
-use crate::handle::{scope as handle_scope, Handle};
+use crate::handle::{root, scope as handle_scope, Handle};
 use crate::object::{Obj, Ref, Str, UInt8Array};
+use crate::profiler;
 use crate::stack::stacktrace_from_last_dtn;
 use crate::sym::TermSym::SymFct;
 use crate::threads::{DoraThread, STACK_SIZE, THREAD};
 use crate::ty::TypeList;
-use crate::vm::{get_vm, stack_pointer, Trap};
+use crate::vm::{get_vm, stack_pointer, ClassDef, Trap, VM};
 
 pub extern "C" fn uint8_to_string(val: u8) -> Ref<Str> {
     handle_scope(|| {
@@ -191,7 +195,65 @@ pub extern "C" fn str_from_bytes(val: Handle<UInt8Array>, offset: usize, len: us
 
 pub extern "C" fn gc_alloc(size: usize, array_ref: bool) -> *mut Obj {
     let vm = get_vm();
-    vm.gc.alloc(vm, size, array_ref).to_mut_ptr()
+    let mut ptr = vm.gc.alloc(vm, size, array_ref);
+
+    if ptr.is_null() && run_oom_handler(vm) {
+        // the handler might have dropped references to old objects
+        vm.gc.collect(vm, GcReason::AllocationFailure);
+        ptr = vm.gc.alloc(vm, size, array_ref);
+    }
+
+    if ptr.is_null() && vm.args.flag_heap_dump_on_oom {
+        heapdump::dump_heap_on_oom(vm);
+    }
+
+    ptr.to_mut_ptr()
+}
+
+// Runs the handler registered with `onOutOfMemory`, returns false if there
+// is none. The handler is removed before it runs, running out of memory
+// again within the handler fails.
+fn run_oom_handler(vm: &VM) -> bool {
+    let handler = vm.gc.take_oom_handler();
+
+    if handler.is_null() {
+        return false;
+    }
+
+    handle_scope(|| {
+        let handler: Handle<Obj> = root(handler.into());
+
+        let run = {
+            let cls_id = handler.header().vtbl().class().cls_id;
+            let cls_id = cls_id.expect("no corresponding class");
+            let cls = vm.classes.idx(cls_id);
+            let cls = cls.read();
+            let name = vm.interner.intern("run");
+            cls.find_method(vm, name, false)
+                .expect("run() method not found")
+        };
+
+        let fct_ptr = vm.ensure_compiled(run);
+
+        let tld = THREAD.with(|thread| {
+            let thread = thread.borrow();
+            let ptr = &thread.tld;
+
+            Address::from_ptr(ptr as *const _)
+        });
+
+        let dora_stub_address = vm.dora_stub();
+        let fct: extern "C" fn(Address, Address, Ref<Obj>) =
+            unsafe { mem::transmute(dora_stub_address) };
+        fct(tld, fct_ptr, handler.direct());
+    });
+
+    true
+}
+
+pub extern "C" fn on_out_of_memory(handler: Handle<Obj>) {
+    let vm = get_vm();
+    vm.gc.set_oom_handler(handler.direct().address());
 }
 
 pub extern "C" fn gc_collect() {
@@ -204,6 +266,92 @@ pub extern "C" fn gc_minor_collect() {
     vm.gc.minor_collect(vm, GcReason::ForceMinorCollect);
 }
 
+pub extern "C" fn gc_pre_write_barrier(object: Handle<Obj>) {
+    let vm = get_vm();
+    vm.gc.pre_write_barrier(object.direct().address());
+}
+
+pub extern "C" fn dump_heap(path: Handle<Str>) {
+    let vm = get_vm();
+    let path = path.to_cstring().into_string().expect("invalid path");
+
+    if let Err(err) = heapdump::dump_heap(vm, &path) {
+        eprintln!("writing heap dump {} failed: {}", path, err);
+    }
+}
+
+pub extern "C" fn register_weak_ref(holder: Handle<Obj>) {
+    let vm = get_vm();
+
+    if let Some(weak_refs) = vm.gc.weak_refs() {
+        let holder = holder.direct();
+        let cls_def = holder.header().vtbl().class();
+
+        if let Some(kind) = weak_kind(vm, cls_def) {
+            weak_refs.register(holder.address(), kind);
+        }
+    }
+}
+
+pub extern "C" fn weak_ref_cleared(holder: Handle<Obj>) -> bool {
+    let vm = get_vm();
+    let holder = holder.direct();
+    let cls_def = holder.header().vtbl().class();
+
+    match weak_kind(vm, cls_def) {
+        Some(WeakKind::WeakRef { referent: offset })
+        | Some(WeakKind::Ephemeron { key: offset, .. }) => {
+            let slot = Slot::at(holder.address().offset(offset as usize));
+            slot.get().is_null()
+        }
+
+        None => false,
+    }
+}
+
+// the first field of WeakRef and Ephemeron is weak, fields of value types stay strong
+fn weak_kind(vm: &VM, cls_def: &ClassDef) -> Option<WeakKind> {
+    let cls_id = cls_def.cls_id.expect("class expected");
+    let is_reference = |idx: usize| cls_def.fields[idx].ty.reference_type(vm);
+
+    if cls_id == vm.vips.weak_ref_class {
+        if is_reference(0) {
+            let referent = cls_def.fields[0].offset;
+            Some(WeakKind::WeakRef { referent })
+        } else {
+            None
+        }
+    } else if cls_id == vm.vips.ephemeron_class {
+        let key = cls_def.fields[0].offset;
+        let value = cls_def.fields[1].offset;
+
+        match (is_reference(0), is_reference(1)) {
+            (true, true) => Some(WeakKind::Ephemeron { key, value }),
+            (true, false) => Some(WeakKind::WeakRef { referent: key }),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+pub extern "C" fn register_cleanup(object: Handle<Obj>, cleanup: Handle<Obj>) {
+    let vm = get_vm();
+
+    if let Some(weak_refs) = vm.gc.weak_refs() {
+        weak_refs.register_cleanup(object.direct().address(), cleanup.direct().address());
+    }
+}
+
+pub extern "C" fn next_cleanup() -> Ref<Obj> {
+    let vm = get_vm();
+
+    match vm.gc.weak_refs() {
+        Some(weak_refs) => weak_refs.next_cleanup().into(),
+        None => Ref::null(),
+    }
+}
+
 pub extern "C" fn argc() -> i32 {
     let vm = get_vm();
 
@@ -271,8 +419,18 @@ pub extern "C" fn trap(trap_id: u32) {
     };
 
     eprintln!("{}", msg);
+
+    if trap == Trap::OOM {
+        vm.gc.dump_heap_usage();
+    }
+
     let stacktrace = stacktrace_from_last_dtn(vm);
     stacktrace.dump_err(vm);
+
+    if trap == Trap::OOM && vm.args.flag_heap_dump_on_oom && heapdump::oom_during_collection() {
+        eprintln!("heap dump skipped: out of memory during collection");
+    }
+
     unsafe {
         libc::_exit(100 + trap_id as i32);
     }
@@ -284,6 +442,9 @@ pub extern "C" fn spawn_thread(obj: Handle<Obj>) {
 
     let vm = get_vm();
     let thread = DoraThread::new(vm);
+    thread
+        .tld
+        .set_concurrent_marking(vm.gc.concurrent_marking_active());
 
     vm.threads.attach_thread(thread.clone());
     let obj = obj.direct();
@@ -297,7 +458,9 @@ pub extern "C" fn spawn_thread(obj: Handle<Obj>) {
         let stack_limit = stack_top.sub(STACK_SIZE);
 
         THREAD.with(|thread| {
-            thread.borrow().tld.set_stack_limit(stack_limit);
+            let thread = thread.borrow();
+            thread.tld.set_stack_limit(stack_limit);
+            profiler::register_thread(&thread.tld, stack_top);
         });
 
         let main = {
@@ -334,6 +497,8 @@ pub extern "C" fn spawn_thread(obj: Handle<Obj>) {
             unsafe { mem::transmute(dora_stub_address) };
         fct(tld, fct_ptr, obj);
 
+        profiler::unregister_thread();
+
         // remove thread from list of all threads
         vm.threads.detach_current_thread();
     });
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use self::TypeSym::*;
 
@@ -8,19 +8,129 @@ use crate::sym::TermSym::{
 };
 use crate::ty::TypeListId;
 use crate::vm::module::ModuleId;
-use crate::vm::{ClassId, ConstId, EnumId, FctId, FieldId, GlobalId, StructId, TraitId, VarId};
+use crate::vm::{
+    ClassId, ConstId, EnumId, FctId, FieldId, FileId, GlobalId, StructId, TraitId, VarId,
+};
 use dora_parser::interner::Name;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SymTable {
     levels: Vec<SymLevel>,
+    packages: Vec<SymPackage>,
+    files: HashMap<FileId, SymFile>,
 }
 
 impl SymTable {
     pub fn new() -> SymTable {
         SymTable {
             levels: vec![SymLevel::new()],
+            packages: Vec::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    // registers file with the package given by its path, files
+    // with an empty path belong to the root package
+    pub fn add_file(&mut self, file: FileId, path: &[Name]) {
+        let package = if path.is_empty() {
+            None
+        } else if let Some(package_id) = self.find_package(path) {
+            Some(package_id)
+        } else {
+            let package_id = PackageId(self.packages.len());
+            self.packages.push(SymPackage::new(path.to_vec()));
+            Some(package_id)
+        };
+
+        let sym_file = SymFile {
+            package,
+            imports: SymLevel::new(),
+        };
+
+        assert!(self.files.insert(file, sym_file).is_none());
+    }
+
+    pub fn find_package(&self, path: &[Name]) -> Option<PackageId> {
+        self.packages
+            .iter()
+            .position(|package| package.path == path)
+            .map(PackageId)
+    }
+
+    pub fn file_package(&self, file: FileId) -> Option<PackageId> {
+        self.files.get(&file).and_then(|f| f.package)
+    }
+
+    pub fn package(&self, id: PackageId) -> &SymPackage {
+        &self.packages[id.0]
+    }
+
+    // returns the level top-level declarations of the given file are added to
+    pub fn decl_level_mut(&mut self, file: FileId) -> &mut SymLevel {
+        match self.file_package(file) {
+            Some(package_id) => &mut self.packages[package_id.0].table,
+            None => self.levels.last_mut().unwrap(),
+        }
+    }
+
+    pub fn mark_public(&mut self, file: FileId, name: Name) {
+        if let Some(package_id) = self.file_package(file) {
+            self.packages[package_id.0].public.insert(name);
+        }
+    }
+
+    pub fn imports_mut(&mut self, file: FileId) -> &mut SymLevel {
+        &mut self
+            .files
+            .get_mut(&file)
+            .expect("file not registered")
+            .imports
+    }
+
+    // looks up type in local levels, the imports and package of the
+    // file and finally in the root package
+    pub fn get_type_in(&self, file: FileId, name: Name) -> Option<TypeSym> {
+        for level in self.levels[1..].iter().rev() {
+            if let Some(val) = level.get_type(name) {
+                return Some(val.clone());
+            }
+        }
+
+        if let Some(sym_file) = self.files.get(&file) {
+            if let Some(val) = sym_file.imports.get_type(name) {
+                return Some(val.clone());
+            }
+
+            if let Some(package_id) = sym_file.package {
+                if let Some(val) = self.packages[package_id.0].table.get_type(name) {
+                    return Some(val.clone());
+                }
+            }
+        }
+
+        self.levels[0].get_type(name).cloned()
+    }
+
+    pub fn get_term_in(&self, file: FileId, name: Name) -> Option<TermSym> {
+        for level in self.levels[1..].iter().rev() {
+            if let Some(val) = level.get_term(name) {
+                return Some(val.clone());
+            }
+        }
+
+        if let Some(sym_file) = self.files.get(&file) {
+            if let Some(val) = sym_file.imports.get_term(name) {
+                return Some(val.clone());
+            }
+
+            if let Some(package_id) = sym_file.package {
+                if let Some(val) = self.packages[package_id.0].table.get_term(name) {
+                    return Some(val.clone());
+                }
+            }
         }
+
+        self.levels[0].get_term(name).cloned()
     }
 
     pub fn push_level(&mut self) {
@@ -102,7 +212,37 @@ impl SymTable {
     }
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PackageId(usize);
+
+#[derive(Clone, Debug)]
+pub struct SymPackage {
+    pub path: Vec<Name>,
+    pub table: SymLevel,
+    pub public: HashSet<Name>,
+}
+
+impl SymPackage {
+    fn new(path: Vec<Name>) -> SymPackage {
+        SymPackage {
+            path,
+            table: SymLevel::new(),
+            public: HashSet::new(),
+        }
+    }
+
+    pub fn is_public(&self, name: Name) -> bool {
+        self.public.contains(&name)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SymFile {
+    package: Option<PackageId>,
+    imports: SymLevel,
+}
+
+#[derive(Clone, Debug)]
 pub struct SymLevel {
     types: HashMap<Name, TypeSym>,
     terms: HashMap<Name, TermSym>,
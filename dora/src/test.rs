@@ -2,6 +2,8 @@ use dora_parser::ast::Ast;
 use dora_parser::lexer::reader::Reader;
 use dora_parser::parser::Parser;
 
+use crate::bytecode::cache::BytecodeCache;
+use crate::bytecode::file::BytecodeFile;
 use crate::driver::cmd::Args;
 use crate::driver::start::parse_bundled_stdlib;
 use crate::semck;
@@ -23,17 +25,81 @@ where
 }
 
 pub fn parse_with_errors<F, T>(code: &'static str, f: F) -> T
+where
+    F: FnOnce(&VM) -> T,
+{
+    parse_files_with_errors(&[("", code)], f)
+}
+
+// parses each file into the package given as path, e.g. `foo::bar`,
+// files with an empty path belong to the root package
+pub fn parse_files_with_errors<F, T>(files: &[(&'static str, &'static str)], f: F) -> T
+where
+    F: FnOnce(&VM) -> T,
+{
+    check_files(files, Default::default(), Cached::None, f)
+}
+
+// like `parse` but semck uses the given bytecode file for the program
+pub fn parse_with_bytecode_file<F, T>(code: &'static str, bytecode_file: BytecodeFile, f: F) -> T
+where
+    F: FnOnce(&VM) -> T,
+{
+    check_files(
+        &[("", code)],
+        Default::default(),
+        Cached::Program(bytecode_file),
+        f,
+    )
+}
+
+// like `parse` but semck uses the given bytecode file for the stdlib
+pub fn parse_with_stdlib_snapshot<F, T>(code: &'static str, snapshot: BytecodeFile, f: F) -> T
+where
+    F: FnOnce(&VM) -> T,
+{
+    check_files(
+        &[("", code)],
+        Default::default(),
+        Cached::StdlibSnapshot(snapshot),
+        f,
+    )
+}
+
+// like `parse` but with the given command line arguments
+pub fn parse_with_args<F, T>(code: &'static str, args: Args, f: F) -> T
+where
+    F: FnOnce(&VM) -> T,
+{
+    check_files(&[("", code)], args, Cached::None, |vm| {
+        if vm.diag.lock().has_errors() {
+            vm.diag.lock().dump(vm);
+            println!("{}", code);
+            panic!("unexpected error in test::parse_with_args()");
+        }
+
+        f(vm)
+    })
+}
+
+enum Cached {
+    None,
+    Program(BytecodeFile),
+    StdlibSnapshot(BytecodeFile),
+}
+
+fn check_files<F, T>(files: &[(&'static str, &'static str)], args: Args, cached: Cached, f: F) -> T
 where
     F: FnOnce(&VM) -> T,
 {
     let mut ast = Ast::new();
-    let args: Args = Default::default();
     let empty = Ast::new();
     let mut vm = VM::new(args, &empty);
 
     parse_bundled_stdlib(&mut vm, &mut ast).expect("failed parsing stdlib");
+    let stdlib_files = ast.files.len();
 
-    {
+    for &(package, code) in files {
         let filename = "<<code>>";
         let reader = Reader::from_string(filename, code);
         let parser = Parser::new(reader, &vm.id_generator, &mut ast, &mut vm.interner);
@@ -53,10 +119,29 @@ where
                 panic!("error during parsing.");
             }
         }
+
+        if !package.is_empty() {
+            let package = package
+                .split("::")
+                .map(|name| vm.interner.intern(name))
+                .collect();
+            ast.files.last_mut().unwrap().package = package;
+        }
     }
 
     vm.ast = &ast;
 
+    match cached {
+        Cached::None => {}
+        Cached::Program(file) => {
+            let files = vm.files.len();
+            vm.bytecode_cache = BytecodeCache::new(&vm, file, files);
+        }
+        Cached::StdlibSnapshot(file) => {
+            vm.stdlib_snapshot = BytecodeCache::stdlib_snapshot(&vm, file, stdlib_files);
+        }
+    }
+
     semck::check(&mut vm);
 
     f(&vm)
@@ -150,7 +150,7 @@ impl DoraThread {
     }
 
     pub fn dtn(&self) -> *const DoraToNativeInfo {
-        self.tld.dtn.load(Ordering::Relaxed) as *const _
+        self.tld.dtn()
     }
 
     pub fn set_dtn(&self, ptr: *const DoraToNativeInfo) {
@@ -321,6 +321,9 @@ impl Default for ThreadState {
 pub struct ThreadLocalData {
     tlab_top: AtomicUsize,
     tlab_end: AtomicUsize,
+    // real end of the TLAB, tlab_end is lowered below it while
+    // allocations are sampled
+    tlab_limit: AtomicUsize,
     concurrent_marking: AtomicBool,
     guard_stack_limit: AtomicUsize,
     real_stack_limit: AtomicUsize,
@@ -332,6 +335,7 @@ impl ThreadLocalData {
         ThreadLocalData {
             tlab_top: AtomicUsize::new(0),
             tlab_end: AtomicUsize::new(0),
+            tlab_limit: AtomicUsize::new(0),
             concurrent_marking: AtomicBool::new(false),
             guard_stack_limit: AtomicUsize::new(0),
             real_stack_limit: AtomicUsize::new(0),
@@ -344,6 +348,17 @@ impl ThreadLocalData {
 
         self.tlab_top.store(start.to_usize(), Ordering::Relaxed);
         self.tlab_end.store(end.to_usize(), Ordering::Relaxed);
+        self.tlab_limit.store(end.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn tlab_set_end(&self, end: Address) {
+        assert!(end <= self.tlab_limit());
+
+        self.tlab_end.store(end.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn tlab_limit(&self) -> Address {
+        self.tlab_limit.load(Ordering::Relaxed).into()
     }
 
     pub fn tlab_rest(&self) -> usize {
@@ -360,6 +375,10 @@ impl ThreadLocalData {
         Region::new(tlab_top.into(), tlab_end.into())
     }
 
+    pub fn dtn(&self) -> *const DoraToNativeInfo {
+        self.dtn.load(Ordering::Relaxed) as *const _
+    }
+
     pub fn set_stack_limit(&self, stack_limit: Address) {
         self.guard_stack_limit
             .store(stack_limit.to_usize(), Ordering::Relaxed);
@@ -375,6 +394,10 @@ impl ThreadLocalData {
         offset_of!(ThreadLocalData, tlab_end) as i32
     }
 
+    pub fn set_concurrent_marking(&self, active: bool) {
+        self.concurrent_marking.store(active, Ordering::Relaxed);
+    }
+
     pub fn concurrent_marking_offset() -> i32 {
         offset_of!(ThreadLocalData, concurrent_marking) as i32
     }
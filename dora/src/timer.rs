@@ -1,3 +1,5 @@
+use parking_lot::Mutex;
+use std::thread::{self, ThreadId};
 use time;
 
 pub struct Timer {
@@ -62,3 +64,44 @@ pub fn in_ms(ns: u64) -> f32 {
 pub fn timestamp() -> u64 {
     time::precise_time_ns()
 }
+
+// Busy time of every thread running tasks of a parallel collector, threads
+// are listed in the order they ran their first task.
+pub struct WorkerTimes {
+    active: bool,
+    times: Mutex<Vec<(ThreadId, f32)>>,
+}
+
+impl WorkerTimes {
+    pub fn new(active: bool) -> WorkerTimes {
+        WorkerTimes {
+            active,
+            times: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn measure<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        let duration = Timer::ms(self.active, f);
+
+        if !self.active {
+            return;
+        }
+
+        let id = thread::current().id();
+        let mut times = self.times.lock();
+
+        if let Some(entry) = times.iter_mut().find(|(thread, _)| *thread == id) {
+            entry.1 += duration;
+        } else {
+            times.push((id, duration));
+        }
+    }
+
+    pub fn times(&self) -> Vec<f32> {
+        let times = self.times.lock();
+        times.iter().map(|&(_, duration)| duration).collect()
+    }
+}
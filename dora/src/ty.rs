@@ -5,8 +5,8 @@ use std::sync::Arc;
 use crate::mem;
 use crate::semck;
 use crate::vm::module::ModuleId;
-use crate::vm::VM;
-use crate::vm::{ClassId, EnumId, FctId, StructId, TraitId, TupleId};
+use crate::vm::{get_vm, VM};
+use crate::vm::{ClassId, EnumId, EnumLayout, FctId, StructId, TraitId, TupleId};
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum BuiltinType {
@@ -137,6 +137,17 @@ impl BuiltinType {
         }
     }
 
+    pub fn is_struct(&self) -> bool {
+        match *self {
+            BuiltinType::Struct(_, _) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_tuple_or_struct(&self) -> bool {
+        self.is_tuple() || self.is_struct()
+    }
+
     pub fn cls_id(&self, vm: &VM) -> Option<ClassId> {
         match *self {
             BuiltinType::Class(cls_id, _) => Some(cls_id),
@@ -207,21 +218,49 @@ impl BuiltinType {
                 params.iter().any(|t| t.contains_type_param(vm))
             }
 
-            &BuiltinType::Lambda(_) => unimplemented!(),
+            &BuiltinType::Lambda(lambda_id) => {
+                let lambda = vm.lambda_types.lock().get(lambda_id);
+                lambda.params.iter().any(|t| t.contains_type_param(vm))
+                    || lambda.ret.contains_type_param(vm)
+            }
 
             _ => false,
         }
     }
 
-    pub fn reference_type(&self) -> bool {
+    pub fn reference_type(&self, vm: &VM) -> bool {
         match *self {
             BuiltinType::Ptr => true,
             BuiltinType::Class(_, _) => true,
             BuiltinType::Trait(_) => true,
+            BuiltinType::Lambda(_) => true,
+            BuiltinType::Enum(enum_id, _) => {
+                vm.enums[enum_id].read().layout() == EnumLayout::Tagged
+            }
             _ => false,
         }
     }
 
+    // offsets of all references in a value of this type, tuples and
+    // structs are stored inline and can contain multiple references
+    pub fn reference_offsets(&self, vm: &VM) -> Vec<i32> {
+        match *self {
+            BuiltinType::Tuple(tuple_id) => {
+                vm.tuples.lock().get_tuple(tuple_id).references().to_owned()
+            }
+            BuiltinType::Struct(sid, list_id) => {
+                let params = vm.lists.lock().get(list_id);
+                let sid = semck::specialize::specialize_struct_id_params(vm, sid, params);
+                let struc = vm.struct_defs.idx(sid);
+                let struc = struc.lock();
+
+                struc.ref_fields.clone()
+            }
+            _ if self.reference_type(vm) => vec![0],
+            _ => Vec::new(),
+        }
+    }
+
     pub fn value_type(&self) -> bool {
         match *self {
             BuiltinType::Unit
@@ -421,7 +460,17 @@ impl BuiltinType {
 
                 _ => false,
             },
-            BuiltinType::Trait(_) => unimplemented!(),
+            BuiltinType::Trait(trait_id) => match other {
+                BuiltinType::Trait(other_trait_id) => trait_id == other_trait_id,
+                BuiltinType::Nil => true,
+                BuiltinType::Class(cls_id, _) => {
+                    let cls = vm.classes.idx(cls_id);
+                    let cls = cls.read();
+
+                    cls.implements_trait_in_hierarchy(vm, trait_id)
+                }
+                _ => false,
+            },
             BuiltinType::Module(_) => *self == other,
             BuiltinType::Enum(_, _) => *self == other,
 
@@ -456,7 +505,10 @@ impl BuiltinType {
             BuiltinType::Int64 => 8,
             BuiltinType::Float32 => 4,
             BuiltinType::Float64 => 8,
-            BuiltinType::Enum(_, _) => 4,
+            BuiltinType::Enum(enum_id, _) => match vm.enums[enum_id].read().layout() {
+                EnumLayout::Int => 4,
+                EnumLayout::Tagged => mem::ptr_width(),
+            },
             BuiltinType::Nil => panic!("no size for nil."),
             BuiltinType::This => panic!("no size for Self."),
             BuiltinType::Any => panic!("no size for Any."),
@@ -494,7 +546,10 @@ impl BuiltinType {
             BuiltinType::Nil => panic!("no alignment for nil."),
             BuiltinType::This => panic!("no alignment for Self."),
             BuiltinType::Any => panic!("no alignment for Any."),
-            BuiltinType::Enum(_, _) => 4,
+            BuiltinType::Enum(enum_id, _) => match vm.enums[enum_id].read().layout() {
+                EnumLayout::Int => 4,
+                EnumLayout::Tagged => mem::ptr_width(),
+            },
             BuiltinType::Class(_, _)
             | BuiltinType::Module(_)
             | BuiltinType::Lambda(_)
@@ -526,7 +581,13 @@ impl BuiltinType {
             BuiltinType::Int64 => MachineMode::Int64,
             BuiltinType::Float32 => MachineMode::Float32,
             BuiltinType::Float64 => MachineMode::Float64,
-            BuiltinType::Enum(_, _) => MachineMode::Int32,
+            BuiltinType::Enum(enum_id, _) => {
+                let vm = get_vm();
+                match vm.enums[enum_id].read().layout() {
+                    EnumLayout::Int => MachineMode::Int32,
+                    EnumLayout::Tagged => MachineMode::Ptr,
+                }
+            }
             BuiltinType::Nil => panic!("no machine mode for nil."),
             BuiltinType::This => panic!("no machine mode for Self."),
             BuiltinType::Any => panic!("no machine mode for Any."),
@@ -605,7 +666,7 @@ impl BuiltinType {
             | BuiltinType::Ptr
             | BuiltinType::Trait(_)
             | BuiltinType::Nil => true,
-            BuiltinType::Class(_, list_id) => {
+            BuiltinType::Class(_, list_id) | BuiltinType::Struct(_, list_id) => {
                 let params = vm.lists.lock().get(list_id);
 
                 for param in params.iter() {
@@ -617,7 +678,11 @@ impl BuiltinType {
                 true
             }
             BuiltinType::Tuple(tuple_id) => vm.tuples.lock().get_tuple(tuple_id).is_concrete_type(),
-            BuiltinType::Lambda(_) | BuiltinType::Struct(_, _) => unimplemented!(),
+            BuiltinType::Lambda(lambda_id) => {
+                let lambda = vm.lambda_types.lock().get(lambda_id);
+                lambda.params.iter().all(|t| t.is_concrete_type(vm))
+                    && lambda.ret.is_concrete_type(vm)
+            }
             BuiltinType::ClassTypeParam(_, _) | BuiltinType::FctTypeParam(_, _) => false,
         }
     }
@@ -785,6 +850,16 @@ impl TypeLists {
     pub fn get(&self, id: TypeListId) -> TypeList {
         self.values[id.idx()].clone()
     }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+        self.lists.retain(|_, id| id.idx() < len);
+        self.next_id = self.values.len();
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -836,6 +911,12 @@ impl LambdaTypes {
     pub fn get(&self, id: LambdaId) -> Arc<LambdaType> {
         self.values[id.0].clone()
     }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+        self.types.retain(|_, id| id.0 < len);
+        self.next_lambda_id = self.values.len();
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -844,6 +925,16 @@ pub struct LambdaType {
     ret: BuiltinType,
 }
 
+impl LambdaType {
+    pub fn params(&self) -> &[BuiltinType] {
+        &self.params
+    }
+
+    pub fn ret(&self) -> BuiltinType {
+        self.ret
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum TypeParamId {
     Fct(TypeListId),
@@ -16,7 +16,13 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        // lambdas are checked together with their enclosing function, cached
+        // functions were checked before their bytecode was written
+        if !fct.is_src()
+            || fct.is_lambda()
+            || !vm.is_checked_file(fct.file)
+            || vm.has_cached_bytecode(fct.id)
+        {
             continue;
         }
 
@@ -31,14 +37,28 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
             src: &mut src,
             ast,
             used_in_call: HashSet::new(),
+            lambda_return_types: Vec::new(),
+            may_throw: vec![fct.throws],
+            in_try: false,
         };
 
         typeck.check();
+
+        // lambdas share the analysis of their enclosing function
+        for (_, lambda) in src.map_lambdas.iter() {
+            let lambda_fct = vm.fcts.idx(lambda.fct_id);
+            let lambda_fct = lambda_fct.read();
+            *lambda_fct.src().write() = src.clone();
+        }
     }
 
     for xconst in vm.consts.iter() {
         let mut xconst = xconst.lock();
 
+        if !vm.is_checked_file(xconst.file) {
+            continue;
+        }
+
         let (_, value) = {
             let mut constck = ConstCheck {
                 vm,
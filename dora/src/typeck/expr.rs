@@ -1,18 +1,21 @@
 use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::mem;
 use std::sync::Arc;
 use std::{f32, f64};
 
 use crate::error::msg::SemError;
+use crate::semck::matchck::pattern_class_type;
 use crate::semck::specialize::replace_type_param;
 use crate::semck::typeparamck;
 use crate::semck::{always_returns, expr_always_returns};
 use crate::sym::TypeSym::SymClass;
-use crate::ty::{BuiltinType, TypeList, TypeParamId};
+use crate::ty::{BuiltinType, LambdaId, TypeList, TypeParamId};
 use crate::typeck::lookup::MethodLookup;
 use crate::vm::{
     self, ensure_tuple, find_field_in_class, find_methods_in_class, CallType, ClassId, ConvInfo,
-    EnumId, Fct, FctId, FctParent, FctSrc, FileId, ForTypeInfo, IdentType, Intrinsic, VM,
+    EnumId, Fct, FctId, FctParent, FctSrc, FileId, ForTypeInfo, IdentType, Intrinsic, StructData,
+    StructId, VM,
 };
 
 use dora_parser::ast::visit::Visitor;
@@ -30,11 +33,20 @@ pub struct TypeCheck<'a, 'ast: 'a> {
     pub src: &'a mut FctSrc,
     pub ast: &'ast Function,
     pub used_in_call: HashSet<NodeId>,
+    pub lambda_return_types: Vec<BuiltinType>,
+    // whether exceptions may leave the current code, either because they are
+    // caught by an enclosing `do` or the function is marked with `throws`
+    pub may_throw: Vec<bool>,
+    pub in_try: bool,
 }
 
 impl<'a, 'ast> TypeCheck<'a, 'ast> {
     pub fn check(&mut self) {
         let block = self.ast.block.as_ref().expect("missing block");
+        self.check_fct_block(block);
+    }
+
+    fn check_fct_block(&mut self, block: &'ast ExprBlockType) {
         let mut returns = false;
 
         for stmt in &block.stmts {
@@ -50,7 +62,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 returns = true;
             }
 
-            let return_type = self.fct.return_type;
+            let return_type = self.return_type();
             self.check_expr(value, return_type)
         } else {
             BuiltinType::Unit
@@ -61,6 +73,14 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         }
     }
 
+    fn return_type(&self) -> BuiltinType {
+        // return inside a lambda returns from the lambda
+        self.lambda_return_types
+            .last()
+            .cloned()
+            .unwrap_or(self.fct.return_type)
+    }
+
     pub fn check_stmt_var(&mut self, s: &'ast StmtVarType) {
         let var = *self.src.map_vars.get(s.id).unwrap();
 
@@ -140,6 +160,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         let name = self.vm.interner.intern("makeIterator");
 
         let mut lookup = MethodLookup::new(self.vm, self.file)
+            .caller(self.fct)
             .method(object_type)
             .pos(s.pos)
             .name(name)
@@ -224,7 +245,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         if !expr_type.is_error() && !expr_type.is_bool() {
             let expr_type = expr_type.name(self.vm);
             let msg = SemError::WhileCondType(expr_type);
-            self.vm.diag.lock().report(self.file, s.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, s.pos, s.cond.span(), msg);
         }
 
         self.visit_stmt(&s.block);
@@ -240,8 +264,91 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         self.check_fct_return_type(s.pos, expr_type);
     }
 
+    fn check_stmt_throw(&mut self, s: &'ast StmtThrowType) {
+        let ty = self.check_expr(&s.expr, BuiltinType::Any);
+
+        if !ty.is_error() && !self.is_exception(ty) {
+            let ty = ty.name(self.vm);
+            let msg = SemError::ThrowNotException(ty);
+            self.vm.diag.lock().report(self.file, s.pos, msg);
+        }
+
+        self.check_throw_allowed(s.pos);
+    }
+
+    fn check_stmt_do(&mut self, s: &'ast StmtDoType) {
+        let mut catches_all = false;
+
+        for catch in &s.catch_blocks {
+            let ty = self.src.ty(catch.data_type.id());
+            let var = *self.src.map_vars.get(catch.id).unwrap();
+            self.src.vars[var].ty = ty;
+
+            if ty.is_error() {
+                continue;
+            }
+
+            if !self.is_exception(ty) {
+                let ty = ty.name(self.vm);
+                let msg = SemError::CatchTypeNotException(ty);
+                self.vm.diag.lock().report(self.file, catch.pos, msg);
+            } else if ty.cls_id(self.vm) == Some(self.vm.vips.exception_class) {
+                catches_all = true;
+            }
+        }
+
+        let may_throw = catches_all || *self.may_throw.last().unwrap();
+        self.may_throw.push(may_throw);
+        self.visit_stmt(&s.do_block);
+        self.may_throw.pop();
+
+        for catch in &s.catch_blocks {
+            self.visit_stmt(&catch.block);
+        }
+    }
+
+    fn is_exception(&self, ty: BuiltinType) -> bool {
+        let exception = self.vm.cls(self.vm.vips.exception_class);
+        ty.subclass_from(self.vm, exception)
+    }
+
+    fn check_throw_allowed(&mut self, pos: Position) {
+        if !*self.may_throw.last().unwrap() {
+            self.vm
+                .diag
+                .lock()
+                .report(self.file, pos, SemError::UncheckedThrow);
+        }
+    }
+
+    fn call_throws(&self, id: NodeId) -> bool {
+        self.src
+            .map_calls
+            .get(id)
+            .and_then(|call_type| call_type.fct_id())
+            .map(|fct_id| self.vm.fcts.idx(fct_id).read().throws)
+            .unwrap_or(false)
+    }
+
+    fn check_expr_try(&mut self, e: &'ast ExprTryType, expected_ty: BuiltinType) -> BuiltinType {
+        let ty = if e.expr.is_call() {
+            self.in_try = true;
+            self.check_expr(&e.expr, expected_ty)
+        } else {
+            self.vm
+                .diag
+                .lock()
+                .report(self.file, e.pos, SemError::TryNeedsCall);
+            self.check_expr(&e.expr, expected_ty)
+        };
+
+        self.src.set_ty(e.id, ty);
+
+        ty
+    }
+
     fn check_fct_return_type(&mut self, pos: Position, expr_type: BuiltinType) {
-        let fct_type = self.fct.return_type;
+        let fct_type = self.return_type();
 
         if !expr_type.is_error() && !fct_type.allows(self.vm, expr_type) {
             let msg = if expr_type.is_nil() {
@@ -262,14 +369,14 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
     fn check_expr_block(
         &mut self,
         block: &'ast ExprBlockType,
-        _expected_ty: BuiltinType,
+        expected_ty: BuiltinType,
     ) -> BuiltinType {
         for stmt in &block.stmts {
             self.visit_stmt(stmt);
         }
 
         let ty = if let Some(ref expr) = block.expr {
-            self.check_expr(expr, BuiltinType::Any)
+            self.check_expr(expr, expected_ty)
         } else {
             BuiltinType::Unit
         };
@@ -304,19 +411,22 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         ty
     }
 
-    fn check_expr_if(&mut self, expr: &'ast ExprIfType, _expected_ty: BuiltinType) -> BuiltinType {
+    fn check_expr_if(&mut self, expr: &'ast ExprIfType, expected_ty: BuiltinType) -> BuiltinType {
         let expr_type = self.check_expr(&expr.cond, BuiltinType::Any);
 
         if !expr_type.is_bool() && !expr_type.is_error() {
             let expr_type = expr_type.name(self.vm);
             let msg = SemError::IfCondType(expr_type);
-            self.vm.diag.lock().report(self.file, expr.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, expr.pos, expr.cond.span(), msg);
         }
 
-        let then_type = self.check_expr(&expr.then_block, BuiltinType::Any);
+        let then_type = self.check_expr(&expr.then_block, expected_ty);
 
         let merged_type = if let Some(ref else_block) = expr.else_block {
-            let else_type = self.check_expr(else_block, BuiltinType::Any);
+            let else_type = self.check_expr(else_block, expected_ty);
 
             if expr_always_returns(&expr.then_block) {
                 else_type
@@ -344,17 +454,275 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         merged_type
     }
 
+    fn check_expr_match(
+        &mut self,
+        expr: &'ast ExprMatchType,
+        expected_ty: BuiltinType,
+    ) -> BuiltinType {
+        let value_type = self.check_expr(&expr.expr, BuiltinType::Any);
+
+        let mut merged_type: Option<BuiltinType> = None;
+        let mut last_type = BuiltinType::Unit;
+
+        for case in &expr.cases {
+            self.check_match_pattern(&case.pattern, value_type);
+            let case_type = self.check_expr(&case.value, expected_ty);
+            last_type = case_type;
+
+            if expr_always_returns(&case.value) || case_type.is_error() {
+                continue;
+            }
+
+            match merged_type {
+                Some(merged) if merged.is_error() => merged_type = Some(case_type),
+                Some(merged) => {
+                    if !merged.allows(self.vm, case_type) {
+                        let merged = merged.name(self.vm);
+                        let case_type = case_type.name(self.vm);
+                        let msg = SemError::MatchBranchTypesIncompatible(merged, case_type);
+                        self.vm.diag.lock().report(self.file, case.pos, msg);
+                    }
+                }
+                None => merged_type = Some(case_type),
+            }
+        }
+
+        let merged_type = merged_type.unwrap_or(last_type);
+        self.src.set_ty(expr.id, merged_type);
+
+        merged_type
+    }
+
+    fn check_match_pattern(&mut self, pattern: &'ast MatchPattern, ty: BuiltinType) {
+        self.src.set_ty(pattern.id(), ty);
+
+        match *pattern {
+            MatchPattern::Underscore(_) => {}
+
+            MatchPattern::Lit(ref lit) => {
+                let lit_type = self.check_expr(&lit.expr, ty);
+
+                if ty.is_error() || lit_type.is_error() {
+                    return;
+                }
+
+                if ty != lit_type {
+                    let ty = ty.name(self.vm);
+                    let msg = SemError::PatternTypeMismatch(ty);
+                    self.vm.diag.lock().report(self.file, lit.pos, msg);
+                } else if ty.reference_type(self.vm) {
+                    // strings are compared with equals()
+                    let name = self.vm.interner.intern("equals");
+                    let (_, fct_id, _) =
+                        lookup_method(self.vm, ty, false, name, &[ty], &TypeList::empty(), None)
+                            .expect("equals() not found");
+
+                    let call_type = CallType::Method(ty, fct_id, TypeList::empty());
+                    self.src.map_calls.insert(lit.id, Arc::new(call_type));
+                }
+            }
+
+            MatchPattern::Ident(ref ident) => {
+                if let Some(&var_id) = self.src.map_vars.get(ident.id) {
+                    self.src.vars[var_id].ty = ty;
+                } else {
+                    self.check_match_pattern_class(ident.id, ident.pos, ident.name, None, ty);
+                }
+            }
+
+            MatchPattern::Tuple(ref tuple) => {
+                let subtypes = match ty {
+                    BuiltinType::Tuple(tuple_id) => (*self.vm.tuples.lock().get(tuple_id)).clone(),
+                    BuiltinType::Unit => Vec::new(),
+                    BuiltinType::Error => vec![BuiltinType::Error; tuple.params.len()],
+
+                    _ => {
+                        let ty = ty.name(self.vm);
+                        let msg = SemError::PatternTypeMismatch(ty);
+                        self.vm.diag.lock().report(self.file, tuple.pos, msg);
+
+                        vec![BuiltinType::Error; tuple.params.len()]
+                    }
+                };
+
+                self.check_match_pattern_params(tuple.pos, &tuple.params, &subtypes);
+            }
+
+            MatchPattern::Variant(ref variant) => {
+                let params = variant.params.as_ref();
+
+                match variant.path.len() {
+                    1 => self.check_match_pattern_class(
+                        variant.id,
+                        variant.pos,
+                        variant.path[0],
+                        params,
+                        ty,
+                    ),
+                    2 => self.check_match_pattern_enum(variant, ty),
+
+                    _ => {
+                        let name = variant
+                            .path
+                            .iter()
+                            .map(|&name| self.vm.interner.str(name).to_string())
+                            .collect::<Vec<_>>()
+                            .join("::");
+                        let msg = SemError::UnknownIdentifier(name);
+                        self.vm.diag.lock().report(self.file, variant.pos, msg);
+                        self.check_match_pattern_params_error(params);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_match_pattern_enum(&mut self, variant: &'ast MatchPatternVariant, ty: BuiltinType) {
+        let params = variant.params.as_ref();
+        let enum_id = self
+            .vm
+            .sym
+            .lock()
+            .get_type_in(self.file, variant.path[0])
+            .and_then(|sym| sym.to_enum());
+
+        let enum_id = if let Some(enum_id) = enum_id {
+            enum_id
+        } else {
+            let name = self.vm.interner.str(variant.path[0]).to_string();
+            let msg = SemError::UnknownIdentifier(name);
+            self.vm.diag.lock().report(self.file, variant.pos, msg);
+            self.check_match_pattern_params_error(params);
+            return;
+        };
+
+        if !ty.is_error() && ty.enum_id() != Some(enum_id) {
+            let ty = ty.name(self.vm);
+            let msg = SemError::PatternTypeMismatch(ty);
+            self.vm.diag.lock().report(self.file, variant.pos, msg);
+            self.check_match_pattern_params_error(params);
+            return;
+        }
+
+        let xenum = self.vm.enums[enum_id].read();
+
+        if let Some(&value) = xenum.name_to_value.get(&variant.path[1]) {
+            self.src
+                .map_idents
+                .insert(variant.id, IdentType::EnumValue(enum_id, value));
+
+            let subtypes = xenum.variants[value as usize].types.clone();
+            let params = params.map(|p| p.as_slice()).unwrap_or(&[]);
+            self.check_match_pattern_params(variant.pos, params, &subtypes);
+        } else {
+            let name = self.vm.interner.str(variant.path[1]).to_string();
+            let msg = SemError::UnknownEnumValue(name);
+            self.vm.diag.lock().report(self.file, variant.pos, msg);
+            self.check_match_pattern_params_error(params);
+        }
+    }
+
+    fn check_match_pattern_class(
+        &mut self,
+        id: NodeId,
+        pos: Position,
+        name: Name,
+        params: Option<&'ast Vec<Box<MatchPattern>>>,
+        ty: BuiltinType,
+    ) {
+        let cls_id = self
+            .vm
+            .sym
+            .lock()
+            .get_type_in(self.file, name)
+            .and_then(|sym| sym.to_class());
+
+        let cls_id = if let Some(cls_id) = cls_id {
+            cls_id
+        } else {
+            let name = self.vm.interner.str(name).to_string();
+            let msg = SemError::UnknownClass(name);
+            self.vm.diag.lock().report(self.file, pos, msg);
+            self.check_match_pattern_params_error(params);
+            return;
+        };
+
+        if ty.is_error() {
+            self.check_match_pattern_params_error(params);
+            return;
+        }
+
+        let cls_ty = if let Some(cls_ty) = pattern_class_type(self.vm, cls_id, ty) {
+            cls_ty
+        } else {
+            let ty = ty.name(self.vm);
+            let msg = SemError::PatternTypeMismatch(ty);
+            self.vm.diag.lock().report(self.file, pos, msg);
+            self.check_match_pattern_params_error(params);
+            return;
+        };
+
+        self.src.set_ty(id, cls_ty);
+        self.src.map_idents.insert(id, IdentType::Class(cls_id));
+
+        if let Some(params) = params {
+            let type_params = cls_ty.type_params(self.vm);
+            let cls = self.vm.classes.idx(cls_id);
+            let cls = cls.read();
+
+            let subtypes = cls
+                .fields
+                .iter()
+                .map(|f| replace_type_param(self.vm, f.ty, &type_params, &TypeList::empty(), None))
+                .collect::<Vec<_>>();
+
+            self.check_match_pattern_params(pos, params, &subtypes);
+        }
+    }
+
+    fn check_match_pattern_params(
+        &mut self,
+        pos: Position,
+        params: &'ast [Box<MatchPattern>],
+        subtypes: &[BuiltinType],
+    ) {
+        if params.len() != subtypes.len() {
+            let msg = SemError::PatternWrongNumberOfParams(subtypes.len(), params.len());
+            self.vm.diag.lock().report(self.file, pos, msg);
+
+            for param in params {
+                self.check_match_pattern(param, BuiltinType::Error);
+            }
+
+            return;
+        }
+
+        for (param, &subtype) in params.iter().zip(subtypes) {
+            self.check_match_pattern(param, subtype);
+        }
+    }
+
+    fn check_match_pattern_params_error(&mut self, params: Option<&'ast Vec<Box<MatchPattern>>>) {
+        for param in params.into_iter().flatten() {
+            self.check_match_pattern(param, BuiltinType::Error);
+        }
+    }
+
     fn check_expr_ident(
         &mut self,
         e: &'ast ExprIdentType,
         _expected_ty: BuiltinType,
     ) -> BuiltinType {
-        let ident_type = self.src.map_idents.get(e.id).unwrap();
+        self.check_ident(e.id, e.pos)
+    }
+
+    fn check_ident(&mut self, id: NodeId, pos: Position) -> BuiltinType {
+        let ident_type = self.src.map_idents.get(id).unwrap();
 
         match ident_type {
             &IdentType::Var(varid) => {
                 let ty = self.src.vars[varid].ty;
-                self.src.set_ty(e.id, ty);
+                self.src.set_ty(id, ty);
 
                 ty
             }
@@ -362,7 +730,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             &IdentType::Global(globalid) => {
                 let glob = self.vm.globals.idx(globalid);
                 let ty = glob.read().ty;
-                self.src.set_ty(e.id, ty);
+                self.src.set_ty(id, ty);
 
                 ty
             }
@@ -373,50 +741,68 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 let cls = cls.read();
                 let field = &cls.fields[fieldid];
 
-                self.src.set_ty(e.id, field.ty);
+                self.src.set_ty(id, field.ty);
 
                 field.ty
             }
 
             &IdentType::Struct(sid) => {
+                if !self.used_in_call.contains(&id) {
+                    let struc = self.vm.structs.idx(sid);
+                    let struc = struc.lock();
+
+                    if !struc.fields.is_empty() {
+                        let name = self.vm.interner.str(struc.name).to_string();
+                        let field_types = struc
+                            .fields
+                            .iter()
+                            .map(|field| field.ty.name(self.vm))
+                            .collect::<Vec<_>>();
+                        let msg = SemError::StructArgsIncompatible(name, field_types, Vec::new());
+                        self.vm.diag.lock().report(self.file, pos, msg);
+                    }
+                }
+
                 let list_id = self.vm.lists.lock().insert(TypeList::empty());
                 let ty = BuiltinType::Struct(sid, list_id);
-                self.src.set_ty(e.id, ty);
+                self.src.set_ty(id, ty);
 
                 ty
             }
 
+            &IdentType::StructField(_, _) => unreachable!(),
+
             &IdentType::Const(const_id) => {
                 let xconst = self.vm.consts.idx(const_id);
                 let xconst = xconst.lock();
 
-                self.src.set_ty(e.id, xconst.ty);
+                self.src.set_ty(id, xconst.ty);
 
                 xconst.ty
             }
 
             &IdentType::Fct(_) => {
-                if !self.used_in_call.contains(&e.id) {
+                if !self.used_in_call.contains(&id) {
                     self.vm
                         .diag
                         .lock()
-                        .report(self.file, e.pos, SemError::FctUsedAsIdentifier);
+                        .report(self.file, pos, SemError::FctUsedAsIdentifier);
                 }
 
-                self.src.set_ty(e.id, BuiltinType::Error);
+                self.src.set_ty(id, BuiltinType::Error);
 
                 BuiltinType::Error
             }
 
             &IdentType::Class(_) => {
-                if !self.used_in_call.contains(&e.id) {
+                if !self.used_in_call.contains(&id) {
                     self.vm
                         .diag
                         .lock()
-                        .report(self.file, e.pos, SemError::ClsUsedAsIdentifier);
+                        .report(self.file, pos, SemError::ClsUsedAsIdentifier);
                 }
 
-                self.src.set_ty(e.id, BuiltinType::Error);
+                self.src.set_ty(id, BuiltinType::Error);
 
                 BuiltinType::Error
             }
@@ -426,28 +812,28 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             | &IdentType::StructAndModule(_, module_id) => {
                 let module = self.vm.modules.idx(module_id);
                 let ty = module.read().ty;
-                self.src.set_ty(e.id, ty);
+                self.src.set_ty(id, ty);
 
                 ty
             }
 
             &IdentType::TypeParam(_) => {
-                let msg = if self.used_in_call.contains(&e.id) {
+                let msg = if self.used_in_call.contains(&id) {
                     SemError::TypeParamUsedAsCallee
                 } else {
                     SemError::TypeParamUsedAsIdentifier
                 };
 
-                self.vm.diag.lock().report(self.file, e.pos, msg);
-                self.src.set_ty(e.id, BuiltinType::Error);
+                self.vm.diag.lock().report(self.file, pos, msg);
+                self.src.set_ty(id, BuiltinType::Error);
 
                 BuiltinType::Error
             }
 
             &IdentType::Enum(_) => {
                 let msg = SemError::EnumUsedAsIdentifier;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
-                self.src.set_ty(e.id, BuiltinType::Error);
+                self.vm.diag.lock().report(self.file, pos, msg);
+                self.src.set_ty(id, BuiltinType::Error);
 
                 BuiltinType::Error
             }
@@ -482,7 +868,13 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
     fn check_expr_assign_ident(&mut self, e: &'ast ExprBinType) {
         let lhs_type;
 
-        let rhs_type = self.check_expr(&e.rhs, BuiltinType::Any);
+        let expected_ty = match self.src.map_idents.get(e.lhs.id()) {
+            Some(&IdentType::Var(varid)) => self.src.vars[varid].ty,
+            Some(&IdentType::Global(gid)) => self.vm.globals.idx(gid).read().ty,
+            _ => BuiltinType::Any,
+        };
+
+        let rhs_type = self.check_expr(&e.rhs, expected_ty);
 
         self.src.set_ty(e.id, BuiltinType::Unit);
 
@@ -520,12 +912,17 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 lhs_type = glob.ty;
             }
 
-            &IdentType::Field(_, _) => {
+            &IdentType::Field(_, _) | &IdentType::StructField(_, _) => {
                 unreachable!();
             }
 
             &IdentType::Struct(_) | &IdentType::StructAndModule(_, _) => {
-                unimplemented!();
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, e.pos, SemError::InvalidLhsAssignment);
+
+                return;
             }
 
             &IdentType::Const(_) => {
@@ -642,49 +1039,79 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         };
 
         let object_type = self.check_expr(&field_expr.lhs, BuiltinType::Any);
-        let rhs_type = self.check_expr(&e.rhs, BuiltinType::Any);
 
-        if object_type.cls_id(self.vm).is_some() {
-            if let Some((cls_ty, field_id)) = find_field_in_class(self.vm, object_type, name) {
-                let ident_type = IdentType::Field(cls_ty, field_id);
-                self.src
-                    .map_idents
-                    .insert_or_replace(e.lhs.id(), ident_type);
+        let field = if object_type.cls_id(self.vm).is_some() {
+            find_field_in_class(self.vm, object_type, name)
+        } else {
+            None
+        };
 
-                let cls = self
-                    .vm
-                    .classes
-                    .idx(cls_ty.cls_id(self.vm).expect("no class"));
-                let cls = cls.read();
-                let field = &cls.fields[field_id];
+        let fty = field.map(|(cls_ty, field_id)| {
+            let cls = self
+                .vm
+                .classes
+                .idx(cls_ty.cls_id(self.vm).expect("no class"));
+            let cls = cls.read();
+            let field = &cls.fields[field_id];
 
-                let class_type_params = cls_ty.type_params(self.vm);
+            let class_type_params = cls_ty.type_params(self.vm);
 
-                let fty = replace_type_param(
-                    self.vm,
-                    field.ty,
-                    &class_type_params,
-                    &TypeList::empty(),
-                    None,
-                );
+            replace_type_param(
+                self.vm,
+                field.ty,
+                &class_type_params,
+                &TypeList::empty(),
+                None,
+            )
+        });
 
-                if !e.initializer && !field.reassignable {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::LetReassigned);
-                }
+        let rhs_type = self.check_expr(&e.rhs, fty.unwrap_or(BuiltinType::Any));
 
-                if !fty.allows(self.vm, rhs_type) && !rhs_type.is_error() {
-                    let name = self.vm.interner.str(name).to_string();
+        if let Some((cls_ty, field_id)) = field {
+            let ident_type = IdentType::Field(cls_ty, field_id);
+            self.src
+                .map_idents
+                .insert_or_replace(e.lhs.id(), ident_type);
 
-                    let object_type = object_type.name(self.vm);
-                    let lhs_type = fty.name(self.vm);
-                    let rhs_type = rhs_type.name(self.vm);
+            let cls = self
+                .vm
+                .classes
+                .idx(cls_ty.cls_id(self.vm).expect("no class"));
+            let cls = cls.read();
+            let field = &cls.fields[field_id];
+            let fty = fty.unwrap();
 
-                    let msg = SemError::AssignField(name, object_type, lhs_type, rhs_type);
-                    self.vm.diag.lock().report(self.file, e.pos, msg);
-                }
+            if !e.initializer && !field.reassignable {
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, e.pos, SemError::LetReassigned);
+            }
+
+            if !fty.allows(self.vm, rhs_type) && !rhs_type.is_error() {
+                let name = self.vm.interner.str(name).to_string();
+
+                let object_type = object_type.name(self.vm);
+                let lhs_type = fty.name(self.vm);
+                let rhs_type = rhs_type.name(self.vm);
+
+                let msg = SemError::AssignField(name, object_type, lhs_type, rhs_type);
+                self.vm.diag.lock().report(self.file, e.pos, msg);
+            }
+
+            self.src.set_ty(e.id, BuiltinType::Unit);
+            return;
+        }
+
+        if let BuiltinType::Struct(struct_id, _) = object_type {
+            let struc = self.vm.structs.idx(struct_id);
+
+            if struc.lock().field_by_name(name).is_some() {
+                // struct fields are immutable, a modified copy needs to be created instead
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, e.pos, SemError::LetReassigned);
 
                 self.src.set_ty(e.id, BuiltinType::Unit);
                 return;
@@ -785,7 +1212,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let ty = ty.name(self.vm);
             let msg = SemError::UnOpType(op.as_str().into(), ty);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         self.src.set_ty(e.id, BuiltinType::Error);
@@ -875,7 +1305,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType(op.as_str().into(), lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
 
@@ -933,7 +1366,13 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         lhs_type: BuiltinType,
         rhs_type: BuiltinType,
     ) {
-        if lhs_type.allows(self.vm, rhs_type) {
+        // only enums without payloads can be compared by their variant
+        let simple_enumeration = {
+            let enum_id = lhs_type.enum_id().unwrap();
+            self.vm.enums[enum_id].read().simple_enumeration
+        };
+
+        if simple_enumeration && lhs_type.allows(self.vm, rhs_type) {
             let intrinsic = match op {
                 CmpOp::Eq => Intrinsic::EnumEq,
                 CmpOp::Ne => Intrinsic::EnumNe,
@@ -950,7 +1389,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType("equals".into(), lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
         }
@@ -970,11 +1412,14 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType(op, lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
     }
 
-    fn check_expr_call(&mut self, e: &'ast ExprCallType, _expected_ty: BuiltinType) -> BuiltinType {
+    fn check_expr_call(&mut self, e: &'ast ExprCallType, expected_ty: BuiltinType) -> BuiltinType {
         self.used_in_call.insert(e.callee.id());
 
         let expr_type = self.check_expr(&e.callee, BuiltinType::Any);
@@ -988,11 +1433,11 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
         match ident_type {
             Some(IdentType::Fct(fct_id)) => {
-                self.check_expr_call_ident(e, fct_id, TypeList::empty(), &arg_types)
+                self.check_expr_call_ident(e, fct_id, TypeList::empty(), &arg_types, expected_ty)
             }
 
             Some(IdentType::FctType(fct_id, type_params)) => {
-                self.check_expr_call_ident(e, fct_id, type_params, &arg_types)
+                self.check_expr_call_ident(e, fct_id, type_params, &arg_types, expected_ty)
             }
 
             Some(IdentType::Class(cls_id)) | Some(IdentType::ClassAndModule(cls_id, _)) => {
@@ -1009,11 +1454,18 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 method_name,
                 TypeList::empty(),
                 &arg_types,
+                expected_ty,
             ),
 
-            Some(IdentType::MethodType(object_type, method_name, type_params)) => {
-                self.check_expr_call_method(e, object_type, method_name, type_params, &arg_types)
-            }
+            Some(IdentType::MethodType(object_type, method_name, type_params)) => self
+                .check_expr_call_method(
+                    e,
+                    object_type,
+                    method_name,
+                    type_params,
+                    &arg_types,
+                    expected_ty,
+                ),
 
             Some(IdentType::StaticMethod(object_type, method_name)) => self
                 .check_expr_call_static_method(
@@ -1022,6 +1474,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                     method_name,
                     TypeList::empty(),
                     &arg_types,
+                    expected_ty,
                 ),
 
             Some(IdentType::StaticMethodType(object_type, method_name, type_params)) => self
@@ -1031,6 +1484,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                     method_name,
                     type_params,
                     &arg_types,
+                    expected_ty,
                 ),
 
             Some(IdentType::TypeParamStaticMethod(ty, name)) => {
@@ -1053,6 +1507,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 self.check_expr_call_enum(e, enum_id, variant_id, &arg_types)
             }
 
+            Some(IdentType::Struct(struct_id)) | Some(IdentType::StructAndModule(struct_id, _)) => {
+                self.check_expr_call_struct(e, struct_id, &arg_types)
+            }
+
             _ => {
                 if expr_type.is_error() {
                     self.src.set_ty(e.id, expr_type);
@@ -1102,6 +1560,54 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         return ty;
     }
 
+    fn check_expr_call_struct(
+        &mut self,
+        e: &'ast ExprCallType,
+        struct_id: StructId,
+        arg_types: &[BuiltinType],
+    ) -> BuiltinType {
+        let struc = self.vm.structs.idx(struct_id);
+        let struc = struc.lock();
+
+        if !self.check_expr_call_struct_args(&*struc, arg_types) {
+            let struct_name = self.vm.interner.str(struc.name).to_string();
+            let field_types = struc
+                .fields
+                .iter()
+                .map(|field| field.ty.name(self.vm))
+                .collect::<Vec<_>>();
+            let arg_types = arg_types
+                .iter()
+                .map(|a| a.name(self.vm))
+                .collect::<Vec<_>>();
+            let msg = SemError::StructArgsIncompatible(struct_name, field_types, arg_types);
+            self.vm.diag.lock().report(self.file, e.pos, msg);
+        }
+
+        let list_id = self.vm.lists.lock().insert(TypeList::empty());
+        let ty = BuiltinType::Struct(struct_id, list_id);
+        self.src.set_ty(e.id, ty);
+        ty
+    }
+
+    fn check_expr_call_struct_args(
+        &mut self,
+        struc: &StructData,
+        arg_types: &[BuiltinType],
+    ) -> bool {
+        if struc.fields.len() != arg_types.len() {
+            return false;
+        }
+
+        for (field, &arg_ty) in struc.fields.iter().zip(arg_types) {
+            if !field.ty.allows(self.vm, arg_ty) {
+                return false;
+            }
+        }
+
+        true
+    }
+
     fn check_expr_call_enum_args(
         &mut self,
         variant: &vm::EnumVariant,
@@ -1129,17 +1635,15 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
     ) -> BuiltinType {
         let mut fcts = Vec::new();
 
-        let (type_param, tp_id) = match tp {
+        let type_param = self.fct.type_param_def(self.vm, tp);
+
+        let tp_id = match tp {
             BuiltinType::FctTypeParam(fct_id, tp_id) => {
                 assert_eq!(self.fct.id, fct_id);
-                (self.fct.type_params[tp_id.idx()].clone(), tp_id)
+                TypeParamId::Fct(tp_id)
             }
 
-            BuiltinType::ClassTypeParam(cls_id, tp_id) => {
-                let cls = self.vm.classes.idx(cls_id);
-                let cls = cls.read();
-                (cls.type_params[tp_id.idx()].clone(), tp_id)
-            }
+            BuiltinType::ClassTypeParam(_, tp_id) => TypeParamId::Class(tp_id),
 
             _ => unreachable!(),
         };
@@ -1195,10 +1699,13 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 .map(|a| a.name(self.vm))
                 .collect::<Vec<_>>();
             let msg = SemError::ParamTypesIncompatible(fct_name, fct_params, arg_types);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
-        let call_type = CallType::TraitStatic(TypeParamId::Fct(tp_id), trait_id, fct_id);
+        let call_type = CallType::TraitStatic(tp_id, trait_id, fct_id);
         self.src.map_calls.insert(e.id, Arc::new(call_type));
 
         let return_type = replace_type_param(
@@ -1220,6 +1727,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         expr_type: BuiltinType,
         arg_types: &[BuiltinType],
     ) -> BuiltinType {
+        if let BuiltinType::Lambda(lambda_id) = expr_type {
+            return self.check_expr_call_lambda(e, expr_type, lambda_id, arg_types);
+        }
+
         let get = self.vm.interner.intern("get");
 
         if let Some((_, fct_id, return_type)) =
@@ -1240,21 +1751,68 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         }
     }
 
+    fn check_expr_call_lambda(
+        &mut self,
+        e: &'ast ExprCallType,
+        expr_type: BuiltinType,
+        lambda_id: LambdaId,
+        arg_types: &[BuiltinType],
+    ) -> BuiltinType {
+        let lambda = self.vm.lambda_types.lock().get(lambda_id);
+
+        let compatible = lambda.params().len() == arg_types.len()
+            && lambda
+                .params()
+                .iter()
+                .zip(arg_types)
+                .all(|(def, &arg)| arg.is_error() || def.allows(self.vm, arg));
+
+        if !compatible {
+            let lambda_name = expr_type.name(self.vm);
+            let lambda_params = lambda
+                .params()
+                .iter()
+                .map(|a| a.name(self.vm))
+                .collect::<Vec<_>>();
+            let arg_types = arg_types
+                .iter()
+                .map(|a| a.name(self.vm))
+                .collect::<Vec<_>>();
+            let msg = SemError::ParamTypesIncompatible(lambda_name, lambda_params, arg_types);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
+        }
+
+        let call_type = CallType::Lambda(expr_type);
+        self.src.map_calls.insert(e.id, Arc::new(call_type));
+
+        let return_type = lambda.ret();
+        self.src.set_ty(e.id, return_type);
+
+        return_type
+    }
+
     fn check_expr_call_ident(
         &mut self,
         e: &'ast ExprCallType,
         fct_id: FctId,
         type_params: TypeList,
         arg_types: &[BuiltinType],
+        expected_ty: BuiltinType,
     ) -> BuiltinType {
         let mut lookup = MethodLookup::new(self.vm, self.file)
+            .caller(self.fct)
             .pos(e.pos)
             .callee(fct_id)
             .args(&arg_types)
-            .fct_type_params(&type_params);
+            .fct_type_params(&type_params)
+            .expected_return_type(expected_ty);
 
         let ty = if lookup.find() {
-            let call_type = CallType::Fct(fct_id, TypeList::empty(), type_params.clone());
+            let type_params = lookup.found_fct_type_params().unwrap().clone();
+            let call_type = CallType::Fct(fct_id, TypeList::empty(), type_params);
             self.src.map_calls.insert(e.id, Arc::new(call_type));
 
             lookup.found_ret().unwrap()
@@ -1274,26 +1832,26 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         method_name: Name,
         type_params: TypeList,
         arg_types: &[BuiltinType],
+        expected_ty: BuiltinType,
     ) -> BuiltinType {
         let cls_id = object_type.cls_id(self.vm).unwrap();
         let cls_type_params = object_type.type_params(self.vm);
         assert_eq!(cls_type_params.len(), 0);
 
         let mut lookup = MethodLookup::new(self.vm, self.file)
+            .caller(self.fct)
             .pos(e.pos)
             .static_method(cls_id)
             .name(method_name)
             .args(arg_types)
-            .fct_type_params(&type_params);
+            .fct_type_params(&type_params)
+            .expected_return_type(expected_ty);
 
         if lookup.find() {
             let fct_id = lookup.found_fct_id().unwrap();
             let return_type = lookup.found_ret().unwrap();
-            let call_type = Arc::new(CallType::Fct(
-                fct_id,
-                TypeList::empty(),
-                type_params.clone(),
-            ));
+            let type_params = lookup.found_fct_type_params().unwrap().clone();
+            let call_type = Arc::new(CallType::Fct(fct_id, TypeList::empty(), type_params));
             self.src.map_calls.insert(e.id, call_type.clone());
 
             self.src.set_ty(e.id, return_type);
@@ -1313,6 +1871,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         method_name: Name,
         type_params: TypeList,
         arg_types: &[BuiltinType],
+        expected_ty: BuiltinType,
     ) -> BuiltinType {
         if object_type.is_type_param() {
             assert_eq!(type_params.len(), 0);
@@ -1326,24 +1885,27 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         }
 
         let mut lookup = MethodLookup::new(self.vm, self.file)
+            .caller(self.fct)
             .method(object_type)
             .pos(e.pos)
             .name(method_name)
             .fct_type_params(&type_params)
-            .args(arg_types);
+            .args(arg_types)
+            .expected_return_type(expected_ty);
 
         if lookup.find() {
             let fct_id = lookup.found_fct_id().unwrap();
             let return_type = lookup.found_ret().unwrap();
+            let type_params = lookup.found_fct_type_params().unwrap().clone();
 
             let call_type = if let BuiltinType::Trait(trait_id) = object_type {
                 CallType::Trait(trait_id, fct_id)
             } else {
                 let method_type = lookup.found_class_type().unwrap();
                 if method_type.is_module() {
-                    CallType::ModuleMethod(method_type, fct_id, type_params.clone())
+                    CallType::ModuleMethod(method_type, fct_id, type_params)
                 } else {
-                    CallType::Method(method_type, fct_id, type_params.clone())
+                    CallType::Method(method_type, fct_id, type_params)
                 }
             };
 
@@ -1368,6 +1930,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         arg_types: &[BuiltinType],
     ) -> BuiltinType {
         let mut lookup = MethodLookup::new(self.vm, self.file)
+            .caller(self.fct)
             .pos(e.pos)
             .ctor(cls_id)
             .args(arg_types)
@@ -1404,21 +1967,8 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         name: Name,
         arg_types: &[BuiltinType],
     ) -> BuiltinType {
-        match object_type {
-            BuiltinType::FctTypeParam(_, tpid) => {
-                let tp = &self.fct.type_params[tpid.idx()];
-                self.check_expr_call_generic_type_param(e, object_type, tp, name, arg_types)
-            }
-
-            BuiltinType::ClassTypeParam(cls_id, tpid) => {
-                let cls = self.vm.classes.idx(cls_id);
-                let cls = cls.read();
-                let tp = &cls.type_params[tpid.idx()];
-                self.check_expr_call_generic_type_param(e, object_type, tp, name, arg_types)
-            }
-
-            _ => unreachable!(),
-        }
+        let tp = self.fct.type_param_def(self.vm, object_type);
+        self.check_expr_call_generic_type_param(e, object_type, &tp, name, arg_types)
     }
 
     fn check_expr_call_generic_type_param(
@@ -1508,9 +2058,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             return BuiltinType::Error;
         }
 
-        match self.vm.sym.lock().get_type(class) {
+        match self.vm.sym.lock().get_type_in(self.file, class) {
             Some(SymClass(cls_id)) => {
                 let mut lookup = MethodLookup::new(self.vm, self.file)
+                    .caller(self.fct)
                     .pos(e.pos)
                     .static_method(cls_id)
                     .name(method_name)
@@ -1618,6 +2169,11 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
     }
 
     fn check_expr_path(&mut self, e: &'ast ExprPathType, _expected_ty: BuiltinType) -> BuiltinType {
+        if self.src.map_idents.get(e.id).is_some() {
+            // path was already resolved to a package member by nameck
+            return self.check_ident(e.id, e.pos);
+        }
+
         let ident_type = self.src.map_idents.get(e.lhs.id());
 
         let name = if let Some(ident) = e.rhs.to_ident() {
@@ -1795,6 +2351,20 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             return BuiltinType::Error;
         }
 
+        if let BuiltinType::Struct(struct_id, _) = object_type {
+            let struc = self.vm.structs.idx(struct_id);
+            let struc = struc.lock();
+
+            if let Some(field_id) = struc.field_by_name(name) {
+                let ident_type = IdentType::StructField(object_type, field_id);
+                self.src.map_idents.insert_or_replace(e.id, ident_type);
+
+                let fty = struc.fields[field_id.to_usize()].ty;
+                self.src.set_ty(e.id, fty);
+                return fty;
+            }
+        }
+
         if object_type.cls_id(self.vm).is_some() {
             if let Some((cls_ty, field_id)) = find_field_in_class(self.vm, object_type, name) {
                 let ident_type = IdentType::Field(cls_ty, field_id);
@@ -1934,21 +2504,44 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         e: &'ast ExprLambdaType,
         _expected_ty: BuiltinType,
     ) -> BuiltinType {
-        let ret = if let Some(ref ty) = e.ret {
+        let ret = if let Some(ref ty) = e.fct.return_type {
             self.src.ty(ty.id())
         } else {
             BuiltinType::Unit
         };
 
         let params = e
+            .fct
             .params
             .iter()
             .map(|p| self.src.ty(p.data_type.id()))
             .collect::<Vec<_>>();
 
-        let ty = self.vm.lambda_types.lock().insert(params, ret);
+        for (param, &ty) in e.fct.params.iter().zip(&params) {
+            let var_id = *self.src.map_vars.get(param.id).unwrap();
+            self.src.vars[var_id].ty = ty;
+        }
+
+        let ty = self.vm.lambda_types.lock().insert(params.clone(), ret);
         let ty = BuiltinType::Lambda(ty);
 
+        self.lambda_return_types.push(ret);
+        self.may_throw.push(false);
+        self.check_fct_block(e.fct.block());
+        self.may_throw.pop();
+        self.lambda_return_types.pop();
+
+        let lambda_fct_id = self.src.map_lambdas.get(e.fct.id).unwrap().fct_id;
+        let lambda_fct = self.vm.fcts.idx(lambda_fct_id);
+        let mut lambda_fct = lambda_fct.write();
+
+        // first parameter is the lambda object itself
+        lambda_fct.param_types = Some(ty).into_iter().chain(params).collect();
+        lambda_fct.return_type = ret;
+        lambda_fct.type_params = self.fct.type_params.clone();
+        lambda_fct.where_bounds = self.fct.where_bounds.clone();
+        lambda_fct.initialized = true;
+
         self.src.set_ty(e.id, ty);
 
         ty
@@ -1975,7 +2568,13 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             return ty;
         }
 
-        if !typeparamck::check_type(self.vm, self.file, e.data_type.pos(), check_type) {
+        if !typeparamck::check_type(
+            self.vm,
+            self.file,
+            e.data_type.pos(),
+            check_type,
+            Some(self.fct),
+        ) {
             let ty = if e.is {
                 BuiltinType::Bool
             } else {
@@ -2084,23 +2683,13 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             if idx % 2 != 0 {
                 let part_expr = self.check_expr(part, BuiltinType::Any);
 
-                let implements_stringable = match part_expr {
-                    BuiltinType::FctTypeParam(fct_id, tp_id) => {
-                        assert_eq!(self.fct.id, fct_id);
-                        self.fct.type_params[tp_id.idx()]
-                            .trait_bounds
-                            .contains(&stringable_trait)
-                    }
-
-                    BuiltinType::ClassTypeParam(cls_id, tp_id) => {
-                        let cls = self.vm.classes.idx(cls_id);
-                        let cls = cls.read();
-                        cls.type_params[tp_id.idx()]
-                            .trait_bounds
-                            .contains(&stringable_trait)
-                    }
-
-                    _ => part_expr.implements_trait(self.vm, stringable_trait),
+                let implements_stringable = if part_expr.is_type_param() {
+                    self.fct
+                        .type_param_def(self.vm, part_expr)
+                        .trait_bounds
+                        .contains(&stringable_trait)
+                } else {
+                    part_expr.implements_trait(self.vm, stringable_trait)
                 };
 
                 if implements_stringable || part_expr.is_error() {
@@ -2124,7 +2713,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
     }
 
     fn check_expr(&mut self, e: &'ast Expr, expected_ty: BuiltinType) -> BuiltinType {
-        match *e {
+        // only the call directly wrapped by `try` is marked
+        let in_try = mem::replace(&mut self.in_try, false);
+
+        let ty = match *e {
             ExprLitChar(ref expr) => self.check_expr_lit_char(expr, expected_ty),
             ExprLitInt(ref expr) => self.check_expr_lit_int(expr, false, expected_ty),
             ExprLitFloat(ref expr) => self.check_expr_lit_float(expr, false, expected_ty),
@@ -2147,7 +2739,27 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             ExprBlock(ref expr) => self.check_expr_block(expr, expected_ty),
             ExprIf(ref expr) => self.check_expr_if(expr, expected_ty),
             ExprTuple(ref expr) => self.check_expr_tuple(expr, expected_ty),
+            ExprMatch(ref expr) => self.check_expr_match(expr, expected_ty),
+            ExprTry(ref expr) => self.check_expr_try(expr, expected_ty),
+        };
+
+        if self.call_throws(e.id()) {
+            if !in_try {
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, e.pos(), SemError::ThrowingCallWithoutTry);
+            }
+
+            self.check_throw_allowed(e.pos());
+        } else if in_try {
+            self.vm
+                .diag
+                .lock()
+                .report(self.file, e.pos(), SemError::TryCallNonThrowing);
         }
+
+        ty
     }
 }
 
@@ -2162,6 +2774,9 @@ impl<'a, 'ast> Visitor<'ast> for TypeCheck<'a, 'ast> {
             StmtWhile(ref stmt) => self.check_stmt_while(stmt),
             StmtFor(ref stmt) => self.check_stmt_for(stmt),
             StmtReturn(ref stmt) => self.check_stmt_return(stmt),
+            StmtThrow(ref stmt) => self.check_stmt_throw(stmt),
+            StmtDo(ref stmt) => self.check_stmt_do(stmt),
+            StmtDefer(ref stmt) => self.visit_stmt(&stmt.block),
 
             // for the rest of the statements, no special handling is necessary
             StmtBreak(_) => visit::walk_stmt(self, s),
@@ -2264,7 +2879,7 @@ fn arg_allows(
                 self_ty,
             )
         }
-        BuiltinType::Trait(_) => panic!("trait should not occur in fct definition."),
+        BuiltinType::Trait(_) => def.allows(vm, arg),
 
         BuiltinType::ClassTypeParam(cls_id, tpid) => {
             if def == arg {
@@ -2396,11 +3011,175 @@ fn arg_allows(
 
         BuiltinType::Module(_) => def == arg,
 
-        BuiltinType::Lambda(_) => {
-            // for now expect the exact same params and return types
-            // possible improvement: allow super classes for params,
-            //                             sub class for return type
-            def == arg
+        BuiltinType::Lambda(lambda_id) => match arg {
+            BuiltinType::Lambda(other_lambda_id) => {
+                if lambda_id == other_lambda_id {
+                    return true;
+                }
+
+                // for now expect the exact same params and return types
+                // possible improvement: allow super classes for params,
+                //                             sub class for return type
+                let lambda = vm.lambda_types.lock().get(lambda_id);
+                let other_lambda = vm.lambda_types.lock().get(other_lambda_id);
+
+                if lambda.params().len() != other_lambda.params().len() {
+                    return false;
+                }
+
+                let types = lambda.params().iter().cloned().chain(Some(lambda.ret()));
+                let other_types = other_lambda
+                    .params()
+                    .iter()
+                    .cloned()
+                    .chain(Some(other_lambda.ret()));
+
+                for (ty, other_ty) in types.zip(other_types) {
+                    if !arg_allows(
+                        vm,
+                        ty,
+                        other_ty,
+                        global_cls_id,
+                        global_fct_id,
+                        cls_tps,
+                        fct_tps,
+                        self_ty,
+                    ) {
+                        return false;
+                    }
+                }
+
+                true
+            }
+
+            _ => false,
+        },
+    }
+}
+
+// Infers the type params of `fct` from the argument types and the type the call
+// is expected to return. Returns the index of the first type param without a
+// unique solution on failure.
+pub fn infer_fct_type_params(
+    vm: &VM,
+    fct: &Fct,
+    args: &[BuiltinType],
+    expected_ty: BuiltinType,
+) -> Result<TypeList, usize> {
+    let mut inference = TypeParamInference {
+        vm,
+        fct_id: fct.id,
+        solution: vec![None; fct.type_params.len()],
+        conflict: None,
+    };
+
+    let def = fct.params_without_self();
+
+    for (ind, &arg) in args.iter().enumerate() {
+        let def = if fct.variadic_arguments && ind + 1 >= def.len() {
+            def.last().cloned()
+        } else {
+            def.get(ind).cloned()
+        };
+
+        if let Some(def) = def {
+            inference.unify(def, arg);
+        }
+    }
+
+    if let Some(idx) = inference.conflict {
+        return Err(idx);
+    }
+
+    // the expected type only solves type params the arguments leave open, a
+    // mismatch with the arguments is reported by the caller as wrong type
+    let mut hint = TypeParamInference {
+        vm,
+        fct_id: fct.id,
+        solution: vec![None; fct.type_params.len()],
+        conflict: None,
+    };
+
+    if expected_ty != BuiltinType::Any && !expected_ty.is_error() {
+        hint.unify(fct.return_type, expected_ty);
+    }
+
+    let mut types = Vec::with_capacity(inference.solution.len());
+
+    for (idx, ty) in inference.solution.into_iter().enumerate() {
+        match ty.or(hint.solution[idx]) {
+            Some(ty) => types.push(ty),
+            None => return Err(idx),
+        }
+    }
+
+    Ok(TypeList::with(types))
+}
+
+struct TypeParamInference<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    fct_id: FctId,
+    solution: Vec<Option<BuiltinType>>,
+    conflict: Option<usize>,
+}
+
+impl<'a, 'ast> TypeParamInference<'a, 'ast> {
+    fn unify(&mut self, def: BuiltinType, arg: BuiltinType) {
+        match (def, arg) {
+            (_, BuiltinType::Nil) | (_, BuiltinType::Error) | (_, BuiltinType::Any) => {}
+
+            (BuiltinType::FctTypeParam(fct_id, tpid), _) if fct_id == self.fct_id => {
+                let idx = tpid.idx();
+
+                match self.solution[idx] {
+                    None => self.solution[idx] = Some(arg),
+                    Some(ty) if ty.allows(self.vm, arg) => {}
+                    Some(ty) if arg.allows(self.vm, ty) => self.solution[idx] = Some(arg),
+                    Some(_) => {
+                        if self.conflict.is_none() {
+                            self.conflict = Some(idx);
+                        }
+                    }
+                }
+            }
+
+            (
+                BuiltinType::Class(cls_id, list_id),
+                BuiltinType::Class(other_cls_id, other_list_id),
+            ) if cls_id == other_cls_id => {
+                let params = self.vm.lists.lock().get(list_id);
+                let other_params = self.vm.lists.lock().get(other_list_id);
+
+                for (tp, op) in params.iter().zip(other_params.iter()) {
+                    self.unify(tp, op);
+                }
+            }
+
+            (BuiltinType::Tuple(tuple_id), BuiltinType::Tuple(other_tuple_id)) => {
+                let subtypes = self.vm.tuples.lock().get(tuple_id);
+                let other_subtypes = self.vm.tuples.lock().get(other_tuple_id);
+
+                if subtypes.len() == other_subtypes.len() {
+                    for (&ty, &other_ty) in subtypes.iter().zip(other_subtypes.iter()) {
+                        self.unify(ty, other_ty);
+                    }
+                }
+            }
+
+            (BuiltinType::Lambda(lambda_id), BuiltinType::Lambda(other_lambda_id)) => {
+                let lambda = self.vm.lambda_types.lock().get(lambda_id);
+                let other_lambda = self.vm.lambda_types.lock().get(other_lambda_id);
+
+                if lambda.params().len() == other_lambda.params().len() {
+                    for (&ty, &other_ty) in lambda.params().iter().zip(other_lambda.params()) {
+                        self.unify(ty, other_ty);
+                    }
+
+                    self.unify(lambda.ret(), other_lambda.ret());
+                }
+            }
+
+            _ => {}
         }
     }
 }
@@ -2,9 +2,9 @@ use crate::error::msg::SemError;
 use crate::semck::specialize::replace_type_param;
 use crate::semck::typeparamck;
 use crate::ty::{BuiltinType, TypeList};
-use crate::typeck::expr::args_compatible;
+use crate::typeck::expr::{args_compatible, infer_fct_type_params};
 use crate::vm::{
-    find_methods_in_class, find_methods_in_enum, ClassId, FctId, FctParent, FileId, TraitId,
+    find_methods_in_class, find_methods_in_enum, ClassId, Fct, FctId, FctParent, FileId, TraitId,
     TypeParam, VM,
 };
 
@@ -31,11 +31,14 @@ pub struct MethodLookup<'a, 'ast: 'a> {
     cls_tps: Option<&'a TypeList>,
     fct_tps: Option<&'a TypeList>,
     ret: Option<BuiltinType>,
+    expected_ret: Option<BuiltinType>,
     pos: Option<Position>,
+    caller: Option<&'a Fct<'ast>>,
 
     found_fct_id: Option<FctId>,
     found_class_type: Option<BuiltinType>,
     found_ret: Option<BuiltinType>,
+    found_fct_tps: Option<TypeList>,
 
     found_multiple_functions: bool,
 }
@@ -51,11 +54,14 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
             cls_tps: None,
             fct_tps: None,
             ret: None,
+            expected_ret: None,
             pos: None,
+            caller: None,
 
             found_fct_id: None,
             found_class_type: None,
             found_ret: None,
+            found_fct_tps: None,
 
             found_multiple_functions: false,
         }
@@ -109,6 +115,11 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
         self
     }
 
+    pub fn caller(mut self, caller: &'a Fct<'ast>) -> MethodLookup<'a, 'ast> {
+        self.caller = Some(caller);
+        self
+    }
+
     pub fn cls_type_params(mut self, cls_tps: &'a TypeList) -> MethodLookup<'a, 'ast> {
         self.cls_tps = Some(cls_tps);
         self
@@ -129,6 +140,12 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
         self
     }
 
+    // only used as a hint for inferring the type params of the function
+    pub fn expected_return_type(mut self, ret: BuiltinType) -> MethodLookup<'a, 'ast> {
+        self.expected_ret = Some(ret);
+        self
+    }
+
     pub fn find(&mut self) -> bool {
         let kind = self.kind.expect("kind not set");
         let args = self.args.expect("args not set");
@@ -227,6 +244,26 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
         let fct = self.vm.fcts.idx(fct_id);
         let fct = fct.read();
 
+        if let LookupKind::Trait(trait_id) = kind {
+            let uses_self = fct.return_type == BuiltinType::This
+                || fct
+                    .params_without_self()
+                    .iter()
+                    .any(|&ty| ty == BuiltinType::This);
+
+            if uses_self {
+                let xtrait = self.vm.traits[trait_id].read();
+                let trait_name = self.vm.interner.str(xtrait.name).to_string();
+                let name = self.vm.interner.str(fct.name).to_string();
+                let msg = SemError::TraitObjectMethodUsesSelf(trait_name, name);
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, self.pos.expect("pos not set"), msg);
+                return false;
+            }
+        }
+
         let cls_id = match fct.parent {
             FctParent::Class(cls_id) => Some(cls_id),
             FctParent::Impl(impl_id) => {
@@ -248,14 +285,46 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
             return false;
         }
 
-        let fct_tps: TypeList = if let Some(fct_tps) = self.fct_tps {
-            if !self.check_fct_tps(fct_tps) {
-                return false;
+        if !fct.where_bounds.is_empty()
+            && !typeparamck::check_where_bounds(
+                self.vm,
+                self.file,
+                self.pos.expect("no pos"),
+                &*fct,
+                &cls_tps,
+                self.caller,
+            )
+        {
+            return false;
+        }
+
+        let fct_tps: TypeList = match self.fct_tps {
+            Some(fct_tps) if fct_tps.len() > 0 || fct.type_params.is_empty() => {
+                if !self.check_fct_tps(fct_tps) {
+                    return false;
+                }
+
+                fct_tps.clone()
             }
 
-            fct_tps.clone()
-        } else {
-            TypeList::empty()
+            _ if !fct.type_params.is_empty() => {
+                if args.contains(&BuiltinType::Error) {
+                    return false;
+                }
+
+                let fct_tps = match self.infer_fct_tps(&*fct, args) {
+                    Some(fct_tps) => fct_tps,
+                    None => return false,
+                };
+
+                if !self.check_fct_tps(&fct_tps) {
+                    return false;
+                }
+
+                fct_tps
+            }
+
+            _ => TypeList::empty(),
         };
 
         if args.contains(&BuiltinType::Error) {
@@ -297,6 +366,7 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
 
         if self.ret.is_none() || self.ret.unwrap() == cmp_type {
             self.found_ret = Some(cmp_type);
+            self.found_fct_tps = Some(fct_tps);
             true
         } else {
             false
@@ -398,6 +468,24 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
         self.check_tps(&fct_tps, tps)
     }
 
+    fn infer_fct_tps(&self, fct: &Fct, args: &[BuiltinType]) -> Option<TypeList> {
+        let expected_ret = self.expected_ret.unwrap_or(BuiltinType::Any);
+
+        match infer_fct_type_params(self.vm, fct, args, expected_ret) {
+            Ok(fct_tps) => Some(fct_tps),
+            Err(idx) => {
+                let fct_name = self.vm.interner.str(fct.name).to_string();
+                let tp_name = self.vm.interner.str(fct.type_params[idx].name).to_string();
+                let msg = SemError::CannotInferTypeParam(tp_name, fct_name);
+                self.vm
+                    .diag
+                    .lock()
+                    .report(self.file, self.pos.expect("pos not set"), msg);
+                None
+            }
+        }
+    }
+
     fn check_tps(&self, specified_tps: &[TypeParam], tps: &TypeList) -> bool {
         typeparamck::check_params(
             self.vm,
@@ -405,6 +493,7 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
             self.pos.expect("no pos"),
             specified_tps,
             tps,
+            self.caller,
         )
     }
 
@@ -419,4 +508,8 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
     pub fn found_ret(&self) -> Option<BuiltinType> {
         self.found_ret
     }
+
+    pub fn found_fct_type_params(&self) -> Option<&TypeList> {
+        self.found_fct_tps.as_ref()
+    }
 }
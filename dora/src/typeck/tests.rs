@@ -770,6 +770,53 @@ fn struct_lit() {
     );
 }
 
+#[test]
+fn struct_ctor() {
+    ok("struct Foo { a: Int32 } fun foo() -> Foo { Foo(1) }");
+    ok("struct Foo { a: Int32, b: String } fun foo() -> Foo { Foo(1, \"b\") }");
+    ok("struct Foo {} fun foo() -> Foo { Foo() }");
+    err(
+        "struct Foo { a: Int32 } fun foo() -> Foo { Foo(true) }",
+        pos(1, 47),
+        SemError::StructArgsIncompatible("Foo".into(), vec!["Int32".into()], vec!["Bool".into()]),
+    );
+    err(
+        "struct Foo { a: Int32 } fun foo() -> Foo { Foo(1, 2) }",
+        pos(1, 47),
+        SemError::StructArgsIncompatible(
+            "Foo".into(),
+            vec!["Int32".into()],
+            vec!["Int32".into(), "Int32".into()],
+        ),
+    );
+    err(
+        "struct Foo { a: Int32 } fun foo() -> Foo { Foo }",
+        pos(1, 44),
+        SemError::StructArgsIncompatible("Foo".into(), vec!["Int32".into()], Vec::new()),
+    );
+}
+
+#[test]
+fn struct_field() {
+    ok("struct Foo { a: Int32 } fun foo(x: Foo) -> Int32 { x.a }");
+    ok("struct Foo { a: Bar } struct Bar { b: String } fun foo(x: Foo) -> String { x.a.b }");
+    err(
+        "struct Foo { a: Int32 } fun foo(x: Foo) -> Bool { x.a }",
+        pos(1, 49),
+        SemError::ReturnType("Bool".into(), "Int32".into()),
+    );
+    err(
+        "struct Foo { a: Int32 } fun foo(x: Foo) -> Int32 { x.b }",
+        pos(1, 53),
+        SemError::UnknownField("b".into(), "Foo".into()),
+    );
+    err(
+        "struct Foo { a: Int32 } fun foo(x: Foo) { x.a = 1; }",
+        pos(1, 47),
+        SemError::LetReassigned,
+    );
+}
+
 #[test]
 fn lit_int64() {
     ok("fun f() -> Int64 { return 1L; }");
@@ -1040,7 +1087,7 @@ fn test_fct_with_type_params() {
     err(
         "fun f[T]() {} fun g() { f(); }",
         pos(1, 26),
-        SemError::WrongNumberTypeParams(1, 0),
+        SemError::CannotInferTypeParam("T".into(), "f".into()),
     );
     ok("fun f[T]() {} fun g() { f[Int32](); }");
     ok("fun f[T1, T2]() {} fun g() { f[Int32, String](); }");
@@ -1245,6 +1292,64 @@ fn lambda_assignment() {
     );
 }
 
+#[test]
+fn lambda_call() {
+    ok("fun f(x: (Int32) -> Int32) -> Int32 { x(1) }");
+    ok("fun f(x: () -> ()) { x(); }");
+    ok("fun f() -> Int32 { let x = |a: Int32, b: Int32| -> Int32 { a + b }; x(1, 2) }");
+    err(
+        "fun f(x: (Int32) -> Int32) { x(\"a\"); }",
+        pos(1, 31),
+        SemError::ParamTypesIncompatible(
+            "(Int32) -> Int32".into(),
+            vec!["Int32".into()],
+            vec!["String".into()],
+        ),
+    );
+    err(
+        "fun f(x: (Int32) -> Int32) { x(); }",
+        pos(1, 31),
+        SemError::ParamTypesIncompatible("(Int32) -> Int32".into(), vec!["Int32".into()], vec![]),
+    );
+    err(
+        "fun f(x: (Int32) -> Int32) -> String { x(1) }",
+        pos(1, 38),
+        SemError::ReturnType("String".into(), "Int32".into()),
+    );
+}
+
+#[test]
+fn lambda_body() {
+    ok("fun f(a: Int32) -> (Int32) -> Int32 { |b: Int32| -> Int32 { a + b } }");
+    ok("fun f() { var a = 1; let x = || { a = a + 1; }; x(); }");
+    ok("fun f() -> Int32 { let x = || -> Int32 { return 1; }; x() }");
+    err(
+        "fun f() { let x = || -> Int32 { \"a\" }; }",
+        pos(1, 31),
+        SemError::ReturnType("Int32".into(), "String".into()),
+    );
+    err(
+        "fun f() -> Int32 { let x = || { return 1; }; 1 }",
+        pos(1, 33),
+        SemError::ReturnType("()".into(), "Int32".into()),
+    );
+}
+
+#[test]
+fn lambda_argument() {
+    ok("fun f(x: (Int32) -> Int32) {} fun g() { f(|a: Int32| -> Int32 { a }); }");
+    ok("fun g() { let v = Vec[Int32](); let w = v.map[String](|a: Int32| -> String { a.toString() }); }");
+    err(
+        "fun f(x: (Int32) -> Int32) {} fun g() { f(|a: Int64| -> Int32 { 1 }); }",
+        pos(1, 42),
+        SemError::ParamTypesIncompatible(
+            "f".into(),
+            vec!["(Int32) -> Int32".into()],
+            vec!["(Int64) -> Int32".into()],
+        ),
+    );
+}
+
 #[test]
 fn method_call_with_multiple_matching_traits() {
     err(
@@ -1555,6 +1660,38 @@ fn test_trait_object_as_argument() {
     );
 }
 
+#[test]
+fn test_trait_object_assignment() {
+    ok("trait Foo { fun bar() -> Int32; }
+        class A impl Foo for A { fun bar() -> Int32 { 1 } }
+        fun f() -> Int32 { let x: Foo = A(); return x.bar(); }");
+    ok("trait Foo { fun bar() -> Int32; }
+        @open class A impl Foo for A { fun bar() -> Int32 { 1 } }
+        class B: A
+        fun f(b: B) -> Foo { return b; }");
+    ok("trait Foo { fun bar() -> Int32; }
+        fun f() -> Foo { return nil; }");
+    err(
+        "trait Foo { fun bar() -> Int32; }
+        class A
+        fun f() { let x: Foo = A(); }",
+        pos(3, 19),
+        SemError::AssignType("x".into(), "Foo".into(), "A".into()),
+    );
+    err(
+        "trait Foo { fun bar() -> Int32; } trait Baz {}
+        fun f(x: Foo) -> Baz { return x; }",
+        pos(2, 32),
+        SemError::ReturnType("Baz".into(), "Foo".into()),
+    );
+    err(
+        "trait Foo { fun equals(other: Self) -> Bool; }
+        fun f(x: Foo, y: Foo) -> Bool { return x.equals(y); }",
+        pos(2, 56),
+        SemError::TraitObjectMethodUsesSelf("Foo".into(), "equals".into()),
+    );
+}
+
 #[test]
 fn test_type_param_used_as_value() {
     err(
@@ -2128,3 +2265,257 @@ fn check_no_type_params_with_generic_type() {
         SemError::WrongNumberTypeParams(1, 0),
     );
 }
+
+#[test]
+fn match_arms() {
+    ok("enum Foo { A, B } fun f(x: Foo) -> Int32 { match x { Foo::A => 1, Foo::B => 2 } }");
+    ok("fun f(x: (Int32, Bool)) -> Int32 { match x { (a, true) => a, (_, false) => 0 } }");
+    ok("fun f(x: Option[Int32]) -> Int32 { match x { Some(y) => y, None => 0 } }");
+    ok("fun f(x: Int64) -> Int64 { match x { 1L => 0L, -1L => 0L, y => y } }");
+    ok("fun f(x: String) -> Bool { match x { \"a\" => true, _ => false } }");
+    err(
+        "fun f(x: Int32) -> Int32 { match x { 1 => 1, _ => \"a\" } }",
+        pos(1, 46),
+        SemError::MatchBranchTypesIncompatible("Int32".into(), "String".into()),
+    );
+}
+
+#[test]
+fn match_patterns() {
+    err(
+        "fun f(x: Int32) -> Int32 { match x { true => 1, _ => 0 } }",
+        pos(1, 38),
+        SemError::PatternTypeMismatch("Int32".into()),
+    );
+    err(
+        "fun f(x: Int32) -> Int32 { match x { (a, b) => 1 } }",
+        pos(1, 38),
+        SemError::PatternTypeMismatch("Int32".into()),
+    );
+    err(
+        "fun f(x: (Int32, Int32)) -> Int32 { match x { (a, b, c) => a } }",
+        pos(1, 47),
+        SemError::PatternWrongNumberOfParams(2, 3),
+    );
+    err(
+        "enum Foo { A, B } fun f(x: Foo) -> Int32 { match x { Foo::C => 1, _ => 0 } }",
+        pos(1, 54),
+        SemError::UnknownEnumValue("C".into()),
+    );
+    err(
+        "enum Foo { A, B } enum Bar { A } fun f(x: Foo) -> Int32 { match x { Bar::A => 1, _ => 0 } }",
+        pos(1, 69),
+        SemError::PatternTypeMismatch("Foo".into()),
+    );
+    err(
+        "fun f(x: Option[Int32]) -> Int32 { match x { Some(a, b) => a, _ => 0 } }",
+        pos(1, 46),
+        SemError::PatternWrongNumberOfParams(1, 2),
+    );
+    err(
+        "class Foo fun f(x: Option[Int32]) -> Int32 { match x { Foo => 1, _ => 0 } }",
+        pos(1, 56),
+        SemError::PatternTypeMismatch("Option[Int32]".into()),
+    );
+}
+
+#[test]
+fn throw_and_try() {
+    ok("fun f() throws { throw Exception(\"a\"); }");
+    ok("fun g() throws -> Int32 { 1 } fun f() throws -> Int32 { try g() }");
+    ok("class Foo(msg: String): Exception(msg) fun f() throws { throw Foo(\"a\"); }");
+    err(
+        "fun f() throws { throw 1; }",
+        pos(1, 18),
+        SemError::ThrowNotException("Int32".into()),
+    );
+    err(
+        "fun f() { throw Exception(\"a\"); }",
+        pos(1, 11),
+        SemError::UncheckedThrow,
+    );
+    err(
+        "fun g() throws {} fun f() throws { g(); }",
+        pos(1, 37),
+        SemError::ThrowingCallWithoutTry,
+    );
+    err(
+        "fun g() throws {} fun f() { try g(); }",
+        pos(1, 34),
+        SemError::UncheckedThrow,
+    );
+    err(
+        "fun g() {} fun f() { try g(); }",
+        pos(1, 27),
+        SemError::TryCallNonThrowing,
+    );
+    err(
+        "fun f() throws -> Int32 { try 1 }",
+        pos(1, 27),
+        SemError::TryNeedsCall,
+    );
+}
+
+#[test]
+fn do_catch() {
+    ok("fun g() throws {} fun f() { do { try g(); } catch e: Exception {} }");
+    ok("fun g() throws {} fun f() -> String { do { try g(); } catch e: Exception { return e.message; } \"\" }");
+    ok("class Foo(msg: String): Exception(msg)
+        fun g() throws {} fun f() throws { do { try g(); } catch e: Foo {} }");
+    err(
+        "class Foo(msg: String): Exception(msg)
+        fun g() throws {} fun f() { do { try g(); } catch e: Foo {} }",
+        pos(2, 47),
+        SemError::UncheckedThrow,
+    );
+    err(
+        "fun g() throws {} fun f() throws { do { try g(); } catch e: Int32 {} }",
+        pos(1, 52),
+        SemError::CatchTypeNotException("Int32".into()),
+    );
+    ok("fun g() throws {} fun f() throws { defer { 1; } try g(); }");
+}
+
+#[test]
+fn throws_in_subclass_and_impl() {
+    err(
+        "@open class A { @open fun f() throws {} } class B: A { @override fun f() {} }",
+        pos(1, 66),
+        SemError::OverrideMismatch,
+    );
+    err(
+        "trait Foo { fun f() throws; } class A impl Foo for A { fun f() {} }",
+        pos(1, 56),
+        SemError::ThrowsDifference("f".into()),
+    );
+}
+
+#[test]
+fn test_where_clause() {
+    ok("trait Foo { fun foo() -> Int32; }
+        class A[T](let t: T) { fun f() -> Int32 where T: Foo { self.t.foo() } }");
+    ok("trait Foo { fun foo() -> Int32; }
+        class X impl Foo for X { fun foo() -> Int32 = 1; }
+        class A[T](let t: T) { fun f() -> Int32 where T: Foo { self.t.foo() } }
+        fun g(a: A[X]) -> Int32 { a.f() }");
+    ok("trait Foo { fun foo() -> Int32; }
+        fun f[T](t: T) -> Int32 where T: Foo { t.foo() }");
+    ok("class A[T](let t: T) { fun f() -> T = self.t; }
+        fun g(a: A[Object]) -> Object { a.f() }");
+
+    err(
+        "trait Foo { fun foo() -> Int32; }
+        class A[T](let t: T) { fun f() -> Int32 { self.t.foo() } }",
+        pos(2, 61),
+        SemError::UnknownMethodForTypeParam("T".into(), "foo".into(), Vec::new()),
+    );
+    err(
+        "trait Foo {}
+        class A[T] { fun f() where T: Foo {} }
+        fun g(a: A[Int32]) { a.f(); }",
+        pos(3, 33),
+        SemError::TraitBoundNotSatisfied("Int32".into(), "Foo".into()),
+    );
+    err(
+        "trait Foo {}
+        class A[T] { fun f() where T: Foo {} }
+        class B[S] { fun g(a: A[S]) { a.f(); } }",
+        pos(3, 42),
+        SemError::TraitBoundNotSatisfied("S".into(), "Foo".into()),
+    );
+    ok("trait Foo {}
+        class A[T] { fun f() where T: Foo {} }
+        class B[S] { fun g(a: A[S]) where S: Foo { a.f(); } }");
+    err(
+        "trait Foo {}
+        class A { fun f() where Int32: Foo {} }",
+        pos(2, 33),
+        SemError::WhereBoundTypeParamExpected("Int32".into()),
+    );
+    err(
+        "class A[T] { fun f() where T: Int32 {} }",
+        pos(1, 31),
+        SemError::BoundExpected,
+    );
+    err(
+        "trait Foo {}
+        class A[T] { fun f() where T: Foo, T: Foo {} }",
+        pos(2, 44),
+        SemError::DuplicateTraitBound,
+    );
+    err(
+        "trait Foo {}
+        @open class A[T] { @open fun f() where T: Foo {} }",
+        pos(2, 34),
+        SemError::WhereClauseOnVirtualMethod,
+    );
+    err(
+        "trait Foo {}
+        class X
+        impl Foo for X where Int32: Foo {}",
+        pos(3, 30),
+        SemError::WhereBoundTypeParamExpected("Int32".into()),
+    );
+}
+
+#[test]
+fn test_infer_fct_type_params() {
+    ok("fun f[T](t: T) -> T = t; fun g() -> Int32 { f(1) }");
+    ok("fun f[T](t: Array[T]) -> T = t(0L); fun g(a: Array[String]) -> String { f(a) }");
+    ok("fun f[A, B](a: A, b: B) -> (A, B) = (a, b);
+        fun g() -> (Int32, String) { f(1, \"a\") }");
+    ok("fun f[T]() -> Array[T] = Array::empty[T]();
+        fun g() { let x: Array[Int32] = f(); }");
+    ok("module M { fun f[T](t: T) -> T = t; }
+        fun g() -> Int64 { M::f(1L) }");
+    ok("class A { fun f[T](t: T) -> T = t; }
+        fun g(a: A) -> Bool { a.f(true) }");
+    ok("fun f[T](t: T) -> T = t; fun g[S](s: S) -> S { f(s) }");
+
+    err(
+        "fun f[T]() -> Array[T] = Array::empty[T]();
+        fun g() { let x = f(); }",
+        pos(2, 28),
+        SemError::CannotInferTypeParam("T".into(), "f".into()),
+    );
+    err(
+        "fun f[T](a: T, b: T) {} fun g() { f(1, \"a\"); }",
+        pos(1, 36),
+        SemError::CannotInferTypeParam("T".into(), "f".into()),
+    );
+    err(
+        "trait Foo {} fun f[T: Foo](t: T) {} fun g() { f(1); }",
+        pos(1, 48),
+        SemError::TraitBoundNotSatisfied("Int32".into(), "Foo".into()),
+    );
+}
+
+#[test]
+fn test_infer_fct_type_params_expected_type() {
+    // only the expected type gives the type param
+    ok("fun f[T]() -> Option[T] = None[T](); fun g() -> Option[Int32] { f() }");
+    ok("fun f[T]() -> T = defaultValue[T](); fun g() { var x: String = f(); x = \"a\"; }");
+    ok("fun f[A, B](a: A) -> (A, B) = (a, defaultValue[B]());
+        fun g() { let x: (Int32, String) = f(1); }");
+
+    // arguments take precedence, a conflicting expected type is a type mismatch
+    err(
+        "fun id[T](x: T) -> T = x; fun g() { let x: Int64 = id(1); }",
+        pos(1, 37),
+        SemError::AssignType("x".into(), "Int64".into(), "Int32".into()),
+    );
+    err(
+        "fun id[T](x: T) -> T = x; fun g() -> Int64 { id(1) }",
+        pos(1, 44),
+        SemError::ReturnType("Int64".into(), "Int32".into()),
+    );
+    err(
+        "fun f[T](x: T) -> (T, T) = (x, x); fun g() { let x: (String, String) = f(1); }",
+        pos(1, 46),
+        SemError::AssignType(
+            "x".into(),
+            "(String, String)".into(),
+            "(Int32, Int32)".into(),
+        ),
+    );
+}
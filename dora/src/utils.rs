@@ -29,6 +29,11 @@ impl<T> GrowableVec<T> {
         elements.len()
     }
 
+    pub fn truncate(&self, len: usize) {
+        let mut elements = self.elements.lock();
+        elements.truncate(len);
+    }
+
     pub fn iter(&self) -> GrowableVecIter<T> {
         GrowableVecIter { vec: self, idx: 0 }
     }
@@ -63,6 +68,25 @@ impl<'a, T> Iterator for GrowableVecIter<'a, T> {
     }
 }
 
+// escapes a string for use inside a JSON string literal
+pub fn json_escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => result.push(ch),
+        }
+    }
+
+    result
+}
+
 #[test]
 fn test_push() {
     let vec: GrowableVec<Mutex<i32>> = GrowableVec::new();
@@ -1,8 +1,11 @@
 use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
 use std::mem;
 use std::ptr;
 use std::sync::Arc;
 
+use crate::bytecode::cache::BytecodeCache;
+use crate::bytecode::BytecodeFunction;
 use crate::compiler;
 use crate::compiler::compile_stub;
 use crate::compiler::dora_stub;
@@ -13,8 +16,9 @@ use crate::driver::cmd::Args;
 use crate::error::diag::Diagnostic;
 use crate::gc::{Address, Gc};
 use crate::object::{Ref, Testing};
+use crate::profiler::{self, Profiler};
 use crate::safepoint;
-use crate::stack::DoraToNativeInfo;
+use crate::stack::{self, DoraToNativeInfo};
 use crate::stdlib;
 use crate::sym::TermSym::SymFct;
 use crate::sym::{SymTable, TermSym};
@@ -34,16 +38,19 @@ pub use self::class::{
 };
 pub use self::cnst::{ConstData, ConstId, ConstValue};
 pub use self::enums::{
-    find_methods_in_enum, EnumData, EnumDef, EnumDefId, EnumId, EnumLayout, EnumVariant,
+    enum_payload_field, find_methods_in_enum, EnumData, EnumDef, EnumDefId, EnumDefVariant, EnumId,
+    EnumLayout, EnumVariant, ENUM_TAG_FIELD,
 };
 pub use self::extension::{ExtensionData, ExtensionId};
 pub use self::fct::{Fct, FctDef, FctDefId, FctId, FctKind, FctParent, Intrinsic};
 pub use self::field::{Field, FieldDef, FieldId};
 pub use self::global::{GlobalData, GlobalId};
 pub use self::impls::{ImplData, ImplId};
-pub use self::src::{CallType, ConvInfo, FctSrc, ForTypeInfo, IdentType, NodeMap, Var, VarId};
+pub use self::src::{
+    CallType, ConvInfo, FctSrc, ForTypeInfo, IdentType, LambdaInfo, NodeMap, Var, VarId,
+};
 pub use self::strct::{
-    StructData, StructDef, StructDefId, StructFieldData, StructFieldDef, StructId,
+    StructData, StructDef, StructDefId, StructFieldData, StructFieldDef, StructFieldId, StructId,
 };
 pub use self::traits::{TraitData, TraitId};
 pub use self::tuple::{ensure_tuple, TupleId, Tuples};
@@ -115,11 +122,17 @@ pub struct VM<'ast> {
     pub native_stubs: Mutex<NativeStubs>,
     pub lists: Mutex<TypeLists>,
     pub lambda_types: Mutex<LambdaTypes>,
+    pub lambda_defs: Mutex<HashMap<FctDefId, ClassDefId>>, // context objects of lambdas
+    pub box_defs: Mutex<HashMap<BuiltinType, ClassDefId>>, // boxes for captured variables
     pub compile_stub: Mutex<Address>,
     pub dora_stub: Mutex<Address>,
     pub trap_stub: Mutex<Address>,
+    pub throw_stub: Mutex<Address>,
     pub guard_check_stub: Mutex<Address>,
     pub threads: Threads,
+    pub profiler: Option<Profiler>, // CPU profiler for --profile
+    pub bytecode_cache: Option<BytecodeCache>, // bytecode loaded from a `.dorabc` file
+    pub stdlib_snapshot: Option<BytecodeCache>, // bytecode of the checked stdlib
 }
 
 impl<'ast> VM<'ast> {
@@ -130,6 +143,7 @@ impl<'ast> VM<'ast> {
         let empty_trait_id: TraitId = 0.into();
         let empty_fct_id: FctId = 0.into();
         let gc = Gc::new(&args);
+        let profiler = args.flag_profile.as_ref().map(|path| Profiler::new(path));
 
         let vm = Box::new(VM {
             args,
@@ -183,6 +197,9 @@ impl<'ast> VM<'ast> {
                 testing_class: empty_class_id,
                 stacktrace_class: empty_class_id,
                 stacktrace_element_class: empty_class_id,
+                exception_class: empty_class_id,
+                weak_ref_class: empty_class_id,
+                ephemeron_class: empty_class_id,
 
                 equals_trait: empty_trait_id,
                 comparable_trait: empty_trait_id,
@@ -211,12 +228,18 @@ impl<'ast> VM<'ast> {
             code_map: Mutex::new(CodeMap::new()),
             lists: Mutex::new(TypeLists::new()),
             lambda_types: Mutex::new(LambdaTypes::new()),
+            lambda_defs: Mutex::new(HashMap::new()),
+            box_defs: Mutex::new(HashMap::new()),
             native_stubs: Mutex::new(NativeStubs::new()),
             compile_stub: Mutex::new(Address::null()),
             dora_stub: Mutex::new(Address::null()),
             trap_stub: Mutex::new(Address::null()),
+            throw_stub: Mutex::new(Address::null()),
             guard_check_stub: Mutex::new(Address::null()),
             threads: Threads::new(),
+            profiler,
+            bytecode_cache: None,
+            stdlib_snapshot: None,
         });
 
         set_vm(&vm);
@@ -229,7 +252,9 @@ impl<'ast> VM<'ast> {
         let stack_limit = stack_top.sub(STACK_SIZE);
 
         THREAD.with(|thread| {
-            thread.borrow().tld.set_stack_limit(stack_limit);
+            let thread = thread.borrow();
+            thread.tld.set_stack_limit(stack_limit);
+            profiler::register_thread(&thread.tld, stack_top);
         });
 
         let tld = THREAD.with(|thread| {
@@ -242,7 +267,11 @@ impl<'ast> VM<'ast> {
         let dora_stub_address = self.dora_stub();
         let fct: extern "C" fn(Address, Address) -> i32 =
             unsafe { mem::transmute(dora_stub_address) };
-        fct(tld, ptr)
+        let result = fct(tld, ptr);
+
+        profiler::unregister_thread();
+
+        result
     }
 
     pub fn run_test(&self, fct_id: FctId, testing: Ref<Testing>) {
@@ -292,14 +321,16 @@ impl<'ast> VM<'ast> {
 
     pub fn add_fct_to_sym(&mut self, fct: Fct<'ast>) -> Result<FctId, TermSym> {
         let name = fct.name;
+        let file = fct.file;
         let fctid = self.add_fct(fct);
 
         let mut sym = self.sym.lock();
+        let level = sym.decl_level_mut(file);
 
-        match sym.get_term(name) {
-            Some(sym) => Err(sym),
+        match level.get_term(name) {
+            Some(sym) => Err(sym.clone()),
             None => {
-                assert!(sym.insert_term(name, SymFct(fctid)).is_none());
+                assert!(level.insert_term(name, SymFct(fctid)).is_none());
 
                 Ok(fctid)
             }
@@ -388,6 +419,38 @@ impl<'ast> VM<'ast> {
         }
     }
 
+    #[cfg(test)]
+    pub fn trait_method_def_by_name(
+        &self,
+        trait_name: &'static str,
+        function_name: &'static str,
+    ) -> Option<FctDefId> {
+        let trait_name = self.interner.intern(trait_name);
+        let function_name = self.interner.intern(function_name);
+
+        let trait_id = self
+            .sym
+            .lock()
+            .get_trait(trait_name)
+            .expect("trait not found");
+        let xtrait = self.traits[trait_id].read();
+
+        for &fct_id in &xtrait.methods {
+            let fct = self.fcts.idx(fct_id);
+            let fct = fct.read();
+
+            if fct.name == function_name {
+                return fct
+                    .specializations
+                    .read()
+                    .get(&(TypeList::Empty, TypeList::Empty))
+                    .and_then(|fct_def_id| Some(*fct_def_id));
+            }
+        }
+
+        None
+    }
+
     #[cfg(test)]
     pub fn module_method_def_by_name_with_type_params(
         &self,
@@ -430,6 +493,25 @@ impl<'ast> VM<'ast> {
         specialize_class_id(self, cls_id)
     }
 
+    pub fn enum_variant_cls_def_by_name(
+        &self,
+        enum_name: &'static str,
+        variant_name: &'static str,
+    ) -> ClassDefId {
+        use crate::semck::specialize::specialize_enum_id_params;
+
+        let enum_name = self.interner.intern(enum_name);
+        let enum_id = self.sym.lock().get_enum(enum_name).expect("enum not found");
+        let enum_def_id = specialize_enum_id_params(self, enum_id, TypeList::empty());
+
+        let variant_name = self.interner.intern(variant_name);
+        let variant_id = self.enums[enum_id].read().name_to_value[&variant_name];
+
+        let enum_def = self.enum_defs.idx(enum_def_id);
+        let enum_def = enum_def.read();
+        enum_def.variant_cls_def(variant_id)
+    }
+
     pub fn cls_def_by_name_with_type_params(
         &self,
         name: &'static str,
@@ -479,6 +561,21 @@ impl<'ast> VM<'ast> {
         (cls_id, field_id)
     }
 
+    // body of the function isn't type checked when its bytecode is cached
+    pub fn has_cached_bytecode(&self, id: FctId) -> bool {
+        self.stdlib_snapshot
+            .iter()
+            .chain(self.bytecode_cache.iter())
+            .any(|cache| cache.contains(id))
+    }
+
+    pub fn cached_bytecode(&self, id: FctId) -> Option<BytecodeFunction> {
+        self.stdlib_snapshot
+            .iter()
+            .chain(self.bytecode_cache.iter())
+            .find_map(|cache| cache.bytecode(id))
+    }
+
     pub fn fct_by_name(&self, name: &str) -> Option<FctId> {
         let name = self.interner.intern(name);
         self.sym.lock().get_fct(name)
@@ -552,6 +649,16 @@ impl<'ast> VM<'ast> {
         fct_def
     }
 
+    #[cfg(test)]
+    pub fn struct_def_by_name(&self, name: &'static str) -> StructDefId {
+        use crate::semck::specialize::specialize_struct_id;
+
+        let name = self.interner.intern(name);
+        let struct_id = self.sym.lock().get_struct(name).expect("struct not found");
+
+        specialize_struct_id(self, struct_id)
+    }
+
     #[cfg(test)]
     pub fn global_by_name(&self, name: &str) -> GlobalId {
         let name = self.interner.intern(name);
@@ -621,6 +728,25 @@ impl<'ast> VM<'ast> {
         *trap_stub_address
     }
 
+    pub fn throw_stub(&self) -> Address {
+        let mut throw_stub_address = self.throw_stub.lock();
+
+        if throw_stub_address.is_null() {
+            let ifct = NativeFct {
+                ptr: Address::from_ptr(stack::throw_exception as *const u8),
+                args: &[BuiltinType::Ptr],
+                return_type: BuiltinType::Unit,
+                desc: NativeFctDescriptor::ThrowStub,
+            };
+            let jit_fct_id = native_stub::generate(self, ifct, false);
+            let jit_fct = self.jit_fcts.idx(jit_fct_id);
+            let fct_ptr = jit_fct.instruction_start();
+            *throw_stub_address = fct_ptr;
+        }
+
+        *throw_stub_address
+    }
+
     pub fn guard_check_stub(&self) -> Address {
         let mut guard_check_stub_address = self.guard_check_stub.lock();
 
@@ -643,6 +769,16 @@ impl<'ast> VM<'ast> {
     pub fn file(&self, idx: FileId) -> &File {
         &self.files[idx.0 as usize]
     }
+
+    // semck only checks the files of `ast`, which are the last files of the
+    // VM: the REPL checks each input on its own
+    pub fn first_checked_file(&self) -> u32 {
+        (self.files.len() - self.ast.files.len()) as u32
+    }
+
+    pub fn is_checked_file(&self, file: FileId) -> bool {
+        file.0 >= self.first_checked_file()
+    }
 }
 
 unsafe impl<'ast> Sync for VM<'ast> {}
@@ -650,6 +786,12 @@ unsafe impl<'ast> Sync for VM<'ast> {}
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct FileId(u32);
 
+impl FileId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 impl From<u32> for FileId {
     fn from(data: u32) -> FileId {
         FileId(data)
@@ -11,7 +11,7 @@ use crate::sym::SymLevel;
 use crate::ty::{BuiltinType, TypeList};
 use crate::utils::GrowableVec;
 use crate::vm::VM;
-use crate::vm::{ExtensionId, FctId, Field, FieldDef, FieldId, FileId, ImplId, TraitId};
+use crate::vm::{ExtensionId, FctDefId, FctId, Field, FieldDef, FieldId, FileId, ImplId, TraitId};
 use crate::vtable::VTableBox;
 use dora_parser::interner::Name;
 use dora_parser::lexer::position::Position;
@@ -76,6 +76,10 @@ pub struct Class {
     // true if this class is the generic Array class
     pub is_array: bool,
     pub is_str: bool,
+
+    // true for WeakRef and Ephemeron, reference fields are not traced
+    // if the GC supports weak references
+    pub is_weak: bool,
 }
 
 impl Class {
@@ -199,6 +203,28 @@ impl Class {
     pub fn implements_trait(&self, vm: &VM, trait_id: TraitId) -> bool {
         self.traits.contains(&trait_id) || vm.vips.zero_trait == trait_id && !self.ty.is_cls()
     }
+
+    pub fn implements_trait_in_hierarchy(&self, vm: &VM, trait_id: TraitId) -> bool {
+        if self.traits.contains(&trait_id) {
+            return true;
+        }
+
+        let mut parent_class = self.parent_class;
+
+        while let Some(parent) = parent_class {
+            let cls_id = parent.cls_id(vm).expect("no class");
+            let cls = vm.classes.idx(cls_id);
+            let cls = cls.read();
+
+            if cls.traits.contains(&trait_id) {
+                return true;
+            }
+
+            parent_class = cls.parent_class;
+        }
+
+        false
+    }
 }
 
 pub fn find_field_in_class(
@@ -393,6 +419,7 @@ impl GrowableVec<RwLock<ClassDef>> {
 pub struct ClassDef {
     pub id: ClassDefId,
     pub cls_id: Option<ClassId>,
+    pub lambda: Option<FctDefId>,
     pub type_params: TypeList,
     pub parent_id: Option<ClassDefId>,
     pub fields: Vec<FieldDef>,
@@ -419,6 +446,13 @@ impl ClassDef {
             };
 
             format!("{}<{}>", name, params)
+        } else if let Some(fct_def_id) = self.lambda {
+            let fct_def = vm.fct_defs.idx(fct_def_id);
+            let fct_def = fct_def.read();
+            let fct = vm.fcts.idx(fct_def.fct_id);
+            let fct = fct.read();
+
+            fct.full_name(vm)
         } else {
             "<Unknown>".into()
         }
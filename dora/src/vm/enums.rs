@@ -10,11 +10,17 @@ use dora_parser::lexer::position::Position;
 
 use crate::ty::{BuiltinType, TypeList};
 use crate::utils::GrowableVec;
-use crate::vm::{ExtensionId, FctId, FileId, TypeParam, VM};
+use crate::vm::{ClassDefId, ExtensionId, FctId, FieldId, FileId, TypeParam, VM};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EnumId(u32);
 
+impl EnumId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 impl From<usize> for EnumId {
     fn from(data: usize) -> EnumId {
         EnumId(data.try_into().unwrap())
@@ -40,6 +46,19 @@ pub struct EnumData {
     pub name_to_value: HashMap<Name, u32>,
     pub extensions: Vec<ExtensionId>,
     pub specializations: RwLock<HashMap<TypeList, EnumDefId>>,
+    pub simple_enumeration: bool,
+}
+
+impl EnumData {
+    pub fn layout(&self) -> EnumLayout {
+        if self.simple_enumeration {
+            // no variant carries a payload: values are just the variant index
+            EnumLayout::Int
+        } else {
+            // every value is a heap object holding the variant index and its payload
+            EnumLayout::Tagged
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -51,6 +70,12 @@ pub struct EnumVariant {
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EnumDefId(u32);
 
+impl EnumDefId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 impl From<usize> for EnumDefId {
     fn from(data: usize) -> EnumDefId {
         EnumDefId(data as u32)
@@ -71,18 +96,35 @@ pub struct EnumDef {
     pub size: i32,
     pub align: i32,
     pub layout: EnumLayout,
+    pub variants: Vec<EnumDefVariant>,
 }
 
-#[derive(Debug)]
+impl EnumDef {
+    pub fn variant_cls_def(&self, variant_id: u32) -> ClassDefId {
+        self.variants[variant_id as usize]
+            .cls_def_id
+            .expect("enum variant without class")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum EnumLayout {
     Int,
-    Ptr,
     Tagged,
 }
 
 #[derive(Debug)]
 pub struct EnumDefVariant {
     pub types: Vec<BuiltinType>,
+    pub cls_def_id: Option<ClassDefId>,
+}
+
+// Objects for variants of tagged enums store the variant index in the first field,
+// the payload follows in the fields after it.
+pub const ENUM_TAG_FIELD: usize = 0;
+
+pub fn enum_payload_field(idx: usize) -> FieldId {
+    (ENUM_TAG_FIELD + 1 + idx).into()
 }
 
 pub fn find_methods_in_enum(
@@ -20,7 +20,7 @@ impl From<usize> for ExtensionId {
 }
 
 impl ExtensionId {
-    fn to_usize(self) -> usize {
+    pub fn to_usize(self) -> usize {
         self.0 as usize
     }
 }
@@ -1,4 +1,4 @@
-use crate::ty::TypeList;
+use crate::ty::{TypeList, TypeListId};
 use parking_lot::RwLock;
 use std::collections::HashMap;
 
@@ -58,6 +58,7 @@ pub struct Fct<'ast> {
     pub param_types: Vec<BuiltinType>,
     pub return_type: BuiltinType,
     pub is_constructor: bool,
+    pub throws: bool,
     pub file: FileId,
     pub variadic_arguments: bool,
 
@@ -66,6 +67,10 @@ pub struct Fct<'ast> {
     pub initialized: bool,
 
     pub type_params: Vec<TypeParam>,
+
+    // bounds on type params of the class added by a `where` clause
+    pub where_bounds: Vec<(TypeListId, TraitId)>,
+
     pub kind: FctKind,
 
     pub specializations: RwLock<HashMap<(TypeList, TypeList), FctDefId>>,
@@ -90,6 +95,13 @@ impl<'ast> Fct<'ast> {
         }
     }
 
+    pub fn is_lambda(&self) -> bool {
+        match self.parent {
+            FctParent::Function(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn cls_id(&self) -> ClassId {
         match self.parent {
             FctParent::Class(clsid) => clsid,
@@ -130,6 +142,13 @@ impl<'ast> Fct<'ast> {
                 }
             }
 
+            FctParent::Function(fct_id) => {
+                let fct = vm.fcts.idx(fct_id);
+                let fct = fct.read();
+                repr.push_str(&vm.interner.str(fct.name));
+                repr.push_str(".");
+            }
+
             _ => {}
         }
 
@@ -197,6 +216,9 @@ impl<'ast> Fct<'ast> {
             | FctParent::Impl(_)
             | FctParent::Extension(_) => !self.is_static,
 
+            // lambdas receive their context object as first argument
+            FctParent::Function(_) => true,
+
             _ => false,
         }
     }
@@ -212,6 +234,38 @@ impl<'ast> Fct<'ast> {
             &self.param_types
         }
     }
+
+    // definition of a type param as seen from within this function, type
+    // params of the class also have the bounds of the `where` clause
+    pub fn type_param_def(&self, vm: &VM, ty: BuiltinType) -> TypeParam {
+        match ty {
+            BuiltinType::FctTypeParam(fct_id, tp_id) => {
+                if fct_id == self.id {
+                    self.type_params[tp_id.idx()].clone()
+                } else {
+                    let fct = vm.fcts.idx(fct_id);
+                    let fct = fct.read();
+                    fct.type_params[tp_id.idx()].clone()
+                }
+            }
+
+            BuiltinType::ClassTypeParam(cls_id, tp_id) => {
+                let cls = vm.classes.idx(cls_id);
+                let cls = cls.read();
+                let mut type_param = cls.type_params[tp_id.idx()].clone();
+
+                for &(bound_tp_id, trait_id) in &self.where_bounds {
+                    if bound_tp_id == tp_id {
+                        type_param.trait_bounds.insert(trait_id);
+                    }
+                }
+
+                type_param
+            }
+
+            _ => panic!("type param expected"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -221,6 +275,7 @@ pub enum FctParent {
     Module(ModuleId),
     Impl(ImplId),
     Extension(ExtensionId),
+    Function(FctId),
     None,
 }
 
@@ -542,7 +597,7 @@ impl Intrinsic {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct FctDefId(pub usize);
 
 impl FctDefId {
@@ -16,6 +16,12 @@ impl From<u32> for ImplId {
     }
 }
 
+impl ImplId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 #[derive(Debug)]
 pub struct ImplData {
     pub id: ImplId,
@@ -5,12 +5,14 @@ use std::sync::Arc;
 
 use dora_parser::ast;
 use dora_parser::interner::Name;
+use dora_parser::lexer::position::Position;
 
 use crate::compiler::fct::JitFctId;
 use crate::ty::{BuiltinType, TypeList};
 use crate::vm::module::ModuleId;
 use crate::vm::{
-    ClassId, ConstId, EnumId, FctId, FieldId, GlobalId, Intrinsic, StructId, TraitId, TypeParamId,
+    ClassId, ConstId, EnumId, FctId, FieldId, GlobalId, Intrinsic, StructFieldId, StructId,
+    TraitId, TypeParamId,
 };
 
 #[derive(Debug)]
@@ -22,6 +24,7 @@ pub struct FctSrc {
     pub map_convs: NodeMap<ConvInfo>,
     pub map_cls: NodeMap<ClassId>,
     pub map_fors: NodeMap<ForTypeInfo>,
+    pub map_lambdas: NodeMap<LambdaInfo>,
 
     pub always_returns: bool, // true if function is always exited via return statement
     // false if function execution could reach the closing } of this function
@@ -39,6 +42,7 @@ impl Clone for FctSrc {
             map_convs: self.map_convs.clone(),
             map_cls: self.map_cls.clone(),
             map_fors: self.map_fors.clone(),
+            map_lambdas: self.map_lambdas.clone(),
 
             vars: self.vars.clone(),
             always_returns: self.always_returns,
@@ -57,6 +61,7 @@ impl FctSrc {
             map_convs: NodeMap::new(),
             map_cls: NodeMap::new(),
             map_fors: NodeMap::new(),
+            map_lambdas: NodeMap::new(),
 
             vars: Vec::new(),
             always_returns: false,
@@ -149,6 +154,9 @@ pub enum IdentType {
     /// name of structure
     Struct(StructId),
 
+    /// struct field expression: <expr>.<field_name>
+    StructField(BuiltinType, StructFieldId),
+
     // name of constant
     Const(ConstId),
 
@@ -252,6 +260,14 @@ pub struct ForTypeInfo {
     pub iterator_type: BuiltinType,
 }
 
+#[derive(Debug, Clone)]
+pub struct LambdaInfo {
+    pub fct_id: FctId,
+    // variables of enclosing functions used in the lambda, these
+    // are stored in the context object in this order
+    pub captures: Vec<VarId>,
+}
+
 #[derive(Debug, Clone)]
 pub enum CallType {
     // Function calls, e.g. fct(<args>) or Class::static_fct(<args>)
@@ -271,6 +287,9 @@ pub enum CallType {
     // Invoke on expression, e.g. <expr>(<args>)
     Expr(BuiltinType, FctId),
 
+    // Invoke lambda, e.g. <lambda>(<args>)
+    Lambda(BuiltinType),
+
     // Invoke method on trait object
     Trait(TraitId, FctId),
 
@@ -327,7 +346,7 @@ impl CallType {
             CallType::Expr(_, fctid) => Some(fctid),
             CallType::Trait(_, fctid) => Some(fctid),
             CallType::TraitStatic(_, _, fctid) => Some(fctid),
-            CallType::Intrinsic(_) => None,
+            CallType::Lambda(_) | CallType::Intrinsic(_) => None,
         }
     }
 }
@@ -341,7 +360,12 @@ pub struct Var {
     pub name: Name,
     pub ty: BuiltinType,
     pub reassignable: bool,
+    pub captured: bool,
     pub node_id: ast::NodeId,
+    pub pos: Position,
+
+    // variable of an enclosing scope with the same name
+    pub shadows: Option<VarId>,
 }
 
 impl Index<VarId> for Vec<Var> {
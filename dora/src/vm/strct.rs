@@ -12,6 +12,12 @@ use crate::vm::{FileId, TypeList};
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct StructId(u32);
 
+impl StructId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 impl GrowableVec<Mutex<StructData>> {
     pub fn idx(&self, index: StructId) -> Arc<Mutex<StructData>> {
         self.idx_usize(index.0 as usize)
@@ -34,6 +40,15 @@ pub struct StructData {
     pub specializations: RwLock<HashMap<TypeList, StructDefId>>,
 }
 
+impl StructData {
+    pub fn field_by_name(&self, name: Name) -> Option<StructFieldId> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.id)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct StructFieldId(u32);
 
@@ -43,6 +58,12 @@ impl From<u32> for StructFieldId {
     }
 }
 
+impl StructFieldId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 #[derive(Debug)]
 pub struct StructFieldData {
     pub id: StructFieldId,
@@ -60,6 +81,12 @@ impl From<usize> for StructDefId {
     }
 }
 
+impl StructDefId {
+    pub fn to_usize(self) -> usize {
+        self.0
+    }
+}
+
 impl GrowableVec<Mutex<StructDef>> {
     pub fn idx(&self, index: StructDefId) -> Arc<Mutex<StructDef>> {
         self.idx_usize(index.0)
@@ -67,6 +94,8 @@ impl GrowableVec<Mutex<StructDef>> {
 }
 
 pub struct StructDef {
+    pub struct_id: StructId,
+    pub type_params: TypeList,
     pub fields: Vec<StructFieldDef>,
     pub size: i32,
     pub align: i32,
@@ -102,6 +102,15 @@ impl Tuples {
     pub fn get(&self, id: TupleId) -> Arc<Vec<BuiltinType>> {
         self.all[id.0 as usize].args.clone()
     }
+
+    pub fn len(&self) -> usize {
+        self.all.len()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.all.truncate(len);
+        self.map.retain(|_, id| (id.0 as usize) < len);
+    }
 }
 
 pub fn ensure_tuple(vm: &VM, args: Vec<BuiltinType>) -> TupleId {
@@ -147,8 +156,8 @@ fn determine_tuple_size<'ast>(vm: &VM, subtypes: &[BuiltinType]) -> Option<Concr
         let element_offset = mem::align_i32(size, element_align);
         offsets.push(element_offset);
 
-        if ty.reference_type() {
-            references.push(element_offset);
+        for ref_offset in ty.reference_offsets(vm) {
+            references.push(element_offset + ref_offset);
         }
 
         size = element_offset + element_size;
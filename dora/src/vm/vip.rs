@@ -28,6 +28,9 @@ pub struct KnownElements {
     pub testing_class: ClassId,
     pub stacktrace_class: ClassId,
     pub stacktrace_element_class: ClassId,
+    pub exception_class: ClassId,
+    pub weak_ref_class: ClassId,
+    pub ephemeron_class: ClassId,
 
     pub equals_trait: TraitId,
     pub comparable_trait: TraitId,
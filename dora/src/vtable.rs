@@ -26,6 +26,10 @@ impl VTableBox {
             subtype_depth: 0,
             subtype_display: [ptr::null(); DISPLAY_SIZE],
             subtype_overflow: ptr::null(),
+            itables: ptr::null(),
+            itables_size: 0,
+            itables_traits: 0,
+            retired_itables: Vec::new(),
             table_length: entries.len(),
             table: [0],
         };
@@ -86,6 +90,12 @@ pub struct VTable {
     pub subtype_depth: usize,
     pub subtype_display: [*const VTable; DISPLAY_SIZE],
     pub subtype_overflow: *const *const VTable,
+    pub itables: *const *mut usize,
+    pub itables_size: usize,
+    pub itables_traits: usize,
+    // itables replaced by `reallocate_itables`, code running in other
+    // threads might still read them
+    retired_itables: Vec<(*const *mut usize, usize)>,
     pub table_length: usize,
     pub table: [usize; 1],
 }
@@ -139,6 +149,10 @@ impl VTable {
         offset_of!(VTable, subtype_overflow) as i32
     }
 
+    pub fn offset_of_itables() -> i32 {
+        offset_of!(VTable, itables) as i32
+    }
+
     pub fn get_subtype_overflow(&self, ind: usize) -> *const VTable {
         assert!(
             self.subtype_depth as usize >= DISPLAY_SIZE
@@ -178,6 +192,109 @@ impl VTable {
         }
     }
 
+    // itables are indexed by trait id, each implemented trait points to
+    // a table with one entry per trait method. Traits without methods or
+    // not implemented by this class have a null itable.
+    pub fn allocate_itables(&mut self, method_counts: &[usize], entry: usize) {
+        assert!(self.itables.is_null());
+
+        let traits = method_counts.len();
+        let size = traits + method_counts.iter().sum::<usize>();
+
+        if size == 0 {
+            return;
+        }
+
+        let lay = Layout::array::<usize>(size).unwrap();
+
+        unsafe {
+            let data = alloc(lay).cast::<usize>();
+            let mut itable = data.add(traits);
+
+            for (idx, &methods) in method_counts.iter().enumerate() {
+                if methods == 0 {
+                    *data.add(idx) = 0;
+                    continue;
+                }
+
+                *data.add(idx) = itable as usize;
+
+                for method_idx in 0..methods {
+                    *itable.add(method_idx) = entry;
+                }
+
+                itable = itable.add(methods);
+            }
+
+            self.itables = data as *const *mut usize;
+            self.itables_size = size;
+            self.itables_traits = traits;
+        }
+    }
+
+    // whether the itables have a table for the trait
+    pub fn has_itable(&self, trait_idx: usize) -> bool {
+        trait_idx < self.itables_traits && unsafe { !(*self.itables.add(trait_idx)).is_null() }
+    }
+
+    // Replaces the itables with tables for the given method counts, used when
+    // traits are implemented after the vtable was created. Entries of traits
+    // already in the itables are kept.
+    pub fn reallocate_itables(&mut self, method_counts: &[usize], entry: usize) {
+        let old_itables = self.itables;
+        let old_traits = self.itables_traits;
+
+        if !old_itables.is_null() {
+            self.retired_itables.push((old_itables, self.itables_size));
+        }
+
+        self.itables = ptr::null();
+        self.itables_size = 0;
+        self.itables_traits = 0;
+        self.allocate_itables(method_counts, entry);
+
+        for (trait_idx, &methods) in method_counts.iter().enumerate().take(old_traits) {
+            unsafe {
+                let old_itable = *old_itables.add(trait_idx);
+
+                if old_itable.is_null() {
+                    continue;
+                }
+
+                let itable = *self.itables.add(trait_idx);
+                ptr::copy_nonoverlapping(old_itable, itable, methods);
+            }
+        }
+    }
+
+    pub fn deallocate_itables(&mut self) {
+        assert!(!self.itables.is_null());
+        let lay = Layout::array::<usize>(self.itables_size).unwrap();
+
+        unsafe {
+            dealloc(self.itables as *mut _, lay);
+        }
+
+        for &(itables, size) in &self.retired_itables {
+            let lay = Layout::array::<usize>(size).unwrap();
+
+            unsafe {
+                dealloc(itables as *mut _, lay);
+            }
+        }
+    }
+
+    pub fn set_itable_entry(&self, trait_idx: usize, method_idx: usize, entry: usize) {
+        assert!(!self.itables.is_null());
+
+        unsafe {
+            let itable = *self.itables.add(trait_idx);
+            assert!(!itable.is_null());
+
+            *itable.add(method_idx) = entry;
+        }
+    }
+
     pub fn is_array_ref(&self) -> bool {
         let cls = self.class();
 
@@ -194,5 +311,9 @@ impl Drop for VTable {
             let elems = self.subtype_depth as usize - DISPLAY_SIZE + 1;
             self.deallocate_overflow(elems);
         }
+
+        if !self.itables.is_null() {
+            self.deallocate_itables();
+        }
     }
 }
@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// runs the REPL with the given lines as input
+fn repl(lines: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dora"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("starting repl failed");
+
+    {
+        let stdin = child.stdin.as_mut().expect("missing stdin");
+
+        for line in lines {
+            writeln!(stdin, "{}", line).expect("writing input failed");
+        }
+    }
+
+    child.wait_with_output().expect("running repl failed")
+}
+
+// lines printed by the inputs without the prompts
+fn results(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .split(">>> ")
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[test]
+fn trait_implemented_after_class_was_instantiated() {
+    let output = repl(&[
+        "class Foo(let x: Int32)",
+        "let f = Foo(1);",
+        "f.x",
+        "trait Greet { fun greet() -> Int32; }",
+        "impl Greet for Foo { fun greet() -> Int32 { self.x + 41 } }",
+        "let g: Greet = f;",
+        "g.greet()",
+        "trait Twice { fun twice() -> Int32; }",
+        "impl Twice for Foo { fun twice() -> Int32 { 2 * self.x } }",
+        "let t: Twice = Foo(4);",
+        "t.twice() + g.greet()",
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(results(&output), vec!["1", "42", "50"]);
+}
+
+#[test]
+fn failed_input_is_rolled_back() {
+    let output = repl(&[
+        "fun foo() -> Int32 { 1 }",
+        "let x = foo();",
+        "fun bar() -> Int32 { true }",
+        "let x = undefined();",
+        "fun bar() -> Int32 { x + 1 }",
+        "bar() + x",
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(results(&output), vec!["3"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("error: ").count(), 2, "{}", stderr);
+}
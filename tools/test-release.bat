@@ -1 +1 @@
-cargo build --release && cargo test --release && ruby tools\tester.rb --release %* && cargo run --release -- test dora-boots
+cargo build --release && cargo run --release -- stdlib-snapshot && cargo test --release && ruby tools\tester.rb --release %* && cargo run --release -- test dora-boots
@@ -1 +1 @@
-cargo build && cargo test && ruby tools\tester.rb %* && cargo run -- test dora-boots
+cargo build && cargo run -- stdlib-snapshot && cargo test && ruby tools\tester.rb %* && cargo run -- test dora-boots
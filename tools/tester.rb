@@ -72,12 +72,15 @@ class TestExpectation
                 :code,
                 :message,
                 :stdout,
-                :stderr
+                :stdout_contains,
+                :stderr,
+                :heap_analyze
 
   def initialize(opts = {})
     fail = opts.fetch(:fail, false)
 
     self.fail = fail
+    self.stdout_contains = []
   end
 end
 
@@ -145,6 +148,7 @@ class TestCase
     cmdline = "#{binary} #{vm_args} #{optional_vm_args} #{test_file} #{args}"
     process_result = TestUtility.spawn_with_timeout(cmdline, self.timeout)
     result = check_test_run_result(process_result)
+    result = check_heap_analyze() if result == true && self.expectation.heap_analyze
     if $no_capture || result != true
       mutex.synchronize do
         puts "#==== STDOUT"
@@ -158,6 +162,22 @@ class TestCase
     result
   end
 
+  # runs the heap analyzer on the heap dump written by the test
+  def check_heap_analyze
+    path, text = self.expectation.heap_analyze
+    cmdline = "#{binary} heap-analyze #{path}"
+    process_result = TestUtility.spawn_with_timeout(cmdline, self.timeout)
+    File.delete(path) if File.exist?(path)
+    stdout = process_result[:stdout]
+
+    return "heap-analyze failed (#{process_result[:status]}): #{stdout.inspect}" unless
+      process_result[:status].success?
+    return "heap-analyze output does not contain #{text.inspect} (got #{stdout.inspect})" unless
+      stdout.include?(text)
+
+    true
+  end
+
   def binary
     return $binary if $binary
     dir = $release ? "release" : "debug"
@@ -194,6 +214,11 @@ class TestCase
     return "stdout does not match (expected #{self.expectation.stdout.inspect} but got #{stdout.inspect})" if
       self.expectation.stdout && self.expectation.stdout != stdout
 
+    for text in self.expectation.stdout_contains
+      return "stdout does not contain #{text.inspect} (got #{stdout.inspect})" unless
+        stdout.include?(text)
+    end
+
     return "stderr does not match (expected #{self.expectation.stderr.inspect} but got #{stderr.inspect})" if
       self.expectation.stderr && self.expectation.stderr != stderr
 
@@ -329,9 +354,12 @@ def test_name(num)
   num == 1 ? "test" : "tests"
 end
 
+# only the `-->` line directly after the error message gives its position,
+# warnings printed before the error have `-->` lines too
 def read_error_message(content)
   position = nil
   message = nil
+  after_error = false
 
   content.each_line do |line|
     line = line.strip
@@ -339,14 +367,21 @@ def read_error_message(content)
     if line == "1 error found." || line == "error during parsing."
       return position, message
 
-    elsif (m = line.match(/^error in (.+) at (\d+:\d+): (.+)$/)) != nil
+    elsif (m = line.match(/^error(\[E\d+\])?: (.+)$/)) != nil
+      message = m[2].to_s
+      position = nil
+      after_error = true
+      next
+
+    elsif after_error && (m = line.match(/^--> (.+):(\d+:\d+)$/)) != nil
       position = m[2].to_s
-      message = m[3].to_s
 
     elsif (m = line.match(/^error at (\d+:\d+): (.+)$/)) != nil
       position = m[1].to_s
       message = m[2].to_s
     end
+
+    after_error = false
   end
 
   return nil, nil
@@ -438,6 +473,7 @@ def parse_test_file(file)
         when "cast" then test_case.expectation.code = 105
         when "oom" then test_case.expectation.code = 106
         when "stack-overflow" then test_case.expectation.code = 107
+        when "exception" then test_case.expectation.code = 108
         when "fail"
           # do nothing
         else
@@ -458,9 +494,15 @@ def parse_test_file(file)
           test_case.expectation.stdout = arguments[1]
         end
 
+      when "stdout-contains"
+        test_case.expectation.stdout_contains.push(arguments[1])
+
       when "stderr"
         test_case.expectation.stderr = arguments[1]
 
+      when "heap-analyze"
+        test_case.expectation.heap_analyze = arguments[1..2]
+
       when "args"
         test_case.args = arguments[1..-1].join(" ")
 